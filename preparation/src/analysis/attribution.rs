@@ -0,0 +1,72 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_with::{serde_as, TimestampSecondsWithFrac};
+
+use crate::configuration::DatasetConfiguration;
+
+
+/// Provenance of a file derived from snapshots: where the data comes from, under what license
+/// it is published, which recorder version captured it and when.
+#[serde_as]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DatasetAttribution {
+    /// Example: `Ljubljanski potniški promet (LPP)`.
+    pub source: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<String>,
+
+    /// Version of the recorder that derived the file.
+    pub recorder_version: String,
+
+    /// When the earliest of the source data was captured.
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub captured_from: DateTime<Utc>,
+
+    /// When the latest of the source data was captured.
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub captured_to: DateTime<Utc>,
+}
+
+impl DatasetAttribution {
+    pub fn new(
+        dataset_configuration: &DatasetConfiguration,
+        captured_from: DateTime<Utc>,
+        captured_to: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            source: dataset_configuration.source.clone(),
+            license: dataset_configuration.license.clone(),
+            attribution: dataset_configuration.attribution.clone(),
+            recorder_version: env!("CARGO_PKG_VERSION").to_string(),
+            captured_from,
+            captured_to,
+        }
+    }
+
+    /// Example: `Data: Ljubljanski potniški promet (LPP), captured 2023-11-06 04:00 - 2023-11-06
+    /// 04:25 UTC with lpp-timetable-recorder 1.0.0. License: CC BY 4.0.`
+    pub fn to_notice(&self) -> String {
+        let mut notice = format!(
+            "Data: {}, captured {} - {} UTC with lpp-timetable-recorder {}.",
+            self.source,
+            self.captured_from.format("%Y-%m-%d %H:%M"),
+            self.captured_to.format("%Y-%m-%d %H:%M"),
+            self.recorder_version
+        );
+
+        if let Some(license) = &self.license {
+            notice.push_str(&format!(" License: {}.", license));
+        }
+
+        if let Some(attribution) = &self.attribution {
+            notice.push(' ');
+            notice.push_str(attribution);
+        }
+
+        notice
+    }
+}
@@ -0,0 +1,333 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::recorder::formats::{AllRoutesSnapshot, AllStationsSnapshot};
+
+/// Default name of the changelog file (`changelog.txt`) in the storage root,
+/// to which a changelog is appended after each snapshot.
+pub const CHANGELOG_FILE_NAME: &str = "changelog.txt";
+
+
+/// Identifies a single trip timetable in a station snapshot:
+/// station code, full route name (e.g. `3G`) and trip name.
+type TimetableKey = (String, String, String);
+
+/// The parts of a station and route snapshot pair that are compared in a [`SnapshotChangelog`].
+/// Much smaller than the snapshots themselves, so it can be kept around until the next snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkSummary {
+    pub captured_at: DateTime<Utc>,
+    pub service_date: NaiveDate,
+
+    /// Station names by their station code.
+    stations: BTreeMap<String, String>,
+
+    /// Full route names (e.g. `3G`).
+    routes: BTreeSet<String>,
+
+    /// Departures (hour and minute) of each trip timetable.
+    timetables: BTreeMap<TimetableKey, Vec<(u8, u8)>>,
+}
+
+impl NetworkSummary {
+    pub fn from_snapshots(
+        station_snapshot: &AllStationsSnapshot,
+        route_snapshot: &AllRoutesSnapshot,
+    ) -> Self {
+        let mut stations = BTreeMap::new();
+        let mut timetables = BTreeMap::new();
+
+        for station in &station_snapshot.station_details {
+            stations.insert(
+                station.station_code.to_string(),
+                station.name.clone(),
+            );
+
+            for trip_timetable in station
+                .timetables
+                .iter()
+                .flat_map(|timetable| timetable.trip_timetables.iter())
+            {
+                let mut departures = trip_timetable
+                    .timetable
+                    .iter()
+                    .map(|entry| (entry.hour, entry.minute))
+                    .collect::<Vec<_>>();
+                departures.sort_unstable();
+
+                timetables.insert(
+                    (
+                        station.station_code.to_string(),
+                        trip_timetable.route.to_string(),
+                        trip_timetable.trip_name.clone(),
+                    ),
+                    departures,
+                );
+            }
+        }
+
+        let routes = route_snapshot
+            .routes
+            .iter()
+            .map(|trip| trip.route_details.route.to_string())
+            .collect();
+
+        Self {
+            captured_at: station_snapshot.captured_at,
+            service_date: station_snapshot.service_date_or_capture_date(),
+            stations,
+            routes,
+            timetables,
+        }
+    }
+}
+
+
+/// Differences between two consecutive snapshots: which stations and routes appeared or
+/// disappeared and how many trip timetables changed. Its [`Display`] implementation
+/// is a short human-readable report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotChangelog {
+    pub previous_captured_at: DateTime<Utc>,
+    pub previous_service_date: NaiveDate,
+    pub captured_at: DateTime<Utc>,
+    pub service_date: NaiveDate,
+
+    /// Station codes and names of the stations that are new in the current snapshot.
+    pub new_stations: Vec<(String, String)>,
+
+    /// Station codes and names of the stations that are missing from the current snapshot.
+    pub removed_stations: Vec<(String, String)>,
+
+    pub new_routes: Vec<String>,
+    pub removed_routes: Vec<String>,
+
+    /// Number of trip timetables that were added, removed or whose departures changed.
+    pub changed_timetables: usize,
+
+    /// Number of stations with at least one changed trip timetable.
+    pub stations_with_changed_timetables: usize,
+}
+
+impl SnapshotChangelog {
+    pub fn between(previous: &NetworkSummary, current: &NetworkSummary) -> Self {
+        let new_stations = entries_missing_from(&current.stations, &previous.stations);
+        let removed_stations = entries_missing_from(&previous.stations, &current.stations);
+
+        let new_routes = current
+            .routes
+            .difference(&previous.routes)
+            .cloned()
+            .collect();
+        let removed_routes = previous
+            .routes
+            .difference(&current.routes)
+            .cloned()
+            .collect();
+
+        let changed_timetable_keys = previous
+            .timetables
+            .keys()
+            .chain(current.timetables.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter(|key| previous.timetables.get(*key) != current.timetables.get(*key))
+            .collect::<Vec<_>>();
+
+        let stations_with_changed_timetables = changed_timetable_keys
+            .iter()
+            .map(|(station_code, _, _)| station_code)
+            .collect::<BTreeSet<_>>()
+            .len();
+
+        Self {
+            previous_captured_at: previous.captured_at,
+            previous_service_date: previous.service_date,
+            captured_at: current.captured_at,
+            service_date: current.service_date,
+            new_stations,
+            removed_stations,
+            new_routes,
+            removed_routes,
+            changed_timetables: changed_timetable_keys.len(),
+            stations_with_changed_timetables,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.new_stations.is_empty()
+            && self.removed_stations.is_empty()
+            && self.new_routes.is_empty()
+            && self.removed_routes.is_empty()
+            && self.changed_timetables == 0
+    }
+
+    /// Appends this changelog (followed by an empty line) to the given file, creating it if needed.
+    pub fn append_to_file(&self, file_path: &Path) -> std::io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(file_path)?;
+
+        writeln!(file, "{}", self)?;
+        writeln!(file)
+    }
+}
+
+impl Display for SnapshotChangelog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Snapshot of {} (service date {}) compared to snapshot of {} (service date {}):",
+            self.captured_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            self.service_date,
+            self.previous_captured_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            self.previous_service_date,
+        )?;
+
+        if self.is_empty() {
+            return write!(f, "- no changes");
+        }
+
+        let station_list = |stations: &[(String, String)]| {
+            stations
+                .iter()
+                .map(|(station_code, name)| format!("{} ({})", name, station_code))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        writeln!(
+            f,
+            "- {} new station(s){}",
+            self.new_stations.len(),
+            list_suffix(&station_list(&self.new_stations))
+        )?;
+        writeln!(
+            f,
+            "- {} removed station(s){}",
+            self.removed_stations.len(),
+            list_suffix(&station_list(&self.removed_stations))
+        )?;
+        writeln!(
+            f,
+            "- {} new route(s){}",
+            self.new_routes.len(),
+            list_suffix(&self.new_routes.join(", "))
+        )?;
+        writeln!(
+            f,
+            "- {} removed route(s){}",
+            self.removed_routes.len(),
+            list_suffix(&self.removed_routes.join(", "))
+        )?;
+        write!(
+            f,
+            "- {} timetable change(s) at {} station(s)",
+            self.changed_timetables, self.stations_with_changed_timetables
+        )
+    }
+}
+
+fn list_suffix(list: &str) -> String {
+    if list.is_empty() {
+        String::new()
+    } else {
+        format!(": {}", list)
+    }
+}
+
+fn entries_missing_from(
+    entries: &BTreeMap<String, String>,
+    other_entries: &BTreeMap<String, String>,
+) -> Vec<(String, String)> {
+    entries
+        .iter()
+        .filter(|(key, _)| !other_entries.contains_key(*key))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    /// Station code, route, trip name and departures of a trip timetable.
+    type TimetableFixture<'a> = ((&'a str, &'a str, &'a str), &'a [(u8, u8)]);
+
+    fn summary(
+        stations: &[(&str, &str)],
+        routes: &[&str],
+        timetables: &[TimetableFixture],
+    ) -> NetworkSummary {
+        NetworkSummary {
+            captured_at: Utc.with_ymd_and_hms(2023, 11, 6, 12, 0, 0).unwrap(),
+            service_date: NaiveDate::from_ymd_opt(2023, 11, 6).unwrap(),
+            stations: stations
+                .iter()
+                .map(|(code, name)| (code.to_string(), name.to_string()))
+                .collect(),
+            routes: routes.iter().map(|route| route.to_string()).collect(),
+            timetables: timetables
+                .iter()
+                .map(|((code, route, trip), departures)| {
+                    (
+                        (
+                            code.to_string(),
+                            route.to_string(),
+                            trip.to_string(),
+                        ),
+                        departures.to_vec(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn changelog_lists_station_route_and_timetable_changes() {
+        let previous = summary(
+            &[("600011", "KONGRESNI TRG"), ("600012", "DRAMA")],
+            &["2", "3G"],
+            &[
+                (("600011", "2", "NOVE JARŠE"), &[(5, 10), (5, 40)]),
+                (("600012", "2", "NOVE JARŠE"), &[(5, 12)]),
+            ],
+        );
+        let current = summary(
+            &[("600011", "KONGRESNI TRG"), ("600013", "AJDOVŠČINA")],
+            &["2", "11B"],
+            &[
+                (("600011", "2", "NOVE JARŠE"), &[(5, 10), (5, 45)]),
+                (("600013", "2", "NOVE JARŠE"), &[(5, 14)]),
+            ],
+        );
+
+        let changelog = SnapshotChangelog::between(&previous, &current);
+
+        assert_eq!(
+            changelog.new_stations,
+            vec![(String::from("600013"), String::from("AJDOVŠČINA"))]
+        );
+        assert_eq!(
+            changelog.removed_stations,
+            vec![(String::from("600012"), String::from("DRAMA"))]
+        );
+        assert_eq!(changelog.new_routes, vec![String::from("11B")]);
+        assert_eq!(changelog.removed_routes, vec![String::from("3G")]);
+        assert_eq!(changelog.changed_timetables, 3);
+        assert_eq!(changelog.stations_with_changed_timetables, 3);
+
+        assert!(SnapshotChangelog::between(&current, &current).is_empty());
+    }
+}
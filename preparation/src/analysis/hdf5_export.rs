@@ -0,0 +1,651 @@
+//! Export of timetables and recorded arrivals into a NetCDF-4 file, i.e. an HDF5 file following
+//! netCDF conventions, for scientific tools (MATLAB's `h5read`/`ncread`, h5py, xarray, ...).
+//! Available with the `hdf5-export` cargo feature, which requires the netCDF-C library
+//! (built with HDF5 support).
+//!
+//! ## Layout
+//!
+//! All dimensions are defined in the root group and shared by the other groups:
+//! `station`, `trip`, `vehicle`, `departure` and `observation`.
+//!
+//! ```text
+//! /                                  global attributes: title, source, created_at, service_date, ...
+//! /stations/station_code   string   (station)
+//! /stations/name           string   (station)
+//! /stations/latitude       f64      (station)
+//! /stations/longitude      f64      (station)
+//! /trips/trip_id           string   (trip)
+//! /trips/route             string   (trip)         e.g. "3G"
+//! /trips/name              string   (trip)         e.g. "MESTNI LOG - VIŽMARJE"
+//! /vehicles/vehicle_id     string   (vehicle)
+//! /timetables/trip         u32      (departure)    index into /trips
+//! /timetables/station      u32      (departure)    index into /stations
+//! /timetables/stop_number  u32      (departure)
+//! /timetables/departure_minute  u16 (departure)    minutes since midnight of the service date
+//! /timetables/is_interpolated   u8  (departure)    1 if estimated from neighbouring stations
+//! /arrivals/captured_at    f64      (observation)  seconds since 1970-01-01 00:00:00 UTC
+//! /arrivals/trip           u32      (observation)  index into /trips
+//! /arrivals/station        u32      (observation)  index into /stations
+//! /arrivals/vehicle        u32      (observation)  index into /vehicles
+//! /arrivals/estimation_type  u8     (observation)  0 location-based, 1 timetable-based,
+//!                                                  2 arriving, 3 on detour
+//! /arrivals/eta_minutes    i32      (observation)  fill value -1 (buses on a detour)
+//! ```
+//!
+//! Both `/timetables` and `/arrivals` are long-format tables: a `departure` is one scheduled
+//! departure of a trip from one of its stations, and an `observation` is one bus arriving to
+//! a station in one arrival snapshot. Their numeric variables are chunked along their dimension
+//! and compressed with deflate (after the shuffle filter). Strings are only stored once,
+//! in the lookup groups, because netCDF can't compress string variables.
+//! Groups whose dimension would be empty (e.g. `/arrivals` if no arrivals were recorded)
+//! are left out, as a zero-length dimension would be an unlimited one.
+
+use std::{collections::HashMap, hash::Hash, path::Path};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use netcdf::{types::NcVariableType, AttributeValue, FileMut, NcTypeDescriptor};
+
+use crate::{
+    api::{arrivals_on_route::ArrivalEstimation, routes::RouteDetails, GeographicalLocation},
+    recorder::formats::{AllRoutesSnapshot, TripArrivalsSnapshot},
+};
+
+/// Fill value of `/arrivals/eta_minutes` (buses on a detour have no ETA).
+const MISSING_ETA_MINUTES: i32 = -1;
+
+
+/// How the numeric variables of an export are stored.
+#[derive(Clone, Copy, Debug)]
+pub struct Hdf5ExportOptions {
+    /// Deflate level, from `0` (no compression) to `9`.
+    pub compression_level: u8,
+
+    /// Maximum number of values per chunk.
+    pub chunk_size: usize,
+}
+
+/// Provenance of an export, saved as global attributes.
+#[derive(Clone, Debug)]
+pub struct Hdf5ExportMetadata {
+    pub created_at: DateTime<Utc>,
+
+    /// Service date of the timetables.
+    pub service_date: NaiveDate,
+
+    /// File path of the route snapshot the timetables were taken from.
+    pub route_snapshot_file_path: String,
+
+    pub arrival_snapshots: usize,
+}
+
+
+/// Values that are stored once and referred to by their index.
+#[derive(Debug)]
+struct LookupTable<K, V> {
+    indices: HashMap<K, u32>,
+    values: Vec<V>,
+}
+
+impl<K, V> LookupTable<K, V>
+where
+    K: Eq + Hash,
+{
+    fn new() -> Self {
+        Self {
+            indices: HashMap::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Returns the index of the value with the given key, adding the value if it's new.
+    fn index_of<F>(&mut self, key: K, value: F) -> u32
+    where
+        F: FnOnce() -> V,
+    {
+        let next_index = self.values.len() as u32;
+
+        *self.indices.entry(key).or_insert_with(|| {
+            self.values.push(value());
+            next_index
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+}
+
+#[derive(Debug)]
+struct ExportedStation {
+    station_code: String,
+    name: String,
+    location: GeographicalLocation,
+}
+
+#[derive(Debug)]
+struct ExportedTrip {
+    trip_id: String,
+    route: String,
+    name: String,
+}
+
+impl ExportedTrip {
+    fn from_route_details(route_details: &RouteDetails) -> Self {
+        Self {
+            trip_id: route_details.trip_id.to_string(),
+            route: route_details.route.to_string(),
+            name: route_details.name.clone(),
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+struct TimetableColumns {
+    trip: Vec<u32>,
+    station: Vec<u32>,
+    stop_number: Vec<u32>,
+    departure_minute: Vec<u16>,
+    is_interpolated: Vec<u8>,
+}
+
+#[derive(Default, Debug)]
+struct ArrivalColumns {
+    captured_at: Vec<f64>,
+    trip: Vec<u32>,
+    station: Vec<u32>,
+    vehicle: Vec<u32>,
+    estimation_type: Vec<u8>,
+    eta_minutes: Vec<i32>,
+}
+
+
+/// Timetables and arrivals converted into the columns of an export (see the module docs).
+#[derive(Debug)]
+pub struct Hdf5Export {
+    stations: LookupTable<String, ExportedStation>,
+    trips: LookupTable<String, ExportedTrip>,
+    vehicles: LookupTable<String, String>,
+    timetables: TimetableColumns,
+    arrivals: ArrivalColumns,
+}
+
+impl Hdf5Export {
+    pub fn new() -> Self {
+        Self {
+            stations: LookupTable::new(),
+            trips: LookupTable::new(),
+            vehicles: LookupTable::new(),
+            timetables: TimetableColumns::default(),
+            arrivals: ArrivalColumns::default(),
+        }
+    }
+
+    /// Adds every departure of every trip in the route snapshot.
+    pub fn add_route_snapshot(&mut self, snapshot: &AllRoutesSnapshot) {
+        for trip in &snapshot.routes {
+            let trip_index = self
+                .trips
+                .index_of(trip.route_details.trip_id.to_string(), || {
+                    ExportedTrip::from_route_details(&trip.route_details)
+                });
+
+            for station_with_timetable in &trip.stations_on_route_with_timetables {
+                let station = &station_with_timetable.station;
+                let station_index =
+                    self.stations
+                        .index_of(station.station_code.to_string(), || {
+                            ExportedStation {
+                                station_code: station.station_code.to_string(),
+                                name: station.name.clone(),
+                                location: station.location,
+                            }
+                        });
+
+                for entry in &station_with_timetable.timetable.timetable {
+                    self.timetables.trip.push(trip_index);
+                    self.timetables.station.push(station_index);
+                    self.timetables
+                        .stop_number
+                        .push(station.stop_number.max(0) as u32);
+                    self.timetables
+                        .departure_minute
+                        .push(u16::from(entry.hour) * 60 + u16::from(entry.minute));
+                    self.timetables.is_interpolated.push(u8::from(
+                        station_with_timetable.timetable_is_interpolated,
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Adds every bus arriving to a station in the arrival snapshot.
+    pub fn add_trip_arrivals(&mut self, snapshot: &TripArrivalsSnapshot) {
+        let trip_index = self.trips.index_of(snapshot.trip.trip_id.to_string(), || {
+            ExportedTrip::from_route_details(&snapshot.trip)
+        });
+        let captured_at = snapshot.captured_at.timestamp_micros() as f64 / 1_000_000.0;
+
+        for station in &snapshot.stations {
+            let station_index = self
+                .stations
+                .index_of(station.station_code.to_string(), || {
+                    ExportedStation {
+                        station_code: station.station_code.to_string(),
+                        name: station.name.clone(),
+                        location: station.location,
+                    }
+                });
+
+            for arrival in &station.arrivals {
+                let vehicle_id = arrival.vehicle_id.to_string();
+                let vehicle_index = self.vehicles.index_of(vehicle_id.clone(), || vehicle_id);
+
+                let (estimation_type, eta_minutes) = match arrival.arrival_estimation {
+                    ArrivalEstimation::LocationBased { eta_in_minutes } => {
+                        (0, eta_in_minutes as i32)
+                    }
+                    ArrivalEstimation::TimetableBased { eta_in_minutes } => {
+                        (1, eta_in_minutes as i32)
+                    }
+                    ArrivalEstimation::CurrentlyArrivingToStation => (2, 0),
+                    ArrivalEstimation::OnDetour => (3, MISSING_ETA_MINUTES),
+                };
+
+                self.arrivals.captured_at.push(captured_at);
+                self.arrivals.trip.push(trip_index);
+                self.arrivals.station.push(station_index);
+                self.arrivals.vehicle.push(vehicle_index);
+                self.arrivals.estimation_type.push(estimation_type);
+                self.arrivals.eta_minutes.push(eta_minutes);
+            }
+        }
+    }
+
+    pub fn number_of_departures(&self) -> usize {
+        self.timetables.trip.len()
+    }
+
+    pub fn number_of_observations(&self) -> usize {
+        self.arrivals.trip.len()
+    }
+
+    /// Writes the export into a new NetCDF-4 file (replacing any existing file).
+    pub fn write(
+        &self,
+        file_path: &Path,
+        options: &Hdf5ExportOptions,
+        metadata: &Hdf5ExportMetadata,
+    ) -> Result<()> {
+        let mut file = netcdf::create(file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to create NetCDF-4 file."))?;
+
+        self.write_into(&mut file, options, metadata)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to write export into NetCDF-4 file."))?;
+
+        file.close()
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to close NetCDF-4 file."))
+    }
+
+    fn write_into(
+        &self,
+        file: &mut FileMut,
+        options: &Hdf5ExportOptions,
+        metadata: &Hdf5ExportMetadata,
+    ) -> netcdf::Result<()> {
+        file.add_attribute(
+            "title",
+            "LPP (Ljubljana city buses) timetables and recorded arrivals",
+        )?;
+        file.add_attribute(
+            "source",
+            format!(
+                "lpp-timetable-recorder {}",
+                env!("CARGO_PKG_VERSION")
+            ),
+        )?;
+        file.add_attribute("created_at", metadata.created_at.to_rfc3339())?;
+        file.add_attribute("service_date", metadata.service_date.to_string())?;
+        file.add_attribute(
+            "route_snapshot",
+            metadata.route_snapshot_file_path.clone(),
+        )?;
+        file.add_attribute(
+            "arrival_snapshots",
+            metadata.arrival_snapshots as u64,
+        )?;
+
+        if self.stations.len() > 0 {
+            file.add_dimension("station", self.stations.len())?;
+            file.add_group("stations")?;
+
+            put_string_variable(
+                file,
+                "stations/station_code",
+                "station",
+                self.stations
+                    .values
+                    .iter()
+                    .map(|station| station.station_code.as_str()),
+            )?;
+            put_string_variable(
+                file,
+                "stations/name",
+                "station",
+                self.stations
+                    .values
+                    .iter()
+                    .map(|station| station.name.as_str()),
+            )?;
+            put_numeric_variable(
+                file,
+                "stations/latitude",
+                "station",
+                &self
+                    .stations
+                    .values
+                    .iter()
+                    .map(|station| station.location.latitude)
+                    .collect::<Vec<_>>(),
+                options,
+                &[("units", "degrees_north".into())],
+            )?;
+            put_numeric_variable(
+                file,
+                "stations/longitude",
+                "station",
+                &self
+                    .stations
+                    .values
+                    .iter()
+                    .map(|station| station.location.longitude)
+                    .collect::<Vec<_>>(),
+                options,
+                &[("units", "degrees_east".into())],
+            )?;
+        }
+
+        if self.trips.len() > 0 {
+            file.add_dimension("trip", self.trips.len())?;
+            file.add_group("trips")?;
+
+            put_string_variable(
+                file,
+                "trips/trip_id",
+                "trip",
+                self.trips.values.iter().map(|trip| trip.trip_id.as_str()),
+            )?;
+            put_string_variable(
+                file,
+                "trips/route",
+                "trip",
+                self.trips.values.iter().map(|trip| trip.route.as_str()),
+            )?;
+            put_string_variable(
+                file,
+                "trips/name",
+                "trip",
+                self.trips.values.iter().map(|trip| trip.name.as_str()),
+            )?;
+        }
+
+        if self.vehicles.len() > 0 {
+            file.add_dimension("vehicle", self.vehicles.len())?;
+            file.add_group("vehicles")?;
+
+            put_string_variable(
+                file,
+                "vehicles/vehicle_id",
+                "vehicle",
+                self.vehicles.values.iter().map(String::as_str),
+            )?;
+        }
+
+        if self.number_of_departures() > 0 {
+            file.add_dimension("departure", self.number_of_departures())?;
+            file.add_group("timetables")?;
+
+            put_numeric_variable(
+                file,
+                "timetables/trip",
+                "departure",
+                &self.timetables.trip,
+                options,
+                &[("long_name", "index into /trips".into())],
+            )?;
+            put_numeric_variable(
+                file,
+                "timetables/station",
+                "departure",
+                &self.timetables.station,
+                options,
+                &[("long_name", "index into /stations".into())],
+            )?;
+            put_numeric_variable(
+                file,
+                "timetables/stop_number",
+                "departure",
+                &self.timetables.stop_number,
+                options,
+                &[],
+            )?;
+            put_numeric_variable(
+                file,
+                "timetables/departure_minute",
+                "departure",
+                &self.timetables.departure_minute,
+                options,
+                &[(
+                    "units",
+                    "minutes since midnight of the service date (local time)".into(),
+                )],
+            )?;
+            put_numeric_variable(
+                file,
+                "timetables/is_interpolated",
+                "departure",
+                &self.timetables.is_interpolated,
+                options,
+                &[(
+                    "long_name",
+                    "1 if estimated from the timetables of neighbouring stations".into(),
+                )],
+            )?;
+        }
+
+        if self.number_of_observations() > 0 {
+            file.add_dimension("observation", self.number_of_observations())?;
+            file.add_group("arrivals")?;
+
+            put_numeric_variable(
+                file,
+                "arrivals/captured_at",
+                "observation",
+                &self.arrivals.captured_at,
+                options,
+                &[(
+                    "units",
+                    "seconds since 1970-01-01 00:00:00 UTC".into(),
+                )],
+            )?;
+            put_numeric_variable(
+                file,
+                "arrivals/trip",
+                "observation",
+                &self.arrivals.trip,
+                options,
+                &[("long_name", "index into /trips".into())],
+            )?;
+            put_numeric_variable(
+                file,
+                "arrivals/station",
+                "observation",
+                &self.arrivals.station,
+                options,
+                &[("long_name", "index into /stations".into())],
+            )?;
+            put_numeric_variable(
+                file,
+                "arrivals/vehicle",
+                "observation",
+                &self.arrivals.vehicle,
+                options,
+                &[("long_name", "index into /vehicles".into())],
+            )?;
+            put_numeric_variable(
+                file,
+                "arrivals/estimation_type",
+                "observation",
+                &self.arrivals.estimation_type,
+                options,
+                &[
+                    ("flag_values", vec![0u8, 1, 2, 3].into()),
+                    (
+                        "flag_meanings",
+                        "location_based timetable_based arriving on_detour".into(),
+                    ),
+                ],
+            )?;
+
+            let mut eta_minutes =
+                file.add_variable::<i32>("arrivals/eta_minutes", &["observation"])?;
+            configure_storage(
+                &mut eta_minutes,
+                self.number_of_observations(),
+                options,
+            )?;
+            eta_minutes.set_fill_value(MISSING_ETA_MINUTES)?;
+            eta_minutes.put_attribute("units", "minutes")?;
+            eta_minutes.put_values(&self.arrivals.eta_minutes, ..)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+fn configure_storage(
+    variable: &mut netcdf::VariableMut,
+    length: usize,
+    options: &Hdf5ExportOptions,
+) -> netcdf::Result<()> {
+    variable.set_chunking(&[options.chunk_size.min(length).max(1)])?;
+
+    if options.compression_level > 0 {
+        variable.set_compression(i32::from(options.compression_level), true)?;
+    }
+
+    Ok(())
+}
+
+fn put_numeric_variable<T>(
+    file: &mut FileMut,
+    name: &str,
+    dimension: &str,
+    values: &[T],
+    options: &Hdf5ExportOptions,
+    attributes: &[(&str, AttributeValue)],
+) -> netcdf::Result<()>
+where
+    T: NcTypeDescriptor,
+{
+    let mut variable = file.add_variable::<T>(name, &[dimension])?;
+    configure_storage(&mut variable, values.len(), options)?;
+
+    for (attribute_name, attribute_value) in attributes {
+        variable.put_attribute(attribute_name, attribute_value.clone())?;
+    }
+
+    variable.put_values(values, ..)
+}
+
+fn put_string_variable<'v, I>(
+    file: &mut FileMut,
+    name: &str,
+    dimension: &str,
+    values: I,
+) -> netcdf::Result<()>
+where
+    I: Iterator<Item = &'v str>,
+{
+    let mut variable = file.add_variable_with_type(name, &[dimension], &NcVariableType::String)?;
+
+    for (index, value) in values.enumerate() {
+        variable.put_string(value, index)?;
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::api::{
+        arrivals_on_route::StationArrivalDetails,
+        test_fixtures::{arrival, trip},
+        StationCode,
+    };
+
+    #[test]
+    fn convert_arrivals_into_columns() {
+        let snapshot = TripArrivalsSnapshot {
+            captured_at: Utc.with_ymd_and_hms(2023, 11, 6, 8, 0, 30).unwrap(),
+            polling_round_id: Uuid::new_v4(),
+            trip: trip("6B", "ČRNUČE - BAVARSKI DVOR", "trip"),
+            stations: vec![
+                StationArrivalDetails {
+                    station_code: StationCode::new("600011"),
+                    internal_station_id: 1,
+                    name: String::from("BAVARSKI DVOR"),
+                    stop_number: 1,
+                    location: GeographicalLocation::new(46.058, 14.506),
+                    arrivals: vec![
+                        arrival(
+                            "vehicle-1",
+                            ArrivalEstimation::LocationBased { eta_in_minutes: 3 },
+                        ),
+                        arrival("vehicle-2", ArrivalEstimation::OnDetour),
+                    ],
+                },
+                StationArrivalDetails {
+                    station_code: StationCode::new("300011"),
+                    internal_station_id: 2,
+                    name: String::from("ČRNUČE"),
+                    stop_number: 2,
+                    location: GeographicalLocation::new(46.102, 14.530),
+                    arrivals: vec![arrival(
+                        "vehicle-1",
+                        ArrivalEstimation::CurrentlyArrivingToStation,
+                    )],
+                },
+            ],
+        };
+
+        let mut export = Hdf5Export::new();
+        export.add_trip_arrivals(&snapshot);
+        export.add_trip_arrivals(&snapshot);
+
+        // Trips, stations and vehicles are only stored once.
+        assert_eq!(export.trips.len(), 1);
+        assert_eq!(export.stations.len(), 2);
+        assert_eq!(export.vehicles.len(), 2);
+        assert_eq!(export.number_of_departures(), 0);
+        assert_eq!(export.number_of_observations(), 6);
+
+        assert_eq!(export.arrivals.captured_at[0], 1699257630.0);
+        assert_eq!(export.arrivals.station[..3], [0, 0, 1]);
+        assert_eq!(export.arrivals.vehicle[..3], [0, 1, 0]);
+        assert_eq!(export.arrivals.estimation_type[..3], [0, 3, 2]);
+        assert_eq!(
+            export.arrivals.eta_minutes[..3],
+            [3, MISSING_ETA_MINUTES, 0]
+        );
+    }
+}
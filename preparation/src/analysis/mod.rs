@@ -0,0 +1,12 @@
+pub mod attribution;
+pub mod changelog;
+#[cfg(feature = "hdf5-export")]
+pub mod hdf5_export;
+pub mod nearby_stations;
+pub mod query;
+pub mod report;
+pub mod route_families;
+pub mod route_segments;
+pub mod sampling;
+pub mod station_posters;
+pub mod timetable_matrix;
@@ -0,0 +1,131 @@
+use rstar::{primitives::GeomWithData, RTree, AABB};
+
+use crate::{
+    api::{GeographicalLocation, MEAN_EARTH_RADIUS_IN_METERS},
+    recorder::formats::StationDetailsWithBusesAndTimetables,
+};
+
+/// Location of a station as `[longitude, latitude]`, along with its index in the snapshot.
+type IndexedStationLocation = GeomWithData<[f64; 2], usize>;
+
+
+/// Spatial index (an R-tree) over the stations of a station snapshot,
+/// for finding the stations near a location.
+pub struct StationIndex<'s> {
+    stations: &'s [StationDetailsWithBusesAndTimetables],
+    tree: RTree<IndexedStationLocation>,
+}
+
+/// A station found by [`StationIndex::nearest_stations`].
+#[derive(Clone, Copy, Debug)]
+pub struct NearbyStation<'s> {
+    pub station: &'s StationDetailsWithBusesAndTimetables,
+    pub distance_in_meters: f64,
+}
+
+impl<'s> StationIndex<'s> {
+    pub fn new(stations: &'s [StationDetailsWithBusesAndTimetables]) -> Self {
+        let station_locations = stations
+            .iter()
+            .enumerate()
+            .map(|(station_index, station)| {
+                GeomWithData::new(
+                    [station.location.longitude, station.location.latitude],
+                    station_index,
+                )
+            })
+            .collect();
+
+        Self {
+            stations,
+            tree: RTree::bulk_load(station_locations),
+        }
+    }
+
+    /// Returns the stations at most `radius_in_meters` away from `location`, nearest first.
+    pub fn nearest_stations(
+        &self,
+        location: GeographicalLocation,
+        radius_in_meters: f64,
+    ) -> Vec<NearbyStation<'s>> {
+        // The tree is indexed by degrees, so we first look up the stations in the bounding box
+        // of the circle around the location and then drop the ones in its corners.
+        let angular_radius = radius_in_meters / MEAN_EARTH_RADIUS_IN_METERS;
+        let latitude_radius = angular_radius.to_degrees();
+        let longitude_radius = match angular_radius.sin() / location.latitude.to_radians().cos() {
+            ratio if ratio < 1.0 => ratio.asin().to_degrees(),
+            // The circle contains a pole, so it spans all longitudes.
+            _ => 180.0,
+        };
+
+        let bounding_box = AABB::from_corners(
+            [
+                location.longitude - longitude_radius,
+                location.latitude - latitude_radius,
+            ],
+            [
+                location.longitude + longitude_radius,
+                location.latitude + latitude_radius,
+            ],
+        );
+
+        let mut nearby_stations: Vec<_> = self
+            .tree
+            .locate_in_envelope(&bounding_box)
+            .filter_map(|station_location| {
+                let station = &self.stations[station_location.data];
+                let distance_in_meters = location.distance_in_meters_to(&station.location);
+
+                (distance_in_meters <= radius_in_meters).then_some(NearbyStation {
+                    station,
+                    distance_in_meters,
+                })
+            })
+            .collect();
+
+        nearby_stations.sort_by(|first, second| {
+            first
+                .distance_in_meters
+                .total_cmp(&second.distance_in_meters)
+        });
+
+        nearby_stations
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_fixtures::station_at;
+
+    #[test]
+    fn find_stations_within_radius_nearest_first() {
+        let stations = vec![
+            // About 290 m away.
+            station_at("600012", 46.0525, 14.5035),
+            // About 20 m away.
+            station_at("600011", 46.0503, 14.5049),
+            // About 900 m away.
+            station_at("600021", 46.0580, 14.5060),
+            // About 350 m away, in the corner of the bounding box the tree is searched in.
+            station_at("600031", 46.0523, 14.5083),
+        ];
+        let station_index = StationIndex::new(&stations);
+
+        let nearby_stations =
+            station_index.nearest_stations(GeographicalLocation::new(46.0501, 14.5050), 300.0);
+        let nearby_station_codes: Vec<_> = nearby_stations
+            .iter()
+            .map(|nearby_station| nearby_station.station.station_code.as_ref())
+            .collect();
+
+        assert_eq!(nearby_station_codes, vec!["600011", "600012"]);
+        assert!(nearby_stations[0].distance_in_meters < 50.0);
+        assert!(nearby_stations[1].distance_in_meters <= 300.0);
+
+        assert!(station_index
+            .nearest_stations(GeographicalLocation::new(46.1, 14.6), 300.0)
+            .is_empty());
+    }
+}
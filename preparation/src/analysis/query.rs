@@ -0,0 +1,158 @@
+use jaq_core::{
+    load::{self, Arena, File, Loader},
+    Compiler,
+    Ctx,
+    Native,
+    RcIter,
+};
+use jaq_json::Val;
+use thiserror::Error;
+
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotQueryError {
+    #[error("Failed to parse query expression: {reason}")]
+    Parse { reason: String },
+
+    #[error("Failed to compile query expression: {reason}")]
+    Compile { reason: String },
+
+    #[error("Failed to evaluate query expression: {reason}")]
+    Evaluation { reason: String },
+}
+
+
+/// A compiled jq expression (e.g. `.routes | length`) to evaluate against snapshots.
+/// The jq standard library is available (`map`, `select`, `length`, ...).
+pub struct SnapshotQuery {
+    filter: jaq_core::Filter<Native<Val>>,
+}
+
+impl SnapshotQuery {
+    pub fn compile(expression: &str) -> Result<Self, SnapshotQueryError> {
+        let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+        let arena = Arena::default();
+
+        let modules = loader
+            .load(
+                &arena,
+                File {
+                    code: expression,
+                    path: (),
+                },
+            )
+            .map_err(|errors| SnapshotQueryError::Parse {
+                reason: errors
+                    .into_iter()
+                    .map(|(_, error)| describe_load_error(error))
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            })?;
+
+        let filter = Compiler::default()
+            .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+            .compile(modules)
+            .map_err(|errors| SnapshotQueryError::Compile {
+                reason: errors
+                    .into_iter()
+                    .flat_map(|(_, errors)| errors)
+                    .map(|(name, undefined)| format!("undefined {} `{}`", undefined.as_str(), name))
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            })?;
+
+        Ok(Self { filter })
+    }
+
+    /// Evaluates the expression against a (serialized) snapshot and returns all of its outputs.
+    pub fn run(
+        &self,
+        snapshot: serde_json::Value,
+    ) -> Result<Vec<serde_json::Value>, SnapshotQueryError> {
+        let inputs = RcIter::new(core::iter::empty());
+
+        self.filter
+            .run((Ctx::new([], &inputs), Val::from(snapshot)))
+            .map(|output| {
+                output.map(serde_json::Value::from).map_err(|error| {
+                    SnapshotQueryError::Evaluation {
+                        reason: error.to_string(),
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+fn describe_load_error(error: load::Error<&str>) -> String {
+    match error {
+        load::Error::Io(errors) => errors
+            .into_iter()
+            .map(|(path, reason)| format!("{}: {}", path, reason))
+            .collect::<Vec<_>>()
+            .join("; "),
+        load::Error::Lex(errors) => errors
+            .into_iter()
+            .map(|(expected, found)| describe_unexpected(expected.as_str(), found))
+            .collect::<Vec<_>>()
+            .join("; "),
+        load::Error::Parse(errors) => errors
+            .into_iter()
+            .map(|(expected, found)| describe_unexpected(expected.as_str(), found))
+            .collect::<Vec<_>>()
+            .join("; "),
+    }
+}
+
+fn describe_unexpected(expected: &str, found: &str) -> String {
+    match found.is_empty() {
+        true => format!("expected {} at the end", expected),
+        false => format!("expected {} at `{}`", expected, found),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn query_snapshot_values() {
+        let snapshot = json!({
+            "station_details": [
+                { "name": "KONGRESNI TRG", "routes_on_station": ["2", "6B", "11"] },
+                { "name": "BAVARSKI DVOR", "routes_on_station": ["6B"] },
+                { "name": "ŽELEZNA", "routes_on_station": ["2"] },
+            ]
+        });
+
+        let query = SnapshotQuery::compile(
+            r#"[.station_details[] | select(.routes_on_station | index("6B"))] | length"#,
+        )
+        .unwrap();
+        assert_eq!(
+            query.run(snapshot.clone()).unwrap(),
+            vec![json!(2)]
+        );
+
+        let query = SnapshotQuery::compile(".station_details[].name").unwrap();
+        assert_eq!(query.run(snapshot).unwrap().len(), 3);
+
+        assert!(matches!(
+            SnapshotQuery::compile(".station_details["),
+            Err(SnapshotQueryError::Parse { .. })
+        ));
+        assert!(matches!(
+            SnapshotQuery::compile("no_such_filter"),
+            Err(SnapshotQueryError::Compile { .. })
+        ));
+        assert!(matches!(
+            SnapshotQuery::compile(".station_details | keys_unsorted | .[0] + 1")
+                .unwrap()
+                .run(json!({ "station_details": { "a": 1 } })),
+            Err(SnapshotQueryError::Evaluation { .. })
+        ));
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+
+use askama::Template;
+
+use super::attribution::DatasetAttribution;
+use crate::{
+    display_names::DisplayNames,
+    recorder::formats::{AllRoutesSnapshot, AllStationsSnapshot},
+};
+
+/// Default name of the HTML report file (`report.html`) in the storage root.
+pub const REPORT_FILE_NAME: &str = "report.html";
+
+
+/// A self-contained HTML page with the routes, their stations and the station timetables
+/// of a station and route snapshot pair, for sharing with people who don't read JSON.
+#[derive(Template, Debug, Clone)]
+#[template(path = "report.html")]
+pub struct SnapshotReport {
+    /// Example: `2023-11-06 12:00:00 UTC`.
+    pub captured_at: String,
+
+    /// Example: `2023-11-06 (Weekday)`.
+    pub service_date: String,
+
+    pub routes: Vec<ReportRoute>,
+    pub stations: Vec<ReportStation>,
+
+    pub attribution: DatasetAttribution,
+}
+
+/// A single trip (one direction of a route) and its stations, in order.
+#[derive(Debug, Clone)]
+pub struct ReportRoute {
+    /// Example: `3G`.
+    pub route: String,
+
+    /// Example: `LITOSTROJ - Bavarski dvor - RUDNIK`.
+    pub trip_name: String,
+
+    pub stations: Vec<ReportRouteStation>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReportRouteStation {
+    pub stop_number: i32,
+    pub station_code: String,
+    pub name: String,
+
+    /// Whether the station's timetable on this trip was interpolated.
+    pub timetable_is_interpolated: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReportStation {
+    pub station_code: String,
+    pub name: String,
+    pub timetables: Vec<ReportTimetable>,
+}
+
+/// Departures of a single trip from a station, grouped by hour.
+#[derive(Debug, Clone)]
+pub struct ReportTimetable {
+    pub route: String,
+    pub trip_name: String,
+
+    /// Hours with at least one departure and their departure minutes,
+    /// e.g. `(5, "10 40")`.
+    pub departures_by_hour: Vec<(u8, String)>,
+}
+
+impl SnapshotReport {
+    pub fn from_snapshots(
+        station_snapshot: &AllStationsSnapshot,
+        route_snapshot: &AllRoutesSnapshot,
+        attribution: DatasetAttribution,
+        display_names: &DisplayNames,
+    ) -> Self {
+        let mut sorted_trips = route_snapshot.routes.iter().collect::<Vec<_>>();
+        sorted_trips.sort_by_key(|trip| {
+            (
+                trip.route_details.route.base_route_number,
+                trip.route_details.route.to_string(),
+                trip.route_details.name.clone(),
+            )
+        });
+
+        let routes = sorted_trips
+            .into_iter()
+            .map(|trip| ReportRoute {
+                route: trip.route_details.route.to_string(),
+                trip_name: display_names.display_name(&trip.route_details.name),
+                stations: trip
+                    .stations_on_route_with_timetables
+                    .iter()
+                    .map(|station_with_timetable| ReportRouteStation {
+                        stop_number: station_with_timetable.station.stop_number,
+                        station_code: station_with_timetable.station.station_code.to_string(),
+                        name: display_names.display_name(&station_with_timetable.station.name),
+                        timetable_is_interpolated: station_with_timetable.timetable_is_interpolated,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let mut stations = station_snapshot
+            .station_details
+            .iter()
+            .map(|station| ReportStation {
+                station_code: station.station_code.to_string(),
+                name: display_names.display_name(&station.name),
+                timetables: station
+                    .timetables
+                    .iter()
+                    .flat_map(|timetable| timetable.trip_timetables.iter())
+                    .map(|trip_timetable| {
+                        let mut minutes_by_hour: BTreeMap<u8, Vec<u8>> = BTreeMap::new();
+                        for entry in &trip_timetable.timetable {
+                            minutes_by_hour
+                                .entry(entry.hour)
+                                .or_default()
+                                .push(entry.minute);
+                        }
+
+                        ReportTimetable {
+                            route: trip_timetable.route.to_string(),
+                            trip_name: display_names.display_name(&trip_timetable.trip_name),
+                            departures_by_hour: minutes_by_hour
+                                .into_iter()
+                                .map(|(hour, mut minutes)| {
+                                    minutes.sort_unstable();
+                                    let minutes = minutes
+                                        .iter()
+                                        .map(|minute| format!("{:02}", minute))
+                                        .collect::<Vec<_>>()
+                                        .join(" ");
+
+                                    (hour, minutes)
+                                })
+                                .collect(),
+                        }
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        stations.sort_by(|first, second| {
+            (&first.name, &first.station_code).cmp(&(&second.name, &second.station_code))
+        });
+
+        Self {
+            captured_at: station_snapshot
+                .captured_at
+                .format("%Y-%m-%d %H:%M:%S UTC")
+                .to_string(),
+            service_date: station_snapshot.service_day_description(),
+            routes,
+            stations,
+            attribution,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    #[test]
+    fn report_escapes_names() {
+        let report = SnapshotReport {
+            captured_at: String::from("2023-11-06 12:00:00 UTC"),
+            service_date: String::from("2023-11-06 (Weekday)"),
+            routes: vec![],
+            stations: vec![ReportStation {
+                station_code: String::from("600011"),
+                name: String::from("<KONGRESNI TRG>"),
+                timetables: vec![ReportTimetable {
+                    route: String::from("2"),
+                    trip_name: String::from("NOVE JARŠE"),
+                    departures_by_hour: vec![(5, String::from("10 40"))],
+                }],
+            }],
+            attribution: DatasetAttribution {
+                source: String::from("Ljubljanski potniški promet (LPP)"),
+                license: Some(String::from("CC BY 4.0")),
+                attribution: None,
+                recorder_version: String::from("1.0.0"),
+                captured_from: Utc.with_ymd_and_hms(2023, 11, 6, 4, 0, 0).unwrap(),
+                captured_to: Utc.with_ymd_and_hms(2023, 11, 6, 4, 25, 0).unwrap(),
+            },
+        };
+
+        let html = report.render().unwrap();
+
+        assert!(html.contains("&lt;KONGRESNI TRG&gt;"));
+        assert!(!html.contains("<KONGRESNI TRG>"));
+        assert!(html.contains("10 40"));
+        assert!(html.contains(
+            "Data: Ljubljanski potniški promet (LPP), captured 2023-11-06 04:00 - 2023-11-06 04:25 \
+            UTC with lpp-timetable-recorder 1.0.0. License: CC BY 4.0."
+        ));
+    }
+}
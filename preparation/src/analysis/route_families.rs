@@ -0,0 +1,357 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_with::{serde_as, TimestampSecondsWithFrac};
+
+use super::attribution::DatasetAttribution;
+use crate::{
+    api::{BaseBusRoute, BusRoute, StationCode, TripId},
+    display_names::DisplayNames,
+    recorder::formats::{AllRoutesSnapshot, TripWithStationsAndTimetables},
+};
+
+/// Default name of the route family file (`route_families.json`) in the storage root.
+pub const ROUTE_FAMILIES_FILE_NAME: &str = "route_families.json";
+
+
+/// Routes grouped by their base route number (e.g. `3`, `3B` and `3G`), along with
+/// how each route variant branches off the main route. Used to draw branching line diagrams.
+#[serde_as]
+#[derive(Serialize, Debug, Clone)]
+pub struct RouteFamilies {
+    /// File name of the route snapshot these families were derived from.
+    pub source_snapshot: String,
+
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub source_snapshot_captured_at: DateTime<Utc>,
+
+    pub attribution: DatasetAttribution,
+
+    pub families: Vec<RouteFamily>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RouteFamily {
+    pub base_route: BaseBusRoute,
+
+    /// All trips (directions) of all routes in this family.
+    pub trips: Vec<FamilyTrip>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FamilyTrip {
+    pub route: BusRoute,
+    pub trip_id: TripId,
+
+    /// Example: `LITOSTROJ - Bavarski dvor - RUDNIK`.
+    pub name: String,
+
+    /// Stations of this trip (that had a timetable), in order.
+    pub station_codes: Vec<StationCode>,
+
+    /// Set if this trip is a variant (e.g. `3G`) of a trip of the main route (e.g. `3`).
+    /// Missing for trips of the main route and for variants that don't share
+    /// any stations with the main route.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant_of: Option<VariantRelationship>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct VariantRelationship {
+    /// The main route trip this variant follows most closely (usually the one in the same direction).
+    pub parent_trip_id: TripId,
+
+    /// Number of stations the variant shares with the parent trip, in the same order.
+    pub shared_stations: usize,
+
+    /// Stations at which the variant leaves or rejoins the parent trip, in order.
+    pub branch_points: Vec<BranchPoint>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BranchPoint {
+    /// A station of the parent trip.
+    pub station_code: StationCode,
+    pub kind: BranchPointKind,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BranchPointKind {
+    /// The variant leaves the parent trip after this station.
+    Diverges,
+
+    /// The variant (re)joins the parent trip at this station.
+    Rejoins,
+}
+
+
+/// Groups the routes of a snapshot into families and relates each variant trip to the
+/// main route trip it shares the most stations with.
+pub fn derive_route_families(
+    snapshot: &AllRoutesSnapshot,
+    source_snapshot_file_name: String,
+    attribution: DatasetAttribution,
+    display_names: &DisplayNames,
+) -> RouteFamilies {
+    let mut trips_by_base_route: BTreeMap<BaseBusRoute, Vec<&TripWithStationsAndTimetables>> =
+        BTreeMap::new();
+
+    for trip in &snapshot.routes {
+        trips_by_base_route
+            .entry(trip.route_details.route.to_base_route())
+            .or_default()
+            .push(trip);
+    }
+
+    let families = trips_by_base_route
+        .into_iter()
+        .map(|(base_route, trips)| derive_route_family(base_route, trips, display_names))
+        .collect();
+
+    RouteFamilies {
+        source_snapshot: source_snapshot_file_name,
+        source_snapshot_captured_at: snapshot.captured_at,
+        attribution,
+        families,
+    }
+}
+
+fn is_main_route(route: &BusRoute) -> bool {
+    route.prefix.is_none() && route.suffix.is_none() && route.additional_info.is_none()
+}
+
+fn derive_route_family(
+    base_route: BaseBusRoute,
+    mut trips: Vec<&TripWithStationsAndTimetables>,
+    display_names: &DisplayNames,
+) -> RouteFamily {
+    trips.sort_by_cached_key(|trip| {
+        (
+            trip.route_details.route.to_string(),
+            trip.route_details.name.clone(),
+        )
+    });
+
+    let station_codes_of = |trip: &TripWithStationsAndTimetables| -> Vec<StationCode> {
+        trip.stations_on_route_with_timetables
+            .iter()
+            .map(|station| station.station.station_code.clone())
+            .collect()
+    };
+
+    let main_trips: Vec<(&TripId, Vec<StationCode>)> = trips
+        .iter()
+        .filter(|trip| is_main_route(&trip.route_details.route))
+        .map(|trip| {
+            (
+                &trip.route_details.trip_id,
+                station_codes_of(trip),
+            )
+        })
+        .collect();
+
+    let family_trips = trips
+        .iter()
+        .map(|trip| {
+            let station_codes = station_codes_of(trip);
+
+            let variant_of = match is_main_route(&trip.route_details.route) {
+                true => None,
+                false => main_trips
+                    .iter()
+                    .map(|(main_trip_id, main_station_codes)| {
+                        relate_to_parent(main_trip_id, main_station_codes, &station_codes)
+                    })
+                    .filter(|relationship| relationship.shared_stations > 0)
+                    // On ties, the first (i.e. lexicographically smallest) parent trip is kept.
+                    .reduce(|best, candidate| {
+                        match candidate.shared_stations > best.shared_stations {
+                            true => candidate,
+                            false => best,
+                        }
+                    }),
+            };
+
+            FamilyTrip {
+                route: trip.route_details.route.clone(),
+                trip_id: trip.route_details.trip_id.clone(),
+                name: display_names.display_name(&trip.route_details.name),
+                station_codes,
+                variant_of,
+            }
+        })
+        .collect();
+
+    RouteFamily {
+        base_route,
+        trips: family_trips,
+    }
+}
+
+fn relate_to_parent(
+    parent_trip_id: &TripId,
+    parent_stations: &[StationCode],
+    variant_stations: &[StationCode],
+) -> VariantRelationship {
+    let alignment = align_station_sequences(parent_stations, variant_stations);
+
+    VariantRelationship {
+        parent_trip_id: parent_trip_id.clone(),
+        shared_stations: alignment.len(),
+        branch_points: branch_points(parent_stations, variant_stations, &alignment),
+    }
+}
+
+
+/// Aligns two station sequences by their longest common subsequence.
+/// Returns the index pairs `(parent index, variant index)` of the shared stations, in order.
+fn align_station_sequences(
+    parent_stations: &[StationCode],
+    variant_stations: &[StationCode],
+) -> Vec<(usize, usize)> {
+    let (parent_length, variant_length) = (parent_stations.len(), variant_stations.len());
+
+    // common_lengths[i][j] is the length of the longest common subsequence
+    // of parent_stations[i..] and variant_stations[j..].
+    let mut common_lengths = vec![vec![0usize; variant_length + 1]; parent_length + 1];
+
+    for parent_index in (0..parent_length).rev() {
+        for variant_index in (0..variant_length).rev() {
+            common_lengths[parent_index][variant_index] =
+                match parent_stations[parent_index] == variant_stations[variant_index] {
+                    true => common_lengths[parent_index + 1][variant_index + 1] + 1,
+                    false => common_lengths[parent_index + 1][variant_index]
+                        .max(common_lengths[parent_index][variant_index + 1]),
+                };
+        }
+    }
+
+    let mut alignment = Vec::with_capacity(common_lengths[0][0]);
+    let (mut parent_index, mut variant_index) = (0, 0);
+
+    while parent_index < parent_length && variant_index < variant_length {
+        if parent_stations[parent_index] == variant_stations[variant_index] {
+            alignment.push((parent_index, variant_index));
+            parent_index += 1;
+            variant_index += 1;
+        } else if common_lengths[parent_index + 1][variant_index]
+            >= common_lengths[parent_index][variant_index + 1]
+        {
+            parent_index += 1;
+        } else {
+            variant_index += 1;
+        }
+    }
+
+    alignment
+}
+
+/// Finds the stations at which the variant leaves and rejoins the parent trip.
+/// The start and end of the parent trip are not branch points, unless the variant continues past them.
+fn branch_points(
+    parent_stations: &[StationCode],
+    variant_stations: &[StationCode],
+    alignment: &[(usize, usize)],
+) -> Vec<BranchPoint> {
+    let (Some(&(_, first_variant_index)), Some(&(_, last_variant_index))) =
+        (alignment.first(), alignment.last())
+    else {
+        return Vec::new();
+    };
+
+    let branch_point = |parent_index: usize, kind: BranchPointKind| BranchPoint {
+        station_code: parent_stations[parent_index].clone(),
+        kind,
+    };
+
+    let mut branch_points = Vec::new();
+
+    // The variant starts elsewhere and joins the parent trip later.
+    if first_variant_index > 0 {
+        branch_points.push(branch_point(
+            alignment[0].0,
+            BranchPointKind::Rejoins,
+        ));
+    }
+
+    for shared_pair in alignment.windows(2) {
+        let ((parent_index, variant_index), (next_parent_index, next_variant_index)) =
+            (shared_pair[0], shared_pair[1]);
+
+        // The variant takes a detour or skips some of the parent's stations in between.
+        if next_parent_index > parent_index + 1 || next_variant_index > variant_index + 1 {
+            branch_points.push(branch_point(
+                parent_index,
+                BranchPointKind::Diverges,
+            ));
+            branch_points.push(branch_point(
+                next_parent_index,
+                BranchPointKind::Rejoins,
+            ));
+        }
+    }
+
+    // The variant leaves the parent trip and ends elsewhere.
+    if last_variant_index + 1 < variant_stations.len() {
+        branch_points.push(branch_point(
+            alignment[alignment.len() - 1].0,
+            BranchPointKind::Diverges,
+        ));
+    }
+
+    branch_points
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stations(station_codes: &[&str]) -> Vec<StationCode> {
+        station_codes
+            .iter()
+            .map(|station_code| StationCode::new(*station_code))
+            .collect()
+    }
+
+    #[test]
+    fn find_branch_points_of_variant() {
+        let parent_trip_id = TripId::new("parent");
+        let parent = stations(&["A", "B", "C", "D", "E", "F"]);
+
+        // Takes a detour between B and D, then ends at a garage after E.
+        let variant = stations(&["A", "B", "X", "D", "E", "G"]);
+
+        assert_eq!(
+            relate_to_parent(&parent_trip_id, &parent, &variant),
+            VariantRelationship {
+                parent_trip_id: parent_trip_id.clone(),
+                shared_stations: 4,
+                branch_points: vec![
+                    BranchPoint {
+                        station_code: StationCode::new("B"),
+                        kind: BranchPointKind::Diverges,
+                    },
+                    BranchPoint {
+                        station_code: StationCode::new("D"),
+                        kind: BranchPointKind::Rejoins,
+                    },
+                    BranchPoint {
+                        station_code: StationCode::new("E"),
+                        kind: BranchPointKind::Diverges,
+                    },
+                ],
+            }
+        );
+
+        // A shortened variant that only drives part of the parent trip doesn't branch off.
+        let shortened_variant = stations(&["C", "D", "E"]);
+        assert!(
+            relate_to_parent(&parent_trip_id, &parent, &shortened_variant)
+                .branch_points
+                .is_empty()
+        );
+    }
+}
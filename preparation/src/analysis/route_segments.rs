@@ -0,0 +1,269 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_with::{serde_as, TimestampSecondsWithFrac};
+
+use super::attribution::DatasetAttribution;
+use crate::{
+    api::{
+        stations_on_route::StationOnRoute,
+        timetable::TimetableEntry,
+        BusRoute,
+        StationCode,
+        TripId,
+    },
+    display_names::DisplayNames,
+    recorder::{
+        formats::{AllRoutesSnapshot, TripStationWithTimetable, TripWithStationsAndTimetables},
+        interpolation::{distances_along_route, is_usable_route_shape},
+    },
+};
+
+/// Name of the segment file (`segments.json`) in each trip's output directory.
+pub const ROUTE_SEGMENTS_FILE_NAME: &str = "segments.json";
+
+
+/// Station-to-station segments of a single trip (direction of a route), with their distances
+/// and scheduled travel times. Used to color route segments by speed.
+#[serde_as]
+#[derive(Serialize, Debug, Clone)]
+pub struct RouteSegments {
+    /// File name of the route snapshot these segments were derived from.
+    pub source_snapshot: String,
+
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub source_snapshot_captured_at: DateTime<Utc>,
+
+    pub attribution: DatasetAttribution,
+
+    pub route: BusRoute,
+    pub trip_id: TripId,
+
+    /// Example: `LITOSTROJ - Bavarski dvor - RUDNIK`.
+    pub name: String,
+
+    pub distance_source: DistanceSource,
+
+    /// Distance from the first to the last station of the trip (with a timetable), in meters.
+    pub total_distance_in_meters: f64,
+
+    /// Segments between consecutive stations (with a timetable), in order.
+    pub segments: Vec<RouteSegment>,
+}
+
+/// How segment distances were measured.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DistanceSource {
+    /// Along the GeoJSON shape of the route, with stations projected onto it.
+    RouteShape,
+
+    /// As straight lines between consecutive stations (the route shape wasn't captured).
+    StraightLine,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RouteSegment {
+    pub from_station_code: StationCode,
+    pub to_station_code: StationCode,
+
+    pub distance_in_meters: f64,
+
+    /// Missing if the timetables of the two stations can't be paired up
+    /// (see [`ScheduledTravelTime`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheduled_travel_time: Option<ScheduledTravelTime>,
+}
+
+/// Scheduled travel times over a segment. The `n`-th timetable entry of both stations
+/// is considered to be the same bus, so both timetables must have the same number of entries.
+/// As timetables only have minute precision, short segments often take "zero" minutes.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ScheduledTravelTime {
+    /// Number of buses the travel times were derived from.
+    pub number_of_trips: usize,
+
+    pub minimum_minutes: u32,
+    pub median_minutes: u32,
+    pub maximum_minutes: u32,
+
+    /// Missing if the median travel time is zero minutes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub median_speed_in_kilometers_per_hour: Option<f64>,
+
+    /// Whether the timetable of either station was interpolated
+    /// (i.e. the travel time is an estimate, see `timetable_is_interpolated`).
+    pub is_interpolated: bool,
+}
+
+
+/// Derives the segments of every trip in the route snapshot, ordered by route and trip.
+pub fn derive_route_segments(
+    route_snapshot: &AllRoutesSnapshot,
+    source_snapshot: String,
+    attribution: DatasetAttribution,
+    display_names: &DisplayNames,
+) -> Vec<RouteSegments> {
+    let mut trips: Vec<&TripWithStationsAndTimetables> = route_snapshot.routes.iter().collect();
+    trips.sort_by_cached_key(|trip| {
+        (
+            trip.route_details.route.base_route_number,
+            trip.route_details.route.to_string(),
+            trip.route_details.trip_id.to_string(),
+        )
+    });
+
+    trips
+        .into_iter()
+        .map(|trip| {
+            derive_trip_segments(
+                trip,
+                source_snapshot.clone(),
+                route_snapshot.captured_at,
+                attribution.clone(),
+                display_names,
+            )
+        })
+        .collect()
+}
+
+fn derive_trip_segments(
+    trip: &TripWithStationsAndTimetables,
+    source_snapshot: String,
+    source_snapshot_captured_at: DateTime<Utc>,
+    attribution: DatasetAttribution,
+    display_names: &DisplayNames,
+) -> RouteSegments {
+    let mut stations: Vec<&TripStationWithTimetable> =
+        trip.stations_on_route_with_timetables.iter().collect();
+    stations.sort_by_key(|station| station.station.stop_number);
+
+    let route_shape = trip.route_details.route_shape.as_ref();
+    let distance_source = match is_usable_route_shape(route_shape) {
+        true => DistanceSource::RouteShape,
+        false => DistanceSource::StraightLine,
+    };
+
+    let stations_on_route: Vec<StationOnRoute> = stations
+        .iter()
+        .map(|station| station.station.clone())
+        .collect();
+    let distances = distances_along_route(&stations_on_route, route_shape);
+
+    let segments = stations
+        .windows(2)
+        .zip(distances.windows(2))
+        .map(|(segment_stations, segment_distances)| {
+            let (from_station, to_station) = (segment_stations[0], segment_stations[1]);
+            // Distances along a shape can decrease slightly if a station lies before
+            // its predecessor's projection.
+            let distance_in_meters = (segment_distances[1] - segment_distances[0]).max(0.0);
+
+            RouteSegment {
+                from_station_code: from_station.station.station_code.clone(),
+                to_station_code: to_station.station.station_code.clone(),
+                distance_in_meters,
+                scheduled_travel_time: scheduled_travel_time(
+                    from_station,
+                    to_station,
+                    distance_in_meters,
+                ),
+            }
+        })
+        .collect();
+
+    RouteSegments {
+        source_snapshot,
+        source_snapshot_captured_at,
+        attribution,
+        route: trip.route_details.route.clone(),
+        trip_id: trip.route_details.trip_id.clone(),
+        name: display_names.display_name(&trip.route_details.name),
+        distance_source,
+        total_distance_in_meters: distances.last().copied().unwrap_or_default(),
+        segments,
+    }
+}
+
+fn scheduled_travel_time(
+    from_station: &TripStationWithTimetable,
+    to_station: &TripStationWithTimetable,
+    distance_in_meters: f64,
+) -> Option<ScheduledTravelTime> {
+    let mut travel_times = travel_times_in_minutes(
+        &from_station.timetable.timetable,
+        &to_station.timetable.timetable,
+    )?;
+    travel_times.sort_unstable();
+
+    let median_minutes = travel_times[travel_times.len() / 2];
+    let median_speed_in_kilometers_per_hour = match median_minutes {
+        0 => None,
+        _ => Some(distance_in_meters / 1000.0 / (median_minutes as f64 / 60.0)),
+    };
+
+    Some(ScheduledTravelTime {
+        number_of_trips: travel_times.len(),
+        minimum_minutes: travel_times[0],
+        median_minutes,
+        maximum_minutes: travel_times[travel_times.len() - 1],
+        median_speed_in_kilometers_per_hour,
+        is_interpolated: from_station.timetable_is_interpolated
+            || to_station.timetable_is_interpolated,
+    })
+}
+
+/// Pairs up the timetable entries of two consecutive stations and returns the travel time
+/// of each bus. Returns `None` if the timetables can't be paired up (they are empty, have
+/// a different number of entries or a bus would arrive to the next station before the previous one).
+fn travel_times_in_minutes(
+    from_entries: &[TimetableEntry],
+    to_entries: &[TimetableEntry],
+) -> Option<Vec<u32>> {
+    if from_entries.is_empty() || from_entries.len() != to_entries.len() {
+        return None;
+    }
+
+    from_entries
+        .iter()
+        .zip(to_entries.iter())
+        .map(|(from_entry, to_entry)| {
+            let from_minute_of_day = from_entry.hour as u32 * 60 + from_entry.minute as u32;
+            let to_minute_of_day = to_entry.hour as u32 * 60 + to_entry.minute as u32;
+
+            to_minute_of_day.checked_sub(from_minute_of_day)
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(times: &[(u8, u8)]) -> Vec<TimetableEntry> {
+        times
+            .iter()
+            .map(|(hour, minute)| TimetableEntry::new(*hour, *minute).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn pair_up_travel_times() {
+        assert_eq!(
+            travel_times_in_minutes(
+                &entries(&[(5, 58), (6, 30)]),
+                &entries(&[(6, 1), (6, 32)])
+            ),
+            Some(vec![3, 2])
+        );
+
+        assert_eq!(
+            travel_times_in_minutes(&entries(&[(5, 58)]), &entries(&[])),
+            None
+        );
+        assert_eq!(
+            travel_times_in_minutes(&entries(&[(6, 10)]), &entries(&[(6, 5)])),
+            None
+        );
+    }
+}
@@ -0,0 +1,472 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use chrono::NaiveDate;
+use memmap2::MmapOptions;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+use crate::{
+    recorder::formats::{
+        decompressing_reader,
+        SnapshotCompression,
+        SnapshotLoadError,
+        SnapshotSerialization,
+        TripWithStationsAndTimetables,
+    },
+    storage::locate_snapshot_contents,
+};
+
+
+/// Which route entries of a snapshot are parsed by [`read_sampled_route_snapshot`].
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SamplingStrategy {
+    /// Parse every `n`-th route entry (starting with the first one).
+    EveryNth(usize),
+
+    /// Parse each route entry with probability `fraction`.
+    /// The same seed always selects the same entries.
+    Random { fraction: f64, seed: u64 },
+}
+
+impl SamplingStrategy {
+    /// Samples roughly `fraction` (from `0.0` to `1.0`) of all route entries: randomly if a seed
+    /// is given, otherwise every n-th entry (e.g. every 10th for a fraction of `0.1`).
+    pub fn from_fraction(fraction: f64, seed: Option<u64>) -> Self {
+        match seed {
+            Some(seed) => Self::Random { fraction, seed },
+            None => Self::EveryNth((1.0 / fraction).round().max(1.0) as usize),
+        }
+    }
+
+    fn sampler(self) -> Sampler {
+        match self {
+            SamplingStrategy::EveryNth(n) => Sampler::EveryNth { n: n.max(1) },
+            SamplingStrategy::Random { fraction, seed } => Sampler::Random {
+                fraction,
+                state: seed,
+            },
+        }
+    }
+}
+
+enum Sampler {
+    EveryNth { n: usize },
+    Random { fraction: f64, state: u64 },
+}
+
+impl Sampler {
+    fn includes(&mut self, index: usize) -> bool {
+        match self {
+            Sampler::EveryNth { n } => index % *n == 0,
+            Sampler::Random { fraction, state } => {
+                // SplitMix64: good enough for sampling and doesn't need another dependency.
+                *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                let mut z = *state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                z ^= z >> 31;
+
+                ((z >> 11) as f64 / (1u64 << 53) as f64) < *fraction
+            }
+        }
+    }
+}
+
+
+/// A route snapshot of which only some route entries were parsed.
+#[derive(Debug, Clone)]
+pub struct SampledRouteSnapshot {
+    pub service_date: Option<NaiveDate>,
+
+    /// Number of all route entries in the snapshot, including the skipped ones.
+    pub total_routes: usize,
+
+    pub sampled_routes: Vec<TripWithStationsAndTimetables>,
+}
+
+/// Reads a route snapshot from a file, only parsing the route entries selected by `strategy`.
+/// The other entries are skipped without being loaded into memory.
+///
+/// Like [`load_snapshot`](crate::recorder::formats::load_snapshot), this supports
+/// uncompressed, gzip and zstd files with JSON or MessagePack contents.
+pub fn read_sampled_route_snapshot(
+    file_path: &Path,
+    strategy: SamplingStrategy,
+) -> Result<SampledRouteSnapshot, SnapshotLoadError> {
+    let location = locate_snapshot_contents(file_path)?;
+
+    let mut file = File::open(&location.file_path)?;
+    file.seek(SeekFrom::Start(location.offset))?;
+    let (compression, reader) = decompressing_reader(BufReader::new(file.take(location.length)))?;
+
+    read_sampled_route_snapshot_from_reader(reader, compression, strategy)
+}
+
+/// Like [`read_sampled_route_snapshot`], but memory-maps the file instead of reading it
+/// through a buffer. Uncompressed JSON is then parsed directly from the mapped bytes,
+/// which avoids copying the (mostly skipped) route entries into a read buffer.
+pub fn read_sampled_route_snapshot_memory_mapped(
+    file_path: &Path,
+    strategy: SamplingStrategy,
+) -> Result<SampledRouteSnapshot, SnapshotLoadError> {
+    let location = locate_snapshot_contents(file_path)?;
+    let file = File::open(&location.file_path)?;
+
+    // SAFETY: The mapping is only read from. Snapshot files are written once and never
+    // modified in place (fsck moves corrupt ones instead), so the contents don't change
+    // while mapped. Truncating the file concurrently would still be undefined behaviour,
+    // which is why this read path is opt-in.
+    let mapped_file = unsafe {
+        MmapOptions::new()
+            .offset(location.offset)
+            .len(location.length as usize)
+            .map(&file)?
+    };
+
+    read_sampled_route_snapshot_from_slice(&mapped_file, strategy)
+}
+
+/// Only uncompressed JSON is parsed directly from `contents`,
+/// everything else is read like in [`read_sampled_route_snapshot`].
+fn read_sampled_route_snapshot_from_slice(
+    contents: &[u8],
+    strategy: SamplingStrategy,
+) -> Result<SampledRouteSnapshot, SnapshotLoadError> {
+    let compression = SnapshotCompression::detect(contents);
+
+    let mut remaining_contents = contents;
+    if compression == SnapshotCompression::None
+        && SnapshotSerialization::detect(&mut remaining_contents, compression)?
+            == SnapshotSerialization::Json
+    {
+        return deserialize_sampled_route_snapshot(
+            &mut serde_json::Deserializer::from_slice(remaining_contents),
+            SnapshotSerialization::Json,
+            strategy,
+        );
+    }
+
+    let (compression, reader) = decompressing_reader(contents)?;
+    read_sampled_route_snapshot_from_reader(reader, compression, strategy)
+}
+
+fn read_sampled_route_snapshot_from_reader<R>(
+    mut reader: R,
+    compression: SnapshotCompression,
+    strategy: SamplingStrategy,
+) -> Result<SampledRouteSnapshot, SnapshotLoadError>
+where
+    R: BufRead,
+{
+    match SnapshotSerialization::detect(&mut reader, compression)? {
+        SnapshotSerialization::Json => deserialize_sampled_route_snapshot(
+            &mut serde_json::Deserializer::from_reader(reader),
+            SnapshotSerialization::Json,
+            strategy,
+        ),
+        SnapshotSerialization::MessagePack => deserialize_sampled_route_snapshot(
+            &mut rmp_serde::Deserializer::new(reader),
+            SnapshotSerialization::MessagePack,
+            strategy,
+        ),
+    }
+}
+
+fn deserialize_sampled_route_snapshot<'de, D>(
+    deserializer: D,
+    serialization: SnapshotSerialization,
+    strategy: SamplingStrategy,
+) -> Result<SampledRouteSnapshot, SnapshotLoadError>
+where
+    D: Deserializer<'de>,
+{
+    let seed = SampledRouteSnapshotSeed {
+        sampler: strategy.sampler(),
+    };
+
+    seed.deserialize(deserializer)
+        .map_err(|error| SnapshotLoadError::decoding(serialization, error))?
+        .ok_or(SnapshotLoadError::UnknownSnapshotKind)
+}
+
+
+/// Deserializes the top-level snapshot object, returning `None` if it has no `routes` field.
+struct SampledRouteSnapshotSeed {
+    sampler: Sampler,
+}
+
+impl<'de> DeserializeSeed<'de> for SampledRouteSnapshotSeed {
+    type Value = Option<SampledRouteSnapshot>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SampledRouteSnapshotSeed {
+    type Value = Option<SampledRouteSnapshot>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a route snapshot")
+    }
+
+    fn visit_map<A>(mut self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut service_date = None;
+        let mut routes = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "service_date" => service_date = Some(map.next_value::<NaiveDate>()?),
+                "routes" => {
+                    routes = Some(map.next_value_seed(SampledRoutesSeed {
+                        sampler: &mut self.sampler,
+                    })?)
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(routes.map(
+            |(total_routes, sampled_routes)| SampledRouteSnapshot {
+                service_date,
+                total_routes,
+                sampled_routes,
+            },
+        ))
+    }
+}
+
+/// Deserializes the `routes` array into the number of all entries and the sampled entries.
+struct SampledRoutesSeed<'s> {
+    sampler: &'s mut Sampler,
+}
+
+impl<'de, 's> DeserializeSeed<'de> for SampledRoutesSeed<'s> {
+    type Value = (usize, Vec<TripWithStationsAndTimetables>);
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 's> Visitor<'de> for SampledRoutesSeed<'s> {
+    type Value = (usize, Vec<TripWithStationsAndTimetables>);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of routes")
+    }
+
+    fn visit_seq<A>(self, mut sequence: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut total_routes = 0;
+        let mut sampled_routes = Vec::new();
+
+        loop {
+            let is_included = self.sampler.includes(total_routes);
+
+            let has_next_element = match is_included {
+                true => match sequence.next_element::<TripWithStationsAndTimetables>()? {
+                    Some(route) => {
+                        sampled_routes.push(route);
+                        true
+                    }
+                    None => false,
+                },
+                false => sequence.next_element::<IgnoredAny>()?.is_some(),
+            };
+
+            if !has_next_element {
+                break;
+            }
+
+            total_routes += 1;
+        }
+
+        if total_routes > 0 && sampled_routes.is_empty() {
+            return Err(de::Error::custom(
+                "sample contains no routes, use a larger sample fraction",
+            ));
+        }
+
+        Ok((total_routes, sampled_routes))
+    }
+}
+
+
+/// Approximate statistics of a route snapshot, extrapolated from a sample of its routes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteSnapshotStatistics {
+    pub total_routes: usize,
+    pub sampled_routes: usize,
+
+    pub mean_stations_per_trip: f64,
+    pub mean_departures_per_trip: f64,
+
+    /// `mean_departures_per_trip` multiplied by the number of all routes.
+    pub estimated_total_departures: f64,
+
+    /// Share of stations (from `0.0` to `1.0`) whose timetable was interpolated.
+    pub interpolated_station_share: f64,
+}
+
+impl RouteSnapshotStatistics {
+    pub fn from_sample(sample: &SampledRouteSnapshot) -> Self {
+        let sampled_routes = sample.sampled_routes.len();
+
+        let mut number_of_stations = 0;
+        let mut number_of_interpolated_stations = 0;
+        let mut number_of_departures = 0;
+
+        for route in &sample.sampled_routes {
+            for station in &route.stations_on_route_with_timetables {
+                number_of_stations += 1;
+                number_of_departures += station.timetable.timetable.len();
+
+                if station.timetable_is_interpolated {
+                    number_of_interpolated_stations += 1;
+                }
+            }
+        }
+
+        let per_route = |count: usize| match sampled_routes {
+            0 => 0.0,
+            _ => count as f64 / sampled_routes as f64,
+        };
+        let mean_departures_per_trip = per_route(number_of_departures);
+
+        Self {
+            total_routes: sample.total_routes,
+            sampled_routes,
+            mean_stations_per_trip: per_route(number_of_stations),
+            mean_departures_per_trip,
+            estimated_total_departures: mean_departures_per_trip * sample.total_routes as f64,
+            interpolated_station_share: match number_of_stations {
+                0 => 0.0,
+                _ => number_of_interpolated_stations as f64 / number_of_stations as f64,
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routes_json(number_of_routes: usize) -> String {
+        let route = r#"{
+            "captured_at": "1699272000.0",
+            "route_details": {
+                "route_id": "a", "trip_id": "b", "internal_trip_id": 1, "route": "3G",
+                "name": "BEŽIGRAD - GROSUPLJE", "short_name": null
+            },
+            "stations_on_route_with_timetables": []
+        }"#;
+
+        format!(
+            r#"{{"captured_at": "1699272000.0", "service_date": "2023-11-06",
+                "service_day_type": "weekday", "routes": [{}]}}"#,
+            vec![route; number_of_routes].join(",")
+        )
+    }
+
+    #[test]
+    fn sample_every_nth_route() {
+        let json = routes_json(25);
+
+        let sample = read_sampled_route_snapshot_from_reader(
+            json.as_bytes(),
+            SnapshotCompression::None,
+            SamplingStrategy::from_fraction(0.1, None),
+        )
+        .unwrap();
+
+        assert_eq!(sample.total_routes, 25);
+        assert_eq!(sample.sampled_routes.len(), 3);
+        assert_eq!(
+            sample.service_date,
+            NaiveDate::from_ymd_opt(2023, 11, 6)
+        );
+    }
+
+    #[test]
+    fn random_sample_is_reproducible() {
+        let json = routes_json(200);
+        let strategy = SamplingStrategy::from_fraction(0.25, Some(42));
+
+        let first = read_sampled_route_snapshot_from_reader(
+            json.as_bytes(),
+            SnapshotCompression::None,
+            strategy,
+        )
+        .unwrap();
+        let second = read_sampled_route_snapshot_from_reader(
+            json.as_bytes(),
+            SnapshotCompression::None,
+            strategy,
+        )
+        .unwrap();
+
+        assert_eq!(first.total_routes, 200);
+        assert_eq!(
+            first.sampled_routes.len(),
+            second.sampled_routes.len()
+        );
+        assert!((20..=80).contains(&first.sampled_routes.len()));
+    }
+
+    #[test]
+    fn sample_from_slice_matches_reader() {
+        let json = routes_json(25);
+        let strategy = SamplingStrategy::from_fraction(0.25, Some(7));
+
+        let from_reader = read_sampled_route_snapshot_from_reader(
+            json.as_bytes(),
+            SnapshotCompression::None,
+            strategy,
+        )
+        .unwrap();
+        let from_slice = read_sampled_route_snapshot_from_slice(json.as_bytes(), strategy).unwrap();
+
+        assert_eq!(from_slice.total_routes, from_reader.total_routes);
+        assert_eq!(
+            from_slice.sampled_routes.len(),
+            from_reader.sampled_routes.len()
+        );
+        assert_eq!(from_slice.service_date, from_reader.service_date);
+    }
+
+    #[test]
+    fn sample_compressed_and_message_pack_snapshots_from_slice() {
+        let json = routes_json(25);
+        let strategy = SamplingStrategy::from_fraction(0.1, None);
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let message_pack = rmp_serde::to_vec_named(&value).unwrap();
+        let zstd_json = zstd::stream::encode_all(json.as_bytes(), 0).unwrap();
+
+        for contents in [message_pack, zstd_json] {
+            let sample = read_sampled_route_snapshot_from_slice(&contents, strategy).unwrap();
+
+            assert_eq!(sample.total_routes, 25);
+            assert_eq!(sample.sampled_routes.len(), 3);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use chrono::{DateTime, Utc};
+use miette::{miette, IntoDiagnostic, Result};
+use qrcode::{render::svg, QrCode};
+use serde::Serialize;
+use serde_with::{serde_as, TimestampSecondsWithFrac};
+
+use super::attribution::DatasetAttribution;
+use crate::{
+    api::{BusRoute, GeographicalLocation, StationCode},
+    display_names::DisplayNames,
+    recorder::formats::{AllStationsSnapshot, StationDetailsWithBusesAndTimetables},
+};
+
+/// Name of the station poster file (`station_posters.json`) in the output directory.
+pub const STATION_POSTERS_FILE_NAME: &str = "station_posters.json";
+
+/// Placeholder for the station code in station URL templates.
+const STATION_CODE_PLACEHOLDER: &str = "{station_code}";
+
+
+/// Data for printable station posters: what stops at each station
+/// and a link to the station (e.g. in the visualization).
+#[serde_as]
+#[derive(Serialize, Debug, Clone)]
+pub struct StationPosters {
+    /// File name of the station snapshot the posters were derived from.
+    pub source_snapshot: String,
+
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub source_snapshot_captured_at: DateTime<Utc>,
+
+    pub attribution: DatasetAttribution,
+
+    pub stations: Vec<StationPoster>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StationPoster {
+    pub station_code: StationCode,
+
+    /// Example: `ŽELEZNA`.
+    pub name: String,
+
+    pub location: GeographicalLocation,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub district: Option<String>,
+
+    /// Routes (and their destinations) that stop at the station, ordered by route number.
+    pub routes: Vec<PosterRoute>,
+
+    /// Link to the station, rendered from the URL template.
+    pub url: String,
+
+    /// Path of the QR code of `url`, relative to the poster file. Only set if QR codes were rendered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub qr_code_file: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PosterRoute {
+    pub route: BusRoute,
+
+    /// Example: `BEŽIGRAD`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+}
+
+
+/// A URL with a `{station_code}` placeholder, e.g. `https://example.com/?station={station_code}`.
+#[derive(Debug, Clone)]
+pub struct StationUrlTemplate(String);
+
+impl StationUrlTemplate {
+    pub fn parse(template: String) -> Result<Self> {
+        if !template.contains(STATION_CODE_PLACEHOLDER) {
+            return Err(miette!(
+                "Station URL template \"{}\" does not contain {}.",
+                template,
+                STATION_CODE_PLACEHOLDER
+            ));
+        }
+
+        Ok(Self(template))
+    }
+
+    pub fn render(&self, station_code: &StationCode) -> String {
+        self.0
+            .replace(STATION_CODE_PLACEHOLDER, station_code.as_ref())
+    }
+}
+
+
+/// Derives poster data for every station in the snapshot, ordered by station code.
+pub fn derive_station_posters(
+    station_snapshot: &AllStationsSnapshot,
+    source_snapshot: String,
+    attribution: DatasetAttribution,
+    url_template: &StationUrlTemplate,
+    display_names: &DisplayNames,
+) -> StationPosters {
+    let mut stations: Vec<_> = station_snapshot
+        .station_details
+        .iter()
+        .map(|station| StationPoster {
+            station_code: station.station_code.clone(),
+            name: display_names.display_name(&station.name),
+            location: station.location,
+            district: station.district.clone(),
+            routes: routes_on_station(station, display_names),
+            url: url_template.render(&station.station_code),
+            qr_code_file: None,
+        })
+        .collect();
+
+    stations.sort_by(|first, second| {
+        first
+            .station_code
+            .as_ref()
+            .cmp(second.station_code.as_ref())
+    });
+
+    StationPosters {
+        source_snapshot,
+        source_snapshot_captured_at: station_snapshot.captured_at,
+        attribution,
+        stations,
+    }
+}
+
+fn routes_on_station(
+    station: &StationDetailsWithBusesAndTimetables,
+    display_names: &DisplayNames,
+) -> Vec<PosterRoute> {
+    let mut routes: Vec<_> = station
+        .trips_on_station
+        .iter()
+        .map(|trip| PosterRoute {
+            route: trip.route.clone(),
+            destination: trip
+                .short_trip_name
+                .as_deref()
+                .map(|short_trip_name| display_names.display_name(short_trip_name)),
+        })
+        .collect();
+
+    routes.sort_by_cached_key(|route| {
+        (
+            route.route.base_route_number,
+            route.route.to_string(),
+            route.destination.clone(),
+        )
+    });
+    routes.dedup();
+
+    routes
+}
+
+/// Renders a QR code of the URL as an SVG image.
+pub fn render_qr_code_svg(url: &str) -> Result<String> {
+    let qr_code = QrCode::new(url.as_bytes()).into_diagnostic()?;
+
+    Ok(qr_code
+        .render::<svg::Color>()
+        .min_dimensions(256, 256)
+        .build())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_station_urls() {
+        let url_template = StationUrlTemplate::parse(String::from(
+            "https://example.com/?station={station_code}",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            url_template.render(&StationCode::new("600011")),
+            "https://example.com/?station=600011"
+        );
+        assert!(StationUrlTemplate::parse(String::from("https://example.com/")).is_err());
+
+        let svg = render_qr_code_svg("https://example.com/?station=600011").unwrap();
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.contains("<svg"));
+    }
+}
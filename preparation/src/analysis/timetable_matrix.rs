@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+
+use crate::{
+    api::{timetable::TripTimetable, BusRoute, StationCode},
+    display_names::DisplayNames,
+    recorder::formats::StationDetailsWithBusesAndTimetables,
+};
+
+/// Separator of the departure minutes in a matrix cell, e.g. `05 25 45`.
+const MINUTE_SEPARATOR: &str = " ";
+
+
+/// All departures from a single station on a single day, as an hour × trip matrix
+/// whose cells hold the departure minutes (e.g. `05 25 45`).
+#[derive(Debug, Clone)]
+pub struct TimetableMatrix {
+    pub station_code: StationCode,
+
+    /// Example: `BAVARSKI DVOR`.
+    pub station_name: String,
+
+    pub service_date: NaiveDate,
+
+    /// Trips departing from the station, ordered by route number.
+    pub columns: Vec<TimetableMatrixColumn>,
+
+    /// Every hour from the first to the last departure (including hours without departures).
+    pub rows: Vec<TimetableMatrixRow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimetableMatrixColumn {
+    pub route: BusRoute,
+
+    /// Example: `RUDNIK`.
+    pub destination: String,
+}
+
+impl TimetableMatrixColumn {
+    /// Example: `3G RUDNIK`.
+    pub fn label(&self) -> String {
+        format!("{} {}", self.route, self.destination)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimetableMatrixRow {
+    pub hour: u8,
+
+    /// Sorted departure minutes of each column (in the same order as the columns).
+    pub minutes: Vec<Vec<u8>>,
+}
+
+
+/// Builds the departure matrix of a station from its recorded timetables.
+/// Trips of the same route and destination (e.g. from different route groups) share a column.
+pub fn derive_timetable_matrix(
+    station: &StationDetailsWithBusesAndTimetables,
+    service_date: NaiveDate,
+    display_names: &DisplayNames,
+) -> TimetableMatrix {
+    let mut trips: Vec<&TripTimetable> = station
+        .timetables
+        .iter()
+        .flat_map(|route_group| route_group.trip_timetables.iter())
+        .collect();
+    trips.sort_by_cached_key(|trip| {
+        (
+            trip.route.base_route_number,
+            trip.route.to_string(),
+            trip.trip_name.clone(),
+        )
+    });
+
+    let mut columns: Vec<TimetableMatrixColumn> = Vec::new();
+    // Departure minutes by hour, per column.
+    let mut departures: Vec<BTreeMap<u8, Vec<u8>>> = Vec::new();
+
+    for trip in trips {
+        let column = TimetableMatrixColumn {
+            route: trip.route.clone(),
+            destination: display_names
+                .display_name(trip.short_trip_name.as_deref().unwrap_or(&trip.trip_name)),
+        };
+
+        let column_index = match columns.iter().position(|existing| *existing == column) {
+            Some(column_index) => column_index,
+            None => {
+                columns.push(column);
+                departures.push(BTreeMap::new());
+                columns.len() - 1
+            }
+        };
+
+        for entry in &trip.timetable {
+            departures[column_index]
+                .entry(entry.hour)
+                .or_default()
+                .push(entry.minute);
+        }
+    }
+
+    let first_hour = departures
+        .iter()
+        .filter_map(|minutes_by_hour| minutes_by_hour.keys().next())
+        .min()
+        .copied();
+    let last_hour = departures
+        .iter()
+        .filter_map(|minutes_by_hour| minutes_by_hour.keys().next_back())
+        .max()
+        .copied();
+
+    let rows = match (first_hour, last_hour) {
+        (Some(first_hour), Some(last_hour)) => (first_hour..=last_hour)
+            .map(|hour| TimetableMatrixRow {
+                hour,
+                minutes: departures
+                    .iter()
+                    .map(|minutes_by_hour| {
+                        let mut minutes = minutes_by_hour.get(&hour).cloned().unwrap_or_default();
+                        minutes.sort_unstable();
+                        minutes.dedup();
+                        minutes
+                    })
+                    .collect(),
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    TimetableMatrix {
+        station_code: station.station_code.clone(),
+        station_name: display_names.display_name(&station.name),
+        service_date,
+        columns,
+        rows,
+    }
+}
+
+impl TimetableMatrix {
+    /// Renders the matrix as CSV: a header row (`hour` and the column labels),
+    /// followed by one row per hour.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+
+        let header = std::iter::once(String::from("hour"))
+            .chain(self.columns.iter().map(TimetableMatrixColumn::label));
+        push_csv_record(&mut csv, header);
+
+        for row in &self.rows {
+            let record = std::iter::once(row.hour.to_string())
+                .chain(row.minutes.iter().map(|minutes| format_minutes(minutes)));
+            push_csv_record(&mut csv, record);
+        }
+
+        csv
+    }
+
+    /// Renders the matrix as a plain-text table with aligned columns, for printing to a terminal.
+    pub fn to_console_table(&self) -> String {
+        let header: Vec<String> = std::iter::once(String::from("hour"))
+            .chain(self.columns.iter().map(TimetableMatrixColumn::label))
+            .collect();
+
+        let rows: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                std::iter::once(format!("{:02}", row.hour))
+                    .chain(row.minutes.iter().map(|minutes| format_minutes(minutes)))
+                    .collect()
+            })
+            .collect();
+
+        let column_widths: Vec<usize> = (0..header.len())
+            .map(|column_index| {
+                std::iter::once(&header)
+                    .chain(rows.iter())
+                    .map(|cells| cells[column_index].chars().count())
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let format_line = |cells: &[String]| {
+            cells
+                .iter()
+                .zip(column_widths.iter())
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join(" | ")
+                .trim_end()
+                .to_string()
+        };
+
+        let separator = column_widths
+            .iter()
+            .map(|width| "-".repeat(*width))
+            .collect::<Vec<_>>()
+            .join("-+-");
+
+        let mut table = format!(
+            "{} ({}), {}\n\n",
+            self.station_name, self.station_code, self.service_date
+        );
+        table.push_str(&format_line(&header));
+        table.push('\n');
+        table.push_str(&separator);
+        table.push('\n');
+
+        for row in &rows {
+            table.push_str(&format_line(row));
+            table.push('\n');
+        }
+
+        table
+    }
+}
+
+
+fn format_minutes(minutes: &[u8]) -> String {
+    minutes
+        .iter()
+        .map(|minute| format!("{:02}", minute))
+        .collect::<Vec<_>>()
+        .join(MINUTE_SEPARATOR)
+}
+
+/// Appends a single CSV record, quoting fields that contain commas, quotes or line breaks.
+fn push_csv_record<I>(csv: &mut String, fields: I)
+where
+    I: Iterator<Item = String>,
+{
+    let fields: Vec<String> = fields
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect();
+
+    csv.push_str(&fields.join(","));
+    csv.push('\n');
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        test_fixtures::timetable,
+        timetable::RouteGroupTimetable,
+        BaseBusRoute,
+        GeographicalLocation,
+    };
+
+    #[test]
+    fn build_hour_by_route_matrix() {
+        let station = StationDetailsWithBusesAndTimetables {
+            captured_at: None,
+            station_code: StationCode::new("600011"),
+            stable_id: None,
+            internal_station_id: 1,
+            name: String::from("BAVARSKI DVOR"),
+            location: GeographicalLocation::new(46.06, 14.50),
+            district: None,
+            trips_on_station: Vec::new(),
+            timetables: vec![
+                RouteGroupTimetable {
+                    route_group_name: BaseBusRoute::new_from_number(6),
+                    trip_timetables: vec![timetable("6B", "CENTER - ČRNUČE, \"NOVO\"", &[(7, 10)])],
+                    skipped_trips: Vec::new(),
+                },
+                RouteGroupTimetable {
+                    route_group_name: BaseBusRoute::new_from_number(1),
+                    trip_timetables: vec![timetable(
+                        "1",
+                        "CENTER - VIŽMARJE",
+                        &[(5, 45), (5, 5), (7, 25)],
+                    )],
+                    skipped_trips: Vec::new(),
+                },
+            ],
+        };
+
+        let matrix = derive_timetable_matrix(
+            &station,
+            NaiveDate::from_ymd_opt(2023, 11, 6).unwrap(),
+            &DisplayNames::default(),
+        );
+
+        assert_eq!(
+            matrix
+                .columns
+                .iter()
+                .map(TimetableMatrixColumn::label)
+                .collect::<Vec<_>>(),
+            vec!["1 VIŽMARJE", "6B ČRNUČE, \"NOVO\""]
+        );
+        assert_eq!(
+            matrix.rows.iter().map(|row| row.hour).collect::<Vec<_>>(),
+            vec![5, 6, 7]
+        );
+
+        assert_eq!(
+            matrix.to_csv(),
+            "hour,1 VIŽMARJE,\"6B ČRNUČE, \"\"NOVO\"\"\"\n\
+             5,05 45,\n\
+             6,,\n\
+             7,25,10\n"
+        );
+    }
+}
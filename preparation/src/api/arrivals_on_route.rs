@@ -1,13 +1,23 @@
 #![allow(dead_code)]
 
 use miette::{miette, Result};
-use reqwest::{Client, StatusCode};
+use reqwest::StatusCode;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 use url::Url;
 
 use super::{
-    errors::{FullUrlConstructionError, LppApiFetchError},
+    client::LppApiClient,
+    errors::{FullUrlConstructionError, LppApiEndpoint, LppApiFetchError},
+    serde_util::{
+        bool_or_int,
+        bool_or_int_schema,
+        int_or_string,
+        int_or_string_schema,
+        string_or_int,
+        string_or_int_schema,
+    },
     BusRoute,
     GeographicalLocation,
     RouteId,
@@ -20,19 +30,27 @@ use crate::configuration::LppApiConfiguration;
  * RAW RESPONSE SCHEMAS
  */
 
-#[derive(Serialize, Deserialize, Clone)]
-struct RawArrivalsOnRouteResponse {
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub(super) struct RawArrivalsOnRouteResponse {
+    #[serde(deserialize_with = "bool_or_int")]
+    #[schemars(schema_with = "bool_or_int_schema")]
     success: bool,
+
+    /// Error message, usually only set if `success` is `false`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
     data: Vec<RawStationArrivalDetails>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 struct RawStationArrivalDetails {
     /// Unique internal station identifier.
     ///
     /// Example: `3307`.
     ///
     /// LPP documentation: "Integer ID of station".
+    #[serde(deserialize_with = "int_or_string")]
+    #[schemars(schema_with = "int_or_string_schema")]
     station_int_id: i32,
 
     /// Station name.
@@ -47,6 +65,8 @@ struct RawStationArrivalDetails {
     /// Example: `201011`.
     ///
     /// LPP documentation: "Destination of route (direction)".
+    #[serde(deserialize_with = "string_or_int")]
+    #[schemars(schema_with = "string_or_int_schema")]
     station_code: String,
 
     /// Stop number (starts at 1 and is incremented for
@@ -55,6 +75,8 @@ struct RawStationArrivalDetails {
     /// Example: `1`.
     ///
     /// LPP documentation: "Order of stations, 1 is starting station".
+    #[serde(deserialize_with = "int_or_string")]
+    #[schemars(schema_with = "int_or_string_schema")]
     order_no: i32,
 
     /// Geographical latitude of the bus station.
@@ -80,7 +102,7 @@ struct RawStationArrivalDetails {
     arrivals: Vec<RawArrivalData>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 struct RawArrivalData {
     /// Unique route identifier belonging to this trip.
     ///
@@ -91,6 +113,8 @@ struct RawArrivalData {
     /// locked behind authentication).
     ///
     /// LPP documentation: "ID of the vehicle".
+    #[serde(deserialize_with = "string_or_int")]
+    #[schemars(schema_with = "string_or_int_schema")]
     vehicle_id: String,
 
     /// Type of prediction in `eta_min`:
@@ -101,11 +125,15 @@ struct RawArrivalData {
     ///
     /// LPP documentation: "A type of arrival: (0 - predicted,
     /// 1 - scheduled, 2 - approaching station (prihod), 3 - detour (obvoz))"-
+    #[serde(deserialize_with = "int_or_string")]
+    #[schemars(schema_with = "int_or_string_schema")]
     r#type: i32,
 
     /// Estimated time of arrival in minutes.
     ///
     /// LPP documentation: "Estimated time of arrival in minutes".
+    #[serde(deserialize_with = "int_or_string")]
+    #[schemars(schema_with = "int_or_string_schema")]
     eta_min: i32,
 
     /// Name of the route.
@@ -126,6 +154,8 @@ struct RawArrivalData {
     /// - `1` if heading to garage
     ///
     /// LPP documentation: "0 if normal route, 1 if vehicle is headed to garage".
+    #[serde(deserialize_with = "int_or_string")]
+    #[schemars(schema_with = "int_or_string_schema")]
     depot: i32,
 }
 
@@ -315,7 +345,7 @@ where
 
 pub async fn fetch_arrivals_on_route<T>(
     api_configuration: &LppApiConfiguration,
-    client: &Client,
+    client: &LppApiClient,
     trip_id: T,
 ) -> Result<Vec<StationArrivalDetails>, LppApiFetchError>
 where
@@ -323,21 +353,21 @@ where
 {
     let full_url = build_arrivals_on_route_url(api_configuration, trip_id)?;
 
-    let response = client
-        .get(full_url)
-        .header("User-Agent", &api_configuration.user_agent)
-        .send()
-        .await
-        .map_err(LppApiFetchError::RequestError)?;
+    let response = client.get(full_url).await?;
 
 
     let response_status = response.status();
     if response_status.is_client_error() {
         if response_status.eq(&StatusCode::TOO_MANY_REQUESTS) {
+            let retry_after = response.retry_after();
+
             warn!(
+                retry_after = ?retry_after,
                 "LPP API is rate-limiting us! Got 429 Too Many Requests \
                 (was trying to fetch arrivals on route)."
             );
+
+            return Err(LppApiFetchError::RateLimited { retry_after });
         }
 
         return Err(LppApiFetchError::ClientHTTPError(response_status));
@@ -346,15 +376,14 @@ where
     }
 
 
-    let response_raw_json = response
-        .json::<RawArrivalsOnRouteResponse>()
-        .await
-        .map_err(LppApiFetchError::ResponseDecodingError)?;
+    let response_raw_json =
+        response.json::<RawArrivalsOnRouteResponse>(LppApiEndpoint::ArrivalsOnRoute)?;
 
     if !response_raw_json.success {
-        return Err(LppApiFetchError::APIResponseNotSuccessful {
-            reason: String::from("success field is false"),
-        });
+        return Err(LppApiFetchError::unsuccessful_response(
+            LppApiEndpoint::ArrivalsOnRoute,
+            response_raw_json.message,
+        ));
     }
 
 
@@ -0,0 +1,595 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    Client,
+    StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::{sync::OnceCell, time::Instant};
+use tracing::{debug, trace};
+use url::Url;
+
+use super::{
+    concurrency::{AdaptiveConcurrencyLimiter, ConcurrencyPolicy, ConcurrencyStatistics},
+    errors::{LppApiEndpoint, LppApiFetchError},
+    rate_limit::{RateLimitPolicy, TokenBucketRateLimiter},
+    schema_drift::{find_schema_drift, SchemaDriftSampler},
+    serde_util::bool_or_int,
+};
+use crate::{pause::PauseSwitch, telemetry};
+
+
+/// A fully-received HTTP response from the LPP API.
+#[derive(Debug)]
+pub struct LppApiResponse {
+    url: Url,
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+
+    /// Set if this response was sampled for a schema drift check.
+    schema_drift_sampler: Option<Arc<SchemaDriftSampler>>,
+}
+
+impl LppApiResponse {
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// How long the LPP API asked us to wait before retrying, parsed from the `Retry-After` header
+    /// (if present and valid).
+    pub fn retry_after(&self) -> Option<Duration> {
+        let retry_after = self.headers().get(RETRY_AFTER)?.to_str().ok()?;
+
+        parse_retry_after(retry_after, Utc::now())
+    }
+
+    /// Deserializes the response body (of a request to `endpoint`) as JSON.
+    ///
+    /// Unsuccessful responses often leave out `data`, so if the body doesn't match `T`,
+    /// but has `success` set to `false`, this returns
+    /// [`LppApiFetchError::APIResponseNotSuccessful`] (with the response's error message).
+    ///
+    /// If this response was sampled for a schema drift check, the fields of the response are
+    /// also compared with the fields of `T` and any differences are logged.
+    pub fn json<T>(&self, endpoint: LppApiEndpoint) -> Result<T, LppApiFetchError>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        let Some(schema_drift_sampler) = &self.schema_drift_sampler else {
+            return serde_json::from_slice(&self.body)
+                .map_err(|error| self.decoding_error(endpoint, error));
+        };
+
+        let observed_value: serde_json::Value =
+            serde_json::from_slice(&self.body).map_err(LppApiFetchError::ResponseDecodingError)?;
+        let parsed_response: T = serde_json::from_value(observed_value.clone())
+            .map_err(|error| self.decoding_error(endpoint, error))?;
+
+        // This can only fail for types that can't be represented in JSON,
+        // but we've just deserialized the response from JSON.
+        if let Ok(expected_value) = serde_json::to_value(&parsed_response) {
+            schema_drift_sampler.report(
+                self.url.path(),
+                find_schema_drift(&observed_value, &expected_value),
+            );
+        }
+
+        Ok(parsed_response)
+    }
+
+    fn decoding_error(
+        &self,
+        endpoint: LppApiEndpoint,
+        error: serde_json::Error,
+    ) -> LppApiFetchError {
+        match serde_json::from_slice::<RawResponseEnvelope>(&self.body) {
+            Ok(envelope) if !envelope.success => {
+                LppApiFetchError::unsuccessful_response(endpoint, envelope.message)
+            }
+            _ => LppApiFetchError::ResponseDecodingError(error),
+        }
+    }
+}
+
+/// The fields all LPP API responses share, used to recognize unsuccessful responses
+/// that don't match the response schema of their endpoint.
+#[derive(Deserialize)]
+struct RawResponseEnvelope {
+    #[serde(deserialize_with = "bool_or_int")]
+    success: bool,
+
+    #[serde(default)]
+    message: Option<String>,
+}
+
+
+/// Throttling applied to requests right after startup, when the recorder
+/// would otherwise send a burst of requests that can trip LPP's rate limiting.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WarmupPolicy {
+    /// How long after the client is created the warm-up phase lasts.
+    pub duration: Duration,
+
+    /// Minimum time between the starts of two requests during the warm-up phase.
+    pub request_spacing: Duration,
+}
+
+/// Spaces out requests while the warm-up phase lasts.
+#[derive(Debug)]
+struct WarmupPacer {
+    policy: WarmupPolicy,
+    started_at: Instant,
+    next_request_allowed_at: tokio::sync::Mutex<Instant>,
+}
+
+impl WarmupPacer {
+    fn new(policy: WarmupPolicy) -> Self {
+        let started_at = Instant::now();
+
+        Self {
+            policy,
+            started_at,
+            next_request_allowed_at: tokio::sync::Mutex::new(started_at),
+        }
+    }
+
+    /// Waits until the next request may be sent.
+    async fn wait_for_turn(&self) {
+        // Holding the lock while sleeping makes requests take turns.
+        let mut next_request_allowed_at = self.next_request_allowed_at.lock().await;
+
+        if self.started_at.elapsed() >= self.policy.duration {
+            return;
+        }
+
+        if *next_request_allowed_at > Instant::now() {
+            trace!("Delaying request, the API client is still warming up.");
+            tokio::time::sleep_until(*next_request_allowed_at).await;
+        }
+
+        *next_request_allowed_at = Instant::now() + self.policy.request_spacing;
+    }
+}
+
+
+type SharedResponse = Result<Arc<LppApiResponse>, Arc<reqwest::Error>>;
+type InFlightRequests = Arc<Mutex<HashMap<Url, Arc<OnceCell<SharedResponse>>>>>;
+
+/// HTTP client shared by all LPP API fetching functions.
+///
+/// Identical GET requests that are in flight at the same time are coalesced:
+/// only the first one is actually sent, while the others wait for and share its response.
+///
+/// If a [`WarmupPolicy`] is given, requests are spaced out for a while after the client is created.
+/// If a [`ConcurrencyPolicy`] is given, the number of requests in flight at the same time is
+/// limited, and the limit adapts to LPP's rate limiting (see [`AdaptiveConcurrencyLimiter`]).
+/// If a [`RateLimitPolicy`] is given, requests are started at no more than its rate
+/// (see [`TokenBucketRateLimiter`]). Clones of the client share the same budget.
+/// If a schema drift sample rate is given, that fraction of responses is checked for fields
+/// that don't match our response schemas (see [`SchemaDriftSampler`]).
+/// If a [`PauseSwitch`] is given, no new requests are sent while recording is paused.
+#[derive(Clone, Debug)]
+pub struct LppApiClient {
+    http_client: Client,
+    in_flight_requests: InFlightRequests,
+    warmup_pacer: Option<Arc<WarmupPacer>>,
+    concurrency_limiter: Option<Arc<AdaptiveConcurrencyLimiter>>,
+    rate_limiter: Option<Arc<TokenBucketRateLimiter>>,
+    schema_drift_sampler: Option<Arc<SchemaDriftSampler>>,
+    pause_switch: Option<PauseSwitch>,
+}
+
+impl LppApiClient {
+    pub fn new(
+        http_client: Client,
+        warmup_policy: Option<WarmupPolicy>,
+        concurrency_policy: Option<ConcurrencyPolicy>,
+        rate_limit_policy: Option<RateLimitPolicy>,
+        schema_drift_sample_rate: Option<f64>,
+        pause_switch: Option<PauseSwitch>,
+    ) -> Self {
+        if let Some(policy) = &warmup_policy {
+            debug!(
+                warmup_duration = ?policy.duration,
+                request_spacing = ?policy.request_spacing,
+                "API client will space out requests while warming up."
+            );
+        }
+
+        if let Some(policy) = &concurrency_policy {
+            debug!(
+                initial_concurrency_limit = policy.initial_limit,
+                min_concurrency_limit = policy.min_limit,
+                max_concurrency_limit = policy.max_limit,
+                "API client will adapt its concurrency limit to rate limiting."
+            );
+        }
+
+        if let Some(policy) = &rate_limit_policy {
+            debug!(
+                requests_per_second = policy.requests_per_second,
+                burst = policy.burst,
+                "API client will limit the rate of requests."
+            );
+        }
+
+        Self {
+            http_client,
+            in_flight_requests: Arc::new(Mutex::new(HashMap::new())),
+            warmup_pacer: warmup_policy.map(|policy| Arc::new(WarmupPacer::new(policy))),
+            concurrency_limiter: concurrency_policy
+                .map(|policy| Arc::new(AdaptiveConcurrencyLimiter::new(policy))),
+            rate_limiter: rate_limit_policy
+                .map(|policy| Arc::new(TokenBucketRateLimiter::new(policy))),
+            schema_drift_sampler: schema_drift_sample_rate
+                .map(|sample_rate| Arc::new(SchemaDriftSampler::new(sample_rate))),
+            pause_switch,
+        }
+    }
+
+    /// Returns the current concurrency limit and how it changed,
+    /// or `None` if the client has no concurrency limit.
+    pub fn concurrency_statistics(&self) -> Option<ConcurrencyStatistics> {
+        self.concurrency_limiter
+            .as_ref()
+            .map(|limiter| limiter.statistics())
+    }
+
+    /// Performs a GET request and receives the entire response body.
+    ///
+    /// If an identical request is already in flight, this waits for
+    /// its response instead of sending a new request.
+    pub async fn get(&self, url: Url) -> Result<Arc<LppApiResponse>, LppApiFetchError> {
+        let (request_cell, in_flight_guard) = {
+            // PANIC SAFETY: The lock is never held across an await point or a panicking call.
+            let mut in_flight_requests = self.in_flight_requests.lock().unwrap();
+
+            match in_flight_requests.get(&url) {
+                Some(existing_request_cell) => {
+                    trace!(url = %url, "Coalescing request with an identical in-flight request.");
+                    (existing_request_cell.clone(), None)
+                }
+                None => {
+                    let request_cell = Arc::new(OnceCell::new());
+                    in_flight_requests.insert(url.clone(), request_cell.clone());
+
+                    let guard = InFlightRequestGuard {
+                        in_flight_requests: self.in_flight_requests.clone(),
+                        url: url.clone(),
+                        request_cell: request_cell.clone(),
+                    };
+
+                    (request_cell, Some(guard))
+                }
+            }
+        };
+
+        let response = request_cell
+            .get_or_init(|| self.send_get_request(url))
+            .await
+            .clone();
+
+        // The request is complete, so new identical requests must be sent anew.
+        drop(in_flight_guard);
+
+        response.map_err(LppApiFetchError::RequestError)
+    }
+
+    async fn send_get_request(&self, url: Url) -> SharedResponse {
+        if let Some(pause_switch) = &self.pause_switch {
+            if pause_switch.is_paused() {
+                debug!(url = %url, "Holding back request until recording is resumed.");
+                pause_switch.wait_until_resumed().await;
+            }
+        }
+
+        if let Some(warmup_pacer) = &self.warmup_pacer {
+            warmup_pacer.wait_for_turn().await;
+        }
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+
+        let concurrency_permit = match &self.concurrency_limiter {
+            Some(concurrency_limiter) => Some(concurrency_limiter.acquire().await),
+            None => None,
+        };
+
+        let response = self.http_client.get(url.clone()).send().await?;
+
+        let status = response.status();
+        telemetry::record_api_response(status);
+        if let Some(concurrency_permit) = &concurrency_permit {
+            concurrency_permit.record_response(status);
+        }
+
+        let headers = response.headers().clone();
+        let body = response.bytes().await?.to_vec();
+
+        let schema_drift_sampler = self
+            .schema_drift_sampler
+            .as_ref()
+            .filter(|sampler| sampler.should_check_next_response())
+            .cloned();
+
+        Ok(Arc::new(LppApiResponse {
+            url,
+            status,
+            headers,
+            body,
+            schema_drift_sampler,
+        }))
+    }
+}
+
+
+/// Parses the value of a `Retry-After` header, which is either a number of seconds
+/// or an HTTP date (e.g. `Wed, 21 Oct 2015 07:28:00 GMT`). Dates in the past yield a zero duration.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let retry_at = DateTime::parse_from_rfc2822(value).ok()?;
+
+    Some(
+        retry_at
+            .with_timezone(&Utc)
+            .signed_duration_since(now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+
+/// Removes a request from the in-flight request map when the request that was
+/// sent first completes (or is cancelled).
+struct InFlightRequestGuard {
+    in_flight_requests: InFlightRequests,
+    url: Url,
+    request_cell: Arc<OnceCell<SharedResponse>>,
+}
+
+impl Drop for InFlightRequestGuard {
+    fn drop(&mut self) {
+        let Ok(mut in_flight_requests) = self.in_flight_requests.lock() else {
+            return;
+        };
+
+        let is_same_request = in_flight_requests
+            .get(&self.url)
+            .map(|request_cell| Arc::ptr_eq(request_cell, &self.request_cell))
+            .unwrap_or(false);
+
+        if is_same_request {
+            in_flight_requests.remove(&self.url);
+        }
+    }
+}
+
+
+/// A stand-in for the LPP API in tests of code that issues requests.
+#[cfg(test)]
+pub(crate) mod stub_server {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use url::Url;
+
+    /// Paths (with the query) of all requests the stub server received, in order.
+    pub type ReceivedRequests = Arc<Mutex<Vec<String>>>;
+
+    /// Starts a minimal HTTP server that responds to each request with the status line
+    /// (e.g. `200 OK`) and JSON body returned by `respond`, which is given the request path
+    /// (with the query) and the number of identical requests received before it.
+    /// Responses carry a `Retry-After: 0` header, so rate-limited requests are retried right away.
+    ///
+    /// Returns the server's base URL and the requests it receives.
+    pub async fn start_stub_server<F>(respond: F) -> (Url, ReceivedRequests)
+    where
+        F: Fn(&str, usize) -> (&'static str, String) + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let requests: ReceivedRequests = Arc::new(Mutex::new(Vec::new()));
+        let server_requests = requests.clone();
+        let respond = Arc::new(respond);
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let requests = server_requests.clone();
+                let respond = respond.clone();
+
+                tokio::spawn(async move {
+                    let mut request_buffer = [0u8; 4096];
+                    let request_length = stream.read(&mut request_buffer).await.unwrap();
+                    let request = String::from_utf8_lossy(&request_buffer[..request_length]);
+                    let request_path = request.split(' ').nth(1).unwrap().to_string();
+
+                    let previous_identical_requests = {
+                        let mut requests = requests.lock().unwrap();
+                        requests.push(request_path.clone());
+                        requests.iter().filter(|path| **path == request_path).count() - 1
+                    };
+
+                    let (status, body) = respond(&request_path, previous_identical_requests);
+
+                    let response = format!(
+                        "HTTP/1.1 {}\r\ncontent-type: application/json\r\nretry-after: 0\r\n\
+                        content-length: {}\r\nconnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+
+        (
+            Url::parse(&format!("http://{}/", address)).unwrap(),
+            requests,
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Starts a minimal HTTP server that responds to every request with `{"success":true}`
+    /// after a short delay. Returns the server's base URL and a request counter.
+    async fn start_counting_server() -> (Url, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let request_counter = Arc::new(AtomicUsize::new(0));
+        let server_request_counter = request_counter.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let request_counter = server_request_counter.clone();
+
+                tokio::spawn(async move {
+                    let mut request_buffer = [0u8; 4096];
+                    let _ = stream.read(&mut request_buffer).await.unwrap();
+                    request_counter.fetch_add(1, Ordering::SeqCst);
+
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+
+                    let body = r#"{"success":true}"#;
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                        content-length: {}\r\nconnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+
+        (
+            Url::parse(&format!("http://{}/", address)).unwrap(),
+            request_counter,
+        )
+    }
+
+    #[tokio::test]
+    async fn coalesces_identical_in_flight_requests() {
+        let (base_url, request_counter) = start_counting_server().await;
+        let client = LppApiClient::new(Client::new(), None, None, None, None, None);
+
+        let url = base_url.join("station/station-details").unwrap();
+        let (first, second, third) = tokio::join!(
+            client.get(url.clone()),
+            client.get(url.clone()),
+            client.get(url.clone()),
+        );
+
+        assert_eq!(first.unwrap().status(), StatusCode::OK);
+        assert_eq!(second.unwrap().status(), StatusCode::OK);
+        assert_eq!(third.unwrap().status(), StatusCode::OK);
+        assert_eq!(request_counter.load(Ordering::SeqCst), 1);
+
+        // Once the request has completed, an identical request must be sent again.
+        client.get(url).await.unwrap();
+        assert_eq!(request_counter.load(Ordering::SeqCst), 2);
+        assert!(client.in_flight_requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn does_not_coalesce_different_requests() {
+        let (base_url, request_counter) = start_counting_server().await;
+        let client = LppApiClient::new(Client::new(), None, None, None, None, None);
+
+        let (first, second) = tokio::join!(
+            client.get(base_url.join("route/routes").unwrap()),
+            client.get(base_url.join("station/station-details").unwrap()),
+        );
+
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(request_counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn spaces_out_requests_while_warming_up() {
+        let (base_url, request_counter) = start_counting_server().await;
+        let client = LppApiClient::new(
+            Client::new(),
+            Some(WarmupPolicy {
+                duration: Duration::from_secs(60),
+                request_spacing: Duration::from_millis(300),
+            }),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let started_at = Instant::now();
+        let (first, second, third) = tokio::join!(
+            client.get(base_url.join("route/routes").unwrap()),
+            client.get(base_url.join("station/station-details").unwrap()),
+            client.get(base_url.join("station/timetable").unwrap()),
+        );
+
+        first.unwrap();
+        second.unwrap();
+        third.unwrap();
+        assert_eq!(request_counter.load(Ordering::SeqCst), 3);
+
+        // The third request may only start 600 ms after the first one.
+        assert!(started_at.elapsed() >= Duration::from_millis(600));
+    }
+
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+}
@@ -1,9 +1,21 @@
-use std::fmt::Display;
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+        RwLock,
+    },
+};
 
 use serde::{de::Error, Deserialize, Serialize};
 use unicode_segmentation::UnicodeSegmentation;
 
-use super::errors::RouteNameParseError;
+use super::errors::{RouteNameParseError, StationCodeParseError};
+
+/// Mean radius of the Earth, which is treated as a sphere when computing distances.
+pub const MEAN_EARTH_RADIUS_IN_METERS: f64 = 6_371_008.8;
+
 
 /// Represents a location on the Earth in the
 /// [geographical coordinate system](https://en.wikipedia.org/wiki/Geographic_coordinate_system).
@@ -28,6 +40,20 @@ impl GeographicalLocation {
             longitude,
         }
     }
+
+    /// Computes the great-circle distance between two locations in meters
+    /// (using the [haversine formula](https://en.wikipedia.org/wiki/Haversine_formula)).
+    pub fn distance_in_meters_to(&self, other: &GeographicalLocation) -> f64 {
+        let latitude_delta = (other.latitude - self.latitude).to_radians();
+        let longitude_delta = (other.longitude - self.longitude).to_radians();
+
+        let haversine = (latitude_delta / 2.0).sin().powi(2)
+            + self.latitude.to_radians().cos()
+                * other.latitude.to_radians().cos()
+                * (longitude_delta / 2.0).sin().powi(2);
+
+        2.0 * MEAN_EARTH_RADIUS_IN_METERS * haversine.sqrt().asin()
+    }
 }
 
 
@@ -45,6 +71,11 @@ impl GeographicalLocation {
 pub struct StationCode(String);
 
 impl StationCode {
+    /// Number of digits in a station code (e.g. `600011`).
+    pub const LENGTH: usize = 6;
+
+    /// Creates a station code without validating it. Prefer [`Self::parse`]
+    /// for codes that come from the LPP API.
     #[inline]
     pub fn new<S>(id: S) -> Self
     where
@@ -52,6 +83,24 @@ impl StationCode {
     {
         Self(id.into())
     }
+
+    /// Creates a station code, checking that it consists of exactly six ASCII digits.
+    pub fn parse<S>(id: S) -> Result<Self, StationCodeParseError>
+    where
+        S: Into<String>,
+    {
+        let id = id.into();
+
+        if Self::is_valid_code(&id) {
+            Ok(Self(id))
+        } else {
+            Err(StationCodeParseError::new(id))
+        }
+    }
+
+    fn is_valid_code(id: &str) -> bool {
+        id.len() == Self::LENGTH && id.bytes().all(|byte| byte.is_ascii_digit())
+    }
 }
 
 impl From<String> for StationCode {
@@ -147,6 +196,12 @@ impl BusRoute {
             return Err(RouteNameParseError::new(full_route_name));
         }
 
+        let parse_route_number = |route_number: &str| {
+            route_number
+                .parse::<u32>()
+                .map_err(|_| RouteNameParseError::new(full_route_name.as_str()))
+        };
+
         let mut full_route_name = full_route_name.as_str();
 
         // The route name *can* be this for example: `56 DOBROVA - ŠOLSKA`.
@@ -190,10 +245,10 @@ impl BusRoute {
                                     first_non_numeric, partial_additional_information
                                 ),
                             ),
-                            None => unreachable!(),
+                            None => return Err(RouteNameParseError::new(full_route_name)),
                         };
 
-                    let route_number = route_number_str.parse::<u32>().unwrap();
+                    let route_number = parse_route_number(route_number_str)?;
 
                     Ok((
                         prefix,
@@ -208,10 +263,10 @@ impl BusRoute {
                             Some((route_number_str, additional_information)) => {
                                 (route_number_str, additional_information)
                             }
-                            None => unreachable!(),
+                            None => return Err(RouteNameParseError::new(full_route_name)),
                         };
 
-                    let route_number = route_number_str.parse::<u32>().unwrap();
+                    let route_number = parse_route_number(route_number_str)?;
 
                     let additional_information = if additional_information.is_empty() {
                         None
@@ -227,7 +282,7 @@ impl BusRoute {
                     ))
                 }
             } else {
-                let route_number = full_route_name.parse::<u32>().unwrap();
+                let route_number = parse_route_number(full_route_name)?;
 
                 // There is no suffix nor any additional information.
                 Ok((prefix, route_number, None, None))
@@ -235,19 +290,33 @@ impl BusRoute {
         }
     }
 
+    /// Parses a route name (e.g. `N3B`). Successfully parsed names are cached
+    /// (see [`route_name_cache_statistics`]), as the same few hundred route names
+    /// are parsed over and over again while capturing or loading a snapshot.
     pub fn from_route_name<S>(route_name: S) -> Result<Self, RouteNameParseError>
     where
         S: Into<String>,
     {
+        let route_name = route_name.into();
+
+        let cache = ROUTE_NAME_CACHE.get_or_init(RouteNameCache::default);
+        if let Some(route) = cache.get(&route_name) {
+            return Ok(route);
+        }
+
         let (prefix, base_route_number, suffix, additional_info) =
-            Self::components_from_route_name(route_name.into())?;
+            Self::components_from_route_name(route_name.clone())?;
 
-        Ok(Self {
+        let route = Self {
             prefix,
             base_route_number,
             suffix,
             additional_info,
-        })
+        };
+
+        cache.insert(route_name, &route);
+
+        Ok(route)
     }
 
     #[inline]
@@ -282,7 +351,7 @@ impl Display for BusRoute {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{prefix}{base}{suffix}{potential_additional_info}",
+            "{prefix}{base}{suffix}{additional_info}",
             prefix = match self.prefix.as_ref() {
                 Some(prefix) => prefix,
                 None => "",
@@ -292,9 +361,11 @@ impl Display for BusRoute {
                 Some(suffix) => suffix,
                 None => "",
             },
-            potential_additional_info = match self.additional_info.as_ref() {
-                Some(info) => format!(" {}", info),
-                None => "".to_string(),
+            // Additional information keeps its leading whitespace (if any) when parsed,
+            // so it is written out as-is for the route name to parse back into the same route.
+            additional_info = match self.additional_info.as_ref() {
+                Some(info) => info,
+                None => "",
             }
         )
     }
@@ -321,11 +392,67 @@ impl<'de> Deserialize<'de> for BusRoute {
 
 
 
+/// Maximum number of distinct route names kept in the route name cache. LPP only has a few
+/// hundred, so this only stops unexpected input from growing the cache without bounds.
+const MAX_CACHED_ROUTE_NAMES: usize = 4096;
+
+/// Process-wide cache of parsed route names, see [`BusRoute::from_route_name`].
+static ROUTE_NAME_CACHE: OnceLock<RouteNameCache> = OnceLock::new();
+
+#[derive(Default, Debug)]
+struct RouteNameCache {
+    routes: RwLock<HashMap<String, BusRoute>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl RouteNameCache {
+    fn get(&self, route_name: &str) -> Option<BusRoute> {
+        // PANIC SAFETY: The lock is never held across a panicking call.
+        let route = self.routes.read().unwrap().get(route_name).cloned();
+
+        match route.is_some() {
+            true => self.hits.fetch_add(1, Ordering::Relaxed),
+            false => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+
+        route
+    }
+
+    fn insert(&self, route_name: String, route: &BusRoute) {
+        // PANIC SAFETY: The lock is never held across a panicking call.
+        let mut routes = self.routes.write().unwrap();
+
+        if routes.len() < MAX_CACHED_ROUTE_NAMES {
+            routes.insert(route_name, route.clone());
+        }
+    }
+}
+
+/// How many route name parses (since the start of the process) were answered from the cache.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct RouteNameCacheStatistics {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+pub fn route_name_cache_statistics() -> RouteNameCacheStatistics {
+    ROUTE_NAME_CACHE
+        .get()
+        .map(|cache| RouteNameCacheStatistics {
+            hits: cache.hits.load(Ordering::Relaxed),
+            misses: cache.misses.load(Ordering::Relaxed),
+        })
+        .unwrap_or_default()
+}
+
+
+
 /// Represents a bus route name
 /// *without a prefix or suffix*, i.e. the "base" route.
 ///
 /// Example: `11`.
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub struct BaseBusRoute(u32);
 
 impl BaseBusRoute {
@@ -430,6 +557,12 @@ impl From<String> for VehicleId {
     }
 }
 
+impl Display for VehicleId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 
 
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
@@ -452,6 +585,12 @@ impl From<String> for TripId {
     }
 }
 
+impl Display for TripId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 impl AsRef<str> for TripId {
     fn as_ref(&self) -> &str {
         &self.0
@@ -462,8 +601,22 @@ impl AsRef<str> for TripId {
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
 
+    #[test]
+    fn parse_station_codes() {
+        assert_eq!(
+            StationCode::parse("600011").unwrap(),
+            StationCode::new("600011")
+        );
+
+        for invalid_code in ["", "60001", "6000111", "60001a", " 600011", "６00011"] {
+            assert!(StationCode::parse(invalid_code).is_err());
+        }
+    }
+
     #[test]
     fn parse_bus_route_correctly() {
         assert_eq!(
@@ -511,4 +664,68 @@ mod tests {
             BusRoute::from_components(None, 76, None, Some("(GROS.)".to_string())),
         );
     }
+
+    #[test]
+    fn reject_malformed_bus_routes() {
+        for route_name in ["", "N", "AB", "A+", "99999999999", "N99999999999B"] {
+            assert!(
+                BusRoute::from_route_name(route_name).is_err(),
+                "{route_name:?} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn cache_parsed_route_names() {
+        let cache = RouteNameCache::default();
+        let route = BusRoute::from_components(
+            Some(String::from("N")),
+            3,
+            Some(String::from("B")),
+            None,
+        );
+
+        assert_eq!(cache.get("N3B"), None);
+        cache.insert(String::from("N3B"), &route);
+        assert_eq!(cache.get("N3B"), Some(route.clone()));
+
+        assert_eq!(cache.hits.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.misses.load(Ordering::Relaxed), 1);
+
+        // Parsing the same name again (now from the process-wide cache) gives the same route.
+        assert_eq!(BusRoute::from_route_name("N3B").unwrap(), route);
+        assert_eq!(BusRoute::from_route_name("N3B").unwrap(), route);
+    }
+
+    /// Routes whose name is unambiguous, i.e. that parse back into the same route.
+    fn valid_bus_route() -> impl Strategy<Value = BusRoute> {
+        (
+            proptest::option::of("[A-ZČŠŽ]"),
+            any::<u32>(),
+            proptest::option::of("[A-ZČŠŽ]"),
+            proptest::option::of("[ (][A-ZČŠŽ .()-]{0,20}"),
+        )
+            .prop_map(
+                |(prefix, base_route_number, suffix, additional_info)| {
+                    BusRoute::from_components(prefix, base_route_number, suffix, additional_info)
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn parsing_arbitrary_route_names_never_panics(route_name in any::<String>()) {
+            let _ = BusRoute::from_route_name(route_name);
+        }
+
+        #[test]
+        fn parsing_route_like_names_never_panics(route_name in "[A-Z+( ]?[0-9]{0,12}[A-Z+( ]?.{0,8}") {
+            let _ = BusRoute::from_route_name(route_name);
+        }
+
+        #[test]
+        fn bus_route_display_round_trips(route in valid_bus_route()) {
+            prop_assert_eq!(BusRoute::from_route_name(route.to_string()).unwrap(), route);
+        }
+    }
 }
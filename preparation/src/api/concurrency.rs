@@ -0,0 +1,259 @@
+//! Adaptive limit on the number of requests the [`LppApiClient`](super::client::LppApiClient)
+//! has in flight at the same time.
+//!
+//! The limit follows an AIMD (additive increase, multiplicative decrease) scheme: after a full
+//! window of clean responses (as many as the current limit) it is raised by one, and as soon as
+//! the API responds with `429 Too Many Requests` it is halved.
+
+use std::sync::{Arc, Mutex};
+
+use reqwest::StatusCode;
+use tokio::sync::Notify;
+use tracing::{debug, trace, warn};
+
+
+/// Bounds of the adaptive concurrency limit.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ConcurrencyPolicy {
+    /// Limit the client starts with.
+    pub initial_limit: usize,
+
+    /// The limit is never halved below this.
+    pub min_limit: usize,
+
+    /// The limit is never raised above this.
+    pub max_limit: usize,
+}
+
+
+/// Current limit and how it changed since the client was created.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ConcurrencyStatistics {
+    pub current_limit: usize,
+
+    /// Highest limit reached so far.
+    pub peak_limit: usize,
+
+    /// Number of `429 Too Many Requests` responses.
+    pub rate_limited_responses: u64,
+
+    pub limit_increases: u64,
+    pub limit_decreases: u64,
+}
+
+
+#[derive(Debug)]
+struct LimiterState {
+    policy: ConcurrencyPolicy,
+    limit: usize,
+    in_flight: usize,
+
+    /// Clean responses since the limit last changed.
+    clean_responses: usize,
+
+    /// Incremented on every decrease, so that the `429` responses of requests sent before
+    /// the decrease (i.e. of the same burst) don't halve the limit again.
+    decrease_epoch: u64,
+
+    statistics: ConcurrencyStatistics,
+}
+
+/// Keeps the number of requests in flight under an AIMD-controlled limit.
+#[derive(Debug)]
+pub struct AdaptiveConcurrencyLimiter {
+    state: Mutex<LimiterState>,
+    limit_changed_or_permit_released: Notify,
+}
+
+impl AdaptiveConcurrencyLimiter {
+    pub fn new(policy: ConcurrencyPolicy) -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                policy,
+                limit: policy.initial_limit,
+                in_flight: 0,
+                clean_responses: 0,
+                decrease_epoch: 0,
+                statistics: ConcurrencyStatistics {
+                    current_limit: policy.initial_limit,
+                    peak_limit: policy.initial_limit,
+                    rate_limited_responses: 0,
+                    limit_increases: 0,
+                    limit_decreases: 0,
+                },
+            }),
+            limit_changed_or_permit_released: Notify::new(),
+        }
+    }
+
+    /// Waits until another request may be sent. The request counts as in flight
+    /// until the returned permit is dropped.
+    pub async fn acquire(self: &Arc<Self>) -> ConcurrencyPermit {
+        loop {
+            // Created before checking the state, so a release in between is not missed.
+            let notified = self.limit_changed_or_permit_released.notified();
+
+            {
+                // PANIC SAFETY: The lock is never held across an await point or a panicking call.
+                let mut state = self.state.lock().unwrap();
+
+                if state.in_flight < state.limit {
+                    state.in_flight += 1;
+
+                    return ConcurrencyPermit {
+                        limiter: self.clone(),
+                        decrease_epoch: state.decrease_epoch,
+                    };
+                }
+
+                trace!(
+                    concurrency_limit = state.limit,
+                    "Delaying request, too many requests are in flight."
+                );
+            }
+
+            notified.await;
+        }
+    }
+
+    pub fn statistics(&self) -> ConcurrencyStatistics {
+        // PANIC SAFETY: The lock is never held across an await point or a panicking call.
+        self.state.lock().unwrap().statistics
+    }
+
+    fn record_response(&self, status: StatusCode, permit_decrease_epoch: u64) {
+        // PANIC SAFETY: The lock is never held across an await point or a panicking call.
+        let mut state = self.state.lock().unwrap();
+
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            state.statistics.rate_limited_responses += 1;
+            state.clean_responses = 0;
+
+            if permit_decrease_epoch != state.decrease_epoch {
+                return;
+            }
+
+            let previous_limit = state.limit;
+            state.limit = (state.limit / 2).max(state.policy.min_limit);
+            state.decrease_epoch += 1;
+            state.statistics.current_limit = state.limit;
+
+            if state.limit < previous_limit {
+                state.statistics.limit_decreases += 1;
+
+                warn!(
+                    previous_concurrency_limit = previous_limit,
+                    concurrency_limit = state.limit,
+                    "LPP API is rate-limiting us, halving the API client's concurrency limit."
+                );
+            }
+
+            return;
+        }
+
+        state.clean_responses += 1;
+
+        if state.clean_responses >= state.limit && state.limit < state.policy.max_limit {
+            state.limit += 1;
+            state.clean_responses = 0;
+            state.statistics.current_limit = state.limit;
+            state.statistics.peak_limit = state.statistics.peak_limit.max(state.limit);
+            state.statistics.limit_increases += 1;
+
+            debug!(
+                concurrency_limit = state.limit,
+                "Responses are clean, raising the API client's concurrency limit."
+            );
+
+            drop(state);
+            self.limit_changed_or_permit_released.notify_waiters();
+        }
+    }
+
+    fn release(&self) {
+        {
+            // PANIC SAFETY: The lock is never held across an await point or a panicking call.
+            let mut state = self.state.lock().unwrap();
+            state.in_flight -= 1;
+        }
+
+        self.limit_changed_or_permit_released.notify_waiters();
+    }
+}
+
+
+/// A request that is in flight, see [`AdaptiveConcurrencyLimiter::acquire`].
+pub struct ConcurrencyPermit {
+    limiter: Arc<AdaptiveConcurrencyLimiter>,
+    decrease_epoch: u64,
+}
+
+impl ConcurrencyPermit {
+    /// Adjusts the limit according to the status of the response to this request.
+    /// Requests that failed without a response should just drop their permit.
+    pub fn record_response(&self, status: StatusCode) {
+        self.limiter.record_response(status, self.decrease_epoch);
+    }
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn raise_limit_on_clean_responses_and_halve_it_on_rate_limiting() {
+        let limiter = Arc::new(AdaptiveConcurrencyLimiter::new(
+            ConcurrencyPolicy {
+                initial_limit: 2,
+                min_limit: 1,
+                max_limit: 4,
+            },
+        ));
+
+        // A full window of clean responses (two, at a limit of two) raises the limit by one.
+        for _ in 0..2 {
+            limiter.acquire().await.record_response(StatusCode::OK);
+        }
+        assert_eq!(limiter.statistics().current_limit, 3);
+
+        // The limit can't be exceeded.
+        let permits = vec![
+            limiter.acquire().await,
+            limiter.acquire().await,
+            limiter.acquire().await,
+        ];
+        assert!(tokio::time::timeout(
+            std::time::Duration::from_millis(50),
+            limiter.acquire()
+        )
+        .await
+        .is_err());
+
+        // Requests of the same burst halve the limit only once.
+        for permit in &permits {
+            permit.record_response(StatusCode::TOO_MANY_REQUESTS);
+        }
+        drop(permits);
+
+        let statistics = limiter.statistics();
+        assert_eq!(statistics.current_limit, 1);
+        assert_eq!(statistics.peak_limit, 3);
+        assert_eq!(statistics.rate_limited_responses, 3);
+        assert_eq!(statistics.limit_increases, 1);
+        assert_eq!(statistics.limit_decreases, 1);
+
+        // Never below the minimum.
+        limiter
+            .acquire()
+            .await
+            .record_response(StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(limiter.statistics().current_limit, 1);
+    }
+}
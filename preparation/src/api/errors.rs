@@ -1,3 +1,9 @@
+use std::{
+    fmt::{self, Display},
+    sync::Arc,
+    time::Duration,
+};
+
 use miette::Diagnostic;
 use reqwest::StatusCode;
 use thiserror::Error;
@@ -18,11 +24,22 @@ pub enum LppApiFetchError {
     UrlError(#[from] FullUrlConstructionError),
 
     #[error("Failed to perform request: {0}")]
-    RequestError(reqwest::Error),
+    RequestError(Arc<reqwest::Error>),
 
-    /// This can happend when e.g. the `success` field is set to `false` in the JSON response.
-    #[error("Request was not successful: {reason}")]
-    APIResponseNotSuccessful { reason: String },
+    /// The `success` field is set to `false` in the JSON response.
+    #[error(
+        "Request to {endpoint} was not successful ({category}): {}",
+        message.as_deref().unwrap_or("no error message")
+    )]
+    APIResponseNotSuccessful {
+        endpoint: LppApiEndpoint,
+        category: UnsuccessfulResponseCategory,
+
+        /// Error message of the response, if it had one.
+        ///
+        /// Example: `No active routes on station 604021 or station-code is invalid`.
+        message: Option<String>,
+    },
 
     #[error(
         "Received response was malformed (or did the schema change?).{}",
@@ -36,11 +53,24 @@ pub enum LppApiFetchError {
     #[error("HTTP request failed with client error: {0}")]
     ClientHTTPError(StatusCode),
 
+    /// The LPP API responded with `429 Too Many Requests`.
+    #[error(
+        "HTTP request was rate-limited (429 Too Many Requests){}",
+        match retry_after {
+            Some(retry_after) => format!(", asked to retry after {:?}.", retry_after),
+            None => String::from("."),
+        }
+    )]
+    RateLimited {
+        /// How long to wait before retrying, as asked by the `Retry-After` header (if present).
+        retry_after: Option<Duration>,
+    },
+
     #[error("HTTP request failed with server error: {0}")]
     ServerHTTPError(StatusCode),
 
     #[error("Failed to decode JSON response: {0}")]
-    ResponseDecodingError(reqwest::Error),
+    ResponseDecodingError(serde_json::Error),
 }
 
 impl LppApiFetchError {
@@ -52,6 +82,133 @@ impl LppApiFetchError {
             reason: Some(reason.into()),
         }
     }
+
+    /// Builds the error of a response whose `success` field is `false`,
+    /// recognizing what went wrong from its error message.
+    pub fn unsuccessful_response(endpoint: LppApiEndpoint, message: Option<String>) -> Self {
+        Self::APIResponseNotSuccessful {
+            endpoint,
+            category: UnsuccessfulResponseCategory::recognize(endpoint, message.as_deref()),
+            message,
+        }
+    }
+}
+
+
+/// LPP API endpoints whose responses we parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LppApiEndpoint {
+    StationDetails,
+    RoutesOnStation,
+    Timetable,
+    Routes,
+    StationsOnRoute,
+    ArrivalsOnRoute,
+}
+
+impl Display for LppApiEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sub_url = match self {
+            LppApiEndpoint::StationDetails => "station/station-details",
+            LppApiEndpoint::RoutesOnStation => "station/routes-on-station",
+            LppApiEndpoint::Timetable => "station/timetable",
+            LppApiEndpoint::Routes => "route/routes",
+            LppApiEndpoint::StationsOnRoute => "route/stations-on-route",
+            LppApiEndpoint::ArrivalsOnRoute => "route/arrivals-on-route",
+        };
+
+        write!(f, "{}", sub_url)
+    }
+}
+
+/// What an unsuccessful LPP API response was about, recognized from its error message.
+/// Only the categories that match the parameters of each endpoint are recognized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsuccessfulResponseCategory {
+    /// The station exists, but no routes currently stop there. LPP reports invalid station codes
+    /// with the same message (`No active routes on station 604021 or station-code is invalid`).
+    NoActiveRoutesOnStation,
+
+    /// The requested `station-code` does not exist.
+    InvalidStationCode,
+
+    /// One of the requested `route-group-number`s does not exist.
+    InvalidRouteGroup,
+
+    /// The requested `route-id` does not exist.
+    InvalidRouteId,
+
+    /// The requested `trip-id` does not exist.
+    InvalidTripId,
+
+    /// The error message is missing or we don't recognize it.
+    Unrecognized,
+}
+
+impl UnsuccessfulResponseCategory {
+    pub fn recognize(endpoint: LppApiEndpoint, message: Option<&str>) -> Self {
+        let Some(message) = message else {
+            return Self::Unrecognized;
+        };
+
+        // Messages refer to parameters both by their query name (`station-code`) and in prose.
+        let message = message.to_lowercase().replace(['-', '_'], " ");
+        let mentions_invalid_value = [
+            "invalid",
+            "not found",
+            "unknown",
+            "does not exist",
+            "no such",
+        ]
+        .iter()
+        .any(|phrase| message.contains(phrase));
+        let mentions_invalid =
+            |parameter: &str| message.contains(parameter) && mentions_invalid_value;
+
+        let candidates: &[UnsuccessfulResponseCategory] = match endpoint {
+            LppApiEndpoint::StationDetails => &[],
+            LppApiEndpoint::RoutesOnStation => {
+                &[Self::NoActiveRoutesOnStation, Self::InvalidStationCode]
+            }
+            LppApiEndpoint::Timetable => &[
+                Self::NoActiveRoutesOnStation,
+                Self::InvalidStationCode,
+                Self::InvalidRouteGroup,
+            ],
+            LppApiEndpoint::Routes => &[Self::InvalidRouteId],
+            LppApiEndpoint::StationsOnRoute | LppApiEndpoint::ArrivalsOnRoute => {
+                &[Self::InvalidTripId]
+            }
+        };
+
+        candidates
+            .iter()
+            .copied()
+            .find(|category| match category {
+                Self::NoActiveRoutesOnStation => message.starts_with("no active routes on station"),
+                Self::InvalidStationCode => mentions_invalid("station code"),
+                Self::InvalidRouteGroup => mentions_invalid("route group"),
+                Self::InvalidRouteId => mentions_invalid("route id"),
+                Self::InvalidTripId => mentions_invalid("trip id"),
+                Self::Unrecognized => false,
+            })
+            .unwrap_or(Self::Unrecognized)
+    }
+}
+
+impl Display for UnsuccessfulResponseCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            UnsuccessfulResponseCategory::NoActiveRoutesOnStation => "no active routes on station",
+            UnsuccessfulResponseCategory::InvalidStationCode => "invalid station code",
+            UnsuccessfulResponseCategory::InvalidRouteGroup => "invalid route group",
+            UnsuccessfulResponseCategory::InvalidRouteId => "invalid route ID",
+            UnsuccessfulResponseCategory::InvalidTripId => "invalid trip ID",
+            UnsuccessfulResponseCategory::Unrecognized => "unrecognized error",
+        };
+
+        write!(f, "{}", description)
+    }
 }
 
 
@@ -73,6 +230,24 @@ impl RouteTimetableParseError {
 }
 
 
+#[derive(Error, Debug, Diagnostic, Clone, PartialEq, Eq)]
+#[error("Invalid station code (expected six digits): {}", station_code)]
+pub struct StationCodeParseError {
+    station_code: String,
+}
+
+impl StationCodeParseError {
+    pub fn new<S>(station_code: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            station_code: station_code.into(),
+        }
+    }
+}
+
+
 #[derive(Error, Debug, Diagnostic)]
 #[error("Invalid bus route name: {}", route_name)]
 pub struct RouteNameParseError {
@@ -89,3 +264,53 @@ impl RouteNameParseError {
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognize_unsuccessful_response_categories() {
+        for (endpoint, message, expected_category) in [
+            (
+                LppApiEndpoint::Timetable,
+                Some("No active routes on station 604021 or station-code is invalid"),
+                UnsuccessfulResponseCategory::NoActiveRoutesOnStation,
+            ),
+            (
+                LppApiEndpoint::RoutesOnStation,
+                Some("Invalid station_code"),
+                UnsuccessfulResponseCategory::InvalidStationCode,
+            ),
+            (
+                LppApiEndpoint::Timetable,
+                Some("Route group 999 not found"),
+                UnsuccessfulResponseCategory::InvalidRouteGroup,
+            ),
+            (
+                LppApiEndpoint::StationsOnRoute,
+                Some("trip-id does not exist"),
+                UnsuccessfulResponseCategory::InvalidTripId,
+            ),
+            // Categories only apply to endpoints that have the parameter.
+            (
+                LppApiEndpoint::StationDetails,
+                Some("Invalid station_code"),
+                UnsuccessfulResponseCategory::Unrecognized,
+            ),
+            (
+                LppApiEndpoint::Routes,
+                None,
+                UnsuccessfulResponseCategory::Unrecognized,
+            ),
+        ] {
+            assert_eq!(
+                UnsuccessfulResponseCategory::recognize(endpoint, message),
+                expected_category,
+                "{:?}",
+                message
+            );
+        }
+    }
+}
@@ -1,10 +1,18 @@
 pub mod arrivals_on_route;
+pub mod client;
 mod common;
+pub mod concurrency;
 pub mod errors;
+pub mod rate_limit;
+pub mod response_schemas;
 pub mod routes;
 pub mod routes_on_station;
+mod schema_drift;
+mod serde_util;
 pub mod station_details;
 pub mod stations_on_route;
+#[cfg(test)]
+pub(crate) mod test_fixtures;
 pub mod timetable;
 
 pub use common::*;
@@ -0,0 +1,106 @@
+//! Token bucket rate limit on the requests the [`LppApiClient`](super::client::LppApiClient) sends.
+//!
+//! The bucket holds up to `burst` tokens and is refilled at `requests_per_second` tokens per second.
+//! Every request takes a token before it is sent, waiting for one if the bucket is empty.
+//! Clones of a client share its bucket, so all recorder tasks using it share one request budget.
+
+use std::time::Duration;
+
+use tokio::{sync::Mutex, time::Instant};
+use tracing::trace;
+
+
+/// Sustained rate and burst size of the rate limit.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RateLimitPolicy {
+    /// Rate at which the bucket is refilled (greater than `0.0`).
+    pub requests_per_second: f64,
+
+    /// Capacity of the bucket, i.e. how many requests may be sent at once after a quiet period
+    /// (at least `1`).
+    pub burst: u32,
+}
+
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn refill(&mut self, policy: &RateLimitPolicy, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * policy.requests_per_second)
+            .min(policy.burst as f64);
+        self.refilled_at = now;
+    }
+}
+
+/// Keeps the rate of requests under a [`RateLimitPolicy`].
+#[derive(Debug)]
+pub struct TokenBucketRateLimiter {
+    policy: RateLimitPolicy,
+    bucket: Mutex<TokenBucket>,
+}
+
+impl TokenBucketRateLimiter {
+    pub fn new(policy: RateLimitPolicy) -> Self {
+        Self {
+            policy,
+            bucket: Mutex::new(TokenBucket {
+                tokens: policy.burst as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until the next request may be sent and takes its token.
+    pub async fn acquire(&self) {
+        // Holding the lock while sleeping makes requests take turns.
+        let mut bucket = self.bucket.lock().await;
+        bucket.refill(&self.policy, Instant::now());
+
+        if bucket.tokens < 1.0 {
+            let wait_duration =
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.policy.requests_per_second);
+
+            trace!(
+                wait_duration = ?wait_duration,
+                "Delaying request, the API client's rate limit has been reached."
+            );
+            tokio::time::sleep(wait_duration).await;
+
+            bucket.refill(&self.policy, Instant::now());
+        }
+
+        bucket.tokens -= 1.0;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn allow_a_burst_and_then_the_sustained_rate() {
+        let limiter = TokenBucketRateLimiter::new(RateLimitPolicy {
+            requests_per_second: 20.0,
+            burst: 2,
+        });
+
+        // A full bucket lets the burst through right away.
+        let started_at = Instant::now();
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(started_at.elapsed() < Duration::from_millis(40));
+
+        // Then the requests are spaced out by 50 ms each.
+        for _ in 0..4 {
+            limiter.acquire().await;
+        }
+        assert!(started_at.elapsed() >= Duration::from_millis(190));
+    }
+}
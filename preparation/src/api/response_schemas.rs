@@ -0,0 +1,111 @@
+use schemars::{schema::RootSchema, schema_for};
+
+use super::{
+    arrivals_on_route::RawArrivalsOnRouteResponse,
+    routes::{RawRouteWithShapeResponse, RawRoutesResponse},
+    routes_on_station::RawRoutesOnStationResponse,
+    station_details::RawStationDetailsResponse,
+    stations_on_route::RawStationsOnRouteResponse,
+    timetable::RawTimetableResponse,
+};
+
+
+/// JSON Schema of the responses we expect from a single LPP API endpoint.
+pub struct ApiResponseSchema {
+    /// Identifier of the schema, usable as a file name. Example: `route-routes-with-shape`.
+    pub name: &'static str,
+
+    /// Endpoint (relative to the base API URL) the responses come from.
+    ///
+    /// Example: `route/routes?shape=1`
+    pub endpoint: &'static str,
+
+    pub schema: RootSchema,
+}
+
+
+/// Generates the JSON Schemas of the responses of all LPP API endpoints we use,
+/// as described by the raw response structures they are parsed into.
+pub fn api_response_schemas() -> Vec<ApiResponseSchema> {
+    vec![
+        ApiResponseSchema {
+            name: "route-routes",
+            endpoint: "route/routes",
+            schema: schema_for!(RawRoutesResponse),
+        },
+        ApiResponseSchema {
+            name: "route-routes-with-shape",
+            endpoint: "route/routes?shape=1",
+            schema: schema_for!(RawRouteWithShapeResponse),
+        },
+        ApiResponseSchema {
+            name: "route-stations-on-route",
+            endpoint: "route/stations-on-route",
+            schema: schema_for!(RawStationsOnRouteResponse),
+        },
+        ApiResponseSchema {
+            name: "route-arrivals-on-route",
+            endpoint: "route/arrivals-on-route",
+            schema: schema_for!(RawArrivalsOnRouteResponse),
+        },
+        ApiResponseSchema {
+            name: "station-station-details",
+            endpoint: "station/station-details",
+            schema: schema_for!(RawStationDetailsResponse),
+        },
+        ApiResponseSchema {
+            name: "station-routes-on-station",
+            endpoint: "station/routes-on-station",
+            schema: schema_for!(RawRoutesOnStationResponse),
+        },
+        ApiResponseSchema {
+            name: "station-timetable",
+            endpoint: "station/timetable",
+            schema: schema_for!(RawTimetableResponse),
+        },
+    ]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use schemars::schema::{InstanceType, Schema, SingleOrVec};
+
+    use super::*;
+
+    #[test]
+    fn describe_leniently_parsed_fields() {
+        let schemas = api_response_schemas();
+        assert!(schemas
+            .iter()
+            .all(|schema| schema.schema.schema.object.is_some()));
+
+        let timetable_schema = schemas
+            .into_iter()
+            .find(|schema| schema.name == "station-timetable")
+            .unwrap()
+            .schema;
+
+        let Some(Schema::Object(trip_timetable)) =
+            timetable_schema.definitions.get("RawTripTimetable")
+        else {
+            panic!("RawTripTimetable is not defined in the timetable schema.");
+        };
+        let Some(Schema::Object(is_garage)) = trip_timetable
+            .object
+            .as_ref()
+            .and_then(|object| object.properties.get("is_garage"))
+        else {
+            panic!("RawTripTimetable does not have an is_garage property.");
+        };
+
+        assert_eq!(
+            is_garage.instance_type,
+            Some(SingleOrVec::Vec(vec![
+                InstanceType::Boolean,
+                InstanceType::Integer,
+                InstanceType::String
+            ]))
+        );
+    }
+}
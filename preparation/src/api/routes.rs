@@ -1,13 +1,23 @@
 #![allow(dead_code)]
 
 use miette::miette;
-use reqwest::{Client, StatusCode};
+use reqwest::StatusCode;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
 use url::Url;
 
 use super::{
-    errors::{FullUrlConstructionError, LppApiFetchError},
+    client::LppApiClient,
+    errors::{FullUrlConstructionError, LppApiEndpoint, LppApiFetchError},
+    serde_util::{
+        bool_or_int,
+        bool_or_int_schema,
+        int_or_string,
+        int_or_string_schema,
+        string_or_int,
+        string_or_int_schema,
+    },
     BusRoute,
     RouteId,
     TripId,
@@ -18,15 +28,21 @@ use crate::configuration::LppApiConfiguration;
  * RAW RESPONSE SCHEMAS
  */
 
-#[derive(Serialize, Deserialize, Clone)]
-struct RawRoutesResponse {
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub(super) struct RawRoutesResponse {
+    #[serde(deserialize_with = "bool_or_int")]
+    #[schemars(schema_with = "bool_or_int_schema")]
     success: bool,
 
+    /// Error message, usually only set if `success` is `false`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+
     /// Per-trip details for all routes.
     data: Vec<RawRouteDetails>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct RawRouteDetails {
     /// Unique route identifier. This identifies all directions of
     /// a route, e.g. bus 3G going to Bežigrad and 3G going to Grosuplje have the same `route_id`.
@@ -49,6 +65,8 @@ pub struct RawRouteDetails {
     /// LPP documentation: "Integer ID of route"
     ///
     /// Example: `3085`
+    #[serde(deserialize_with = "int_or_string")]
+    #[schemars(schema_with = "int_or_string_schema")]
     trip_int_id: i32,
 
     /// Describes the bus number (can have a one-letter suffix).
@@ -56,6 +74,8 @@ pub struct RawRouteDetails {
     /// LPP documentation: "Route group number with optional letter suffix if it exists"
     ///
     /// Example: `3G`
+    #[serde(deserialize_with = "string_or_int")]
+    #[schemars(schema_with = "string_or_int_schema")]
     route_number: String,
 
     /// Contains the full route (well, trip) name.
@@ -74,16 +94,22 @@ pub struct RawRouteDetails {
 }
 
 
-#[derive(Serialize, Deserialize, Clone)]
-struct RawRouteWithShapeResponse {
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub(super) struct RawRouteWithShapeResponse {
+    #[serde(deserialize_with = "bool_or_int")]
+    #[schemars(schema_with = "bool_or_int_schema")]
     success: bool,
 
+    /// Error message, usually only set if `success` is `false`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+
     /// A single route has more than a single trip,
     /// and these details are basically per-trip.
     data: Vec<RawRouteDetailsWithShape>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 struct RawRouteDetailsWithShape {
     /// Unique route identifier. This identifies all directions of
     /// a route, e.g. bus 3G going to Bežigrad and 3G going to Grosuplje have the same `route_id`.
@@ -106,6 +132,8 @@ struct RawRouteDetailsWithShape {
     /// LPP documentation: "Integer ID of route"
     ///
     /// Example: `3085`
+    #[serde(deserialize_with = "int_or_string")]
+    #[schemars(schema_with = "int_or_string_schema")]
     trip_int_id: i32,
 
     /// Describes the bus number (can have a one-letter suffix).
@@ -113,6 +141,8 @@ struct RawRouteDetailsWithShape {
     /// LPP documentation: "Route group number with optional letter suffix if it exists"
     ///
     /// Example: `3G`
+    #[serde(deserialize_with = "string_or_int")]
+    #[schemars(schema_with = "string_or_int_schema")]
     route_number: String,
 
     /// Contains the full route (well, trip) name.
@@ -132,7 +162,7 @@ struct RawRouteDetailsWithShape {
     geojson_shape: RawGeoJSONShape,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 struct RawGeoJSONShape {
     r#type: String,
     coordinates: Vec<[f64; 2]>,
@@ -304,7 +334,7 @@ fn build_routes_url(
 
 pub async fn fetch_all_routes(
     api_configuration: &LppApiConfiguration,
-    client: &Client,
+    client: &LppApiClient,
 ) -> Result<Vec<RouteDetails>, LppApiFetchError> {
     let full_url = build_routes_url(api_configuration, RouteRequestType::AllRoutes)?;
 
@@ -313,20 +343,20 @@ pub async fn fetch_all_routes(
         "Will fetch all routes from the LPP API."
     );
 
-    let response = client
-        .get(full_url)
-        .header("User-Agent", &api_configuration.user_agent)
-        .send()
-        .await
-        .map_err(LppApiFetchError::RequestError)?;
+    let response = client.get(full_url).await?;
 
     let response_status = response.status();
     if response_status.is_client_error() {
         if response_status.eq(&StatusCode::TOO_MANY_REQUESTS) {
+            let retry_after = response.retry_after();
+
             warn!(
+                retry_after = ?retry_after,
                 "LPP API is rate-limiting us! Got 429 Too Many Requests \
                 (was trying to fetch all routes)."
             );
+
+            return Err(LppApiFetchError::RateLimited { retry_after });
         }
 
         return Err(LppApiFetchError::ClientHTTPError(response_status));
@@ -335,15 +365,13 @@ pub async fn fetch_all_routes(
     }
 
 
-    let response_raw_json = response
-        .json::<RawRoutesResponse>()
-        .await
-        .map_err(LppApiFetchError::ResponseDecodingError)?;
+    let response_raw_json = response.json::<RawRoutesResponse>(LppApiEndpoint::Routes)?;
 
     if !response_raw_json.success {
-        return Err(LppApiFetchError::APIResponseNotSuccessful {
-            reason: String::from("success field is false"),
-        });
+        return Err(LppApiFetchError::unsuccessful_response(
+            LppApiEndpoint::Routes,
+            response_raw_json.message,
+        ));
     }
 
 
@@ -360,7 +388,7 @@ pub async fn fetch_all_routes(
 
 pub async fn fetch_single_route_with_shape<S>(
     api_configuration: &LppApiConfiguration,
-    client: &Client,
+    client: &LppApiClient,
     route_id: S,
 ) -> Result<Vec<RouteDetails>, LppApiFetchError>
 where
@@ -374,20 +402,20 @@ where
         },
     )?;
 
-    let response = client
-        .get(full_url)
-        .header("User-Agent", &api_configuration.user_agent)
-        .send()
-        .await
-        .map_err(LppApiFetchError::RequestError)?;
+    let response = client.get(full_url).await?;
 
     let response_status = response.status();
     if response_status.is_client_error() {
         if response_status.eq(&StatusCode::TOO_MANY_REQUESTS) {
+            let retry_after = response.retry_after();
+
             warn!(
+                retry_after = ?retry_after,
                 "LPP API is rate-limiting us! Got 429 Too Many Requests \
                 (was trying to fetch route with shape)."
             );
+
+            return Err(LppApiFetchError::RateLimited { retry_after });
         }
 
         return Err(LppApiFetchError::ClientHTTPError(response_status));
@@ -396,15 +424,13 @@ where
     }
 
 
-    let response_raw_json = response
-        .json::<RawRouteWithShapeResponse>()
-        .await
-        .map_err(LppApiFetchError::ResponseDecodingError)?;
+    let response_raw_json = response.json::<RawRouteWithShapeResponse>(LppApiEndpoint::Routes)?;
 
     if !response_raw_json.success {
-        return Err(LppApiFetchError::APIResponseNotSuccessful {
-            reason: String::from("success field is false"),
-        });
+        return Err(LppApiFetchError::unsuccessful_response(
+            LppApiEndpoint::Routes,
+            response_raw_json.message,
+        ));
     }
 
     let parsed_details = response_raw_json
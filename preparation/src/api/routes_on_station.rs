@@ -1,11 +1,14 @@
 use miette::Result;
-use reqwest::{Client, StatusCode};
+use reqwest::StatusCode;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
 use url::Url;
 
 use super::{
-    errors::{FullUrlConstructionError, LppApiFetchError},
+    client::LppApiClient,
+    errors::{FullUrlConstructionError, LppApiEndpoint, LppApiFetchError},
+    serde_util::{bool_or_int, bool_or_int_schema, string_or_int, string_or_int_schema},
     BusRoute,
     RouteId,
     StationCode,
@@ -19,13 +22,19 @@ use crate::configuration::LppApiConfiguration;
  * RAW RESPONSE SCHEMAS
  */
 
-#[derive(Serialize, Deserialize, Clone)]
-struct RawRoutesOnStationResponse {
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub(super) struct RawRoutesOnStationResponse {
+    #[serde(deserialize_with = "bool_or_int")]
+    #[schemars(schema_with = "bool_or_int_schema")]
     success: bool,
+
+    /// Error message, usually only set if `success` is `false`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
     data: Vec<RawRouteOnStation>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 struct RawRouteOnStation {
     /// Unique route identifier. This identifies all directions of
     /// a route, e.g. bus 3G going to Bežigrad and 3G going to Grosuplje have the same `route_id`.
@@ -48,6 +57,8 @@ struct RawRouteOnStation {
     /// LPP documentation: "Number + suffix letter of route group"
     ///
     /// Example: `3G`
+    #[serde(deserialize_with = "string_or_int")]
+    #[schemars(schema_with = "string_or_int_schema")]
     route_number: String,
 
     /// Contains a short naming for this route (well, trip).
@@ -69,6 +80,8 @@ struct RawRouteOnStation {
     /// LPP documentation: "Does this route go to depot".
     ///
     /// Example: `true`
+    #[serde(deserialize_with = "bool_or_int")]
+    #[schemars(schema_with = "bool_or_int_schema")]
     is_garage: bool,
 }
 
@@ -154,7 +167,7 @@ fn build_routes_on_station_url(
 
 pub async fn fetch_routes_on_station(
     api_configuration: &LppApiConfiguration,
-    client: &Client,
+    client: &LppApiClient,
     station_code: &StationCode,
 ) -> Result<Vec<TripOnStation>, LppApiFetchError> {
     let full_url = build_routes_on_station_url(api_configuration, station_code)?;
@@ -166,19 +179,20 @@ pub async fn fetch_routes_on_station(
     );
 
 
-    let response = client
-        .get(full_url)
-        .send()
-        .await
-        .map_err(LppApiFetchError::RequestError)?;
+    let response = client.get(full_url).await?;
 
     let response_status = response.status();
     if response_status.is_client_error() {
         if response_status.eq(&StatusCode::TOO_MANY_REQUESTS) {
+            let retry_after = response.retry_after();
+
             warn!(
+                retry_after = ?retry_after,
                 "LPP API is rate-limiting us! Got 429 Too Many Requests \
                 (was trying to fetch routes on station)."
             );
+
+            return Err(LppApiFetchError::RateLimited { retry_after });
         }
 
         return Err(LppApiFetchError::ClientHTTPError(response_status));
@@ -187,15 +201,14 @@ pub async fn fetch_routes_on_station(
     }
 
 
-    let response_raw_json = response
-        .json::<RawRoutesOnStationResponse>()
-        .await
-        .map_err(LppApiFetchError::ResponseDecodingError)?;
+    let response_raw_json =
+        response.json::<RawRoutesOnStationResponse>(LppApiEndpoint::RoutesOnStation)?;
 
     if !response_raw_json.success {
-        return Err(LppApiFetchError::APIResponseNotSuccessful {
-            reason: String::from("success field is false"),
-        });
+        return Err(LppApiFetchError::unsuccessful_response(
+            LppApiEndpoint::RoutesOnStation,
+            response_raw_json.message,
+        ));
     }
 
 
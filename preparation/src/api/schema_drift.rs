@@ -0,0 +1,210 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use serde_json::Value;
+use tracing::warn;
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SchemaDriftKind {
+    /// The response contains a field our response schema doesn't know about.
+    UnknownField,
+
+    /// A field of our response schema is missing from the response
+    /// (it was either optional or had a default value, otherwise parsing would have failed).
+    MissingField,
+}
+
+impl Display for SchemaDriftKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaDriftKind::UnknownField => write!(f, "unknown field"),
+            SchemaDriftKind::MissingField => write!(f, "missing field"),
+        }
+    }
+}
+
+/// A difference between an LPP API response and our expected response schema.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SchemaDrift {
+    /// Example: `data[].route_id`.
+    pub field_path: String,
+    pub kind: SchemaDriftKind,
+}
+
+
+/// Compares the keys of a raw response with the keys of the same response after it was
+/// parsed into our response schema and serialized again (`expected`). Array elements are
+/// compared pairwise and their differences are reported under a single `[]` path.
+pub fn find_schema_drift(observed: &Value, expected: &Value) -> Vec<SchemaDrift> {
+    let mut drifts = HashSet::new();
+    collect_schema_drift(observed, expected, "", &mut drifts);
+
+    let mut drifts: Vec<SchemaDrift> = drifts.into_iter().collect();
+    drifts.sort_by(|first, second| first.field_path.cmp(&second.field_path));
+
+    drifts
+}
+
+fn collect_schema_drift(
+    observed: &Value,
+    expected: &Value,
+    path: &str,
+    drifts: &mut HashSet<SchemaDrift>,
+) {
+    let field_path = |key: &str| match path.is_empty() {
+        true => key.to_string(),
+        false => format!("{}.{}", path, key),
+    };
+
+    match (observed, expected) {
+        (Value::Object(observed_object), Value::Object(expected_object)) => {
+            for (key, observed_value) in observed_object {
+                match expected_object.get(key) {
+                    Some(expected_value) => collect_schema_drift(
+                        observed_value,
+                        expected_value,
+                        &field_path(key),
+                        drifts,
+                    ),
+                    None => {
+                        drifts.insert(SchemaDrift {
+                            field_path: field_path(key),
+                            kind: SchemaDriftKind::UnknownField,
+                        });
+                    }
+                }
+            }
+
+            for key in expected_object.keys() {
+                if !observed_object.contains_key(key) {
+                    drifts.insert(SchemaDrift {
+                        field_path: field_path(key),
+                        kind: SchemaDriftKind::MissingField,
+                    });
+                }
+            }
+        }
+        (Value::Array(observed_array), Value::Array(expected_array)) => {
+            let element_path = format!("{}[]", path);
+
+            for (observed_element, expected_element) in observed_array.iter().zip(expected_array) {
+                collect_schema_drift(
+                    observed_element,
+                    expected_element,
+                    &element_path,
+                    drifts,
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+
+/// Decides which responses are checked for schema drift and reports the drift it finds,
+/// each difference only once per endpoint (so a persistent change doesn't flood the logs).
+#[derive(Debug)]
+pub struct SchemaDriftSampler {
+    sample_rate: f64,
+    number_of_responses: AtomicU64,
+    reported_drifts: Mutex<HashSet<(String, SchemaDrift)>>,
+}
+
+impl SchemaDriftSampler {
+    /// `sample_rate` is the fraction of responses to check (from `0.0` to `1.0`).
+    pub fn new(sample_rate: f64) -> Self {
+        Self {
+            sample_rate,
+            number_of_responses: AtomicU64::new(0),
+            reported_drifts: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Returns `true` if the next response should be checked. The first response
+    /// is always checked, after that responses are checked evenly at the sample rate.
+    pub fn should_check_next_response(&self) -> bool {
+        let response_index = self.number_of_responses.fetch_add(1, Ordering::Relaxed) as f64;
+
+        (response_index * self.sample_rate).ceil()
+            < ((response_index + 1.0) * self.sample_rate).ceil()
+    }
+
+    pub fn report(&self, endpoint: &str, drifts: Vec<SchemaDrift>) {
+        // PANIC SAFETY: The lock is never held across a panicking call.
+        let mut reported_drifts = self.reported_drifts.lock().unwrap();
+
+        for drift in drifts {
+            if !reported_drifts.insert((endpoint.to_string(), drift.clone())) {
+                continue;
+            }
+
+            warn!(
+                endpoint = endpoint,
+                field_path = drift.field_path,
+                kind = %drift.kind,
+                "LPP API response does not match our response schema - the API may have changed."
+            );
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn find_unknown_and_missing_fields() {
+        let observed = json!({
+            "success": true,
+            "data": [
+                { "route_id": "A", "route_number": "3G", "is_express": false },
+                { "route_id": "B", "route_number": "6", "is_express": true },
+            ],
+        });
+        let expected = json!({
+            "success": true,
+            "message": null,
+            "data": [
+                { "route_id": "A", "route_number": "3G" },
+                { "route_id": "B", "route_number": "6" },
+            ],
+        });
+
+        assert_eq!(
+            find_schema_drift(&observed, &expected),
+            vec![
+                SchemaDrift {
+                    field_path: String::from("data[].is_express"),
+                    kind: SchemaDriftKind::UnknownField,
+                },
+                SchemaDrift {
+                    field_path: String::from("message"),
+                    kind: SchemaDriftKind::MissingField,
+                },
+            ]
+        );
+
+        assert!(find_schema_drift(&expected, &expected).is_empty());
+    }
+
+    #[test]
+    fn sample_responses_evenly() {
+        let sampler = SchemaDriftSampler::new(0.01);
+
+        let checked_responses: Vec<usize> = (0..300)
+            .filter(|_| sampler.should_check_next_response())
+            .collect();
+
+        assert_eq!(checked_responses, vec![0, 100, 200]);
+    }
+}
@@ -0,0 +1,338 @@
+//! Tolerant deserializers for fields of raw LPP API responses.
+//!
+//! The API is not consistent about the JSON types of some fields: the same integer field
+//! can be `3085` in one response and `"3085"` in the next (and vice versa for numeric codes),
+//! and some boolean fields are sent as `0`/`1`. Use these with `#[serde(deserialize_with = "...")]`,
+//! along with the matching `*_schema` function in `#[schemars(schema_with = "...")]`
+//! (so the response schemas describe every form the field is accepted in).
+
+use std::{fmt, marker::PhantomData, str::FromStr};
+
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{ArrayValidation, InstanceType, Schema, SchemaObject, SingleOrVec},
+};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserializer,
+};
+
+
+struct IntOrStringVisitor<T> {
+    target: PhantomData<T>,
+}
+
+impl<'de, T> Visitor<'de> for IntOrStringVisitor<T>
+where
+    T: FromStr + TryFrom<i64> + TryFrom<u64>,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an integer or a string containing an integer")
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        T::try_from(value).map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        T::try_from(value).map_err(|_| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        value
+            .trim()
+            .parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+}
+
+/// Deserializes an integer that may also be sent as a string (e.g. `3085` or `"3085"`).
+pub fn int_or_string<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr + TryFrom<i64> + TryFrom<u64>,
+{
+    deserializer.deserialize_any(IntOrStringVisitor {
+        target: PhantomData,
+    })
+}
+
+/// Deserializes a list of integers, each of which may also be sent as a string
+/// (see [`int_or_string`]).
+pub fn vec_of_int_or_string<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr + TryFrom<i64> + TryFrom<u64>,
+{
+    struct VecVisitor<T> {
+        target: PhantomData<T>,
+    }
+
+    impl<'de, T> Visitor<'de> for VecVisitor<T>
+    where
+        T: FromStr + TryFrom<i64> + TryFrom<u64>,
+    {
+        type Value = Vec<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a list of integers or strings containing integers")
+        }
+
+        fn visit_seq<A>(self, mut sequence: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            /// Lets us deserialize each element with [`int_or_string`].
+            struct Element<T>(T);
+
+            impl<'de, T> de::Deserialize<'de> for Element<T>
+            where
+                T: FromStr + TryFrom<i64> + TryFrom<u64>,
+            {
+                fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: Deserializer<'de>,
+                {
+                    int_or_string(deserializer).map(Element)
+                }
+            }
+
+            let mut values = Vec::with_capacity(sequence.size_hint().unwrap_or_default());
+            while let Some(Element(value)) = sequence.next_element::<Element<T>>()? {
+                values.push(value);
+            }
+
+            Ok(values)
+        }
+    }
+
+    deserializer.deserialize_seq(VecVisitor {
+        target: PhantomData,
+    })
+}
+
+
+struct StringOrIntVisitor;
+
+impl<'de> Visitor<'de> for StringOrIntVisitor {
+    type Value = String;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string or an integer")
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(value.to_string())
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(value.to_string())
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(value.to_string())
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(value)
+    }
+}
+
+/// Deserializes a string (usually a numeric code, e.g. a station code)
+/// that may also be sent as an integer (e.g. `"600011"` or `600011`).
+pub fn string_or_int<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(StringOrIntVisitor)
+}
+
+
+struct BoolOrIntVisitor;
+
+impl<'de> Visitor<'de> for BoolOrIntVisitor {
+    type Value = bool;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a boolean, 0 or 1")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(value)
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        match value {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(E::invalid_value(
+                de::Unexpected::Signed(value),
+                &self,
+            )),
+        }
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        match value {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(E::invalid_value(
+                de::Unexpected::Unsigned(value),
+                &self,
+            )),
+        }
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        match value.trim() {
+            "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            _ => Err(E::invalid_value(
+                de::Unexpected::Str(value),
+                &self,
+            )),
+        }
+    }
+}
+
+/// Deserializes a boolean that may also be sent as `0`/`1` (or as a string of either form).
+pub fn bool_or_int<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(BoolOrIntVisitor)
+}
+
+
+
+/*
+ * SCHEMAS
+ */
+
+fn schema_of_any_type(instance_types: Vec<InstanceType>) -> Schema {
+    SchemaObject {
+        instance_type: Some(SingleOrVec::Vec(instance_types)),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Schema of fields deserialized with [`int_or_string`].
+pub fn int_or_string_schema(_: &mut SchemaGenerator) -> Schema {
+    schema_of_any_type(vec![InstanceType::Integer, InstanceType::String])
+}
+
+/// Schema of fields deserialized with [`vec_of_int_or_string`].
+pub fn vec_of_int_or_string_schema(generator: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::Array.into()),
+        array: Some(Box::new(ArrayValidation {
+            items: Some(int_or_string_schema(generator).into()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Schema of fields deserialized with [`string_or_int`].
+pub fn string_or_int_schema(_: &mut SchemaGenerator) -> Schema {
+    schema_of_any_type(vec![InstanceType::String, InstanceType::Integer])
+}
+
+/// Schema of fields deserialized with [`bool_or_int`].
+pub fn bool_or_int_schema(_: &mut SchemaGenerator) -> Schema {
+    schema_of_any_type(vec![
+        InstanceType::Boolean,
+        InstanceType::Integer,
+        InstanceType::String,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct TolerantFields {
+        #[serde(deserialize_with = "int_or_string")]
+        int_id: i32,
+        #[serde(deserialize_with = "vec_of_int_or_string")]
+        minutes: Vec<i32>,
+        #[serde(deserialize_with = "string_or_int")]
+        ref_id: String,
+        #[serde(deserialize_with = "bool_or_int")]
+        is_garage: bool,
+    }
+
+    #[test]
+    fn accept_both_payload_variants() {
+        let expected = TolerantFields {
+            int_id: 3085,
+            minutes: vec![5, 35],
+            ref_id: String::from("600011"),
+            is_garage: true,
+        };
+
+        let canonical: TolerantFields = serde_json::from_str(
+            r#"{"int_id": 3085, "minutes": [5, 35], "ref_id": "600011", "is_garage": true}"#,
+        )
+        .unwrap();
+        let flipped: TolerantFields = serde_json::from_str(
+            r#"{"int_id": "3085", "minutes": ["5", 35], "ref_id": 600011, "is_garage": 1}"#,
+        )
+        .unwrap();
+
+        assert_eq!(canonical, expected);
+        assert_eq!(flipped, expected);
+    }
+
+    #[test]
+    fn reject_values_that_are_not_numbers() {
+        assert!(serde_json::from_str::<TolerantFields>(
+            r#"{"int_id": "N/A", "minutes": [], "ref_id": "600011", "is_garage": false}"#,
+        )
+        .is_err());
+        assert!(serde_json::from_str::<TolerantFields>(
+            r#"{"int_id": 1, "minutes": [], "ref_id": "600011", "is_garage": 2}"#,
+        )
+        .is_err());
+    }
+}
@@ -1,10 +1,22 @@
-use reqwest::{Client, StatusCode};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use reqwest::StatusCode;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
 use url::Url;
 
 use super::{
-    errors::{FullUrlConstructionError, LppApiFetchError},
+    client::LppApiClient,
+    errors::{FullUrlConstructionError, LppApiEndpoint, LppApiFetchError, StationCodeParseError},
+    serde_util::{
+        bool_or_int,
+        bool_or_int_schema,
+        int_or_string,
+        int_or_string_schema,
+        string_or_int,
+        string_or_int_schema,
+    },
     BusRoute,
     GeographicalLocation,
     StationCode,
@@ -15,19 +27,27 @@ use crate::configuration::LppApiConfiguration;
  * RAW RESPONSE SCHEMAS
  */
 
-#[derive(Serialize, Deserialize, Clone)]
-struct RawStationDetailsResponse {
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub(super) struct RawStationDetailsResponse {
+    #[serde(deserialize_with = "bool_or_int")]
+    #[schemars(schema_with = "bool_or_int_schema")]
     success: bool,
+
+    /// Error message, usually only set if `success` is `false`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
     data: Vec<RawStationDetails>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 struct RawStationDetails {
     /// Unique internal station identifier.
     ///
     /// Example: `3307`.
     ///
     /// LPP documentation: "Integer ID of station".
+    #[serde(deserialize_with = "int_or_string")]
+    #[schemars(schema_with = "int_or_string_schema")]
     pub int_id: i32,
 
     /// Geographical latitude of the bus station.
@@ -56,6 +76,8 @@ struct RawStationDetails {
     /// Example: `201011`.
     ///
     /// LPP documentation: "Ref ID / station code of the station (ex. 600011)".
+    #[serde(deserialize_with = "string_or_int")]
+    #[schemars(schema_with = "string_or_int_schema")]
     pub ref_id: String,
 
     /// A list of all route groups that stop on this bus station.
@@ -106,6 +128,17 @@ pub struct StationDetails {
     pub routes_on_station: Vec<BusRoute>,
 }
 
+impl StationDetails {
+    /// Returns the number of distinct route groups (base routes) that stop on this station.
+    pub fn number_of_route_groups(&self) -> usize {
+        self.routes_on_station
+            .iter()
+            .map(BusRoute::to_base_route)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+}
+
 impl TryFrom<RawStationDetails> for StationDetails {
     type Error = miette::Report;
 
@@ -156,7 +189,7 @@ fn build_station_details_url(
 /// at <https://data.lpp.si/doc/#api-Station-station_details>.
 pub async fn fetch_station_details(
     api_configuration: &LppApiConfiguration,
-    client: &Client,
+    client: &LppApiClient,
 ) -> Result<Vec<StationDetails>, LppApiFetchError> {
     let full_url = build_station_details_url(api_configuration)?;
 
@@ -165,20 +198,20 @@ pub async fn fetch_station_details(
         "Will fetch station details from the LPP API."
     );
 
-    let response = client
-        .get(full_url)
-        .header("User-Agent", &api_configuration.user_agent)
-        .send()
-        .await
-        .map_err(LppApiFetchError::RequestError)?;
+    let response = client.get(full_url).await?;
 
     let response_status = response.status();
     if response_status.is_client_error() {
         if response_status.eq(&StatusCode::TOO_MANY_REQUESTS) {
+            let retry_after = response.retry_after();
+
             warn!(
+                retry_after = ?retry_after,
                 "LPP API is rate-limiting us! Got 429 Too Many Requests \
                 (was trying to fetch station details)."
             );
+
+            return Err(LppApiFetchError::RateLimited { retry_after });
         }
 
         return Err(LppApiFetchError::ClientHTTPError(response_status));
@@ -187,15 +220,14 @@ pub async fn fetch_station_details(
     }
 
 
-    let response_raw_json = response
-        .json::<RawStationDetailsResponse>()
-        .await
-        .map_err(LppApiFetchError::ResponseDecodingError)?;
+    let response_raw_json =
+        response.json::<RawStationDetailsResponse>(LppApiEndpoint::StationDetails)?;
 
     if !response_raw_json.success {
-        return Err(LppApiFetchError::APIResponseNotSuccessful {
-            reason: String::from("success field is false"),
-        });
+        return Err(LppApiFetchError::unsuccessful_response(
+            LppApiEndpoint::StationDetails,
+            response_raw_json.message,
+        ));
     }
 
 
@@ -208,3 +240,136 @@ pub async fn fetch_station_details(
 
     Ok(parsed_details)
 }
+
+
+/// Removes stations with duplicate station codes (the LPP API occasionally returns some
+/// stations twice). Of the duplicates, the entry with the most route groups is kept,
+/// in place of the first one. Returns the remaining stations and the number of dropped ones.
+pub fn deduplicate_stations(stations: Vec<StationDetails>) -> (Vec<StationDetails>, usize) {
+    let number_of_stations = stations.len();
+
+    let mut deduplicated_stations: Vec<StationDetails> = Vec::with_capacity(number_of_stations);
+    let mut station_code_to_index: HashMap<StationCode, usize> =
+        HashMap::with_capacity(number_of_stations);
+
+    for station in stations {
+        match station_code_to_index.entry(station.station_code.clone()) {
+            Entry::Occupied(existing_entry) => {
+                let existing_station = &mut deduplicated_stations[*existing_entry.get()];
+
+                if station.number_of_route_groups() > existing_station.number_of_route_groups() {
+                    *existing_station = station;
+                }
+            }
+            Entry::Vacant(vacant_entry) => {
+                vacant_entry.insert(deduplicated_stations.len());
+                deduplicated_stations.push(station);
+            }
+        }
+    }
+
+    let number_of_dropped_stations = number_of_stations - deduplicated_stations.len();
+
+    (deduplicated_stations, number_of_dropped_stations)
+}
+
+/// Checks that every station has a valid (six-digit) station code, see [`StationCode::parse`].
+/// Stations with invalid codes are dropped unless `keep_invalid` is set (in which case they
+/// are kept as-is). Returns the remaining stations and the errors for the invalid codes.
+pub fn validate_station_codes(
+    stations: Vec<StationDetails>,
+    keep_invalid: bool,
+) -> (Vec<StationDetails>, Vec<StationCodeParseError>) {
+    let mut validated_stations = Vec::with_capacity(stations.len());
+    let mut invalid_station_codes = Vec::new();
+
+    for station in stations {
+        match StationCode::parse(station.station_code.as_ref()) {
+            Ok(_) => validated_stations.push(station),
+            Err(error) => {
+                invalid_station_codes.push(error);
+
+                if keep_invalid {
+                    validated_stations.push(station);
+                }
+            }
+        }
+    }
+
+    (validated_stations, invalid_station_codes)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_fixtures::station;
+
+    #[test]
+    fn deduplicate_stations_keeps_entry_with_most_route_groups() {
+        let (stations, number_of_dropped_stations) = deduplicate_stations(vec![
+            station("600011", "KONGRESNI TRG", &["2", "3G"]),
+            station("600012", "KONGRESNI TRG", &["2"]),
+            station(
+                "600011",
+                "KONGRESNI TRG (duplicate)",
+                &["2", "3", "3G", "11"],
+            ),
+            station("600011", "KONGRESNI TRG (duplicate)", &["2"]),
+        ]);
+
+        assert_eq!(number_of_dropped_stations, 2);
+        assert_eq!(stations.len(), 2);
+
+        assert_eq!(
+            stations[0].station_code,
+            StationCode::new("600011")
+        );
+        assert_eq!(stations[0].number_of_route_groups(), 3);
+        assert_eq!(
+            stations[1].station_code,
+            StationCode::new("600012")
+        );
+    }
+
+    #[test]
+    fn validate_station_codes_drops_or_keeps_invalid_codes() {
+        let stations = vec![
+            station("600011", "KONGRESNI TRG", &["2"]),
+            station("60001", "KONGRESNI TRG (truncated)", &["2"]),
+        ];
+
+        let (strict_stations, invalid_station_codes) =
+            validate_station_codes(stations.clone(), false);
+        assert_eq!(strict_stations.len(), 1);
+        assert_eq!(
+            invalid_station_codes,
+            vec![StationCodeParseError::new("60001")]
+        );
+
+        let (lenient_stations, invalid_station_codes) = validate_station_codes(stations, true);
+        assert_eq!(lenient_stations.len(), 2);
+        assert_eq!(invalid_station_codes.len(), 1);
+    }
+
+    #[test]
+    fn parse_station_details_with_flipped_field_types() {
+        let canonical = r#"{"success": true, "data": [{
+            "int_id": 3085, "latitude": 46.05, "longitude": 14.5, "name": "ŽELEZNA",
+            "ref_id": "201011", "route_groups_on_station": ["3G", "11B"]
+        }]}"#;
+        let flipped = r#"{"success": 1, "data": [{
+            "int_id": "3085", "latitude": 46.05, "longitude": 14.5, "name": "ŽELEZNA",
+            "ref_id": 201011, "route_groups_on_station": ["3G", "11B"]
+        }]}"#;
+
+        for payload in [canonical, flipped] {
+            let response: RawStationDetailsResponse = serde_json::from_str(payload).unwrap();
+            assert!(response.success);
+
+            let station = StationDetails::try_from(response.data[0].clone()).unwrap();
+            assert_eq!(station.internal_station_id, 3085);
+            assert_eq!(station.station_code, StationCode::new("201011"));
+        }
+    }
+}
@@ -1,10 +1,20 @@
-use reqwest::{Client, StatusCode};
+use reqwest::StatusCode;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 use url::Url;
 
 use super::{
-    errors::{FullUrlConstructionError, LppApiFetchError},
+    client::LppApiClient,
+    errors::{FullUrlConstructionError, LppApiEndpoint, LppApiFetchError},
+    serde_util::{
+        bool_or_int,
+        bool_or_int_schema,
+        int_or_string,
+        int_or_string_schema,
+        string_or_int,
+        string_or_int_schema,
+    },
     GeographicalLocation,
     StationCode,
     TripId,
@@ -15,19 +25,27 @@ use crate::configuration::LppApiConfiguration;
  * RAW RESPONSE SCHEMAS
  */
 
-#[derive(Serialize, Deserialize, Clone)]
-struct RawStationsOnRouteResponse {
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub(super) struct RawStationsOnRouteResponse {
+    #[serde(deserialize_with = "bool_or_int")]
+    #[schemars(schema_with = "bool_or_int_schema")]
     success: bool,
+
+    /// Error message, usually only set if `success` is `false`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
     data: Vec<RawStationOnRoute>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 struct RawStationOnRoute {
     /// Unique internal station identifier.
     ///
     /// Example: `3307`.
     ///
     /// LPP documentation: "Integer ID of station".
+    #[serde(deserialize_with = "int_or_string")]
+    #[schemars(schema_with = "int_or_string_schema")]
     station_int_id: i32,
 
     /// Unique bus station reference (?) identifier used in other requests.
@@ -35,6 +53,8 @@ struct RawStationOnRoute {
     /// Example: `201011`.
     ///
     /// LPP documentation: "Destination of route (direction)".
+    #[serde(deserialize_with = "string_or_int")]
+    #[schemars(schema_with = "string_or_int_schema")]
     station_code: String,
 
     /// Station name.
@@ -50,6 +70,8 @@ struct RawStationOnRoute {
     /// Example: `1`.
     ///
     /// LPP documentation: "Order of stations, 1 is starting station".
+    #[serde(deserialize_with = "int_or_string")]
+    #[schemars(schema_with = "int_or_string_schema")]
     order_no: i32,
 
     /// Geographical latitude of the bus station.
@@ -137,26 +159,26 @@ fn build_stations_on_route_url(
 
 pub async fn fetch_stations_on_route(
     api_configuration: &LppApiConfiguration,
-    client: &Client,
+    client: &LppApiClient,
     trip_id: TripId,
 ) -> Result<Option<Vec<StationOnRoute>>, LppApiFetchError> {
     let full_url = build_stations_on_route_url(api_configuration, trip_id)?;
 
-    let response = client
-        .get(full_url)
-        .header("User-Agent", &api_configuration.user_agent)
-        .send()
-        .await
-        .map_err(LppApiFetchError::RequestError)?;
+    let response = client.get(full_url).await?;
 
 
     let response_status = response.status();
     if response_status.is_client_error() {
         if response_status.eq(&StatusCode::TOO_MANY_REQUESTS) {
+            let retry_after = response.retry_after();
+
             warn!(
+                retry_after = ?retry_after,
                 "LPP API is rate-limiting us! Got 429 Too Many Requests \
                 (was trying to fetch station details)."
             );
+
+            return Err(LppApiFetchError::RateLimited { retry_after });
         }
 
         return Err(LppApiFetchError::ClientHTTPError(response_status));
@@ -165,15 +187,14 @@ pub async fn fetch_stations_on_route(
     }
 
 
-    let response_raw_json = response
-        .json::<RawStationsOnRouteResponse>()
-        .await
-        .map_err(LppApiFetchError::ResponseDecodingError)?;
+    let response_raw_json =
+        response.json::<RawStationsOnRouteResponse>(LppApiEndpoint::StationsOnRoute)?;
 
     if !response_raw_json.success {
-        return Err(LppApiFetchError::APIResponseNotSuccessful {
-            reason: String::from("success field is false"),
-        });
+        return Err(LppApiFetchError::unsuccessful_response(
+            LppApiEndpoint::StationsOnRoute,
+            response_raw_json.message,
+        ));
     }
 
 
@@ -0,0 +1,98 @@
+//! Test fixtures shared by the tests of modules working with stations, trips,
+//! timetables and arrivals.
+
+use chrono::Utc;
+
+use super::{
+    arrivals_on_route::{ArrivalData, ArrivalEstimation},
+    routes::RouteDetails,
+    station_details::StationDetails,
+    timetable::{TimetableEntry, TripTimetable},
+    BusRoute,
+    GeographicalLocation,
+    RouteId,
+    StationCode,
+    TripId,
+    VehicleId,
+};
+use crate::recorder::formats::StationDetailsWithBusesAndTimetables;
+
+
+/// A station with the given routes stopping on it.
+pub fn station(station_code: &str, name: &str, routes_on_station: &[&str]) -> StationDetails {
+    StationDetails {
+        station_code: StationCode::new(station_code),
+        internal_station_id: 0,
+        name: name.to_string(),
+        location: GeographicalLocation::new(46.05, 14.5),
+        routes_on_station: routes_on_station
+            .iter()
+            .map(|route_name| BusRoute::from_route_name(*route_name).unwrap())
+            .collect(),
+    }
+}
+
+/// A recorded station without trips or timetables at the given location.
+pub fn station_at(
+    station_code: &str,
+    latitude: f64,
+    longitude: f64,
+) -> StationDetailsWithBusesAndTimetables {
+    let station = StationDetails {
+        location: GeographicalLocation::new(latitude, longitude),
+        ..station(station_code, "STATION", &[])
+    };
+
+    StationDetailsWithBusesAndTimetables::from_station_and_trips(
+        Utc::now(),
+        station,
+        Vec::new(),
+        Vec::new(),
+    )
+}
+
+/// A trip of the given route, e.g. `trip("6B", "ČRNUČE - BAVARSKI DVOR", "T6")`.
+pub fn trip(route: &str, name: &str, trip_id: &str) -> RouteDetails {
+    RouteDetails {
+        route_id: RouteId::new("route"),
+        trip_id: TripId::new(trip_id),
+        internal_trip_id: 1,
+        route: BusRoute::from_route_name(route).unwrap(),
+        name: name.to_string(),
+        short_name: None,
+        route_shape: None,
+    }
+}
+
+/// Timetable entries departing at the given `(hour, minute)` times.
+pub fn timetable_entries(departures: &[(u8, u8)]) -> Vec<TimetableEntry> {
+    departures
+        .iter()
+        .map(|(hour, minute)| TimetableEntry::new(*hour, *minute).unwrap())
+        .collect()
+}
+
+/// Timetable of a trip departing at the given `(hour, minute)` times.
+/// The short trip name is the last part of the trip name (its destination).
+pub fn timetable(route: &str, trip_name: &str, departures: &[(u8, u8)]) -> TripTimetable {
+    TripTimetable {
+        route: BusRoute::from_route_name(route).unwrap(),
+        trip_name: trip_name.to_string(),
+        short_trip_name: trip_name.rsplit(" - ").next().map(str::to_string),
+        ends_in_garage: false,
+        timetable: timetable_entries(departures),
+        stations: Vec::new(),
+    }
+}
+
+/// An arrival of a vehicle on route 6B that isn't heading to the garage.
+pub fn arrival(vehicle_id: &str, arrival_estimation: ArrivalEstimation) -> ArrivalData {
+    ArrivalData {
+        route_id: RouteId::new("route"),
+        vehicle_id: VehicleId::new(vehicle_id),
+        arrival_estimation,
+        route: BusRoute::from_route_name("6B").unwrap(),
+        trip_name: String::from("ČRNUČE - BAVARSKI DVOR"),
+        heading_to_garage: false,
+    }
+}
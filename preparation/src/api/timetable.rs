@@ -1,31 +1,55 @@
-use chrono::{Local, Timelike};
-use reqwest::{Client, StatusCode};
+use chrono::Timelike;
+use reqwest::StatusCode;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
 use url::Url;
 
 use super::{
-    errors::{FullUrlConstructionError, LppApiFetchError, RouteTimetableParseError},
+    client::LppApiClient,
+    errors::{
+        FullUrlConstructionError,
+        LppApiEndpoint,
+        LppApiFetchError,
+        RouteTimetableParseError,
+        UnsuccessfulResponseCategory,
+    },
+    serde_util::{
+        bool_or_int,
+        bool_or_int_schema,
+        int_or_string,
+        int_or_string_schema,
+        string_or_int,
+        string_or_int_schema,
+        vec_of_int_or_string,
+        vec_of_int_or_string_schema,
+    },
     BaseBusRoute,
     BusRoute,
     StationCode,
 };
-use crate::configuration::LppApiConfiguration;
+use crate::{clock::Clock, configuration::LppApiConfiguration};
 
 
 /*
  * RAW RESPONSE SCHEMAS
  */
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct RawTimetableResponse {
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub(super) struct RawTimetableResponse {
+    #[serde(deserialize_with = "bool_or_int")]
+    #[schemars(schema_with = "bool_or_int_schema")]
     success: bool,
+
+    /// Error message, usually only set if `success` is `false`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     message: Option<String>,
+
     data: RawTimetableData,
 }
 
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 struct RawTimetableData {
     /// Concise information about the requested station.
     ///
@@ -39,13 +63,15 @@ struct RawTimetableData {
     route_groups: Vec<RawTimetableRouteGroupsData>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 struct RawTimetableStationData {
     /// Unique bus station reference (?) identifier used in other requests.
     ///
     /// Example: `600012`.
     ///
     /// LPP documentation: "Reference ID/ station code of station (6 digits, ex. 600011)".
+    #[serde(deserialize_with = "string_or_int")]
+    #[schemars(schema_with = "string_or_int_schema")]
     ref_id: String,
 
     /// Station name.
@@ -56,7 +82,7 @@ struct RawTimetableStationData {
     name: String,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 struct RawTimetableRouteGroupsData {
     /// Route group number the `routes` are about (without prefix or suffix).
     ///
@@ -64,6 +90,8 @@ struct RawTimetableRouteGroupsData {
     ///
     /// LPP documentation: "Route group number for the array item
     /// (always non-suffixed, ex. 6 instead of 6B)".
+    #[serde(deserialize_with = "string_or_int")]
+    #[schemars(schema_with = "string_or_int_schema")]
     route_group_number: String,
 
     /// List of trips in this route group. If `route_group_number` is e.g. "3",
@@ -73,7 +101,7 @@ struct RawTimetableRouteGroupsData {
     routes: Vec<RawTripTimetable>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 struct RawTripTimetable {
     /// All arrivals for the given station.
     ///
@@ -125,16 +153,20 @@ struct RawTripTimetable {
     /// Example: `true`.
     ///
     /// LPP documentation: "true if route ends in garage".
+    #[serde(deserialize_with = "bool_or_int")]
+    #[schemars(schema_with = "bool_or_int_schema")]
     is_garage: bool,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 struct RawTimetableRouteTimetableEntry {
     /// The hour of arrival for this entry.
     ///
     /// Example: `5`.
     ///
     /// LPP documentation: none at all.
+    #[serde(deserialize_with = "int_or_string")]
+    #[schemars(schema_with = "int_or_string_schema")]
     hour: i32,
 
     /// A list of all arrivals in minutes.
@@ -145,6 +177,8 @@ struct RawTimetableRouteTimetableEntry {
     /// Example: `[19]`.
     ///
     /// LPP documentation: none at all.
+    #[serde(deserialize_with = "vec_of_int_or_string")]
+    #[schemars(schema_with = "vec_of_int_or_string_schema")]
     minutes: Vec<i32>,
 
     /// Whether this is the current hour. Seems mostly useless.
@@ -152,6 +186,8 @@ struct RawTimetableRouteTimetableEntry {
     /// Example: `false`.
     ///
     /// LPP documentation: "True if this represents arrivals for current hour.".
+    #[serde(deserialize_with = "bool_or_int")]
+    #[schemars(schema_with = "bool_or_int_schema")]
     is_current: bool,
 
     ///
@@ -162,13 +198,15 @@ struct RawTimetableRouteTimetableEntry {
     timestamp: String,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 struct RawStationOnTimetable {
     /// Unique bus station reference (?) identifier used in other requests.
     ///
     /// Example: `201011`.
     ///
     /// LPP documentation: "".
+    #[serde(deserialize_with = "string_or_int")]
+    #[schemars(schema_with = "string_or_int_schema")]
     ref_id: String,
 
     /// Name of the bus station.
@@ -184,6 +222,8 @@ struct RawStationOnTimetable {
     /// Example: `1`.
     ///
     /// LPP documentation: "Sequential order number of the station on this route".
+    #[serde(deserialize_with = "int_or_string")]
+    #[schemars(schema_with = "int_or_string_schema")]
     order_no: i32,
 }
 
@@ -207,6 +247,22 @@ pub struct RouteGroupTimetable {
     /// This means we'll (likely) get timetables for route "3G" and "3B"
     /// whenever `route_group_name` is "3".
     pub trip_timetables: Vec<TripTimetable>,
+
+    /// Trips whose timetables could not be parsed and were left out
+    /// (only when timetables are not parsed strictly).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_trips: Vec<SkippedTripTimetable>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SkippedTripTimetable {
+    /// Full name of the skipped trip.
+    ///
+    /// Example: `BEŽIGRAD - GROSUPLJE`.
+    pub trip_name: String,
+
+    /// Why the trip's timetable could not be parsed.
+    pub reason: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -304,19 +360,42 @@ pub struct StationOnTimetable {
  * Conversions
  */
 
-impl TryFrom<RawTimetableRouteGroupsData> for RouteGroupTimetable {
-    type Error = miette::Report;
-
-    fn try_from(value: RawTimetableRouteGroupsData) -> Result<Self, Self::Error> {
-        let route_timetables = value
-            .routes
-            .into_iter()
-            .map(TripTimetable::try_from)
-            .collect::<Result<_, _>>()?;
+impl RouteGroupTimetable {
+    /// Parses a route group's timetable. If `strict` is `true`, a single malformed trip
+    /// fails the entire route group, otherwise malformed trips are left out
+    /// and recorded in `skipped_trips`.
+    fn from_raw(value: RawTimetableRouteGroupsData, strict: bool) -> Result<Self, miette::Report> {
+        let route_group_name = BaseBusRoute::new_from_str(value.route_group_number)?;
+
+        let mut trip_timetables = Vec::with_capacity(value.routes.len());
+        let mut skipped_trips = Vec::new();
+
+        for raw_trip_timetable in value.routes {
+            let trip_name = raw_trip_timetable.parent_name.clone();
+
+            match TripTimetable::try_from(raw_trip_timetable) {
+                Ok(trip_timetable) => trip_timetables.push(trip_timetable),
+                Err(error) if strict => return Err(error.into()),
+                Err(error) => {
+                    warn!(
+                        route_group = %route_group_name,
+                        trip_name = trip_name,
+                        error = %error,
+                        "Failed to parse trip timetable, will skip the trip."
+                    );
+
+                    skipped_trips.push(SkippedTripTimetable {
+                        trip_name,
+                        reason: error.to_string(),
+                    });
+                }
+            }
+        }
 
         Ok(Self {
-            route_group_name: BaseBusRoute::new_from_str(value.route_group_number)?,
-            trip_timetables: route_timetables,
+            route_group_name,
+            trip_timetables,
+            skipped_trips,
         })
     }
 }
@@ -417,18 +496,78 @@ pub enum TimetableFetchMode {
 
     /// Capture timetables for up to `previous_hours` before
     /// and `next_hours` after fetching.
-    #[allow(dead_code)]
     Manual {
         next_hours: u32,
         previous_hours: u32,
     },
 }
 
+impl TimetableFetchMode {
+    /// Resolves the mode into the `(next_hours, previous_hours)` of a timetable request
+    /// made at the given (local) hour of day.
+    pub fn next_and_previous_hours(&self, current_hour: u32) -> (u32, u32) {
+        match self {
+            TimetableFetchMode::FullDay => {
+                // Automatically set next and previous to capture entire day.
+                let next_hours = current_hour;
+                let previous_hours = 24u32.saturating_sub(current_hour);
+
+                (next_hours, previous_hours)
+            }
+            TimetableFetchMode::Manual {
+                next_hours,
+                previous_hours,
+            } => (*next_hours, *previous_hours),
+        }
+    }
+}
+
+
+/// Which part of the day the timetables of a snapshot cover.
+/// Resolved into a [`TimetableFetchMode`] when each snapshot is captured.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimetableWindowPolicy {
+    /// Always capture timetables for the entire day.
+    FullDay,
+
+    /// Capture timetables from the start of the current hour until the end of the day,
+    /// which makes late-evening snapshots a lot smaller.
+    ServiceDayRemaining,
+
+    /// Capture timetables for a fixed number of hours around the time of capture.
+    Custom {
+        next_hours: u32,
+        previous_hours: u32,
+    },
+}
+
+impl TimetableWindowPolicy {
+    /// Resolves the policy into a fetch mode for a capture at the given (local) hour of day.
+    pub fn fetch_mode_at_hour(&self, current_hour: u32) -> TimetableFetchMode {
+        match self {
+            TimetableWindowPolicy::FullDay => TimetableFetchMode::FullDay,
+            TimetableWindowPolicy::ServiceDayRemaining => TimetableFetchMode::Manual {
+                next_hours: 24u32.saturating_sub(current_hour),
+                // Includes departures from earlier in the current hour.
+                previous_hours: 1,
+            },
+            TimetableWindowPolicy::Custom {
+                next_hours,
+                previous_hours,
+            } => TimetableFetchMode::Manual {
+                next_hours: *next_hours,
+                previous_hours: *previous_hours,
+            },
+        }
+    }
+}
+
 fn build_timetable_url<I>(
     api_configuration: &LppApiConfiguration,
     station_code: &StationCode,
     route_group_numbers: I,
     timetable_mode: &TimetableFetchMode,
+    current_hour: u32,
 ) -> Result<Url, FullUrlConstructionError>
 where
     I: IntoIterator<Item = BaseBusRoute>,
@@ -441,22 +580,7 @@ where
     url_query_pairs.append_pair("station-code", station_code.as_ref());
 
 
-    let (next_hours, previous_hours) = match timetable_mode {
-        TimetableFetchMode::FullDay => {
-            // Automatically set next and previous to capture entire day.
-            let local_time_now = Local::now();
-            let current_hour = local_time_now.hour();
-
-            let next_hours = current_hour;
-            let previous_hours = 24u32.saturating_sub(current_hour);
-
-            (next_hours, previous_hours)
-        }
-        TimetableFetchMode::Manual {
-            next_hours,
-            previous_hours,
-        } => (*next_hours, *previous_hours),
-    };
+    let (next_hours, previous_hours) = timetable_mode.next_and_previous_hours(current_hour);
 
     url_query_pairs.append_pair("next-hours", &next_hours.to_string());
     url_query_pairs.append_pair("previous-hours", &previous_hours.to_string());
@@ -477,7 +601,8 @@ where
 
 pub async fn fetch_timetable<I>(
     api_configuration: &LppApiConfiguration,
-    client: &Client,
+    client: &LppApiClient,
+    clock: &dyn Clock,
     station_code: &StationCode,
     route_group_numbers: I,
     timetable_mode: TimetableFetchMode,
@@ -490,6 +615,7 @@ where
         station_code,
         route_group_numbers,
         &timetable_mode,
+        clock.local_now().hour(),
     )?;
 
     debug!(
@@ -498,52 +624,57 @@ where
         "Will fetch timetables for station from the LPP API."
     );
 
-    let response = client
-        .get(full_url)
-        .send()
-        .await
-        .map_err(LppApiFetchError::RequestError)?;
+    let response = client.get(full_url).await?;
 
 
     let response_status = response.status();
     if response_status.is_client_error() {
         if response_status.eq(&StatusCode::TOO_MANY_REQUESTS) {
+            let retry_after = response.retry_after();
+
             warn!(
+                retry_after = ?retry_after,
                 "LPP API is rate-limiting us! Got 429 Too Many Requests \
                 (was trying to fetch timetables)."
             );
+
+            return Err(LppApiFetchError::RateLimited { retry_after });
         }
 
         return Err(LppApiFetchError::ClientHTTPError(response_status));
     } else if response_status.is_server_error() {
         // Can be caused by: "No active routes on station 604021 or station-code is invalid".
         // We should handle that case separately.
-        let response_raw_json = response
-            .json::<RawTimetableResponse>()
-            .await
-            .map_err(LppApiFetchError::ResponseDecodingError)?;
-
-        if !response_raw_json.success {
-            if let Some(message) = response_raw_json.message {
-                if message.starts_with("No active routes on station") {
-                    return Ok(Vec::new());
+        let unsuccessful_response_category =
+            match response.json::<RawTimetableResponse>(LppApiEndpoint::Timetable) {
+                Ok(response_raw_json) if !response_raw_json.success => {
+                    Some(UnsuccessfulResponseCategory::recognize(
+                        LppApiEndpoint::Timetable,
+                        response_raw_json.message.as_deref(),
+                    ))
                 }
-            }
+                Ok(_) => None,
+                Err(LppApiFetchError::APIResponseNotSuccessful { category, .. }) => Some(category),
+                Err(error) => return Err(error),
+            };
+
+        if unsuccessful_response_category
+            == Some(UnsuccessfulResponseCategory::NoActiveRoutesOnStation)
+        {
+            return Ok(Vec::new());
         }
 
         return Err(LppApiFetchError::ServerHTTPError(response_status));
     }
 
 
-    let response_raw_json = response
-        .json::<RawTimetableResponse>()
-        .await
-        .map_err(LppApiFetchError::ResponseDecodingError)?;
+    let response_raw_json = response.json::<RawTimetableResponse>(LppApiEndpoint::Timetable)?;
 
     if !response_raw_json.success {
-        return Err(LppApiFetchError::APIResponseNotSuccessful {
-            reason: String::from("success field is false"),
-        });
+        return Err(LppApiFetchError::unsuccessful_response(
+            LppApiEndpoint::Timetable,
+            response_raw_json.message,
+        ));
     }
 
 
@@ -551,7 +682,12 @@ where
         .data
         .route_groups
         .into_iter()
-        .map(RouteGroupTimetable::try_from)
+        .map(|raw_route_group| {
+            RouteGroupTimetable::from_raw(
+                raw_route_group,
+                api_configuration.strict_timetable_parsing,
+            )
+        })
         .collect::<Result<_, _>>()
         .map_err(|error| LppApiFetchError::malformed_response_with_reason(error.to_string()))?;
 
@@ -559,8 +695,57 @@ where
 }
 
 
+/// Merges the results of several timetable requests for the same station
+/// (e.g. when a station's route groups were split across multiple requests).
+///
+/// Trip timetables stay attributed to the route group they were returned under.
+/// If a route group was returned by more than one request, its trip timetables are combined,
+/// skipping trips (identified by their full route and trip name) that were already seen.
+pub fn merge_route_group_timetables<I>(timetable_batches: I) -> Vec<RouteGroupTimetable>
+where
+    I: IntoIterator<Item = Vec<RouteGroupTimetable>>,
+{
+    let mut merged_timetables: Vec<RouteGroupTimetable> = Vec::new();
+
+    for group_timetable in timetable_batches.into_iter().flatten() {
+        let Some(existing_group_timetable) = merged_timetables
+            .iter_mut()
+            .find(|merged| merged.route_group_name == group_timetable.route_group_name)
+        else {
+            merged_timetables.push(group_timetable);
+            continue;
+        };
+
+        for trip_timetable in group_timetable.trip_timetables {
+            let is_duplicate =
+                existing_group_timetable
+                    .trip_timetables
+                    .iter()
+                    .any(|existing_trip| {
+                        existing_trip.route == trip_timetable.route
+                            && existing_trip.trip_name == trip_timetable.trip_name
+                    });
+
+            if !is_duplicate {
+                existing_group_timetable
+                    .trip_timetables
+                    .push(trip_timetable);
+            }
+        }
+
+        existing_group_timetable
+            .skipped_trips
+            .extend(group_timetable.skipped_trips);
+    }
+
+    merged_timetables
+}
+
+
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
 
     #[test]
@@ -568,6 +753,13 @@ mod tests {
         let api_configuration = LppApiConfiguration {
             lpp_base_api_url: Url::parse("https://data.lpp.si/api/").unwrap(),
             user_agent: String::from("visualization-recorder / 1.0.0"),
+            max_route_groups_per_timetable_request: None,
+            warmup: None,
+            concurrency: None,
+            rate_limit: None,
+            schema_drift_sample_rate: None,
+            strict_timetable_parsing: false,
+            lenient_station_codes: false,
         };
 
 
@@ -577,6 +769,7 @@ mod tests {
                 &StationCode::new("600012"),
                 [BaseBusRoute::new_from_str("3").unwrap()],
                 &TimetableFetchMode::Manual { next_hours: 12, previous_hours: 12 },
+                9,
             ).unwrap(),
             Url::parse("https://data.lpp.si/api/station/timetable?station-code=600012&next-hours=12&previous-hours=12&route-group-number=3").unwrap()
         );
@@ -587,8 +780,204 @@ mod tests {
                 &StationCode::new("600012"),
                 [BaseBusRoute::new_from_number(3), BaseBusRoute::new_from_number(18)],
                 &TimetableFetchMode::Manual { next_hours: 12, previous_hours: 12 },
+                9,
             ).unwrap(),
             Url::parse("https://data.lpp.si/api/station/timetable?station-code=600012&next-hours=12&previous-hours=12&route-group-number=3&route-group-number=18").unwrap()
         );
+
+        assert_eq!(
+            build_timetable_url(
+                &api_configuration,
+                &StationCode::new("600012"),
+                [BaseBusRoute::new_from_number(3)],
+                &TimetableFetchMode::FullDay,
+                9,
+            ).unwrap(),
+            Url::parse("https://data.lpp.si/api/station/timetable?station-code=600012&next-hours=9&previous-hours=15&route-group-number=3").unwrap()
+        );
+    }
+
+    fn trip_timetable(route: &str, trip_name: &str) -> TripTimetable {
+        TripTimetable {
+            route: BusRoute::from_route_name(route).unwrap(),
+            trip_name: trip_name.to_string(),
+            short_trip_name: None,
+            ends_in_garage: false,
+            timetable: vec![TimetableEntry::new(5, 0).unwrap()],
+            stations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn merge_batched_route_group_timetables() {
+        let first_batch = vec![RouteGroupTimetable {
+            route_group_name: BaseBusRoute::new_from_number(3),
+            trip_timetables: vec![trip_timetable("3G", "BEŽIGRAD - GROSUPLJE")],
+            skipped_trips: Vec::new(),
+        }];
+        let second_batch = vec![
+            RouteGroupTimetable {
+                route_group_name: BaseBusRoute::new_from_number(18),
+                trip_timetables: vec![trip_timetable("18", "KOLODVOR - STANEŽIČE")],
+                skipped_trips: Vec::new(),
+            },
+            RouteGroupTimetable {
+                route_group_name: BaseBusRoute::new_from_number(3),
+                trip_timetables: vec![
+                    trip_timetable("3G", "BEŽIGRAD - GROSUPLJE"),
+                    trip_timetable("3B", "BEŽIGRAD - ŠKOFLJICA"),
+                ],
+                skipped_trips: Vec::new(),
+            },
+        ];
+
+        let merged = merge_route_group_timetables([first_batch, second_batch]);
+
+        assert_eq!(merged.len(), 2);
+
+        assert_eq!(
+            merged[0].route_group_name,
+            BaseBusRoute::new_from_number(3)
+        );
+        let group_3_trips: Vec<String> = merged[0]
+            .trip_timetables
+            .iter()
+            .map(|trip| trip.route.to_string())
+            .collect();
+        assert_eq!(group_3_trips, vec!["3G", "3B"]);
+
+        assert_eq!(
+            merged[1].route_group_name,
+            BaseBusRoute::new_from_number(18)
+        );
+        assert_eq!(merged[1].trip_timetables.len(), 1);
+    }
+
+    #[test]
+    fn resolve_timetable_window_policy() {
+        assert_eq!(
+            TimetableWindowPolicy::FullDay.fetch_mode_at_hour(23),
+            TimetableFetchMode::FullDay
+        );
+
+        assert_eq!(
+            TimetableWindowPolicy::ServiceDayRemaining.fetch_mode_at_hour(23),
+            TimetableFetchMode::Manual {
+                next_hours: 1,
+                previous_hours: 1
+            }
+        );
+        assert_eq!(
+            TimetableWindowPolicy::ServiceDayRemaining.fetch_mode_at_hour(4),
+            TimetableFetchMode::Manual {
+                next_hours: 20,
+                previous_hours: 1
+            }
+        );
+
+        assert_eq!(
+            TimetableWindowPolicy::Custom {
+                next_hours: 6,
+                previous_hours: 2
+            }
+            .fetch_mode_at_hour(12),
+            TimetableFetchMode::Manual {
+                next_hours: 6,
+                previous_hours: 2
+            }
+        );
+    }
+
+    #[test]
+    fn skip_malformed_trips_unless_strict() {
+        let raw_trip_timetable = |parent_name: &str, group_name: &str| RawTripTimetable {
+            timetable: vec![RawTimetableRouteTimetableEntry {
+                hour: 5,
+                minutes: vec![0, 30],
+                is_current: false,
+                timestamp: String::from("2023-11-06T05:00:00"),
+            }],
+            stations: Vec::new(),
+            name: None,
+            parent_name: parent_name.to_string(),
+            group_name: group_name.to_string(),
+            route_number_prefix: String::new(),
+            route_number_suffix: String::from("G"),
+            is_garage: false,
+        };
+
+        let raw_route_group = RawTimetableRouteGroupsData {
+            route_group_number: String::from("3"),
+            routes: vec![
+                raw_trip_timetable("BEŽIGRAD - GROSUPLJE", "3"),
+                raw_trip_timetable("GROSUPLJE - BEŽIGRAD", "three"),
+            ],
+        };
+
+        let route_group = RouteGroupTimetable::from_raw(raw_route_group.clone(), false).unwrap();
+        assert_eq!(route_group.trip_timetables.len(), 1);
+        assert_eq!(route_group.trip_timetables[0].timetable.len(), 2);
+        assert_eq!(route_group.skipped_trips.len(), 1);
+        assert_eq!(
+            route_group.skipped_trips[0].trip_name,
+            "GROSUPLJE - BEŽIGRAD"
+        );
+
+        assert!(RouteGroupTimetable::from_raw(raw_route_group, true).is_err());
+    }
+
+    fn raw_route_group(
+        group_name: String,
+        hours_and_minutes: Vec<(i32, Vec<i32>)>,
+    ) -> RawTimetableRouteGroupsData {
+        RawTimetableRouteGroupsData {
+            route_group_number: String::from("3"),
+            routes: vec![RawTripTimetable {
+                timetable: hours_and_minutes
+                    .into_iter()
+                    .map(
+                        |(hour, minutes)| RawTimetableRouteTimetableEntry {
+                            hour,
+                            minutes,
+                            is_current: false,
+                            timestamp: String::new(),
+                        },
+                    )
+                    .collect(),
+                stations: Vec::new(),
+                name: None,
+                parent_name: String::from("BEŽIGRAD - GROSUPLJE"),
+                group_name,
+                route_number_prefix: String::new(),
+                route_number_suffix: String::new(),
+                is_garage: false,
+            }],
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn timetable_entries_accept_exactly_valid_times(hour in any::<u8>(), minute in any::<u8>()) {
+            let is_valid = (1..=24).contains(&hour) && minute <= 59;
+            prop_assert_eq!(TimetableEntry::new(hour, minute).is_ok(), is_valid);
+        }
+
+        #[test]
+        fn parsing_arbitrary_timetables_never_panics(
+            group_name in "[0-9A-Z]{0,12}",
+            hours_and_minutes in proptest::collection::vec(
+                (-30..60i32, proptest::collection::vec(-10..300i32, 0..5)),
+                0..5,
+            ),
+            strict in any::<bool>(),
+        ) {
+            let raw_route_group = raw_route_group(group_name, hours_and_minutes);
+
+            if let Ok(route_group) = RouteGroupTimetable::from_raw(raw_route_group, strict) {
+                for entry in route_group.trip_timetables.iter().flat_map(|trip| &trip.timetable) {
+                    prop_assert!((1..=24).contains(&entry.hour) && entry.minute <= 59);
+                }
+            }
+        }
     }
 }
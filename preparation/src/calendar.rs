@@ -0,0 +1,242 @@
+use std::collections::HashSet;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use miette::{miette, Result};
+use serde::{Deserialize, Serialize};
+
+
+/// Type of the service day, which determines the timetables LPP runs on a given date.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceDayType {
+    /// Monday to Friday (that isn't a public holiday).
+    Weekday,
+
+    /// Saturday (that isn't a public holiday).
+    Saturday,
+
+    /// Sunday.
+    Sunday,
+
+    /// A public holiday (LPP runs Sunday timetables on these).
+    Holiday,
+}
+
+impl ServiceDayType {
+    /// Whether LPP runs the same timetables on both types of days,
+    /// i.e. they are the same type or one is a Sunday and the other a holiday.
+    pub fn has_same_timetables_as(self, other: ServiceDayType) -> bool {
+        self.timetable_day_type() == other.timetable_day_type()
+    }
+
+    fn timetable_day_type(self) -> ServiceDayType {
+        match self {
+            ServiceDayType::Holiday => ServiceDayType::Sunday,
+            service_day_type => service_day_type,
+        }
+    }
+}
+
+
+/// Holiday entry for Easter Monday, whose date is computed for every year.
+const EASTER_MONDAY_ENTRY: &str = "easter-monday";
+
+/// Slovenian public holidays that are work-free days (as of 2023),
+/// used when no holidays are configured.
+pub const DEFAULT_PUBLIC_HOLIDAYS: &[&str] = &[
+    "01-01",
+    "01-02",
+    "02-08",
+    "04-27",
+    "05-01",
+    "05-02",
+    "06-25",
+    "08-15",
+    "10-31",
+    "11-01",
+    "12-25",
+    "12-26",
+    EASTER_MONDAY_ENTRY,
+];
+
+
+/// A list of public holidays used to determine the [`ServiceDayType`] of a date.
+#[derive(Clone, Default, Debug)]
+pub struct HolidayCalendar {
+    /// Holidays that fall on the same date every year, as `(month, day)`.
+    recurring_holidays: HashSet<(u32, u32)>,
+
+    /// Holidays that only apply to a specific date.
+    one_off_holidays: HashSet<NaiveDate>,
+
+    /// Whether Easter Monday (of every year) is a holiday.
+    includes_easter_monday: bool,
+}
+
+impl HolidayCalendar {
+    /// The calendar of [`DEFAULT_PUBLIC_HOLIDAYS`].
+    pub fn slovenian_default() -> Self {
+        // PANIC SAFETY: The default holidays are all valid entries (see the tests).
+        Self::from_entries(DEFAULT_PUBLIC_HOLIDAYS).unwrap()
+    }
+
+    /// Parses a list of holidays. Each entry is either `MM-DD` for a holiday
+    /// that recurs every year, `YYYY-MM-DD` for a holiday on a specific date
+    /// or `easter-monday` for Easter Monday of every year.
+    pub fn from_entries<I, S>(entries: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut calendar = Self::default();
+
+        for entry in entries {
+            let entry = entry.as_ref().trim();
+
+            if entry == EASTER_MONDAY_ENTRY {
+                calendar.includes_easter_monday = true;
+                continue;
+            }
+
+            if let Ok(date) = NaiveDate::parse_from_str(entry, "%Y-%m-%d") {
+                calendar.one_off_holidays.insert(date);
+                continue;
+            }
+
+            // Parse recurring holidays against a leap year so that 02-29 is accepted.
+            let recurring_date = NaiveDate::parse_from_str(&format!("2000-{}", entry), "%Y-%m-%d")
+                .map_err(|_| {
+                    miette!(
+                        "Invalid holiday \"{}\" (expected MM-DD, YYYY-MM-DD or easter-monday).",
+                        entry
+                    )
+                })?;
+
+            calendar
+                .recurring_holidays
+                .insert((recurring_date.month(), recurring_date.day()));
+        }
+
+        Ok(calendar)
+    }
+
+    pub fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.one_off_holidays.contains(&date)
+            || self
+                .recurring_holidays
+                .contains(&(date.month(), date.day()))
+            || (self.includes_easter_monday
+                && easter_sunday(date.year()).and_then(|sunday| sunday.succ_opt()) == Some(date))
+    }
+
+    pub fn service_day_type(&self, date: NaiveDate) -> ServiceDayType {
+        if self.is_holiday(date) {
+            return ServiceDayType::Holiday;
+        }
+
+        match date.weekday() {
+            Weekday::Sat => ServiceDayType::Saturday,
+            Weekday::Sun => ServiceDayType::Sunday,
+            _ => ServiceDayType::Weekday,
+        }
+    }
+}
+
+/// Computes the date of (Western) Easter Sunday with the anonymous Gregorian algorithm.
+fn easter_sunday(year: i32) -> Option<NaiveDate> {
+    let a = year.rem_euclid(19);
+    let b = year.div_euclid(100);
+    let c = year.rem_euclid(100);
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_service_day_types() {
+        let calendar = HolidayCalendar::from_entries(["12-25", "2024-04-01"]).unwrap();
+
+        let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+
+        assert_eq!(
+            calendar.service_day_type(date(2023, 11, 6)),
+            ServiceDayType::Weekday
+        );
+        assert_eq!(
+            calendar.service_day_type(date(2023, 11, 4)),
+            ServiceDayType::Saturday
+        );
+        assert_eq!(
+            calendar.service_day_type(date(2023, 11, 5)),
+            ServiceDayType::Sunday
+        );
+
+        // Recurring and one-off holidays.
+        assert_eq!(
+            calendar.service_day_type(date(2023, 12, 25)),
+            ServiceDayType::Holiday
+        );
+        assert_eq!(
+            calendar.service_day_type(date(2024, 4, 1)),
+            ServiceDayType::Holiday
+        );
+        assert_eq!(
+            calendar.service_day_type(date(2025, 4, 1)),
+            ServiceDayType::Weekday
+        );
+    }
+
+    #[test]
+    fn default_calendar_includes_easter_monday() {
+        let calendar = HolidayCalendar::slovenian_default();
+
+        let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+
+        for easter_monday in [
+            date(2023, 4, 10),
+            date(2024, 4, 1),
+            date(2025, 4, 21),
+            date(2038, 4, 26),
+        ] {
+            assert_eq!(
+                calendar.service_day_type(easter_monday),
+                ServiceDayType::Holiday
+            );
+        }
+
+        assert_eq!(
+            calendar.service_day_type(date(2024, 4, 2)),
+            ServiceDayType::Weekday
+        );
+        assert_eq!(
+            calendar.service_day_type(date(2023, 6, 25)),
+            ServiceDayType::Holiday
+        );
+
+        assert!(ServiceDayType::Holiday.has_same_timetables_as(ServiceDayType::Sunday));
+        assert!(!ServiceDayType::Holiday.has_same_timetables_as(ServiceDayType::Saturday));
+    }
+
+    #[test]
+    fn reject_invalid_holidays() {
+        assert!(HolidayCalendar::from_entries(["13-01"]).is_err());
+        assert!(HolidayCalendar::from_entries(["christmas"]).is_err());
+        assert!(HolidayCalendar::from_entries(["02-29"]).is_ok());
+    }
+}
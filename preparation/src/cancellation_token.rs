@@ -1,14 +1,18 @@
 use std::sync::{atomic, Arc};
 
+use tokio::sync::Notify;
+
 #[derive(Clone, Debug)]
 pub struct CancellationToken {
     is_cancelled: Arc<atomic::AtomicBool>,
+    cancelled_notify: Arc<Notify>,
 }
 
 impl CancellationToken {
     pub fn new() -> Self {
         Self {
             is_cancelled: Arc::new(atomic::AtomicBool::new(false)),
+            cancelled_notify: Arc::new(Notify::new()),
         }
     }
 
@@ -16,8 +20,68 @@ impl CancellationToken {
         self.is_cancelled.load(atomic::Ordering::SeqCst)
     }
 
-    #[allow(dead_code)]
     pub fn cancel(&self) {
         self.is_cancelled.store(true, atomic::Ordering::SeqCst);
+        self.cancelled_notify.notify_waiters();
+    }
+
+    /// Waits until the token is cancelled (returns immediately if it already is).
+    pub async fn cancelled(&self) {
+        // Created before checking the flag, so a cancellation in between is not missed.
+        let notified = self.cancelled_notify.notified();
+
+        if self.is_cancelled() {
+            return;
+        }
+
+        notified.await;
+    }
+
+    /// Runs `future` to completion, unless the token is cancelled first.
+    /// Returns `None` if it was cancelled (e.g. to cut a sleep short when shutting down).
+    pub async fn run_until_cancelled<F>(&self, future: F) -> Option<F::Output>
+    where
+        F: std::future::Future,
+    {
+        tokio::select! {
+            output = future => Some(output),
+            _ = self.cancelled() => None,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn cut_sleep_short_when_cancelled() {
+        let cancellation_token = CancellationToken::new();
+        assert_eq!(
+            cancellation_token.run_until_cancelled(async { 42 }).await,
+            Some(42)
+        );
+
+        let sleeping_token = cancellation_token.clone();
+        let sleeper = tokio::spawn(async move {
+            sleeping_token
+                .run_until_cancelled(tokio::time::sleep(Duration::from_secs(60 * 60)))
+                .await
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancellation_token.cancel();
+
+        let sleep_output = tokio::time::timeout(Duration::from_secs(1), sleeper)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(sleep_output.is_none());
+
+        // Already cancelled tokens don't wait at all.
+        cancellation_token.cancelled().await;
     }
 }
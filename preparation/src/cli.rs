@@ -1,7 +1,11 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand};
 use miette::{miette, Result};
+use uuid::Uuid;
+
+use crate::configuration::{CaptureMode, ConfigurationOverride};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum RunMode {
@@ -19,12 +23,428 @@ pub struct CLIArgs {
     )]
     pub config_file_path: Option<PathBuf>,
 
+    #[arg(
+        long = "strict-config",
+        global = true,
+        help = "Reject configuration files that contain unknown keys (e.g. typos) \
+                instead of ignoring them."
+    )]
+    pub strict_config: bool,
+
+    #[arg(
+        long = "override",
+        global = true,
+        value_name = "KEY=VALUE",
+        help = "Override a configuration value, e.g. \"lpp.recording.capture_mode=timetables-only\" \
+                (the key is a dot-separated path into the configuration file). \
+                Can be given multiple times."
+    )]
+    pub overrides: Vec<String>,
+
     #[arg(
         long = "run-mode",
         help = "Timetable/station recording mode: \"once\" downloads today's data and exits, \
                 \"perpetual\" keeps downloading it as long as configured (24 hours by default)."
     )]
     pub run_mode: Option<String>,
+
+    #[arg(
+        long = "capture-mode",
+        help = "What each snapshot captures: \"full\" fetches everything, \"timetables-only\" \
+                reuses the latest stored station and route metadata and only fetches timetables. \
+                Overrides the capture_mode configuration option."
+    )]
+    pub capture_mode: Option<String>,
+
+    #[arg(
+        long = "plan-only",
+        help = "Only fetch the station details and print the planned requests (and an estimate \
+                of how long a full snapshot would take), without capturing a snapshot."
+    )]
+    pub plan_only: bool,
+
+    #[command(subcommand)]
+    pub command: Option<CLICommand>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum CLICommand {
+    /// Verify that the stored station and route snapshots
+    /// match their signatures (i.e. were not modified since capture).
+    #[command(name = "verify-signatures")]
+    VerifySignatures(VerifySignaturesArgs),
+
+    /// Check that the stored station and route snapshots are readable and intact,
+    /// moving corrupt ones into the corrupt/ directory of the storage root.
+    #[command(name = "fsck")]
+    Fsck(FsckArgs),
+
+    /// Merge the station and route snapshots of each past month into a single indexed
+    /// pack file (e.g. stations/2023-11.pack), which the other commands read transparently.
+    #[command(name = "compact-archive")]
+    CompactArchive(CompactArchiveArgs),
+
+    /// Browse the stored station and route snapshots in a read-only terminal UI.
+    #[command(name = "explore")]
+    Explore,
+
+    /// Print all log lines of a single snapshot run, found by its run identifier
+    /// (as recorded in the snapshots and in runs.jsonl).
+    #[command(name = "logs-for-run")]
+    LogsForRun(LogsForRunArgs),
+
+    /// Group the routes of the latest route snapshot into families (e.g. 3, 3B and 3G)
+    /// and save where each variant branches off its main route.
+    #[command(name = "route-families")]
+    RouteFamilies(RouteFamiliesArgs),
+
+    /// Measure the distance and scheduled travel time between consecutive stations
+    /// of every trip in the latest route snapshot and save them per trip.
+    #[command(name = "route-segments")]
+    RouteSegments(RouteSegmentsArgs),
+
+    /// Export the code, name, routes and a link (optionally as a QR code) of every station
+    /// in the latest station snapshot, for printing station posters.
+    #[command(name = "station-posters")]
+    StationPosters(StationPostersArgs),
+
+    /// Print all departures from a single station on a single day as an hour × route matrix
+    /// of departure minutes and save it as CSV.
+    #[command(name = "timetable-matrix")]
+    TimetableMatrix(TimetableMatrixArgs),
+
+    /// Print (approximate) statistics of the latest route snapshot,
+    /// optionally parsing only a sample of its routes.
+    #[command(name = "stats")]
+    Stats(StatsArgs),
+
+    /// Render the latest (or past) station and route snapshots into a self-contained HTML page
+    /// (routes, their stations and per-station timetables).
+    #[command(name = "report")]
+    Report(ReportArgs),
+
+    /// Evaluate a jq expression (e.g. ".station_details | length") against the latest
+    /// (or a given) snapshot and print its outputs as JSON.
+    #[command(name = "query")]
+    Query(QueryArgs),
+
+    /// Continuously poll the arrivals of all trips of a route and show them
+    /// in a live-updating terminal view (stations, vehicles and their ETAs).
+    #[command(name = "watch")]
+    Watch(WatchArgs),
+
+    /// Probe every LPP API endpoint once (the pre-flight probe of each snapshot)
+    /// and print whether its response could still be parsed.
+    #[command(name = "doctor")]
+    Doctor(DoctorArgs),
+
+    /// Print the JSON Schemas of the LPP API responses we expect (as described
+    /// by the structures they are parsed into), e.g. to compare them over time.
+    #[command(name = "schema-dump")]
+    SchemaDump(SchemaDumpArgs),
+
+    /// Export the timetables of the latest route snapshot and the recorded arrivals
+    /// into a NetCDF-4 (HDF5) file for scientific tools (MATLAB, h5py, xarray, ...).
+    #[cfg(feature = "hdf5-export")]
+    #[command(name = "export-hdf5")]
+    ExportHdf5(ExportHdf5Args),
+
+    /// Configuration file utilities.
+    #[command(name = "config")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Print the expected structure of the configuration file.
+    #[command(name = "schema")]
+    Schema(ConfigSchemaArgs),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ConfigSchemaArgs {
+    #[arg(
+        long = "json",
+        help = "Print the schema as JSON Schema instead of an annotated TOML outline."
+    )]
+    pub json: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct VerifySignaturesArgs {
+    #[arg(
+        long = "public-key-file-path",
+        help = "File path of the hex-encoded Ed25519 public key to verify with. If unspecified, \
+                this defaults to the snapshot-signing-key.pub file in the storage directory."
+    )]
+    pub public_key_file_path: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct FsckArgs {
+    #[arg(
+        long = "since",
+        help = "Only check snapshots modified within this duration (e.g. \"48hours\"). \
+                If unspecified, all snapshots are checked."
+    )]
+    pub since: Option<String>,
+
+    #[arg(
+        long = "dry-run",
+        help = "Only report corrupt snapshots instead of moving them into the corrupt/ directory."
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CompactArchiveArgs {
+    #[arg(
+        long = "dry-run",
+        help = "Only report which snapshots would be packed instead of packing them."
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct LogsForRunArgs {
+    #[arg(help = "Identifier of the snapshot run, e.g. \"67e55044-10b1-426f-9247-bb680e5fe0c8\".")]
+    pub run_id: Uuid,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RouteFamiliesArgs {
+    #[arg(
+        long = "output-file-path",
+        help = "File path to save the route families to. If unspecified, \
+                this defaults to the route_families.json file in the storage directory."
+    )]
+    pub output_file_path: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RouteSegmentsArgs {
+    #[arg(
+        long = "output-directory-path",
+        help = "Directory to save the segments to (as {trip_id}/segments.json). If unspecified, \
+                this defaults to the segments directory in the storage directory."
+    )]
+    pub output_directory_path: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct StationPostersArgs {
+    #[arg(
+        long = "url-template",
+        help = "Link to each station, with {station_code} in place of the station code, \
+                e.g. \"https://example.com/?station={station_code}\"."
+    )]
+    pub url_template: String,
+
+    #[arg(
+        long = "qr-codes",
+        help = "Also render the link of each station as a QR code (qr/{station_code}.svg \
+                in the output directory)."
+    )]
+    pub qr_codes: bool,
+
+    #[arg(
+        long = "output-directory-path",
+        help = "Directory to save the station posters to. If unspecified, \
+                this defaults to the station-posters directory in the storage directory."
+    )]
+    pub output_directory_path: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct TimetableMatrixArgs {
+    #[arg(help = "Code of the station, e.g. \"600011\".")]
+    pub station_code: String,
+
+    #[arg(
+        long = "date",
+        help = "Service date whose timetables to use (e.g. \"2023-11-06\"), \
+                i.e. the latest station snapshot captured for it. If there is none, the latest \
+                snapshot of a day with the same timetables (weekday, Saturday, or Sunday and holiday) \
+                is used. \
+                If unspecified, the latest station snapshot is used."
+    )]
+    pub date: Option<NaiveDate>,
+
+    #[arg(
+        long = "output-file-path",
+        help = "File path to save the CSV matrix to. If unspecified, this defaults to \
+                {station_code}_{date}.csv in the timetable-matrices directory of the storage directory."
+    )]
+    pub output_file_path: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct StatsArgs {
+    #[arg(
+        long = "sample",
+        default_value_t = 1.0,
+        help = "Fraction of routes to parse (from 0.0 to 1.0), e.g. 0.1 parses every 10th route. \
+                Statistics are extrapolated from the sample. Defaults to parsing all routes."
+    )]
+    pub sample: f64,
+
+    #[arg(
+        long = "seed",
+        help = "If set, routes are sampled randomly (reproducibly for the same seed) \
+                instead of taking every n-th route."
+    )]
+    pub seed: Option<u64>,
+
+    #[arg(
+        long = "mmap",
+        help = "Memory-map the snapshot file instead of reading it through a buffer. \
+                Faster for large uncompressed snapshots, but the file must not be modified \
+                while the statistics are computed."
+    )]
+    pub mmap: bool,
+
+    #[arg(
+        long = "file-path",
+        help = "File path of the route snapshot to compute statistics of. If unspecified, \
+                the latest route snapshot in the storage directory is used."
+    )]
+    pub file_path: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ReportArgs {
+    #[arg(
+        long = "at",
+        help = "Render the network as it was at this time (RFC 3339, e.g. \"2023-11-06T12:00:00Z\"), \
+                i.e. from the latest snapshots captured at or before it. \
+                If unspecified, the latest snapshots are used."
+    )]
+    pub at: Option<DateTime<Utc>>,
+
+    #[arg(
+        long = "output-file-path",
+        help = "File path to save the HTML report to. If unspecified, \
+                this defaults to the report.html file in the storage directory."
+    )]
+    pub output_file_path: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct QueryArgs {
+    #[arg(
+        long = "expr",
+        help = "jq expression to evaluate, e.g. \
+                \"[.station_details[] | select(.routes_on_station | index(\\\"6B\\\"))] | length\"."
+    )]
+    pub expression: String,
+
+    #[arg(
+        long = "routes",
+        help = "Query the latest route snapshot instead of the latest station snapshot."
+    )]
+    pub routes: bool,
+
+    #[arg(
+        long = "file-path",
+        conflicts_with = "routes",
+        help = "File path of the (station or route) snapshot to query. If unspecified, \
+                the latest snapshot in the storage directory is used."
+    )]
+    pub file_path: Option<PathBuf>,
+
+    #[arg(
+        long = "near",
+        conflicts_with = "routes",
+        help = "Only keep the stations near this location (\"<latitude>,<longitude>\", \
+                e.g. \"46.0512,14.5060\") in the station snapshot, nearest first. \
+                Each station also gets a `distance_in_meters` field."
+    )]
+    pub near: Option<String>,
+
+    #[arg(
+        long = "radius",
+        default_value_t = 300.0,
+        requires = "near",
+        help = "Radius around the `--near` location in meters."
+    )]
+    pub radius_in_meters: f64,
+
+    #[arg(long = "pretty", help = "Pretty-print the JSON outputs.")]
+    pub pretty: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct WatchArgs {
+    #[arg(help = "Route to watch, e.g. \"6B\".")]
+    pub route: String,
+
+    #[arg(
+        long = "refresh-interval",
+        default_value = "15s",
+        help = "How often the arrivals are requested again (e.g. \"15s\")."
+    )]
+    pub refresh_interval: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DoctorArgs {
+    #[arg(
+        long = "station-code",
+        help = "Code of the station to probe, e.g. \"600011\". \
+                If unspecified, the station_code of the preflight configuration is used."
+    )]
+    pub station_code: Option<String>,
+}
+
+#[cfg(feature = "hdf5-export")]
+#[derive(Args, Debug, Clone)]
+pub struct ExportHdf5Args {
+    #[arg(
+        long = "since",
+        help = "Only export arrivals captured at or after this time (RFC 3339, e.g. \"2023-11-06T00:00:00Z\")."
+    )]
+    pub since: Option<DateTime<Utc>>,
+
+    #[arg(
+        long = "until",
+        help = "Only export arrivals captured before this time (RFC 3339, e.g. \"2023-11-07T00:00:00Z\")."
+    )]
+    pub until: Option<DateTime<Utc>>,
+
+    #[arg(
+        long = "compression-level",
+        default_value_t = 4,
+        help = "Deflate compression level of numeric datasets, from 0 (no compression) to 9."
+    )]
+    pub compression_level: u8,
+
+    #[arg(
+        long = "chunk-size",
+        default_value_t = 4096,
+        help = "Maximum number of values per chunk of numeric datasets."
+    )]
+    pub chunk_size: usize,
+
+    #[arg(
+        long = "output-file-path",
+        help = "File path to save the export to. If unspecified, \
+                this defaults to the lpp-export.nc file in the storage directory."
+    )]
+    pub output_file_path: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SchemaDumpArgs {
+    #[arg(
+        long = "output-directory-path",
+        help = "Directory to save the schemas to, one {name}.schema.json file per endpoint. \
+                If unspecified, all schemas are printed to standard output as a single JSON object, \
+                keyed by endpoint."
+    )]
+    pub output_directory_path: Option<PathBuf>,
 }
 
 impl CLIArgs {
@@ -41,4 +461,25 @@ impl CLIArgs {
             None => Ok(RunMode::Once),
         }
     }
+
+    pub fn configuration_overrides(&self) -> Result<Vec<ConfigurationOverride>> {
+        self.overrides
+            .iter()
+            .map(|raw_override| ConfigurationOverride::parse(raw_override))
+            .collect()
+    }
+
+    pub fn capture_mode(&self) -> Result<Option<CaptureMode>> {
+        match &self.capture_mode {
+            Some(capture_mode) => match capture_mode.to_lowercase().as_str() {
+                "full" => Ok(Some(CaptureMode::Full)),
+                "timetables-only" => Ok(Some(CaptureMode::TimetablesOnly)),
+                invalid_mode => Err(miette!(
+                    "Invalid capture mode: {} (expected full/timetables-only).",
+                    invalid_mode
+                )),
+            },
+            None => Ok(None),
+        }
+    }
 }
@@ -0,0 +1,179 @@
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use chrono::{DateTime, FixedOffset, Local, Utc};
+
+/// A future returned by [`Clock::sleep`].
+pub type ClockSleep<'c> = Pin<Box<dyn Future<Output = ()> + Send + 'c>>;
+
+/// A clock that can be shared between the recorder tasks.
+pub type SharedClock = Arc<dyn Clock>;
+
+
+/// Source of the current time for the recorder: the capture schedule, service day and
+/// timetable window detection, and snapshot timestamps (which the file names are based on)
+/// all read the time through a `Clock`, so tests can control it.
+pub trait Clock: Send + Sync {
+    /// The current time.
+    fn now(&self) -> DateTime<Utc>;
+
+    /// The current local time (in Ljubljana, when recording in production).
+    /// Timetables and service days follow the local time.
+    fn local_now(&self) -> DateTime<FixedOffset>;
+
+    /// Waits for the given duration to pass.
+    fn sleep(&self, duration: Duration) -> ClockSleep<'_>;
+}
+
+
+/// The system clock, with local time in the system time zone.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SystemClock;
+
+impl SystemClock {
+    pub fn shared() -> SharedClock {
+        Arc::new(Self)
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn local_now(&self) -> DateTime<FixedOffset> {
+        Local::now().fixed_offset()
+    }
+
+    fn sleep(&self, duration: Duration) -> ClockSleep<'_> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+
+/// A clock that only moves when told to. Sleeping advances the clock instantly
+/// (plus any pending suspension, see [`ManualClock::suspend_during_next_sleep`]).
+#[cfg(test)]
+#[derive(Debug)]
+pub struct ManualClock {
+    state: std::sync::Mutex<ManualClockState>,
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+struct ManualClockState {
+    now: DateTime<Utc>,
+    utc_offset: FixedOffset,
+    pending_suspension: Option<chrono::Duration>,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new(now: DateTime<FixedOffset>) -> Arc<Self> {
+        Arc::new(Self {
+            state: std::sync::Mutex::new(ManualClockState {
+                now: now.with_timezone(&Utc),
+                utc_offset: *now.offset(),
+                pending_suspension: None,
+            }),
+        })
+    }
+
+    pub fn advance(&self, duration: chrono::Duration) {
+        self.state.lock().unwrap().now += duration;
+    }
+
+    /// Changes the local UTC offset, e.g. for a daylight saving time transition.
+    pub fn set_utc_offset(&self, utc_offset: FixedOffset) {
+        self.state.lock().unwrap().utc_offset = utc_offset;
+    }
+
+    /// Simulates the system being suspended for `duration` during the next sleep.
+    pub fn suspend_during_next_sleep(&self, duration: chrono::Duration) {
+        self.state.lock().unwrap().pending_suspension = Some(duration);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.state.lock().unwrap().now
+    }
+
+    fn local_now(&self) -> DateTime<FixedOffset> {
+        let state = self.state.lock().unwrap();
+        state.now.with_timezone(&state.utc_offset)
+    }
+
+    fn sleep(&self, duration: Duration) -> ClockSleep<'_> {
+        let mut state = self.state.lock().unwrap();
+
+        let suspension = state
+            .pending_suspension
+            .take()
+            .unwrap_or_else(chrono::Duration::zero);
+        state.now = state.now
+            + chrono::Duration::from_std(duration).expect("sleep duration is out of range")
+            + suspension;
+
+        Box::pin(std::future::ready(()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn advance_manual_clock_explicitly_and_by_sleeping() {
+        let ljubljana_summer_offset = FixedOffset::east_opt(2 * 3600).unwrap();
+        let clock = ManualClock::new(
+            ljubljana_summer_offset
+                .with_ymd_and_hms(2024, 6, 3, 4, 30, 0)
+                .unwrap(),
+        );
+        let started_at = clock.now();
+
+        clock.advance(chrono::Duration::minutes(15));
+        assert_eq!(clock.now() - started_at, chrono::Duration::minutes(15));
+        assert_eq!(
+            clock.local_now(),
+            ljubljana_summer_offset
+                .with_ymd_and_hms(2024, 6, 3, 4, 45, 0)
+                .unwrap()
+        );
+
+        clock.suspend_during_next_sleep(chrono::Duration::hours(1));
+        clock.sleep(Duration::from_secs(60)).await;
+        assert_eq!(
+            clock.now() - started_at,
+            chrono::Duration::minutes(76)
+        );
+
+        // The suspension only applies to a single sleep.
+        clock.sleep(Duration::from_secs(60)).await;
+        assert_eq!(
+            clock.now() - started_at,
+            chrono::Duration::minutes(77)
+        );
+    }
+
+    #[tokio::test]
+    async fn system_clock_never_goes_back_across_a_sleep() {
+        let clock = SystemClock;
+
+        let mut previous_now = clock.now();
+        for _ in 0..100 {
+            let now = clock.now();
+            assert!(now >= previous_now);
+            previous_now = now;
+        }
+
+        // Capture scheduling relies on a sleep lasting at least as long as requested.
+        let before_sleep = clock.now();
+        clock.sleep(Duration::from_millis(20)).await;
+        assert!(clock.now() - before_sleep >= chrono::Duration::milliseconds(20));
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use tracing::{info, warn};
+
+use super::list_json_files;
+use crate::{
+    cli::CompactArchiveArgs,
+    configuration::Configuration,
+    recorder::formats::{load_snapshot_from_bytes, Snapshot},
+    signing::signature_file_path,
+    storage::{SnapshotPack, SnapshotPackWriter, PACK_FILE_EXTENSION},
+};
+
+/// Format of the month in pack file names, e.g. `2023-11`.
+const PACK_MONTH_FORMAT: &str = "%Y-%m";
+
+
+/// Packs the station and route snapshots of each past month into a single pack file
+/// (`{month}.pack` in the same directory), deleting the packed files and their signatures.
+///
+/// The current month and the latest snapshot of each kind are never packed, so the recorder
+/// can keep appending to the directories. Snapshots that can't be read are left alone (see `fsck`).
+pub fn run_compact_archive(
+    configuration: &Configuration,
+    arguments: CompactArchiveArgs,
+) -> Result<()> {
+    let storage_root = &configuration.lpp.recording.recording_storage_root;
+
+    let station_storage = storage_root
+        .stations()
+        .wrap_err_with(|| miette!("Failed to open station storage."))?;
+    let route_storage = storage_root
+        .routes()
+        .wrap_err_with(|| miette!("Failed to open route storage."))?;
+
+    let snapshot_directories = [
+        (
+            station_storage.directory_path().to_path_buf(),
+            station_storage
+                .latest()
+                .wrap_err_with(|| miette!("Failed to read latest station snapshot pointer."))?,
+        ),
+        (
+            route_storage.directory_path().to_path_buf(),
+            route_storage
+                .latest()
+                .wrap_err_with(|| miette!("Failed to read latest route snapshot pointer."))?,
+        ),
+    ];
+
+    let current_month = Utc::now().format(PACK_MONTH_FORMAT).to_string();
+
+    let mut number_of_packed_snapshots: usize = 0;
+    let mut number_of_packs: usize = 0;
+
+    for (snapshot_directory, latest_snapshot_file_path) in snapshot_directories {
+        let snapshots_by_month = group_snapshots_by_month(
+            &snapshot_directory,
+            latest_snapshot_file_path.as_deref(),
+        )?;
+
+        for (month, snapshot_file_paths) in snapshots_by_month {
+            if month >= current_month {
+                continue;
+            }
+
+            let pack_file_path =
+                snapshot_directory.join(format!("{}.{}", month, PACK_FILE_EXTENSION));
+
+            if arguments.dry_run {
+                info!(
+                    pack_file_path = %pack_file_path.display(),
+                    snapshots = snapshot_file_paths.len(),
+                    "Would pack snapshots (dry run)."
+                );
+            } else {
+                pack_snapshots(&pack_file_path, &snapshot_file_paths).wrap_err_with(|| {
+                    miette!(
+                        "Failed to pack snapshots into {}.",
+                        pack_file_path.display()
+                    )
+                })?;
+
+                info!(
+                    pack_file_path = %pack_file_path.display(),
+                    snapshots = snapshot_file_paths.len(),
+                    "Packed snapshots."
+                );
+            }
+
+            number_of_packed_snapshots += snapshot_file_paths.len();
+            number_of_packs += 1;
+        }
+    }
+
+    info!(
+        packed_snapshots = number_of_packed_snapshots,
+        packs = number_of_packs,
+        dry_run = arguments.dry_run,
+        "Finished compacting snapshot archive."
+    );
+
+    Ok(())
+}
+
+
+/// Groups the standalone snapshot files in the directory by the month they were captured in
+/// (in UTC), leaving out the latest snapshot and the files that can't be read.
+fn group_snapshots_by_month(
+    snapshot_directory: &Path,
+    latest_snapshot_file_path: Option<&Path>,
+) -> Result<BTreeMap<String, Vec<PathBuf>>> {
+    let mut snapshots_by_month: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+
+    for snapshot_file_path in list_json_files(snapshot_directory)? {
+        if Some(snapshot_file_path.as_path()) == latest_snapshot_file_path {
+            continue;
+        }
+
+        let captured_at = match fs::read(&snapshot_file_path)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| {
+                load_snapshot_from_bytes(&contents).map_err(|error| error.to_string())
+            }) {
+            Ok(loaded_snapshot) => match loaded_snapshot.snapshot {
+                Snapshot::Stations(snapshot) => snapshot.captured_at,
+                Snapshot::Routes(snapshot) => snapshot.captured_at,
+            },
+            Err(error) => {
+                warn!(
+                    file_path = %snapshot_file_path.display(),
+                    error = error,
+                    "Snapshot can't be read, will not pack it (run fsck to quarantine it)."
+                );
+                continue;
+            }
+        };
+
+        snapshots_by_month
+            .entry(captured_at.format(PACK_MONTH_FORMAT).to_string())
+            .or_default()
+            .push(snapshot_file_path);
+    }
+
+    Ok(snapshots_by_month)
+}
+
+/// Writes the snapshots (along with the snapshots of an existing pack of the same month)
+/// into a new pack, then deletes the packed files and their signature files.
+fn pack_snapshots(pack_file_path: &Path, snapshot_file_paths: &[PathBuf]) -> Result<()> {
+    let existing_pack = match SnapshotPack::open(pack_file_path) {
+        Ok(pack) => Some(pack),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+        Err(error) => {
+            return Err(error)
+                .into_diagnostic()
+                .wrap_err_with(|| miette!("Failed to open existing pack."))
+        }
+    };
+
+    let mut pack_writer = SnapshotPackWriter::create(pack_file_path)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to create pack."))?;
+
+    if let Some(existing_pack) = &existing_pack {
+        for packed_snapshot in existing_pack.snapshots() {
+            let contents = existing_pack
+                .read(packed_snapshot)
+                .into_diagnostic()
+                .wrap_err_with(|| miette!("Failed to read snapshot from existing pack."))?;
+
+            pack_writer
+                .add(
+                    packed_snapshot.file_name.clone(),
+                    packed_snapshot.modified_at,
+                    packed_snapshot.signature.clone(),
+                    &contents,
+                )
+                .into_diagnostic()
+                .wrap_err_with(|| miette!("Failed to write snapshot into pack."))?;
+        }
+    }
+
+    for snapshot_file_path in snapshot_file_paths {
+        let file_name = snapshot_file_path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        // A snapshot can already be packed if an earlier compaction
+        // was interrupted before it deleted the packed files.
+        if pack_writer.contains(&file_name) {
+            continue;
+        }
+
+        let contents = fs::read(snapshot_file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to read snapshot file."))?;
+
+        let modified_at: DateTime<Utc> = fs::metadata(snapshot_file_path)
+            .and_then(|metadata| metadata.modified())
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to read snapshot file modification time."))?
+            .into();
+
+        let signature_path = signature_file_path(snapshot_file_path);
+        let signature = match signature_path.is_file() {
+            true => Some(
+                fs::read_to_string(&signature_path)
+                    .into_diagnostic()
+                    .wrap_err_with(|| miette!("Failed to read signature file."))?,
+            ),
+            false => None,
+        };
+
+        pack_writer
+            .add(file_name, modified_at, signature, &contents)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to write snapshot into pack."))?;
+    }
+
+    pack_writer
+        .finish()
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to finish pack."))?;
+
+    for snapshot_file_path in snapshot_file_paths {
+        fs::remove_file(snapshot_file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to remove packed snapshot file."))?;
+
+        let signature_path = signature_file_path(snapshot_file_path);
+        if signature_path.is_file() {
+            fs::remove_file(&signature_path)
+                .into_diagnostic()
+                .wrap_err_with(|| miette!("Failed to remove packed signature file."))?;
+        }
+    }
+
+    Ok(())
+}
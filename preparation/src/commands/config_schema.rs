@@ -0,0 +1,24 @@
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use crate::{
+    cli::ConfigSchemaArgs,
+    configuration::schema::{configuration_schema, render_configuration_schema_as_toml},
+};
+
+
+/// Prints the expected structure of the configuration file to standard output.
+pub fn run_config_schema(arguments: ConfigSchemaArgs) -> Result<()> {
+    let schema = configuration_schema();
+
+    if arguments.json {
+        let json_schema = serde_json::to_string_pretty(&schema)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to serialize configuration schema."))?;
+
+        println!("{}", json_schema);
+    } else {
+        print!("{}", render_configuration_schema_as_toml(&schema));
+    }
+
+    Ok(())
+}
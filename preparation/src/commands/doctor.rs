@@ -0,0 +1,51 @@
+use miette::{miette, Context, IntoDiagnostic, Result};
+use reqwest::Client;
+use tracing::info;
+
+use crate::{
+    api::{client::LppApiClient, StationCode},
+    cli::DoctorArgs,
+    clock::SystemClock,
+    configuration::Configuration,
+    recorder::preflight::run_preflight_probe,
+};
+
+
+/// Runs the pre-flight probe of the LPP API and prints the outcome of every endpoint,
+/// failing if any of them failed.
+pub async fn run_doctor(configuration: &Configuration, arguments: DoctorArgs) -> Result<()> {
+    let station_code = match arguments.station_code {
+        Some(station_code) => StationCode::parse(station_code)?,
+        None => configuration.lpp.recording.preflight.station_code.clone(),
+    };
+
+    let http_client = Client::builder()
+        .user_agent(&configuration.lpp.api.user_agent)
+        .build()
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to build HTTP client."))?;
+
+    let api_client = LppApiClient::new(http_client, None, None, None, None, None);
+
+    info!(
+        station_code = %station_code,
+        "Probing every LPP API endpoint."
+    );
+
+    let preflight_report = run_preflight_probe(
+        &configuration.lpp.api,
+        &api_client,
+        &SystemClock,
+        &station_code,
+    )
+    .await;
+
+    println!("{}", preflight_report.to_console_table());
+
+    if preflight_report.is_healthy() {
+        info!("All LPP API endpoints responded as expected.");
+    }
+
+    preflight_report.into_result()?;
+    Ok(())
+}
@@ -0,0 +1,524 @@
+use std::{path::PathBuf, rc::Rc};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use ratatui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+use super::terminal::TerminalGuard;
+use crate::{
+    api::timetable::{RouteGroupTimetable, TimetableEntry, TripTimetable},
+    configuration::Configuration,
+    recorder::formats::{AllRoutesSnapshot, AllStationsSnapshot},
+    storage::{ArchivableSnapshot, ArchivedSnapshots, SnapshotArchive},
+};
+
+
+/// Opens a read-only terminal UI for browsing the station and route snapshots in the storage root.
+pub fn run_explore(configuration: &Configuration) -> Result<()> {
+    let snapshot_archive =
+        SnapshotArchive::open(&configuration.lpp.recording.recording_storage_root)
+            .wrap_err_with(|| miette!("Failed to open snapshot archive."))?;
+
+    let mut snapshot_entries: Vec<SnapshotEntry> = Vec::new();
+    snapshot_entries.extend(snapshot_entries_of(
+        SnapshotKind::Stations,
+        snapshot_archive
+            .station_snapshots()
+            .wrap_err_with(|| miette!("Failed to list station details snapshots."))?,
+    ));
+    snapshot_entries.extend(snapshot_entries_of(
+        SnapshotKind::Routes,
+        snapshot_archive
+            .route_snapshots()
+            .wrap_err_with(|| miette!("Failed to list route details snapshots."))?,
+    ));
+
+    // Newest first, as that's usually the one we want to look at.
+    snapshot_entries.sort_by(|first, second| second.file_name.cmp(&first.file_name));
+
+    let mut terminal = TerminalGuard::enter()?;
+    let mut explorer = Explorer::new(snapshot_entries);
+
+    loop {
+        terminal
+            .terminal
+            .draw(|frame| explorer.draw(frame))
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to draw terminal UI."))?;
+
+        let event = event::read()
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to read terminal event."))?;
+
+        let Event::Key(key_event) = event else {
+            continue;
+        };
+        if key_event.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        if !explorer.handle_key(key_event.code) {
+            return Ok(());
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SnapshotKind {
+    Stations,
+    Routes,
+}
+
+struct SnapshotEntry {
+    kind: SnapshotKind,
+    file_path: PathBuf,
+    file_name: String,
+}
+
+fn snapshot_entries_of<S>(kind: SnapshotKind, snapshots: ArchivedSnapshots<S>) -> Vec<SnapshotEntry>
+where
+    S: ArchivableSnapshot,
+{
+    snapshots
+        .file_paths()
+        .iter()
+        .map(|file_path| SnapshotEntry {
+            kind,
+            file_path: file_path.clone(),
+            file_name: file_path
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+
+/// A selectable list of items, along with what selecting an item opens.
+struct ListView {
+    title: String,
+    items: Vec<String>,
+    state: ListState,
+    kind: ListViewKind,
+}
+
+enum ListViewKind {
+    Snapshots(Vec<SnapshotEntry>),
+    Stations(Rc<AllStationsSnapshot>),
+    Routes(Rc<AllRoutesSnapshot>),
+    StationsOnRoute {
+        snapshot: Rc<AllRoutesSnapshot>,
+        route_index: usize,
+    },
+}
+
+impl ListView {
+    fn new(title: String, items: Vec<String>, kind: ListViewKind) -> Self {
+        let mut state = ListState::default();
+        if !items.is_empty() {
+            state.select(Some(0));
+        }
+
+        Self {
+            title,
+            items,
+            state,
+            kind,
+        }
+    }
+
+    fn move_selection(&mut self, offset: isize) {
+        if self.items.is_empty() {
+            return;
+        }
+
+        let selected = self.state.selected().unwrap_or_default() as isize;
+        let new_selected = (selected + offset).clamp(0, self.items.len() as isize - 1);
+
+        self.state.select(Some(new_selected as usize));
+    }
+}
+
+/// A scrollable block of text (e.g. a timetable grid).
+struct TextView {
+    title: String,
+    lines: Vec<String>,
+    scroll: u16,
+}
+
+enum View {
+    List(ListView),
+    Text(TextView),
+}
+
+
+/// State of the explorer: a stack of views, the last of which is shown.
+struct Explorer {
+    views: Vec<View>,
+    status: String,
+}
+
+impl Explorer {
+    fn new(snapshot_entries: Vec<SnapshotEntry>) -> Self {
+        let items = snapshot_entries
+            .iter()
+            .map(|entry| {
+                let kind = match entry.kind {
+                    SnapshotKind::Stations => "stations",
+                    SnapshotKind::Routes => "routes  ",
+                };
+
+                format!("{}  {}", kind, entry.file_name)
+            })
+            .collect();
+
+        Self {
+            views: vec![View::List(ListView::new(
+                String::from("Snapshots"),
+                items,
+                ListViewKind::Snapshots(snapshot_entries),
+            ))],
+            status: String::new(),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(frame.size());
+
+        // PANIC SAFETY: The first view is never popped.
+        match self.views.last_mut().unwrap() {
+            View::List(list_view) => {
+                let list = List::new(
+                    list_view
+                        .items
+                        .iter()
+                        .map(|item| ListItem::new(item.as_str()))
+                        .collect::<Vec<_>>(),
+                )
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(list_view.title.as_str()),
+                )
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                .highlight_symbol("> ");
+
+                frame.render_stateful_widget(list, areas[0], &mut list_view.state);
+            }
+            View::Text(text_view) => {
+                let paragraph = Paragraph::new(
+                    text_view
+                        .lines
+                        .iter()
+                        .map(|line| Line::from(line.as_str()))
+                        .collect::<Vec<_>>(),
+                )
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(text_view.title.as_str()),
+                )
+                .scroll((text_view.scroll, 0));
+
+                frame.render_widget(paragraph, areas[0]);
+            }
+        }
+
+        let help = "↑/↓ move   PgUp/PgDn page   Enter open   Esc back   q quit";
+        let status = match self.status.is_empty() {
+            true => help.to_string(),
+            false => format!("{}   |   {}", self.status, help),
+        };
+
+        frame.render_widget(Paragraph::new(status), areas[1]);
+    }
+
+    /// Returns `false` once the explorer should exit.
+    fn handle_key(&mut self, key_code: KeyCode) -> bool {
+        let offset = match key_code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Esc | KeyCode::Backspace | KeyCode::Left => {
+                if self.views.len() > 1 {
+                    self.views.pop();
+                }
+                return true;
+            }
+            KeyCode::Enter | KeyCode::Right => {
+                self.open_selected();
+                return true;
+            }
+            KeyCode::Up | KeyCode::Char('k') => -1,
+            KeyCode::Down | KeyCode::Char('j') => 1,
+            KeyCode::PageUp => -20,
+            KeyCode::PageDown => 20,
+            _ => return true,
+        };
+
+        // PANIC SAFETY: The first view is never popped.
+        match self.views.last_mut().unwrap() {
+            View::List(list_view) => list_view.move_selection(offset),
+            View::Text(text_view) => {
+                let max_scroll = text_view.lines.len().saturating_sub(1) as isize;
+                text_view.scroll = (text_view.scroll as isize + offset).clamp(0, max_scroll) as u16;
+            }
+        }
+
+        true
+    }
+
+    fn open_selected(&mut self) {
+        // PANIC SAFETY: The first view is never popped.
+        let View::List(list_view) = self.views.last().unwrap() else {
+            return;
+        };
+        let Some(selected) = list_view.state.selected() else {
+            return;
+        };
+
+        let new_view = match &list_view.kind {
+            ListViewKind::Snapshots(snapshot_entries) => open_snapshot(&snapshot_entries[selected]),
+            ListViewKind::Stations(snapshot) => Ok(station_timetable_view(snapshot, selected)),
+            ListViewKind::Routes(snapshot) => Ok(stations_on_route_view(snapshot, selected)),
+            ListViewKind::StationsOnRoute {
+                snapshot,
+                route_index,
+            } => Ok(trip_timetable_view(
+                snapshot,
+                *route_index,
+                selected,
+            )),
+        };
+
+        match new_view {
+            Ok(view) => {
+                self.status.clear();
+                self.views.push(view);
+            }
+            Err(error) => self.status = error,
+        }
+    }
+}
+
+
+fn open_snapshot(entry: &SnapshotEntry) -> Result<View, String> {
+    match entry.kind {
+        SnapshotKind::Stations => {
+            let snapshot =
+                ArchivedSnapshots::<AllStationsSnapshot>::read_snapshot(entry.file_path.clone())
+                    .map_err(|error| error.to_string())?
+                    .snapshot;
+
+            let items = snapshot
+                .station_details
+                .iter()
+                .map(|station| {
+                    format!(
+                        "{:<8} {:<32} {:>3} route group(s)",
+                        station.station_code.to_string(),
+                        station.name,
+                        station.timetables.len()
+                    )
+                })
+                .collect();
+
+            Ok(View::List(ListView::new(
+                format!(
+                    "{} ({})",
+                    entry.file_name,
+                    snapshot.service_day_description()
+                ),
+                items,
+                ListViewKind::Stations(Rc::new(snapshot)),
+            )))
+        }
+        SnapshotKind::Routes => {
+            let snapshot =
+                ArchivedSnapshots::<AllRoutesSnapshot>::read_snapshot(entry.file_path.clone())
+                    .map_err(|error| error.to_string())?
+                    .snapshot;
+
+            let items = snapshot
+                .routes
+                .iter()
+                .map(|route| {
+                    format!(
+                        "{:<5} {:<48} {:>3} station(s)",
+                        route.route_details.route.to_string(),
+                        route.route_details.name,
+                        route.stations_on_route_with_timetables.len()
+                    )
+                })
+                .collect();
+
+            Ok(View::List(ListView::new(
+                format!(
+                    "{} ({})",
+                    entry.file_name,
+                    snapshot.service_day_description()
+                ),
+                items,
+                ListViewKind::Routes(Rc::new(snapshot)),
+            )))
+        }
+    }
+}
+
+fn station_timetable_view(snapshot: &AllStationsSnapshot, station_index: usize) -> View {
+    let station = &snapshot.station_details[station_index];
+
+    let mut lines = Vec::new();
+    for route_group in &station.timetables {
+        lines.extend(route_group_lines(route_group));
+    }
+
+    View::Text(TextView {
+        title: format!("{} ({})", station.name, station.station_code),
+        lines,
+        scroll: 0,
+    })
+}
+
+fn route_group_lines(route_group: &RouteGroupTimetable) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for trip_timetable in &route_group.trip_timetables {
+        lines.extend(trip_timetable_lines(trip_timetable));
+        lines.push(String::new());
+    }
+
+    for skipped_trip in &route_group.skipped_trips {
+        lines.push(format!(
+            "{} (skipped: {})",
+            skipped_trip.trip_name, skipped_trip.reason
+        ));
+        lines.push(String::new());
+    }
+
+    lines
+}
+
+fn trip_timetable_lines(trip_timetable: &TripTimetable) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{}  {}{}",
+        trip_timetable.route,
+        trip_timetable.trip_name,
+        match trip_timetable.ends_in_garage {
+            true => " (ends in garage)",
+            false => "",
+        }
+    )];
+    lines.extend(timetable_grid(&trip_timetable.timetable));
+
+    lines
+}
+
+fn stations_on_route_view(snapshot: &Rc<AllRoutesSnapshot>, route_index: usize) -> View {
+    let route = &snapshot.routes[route_index];
+
+    let items = route
+        .stations_on_route_with_timetables
+        .iter()
+        .map(|station_on_route| {
+            format!(
+                "{:>3}. {:<32} {:>4} departure(s){}",
+                station_on_route.station.stop_number,
+                station_on_route.station.name,
+                station_on_route.timetable.timetable.len(),
+                match station_on_route.timetable_is_interpolated {
+                    true => " (interpolated)",
+                    false => "",
+                }
+            )
+        })
+        .collect();
+
+    View::List(ListView::new(
+        format!(
+            "{}  {}",
+            route.route_details.route, route.route_details.name
+        ),
+        items,
+        ListViewKind::StationsOnRoute {
+            snapshot: snapshot.clone(),
+            route_index,
+        },
+    ))
+}
+
+fn trip_timetable_view(
+    snapshot: &AllRoutesSnapshot,
+    route_index: usize,
+    station_index: usize,
+) -> View {
+    let route = &snapshot.routes[route_index];
+    let station_on_route = &route.stations_on_route_with_timetables[station_index];
+
+    View::Text(TextView {
+        title: format!(
+            "{} at {} ({})",
+            route.route_details.route,
+            station_on_route.station.name,
+            station_on_route.station.station_code
+        ),
+        lines: trip_timetable_lines(&station_on_route.timetable),
+        scroll: 0,
+    })
+}
+
+
+/// Formats a timetable as a grid with one row per hour, e.g. `05 | 00 15 30 45`.
+fn timetable_grid(timetable: &[TimetableEntry]) -> Vec<String> {
+    let mut entries: Vec<(u8, u8)> = timetable
+        .iter()
+        .map(|entry| (entry.hour, entry.minute))
+        .collect();
+    entries.sort_unstable();
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut current_hour = None;
+
+    for (hour, minute) in entries {
+        if current_hour != Some(hour) {
+            lines.push(format!("{:02} |", hour));
+            current_hour = Some(hour);
+        }
+
+        // PANIC SAFETY: A line was pushed for the current hour above.
+        let line = lines.last_mut().unwrap();
+        line.push_str(&format!(" {:02}", minute));
+    }
+
+    lines
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_timetable_grid() {
+        let timetable = vec![
+            TimetableEntry::new(6, 5).unwrap(),
+            TimetableEntry::new(5, 30).unwrap(),
+            TimetableEntry::new(5, 0).unwrap(),
+            TimetableEntry::new(7, 45).unwrap(),
+        ];
+
+        assert_eq!(
+            timetable_grid(&timetable),
+            vec!["05 | 00 30", "06 | 05", "07 | 45"]
+        );
+        assert!(timetable_grid(&[]).is_empty());
+    }
+}
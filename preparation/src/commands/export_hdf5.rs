@@ -0,0 +1,118 @@
+use chrono::Utc;
+use miette::{miette, Context, IntoDiagnostic, Result};
+use tracing::{info, warn};
+
+use crate::{
+    analysis::hdf5_export::{Hdf5Export, Hdf5ExportMetadata, Hdf5ExportOptions},
+    cli::ExportHdf5Args,
+    configuration::Configuration,
+    recorder::formats::TripArrivalsSnapshot,
+    storage::{read_snapshot_contents, SnapshotArchive},
+};
+
+/// Name of the default output file (in the storage directory).
+const DEFAULT_EXPORT_FILE_NAME: &str = "lpp-export.nc";
+
+
+/// Exports the timetables of the latest route snapshot and all recorded arrivals
+/// (optionally only those captured between `--since` and `--until`) into a NetCDF-4 (HDF5) file.
+/// See [`crate::analysis::hdf5_export`] for the layout of the file.
+pub fn run_export_hdf5(configuration: &Configuration, arguments: ExportHdf5Args) -> Result<()> {
+    let storage_root = &configuration.lpp.recording.recording_storage_root;
+
+    if arguments.compression_level > 9 {
+        return Err(miette!(
+            "Invalid compression level: {} (expected 0 to 9).",
+            arguments.compression_level
+        ));
+    }
+    if arguments.chunk_size == 0 {
+        return Err(miette!("Chunk size must be at least 1."));
+    }
+
+    let snapshot_archive = SnapshotArchive::open(storage_root)
+        .wrap_err_with(|| miette!("Failed to open snapshot archive."))?;
+
+    let route_snapshot = snapshot_archive
+        .latest_route_snapshot()
+        .wrap_err_with(|| miette!("Failed to load latest route snapshot."))?
+        .ok_or_else(|| miette!("There are no route snapshots yet."))?;
+
+    info!(
+        file_path = %route_snapshot.file_path.display(),
+        "Exporting timetables of route snapshot."
+    );
+
+    let mut export = Hdf5Export::new();
+    export.add_route_snapshot(&route_snapshot.snapshot);
+
+    let arrival_snapshot_file_paths = storage_root
+        .arrivals()
+        .and_then(|arrival_storage_root| arrival_storage_root.snapshot_file_paths())
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to list arrival snapshots."))?;
+
+    let mut exported_arrival_snapshots = 0;
+    for file_path in arrival_snapshot_file_paths {
+        let contents = read_snapshot_contents(&file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to read arrival snapshot {}.",
+                    file_path.display()
+                )
+            })?;
+
+        let arrivals_snapshot = match serde_json::from_slice::<TripArrivalsSnapshot>(&contents) {
+            Ok(arrivals_snapshot) => arrivals_snapshot,
+            Err(error) => {
+                warn!(
+                    error = %error,
+                    file_path = %file_path.display(),
+                    "Skipping arrival snapshot that could not be parsed."
+                );
+                continue;
+            }
+        };
+
+        let is_in_range = arguments.since.map_or(true, |since| {
+            arrivals_snapshot.captured_at >= since
+        }) && arguments.until.map_or(true, |until| {
+            arrivals_snapshot.captured_at < until
+        });
+        if !is_in_range {
+            continue;
+        }
+
+        export.add_trip_arrivals(&arrivals_snapshot);
+        exported_arrival_snapshots += 1;
+    }
+
+    let output_file_path = arguments
+        .output_file_path
+        .unwrap_or_else(|| storage_root.path().join(DEFAULT_EXPORT_FILE_NAME));
+
+    export.write(
+        &output_file_path,
+        &Hdf5ExportOptions {
+            compression_level: arguments.compression_level,
+            chunk_size: arguments.chunk_size,
+        },
+        &Hdf5ExportMetadata {
+            created_at: Utc::now(),
+            service_date: route_snapshot.snapshot.service_date_or_capture_date(),
+            route_snapshot_file_path: route_snapshot.file_path.display().to_string(),
+            arrival_snapshots: exported_arrival_snapshots,
+        },
+    )?;
+
+    info!(
+        file_path = %output_file_path.display(),
+        departures = export.number_of_departures(),
+        arrival_snapshots = exported_arrival_snapshots,
+        observations = export.number_of_observations(),
+        "Export has been saved."
+    );
+
+    Ok(())
+}
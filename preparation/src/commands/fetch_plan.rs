@@ -0,0 +1,60 @@
+use std::time::Instant;
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+use reqwest::Client;
+use tracing::info;
+
+use crate::{
+    api::{
+        client::LppApiClient,
+        station_details::{deduplicate_stations, fetch_station_details, validate_station_codes},
+    },
+    configuration::Configuration,
+    recorder::fetch_plan::FetchPlan,
+};
+
+
+/// Fetches only the station details and prints an estimate of the requests (and time)
+/// a full snapshot would take, without capturing one.
+pub async fn run_fetch_plan(configuration: &Configuration) -> Result<()> {
+    let http_client = Client::builder()
+        .user_agent(&configuration.lpp.api.user_agent)
+        .build()
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to build HTTP client."))?;
+
+    let api_client = LppApiClient::new(http_client, None, None, None, None, None);
+
+    info!("Requesting station details to plan a full snapshot.");
+
+    let request_started_at = Instant::now();
+    let stations = fetch_station_details(&configuration.lpp.api, &api_client)
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to fetch station details."))?;
+    let station_details_request_duration = request_started_at.elapsed();
+
+    let (stations, _) = deduplicate_stations(stations);
+    let (stations, _) = validate_station_codes(
+        stations,
+        configuration.lpp.api.lenient_station_codes,
+    );
+
+    let fetch_plan = FetchPlan::for_stations(
+        &stations,
+        configuration.lpp.api.max_route_groups_per_timetable_request,
+    );
+
+    info!("Fetch plan for a full snapshot:\n{}", fetch_plan);
+    info!(
+        total_requests = fetch_plan.total_requests(),
+        station_details_request_ms = station_details_request_duration.as_millis() as u64,
+        estimated_duration = %humantime::format_duration(
+            fetch_plan.estimated_duration(station_details_request_duration)
+        ),
+        "Estimated snapshot duration, assuming every request takes as long as \
+        the station details request (excluding retries and warmup pacing)."
+    );
+
+    Ok(())
+}
@@ -0,0 +1,477 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, Utc};
+use ed25519_dalek::VerifyingKey;
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::Serialize;
+use serde_with::{serde_as, TimestampSecondsWithFrac};
+use tracing::{error, info, warn};
+
+use super::{list_json_files, list_pack_files};
+use crate::{
+    cli::FsckArgs,
+    configuration::Configuration,
+    recorder::formats::{
+        load_snapshot,
+        load_snapshot_from_bytes,
+        LoadedSnapshot,
+        Snapshot,
+        SnapshotLoadError,
+    },
+    signing::{
+        load_verifying_key_from_file,
+        signature_file_path,
+        verify_file_signature,
+        verify_signature,
+        SignatureVerificationOutcome,
+        PUBLIC_KEY_FILE_NAME,
+    },
+    storage::SnapshotPack,
+};
+
+/// Name of the directory in the storage root that corrupt snapshots are moved into.
+pub const CORRUPT_SNAPSHOTS_DIRECTORY_NAME: &str = "corrupt";
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SnapshotKind {
+    Stations,
+    Routes,
+}
+
+impl SnapshotKind {
+    fn directory_name(&self) -> &'static str {
+        match self {
+            SnapshotKind::Stations => "stations",
+            SnapshotKind::Routes => "routes",
+        }
+    }
+}
+
+enum SnapshotCheckOutcome {
+    Healthy,
+
+    /// The file is valid JSON, but doesn't match the current snapshot format
+    /// (e.g. it was recorded by an older version). These are reported, but not quarantined.
+    FormatMismatch {
+        reason: String,
+    },
+
+    /// The file is unreadable, truncated or doesn't match its signature.
+    Corrupt {
+        reason: String,
+    },
+}
+
+
+#[serde_as]
+#[derive(Serialize)]
+struct FsckReport {
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    checked_at: DateTime<Utc>,
+
+    number_of_checked_files: usize,
+
+    corrupt_files: Vec<CorruptSnapshotReport>,
+}
+
+#[derive(Serialize)]
+struct CorruptSnapshotReport {
+    file_path: PathBuf,
+
+    /// Where the file was moved to (`None` in dry-run mode).
+    quarantined_file_path: Option<PathBuf>,
+
+    reason: String,
+}
+
+
+/// Re-reads stored station and route snapshots (including packed ones) and checks that they
+/// can be deserialized and (if signed) still match their signatures. Corrupt snapshots are moved
+/// into the `corrupt/` directory in the storage root, along with a report. A pack is moved
+/// as a whole if any of its snapshots is corrupt.
+///
+/// Returns an error if any corrupt snapshots were found.
+pub fn run_fsck(configuration: &Configuration, arguments: FsckArgs) -> Result<()> {
+    let storage_root = &configuration.lpp.recording.recording_storage_root;
+
+    let checked_time_window = match arguments.since.as_deref() {
+        Some(since) => Some(
+            humantime::parse_duration(since)
+                .into_diagnostic()
+                .wrap_err_with(|| miette!("Failed to parse --since duration."))?,
+        ),
+        None => None,
+    };
+
+    let verifying_key = load_snapshot_verifying_key(configuration)?;
+    if verifying_key.is_none() {
+        info!("No snapshot signing key is available, will not check signatures.");
+    }
+
+    let snapshot_directories = [
+        (
+            SnapshotKind::Stations,
+            storage_root
+                .stations()
+                .wrap_err_with(|| miette!("Failed to open station storage."))?
+                .directory_path()
+                .to_path_buf(),
+        ),
+        (
+            SnapshotKind::Routes,
+            storage_root
+                .routes()
+                .wrap_err_with(|| miette!("Failed to open route storage."))?
+                .directory_path()
+                .to_path_buf(),
+        ),
+    ];
+
+    let corrupt_snapshots_directory = storage_root.path().join(CORRUPT_SNAPSHOTS_DIRECTORY_NAME);
+    let checked_at = Utc::now();
+
+    let mut number_of_checked_files: usize = 0;
+    let mut corrupt_files = Vec::new();
+
+    for (snapshot_kind, snapshot_directory) in snapshot_directories {
+        let mut checked_snapshots = Vec::new();
+
+        for snapshot_file_path in list_json_files(&snapshot_directory)? {
+            if let Some(time_window) = checked_time_window {
+                if !was_modified_within(&snapshot_file_path, time_window)? {
+                    continue;
+                }
+            }
+
+            number_of_checked_files += 1;
+
+            let outcome = check_snapshot(
+                snapshot_kind,
+                &snapshot_file_path,
+                verifying_key.as_ref(),
+            )?;
+            checked_snapshots.push((snapshot_file_path, outcome));
+        }
+
+        // A pack is checked (and quarantined) as a whole: it is corrupt if any of its snapshots is.
+        for pack_file_path in list_pack_files(&snapshot_directory)? {
+            if let Some(time_window) = checked_time_window {
+                if !was_modified_within(&pack_file_path, time_window)? {
+                    continue;
+                }
+            }
+
+            let (number_of_packed_snapshots, outcome) = check_snapshot_pack(
+                snapshot_kind,
+                &pack_file_path,
+                verifying_key.as_ref(),
+            );
+
+            number_of_checked_files += number_of_packed_snapshots;
+            checked_snapshots.push((pack_file_path, outcome));
+        }
+
+        for (snapshot_file_path, outcome) in checked_snapshots {
+            let reason = match outcome {
+                SnapshotCheckOutcome::Healthy => continue,
+                SnapshotCheckOutcome::FormatMismatch { reason } => {
+                    warn!(
+                        file_path = %snapshot_file_path.display(),
+                        reason = %reason,
+                        "Snapshot does not match the current format (recorded by an older version?)."
+                    );
+                    continue;
+                }
+                SnapshotCheckOutcome::Corrupt { reason } => reason,
+            };
+
+            error!(
+                file_path = %snapshot_file_path.display(),
+                reason = %reason,
+                "Snapshot is corrupt."
+            );
+
+            let quarantined_file_path = if arguments.dry_run {
+                None
+            } else {
+                Some(quarantine_snapshot(
+                    &snapshot_file_path,
+                    &corrupt_snapshots_directory.join(snapshot_kind.directory_name()),
+                )?)
+            };
+
+            corrupt_files.push(CorruptSnapshotReport {
+                file_path: snapshot_file_path,
+                quarantined_file_path,
+                reason,
+            });
+        }
+    }
+
+    info!(
+        checked_files = number_of_checked_files,
+        corrupt_files = corrupt_files.len(),
+        "Finished checking snapshots."
+    );
+
+    if corrupt_files.is_empty() {
+        return Ok(());
+    }
+
+    let number_of_corrupt_files = corrupt_files.len();
+
+    if !arguments.dry_run {
+        let report_file_path = corrupt_snapshots_directory.join(format!(
+            "fsck-report_{}.json",
+            checked_at.format("%Y-%m-%d_%H-%M-%S%.3f+UTC")
+        ));
+
+        let report = FsckReport {
+            checked_at,
+            number_of_checked_files,
+            corrupt_files,
+        };
+
+        let serialized_report = serde_json::to_vec_pretty(&report)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to serialize fsck report."))?;
+
+        fs::write(&report_file_path, serialized_report)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to write fsck report."))?;
+
+        info!(
+            file_path = %report_file_path.display(),
+            "Corrupt snapshots have been quarantined, report saved."
+        );
+    }
+
+    Err(miette!(
+        "{} snapshot(s) are corrupt.",
+        number_of_corrupt_files
+    ))
+}
+
+
+/// Uses the configured signing key if there is one, otherwise the public key
+/// in the storage root (if it exists).
+fn load_snapshot_verifying_key(configuration: &Configuration) -> Result<Option<VerifyingKey>> {
+    if let Some(signing_key) = &configuration.lpp.recording.snapshot_signing_key {
+        return Ok(Some(signing_key.verifying_key()));
+    }
+
+    let public_key_file_path = configuration
+        .lpp
+        .recording
+        .recording_storage_root
+        .path()
+        .join(PUBLIC_KEY_FILE_NAME);
+
+    if !public_key_file_path.is_file() {
+        return Ok(None);
+    }
+
+    load_verifying_key_from_file(&public_key_file_path).map(Some)
+}
+
+fn was_modified_within(file_path: &Path, time_window: Duration) -> Result<bool> {
+    let modified_at = fs::metadata(file_path)
+        .and_then(|metadata| metadata.modified())
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to read modification time of {}.",
+                file_path.display()
+            )
+        })?;
+
+    Ok(SystemTime::now()
+        .duration_since(modified_at)
+        .map(|age| age <= time_window)
+        .unwrap_or(true))
+}
+
+fn check_snapshot(
+    snapshot_kind: SnapshotKind,
+    file_path: &Path,
+    verifying_key: Option<&VerifyingKey>,
+) -> Result<SnapshotCheckOutcome> {
+    if let Some(verifying_key) = verifying_key {
+        let signature_outcome = verify_file_signature(verifying_key, file_path)?;
+
+        if let Some(outcome) = check_signature(signature_outcome) {
+            return Ok(outcome);
+        }
+    }
+
+    Ok(check_loaded_snapshot(
+        snapshot_kind,
+        load_snapshot(file_path),
+    ))
+}
+
+/// Checks every snapshot in a pack, returning the number of checked snapshots and the outcome
+/// of the first corrupt one (or `Healthy`, if none are corrupt).
+/// Packed snapshots in an outdated format are only reported.
+fn check_snapshot_pack(
+    snapshot_kind: SnapshotKind,
+    pack_file_path: &Path,
+    verifying_key: Option<&VerifyingKey>,
+) -> (usize, SnapshotCheckOutcome) {
+    let pack = match SnapshotPack::open(pack_file_path) {
+        Ok(pack) => pack,
+        Err(error) => {
+            return (
+                0,
+                SnapshotCheckOutcome::Corrupt {
+                    reason: error.to_string(),
+                },
+            );
+        }
+    };
+
+    let mut number_of_checked_snapshots = 0;
+
+    for packed_snapshot in pack.snapshots() {
+        number_of_checked_snapshots += 1;
+
+        let outcome = match pack.read(packed_snapshot) {
+            Ok(contents) => {
+                let signature_outcome = verifying_key.and_then(|verifying_key| {
+                    let outcome = match &packed_snapshot.signature {
+                        Some(encoded_signature) => {
+                            verify_signature(verifying_key, &contents, encoded_signature)
+                        }
+                        None => SignatureVerificationOutcome::MissingSignature,
+                    };
+
+                    check_signature(outcome)
+                });
+
+                signature_outcome.unwrap_or_else(|| {
+                    check_loaded_snapshot(snapshot_kind, load_snapshot_from_bytes(&contents))
+                })
+            }
+            Err(error) => SnapshotCheckOutcome::Corrupt {
+                reason: error.to_string(),
+            },
+        };
+
+        match outcome {
+            SnapshotCheckOutcome::Healthy => {}
+            SnapshotCheckOutcome::FormatMismatch { reason } => warn!(
+                file_path = %pack.snapshot_path(packed_snapshot).display(),
+                reason = %reason,
+                "Snapshot does not match the current format (recorded by an older version?)."
+            ),
+            SnapshotCheckOutcome::Corrupt { reason } => {
+                return (
+                    number_of_checked_snapshots,
+                    SnapshotCheckOutcome::Corrupt {
+                        reason: format!(
+                            "packed snapshot {} is corrupt: {}",
+                            packed_snapshot.file_name, reason
+                        ),
+                    },
+                );
+            }
+        }
+    }
+
+    (
+        number_of_checked_snapshots,
+        SnapshotCheckOutcome::Healthy,
+    )
+}
+
+/// Returns the outcome of a snapshot whose signature is malformed or doesn't match,
+/// or `None` if the snapshot still needs to be checked further.
+fn check_signature(
+    signature_outcome: SignatureVerificationOutcome,
+) -> Option<SnapshotCheckOutcome> {
+    match signature_outcome {
+        SignatureVerificationOutcome::Valid | SignatureVerificationOutcome::MissingSignature => {
+            None
+        }
+        SignatureVerificationOutcome::MalformedSignature => Some(SnapshotCheckOutcome::Corrupt {
+            reason: String::from("signature file is malformed"),
+        }),
+        SignatureVerificationOutcome::Invalid => Some(SnapshotCheckOutcome::Corrupt {
+            reason: String::from("contents do not match the signature"),
+        }),
+    }
+}
+
+fn check_loaded_snapshot(
+    snapshot_kind: SnapshotKind,
+    loaded_snapshot: Result<LoadedSnapshot, SnapshotLoadError>,
+) -> SnapshotCheckOutcome {
+    let loaded_snapshot = match loaded_snapshot {
+        Ok(loaded_snapshot) => loaded_snapshot,
+        Err(error) if error.is_corruption() => {
+            return SnapshotCheckOutcome::Corrupt {
+                reason: error.to_string(),
+            };
+        }
+        Err(error) => {
+            return SnapshotCheckOutcome::FormatMismatch {
+                reason: error.to_string(),
+            };
+        }
+    };
+
+    let is_expected_kind = matches!(
+        (snapshot_kind, &loaded_snapshot.snapshot),
+        (SnapshotKind::Stations, Snapshot::Stations(_))
+            | (SnapshotKind::Routes, Snapshot::Routes(_))
+    );
+
+    match is_expected_kind {
+        true => SnapshotCheckOutcome::Healthy,
+        false => SnapshotCheckOutcome::FormatMismatch {
+            reason: format!(
+                "expected a snapshot of {}",
+                snapshot_kind.directory_name()
+            ),
+        },
+    }
+}
+
+/// Moves a snapshot (and its signature, if any) into the quarantine directory.
+fn quarantine_snapshot(file_path: &Path, quarantine_directory: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(quarantine_directory)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to create quarantine directory."))?;
+
+    let move_into_quarantine = |path: &Path| -> Result<PathBuf> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| miette!("Snapshot path has no file name."))?;
+        let quarantined_path = quarantine_directory.join(file_name);
+
+        fs::rename(path, &quarantined_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to move {} into quarantine.",
+                    path.display()
+                )
+            })?;
+
+        Ok(quarantined_path)
+    };
+
+    let quarantined_file_path = move_into_quarantine(file_path)?;
+
+    let signature_path = signature_file_path(file_path);
+    if signature_path.is_file() {
+        move_into_quarantine(&signature_path)?;
+    }
+
+    Ok(quarantined_file_path)
+}
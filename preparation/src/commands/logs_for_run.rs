@@ -0,0 +1,96 @@
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader},
+};
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+use tracing::info;
+
+use crate::{cli::LogsForRunArgs, configuration::Configuration};
+
+
+/// Prints all lines of the (rotated) log files that belong to a single snapshot run,
+/// oldest first. Log lines are matched by the run identifier in their `snapshot-run` span.
+pub fn run_logs_for_run(configuration: &Configuration, arguments: LogsForRunArgs) -> Result<()> {
+    let log_directory_path = &configuration.logging.log_file_output_directory;
+    let log_file_name_start = format!("{}.", configuration.logging.log_file_name_prefix);
+    let run_id = arguments.run_id.to_string();
+
+    let directory_entries = fs::read_dir(log_directory_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to list log directory {}.",
+                log_directory_path.display()
+            )
+        })?;
+
+    let mut log_file_paths = Vec::new();
+    for entry in directory_entries {
+        let entry_path = entry.into_diagnostic()?.path();
+
+        let is_log_file = entry_path.is_file()
+            && entry_path
+                .file_name()
+                .map(|file_name| {
+                    file_name
+                        .to_string_lossy()
+                        .starts_with(&log_file_name_start)
+                })
+                .unwrap_or(false);
+
+        if is_log_file {
+            log_file_paths.push(entry_path);
+        }
+    }
+
+    // Both time- and size-rotated log files sort chronologically by their file name.
+    log_file_paths.sort();
+
+
+    let mut number_of_matching_lines: usize = 0;
+
+    for log_file_path in log_file_paths {
+        let log_file = File::open(&log_file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to open log file {}.",
+                    log_file_path.display()
+                )
+            })?;
+
+        let mut log_file_reader = BufReader::new(log_file);
+        let mut line = Vec::new();
+
+        loop {
+            line.clear();
+
+            let read_bytes = log_file_reader
+                .read_until(b'\n', &mut line)
+                .into_diagnostic()
+                .wrap_err_with(|| {
+                    miette!(
+                        "Failed to read log file {}.",
+                        log_file_path.display()
+                    )
+                })?;
+            if read_bytes == 0 {
+                break;
+            }
+
+            let line = String::from_utf8_lossy(&line);
+            if line.contains(&run_id) {
+                print!("{}", line);
+                number_of_matching_lines += 1;
+            }
+        }
+    }
+
+    info!(
+        matching_lines = number_of_matching_lines,
+        "Finished searching the log files."
+    );
+
+    Ok(())
+}
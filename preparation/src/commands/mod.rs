@@ -0,0 +1,80 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use crate::storage::{is_pack_file, is_snapshot_file};
+
+pub mod compact_archive;
+pub mod config_schema;
+pub mod doctor;
+pub mod explore;
+#[cfg(feature = "hdf5-export")]
+pub mod export_hdf5;
+pub mod fetch_plan;
+pub mod fsck;
+pub mod logs_for_run;
+pub mod query;
+pub mod report;
+pub mod route_families;
+pub mod route_segments;
+pub mod schema_dump;
+pub mod station_posters;
+pub mod stats;
+mod terminal;
+pub mod timetable_matrix;
+pub mod verify_signatures;
+pub mod watch;
+
+
+/// Lists all `.json` snapshot files in a directory (non-recursively), sorted by path.
+fn list_json_files(directory_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut json_files = Vec::new();
+
+    let directory_entries = fs::read_dir(directory_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to list directory {}.",
+                directory_path.display()
+            )
+        })?;
+
+    for entry in directory_entries {
+        let entry_path = entry.into_diagnostic()?.path();
+
+        if is_snapshot_file(&entry_path) {
+            json_files.push(entry_path);
+        }
+    }
+
+    json_files.sort();
+    Ok(json_files)
+}
+
+/// Lists all snapshot packs in a directory, sorted by path.
+fn list_pack_files(directory_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut pack_files = Vec::new();
+
+    let directory_entries = fs::read_dir(directory_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to list directory {}.",
+                directory_path.display()
+            )
+        })?;
+
+    for entry in directory_entries {
+        let entry_path = entry.into_diagnostic()?.path();
+
+        if is_pack_file(&entry_path) {
+            pack_files.push(entry_path);
+        }
+    }
+
+    pack_files.sort();
+    Ok(pack_files)
+}
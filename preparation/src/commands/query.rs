@@ -0,0 +1,184 @@
+use miette::{miette, Context, IntoDiagnostic, Result};
+use tracing::info;
+
+use crate::{
+    analysis::{nearby_stations::StationIndex, query::SnapshotQuery},
+    api::GeographicalLocation,
+    cli::QueryArgs,
+    configuration::Configuration,
+    recorder::formats::{load_snapshot, AllStationsSnapshot, Snapshot},
+    storage::SnapshotArchive,
+};
+
+
+/// Evaluates a jq expression against a snapshot (the latest station snapshot by default)
+/// and prints each of its outputs as a line of JSON.
+pub fn run_query(configuration: &Configuration, arguments: QueryArgs) -> Result<()> {
+    let query = SnapshotQuery::compile(&arguments.expression).into_diagnostic()?;
+
+    let near_location = arguments
+        .near
+        .as_deref()
+        .map(parse_location)
+        .transpose()
+        .wrap_err_with(|| miette!("Failed to parse `--near` location."))?;
+
+    let (snapshot_file_path, snapshot) = match arguments.file_path {
+        Some(file_path) => {
+            let loaded_snapshot = load_snapshot(&file_path)
+                .into_diagnostic()
+                .wrap_err_with(|| miette!("Failed to load snapshot {}.", file_path.display()))?;
+
+            (file_path, loaded_snapshot.snapshot)
+        }
+        None => {
+            let snapshot_archive =
+                SnapshotArchive::open(&configuration.lpp.recording.recording_storage_root)
+                    .wrap_err_with(|| miette!("Failed to open snapshot archive."))?;
+
+            match arguments.routes {
+                true => {
+                    let latest_route_snapshot = snapshot_archive
+                        .latest_route_snapshot()
+                        .wrap_err_with(|| miette!("Failed to load latest route details snapshot."))?
+                        .ok_or_else(|| miette!("There are no route details snapshots yet."))?;
+
+                    (
+                        latest_route_snapshot.file_path,
+                        Snapshot::Routes(latest_route_snapshot.snapshot),
+                    )
+                }
+                false => {
+                    let latest_station_snapshot = snapshot_archive
+                        .latest_station_snapshot()
+                        .wrap_err_with(|| {
+                            miette!("Failed to load latest station details snapshot.")
+                        })?
+                        .ok_or_else(|| miette!("There are no station details snapshots yet."))?;
+
+                    (
+                        latest_station_snapshot.file_path,
+                        Snapshot::Stations(latest_station_snapshot.snapshot),
+                    )
+                }
+            }
+        }
+    };
+
+    let snapshot_value = match (snapshot, near_location) {
+        (Snapshot::Stations(station_snapshot), Some(near_location)) => {
+            serialize_stations_near(
+                &station_snapshot,
+                near_location,
+                arguments.radius_in_meters,
+            )
+        }
+        (Snapshot::Stations(station_snapshot), None) => serde_json::to_value(station_snapshot),
+        (Snapshot::Routes(_), Some(_)) => {
+            return Err(miette!(
+                "Snapshot {} is a route snapshot, `--near` only works with station snapshots.",
+                snapshot_file_path.display()
+            ));
+        }
+        (Snapshot::Routes(route_snapshot), None) => serde_json::to_value(route_snapshot),
+    };
+
+    let snapshot_value = snapshot_value
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to serialize snapshot for querying."))?;
+
+    info!(
+        file_path = %snapshot_file_path.display(),
+        expression = %arguments.expression,
+        "Querying snapshot."
+    );
+
+    let outputs = query.run(snapshot_value).into_diagnostic()?;
+
+    for output in outputs {
+        let serialized_output = match arguments.pretty {
+            true => serde_json::to_string_pretty(&output),
+            false => serde_json::to_string(&output),
+        }
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to serialize query output."))?;
+
+        println!("{}", serialized_output);
+    }
+
+    Ok(())
+}
+
+/// Parses a `<latitude>,<longitude>` location, e.g. `46.0512,14.5060`.
+fn parse_location(location: &str) -> Result<GeographicalLocation> {
+    let (latitude, longitude) = location
+        .split_once(',')
+        .ok_or_else(|| miette!("Expected \"<latitude>,<longitude>\", got \"{}\".", location))?;
+
+    let latitude: f64 = latitude
+        .trim()
+        .parse()
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Invalid latitude: {}.", latitude))?;
+    let longitude: f64 = longitude
+        .trim()
+        .parse()
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Invalid longitude: {}.", longitude))?;
+
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return Err(miette!(
+            "Location {} is out of range (latitude must be within ±90°, longitude within ±180°).",
+            location
+        ));
+    }
+
+    Ok(GeographicalLocation::new(latitude, longitude))
+}
+
+/// Serializes the station snapshot with only the stations within `radius_in_meters`
+/// of `location` (nearest first), each with an additional `distance_in_meters` field.
+fn serialize_stations_near(
+    station_snapshot: &AllStationsSnapshot,
+    location: GeographicalLocation,
+    radius_in_meters: f64,
+) -> serde_json::Result<serde_json::Value> {
+    let nearby_stations = StationIndex::new(&station_snapshot.station_details)
+        .nearest_stations(location, radius_in_meters)
+        .into_iter()
+        .map(|nearby_station| {
+            let mut station_value = serde_json::to_value(nearby_station.station)?;
+            if let Some(station_fields) = station_value.as_object_mut() {
+                station_fields.insert(
+                    String::from("distance_in_meters"),
+                    serde_json::Value::from(nearby_station.distance_in_meters),
+                );
+            }
+
+            Ok(station_value)
+        })
+        .collect::<serde_json::Result<Vec<_>>>()?;
+
+    let mut snapshot_value = serde_json::to_value(station_snapshot)?;
+    snapshot_value["station_details"] = serde_json::Value::Array(nearby_stations);
+
+    Ok(snapshot_value)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_near_location() {
+        assert_eq!(
+            parse_location("46.0512, 14.5060").unwrap(),
+            GeographicalLocation::new(46.0512, 14.5060)
+        );
+
+        assert!(parse_location("46.0512").is_err());
+        assert!(parse_location("46.0512,east").is_err());
+        assert!(parse_location("146.0512,14.5060").is_err());
+    }
+}
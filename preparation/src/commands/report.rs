@@ -0,0 +1,115 @@
+use std::fs;
+
+use askama::Template;
+use miette::{miette, Context, IntoDiagnostic, Result};
+use tracing::info;
+
+use crate::{
+    analysis::{
+        attribution::DatasetAttribution,
+        report::{SnapshotReport, REPORT_FILE_NAME},
+    },
+    cli::ReportArgs,
+    configuration::Configuration,
+    storage::SnapshotArchive,
+};
+
+
+/// Renders the latest station and route snapshots (or the ones at `--at`)
+/// into a self-contained HTML report.
+pub fn run_report(configuration: &Configuration, arguments: ReportArgs) -> Result<()> {
+    let storage_root = &configuration.lpp.recording.recording_storage_root;
+
+    let snapshot_archive = SnapshotArchive::open(storage_root)
+        .wrap_err_with(|| miette!("Failed to open snapshot archive."))?;
+
+    let (station_snapshot, route_snapshot) = match arguments.at {
+        Some(at) => {
+            let network_state = snapshot_archive
+                .network_at(at)
+                .wrap_err_with(|| {
+                    miette!(
+                        "Failed to load snapshots captured at or before {}.",
+                        at
+                    )
+                })?
+                .ok_or_else(|| {
+                    miette!(
+                        "There are no snapshots captured at or before {}.",
+                        at
+                    )
+                })?;
+
+            (
+                network_state.station_snapshot,
+                network_state.route_snapshot,
+            )
+        }
+        None => {
+            let latest_station_snapshot = snapshot_archive
+                .latest_station_snapshot()
+                .wrap_err_with(|| miette!("Failed to load latest station details snapshot."))?
+                .ok_or_else(|| miette!("There are no station details snapshots yet."))?;
+
+            let latest_route_snapshot = snapshot_archive
+                .latest_route_snapshot()
+                .wrap_err_with(|| miette!("Failed to load latest route details snapshot."))?
+                .ok_or_else(|| miette!("There are no route details snapshots yet."))?;
+
+            (latest_station_snapshot, latest_route_snapshot)
+        }
+    };
+
+    info!(
+        station_snapshot_file_path = %station_snapshot.file_path.display(),
+        route_snapshot_file_path = %route_snapshot.file_path.display(),
+        "Rendering HTML report."
+    );
+
+    // Both snapshots of a run share the capture start, and the route snapshot finishes last.
+    let attribution = DatasetAttribution::new(
+        &configuration.dataset,
+        station_snapshot
+            .snapshot
+            .capture_started_at
+            .unwrap_or(station_snapshot.snapshot.captured_at),
+        route_snapshot
+            .snapshot
+            .capture_finished_at
+            .unwrap_or(route_snapshot.snapshot.captured_at),
+    );
+
+    let report = SnapshotReport::from_snapshots(
+        &station_snapshot.snapshot,
+        &route_snapshot.snapshot,
+        attribution,
+        &configuration.dataset.display_names,
+    );
+
+    let rendered_report = report
+        .render()
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to render HTML report."))?;
+
+    let output_file_path = arguments
+        .output_file_path
+        .unwrap_or_else(|| storage_root.path().join(REPORT_FILE_NAME));
+
+    fs::write(&output_file_path, rendered_report)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to write HTML report to {}.",
+                output_file_path.display()
+            )
+        })?;
+
+    info!(
+        file_path = %output_file_path.display(),
+        number_of_routes = report.routes.len(),
+        number_of_stations = report.stations.len(),
+        "HTML report has been saved."
+    );
+
+    Ok(())
+}
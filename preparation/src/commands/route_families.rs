@@ -0,0 +1,80 @@
+use std::fs;
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+use tracing::info;
+
+use crate::{
+    analysis::{
+        attribution::DatasetAttribution,
+        route_families::{derive_route_families, ROUTE_FAMILIES_FILE_NAME},
+    },
+    cli::RouteFamiliesArgs,
+    configuration::Configuration,
+    storage::SnapshotArchive,
+};
+
+
+/// Derives route families (route variants and their branch points)
+/// from the latest route snapshot and saves them as JSON.
+pub fn run_route_families(
+    configuration: &Configuration,
+    arguments: RouteFamiliesArgs,
+) -> Result<()> {
+    let storage_root = &configuration.lpp.recording.recording_storage_root;
+
+    let snapshot_archive = SnapshotArchive::open(storage_root)
+        .wrap_err_with(|| miette!("Failed to open snapshot archive."))?;
+
+    let latest_route_snapshot = snapshot_archive
+        .latest_route_snapshot()
+        .wrap_err_with(|| miette!("Failed to load latest route details snapshot."))?
+        .ok_or_else(|| miette!("There are no route details snapshots yet."))?;
+
+    info!(
+        file_path = %latest_route_snapshot.file_path.display(),
+        "Deriving route families from the latest route details snapshot."
+    );
+
+    let route_snapshot = &latest_route_snapshot.snapshot;
+    let attribution = DatasetAttribution::new(
+        &configuration.dataset,
+        route_snapshot
+            .capture_started_at
+            .unwrap_or(route_snapshot.captured_at),
+        route_snapshot
+            .capture_finished_at
+            .unwrap_or(route_snapshot.captured_at),
+    );
+
+    let route_families = derive_route_families(
+        route_snapshot,
+        latest_route_snapshot.file_name(),
+        attribution,
+        &configuration.dataset.display_names,
+    );
+
+    let output_file_path = arguments
+        .output_file_path
+        .unwrap_or_else(|| storage_root.path().join(ROUTE_FAMILIES_FILE_NAME));
+
+    let serialized_route_families = serde_json::to_vec_pretty(&route_families)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to serialize route families."))?;
+
+    fs::write(&output_file_path, serialized_route_families)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to write route families to {}.",
+                output_file_path.display()
+            )
+        })?;
+
+    info!(
+        file_path = %output_file_path.display(),
+        number_of_families = route_families.families.len(),
+        "Route families have been saved."
+    );
+
+    Ok(())
+}
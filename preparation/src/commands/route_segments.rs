@@ -0,0 +1,103 @@
+use std::fs;
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+use tracing::info;
+
+use crate::{
+    analysis::{
+        attribution::DatasetAttribution,
+        route_segments::{derive_route_segments, DistanceSource, ROUTE_SEGMENTS_FILE_NAME},
+    },
+    cli::RouteSegmentsArgs,
+    configuration::Configuration,
+    storage::SnapshotArchive,
+};
+
+/// Name of the default output directory (in the storage directory).
+const ROUTE_SEGMENTS_DIRECTORY_NAME: &str = "segments";
+
+
+/// Derives station-to-station segments (distances and scheduled travel times) of every trip
+/// in the latest route snapshot and saves them as `{trip_id}/segments.json`.
+pub fn run_route_segments(
+    configuration: &Configuration,
+    arguments: RouteSegmentsArgs,
+) -> Result<()> {
+    let storage_root = &configuration.lpp.recording.recording_storage_root;
+
+    let snapshot_archive = SnapshotArchive::open(storage_root)
+        .wrap_err_with(|| miette!("Failed to open snapshot archive."))?;
+
+    let latest_route_snapshot = snapshot_archive
+        .latest_route_snapshot()
+        .wrap_err_with(|| miette!("Failed to load latest route details snapshot."))?
+        .ok_or_else(|| miette!("There are no route details snapshots yet."))?;
+
+    info!(
+        file_path = %latest_route_snapshot.file_path.display(),
+        "Deriving route segments from the latest route details snapshot."
+    );
+
+    let route_snapshot = &latest_route_snapshot.snapshot;
+    let attribution = DatasetAttribution::new(
+        &configuration.dataset,
+        route_snapshot
+            .capture_started_at
+            .unwrap_or(route_snapshot.captured_at),
+        route_snapshot
+            .capture_finished_at
+            .unwrap_or(route_snapshot.captured_at),
+    );
+
+    let route_segments = derive_route_segments(
+        route_snapshot,
+        latest_route_snapshot.file_name(),
+        attribution,
+        &configuration.dataset.display_names,
+    );
+
+    let output_directory_path = arguments
+        .output_directory_path
+        .unwrap_or_else(|| storage_root.path().join(ROUTE_SEGMENTS_DIRECTORY_NAME));
+
+    for trip_segments in &route_segments {
+        let trip_directory_path = output_directory_path.join(trip_segments.trip_id.as_ref());
+
+        fs::create_dir_all(&trip_directory_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to create segment directory {}.",
+                    trip_directory_path.display()
+                )
+            })?;
+
+        let serialized_segments = serde_json::to_vec_pretty(trip_segments)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to serialize route segments."))?;
+
+        let output_file_path = trip_directory_path.join(ROUTE_SEGMENTS_FILE_NAME);
+        fs::write(&output_file_path, serialized_segments)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to write route segments to {}.",
+                    output_file_path.display()
+                )
+            })?;
+    }
+
+    let trips_measured_along_shape = route_segments
+        .iter()
+        .filter(|trip_segments| trip_segments.distance_source == DistanceSource::RouteShape)
+        .count();
+
+    info!(
+        directory_path = %output_directory_path.display(),
+        number_of_trips = route_segments.len(),
+        trips_measured_along_shape = trips_measured_along_shape,
+        "Route segments have been saved."
+    );
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use std::fs;
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde_json::{Map, Value};
+
+use crate::{api::response_schemas::api_response_schemas, cli::SchemaDumpArgs};
+
+
+/// Prints (or saves) the JSON Schemas of all LPP API responses we parse.
+/// Doesn't require a configuration file (logging is not initialized either).
+pub fn run_schema_dump(arguments: SchemaDumpArgs) -> Result<()> {
+    let schemas = api_response_schemas();
+
+    let Some(output_directory_path) = arguments.output_directory_path else {
+        let mut schemas_by_endpoint = Map::new();
+        for schema in schemas {
+            schemas_by_endpoint.insert(
+                schema.endpoint.to_string(),
+                serde_json::to_value(&schema.schema)
+                    .into_diagnostic()
+                    .wrap_err_with(|| miette!("Failed to serialize schema of {}.", schema.name))?,
+            );
+        }
+
+        let json_schemas = serde_json::to_string_pretty(&Value::Object(schemas_by_endpoint))
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to serialize API response schemas."))?;
+
+        println!("{}", json_schemas);
+        return Ok(());
+    };
+
+    fs::create_dir_all(&output_directory_path)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to create output directory."))?;
+
+    for schema in schemas {
+        let schema_file_path = output_directory_path.join(format!("{}.schema.json", schema.name));
+
+        let json_schema = serde_json::to_string_pretty(&schema.schema)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to serialize schema of {}.", schema.name))?;
+
+        fs::write(&schema_file_path, json_schema + "\n")
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to write schema to {}.",
+                    schema_file_path.display()
+                )
+            })?;
+
+        println!(
+            "Saved schema of {} to {}.",
+            schema.endpoint,
+            schema_file_path.display()
+        );
+    }
+
+    Ok(())
+}
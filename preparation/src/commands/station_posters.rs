@@ -0,0 +1,147 @@
+use std::fs;
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+use tracing::info;
+
+use crate::{
+    analysis::{
+        attribution::DatasetAttribution,
+        station_posters::{
+            derive_station_posters,
+            render_qr_code_svg,
+            StationUrlTemplate,
+            STATION_POSTERS_FILE_NAME,
+        },
+    },
+    cli::StationPostersArgs,
+    configuration::Configuration,
+    storage::SnapshotArchive,
+};
+
+/// Name of the default output directory (in the storage directory).
+const STATION_POSTERS_DIRECTORY_NAME: &str = "station-posters";
+
+/// Name of the QR code directory (in the output directory).
+const QR_CODE_DIRECTORY_NAME: &str = "qr";
+
+
+/// Exports poster data (code, name, routes and a link) for every station
+/// in the latest station snapshot, optionally rendering the links as QR codes.
+pub fn run_station_posters(
+    configuration: &Configuration,
+    arguments: StationPostersArgs,
+) -> Result<()> {
+    let storage_root = &configuration.lpp.recording.recording_storage_root;
+
+    let url_template = StationUrlTemplate::parse(arguments.url_template)?;
+
+    let snapshot_archive = SnapshotArchive::open(storage_root)
+        .wrap_err_with(|| miette!("Failed to open snapshot archive."))?;
+
+    let latest_station_snapshot = snapshot_archive
+        .latest_station_snapshot()
+        .wrap_err_with(|| miette!("Failed to load latest station details snapshot."))?
+        .ok_or_else(|| miette!("There are no station details snapshots yet."))?;
+
+    info!(
+        file_path = %latest_station_snapshot.file_path.display(),
+        "Exporting station posters from the latest station details snapshot."
+    );
+
+    let station_snapshot = &latest_station_snapshot.snapshot;
+    let attribution = DatasetAttribution::new(
+        &configuration.dataset,
+        station_snapshot
+            .capture_started_at
+            .unwrap_or(station_snapshot.captured_at),
+        station_snapshot
+            .capture_finished_at
+            .unwrap_or(station_snapshot.captured_at),
+    );
+
+    let mut station_posters = derive_station_posters(
+        station_snapshot,
+        latest_station_snapshot.file_name(),
+        attribution,
+        &url_template,
+        &configuration.dataset.display_names,
+    );
+
+    let output_directory_path = arguments
+        .output_directory_path
+        .unwrap_or_else(|| storage_root.path().join(STATION_POSTERS_DIRECTORY_NAME));
+
+    fs::create_dir_all(&output_directory_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to create output directory {}.",
+                output_directory_path.display()
+            )
+        })?;
+
+    if arguments.qr_codes {
+        let qr_code_directory_path = output_directory_path.join(QR_CODE_DIRECTORY_NAME);
+
+        fs::create_dir_all(&qr_code_directory_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to create QR code directory {}.",
+                    qr_code_directory_path.display()
+                )
+            })?;
+
+        for station in &mut station_posters.stations {
+            let qr_code_file_name = format!("{}.svg", station.station_code);
+
+            let qr_code = render_qr_code_svg(&station.url).wrap_err_with(|| {
+                miette!(
+                    "Failed to render QR code for station {}.",
+                    station.station_code
+                )
+            })?;
+
+            fs::write(
+                qr_code_directory_path.join(&qr_code_file_name),
+                qr_code,
+            )
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to write QR code for station {}.",
+                    station.station_code
+                )
+            })?;
+
+            station.qr_code_file = Some(format!(
+                "{}/{}",
+                QR_CODE_DIRECTORY_NAME, qr_code_file_name
+            ));
+        }
+    }
+
+    let output_file_path = output_directory_path.join(STATION_POSTERS_FILE_NAME);
+
+    let serialized_station_posters = serde_json::to_vec_pretty(&station_posters)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to serialize station posters."))?;
+
+    fs::write(&output_file_path, serialized_station_posters)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to write station posters to {}.",
+                output_file_path.display()
+            )
+        })?;
+
+    info!(
+        file_path = %output_file_path.display(),
+        number_of_stations = station_posters.stations.len(),
+        qr_codes = arguments.qr_codes,
+        "Station posters have been saved."
+    );
+
+    Ok(())
+}
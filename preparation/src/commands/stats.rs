@@ -0,0 +1,90 @@
+use std::time::Instant;
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+use tracing::info;
+
+use crate::{
+    analysis::sampling::{
+        read_sampled_route_snapshot,
+        read_sampled_route_snapshot_memory_mapped,
+        RouteSnapshotStatistics,
+        SamplingStrategy,
+    },
+    cli::StatsArgs,
+    configuration::Configuration,
+    storage::SnapshotArchive,
+};
+
+
+/// Computes statistics of a route snapshot (the latest one by default). With `--sample`,
+/// only a fraction of its routes is parsed and the statistics are extrapolated from them.
+pub fn run_stats(configuration: &Configuration, arguments: StatsArgs) -> Result<()> {
+    if !(arguments.sample > 0.0 && arguments.sample <= 1.0) {
+        return Err(miette!(
+            "Sample fraction must be larger than 0.0 and at most 1.0, got {}.",
+            arguments.sample
+        ));
+    }
+
+    let storage_root = &configuration.lpp.recording.recording_storage_root;
+
+    let route_snapshot_file_path = match arguments.file_path {
+        Some(file_path) => file_path,
+        None => {
+            let latest_file_path = storage_root
+                .routes()
+                .into_diagnostic()
+                .and_then(|route_storage| route_storage.latest().into_diagnostic())
+                .wrap_err_with(|| miette!("Failed to read latest route snapshot pointer."))?;
+
+            match latest_file_path {
+                Some(latest_file_path) => latest_file_path,
+                None => SnapshotArchive::open(storage_root)
+                    .and_then(|archive| archive.route_snapshots())
+                    .into_diagnostic()
+                    .wrap_err_with(|| miette!("Failed to open snapshot archive."))?
+                    .file_paths()
+                    .last()
+                    .cloned()
+                    .ok_or_else(|| miette!("There are no route details snapshots yet."))?,
+            }
+        }
+    };
+
+    let strategy = SamplingStrategy::from_fraction(arguments.sample, arguments.seed);
+
+    info!(
+        file_path = %route_snapshot_file_path.display(),
+        strategy = ?strategy,
+        memory_mapped = arguments.mmap,
+        "Computing route snapshot statistics."
+    );
+
+    let read_started_at = Instant::now();
+    let sample = match arguments.mmap {
+        true => read_sampled_route_snapshot_memory_mapped(&route_snapshot_file_path, strategy),
+        false => read_sampled_route_snapshot(&route_snapshot_file_path, strategy),
+    }
+    .into_diagnostic()
+    .wrap_err_with(|| miette!("Failed to read route snapshot."))?;
+    let read_duration = read_started_at.elapsed();
+
+    let statistics = RouteSnapshotStatistics::from_sample(&sample);
+
+    info!(
+        service_date = ?sample.service_date,
+        total_routes = statistics.total_routes,
+        sampled_routes = statistics.sampled_routes,
+        read_ms = read_duration.as_millis() as u64,
+        mean_stations_per_trip = format!("{:.1}", statistics.mean_stations_per_trip),
+        mean_departures_per_trip = format!("{:.1}", statistics.mean_departures_per_trip),
+        estimated_total_departures = statistics.estimated_total_departures.round() as u64,
+        interpolated_station_share = format!(
+            "{:.1}%",
+            statistics.interpolated_station_share * 100.0
+        ),
+        "Route snapshot statistics (approximate if sampled)."
+    );
+
+    Ok(())
+}
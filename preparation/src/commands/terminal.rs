@@ -0,0 +1,42 @@
+use std::io::{self, Stdout};
+
+use crossterm::{
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use ratatui::{backend::CrosstermBackend, Terminal};
+
+
+/// Puts the terminal into raw mode and the alternate screen,
+/// and restores it when dropped (even if the command fails).
+pub struct TerminalGuard {
+    pub terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl TerminalGuard {
+    pub fn enter() -> Result<Self> {
+        enable_raw_mode()
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to enable terminal raw mode."))?;
+
+        let mut stdout = io::stdout();
+        execute!(stdout, EnterAlternateScreen)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to enter alternate terminal screen."))?;
+
+        let terminal = Terminal::new(CrosstermBackend::new(stdout))
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to initialize terminal UI."))?;
+
+        Ok(Self { terminal })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(self.terminal.backend_mut(), LeaveAlternateScreen);
+        let _ = self.terminal.show_cursor();
+    }
+}
@@ -0,0 +1,162 @@
+use std::fs;
+
+use chrono::NaiveDate;
+use miette::{miette, Context, IntoDiagnostic, Result};
+use tracing::info;
+
+use crate::{
+    analysis::timetable_matrix::derive_timetable_matrix,
+    api::StationCode,
+    cli::TimetableMatrixArgs,
+    configuration::Configuration,
+    recorder::formats::AllStationsSnapshot,
+    storage::{ArchivedSnapshot, SnapshotArchive},
+};
+
+/// Name of the default output directory (in the storage directory).
+const TIMETABLE_MATRICES_DIRECTORY_NAME: &str = "timetable-matrices";
+
+
+/// Prints the departures of a single station (from the latest station snapshot, or the latest one
+/// for `--date`) as an hour × route matrix and saves it as `{station_code}_{date}.csv`.
+///
+/// If no snapshot was captured for `--date`, the latest one captured on the same type of service
+/// day (e.g. a Sunday for a public holiday, see `lpp.recording.public_holidays`) is used instead.
+pub fn run_timetable_matrix(
+    configuration: &Configuration,
+    arguments: TimetableMatrixArgs,
+) -> Result<()> {
+    let storage_root = &configuration.lpp.recording.recording_storage_root;
+
+    let station_code = StationCode::parse(arguments.station_code)?;
+
+    let snapshot_archive = SnapshotArchive::open(storage_root)
+        .wrap_err_with(|| miette!("Failed to open snapshot archive."))?;
+
+    let station_snapshot = match arguments.date {
+        Some(date) => station_snapshot_for_date(configuration, &snapshot_archive, date)?,
+        None => snapshot_archive
+            .latest_station_snapshot()
+            .wrap_err_with(|| miette!("Failed to load latest station details snapshot."))?
+            .ok_or_else(|| miette!("There are no station details snapshots yet."))?,
+    };
+
+    info!(
+        file_path = %station_snapshot.file_path.display(),
+        station_code = %station_code,
+        "Building timetable matrix from station details snapshot."
+    );
+
+    let service_date = arguments
+        .date
+        .unwrap_or_else(|| station_snapshot.snapshot.service_date_or_capture_date());
+    let station = station_snapshot
+        .snapshot
+        .station_details
+        .iter()
+        .find(|station| station.station_code == station_code)
+        .ok_or_else(|| {
+            miette!(
+                "Station {} is not in station details snapshot {}.",
+                station_code,
+                station_snapshot.file_name()
+            )
+        })?;
+
+    let timetable_matrix = derive_timetable_matrix(
+        station,
+        service_date,
+        &configuration.dataset.display_names,
+    );
+
+    println!("{}", timetable_matrix.to_console_table());
+
+    let output_file_path = match arguments.output_file_path {
+        Some(output_file_path) => output_file_path,
+        None => {
+            let output_directory_path = storage_root.path().join(TIMETABLE_MATRICES_DIRECTORY_NAME);
+
+            fs::create_dir_all(&output_directory_path)
+                .into_diagnostic()
+                .wrap_err_with(|| {
+                    miette!(
+                        "Failed to create output directory {}.",
+                        output_directory_path.display()
+                    )
+                })?;
+
+            output_directory_path.join(format!("{}_{}.csv", station_code, service_date))
+        }
+    };
+
+    fs::write(&output_file_path, timetable_matrix.to_csv())
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to write timetable matrix to {}.",
+                output_file_path.display()
+            )
+        })?;
+
+    info!(
+        file_path = %output_file_path.display(),
+        number_of_columns = timetable_matrix.columns.len(),
+        number_of_hours = timetable_matrix.rows.len(),
+        "Timetable matrix has been saved."
+    );
+
+    Ok(())
+}
+
+/// Finds the latest station snapshot for `date`, falling back to the latest one
+/// captured on a day with the same timetables (see [`crate::calendar::ServiceDayType::has_same_timetables_as`]).
+fn station_snapshot_for_date(
+    configuration: &Configuration,
+    snapshot_archive: &SnapshotArchive,
+    date: NaiveDate,
+) -> Result<ArchivedSnapshot<AllStationsSnapshot>> {
+    if let Some(station_snapshot) = snapshot_archive
+        .latest_station_snapshot_for(date)
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to load station details snapshot for {}.",
+                date
+            )
+        })?
+    {
+        return Ok(station_snapshot);
+    }
+
+    let service_day_type = configuration
+        .lpp
+        .recording
+        .holiday_calendar
+        .service_day_type(date);
+
+    let station_snapshot = snapshot_archive
+        .latest_station_snapshot_for_day_type(service_day_type)
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to load station details snapshot for service day type {:?}.",
+                service_day_type
+            )
+        })?
+        .ok_or_else(|| {
+            miette!(
+                "There is no station details snapshot for {}, nor for any other day \
+                with the same timetables ({:?}).",
+                date,
+                service_day_type
+            )
+        })?;
+
+    info!(
+        date = %date,
+        service_day_type = ?service_day_type,
+        recorded_service_date = %station_snapshot.snapshot.service_date_or_capture_date(),
+        "No station details snapshot was captured for the date, \
+        using the latest one of a day with the same timetables."
+    );
+
+    Ok(station_snapshot)
+}
@@ -0,0 +1,194 @@
+use std::path::Path;
+
+use ed25519_dalek::VerifyingKey;
+use miette::{miette, Context, IntoDiagnostic, Result};
+use tracing::{error, info, warn};
+
+use super::{list_json_files, list_pack_files};
+use crate::{
+    cli::VerifySignaturesArgs,
+    configuration::Configuration,
+    signing::{
+        load_verifying_key_from_file,
+        verify_file_signature,
+        verify_signature,
+        SignatureVerificationOutcome,
+        PUBLIC_KEY_FILE_NAME,
+    },
+    storage::SnapshotPack,
+};
+
+/// Verifies the signatures of all station and route snapshots in the storage root
+/// (including packed ones, whose signatures are stored in their pack).
+///
+/// Returns an error if any of the snapshots is unsigned or does not match its signature.
+pub fn run_verify_signatures(
+    configuration: &Configuration,
+    arguments: VerifySignaturesArgs,
+) -> Result<()> {
+    let storage_root = &configuration.lpp.recording.recording_storage_root;
+
+    let public_key_file_path = arguments
+        .public_key_file_path
+        .unwrap_or_else(|| storage_root.path().join(PUBLIC_KEY_FILE_NAME));
+
+    let verifying_key = load_verifying_key_from_file(&public_key_file_path)?;
+
+    let snapshot_directories = [
+        storage_root
+            .stations()
+            .wrap_err_with(|| miette!("Failed to open station storage."))?
+            .directory_path()
+            .to_path_buf(),
+        storage_root
+            .routes()
+            .wrap_err_with(|| miette!("Failed to open route storage."))?
+            .directory_path()
+            .to_path_buf(),
+    ];
+
+    let mut number_of_valid_files: usize = 0;
+    let mut number_of_failed_files: usize = 0;
+
+    for snapshot_directory in snapshot_directories {
+        let (valid_files, failed_files) =
+            verify_snapshot_directory(&verifying_key, &snapshot_directory)?;
+
+        number_of_valid_files += valid_files;
+        number_of_failed_files += failed_files;
+    }
+
+    info!(
+        valid_files = number_of_valid_files,
+        failed_files = number_of_failed_files,
+        "Finished verifying snapshot signatures."
+    );
+
+    if number_of_failed_files > 0 {
+        return Err(miette!(
+            "{} snapshot(s) failed signature verification.",
+            number_of_failed_files
+        ));
+    }
+
+    Ok(())
+}
+
+/// Verifies the signatures of all loose and packed snapshots in a single snapshot directory.
+///
+/// Returns the number of valid and failed snapshots, in that order.
+fn verify_snapshot_directory(
+    verifying_key: &VerifyingKey,
+    snapshot_directory: &Path,
+) -> Result<(usize, usize)> {
+    let mut number_of_valid_files: usize = 0;
+    let mut number_of_failed_files: usize = 0;
+
+    for snapshot_file_path in list_json_files(snapshot_directory)? {
+        let outcome = verify_file_signature(verifying_key, &snapshot_file_path)?;
+
+        match report_verification_outcome(&snapshot_file_path, outcome) {
+            true => number_of_valid_files += 1,
+            false => number_of_failed_files += 1,
+        }
+    }
+
+    for pack_file_path in list_pack_files(snapshot_directory)? {
+        let pack = SnapshotPack::open(&pack_file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to open snapshot pack."))?;
+
+        for packed_snapshot in pack.snapshots() {
+            let outcome = match &packed_snapshot.signature {
+                Some(encoded_signature) => {
+                    let contents = pack
+                        .read(packed_snapshot)
+                        .into_diagnostic()
+                        .wrap_err_with(|| miette!("Failed to read packed snapshot."))?;
+
+                    verify_signature(verifying_key, &contents, encoded_signature)
+                }
+                None => SignatureVerificationOutcome::MissingSignature,
+            };
+
+            match report_verification_outcome(&pack.snapshot_path(packed_snapshot), outcome) {
+                true => number_of_valid_files += 1,
+                false => number_of_failed_files += 1,
+            }
+        }
+    }
+
+    Ok((number_of_valid_files, number_of_failed_files))
+}
+
+/// Logs a failed verification. Returns `true` if the signature is valid.
+fn report_verification_outcome(
+    snapshot_file_path: &Path,
+    outcome: SignatureVerificationOutcome,
+) -> bool {
+    match outcome {
+        SignatureVerificationOutcome::Valid => return true,
+        SignatureVerificationOutcome::MissingSignature => {
+            warn!(
+                file_path = %snapshot_file_path.display(),
+                "Snapshot is not signed."
+            );
+        }
+        SignatureVerificationOutcome::MalformedSignature => {
+            error!(
+                file_path = %snapshot_file_path.display(),
+                "Snapshot signature file is malformed."
+            );
+        }
+        SignatureVerificationOutcome::Invalid => {
+            error!(
+                file_path = %snapshot_file_path.display(),
+                "Snapshot does not match its signature - it has been modified since capture!"
+            );
+        }
+    }
+
+    false
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use ed25519_dalek::SigningKey;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::signing::sign_file;
+
+    #[test]
+    fn count_tampered_and_unsigned_snapshots_as_failed() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-verify-signatures-test-{}",
+            Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory_path).unwrap();
+
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+
+        let untouched_file_path = directory_path.join("station-details_2023-11-06_03-00-00.json");
+        fs::write(&untouched_file_path, "[]").unwrap();
+        sign_file(&signing_key, &untouched_file_path).unwrap();
+
+        let tampered_file_path = directory_path.join("station-details_2023-11-07_03-00-00.json");
+        fs::write(&tampered_file_path, "[]").unwrap();
+        sign_file(&signing_key, &tampered_file_path).unwrap();
+        fs::write(&tampered_file_path, "[{}]").unwrap();
+
+        let unsigned_file_path = directory_path.join("station-details_2023-11-08_03-00-00.json");
+        fs::write(unsigned_file_path, "[]").unwrap();
+
+        assert_eq!(
+            verify_snapshot_directory(&signing_key.verifying_key(), &directory_path).unwrap(),
+            (1, 2)
+        );
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+}
@@ -0,0 +1,412 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use ratatui::{
+    layout::{Constraint, Direction, Layout},
+    text::Line,
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use reqwest::{Client, StatusCode};
+use tracing::{info, warn};
+
+use super::terminal::TerminalGuard;
+use crate::{
+    api::{
+        arrivals_on_route::{
+            fetch_arrivals_on_route,
+            ArrivalData,
+            ArrivalEstimation,
+            StationArrivalDetails,
+        },
+        client::LppApiClient,
+        errors::LppApiFetchError,
+        routes::{fetch_all_routes, RouteDetails},
+        BusRoute,
+        TripId,
+    },
+    cli::WatchArgs,
+    configuration::Configuration,
+};
+
+/// How often the terminal is checked for key presses between refreshes.
+const KEY_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+
+/// Polls the arrivals of all trips of a single route and shows them in a live-updating
+/// terminal view (stations down the left, vehicles and their ETAs on the right).
+pub async fn run_watch(configuration: &Configuration, arguments: WatchArgs) -> Result<()> {
+    let route = BusRoute::from_route_name(&arguments.route)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Invalid route: {}.", arguments.route))?;
+
+    let refresh_interval = humantime::parse_duration(&arguments.refresh_interval)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to parse duration in --refresh-interval."))?;
+
+    let http_client = Client::builder()
+        .user_agent(&configuration.lpp.api.user_agent)
+        .build()
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to build HTTP client."))?;
+
+    let api_client = LppApiClient::new(http_client, None, None, None, None, None);
+
+    info!(route = %route, "Requesting all routes to find the trips to watch.");
+
+    let mut trips = fetch_all_routes(&configuration.lpp.api, &api_client)
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to fetch all routes."))?
+        .into_iter()
+        .filter(|trip| is_same_route(&trip.route, &route))
+        .collect::<Vec<_>>();
+
+    if trips.is_empty() {
+        return Err(miette!("There are no trips of route {}.", route));
+    }
+
+    trips.sort_by(|first, second| first.name.cmp(&second.name));
+
+    info!(
+        route = %route,
+        number_of_trips = trips.len(),
+        "Watching arrivals (press q to quit)."
+    );
+
+    let mut terminal = TerminalGuard::enter()?;
+    let mut watched_trips = trips
+        .into_iter()
+        .map(|trip| WatchedTrip {
+            trip,
+            arrivals: Ok(Vec::new()),
+            previous_trip_ids: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+    let mut refreshed_at: Option<DateTime<Local>> = None;
+    let mut next_refresh_at = Instant::now();
+
+    loop {
+        terminal
+            .terminal
+            .draw(|frame| draw_watched_trips(frame, &route, &watched_trips, refreshed_at))
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to draw terminal UI."))?;
+
+        if Instant::now() >= next_refresh_at {
+            let mut trips_with_possibly_changed_ids = Vec::new();
+
+            for (trip_index, watched_trip) in watched_trips.iter_mut().enumerate() {
+                let arrivals = fetch_arrivals_on_route(
+                    &configuration.lpp.api,
+                    &api_client,
+                    &watched_trip.trip.trip_id,
+                )
+                .await;
+
+                if trip_id_may_have_changed(&arrivals) {
+                    trips_with_possibly_changed_ids.push(trip_index);
+                }
+
+                watched_trip.arrivals = arrivals.map_err(|error| error.to_string());
+            }
+
+            if !trips_with_possibly_changed_ids.is_empty() {
+                follow_changed_trip_ids(
+                    configuration,
+                    &api_client,
+                    &mut watched_trips,
+                    &trips_with_possibly_changed_ids,
+                )
+                .await;
+            }
+
+            refreshed_at = Some(Local::now());
+            next_refresh_at = Instant::now() + refresh_interval;
+            continue;
+        }
+
+        let has_event = event::poll(KEY_POLL_INTERVAL)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to poll terminal events."))?;
+        if !has_event {
+            continue;
+        }
+
+        let event = event::read()
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to read terminal event."))?;
+
+        let Event::Key(key_event) = event else {
+            continue;
+        };
+        if key_event.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match key_event.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('r') => next_refresh_at = Instant::now(),
+            _ => {}
+        }
+    }
+}
+
+
+struct WatchedTrip {
+    trip: RouteDetails,
+
+    /// Arrivals from the latest refresh, or why fetching them failed.
+    arrivals: Result<Vec<StationArrivalDetails>, String>,
+
+    /// Trip IDs this trip had before LPP changed them (oldest first).
+    previous_trip_ids: Vec<TripId>,
+}
+
+/// LPP occasionally changes trip IDs during the day, after which requests for
+/// the old ID fail with 404 Not Found or return no stations.
+fn trip_id_may_have_changed(
+    arrivals: &Result<Vec<StationArrivalDetails>, LppApiFetchError>,
+) -> bool {
+    match arrivals {
+        Ok(stations) => stations.is_empty(),
+        Err(LppApiFetchError::ClientHTTPError(status)) => *status == StatusCode::NOT_FOUND,
+        Err(_) => false,
+    }
+}
+
+/// Finds the current version of a trip whose ID has changed:
+/// the trip of the same route with the same name, but a different ID.
+fn find_trip_with_changed_id<'t>(
+    trip: &RouteDetails,
+    current_trips: &'t [RouteDetails],
+) -> Option<&'t RouteDetails> {
+    current_trips.iter().find(|current_trip| {
+        current_trip.route == trip.route
+            && current_trip.name == trip.name
+            && current_trip.trip_id != trip.trip_id
+    })
+}
+
+/// Requests all routes again to find the new IDs of the given trips. Trips whose IDs
+/// have changed are switched over to the new ID and their arrivals are fetched again.
+async fn follow_changed_trip_ids(
+    configuration: &Configuration,
+    api_client: &LppApiClient,
+    watched_trips: &mut [WatchedTrip],
+    trip_indices: &[usize],
+) {
+    let current_trips = match fetch_all_routes(&configuration.lpp.api, api_client).await {
+        Ok(current_trips) => current_trips,
+        Err(error) => {
+            warn!(
+                error = ?error,
+                "Failed to fetch all routes, can't check whether any trip IDs have changed."
+            );
+            return;
+        }
+    };
+
+    for &trip_index in trip_indices {
+        let watched_trip = &mut watched_trips[trip_index];
+
+        let Some(current_trip) = find_trip_with_changed_id(&watched_trip.trip, &current_trips)
+        else {
+            continue;
+        };
+
+        info!(
+            route = %watched_trip.trip.route,
+            trip_name = watched_trip.trip.name,
+            previous_trip_id = %watched_trip.trip.trip_id,
+            new_trip_id = %current_trip.trip_id,
+            "Trip ID has changed, watching the trip under its new ID."
+        );
+
+        let previous_trip = std::mem::replace(&mut watched_trip.trip, current_trip.clone());
+        watched_trip.previous_trip_ids.push(previous_trip.trip_id);
+
+        watched_trip.arrivals = fetch_arrivals_on_route(
+            &configuration.lpp.api,
+            api_client,
+            &watched_trip.trip.trip_id,
+        )
+        .await
+        .map_err(|error| error.to_string());
+    }
+}
+
+/// Whether the trip belongs to the watched route. Additional route information
+/// (e.g. `(GROS.)` in `76(GROS.)`) is ignored.
+fn is_same_route(trip_route: &BusRoute, watched_route: &BusRoute) -> bool {
+    trip_route.prefix == watched_route.prefix
+        && trip_route.base_route_number == watched_route.base_route_number
+        && trip_route.suffix == watched_route.suffix
+}
+
+fn draw_watched_trips(
+    frame: &mut Frame,
+    route: &BusRoute,
+    watched_trips: &[WatchedTrip],
+    refreshed_at: Option<DateTime<Local>>,
+) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(frame.size());
+
+    let trip_columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            watched_trips
+                .iter()
+                .map(|_| Constraint::Ratio(1, watched_trips.len() as u32))
+                .collect::<Vec<_>>(),
+        )
+        .split(rows[0]);
+
+    for (watched_trip, area) in watched_trips.iter().zip(trip_columns.iter()) {
+        let lines = match &watched_trip.arrivals {
+            Ok(stations) => stations
+                .iter()
+                .map(|station| Line::from(station_arrivals_line(station)))
+                .collect::<Vec<_>>(),
+            Err(error) => vec![Line::from(format!("Failed to fetch arrivals: {}", error))],
+        };
+
+        let title = match watched_trip.previous_trip_ids.is_empty() {
+            true => format!(
+                "{} {}",
+                watched_trip.trip.route, watched_trip.trip.name
+            ),
+            false => format!(
+                "{} {} (trip ID changed)",
+                watched_trip.trip.route, watched_trip.trip.name
+            ),
+        };
+
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)),
+            *area,
+        );
+    }
+
+    let status = match refreshed_at {
+        Some(refreshed_at) => format!(
+            "Route {} | refreshed at {} | r: refresh now, q: quit",
+            route,
+            refreshed_at.format("%H:%M:%S")
+        ),
+        None => format!("Route {} | refreshing... | q: quit", route),
+    };
+    frame.render_widget(Paragraph::new(status), rows[1]);
+}
+
+/// Example: `12 KONGRESNI TRG      #1234 3 min, #1250 12 min (scheduled)`.
+fn station_arrivals_line(station: &StationArrivalDetails) -> String {
+    let arrivals = station
+        .arrivals
+        .iter()
+        .map(format_arrival)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "{:>2} {:<24} {}",
+        station.stop_number, station.name, arrivals
+    )
+}
+
+/// Example: `#1234 3 min`, `#1250 12 min (scheduled)` or `#1234 arriving`.
+fn format_arrival(arrival: &ArrivalData) -> String {
+    let estimation = match arrival.arrival_estimation {
+        ArrivalEstimation::LocationBased { eta_in_minutes } => format!("{} min", eta_in_minutes),
+        ArrivalEstimation::TimetableBased { eta_in_minutes } => {
+            format!("{} min (scheduled)", eta_in_minutes)
+        }
+        ArrivalEstimation::CurrentlyArrivingToStation => String::from("arriving"),
+        ArrivalEstimation::OnDetour => String::from("detour"),
+    };
+
+    match arrival.heading_to_garage {
+        true => format!(
+            "#{} {} (to garage)",
+            arrival.vehicle_id, estimation
+        ),
+        false => format!("#{} {}", arrival.vehicle_id, estimation),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_fixtures::{arrival, trip};
+
+    #[test]
+    fn format_arrivals_and_match_routes() {
+        assert_eq!(
+            format_arrival(&arrival(
+                "1234",
+                ArrivalEstimation::LocationBased { eta_in_minutes: 3 }
+            )),
+            "#1234 3 min"
+        );
+        assert_eq!(
+            format_arrival(&ArrivalData {
+                heading_to_garage: true,
+                ..arrival(
+                    "1234",
+                    ArrivalEstimation::TimetableBased { eta_in_minutes: 12 }
+                )
+            }),
+            "#1234 12 min (scheduled) (to garage)"
+        );
+        assert_eq!(
+            format_arrival(&arrival(
+                "1234",
+                ArrivalEstimation::CurrentlyArrivingToStation
+            )),
+            "#1234 arriving"
+        );
+
+        let watched_route = BusRoute::from_route_name("76").unwrap();
+        assert!(is_same_route(
+            &BusRoute::from_route_name("76(GROS.)").unwrap(),
+            &watched_route
+        ));
+        assert!(!is_same_route(
+            &BusRoute::from_route_name("76B").unwrap(),
+            &watched_route
+        ));
+    }
+
+    #[test]
+    fn follow_trips_with_changed_ids() {
+        assert!(trip_id_may_have_changed(&Ok(Vec::new())));
+        assert!(trip_id_may_have_changed(&Err(
+            LppApiFetchError::ClientHTTPError(StatusCode::NOT_FOUND)
+        )));
+        assert!(!trip_id_may_have_changed(&Err(
+            LppApiFetchError::ServerHTTPError(StatusCode::BAD_GATEWAY)
+        )));
+
+        let watched_trip = trip("3G", "GROSUPLJE - BEŽIGRAD", "BD96D5A0");
+        let current_trips = vec![
+            trip("3G", "BEŽIGRAD - GROSUPLJE", "0C5EA3E2"),
+            trip("3", "GROSUPLJE - BEŽIGRAD", "77B1F1A4"),
+            trip("3G", "GROSUPLJE - BEŽIGRAD", "E4A0C2B9"),
+        ];
+
+        assert_eq!(
+            find_trip_with_changed_id(&watched_trip, &current_trips)
+                .map(|current_trip| &current_trip.trip_id),
+            Some(&TripId::new("E4A0C2B9"))
+        );
+        assert!(
+            find_trip_with_changed_id(&watched_trip, std::slice::from_ref(&watched_trip)).is_none()
+        );
+    }
+}
@@ -1,5 +1,8 @@
+mod overrides;
+pub mod schema;
 mod structure;
 mod traits;
 pub mod utilities;
 
+pub use overrides::ConfigurationOverride;
 pub use structure::*;
@@ -0,0 +1,167 @@
+use std::fmt::{self, Display, Formatter};
+
+use miette::{miette, Result};
+
+
+/// A configuration value overridden from the command line
+/// (`--override lpp.recording.capture_mode=timetables-only`).
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConfigurationOverride {
+    /// Dot-separated path of the overridden key, e.g. `lpp.recording.capture_mode`.
+    pub key: String,
+
+    pub value: toml::Value,
+}
+
+impl ConfigurationOverride {
+    /// Parses a `key=value` override. The value is parsed as a TOML value
+    /// (e.g. `true`, `5` or `["a", "b"]`) and falls back to a string if that fails,
+    /// so strings like `10s` don't need to be quoted.
+    pub fn parse(raw_override: &str) -> Result<Self> {
+        let Some((key, raw_value)) = raw_override.split_once('=') else {
+            return Err(miette!(
+                "Invalid configuration override \"{}\" (expected key=value).",
+                raw_override
+            ));
+        };
+
+        let key = key.trim();
+        if key.split('.').any(|segment| segment.is_empty()) {
+            return Err(miette!(
+                "Invalid configuration override key \"{}\" (expected e.g. lpp.recording.capture_mode).",
+                key
+            ));
+        }
+
+        let raw_value = raw_value.trim();
+        let value = toml::from_str::<toml::Table>(&format!("value = {}", raw_value))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(raw_value.to_string()));
+
+        Ok(Self {
+            key: key.to_string(),
+            value,
+        })
+    }
+
+    /// Returns `true` if `key_path` (as reported for unknown keys) is this key or lies below it.
+    pub(super) fn covers(&self, key_path: &str) -> bool {
+        key_path == self.key
+            || key_path
+                .strip_prefix(self.key.as_str())
+                .map(|rest| rest.starts_with('.'))
+                .unwrap_or(false)
+    }
+}
+
+impl Display for ConfigurationOverride {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
+
+/// Applies the overrides to the parsed (but not yet resolved) configuration file,
+/// creating any missing tables along the way.
+pub(super) fn apply_overrides(
+    configuration: &mut toml::Table,
+    overrides: &[ConfigurationOverride],
+) -> Result<()> {
+    for configuration_override in overrides {
+        let mut key_segments: Vec<&str> = configuration_override.key.split('.').collect();
+        let value_key = key_segments.pop().unwrap_or_default();
+
+        let mut table = &mut *configuration;
+        for segment in key_segments {
+            let value = table
+                .entry(segment)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+
+            table = value.as_table_mut().ok_or_else(|| {
+                miette!(
+                    "Can't override {}: \"{}\" is not a table.",
+                    configuration_override.key,
+                    segment
+                )
+            })?;
+        }
+
+        table.insert(
+            value_key.to_string(),
+            configuration_override.value.clone(),
+        );
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_apply_overrides() {
+        let interval_override =
+            ConfigurationOverride::parse("lpp.recording.catch_up_interval = 10s").unwrap();
+        assert_eq!(
+            interval_override.key,
+            "lpp.recording.catch_up_interval"
+        );
+        assert_eq!(
+            interval_override.value,
+            toml::Value::String(String::from("10s"))
+        );
+
+        let flag_override =
+            ConfigurationOverride::parse("lpp.recording.prioritize_hub_stations=true").unwrap();
+        assert_eq!(flag_override.value, toml::Value::Boolean(true));
+
+        let url_override =
+            ConfigurationOverride::parse(r#"upload.url="https://example.com/a=b""#).unwrap();
+        assert_eq!(
+            url_override.value,
+            toml::Value::String(String::from("https://example.com/a=b"))
+        );
+
+        assert!(ConfigurationOverride::parse("lpp.recording").is_err());
+        assert!(ConfigurationOverride::parse("lpp..recording=1").is_err());
+
+        let mut configuration: toml::Table = toml::from_str(
+            r#"
+            [lpp.recording]
+            prioritize_hub_stations = false
+            capture_mode = "full"
+            "#,
+        )
+        .unwrap();
+
+        apply_overrides(
+            &mut configuration,
+            &[flag_override.clone(), url_override],
+        )
+        .unwrap();
+
+        assert_eq!(
+            configuration["lpp"]["recording"]["prioritize_hub_stations"],
+            toml::Value::Boolean(true)
+        );
+        assert_eq!(
+            configuration["lpp"]["recording"]["capture_mode"],
+            toml::Value::String(String::from("full"))
+        );
+        // Missing tables are created.
+        assert_eq!(
+            configuration["upload"]["url"],
+            toml::Value::String(String::from("https://example.com/a=b"))
+        );
+
+        let nested_in_value =
+            ConfigurationOverride::parse("lpp.recording.capture_mode.kind=full").unwrap();
+        assert!(apply_overrides(&mut configuration, &[nested_in_value]).is_err());
+
+        assert!(flag_override.covers("lpp.recording.prioritize_hub_stations"));
+        assert!(!flag_override.covers("lpp.recording.prioritize_hub_stations_too"));
+    }
+}
@@ -0,0 +1,267 @@
+use std::fmt::Write;
+
+use schemars::{
+    schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec},
+    schema_for,
+};
+
+use super::UnresolvedConfiguration;
+
+
+/// Generates the JSON Schema of the configuration file.
+pub fn configuration_schema() -> RootSchema {
+    schema_for!(UnresolvedConfiguration)
+}
+
+/// Renders the configuration schema as an annotated TOML outline, e.g.
+///
+/// ```toml
+/// [lpp.api]
+/// # HTTP User-Agent to present in HTTP requests as.
+/// user_agent = <string>
+/// ```
+pub fn render_configuration_schema_as_toml(root_schema: &RootSchema) -> String {
+    let mut output = String::new();
+    render_table(&mut output, root_schema, &root_schema.schema, "");
+
+    output
+}
+
+
+fn render_table(
+    output: &mut String,
+    root_schema: &RootSchema,
+    table_schema: &SchemaObject,
+    table_path: &str,
+) {
+    let Some(object_validation) = table_schema.object.as_ref() else {
+        return;
+    };
+
+    let mut sub_tables = Vec::new();
+
+    for (key, property_schema) in &object_validation.properties {
+        let Some(property_schema) = resolve_schema(root_schema, property_schema) else {
+            continue;
+        };
+
+        if is_table(property_schema) {
+            sub_tables.push((key, property_schema));
+            continue;
+        }
+
+        // Fields that reference a definition carry their own documentation,
+        // which takes precedence over the documentation of the definition.
+        let metadata = match object_validation.properties.get(key) {
+            Some(Schema::Object(SchemaObject {
+                metadata: Some(field_metadata),
+                ..
+            })) => Some(field_metadata.as_ref()),
+            _ => property_schema.metadata.as_deref(),
+        };
+
+        if let Some(description) = metadata.and_then(|metadata| metadata.description.as_ref()) {
+            for description_line in description.lines() {
+                let _ = writeln!(output, "# {}", description_line);
+            }
+        }
+
+        let mut annotations = Vec::new();
+        if !object_validation.required.contains(key) {
+            annotations.push(String::from("optional"));
+        }
+        if let Some(default) = metadata.and_then(|metadata| metadata.default.as_ref()) {
+            if !default.is_null() {
+                annotations.push(format!("default: {}", default));
+            }
+        }
+
+        let _ = write!(
+            output,
+            "{} = <{}>",
+            key,
+            instance_type_label(property_schema)
+        );
+        if !annotations.is_empty() {
+            let _ = write!(output, "  # {}", annotations.join(", "));
+        }
+        output.push('\n');
+    }
+
+    for (key, sub_table_schema) in sub_tables {
+        let sub_table_path = if table_path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", table_path, key)
+        };
+
+        if !output.is_empty() {
+            output.push('\n');
+        }
+
+        let _ = writeln!(output, "[{}]", sub_table_path);
+        render_table(
+            output,
+            root_schema,
+            sub_table_schema,
+            &sub_table_path,
+        );
+    }
+}
+
+/// Follows `$ref`s (including the `allOf: [{ $ref }]` form schemars uses
+/// for documented fields) to the definition of the schema.
+fn resolve_schema<'a>(root_schema: &'a RootSchema, schema: &'a Schema) -> Option<&'a SchemaObject> {
+    let Schema::Object(schema_object) = schema else {
+        return None;
+    };
+
+    if let Some(reference) = schema_object.reference.as_ref() {
+        let definition_name = reference.rsplit('/').next()?;
+        return resolve_schema(
+            root_schema,
+            root_schema.definitions.get(definition_name)?,
+        );
+    }
+
+    if let Some(subschemas) = schema_object.subschemas.as_ref() {
+        if let Some([single_subschema]) = subschemas.all_of.as_deref() {
+            return resolve_schema(root_schema, single_subschema);
+        }
+    }
+
+    Some(schema_object)
+}
+
+fn is_table(schema_object: &SchemaObject) -> bool {
+    schema_object.object.is_some()
+}
+
+fn instance_type_label(schema_object: &SchemaObject) -> String {
+    // Enums with documented variants are rendered as `oneOf` a list of single-value enums.
+    if let Some(variant_schemas) = schema_object
+        .subschemas
+        .as_ref()
+        .and_then(|subschemas| subschemas.one_of.as_ref())
+    {
+        let variant_values: Option<Vec<String>> = variant_schemas
+            .iter()
+            .map(|variant_schema| match variant_schema {
+                Schema::Object(SchemaObject {
+                    enum_values: Some(values),
+                    ..
+                }) => Some(
+                    values
+                        .iter()
+                        .map(|value| value.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" | "),
+                ),
+                _ => None,
+            })
+            .collect();
+
+        if let Some(variant_values) = variant_values {
+            return variant_values.join(" | ");
+        }
+    }
+
+    if let Some(enum_values) = schema_object.enum_values.as_ref() {
+        return enum_values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(" | ");
+    }
+
+    let instance_types: Vec<InstanceType> = match schema_object.instance_type.as_ref() {
+        Some(SingleOrVec::Single(instance_type)) => vec![**instance_type],
+        Some(SingleOrVec::Vec(instance_types)) => instance_types.clone(),
+        None => return String::from("any"),
+    };
+
+    instance_types
+        .into_iter()
+        .filter(|instance_type| *instance_type != InstanceType::Null)
+        .map(|instance_type| match instance_type {
+            InstanceType::Null => "null",
+            InstanceType::Boolean => "boolean",
+            InstanceType::Object => "table",
+            InstanceType::Array => "array",
+            InstanceType::Number => "float",
+            InstanceType::String => "string",
+            InstanceType::Integer => "integer",
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use schemars::JsonSchema;
+
+    use super::*;
+
+    /// How requests are sent.
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    #[serde(rename_all = "kebab-case")]
+    enum ExampleMode {
+        /// One request at a time.
+        Sequential,
+        /// Several requests at once.
+        Concurrent,
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    struct ExampleLimits {
+        /// Maximum number of requests per second.
+        requests_per_second: f64,
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    struct ExampleConfiguration {
+        /// Base URL of the API.
+        base_url: String,
+        /// How many times a failed request is retried.
+        #[serde(default = "default_retries")]
+        retries: u32,
+        /// How requests are sent.
+        mode: ExampleMode,
+        /// Whether to log every request.
+        verbose: Option<bool>,
+        /// Request limits.
+        limits: ExampleLimits,
+    }
+
+    fn default_retries() -> u32 {
+        3
+    }
+
+    #[test]
+    fn render_annotated_toml_outline() {
+        let rendered =
+            render_configuration_schema_as_toml(&schema_for!(ExampleConfiguration));
+
+        assert_eq!(
+            rendered,
+            "\
+# Base URL of the API.
+base_url = <string>
+# How many times a failed request is retried.
+retries = <integer>  # optional, default: 3
+# How requests are sent.
+mode = <\"sequential\" | \"concurrent\">
+# Whether to log every request.
+verbose = <boolean>  # optional
+
+[limits]
+# Maximum number of requests per second.
+requests_per_second = <float>
+"
+        );
+    }
+}
@@ -1,54 +1,176 @@
 use std::{
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
     time::Duration,
 };
 
+use ed25519_dalek::SigningKey;
+use lettre::message::Mailbox;
 use miette::{miette, Context, IntoDiagnostic, Result};
 use reqwest::Url;
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tracing_subscriber::EnvFilter;
 
-use super::{traits::ResolvableConfiguration, utilities::get_default_configuration_file_path};
-use crate::storage::StorageRoot;
+use super::{
+    overrides::{apply_overrides, ConfigurationOverride},
+    traits::ResolvableConfiguration,
+    utilities::{get_default_configuration_file_path, parse_byte_size},
+};
+use crate::{
+    api::{
+        client::WarmupPolicy,
+        concurrency::ConcurrencyPolicy,
+        rate_limit::RateLimitPolicy,
+        timetable::TimetableWindowPolicy,
+        StationCode,
+    },
+    calendar::HolidayCalendar,
+    display_names::{DisplayNameStyle, DisplayNames},
+    districts::DistrictBoundaries,
+    recorder::acceptance::AcceptancePolicy,
+    signing::load_signing_key_from_file,
+    storage::{FileNameTemplate, FileNameTemplates, StorageRoot},
+};
 
 #[derive(Clone)]
 pub struct Configuration {
     pub logging: LoggingConfiguration,
     pub lpp: LppConfiguration,
+
+    /// If set, each saved snapshot is also uploaded to a remote HTTP endpoint.
+    pub upload: Option<UploadConfiguration>,
+
+    /// Provenance embedded into the files derived from snapshots (e.g. the HTML report).
+    pub dataset: DatasetConfiguration,
+
+    pub notifications: NotificationsConfiguration,
+
+    /// If set, traces and health metrics are exported to an OpenTelemetry collector.
+    pub telemetry: Option<TelemetryConfiguration>,
+
+    /// Hex-encoded SHA-256 hash of the configuration file contents
+    /// (and the overrides, if there are any).
+    pub file_hash: String,
+
+    /// Values overridden from the command line (`--override`), in the order they were applied.
+    pub overrides: Vec<ConfigurationOverride>,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, JsonSchema, Clone)]
 pub struct UnresolvedConfiguration {
     logging: UnresolvedLoggingConfiguration,
     lpp: UnresolvedLppConfiguration,
+    #[serde(default)]
+    upload: Option<UnresolvedUploadConfiguration>,
+    #[serde(default)]
+    dataset: UnresolvedDatasetConfiguration,
+    #[serde(default)]
+    notifications: UnresolvedNotificationsConfiguration,
+    #[serde(default)]
+    telemetry: Option<UnresolvedTelemetryConfiguration>,
 }
 
 impl Configuration {
-    pub fn load_from_path<P: AsRef<Path>>(configuration_file_path: P) -> Result<Self> {
+    /// Loads and resolves the configuration file at the given path.
+    ///
+    /// If `reject_unknown_keys` is `true`, any key that is not part of the
+    /// configuration schema (e.g. a typo like `user_agnet`) is considered an error
+    /// instead of being silently ignored.
+    ///
+    /// The `overrides` are applied to the file contents before the configuration is resolved,
+    /// so overridden values are validated like the rest. Overriding unknown keys is always an error.
+    pub fn load_from_path<P: AsRef<Path>>(
+        configuration_file_path: P,
+        reject_unknown_keys: bool,
+        overrides: Vec<ConfigurationOverride>,
+    ) -> Result<Self> {
         let configuration_file_path = configuration_file_path.as_ref();
 
         let configuration_file_contents = fs::read_to_string(configuration_file_path)
             .into_diagnostic()
             .wrap_err_with(|| miette!("Failed to read configuration file."))?;
 
-        let unresolved_configuration: UnresolvedConfiguration =
-            toml::from_str(&configuration_file_contents)
+        let mut unknown_keys = Vec::new();
+        let unresolved_configuration: UnresolvedConfiguration = if overrides.is_empty() {
+            serde_ignored::deserialize(
+                toml::Deserializer::new(&configuration_file_contents),
+                |unknown_key_path| unknown_keys.push(unknown_key_path.to_string()),
+            )
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to parse configuration file as TOML."))?
+        } else {
+            let mut configuration_table: toml::Table = toml::from_str(&configuration_file_contents)
                 .into_diagnostic()
                 .wrap_err_with(|| miette!("Failed to parse configuration file as TOML."))?;
 
+            apply_overrides(&mut configuration_table, &overrides)?;
+
+            serde_ignored::deserialize(
+                toml::Value::Table(configuration_table),
+                |unknown_key_path| unknown_keys.push(unknown_key_path.to_string()),
+            )
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to parse configuration file with overrides."))?
+        };
+
+        let overridden_unknown_keys: Vec<_> = overrides
+            .iter()
+            .filter(|configuration_override| {
+                unknown_keys
+                    .iter()
+                    .any(|unknown_key| configuration_override.covers(unknown_key))
+            })
+            .map(|configuration_override| configuration_override.key.as_str())
+            .collect();
+
+        if !overridden_unknown_keys.is_empty() {
+            return Err(miette!(
+                "Can't override unknown configuration keys: {}.",
+                overridden_unknown_keys.join(", ")
+            ));
+        }
+
+        if reject_unknown_keys && !unknown_keys.is_empty() {
+            return Err(miette!(
+                "Configuration file contains unknown keys: {}.",
+                unknown_keys.join(", ")
+            ));
+        }
+
         let resolved_configuration = unresolved_configuration
             .resolve()
             .wrap_err_with(|| miette!("Failed to resolve configuration."))?;
 
-        Ok(resolved_configuration)
+        // Runs with overrides don't use the configuration as stored in the file,
+        // so the overrides are part of the hash.
+        let mut file_hasher = Sha256::new();
+        file_hasher.update(configuration_file_contents.as_bytes());
+        for configuration_override in &overrides {
+            file_hasher.update(format!("\n# --override {}", configuration_override).as_bytes());
+        }
+
+        Ok(Self {
+            file_hash: hex::encode(file_hasher.finalize()),
+            overrides,
+            ..resolved_configuration
+        })
     }
 
-    pub fn load_from_default_path() -> Result<Self> {
+    pub fn load_from_default_path(
+        reject_unknown_keys: bool,
+        overrides: Vec<ConfigurationOverride>,
+    ) -> Result<Self> {
         let default_configuration_file_path = get_default_configuration_file_path()
             .wrap_err_with(|| miette!("Failed to construct default configuration file path."))?;
 
-        Self::load_from_path(default_configuration_file_path)
+        Self::load_from_path(
+            default_configuration_file_path,
+            reject_unknown_keys,
+            overrides,
+        )
     }
 }
 
@@ -66,17 +188,88 @@ impl ResolvableConfiguration for UnresolvedConfiguration {
             .resolve()
             .wrap_err_with(|| miette!("Failed to resolve table \"lpp\"."))?;
 
-        Ok(Self::Resolved { logging, lpp })
+        let upload = self
+            .upload
+            .map(|upload| upload.resolve())
+            .transpose()
+            .wrap_err_with(|| miette!("Failed to resolve table \"upload\"."))?;
+
+        let dataset = self
+            .dataset
+            .resolve()
+            .wrap_err_with(|| miette!("Failed to resolve table \"dataset\"."))?;
+
+        let notifications = self
+            .notifications
+            .resolve()
+            .wrap_err_with(|| miette!("Failed to resolve table \"notifications\"."))?;
+
+        let telemetry = self
+            .telemetry
+            .map(|telemetry| telemetry.resolve())
+            .transpose()
+            .wrap_err_with(|| miette!("Failed to resolve table \"telemetry\"."))?;
+
+        Ok(Self::Resolved {
+            logging,
+            lpp,
+            upload,
+            dataset,
+            notifications,
+            telemetry,
+            // Filled in by `Configuration::load_from_path`, which has the file contents.
+            file_hash: String::new(),
+            overrides: Vec::new(),
+        })
     }
 }
 
 
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, JsonSchema, Clone)]
 struct UnresolvedLoggingConfiguration {
+    /// The console log output level (overridable with the RUST_LOG environment variable).
     console_output_level_filter: String,
+    /// The log file output level.
     log_file_output_level_filter: String,
+    /// Log file output directory.
     log_file_output_directory: String,
+    /// Log file rotation policy: `hourly`, `daily` or `size`.
+    #[serde(default = "default_log_file_rotation")]
+    log_file_rotation: String,
+    /// Maximum size of a single log file when using `size` rotation (e.g. `64MiB`).
+    #[serde(default = "default_log_file_max_size")]
+    log_file_max_size: String,
+    /// Maximum number of log files to keep; older files are deleted. If unset, all are kept.
+    #[serde(default)]
+    log_file_max_retained_files: Option<usize>,
+    /// Log file name prefix (the rotation timestamp is appended to it).
+    #[serde(default = "default_log_file_name_prefix")]
+    log_file_name_prefix: String,
+}
+
+fn default_log_file_rotation() -> String {
+    String::from("daily")
+}
+
+fn default_log_file_max_size() -> String {
+    String::from("64MiB")
+}
+
+fn default_log_file_name_prefix() -> String {
+    String::from("recording-server.log")
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LogFileRotation {
+    /// Start a new log file every hour.
+    Hourly,
+
+    /// Start a new log file every day.
+    Daily,
+
+    /// Start a new log file whenever the current one reaches the given size.
+    Size { max_file_size_in_bytes: u64 },
 }
 
 #[derive(Clone)]
@@ -84,6 +277,9 @@ pub struct LoggingConfiguration {
     pub console_output_level_filter: String,
     pub log_file_output_level_filter: String,
     pub log_file_output_directory: PathBuf,
+    pub log_file_rotation: LogFileRotation,
+    pub log_file_max_retained_files: Option<usize>,
+    pub log_file_name_prefix: String,
 }
 
 impl ResolvableConfiguration for UnresolvedLoggingConfiguration {
@@ -101,10 +297,40 @@ impl ResolvableConfiguration for UnresolvedLoggingConfiguration {
 
         let log_file_output_directory = PathBuf::from(self.log_file_output_directory);
 
+        let log_file_rotation = match self.log_file_rotation.to_lowercase().as_str() {
+            "hourly" => LogFileRotation::Hourly,
+            "daily" => LogFileRotation::Daily,
+            "size" => LogFileRotation::Size {
+                max_file_size_in_bytes: parse_byte_size(&self.log_file_max_size)
+                    .wrap_err_with(|| miette!("Failed to parse field `log_file_max_size`."))?,
+            },
+            invalid_rotation => {
+                return Err(miette!(
+                    "Invalid value in field `log_file_rotation`: {} (expected hourly/daily/size).",
+                    invalid_rotation
+                ))
+            }
+        };
+
+        if self.log_file_max_retained_files == Some(0) {
+            return Err(miette!(
+                "Field `log_file_max_retained_files` must be at least 1."
+            ));
+        }
+
+        if self.log_file_name_prefix.is_empty() {
+            return Err(miette!(
+                "Field `log_file_name_prefix` must not be empty."
+            ));
+        }
+
         Ok(Self::Resolved {
             console_output_level_filter: self.console_output_level_filter,
             log_file_output_level_filter: self.log_file_output_level_filter,
             log_file_output_directory,
+            log_file_rotation,
+            log_file_max_retained_files: self.log_file_max_retained_files,
+            log_file_name_prefix: self.log_file_name_prefix,
         })
     }
 }
@@ -123,7 +349,7 @@ impl LoggingConfiguration {
 
 
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, JsonSchema, Clone)]
 struct UnresolvedLppConfiguration {
     api: UnresolvedLppApiConfiguration,
     recording: UnresolvedLppRecordingConfiguration,
@@ -146,18 +372,114 @@ impl ResolvableConfiguration for UnresolvedLppConfiguration {
     }
 }
 
+#[cfg(test)]
+impl LppConfiguration {
+    /// Default configuration that requests the API at `lpp_base_api_url` and stores
+    /// recordings into `recording_storage_directory_path`. `recording` may add or override
+    /// fields of the `[lpp.recording]` table (e.g. to enable arrival recording).
+    pub fn for_tests(
+        lpp_base_api_url: &Url,
+        recording_storage_directory_path: &Path,
+        recording: toml::Table,
+    ) -> Self {
+        let mut api_table = toml::Table::new();
+        api_table.insert(
+            String::from("lpp_base_api_url"),
+            lpp_base_api_url.to_string().into(),
+        );
+        api_table.insert(
+            String::from("user_agent"),
+            String::from("visualization-recorder / test").into(),
+        );
 
+        let mut recording_table = toml::Table::new();
+        recording_table.insert(
+            String::from("full_station_and_timetable_details_request_interval"),
+            String::from("24hours").into(),
+        );
+        recording_table.insert(
+            String::from("recording_storage_directory_path"),
+            recording_storage_directory_path.display().to_string().into(),
+        );
+        recording_table.extend(recording);
 
-#[derive(Deserialize, Clone)]
+        let mut configuration_table = toml::Table::new();
+        configuration_table.insert(String::from("api"), api_table.into());
+        configuration_table.insert(String::from("recording"), recording_table.into());
+
+        toml::Value::Table(configuration_table)
+            .try_into::<UnresolvedLppConfiguration>()
+            .unwrap()
+            .resolve()
+            .unwrap()
+    }
+}
+
+
+
+#[derive(Deserialize, JsonSchema, Clone)]
 struct UnresolvedLppApiConfiguration {
+    /// The base URL for LPP's API.
     lpp_base_api_url: String,
+    /// HTTP User-Agent to present in HTTP requests as.
     user_agent: String,
+    /// Splitting of timetable requests for stations with many route groups.
+    #[serde(default)]
+    timetable_batching: UnresolvedTimetableBatchingConfiguration,
+    /// Throttling of requests right after startup.
+    #[serde(default)]
+    warmup: UnresolvedApiWarmupConfiguration,
+    /// Adaptive limit on the number of requests in flight at the same time.
+    #[serde(default)]
+    concurrency: UnresolvedApiConcurrencyConfiguration,
+    /// Token bucket limit on the rate of requests, shared by all recorder tasks.
+    #[serde(default)]
+    rate_limit: UnresolvedApiRateLimitConfiguration,
+    /// Fraction of responses (from `0.0` to `1.0`) that are checked for fields that don't match
+    /// our response schemas, which is logged as an early warning of API changes. `0.0` disables the checks.
+    #[serde(default = "default_schema_drift_sample_rate")]
+    schema_drift_sample_rate: f64,
+    /// Whether a single malformed trip fails the entire timetable of a station
+    /// (otherwise such trips are skipped and recorded in the snapshot). Defaults to `false`.
+    #[serde(default)]
+    strict_timetable_parsing: bool,
+    /// Whether to keep stations whose station codes don't have the usual six-digit format
+    /// (otherwise they are dropped). Either way, they are recorded as a snapshot warning.
+    /// Defaults to `false`.
+    #[serde(default)]
+    lenient_station_codes: bool,
+}
+
+fn default_schema_drift_sample_rate() -> f64 {
+    0.01
 }
 
 #[derive(Clone)]
 pub struct LppApiConfiguration {
     pub lpp_base_api_url: Url,
     pub user_agent: String,
+
+    /// If set, timetable requests for a station are split into several requests
+    /// with at most this many route groups each.
+    pub max_route_groups_per_timetable_request: Option<usize>,
+
+    /// If set, requests are spaced out for a while after startup.
+    pub warmup: Option<WarmupPolicy>,
+
+    /// If set, the number of requests in flight is limited and the limit adapts to rate limiting.
+    pub concurrency: Option<ConcurrencyPolicy>,
+
+    /// If set, requests are started at no more than this rate.
+    pub rate_limit: Option<RateLimitPolicy>,
+
+    /// If set, this fraction of responses is checked for schema drift.
+    pub schema_drift_sample_rate: Option<f64>,
+
+    /// If `true`, a malformed trip fails the entire timetable response instead of being skipped.
+    pub strict_timetable_parsing: bool,
+
+    /// If `true`, stations with malformed station codes are kept instead of being dropped.
+    pub lenient_station_codes: bool,
 }
 
 impl ResolvableConfiguration for UnresolvedLppApiConfiguration {
@@ -168,25 +490,405 @@ impl ResolvableConfiguration for UnresolvedLppApiConfiguration {
             .into_diagnostic()
             .wrap_err_with(|| miette!("Failed to parse lpp_base_api_url as an URL!"))?;
 
+        let max_route_groups_per_timetable_request = self.timetable_batching.resolve()?;
+        let warmup = self.warmup.resolve()?;
+        let concurrency = self.concurrency.resolve()?;
+        let rate_limit = self.rate_limit.resolve()?;
+
+        if !(0.0..=1.0).contains(&self.schema_drift_sample_rate) {
+            return Err(miette!(
+                "Field `schema_drift_sample_rate` must be between 0.0 and 1.0."
+            ));
+        }
+
+        let schema_drift_sample_rate =
+            Some(self.schema_drift_sample_rate).filter(|sample_rate| *sample_rate > 0.0);
+
         Ok(Self::Resolved {
             lpp_base_api_url,
             user_agent: self.user_agent,
+            max_route_groups_per_timetable_request,
+            warmup,
+            concurrency,
+            rate_limit,
+            schema_drift_sample_rate,
+            strict_timetable_parsing: self.strict_timetable_parsing,
+            lenient_station_codes: self.lenient_station_codes,
         })
     }
 }
 
 
+#[derive(Deserialize, JsonSchema, Clone)]
+struct UnresolvedTimetableBatchingConfiguration {
+    /// Whether to split timetable requests for stations with many route groups
+    /// (e.g. hubs) into several smaller requests.
+    #[serde(default)]
+    enabled: bool,
+    /// Maximum number of route groups per timetable request when batching is enabled.
+    #[serde(default = "default_max_route_groups_per_timetable_request")]
+    max_route_groups_per_request: usize,
+}
+
+fn default_max_route_groups_per_timetable_request() -> usize {
+    8
+}
+
+impl Default for UnresolvedTimetableBatchingConfiguration {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_route_groups_per_request: default_max_route_groups_per_timetable_request(),
+        }
+    }
+}
+
+impl ResolvableConfiguration for UnresolvedTimetableBatchingConfiguration {
+    type Resolved = Option<usize>;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        if self.max_route_groups_per_request == 0 {
+            return Err(miette!(
+                "Field `timetable_batching.max_route_groups_per_request` must be at least 1."
+            ));
+        }
+
+        Ok(Some(self.max_route_groups_per_request))
+    }
+}
+
+
+#[derive(Deserialize, JsonSchema, Clone)]
+struct UnresolvedApiWarmupConfiguration {
+    /// How long after startup requests are spaced out (e.g. `2m`). Unset disables the warm-up phase.
+    #[serde(default)]
+    duration: Option<String>,
+    /// Minimum time between the starts of two requests during the warm-up phase (e.g. `500ms`).
+    #[serde(default = "default_warmup_request_spacing")]
+    request_spacing: String,
+}
+
+fn default_warmup_request_spacing() -> String {
+    String::from("1s")
+}
+
+impl Default for UnresolvedApiWarmupConfiguration {
+    fn default() -> Self {
+        Self {
+            duration: None,
+            request_spacing: default_warmup_request_spacing(),
+        }
+    }
+}
+
+impl ResolvableConfiguration for UnresolvedApiWarmupConfiguration {
+    type Resolved = Option<WarmupPolicy>;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        let Some(duration) = self.duration else {
+            return Ok(None);
+        };
+
+        let duration = humantime::parse_duration(&duration)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to parse duration in field `warmup.duration`."))?;
+
+        let request_spacing = humantime::parse_duration(&self.request_spacing)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!("Failed to parse duration in field `warmup.request_spacing`.")
+            })?;
+
+        Ok(Some(WarmupPolicy {
+            duration,
+            request_spacing,
+        }))
+    }
+}
+
+
+#[derive(Deserialize, JsonSchema, Clone)]
+struct UnresolvedApiConcurrencyConfiguration {
+    /// Whether to limit the number of requests in flight at the same time. The limit is raised by one
+    /// after a full window of clean responses and halved whenever the API responds with `429 Too Many Requests`.
+    #[serde(default)]
+    enabled: bool,
+    /// Concurrency limit at startup.
+    #[serde(default = "default_initial_concurrency_limit")]
+    initial_limit: usize,
+    /// The limit is never halved below this.
+    #[serde(default = "default_min_concurrency_limit")]
+    min_limit: usize,
+    /// The limit is never raised above this.
+    #[serde(default = "default_max_concurrency_limit")]
+    max_limit: usize,
+}
+
+fn default_initial_concurrency_limit() -> usize {
+    4
+}
+
+fn default_min_concurrency_limit() -> usize {
+    1
+}
+
+fn default_max_concurrency_limit() -> usize {
+    16
+}
+
+impl Default for UnresolvedApiConcurrencyConfiguration {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            initial_limit: default_initial_concurrency_limit(),
+            min_limit: default_min_concurrency_limit(),
+            max_limit: default_max_concurrency_limit(),
+        }
+    }
+}
+
+impl ResolvableConfiguration for UnresolvedApiConcurrencyConfiguration {
+    type Resolved = Option<ConcurrencyPolicy>;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        if self.min_limit == 0 {
+            return Err(miette!(
+                "Field `concurrency.min_limit` must be at least 1."
+            ));
+        }
+
+        if !(self.min_limit..=self.max_limit).contains(&self.initial_limit) {
+            return Err(miette!(
+                "Field `concurrency.initial_limit` must be between \
+                `concurrency.min_limit` and `concurrency.max_limit`."
+            ));
+        }
+
+        Ok(Some(ConcurrencyPolicy {
+            initial_limit: self.initial_limit,
+            min_limit: self.min_limit,
+            max_limit: self.max_limit,
+        }))
+    }
+}
+
+
+#[derive(Deserialize, JsonSchema, Clone)]
+struct UnresolvedApiRateLimitConfiguration {
+    /// Whether to limit the rate of requests with a token bucket. All recorder tasks
+    /// (snapshots and arrival recording) share the same budget.
+    #[serde(default)]
+    enabled: bool,
+    /// Sustained rate of requests.
+    #[serde(default = "default_rate_limit_requests_per_second")]
+    requests_per_second: f64,
+    /// How many requests may be sent at once after a quiet period.
+    #[serde(default = "default_rate_limit_burst")]
+    burst: u32,
+}
+
+fn default_rate_limit_requests_per_second() -> f64 {
+    5.0
+}
+
+fn default_rate_limit_burst() -> u32 {
+    5
+}
+
+impl Default for UnresolvedApiRateLimitConfiguration {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            requests_per_second: default_rate_limit_requests_per_second(),
+            burst: default_rate_limit_burst(),
+        }
+    }
+}
+
+impl ResolvableConfiguration for UnresolvedApiRateLimitConfiguration {
+    type Resolved = Option<RateLimitPolicy>;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        if !(self.requests_per_second.is_finite() && self.requests_per_second > 0.0) {
+            return Err(miette!(
+                "Field `rate_limit.requests_per_second` must be greater than 0."
+            ));
+        }
+
+        if self.burst == 0 {
+            return Err(miette!(
+                "Field `rate_limit.burst` must be at least 1."
+            ));
+        }
+
+        Ok(Some(RateLimitPolicy {
+            requests_per_second: self.requests_per_second,
+            burst: self.burst,
+        }))
+    }
+}
+
+
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, JsonSchema, Clone)]
 struct UnresolvedLppRecordingConfiguration {
+    /// Interval between full station and timetable snapshots (e.g. `24hours`).
     full_station_and_timetable_details_request_interval: String,
+    /// Station/timetable data output path.
     recording_storage_directory_path: String,
+    /// Path to a file with a hex-encoded Ed25519 secret key to sign snapshots with.
+    #[serde(default)]
+    snapshot_signing_key_file_path: Option<String>,
+    /// Public holidays (`MM-DD` for yearly holidays, `YYYY-MM-DD` for one-off holidays
+    /// or `easter-monday`), used to tag snapshots with the type of service day.
+    /// Defaults to the built-in list of Slovenian public holidays.
+    #[serde(default)]
+    public_holidays: Option<Vec<String>>,
+    /// What each snapshot captures: `full` or `timetables-only`
+    /// (can be overridden with the `--capture-mode` CLI option).
+    #[serde(default)]
+    capture_mode: CaptureMode,
+    /// Whether to fetch stations with the most route groups (hubs) first, so an interrupted
+    /// snapshot is more likely to contain the busiest stations. Defaults to `false`.
+    #[serde(default)]
+    prioritize_hub_stations: bool,
+    /// How many stations are fetched at the same time during a snapshot (each one requests
+    /// its trips and then its timetables, or only its timetables in timetables-only captures).
+    /// Defaults to `4`.
+    #[serde(default = "default_max_concurrent_requests")]
+    max_concurrent_requests: usize,
+    /// Station codes of critical hub stations (e.g. Bavarski dvor). After each snapshot,
+    /// an error is logged if any of them is missing or lacks timetables for some route group.
+    #[serde(default)]
+    critical_hub_stations: Vec<String>,
+    /// Whether to capture a snapshot right away when a scheduled capture was missed
+    /// (e.g. because the system was asleep). If `false`, the recorder waits for the next
+    /// capture on the original schedule instead. Defaults to `true`.
+    #[serde(default = "default_catch_up_missed_captures")]
+    catch_up_missed_captures: bool,
+    /// How many times a failed full snapshot is retried before the recorder gives up.
+    /// Retries reuse the already captured station phases. Defaults to `0`.
+    #[serde(default)]
+    snapshot_retries: u32,
+    /// How long to wait before retrying a failed full snapshot (e.g. `5m`).
+    #[serde(default = "default_snapshot_retry_delay")]
+    snapshot_retry_delay: String,
+    /// How long a snapshot may take (e.g. `2h`) before it stops issuing requests and is saved
+    /// as a partial snapshot with what it captured so far. Unset disables the deadline.
+    #[serde(default)]
+    snapshot_deadline: Option<String>,
+    /// Which part of the day the timetables of each snapshot cover.
+    #[serde(default)]
+    timetable_window: UnresolvedTimetableWindowConfiguration,
+    /// Minimum shares of stations and routes a snapshot must capture to be accepted.
+    #[serde(default)]
+    acceptance: UnresolvedAcceptanceConfiguration,
+    /// Quick probe of every LPP API endpoint before each snapshot (also used by `doctor`).
+    #[serde(default)]
+    preflight: UnresolvedPreflightConfiguration,
+    /// Periodic recording of live arrivals on every active trip, next to the snapshots.
+    #[serde(default)]
+    arrivals: UnresolvedArrivalRecordingConfiguration,
+    /// Snapshot file name templates for each kind of stored data.
+    #[serde(default)]
+    file_name_templates: UnresolvedFileNameTemplatesConfiguration,
+    /// Assigns each station in the station snapshots a city district.
+    #[serde(default)]
+    districts: UnresolvedDistrictsConfiguration,
+}
+
+fn default_max_concurrent_requests() -> usize {
+    4
+}
+
+fn default_catch_up_missed_captures() -> bool {
+    true
+}
+
+fn default_snapshot_retry_delay() -> String {
+    String::from("5m")
+}
+
+/// What a station and route snapshot captures.
+#[derive(
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    Debug
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptureMode {
+    /// Fetch the station list, trips on each station, routes and their stations,
+    /// as well as all timetables.
+    #[default]
+    Full,
+
+    /// Reuse the station and route metadata from the latest stored snapshots
+    /// and only fetch fresh timetables.
+    TimetablesOnly,
 }
 
 #[derive(Clone)]
 pub struct LppRecordingConfiguration {
     pub full_station_and_timetable_details_request_interval: Duration,
     pub recording_storage_root: StorageRoot,
+
+    /// If set, every saved snapshot file is signed with this key.
+    pub snapshot_signing_key: Option<SigningKey>,
+
+    pub holiday_calendar: HolidayCalendar,
+
+    pub capture_mode: CaptureMode,
+
+    /// If `true`, stations are fetched in descending order of their number of route groups.
+    pub prioritize_hub_stations: bool,
+
+    /// Maximum number of stations fetched at the same time during a snapshot (at least `1`).
+    pub max_concurrent_requests: usize,
+
+    /// Stations whose timetables must be complete in every snapshot.
+    pub critical_hub_stations: Vec<StationCode>,
+
+    /// If `true`, a snapshot is captured right away after a scheduled capture was missed.
+    pub catch_up_missed_captures: bool,
+
+    /// Number of times a failed full snapshot is retried (reusing its checkpointed station phases).
+    pub snapshot_retries: u32,
+
+    pub snapshot_retry_delay: Duration,
+
+    /// If set, a snapshot (including its retries) stops issuing requests after this long.
+    pub snapshot_deadline: Option<Duration>,
+
+    pub timetable_window: TimetableWindowPolicy,
+
+    /// Snapshots that don't meet this policy are rejected.
+    pub acceptance_policy: AcceptancePolicy,
+
+    pub preflight: PreflightConfiguration,
+
+    /// If set, live arrivals are recorded alongside the snapshots.
+    pub arrival_recording: Option<ArrivalRecordingConfiguration>,
+
+    /// If set, each station in the station snapshots is assigned a district.
+    pub district_boundaries: Option<DistrictBoundaries>,
 }
 
 impl ResolvableConfiguration for UnresolvedLppRecordingConfiguration {
@@ -203,12 +905,906 @@ impl ResolvableConfiguration for UnresolvedLppRecordingConfiguration {
                     )
                 })?;
 
-        let storage_root = StorageRoot::new(self.recording_storage_directory_path)?;
+        let file_name_templates = self.file_name_templates.resolve()?;
+        let storage_root = StorageRoot::new(
+            self.recording_storage_directory_path,
+            file_name_templates,
+        )?;
+
+        let snapshot_signing_key = match self.snapshot_signing_key_file_path {
+            Some(key_file_path) => Some(
+                load_signing_key_from_file(Path::new(&key_file_path)).wrap_err_with(|| {
+                    miette!("Failed to load key in field `snapshot_signing_key_file_path`.")
+                })?,
+            ),
+            None => None,
+        };
+
+        let holiday_calendar = match &self.public_holidays {
+            Some(public_holidays) => HolidayCalendar::from_entries(public_holidays)
+                .wrap_err_with(|| miette!("Failed to parse field `public_holidays`."))?,
+            None => HolidayCalendar::slovenian_default(),
+        };
+
+        let timetable_window = self
+            .timetable_window
+            .resolve()
+            .wrap_err_with(|| miette!("Failed to resolve table `timetable_window`."))?;
+
+        let acceptance_policy = self
+            .acceptance
+            .resolve()
+            .wrap_err_with(|| miette!("Failed to resolve table `acceptance`."))?;
+
+        let preflight = self
+            .preflight
+            .resolve()
+            .wrap_err_with(|| miette!("Failed to resolve table `preflight`."))?;
+
+        let arrival_recording = self
+            .arrivals
+            .resolve()
+            .wrap_err_with(|| miette!("Failed to resolve table `arrivals`."))?;
+
+        if self.max_concurrent_requests == 0 {
+            return Err(miette!(
+                "Field `max_concurrent_requests` must be at least 1."
+            ));
+        }
+
+        let critical_hub_stations = self
+            .critical_hub_stations
+            .into_iter()
+            .map(StationCode::parse)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to parse field `critical_hub_stations`."))?;
+
+        let district_boundaries = self
+            .districts
+            .resolve()
+            .wrap_err_with(|| miette!("Failed to resolve table `districts`."))?;
+
+        let snapshot_retry_delay = humantime::parse_duration(&self.snapshot_retry_delay)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!("Failed to parse duration in field `snapshot_retry_delay`.")
+            })?;
+
+        let snapshot_deadline = match self.snapshot_deadline {
+            Some(snapshot_deadline) => Some(
+                humantime::parse_duration(&snapshot_deadline)
+                    .into_diagnostic()
+                    .wrap_err_with(|| {
+                        miette!("Failed to parse duration in field `snapshot_deadline`.")
+                    })?,
+            ),
+            None => None,
+        };
 
 
         Ok(Self::Resolved {
             full_station_and_timetable_details_request_interval,
             recording_storage_root: storage_root,
+            snapshot_signing_key,
+            holiday_calendar,
+            capture_mode: self.capture_mode,
+            prioritize_hub_stations: self.prioritize_hub_stations,
+            max_concurrent_requests: self.max_concurrent_requests,
+            critical_hub_stations,
+            catch_up_missed_captures: self.catch_up_missed_captures,
+            snapshot_retries: self.snapshot_retries,
+            snapshot_retry_delay,
+            snapshot_deadline,
+            timetable_window,
+            acceptance_policy,
+            preflight,
+            arrival_recording,
+            district_boundaries,
+        })
+    }
+}
+
+
+#[derive(Deserialize, JsonSchema, Clone)]
+struct UnresolvedDistrictsConfiguration {
+    /// Path to a GeoJSON file with a `Polygon` or `MultiPolygon` feature for each district.
+    /// Unset disables assigning districts to stations.
+    #[serde(default)]
+    boundaries_file_path: Option<String>,
+    /// Feature property that contains the name of the district.
+    #[serde(default = "default_district_name_property")]
+    name_property: String,
+}
+
+fn default_district_name_property() -> String {
+    String::from("name")
+}
+
+impl Default for UnresolvedDistrictsConfiguration {
+    fn default() -> Self {
+        Self {
+            boundaries_file_path: None,
+            name_property: default_district_name_property(),
+        }
+    }
+}
+
+impl ResolvableConfiguration for UnresolvedDistrictsConfiguration {
+    type Resolved = Option<DistrictBoundaries>;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        let Some(boundaries_file_path) = self.boundaries_file_path else {
+            return Ok(None);
+        };
+
+        let district_boundaries = DistrictBoundaries::load_from_file(
+            Path::new(&boundaries_file_path),
+            &self.name_property,
+        )
+        .wrap_err_with(|| miette!("Failed to load file in field `boundaries_file_path`."))?;
+
+        Ok(Some(district_boundaries))
+    }
+}
+
+
+#[derive(Deserialize, JsonSchema, Clone, Default)]
+struct UnresolvedTimetableWindowConfiguration {
+    /// `full-day`, `service-day-remaining` (from the current hour until the end of the day)
+    /// or `custom` (the hours below, around the time of capture).
+    #[serde(default)]
+    policy: TimetableWindowPolicyKind,
+    /// Hours after the time of capture to include (only with the `custom` policy).
+    #[serde(default)]
+    next_hours: Option<u32>,
+    /// Hours before the time of capture to include (only with the `custom` policy).
+    #[serde(default)]
+    previous_hours: Option<u32>,
+}
+
+/// Which part of the day the timetables of each snapshot cover.
+#[derive(
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    Debug
+)]
+#[serde(rename_all = "kebab-case")]
+enum TimetableWindowPolicyKind {
+    /// The entire day.
+    #[default]
+    FullDay,
+
+    /// From the start of the current hour until the end of the day.
+    ServiceDayRemaining,
+
+    /// `next_hours` after and `previous_hours` before the time of capture.
+    Custom,
+}
+
+impl ResolvableConfiguration for UnresolvedTimetableWindowConfiguration {
+    type Resolved = TimetableWindowPolicy;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        match (self.policy, self.next_hours, self.previous_hours) {
+            (TimetableWindowPolicyKind::Custom, Some(next_hours), Some(previous_hours)) => {
+                Ok(TimetableWindowPolicy::Custom {
+                    next_hours,
+                    previous_hours,
+                })
+            }
+            (TimetableWindowPolicyKind::Custom, _, _) => Err(miette!(
+                "Fields `next_hours` and `previous_hours` are required with the custom policy."
+            )),
+            (_, Some(_), _) | (_, _, Some(_)) => Err(miette!(
+                "Fields `next_hours` and `previous_hours` can only be used with the custom policy."
+            )),
+            (TimetableWindowPolicyKind::FullDay, None, None) => Ok(TimetableWindowPolicy::FullDay),
+            (TimetableWindowPolicyKind::ServiceDayRemaining, None, None) => {
+                Ok(TimetableWindowPolicy::ServiceDayRemaining)
+            }
+        }
+    }
+}
+
+
+#[derive(Deserialize, JsonSchema, Clone, Default)]
+struct UnresolvedAcceptanceConfiguration {
+    /// Minimum share (from `0.0` to `1.0`) of stations with route groups whose timetables
+    /// were captured. Defaults to `0.0`.
+    #[serde(default)]
+    min_station_fraction: f64,
+    /// Minimum share (from `0.0` to `1.0`) of routes that were captured. Defaults to `0.0`.
+    #[serde(default)]
+    min_route_fraction: f64,
+}
+
+impl ResolvableConfiguration for UnresolvedAcceptanceConfiguration {
+    type Resolved = AcceptancePolicy;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        if !(0.0..=1.0).contains(&self.min_station_fraction) {
+            return Err(miette!(
+                "Field `min_station_fraction` must be between 0.0 and 1.0."
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.min_route_fraction) {
+            return Err(miette!(
+                "Field `min_route_fraction` must be between 0.0 and 1.0."
+            ));
+        }
+
+        Ok(AcceptancePolicy {
+            min_station_fraction: self.min_station_fraction,
+            min_route_fraction: self.min_route_fraction,
+        })
+    }
+}
+
+
+#[derive(Deserialize, JsonSchema, Clone)]
+struct UnresolvedPreflightConfiguration {
+    /// Whether to probe every LPP API endpoint before each snapshot and fail the run
+    /// if any of them fails. Defaults to `false`.
+    #[serde(default)]
+    enabled: bool,
+    /// Station code of the station to probe (the trips are taken from the trips stopping there).
+    /// Defaults to `600011` (Bavarski dvor).
+    #[serde(default = "default_preflight_station_code")]
+    station_code: String,
+}
+
+impl Default for UnresolvedPreflightConfiguration {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            station_code: default_preflight_station_code(),
+        }
+    }
+}
+
+fn default_preflight_station_code() -> String {
+    String::from("600011")
+}
+
+#[derive(Clone, Debug)]
+pub struct PreflightConfiguration {
+    /// If `true`, every snapshot is preceded by the pre-flight probe.
+    pub enabled: bool,
+
+    pub station_code: StationCode,
+}
+
+impl ResolvableConfiguration for UnresolvedPreflightConfiguration {
+    type Resolved = PreflightConfiguration;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        let station_code = StationCode::parse(self.station_code)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to parse field `station_code`."))?;
+
+        Ok(PreflightConfiguration {
+            enabled: self.enabled,
+            station_code,
+        })
+    }
+}
+
+
+#[derive(Deserialize, JsonSchema, Clone)]
+struct UnresolvedArrivalRecordingConfiguration {
+    /// Whether to record the live arrivals on every active trip. Defaults to `false`.
+    #[serde(default)]
+    enabled: bool,
+    /// Time between the starts of two polling rounds (e.g. `30s`).
+    #[serde(default = "default_arrival_polling_interval")]
+    polling_interval: String,
+    /// How often the list of trips to poll is requested anew (e.g. `1h`).
+    #[serde(default = "default_arrival_trip_list_refresh_interval")]
+    trip_list_refresh_interval: String,
+}
+
+impl Default for UnresolvedArrivalRecordingConfiguration {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            polling_interval: default_arrival_polling_interval(),
+            trip_list_refresh_interval: default_arrival_trip_list_refresh_interval(),
+        }
+    }
+}
+
+fn default_arrival_polling_interval() -> String {
+    String::from("30s")
+}
+
+fn default_arrival_trip_list_refresh_interval() -> String {
+    String::from("1h")
+}
+
+#[derive(Clone, Debug)]
+pub struct ArrivalRecordingConfiguration {
+    /// Time between the starts of two polling rounds.
+    pub polling_interval: Duration,
+
+    /// How often the list of trips to poll is requested anew.
+    pub trip_list_refresh_interval: Duration,
+}
+
+impl ResolvableConfiguration for UnresolvedArrivalRecordingConfiguration {
+    type Resolved = Option<ArrivalRecordingConfiguration>;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        let polling_interval = humantime::parse_duration(&self.polling_interval)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to parse duration in field `polling_interval`."))?;
+
+        let trip_list_refresh_interval =
+            humantime::parse_duration(&self.trip_list_refresh_interval)
+                .into_diagnostic()
+                .wrap_err_with(|| {
+                    miette!("Failed to parse duration in field `trip_list_refresh_interval`.")
+                })?;
+
+        Ok(Some(ArrivalRecordingConfiguration {
+            polling_interval,
+            trip_list_refresh_interval,
+        }))
+    }
+}
+
+
+#[derive(Deserialize, JsonSchema, Clone, Default)]
+struct UnresolvedFileNameTemplatesConfiguration {
+    /// File name template for station details snapshots.
+    /// Supports the `{timestamp}`, `{kind}`, `{sequence}` and `{hash8}` placeholders.
+    #[serde(default)]
+    stations: Option<String>,
+    /// File name template for route details snapshots.
+    #[serde(default)]
+    routes: Option<String>,
+    /// File name template for arrival snapshots.
+    #[serde(default)]
+    arrivals: Option<String>,
+}
+
+impl ResolvableConfiguration for UnresolvedFileNameTemplatesConfiguration {
+    type Resolved = FileNameTemplates;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        let parse_template = |template: Option<String>, field_name: &str| match template {
+            Some(template) => FileNameTemplate::parse(template).wrap_err_with(|| {
+                miette!(
+                    "Failed to parse field `file_name_templates.{}`.",
+                    field_name
+                )
+            }),
+            None => Ok(FileNameTemplate::default()),
+        };
+
+        Ok(Self::Resolved {
+            stations: parse_template(self.stations, "stations")?,
+            routes: parse_template(self.routes, "routes")?,
+            arrivals: parse_template(self.arrivals, "arrivals")?,
+        })
+    }
+}
+
+
+
+#[derive(Deserialize, JsonSchema, Clone)]
+struct UnresolvedUploadConfiguration {
+    /// Base URL snapshots are uploaded to. Each snapshot is sent to `{url}/{file name}`.
+    url: String,
+    /// Bearer token sent in the `Authorization` header. If unset, no token is sent.
+    #[serde(default)]
+    token: Option<String>,
+    /// HTTP method used for uploads: `put` or `post`. Defaults to `put`.
+    #[serde(default)]
+    method: UploadMethod,
+    /// How many times an upload is attempted before the snapshot is put into the outbox.
+    /// Defaults to 5.
+    #[serde(default = "default_upload_max_attempts")]
+    max_attempts: u32,
+    /// Delay before retrying a failed upload, doubled after every failed attempt (e.g. `2s`).
+    #[serde(default = "default_upload_retry_interval")]
+    retry_interval: String,
+    /// Directory to keep snapshots that failed to upload in. They are uploaded again after
+    /// each capture. Defaults to the `upload-outbox` directory in the recording storage root.
+    #[serde(default)]
+    outbox_directory: Option<String>,
+}
+
+fn default_upload_max_attempts() -> u32 {
+    5
+}
+
+fn default_upload_retry_interval() -> String {
+    String::from("2s")
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Debug,
+    Default
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum UploadMethod {
+    #[default]
+    Put,
+    Post,
+}
+
+#[derive(Clone, Debug)]
+pub struct UploadConfiguration {
+    /// Always ends with a slash, so file names can be joined onto it.
+    pub url: Url,
+    pub token: Option<String>,
+    pub method: UploadMethod,
+    pub max_attempts: u32,
+    pub retry_interval: Duration,
+
+    /// If unset, the `upload-outbox` directory in the recording storage root is used.
+    pub outbox_directory: Option<PathBuf>,
+}
+
+impl ResolvableConfiguration for UnresolvedUploadConfiguration {
+    type Resolved = UploadConfiguration;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        let url = match self.url.ends_with('/') {
+            true => self.url,
+            false => format!("{}/", self.url),
+        };
+        let url = Url::parse(&url)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to parse URL in field `url`."))?;
+
+        if self.max_attempts == 0 {
+            return Err(miette!(
+                "Field `max_attempts` must be at least 1."
+            ));
+        }
+
+        let retry_interval = humantime::parse_duration(&self.retry_interval)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to parse duration in field `retry_interval`."))?;
+
+        Ok(Self::Resolved {
+            url,
+            token: self.token,
+            method: self.method,
+            max_attempts: self.max_attempts,
+            retry_interval,
+            outbox_directory: self.outbox_directory.map(PathBuf::from),
+        })
+    }
+}
+
+
+
+#[derive(Deserialize, JsonSchema, Clone)]
+#[serde(default)]
+struct UnresolvedDatasetConfiguration {
+    /// Who the data comes from. Defaults to `Ljubljanski potniški promet (LPP)`.
+    source: String,
+    /// License the derived data is published under (e.g. `CC BY 4.0`). If unset, none is stated.
+    license: Option<String>,
+    /// Additional attribution text (e.g. who recorded the data and where it is published).
+    attribution: Option<String>,
+    /// How station and route names are written in derived files.
+    display_names: UnresolvedDisplayNamesConfiguration,
+}
+
+impl Default for UnresolvedDatasetConfiguration {
+    fn default() -> Self {
+        Self {
+            source: String::from("Ljubljanski potniški promet (LPP)"),
+            license: None,
+            attribution: None,
+            display_names: UnresolvedDisplayNamesConfiguration::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DatasetConfiguration {
+    pub source: String,
+    pub license: Option<String>,
+    pub attribution: Option<String>,
+
+    /// Names in derived files (snapshots always keep the raw names).
+    pub display_names: DisplayNames,
+}
+
+impl ResolvableConfiguration for UnresolvedDatasetConfiguration {
+    type Resolved = DatasetConfiguration;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        if self.source.trim().is_empty() {
+            return Err(miette!("Field `source` must not be empty."));
+        }
+
+        let display_names = self
+            .display_names
+            .resolve()
+            .wrap_err_with(|| miette!("Failed to resolve table `display_names`."))?;
+
+        Ok(Self::Resolved {
+            source: self.source,
+            license: self.license,
+            attribution: self.attribution,
+            display_names,
+        })
+    }
+}
+
+
+#[derive(Deserialize, JsonSchema, Clone, Default)]
+struct UnresolvedDisplayNamesConfiguration {
+    /// `raw` (as recorded, e.g. `BAVARSKI DVOR`, the default) or `title-case`
+    /// (capitalized following Slovenian rules, e.g. `Bavarski dvor`).
+    #[serde(default)]
+    style: DisplayNameStyle,
+    /// Display names of specific station names (or stations in trip names),
+    /// taking precedence over `style`, e.g. `"BTC" = "BTC City"`.
+    #[serde(default)]
+    overrides: BTreeMap<String, String>,
+}
+
+impl ResolvableConfiguration for UnresolvedDisplayNamesConfiguration {
+    type Resolved = DisplayNames;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        if let Some((raw_name, _)) = self
+            .overrides
+            .iter()
+            .find(|(_, display_name)| display_name.trim().is_empty())
+        {
+            return Err(miette!(
+                "Display name override of \"{}\" must not be empty.",
+                raw_name
+            ));
+        }
+
+        Ok(DisplayNames::new(self.style, self.overrides))
+    }
+}
+
+
+
+#[derive(Deserialize, JsonSchema, Clone, Default)]
+struct UnresolvedNotificationsConfiguration {
+    /// If set, run summaries are sent by email.
+    #[serde(default)]
+    email: Option<UnresolvedEmailNotificationConfiguration>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct NotificationsConfiguration {
+    pub email: Option<EmailNotificationConfiguration>,
+}
+
+impl ResolvableConfiguration for UnresolvedNotificationsConfiguration {
+    type Resolved = NotificationsConfiguration;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        let email = self
+            .email
+            .map(|email| email.resolve())
+            .transpose()
+            .wrap_err_with(|| miette!("Failed to resolve table \"email\"."))?;
+
+        Ok(Self::Resolved { email })
+    }
+}
+
+
+#[derive(Deserialize, JsonSchema, Clone)]
+struct UnresolvedEmailNotificationConfiguration {
+    /// Host name of the SMTP server (e.g. `smtp.example.com`).
+    smtp_server: String,
+    /// Port of the SMTP server. Defaults to 587 for `starttls`, 465 for `tls` and 25 for `none`.
+    #[serde(default)]
+    smtp_port: Option<u16>,
+    /// How the connection is secured: `starttls`, `tls` or `none`. Defaults to `starttls`.
+    #[serde(default)]
+    tls: SmtpTls,
+    /// Username to authenticate with. If unset, no authentication is performed.
+    #[serde(default)]
+    username: Option<String>,
+    /// Password to authenticate with (required if `username` is set).
+    #[serde(default)]
+    password: Option<String>,
+    /// Sender address (e.g. `LPP recorder <recorder@example.com>`).
+    from: String,
+    /// Recipient addresses.
+    to: Vec<String>,
+    /// When to send a summary: `every-snapshot` or `failures-only`. Defaults to `failures-only`.
+    #[serde(default)]
+    send: EmailNotificationTrigger,
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Debug,
+    Default
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmtpTls {
+    /// Upgrade a plain connection with STARTTLS.
+    #[default]
+    Starttls,
+
+    /// Connect with TLS from the start (also known as SMTPS).
+    Tls,
+
+    /// Don't secure the connection (only for servers on a trusted network).
+    None,
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Debug,
+    Default
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmailNotificationTrigger {
+    /// Send a summary after each saved snapshot and when recording fails.
+    EverySnapshot,
+
+    /// Only send a summary when recording fails.
+    #[default]
+    FailuresOnly,
+}
+
+#[derive(Clone, Debug)]
+pub struct EmailNotificationConfiguration {
+    pub smtp_server: String,
+    pub smtp_port: Option<u16>,
+    pub tls: SmtpTls,
+
+    /// Username and password, if the server requires authentication.
+    pub credentials: Option<(String, String)>,
+
+    pub from: Mailbox,
+    pub to: Vec<Mailbox>,
+    pub send: EmailNotificationTrigger,
+}
+
+impl ResolvableConfiguration for UnresolvedEmailNotificationConfiguration {
+    type Resolved = EmailNotificationConfiguration;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        let credentials = match (self.username, self.password) {
+            (Some(username), Some(password)) => Some((username, password)),
+            (None, None) => None,
+            (Some(_), None) => {
+                return Err(miette!(
+                    "Field `password` must be set if `username` is."
+                ))
+            }
+            (None, Some(_)) => {
+                return Err(miette!(
+                    "Field `username` must be set if `password` is."
+                ))
+            }
+        };
+
+        let from = self
+            .from
+            .parse::<Mailbox>()
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to parse address in field `from`."))?;
+
+        if self.to.is_empty() {
+            return Err(miette!(
+                "Field `to` must contain at least one address."
+            ));
+        }
+
+        let to = self
+            .to
+            .iter()
+            .map(|address| {
+                address
+                    .parse::<Mailbox>()
+                    .into_diagnostic()
+                    .wrap_err_with(|| {
+                        miette!(
+                            "Failed to parse address \"{}\" in field `to`.",
+                            address
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self::Resolved {
+            smtp_server: self.smtp_server,
+            smtp_port: self.smtp_port,
+            tls: self.tls,
+            credentials,
+            from,
+            to,
+            send: self.send,
+        })
+    }
+}
+
+
+
+#[derive(Deserialize, JsonSchema, Clone)]
+struct UnresolvedTelemetryConfiguration {
+    /// Base URL of the OTLP/HTTP endpoint of the collector (e.g. `http://localhost:4318`).
+    /// Traces are sent to `{endpoint}/v1/traces` and metrics to `{endpoint}/v1/metrics`.
+    endpoint: String,
+    /// Additional HTTP headers sent with every export (e.g. for authentication).
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// Reported as `service.name`. Defaults to `lpp-timetable-recorder`.
+    #[serde(default = "default_telemetry_service_name")]
+    service_name: String,
+    /// Reported as `service.instance.id`, to tell recorders on different machines apart.
+    /// Defaults to the host name.
+    #[serde(default)]
+    instance_name: Option<String>,
+    /// Which spans are exported (e.g. `info` or `lpp_timetable_recorder=debug`).
+    #[serde(default = "default_telemetry_trace_level_filter")]
+    trace_level_filter: String,
+    /// How often metrics are exported (e.g. `30s`).
+    #[serde(default = "default_telemetry_metric_export_interval")]
+    metric_export_interval: String,
+}
+
+fn default_telemetry_service_name() -> String {
+    String::from("lpp-timetable-recorder")
+}
+
+fn default_telemetry_trace_level_filter() -> String {
+    String::from("info")
+}
+
+fn default_telemetry_metric_export_interval() -> String {
+    String::from("30s")
+}
+
+#[derive(Clone, Debug)]
+pub struct TelemetryConfiguration {
+    pub endpoint: Url,
+    pub headers: BTreeMap<String, String>,
+    pub service_name: String,
+
+    /// If unset, the host name is reported instead.
+    pub instance_name: Option<String>,
+
+    trace_level_filter: String,
+
+    pub metric_export_interval: Duration,
+}
+
+impl TelemetryConfiguration {
+    pub fn trace_level_filter(&self) -> EnvFilter {
+        // SAFETY: This is safe because we checked the input is valid in `resolve`.
+        EnvFilter::try_new(&self.trace_level_filter).unwrap()
+    }
+}
+
+impl ResolvableConfiguration for UnresolvedTelemetryConfiguration {
+    type Resolved = TelemetryConfiguration;
+
+    fn resolve(self) -> Result<Self::Resolved> {
+        if !cfg!(feature = "telemetry") {
+            return Err(miette!(
+                "Telemetry is configured, but the recorder was built without the `telemetry` feature \
+                (build it with `--features telemetry` or remove the table)."
+            ));
+        }
+
+        let endpoint = Url::parse(&self.endpoint)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to parse URL in field `endpoint`."))?;
+
+        EnvFilter::try_new(&self.trace_level_filter)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to parse field `trace_level_filter`."))?;
+
+        let metric_export_interval = humantime::parse_duration(&self.metric_export_interval)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!("Failed to parse duration in field `metric_export_interval`.")
+            })?;
+
+        Ok(Self::Resolved {
+            endpoint,
+            headers: self.headers,
+            service_name: self.service_name,
+            instance_name: self.instance_name,
+            trace_level_filter: self.trace_level_filter,
+            metric_export_interval,
         })
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn reject_unknown_keys_only_in_strict_mode() {
+        let temporary_directory = std::env::temp_dir().join(format!(
+            "lpp-recorder-configuration-test-{}",
+            Uuid::new_v4()
+        ));
+        fs::create_dir_all(&temporary_directory).unwrap();
+
+        let example_configuration = fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/data/configuration.EXAMPLE.toml"
+        ))
+        .unwrap();
+
+        // Fill in the storage directory and misspell one of the `[lpp.api]` keys.
+        let configuration_with_typo = example_configuration
+            .replace(
+                "recording_storage_directory_path = \"\"",
+                &format!(
+                    "recording_storage_directory_path = {:?}",
+                    temporary_directory.to_string_lossy()
+                ),
+            )
+            .replace(
+                "strict_timetable_parsing = false",
+                "strict_timetable_parsin = false",
+            );
+
+        let configuration_file_path = temporary_directory.join("configuration.toml");
+        fs::write(&configuration_file_path, configuration_with_typo).unwrap();
+
+        let Err(strict_error) =
+            Configuration::load_from_path(&configuration_file_path, true, Vec::new())
+        else {
+            panic!("expected strict mode to reject the unknown key");
+        };
+        assert!(strict_error
+            .to_string()
+            .contains("lpp.api.strict_timetable_parsin"));
+
+        let lenient_configuration =
+            Configuration::load_from_path(&configuration_file_path, false, Vec::new()).unwrap();
+        assert!(!lenient_configuration.lpp.api.strict_timetable_parsing);
+
+        fs::remove_dir_all(&temporary_directory).unwrap();
+    }
+}
@@ -35,3 +35,63 @@ pub fn replace_placeholders_in_path(
 
     PathBuf::from(path_string)
 }
+
+/// Parses a human-readable size into the number of bytes,
+/// e.g. `64MiB`, `500 KB` or `1048576`.
+///
+/// Decimal (`KB`, `MB`, `GB`) and binary (`KiB`, `MiB`, `GiB`) units are supported.
+pub fn parse_byte_size(size: &str) -> Result<u64> {
+    let size = size.trim();
+
+    let unit_start_index = size
+        .find(|character: char| !character.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(unit_start_index);
+
+    let number = number.parse::<u64>().into_diagnostic().wrap_err_with(|| {
+        miette!(
+            "Invalid size: \"{}\" does not start with a number.",
+            size
+        )
+    })?;
+
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1000,
+        "mb" => 1000 * 1000,
+        "gb" => 1000 * 1000 * 1000,
+        "kib" => 1024,
+        "mib" => 1024 * 1024,
+        "gib" => 1024 * 1024 * 1024,
+        unknown_unit => {
+            return Err(miette!(
+                "Invalid size unit: \"{}\" (expected B, KB, MB, GB, KiB, MiB or GiB).",
+                unknown_unit
+            ))
+        }
+    };
+
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| miette!("Size is too large: {}.", size))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_byte_sizes_correctly() {
+        assert_eq!(parse_byte_size("1048576").unwrap(), 1048576);
+        assert_eq!(parse_byte_size("500 KB").unwrap(), 500_000);
+        assert_eq!(
+            parse_byte_size("64MiB").unwrap(),
+            64 * 1024 * 1024
+        );
+        assert_eq!(parse_byte_size("2gb").unwrap(), 2_000_000_000);
+
+        assert!(parse_byte_size("MiB").is_err());
+        assert!(parse_byte_size("12 parsecs").is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Words that are written in lowercase unless they start a name:
+/// prepositions and conjunctions, as well as common nouns that Slovenian
+/// doesn't capitalize in multi-word place names (e.g. `Bavarski dvor`).
+const LOWERCASE_WORDS: &[&str] = &[
+    "in",
+    "ali",
+    "na",
+    "pri",
+    "za",
+    "v",
+    "pod",
+    "nad",
+    "ob",
+    "od",
+    "do",
+    "s",
+    "z",
+    "k",
+    "h",
+    "iz",
+    "po",
+    "pred",
+    "med",
+    "proti",
+    "cesta",
+    "ulica",
+    "trg",
+    "dvor",
+    "most",
+    "park",
+    "naselje",
+    "postaja",
+    "center",
+    "bolnica",
+    "pokopališče",
+];
+
+/// Abbreviations that are kept in uppercase.
+const UPPERCASE_WORDS: &[&str] = &[
+    "BTC", "UKC", "OŠ", "ŠC", "ZD", "AMZS", "RTV", "LPP", "OF", "P+R", "II", "III", "IV", "VI",
+    "VII", "VIII", "IX",
+];
+
+/// Separator of the station names in trip names, e.g. `LITOSTROJ - Bavarski dvor - RUDNIK`.
+const TRIP_NAME_PART_SEPARATOR: &str = " - ";
+
+
+/// How station and route names, which LPP writes in all-caps
+/// Slovenian (e.g. `BAVARSKI DVOR`), are written in derived files.
+#[derive(
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    Debug
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum DisplayNameStyle {
+    /// As recorded (e.g. `BAVARSKI DVOR`).
+    #[default]
+    Raw,
+
+    /// Capitalized following Slovenian rules (e.g. `Bavarski dvor` or `Brezovica pri Ljubljani`).
+    TitleCase,
+}
+
+
+/// Transforms recorded station and trip names into the names shown in derived files
+/// (exports and reports). Snapshots always keep the raw names.
+#[derive(Clone, Debug, Default)]
+pub struct DisplayNames {
+    style: DisplayNameStyle,
+
+    /// Display names of specific raw names (or trip name parts), taking precedence over `style`.
+    overrides: BTreeMap<String, String>,
+}
+
+impl DisplayNames {
+    pub fn new(style: DisplayNameStyle, overrides: BTreeMap<String, String>) -> Self {
+        Self { style, overrides }
+    }
+
+    /// Returns the display name of a station or trip name. Each station of
+    /// a trip name (e.g. `LITOSTROJ - Bavarski dvor - RUDNIK`) is transformed separately.
+    pub fn display_name(&self, raw_name: &str) -> String {
+        if let Some(display_name) = self.overrides.get(raw_name) {
+            return display_name.clone();
+        }
+
+        raw_name
+            .split(TRIP_NAME_PART_SEPARATOR)
+            .map(|name_part| match self.overrides.get(name_part) {
+                Some(display_name) => display_name.clone(),
+                None => match self.style {
+                    DisplayNameStyle::Raw => name_part.to_string(),
+                    DisplayNameStyle::TitleCase => title_case(name_part),
+                },
+            })
+            .collect::<Vec<_>>()
+            .join(TRIP_NAME_PART_SEPARATOR)
+    }
+}
+
+
+fn title_case(name: &str) -> String {
+    name.split(' ')
+        .enumerate()
+        .map(|(word_index, word)| title_case_word(word, word_index == 0))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn title_case_word(word: &str, is_first_word: bool) -> String {
+    // Words with numbers are left alone (e.g. `5`).
+    if word.chars().any(|character| character.is_numeric()) {
+        return word.to_string();
+    }
+
+    let uppercase_word = word.to_uppercase();
+    let bare_uppercase_word = uppercase_word
+        .trim_matches(|character: char| !character.is_alphabetic() && character != '+');
+    if UPPERCASE_WORDS.contains(&bare_uppercase_word) {
+        return uppercase_word;
+    }
+
+    let lowercase_word = word.to_lowercase();
+    let bare_lowercase_word =
+        lowercase_word.trim_matches(|character: char| !character.is_alphabetic());
+    if !is_first_word && LOWERCASE_WORDS.contains(&bare_lowercase_word) {
+        return lowercase_word;
+    }
+
+    // Capitalize the first letter of every run of letters, e.g. after `-`, `(` or `.`.
+    let mut title_cased_word = String::with_capacity(lowercase_word.len());
+    let mut previous_is_letter = false;
+    for character in lowercase_word.chars() {
+        if character.is_alphabetic() && !previous_is_letter {
+            title_cased_word.extend(character.to_uppercase());
+        } else {
+            title_cased_word.push(character);
+        }
+
+        previous_is_letter = character.is_alphabetic();
+    }
+
+    title_cased_word
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_case_slovenian_names() {
+        let display_names = DisplayNames::new(
+            DisplayNameStyle::TitleCase,
+            BTreeMap::from([(String::from("BTC"), String::from("BTC City"))]),
+        );
+
+        for (raw_name, display_name) in [
+            ("ŽELEZNA", "Železna"),
+            ("BAVARSKI DVOR", "Bavarski dvor"),
+            (
+                "BREZOVICA PRI LJUBLJANI",
+                "Brezovica pri Ljubljani",
+            ),
+            ("SP. KAŠELJ", "Sp. Kašelj"),
+            ("OŠ VIČ", "OŠ Vič"),
+            ("P+R DOLGI MOST", "P+R Dolgi most"),
+            ("ŠMARTNO-LITIJA", "Šmartno-Litija"),
+            ("BROD (TACEN)", "Brod (Tacen)"),
+            ("TRG OF", "Trg OF"),
+            (
+                "LITOSTROJ - Bavarski dvor - RUDNIK",
+                "Litostroj - Bavarski dvor - Rudnik",
+            ),
+            ("BTC", "BTC City"),
+            ("BEŽIGRAD - BTC", "Bežigrad - BTC City"),
+        ] {
+            assert_eq!(display_names.display_name(raw_name), display_name);
+        }
+
+        let raw_display_names = DisplayNames::default();
+        assert_eq!(
+            raw_display_names.display_name("BAVARSKI DVOR"),
+            "BAVARSKI DVOR"
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use std::{fs, path::Path};
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::api::GeographicalLocation;
+
+
+/// City districts (or neighborhoods), loaded from a GeoJSON `FeatureCollection`
+/// of `Polygon` and `MultiPolygon` features, used to assign a district to each station.
+#[derive(Clone, Debug)]
+pub struct DistrictBoundaries {
+    districts: Vec<District>,
+}
+
+#[derive(Clone, Debug)]
+struct District {
+    name: String,
+    polygons: Vec<Polygon>,
+}
+
+/// A polygon with its rings as `(longitude, latitude)` points, in the same order as GeoJSON.
+#[derive(Clone, Debug)]
+struct Polygon {
+    exterior: Vec<(f64, f64)>,
+    holes: Vec<Vec<(f64, f64)>>,
+}
+
+
+#[derive(Deserialize)]
+struct RawFeatureCollection {
+    features: Vec<RawFeature>,
+}
+
+#[derive(Deserialize)]
+struct RawFeature {
+    geometry: RawGeometry,
+    #[serde(default)]
+    properties: Option<serde_json::Map<String, Value>>,
+}
+
+/// Positions may contain a third (altitude) element, which we ignore.
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum RawGeometry {
+    Polygon {
+        coordinates: Vec<Vec<Vec<f64>>>,
+    },
+    MultiPolygon {
+        coordinates: Vec<Vec<Vec<Vec<f64>>>>,
+    },
+}
+
+
+impl DistrictBoundaries {
+    /// Loads a GeoJSON file. The name of each district is taken from
+    /// the `name_property` property of its feature.
+    pub fn load_from_file(file_path: &Path, name_property: &str) -> Result<Self> {
+        let geojson = fs::read_to_string(file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to read district boundaries from {}.",
+                    file_path.display()
+                )
+            })?;
+
+        Self::from_geojson(&geojson, name_property)
+    }
+
+    pub fn from_geojson(geojson: &str, name_property: &str) -> Result<Self> {
+        let feature_collection: RawFeatureCollection = serde_json::from_str(geojson)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to parse district boundaries (expected a GeoJSON \
+                    FeatureCollection of Polygon or MultiPolygon features)."
+                )
+            })?;
+
+        let districts = feature_collection
+            .features
+            .into_iter()
+            .enumerate()
+            .map(|(feature_index, feature)| {
+                let name = match feature
+                    .properties
+                    .as_ref()
+                    .and_then(|properties| properties.get(name_property))
+                {
+                    Some(Value::String(name)) => name.clone(),
+                    Some(Value::Number(name)) => name.to_string(),
+                    _ => {
+                        return Err(miette!(
+                            "District boundary feature {} has no \"{}\" property.",
+                            feature_index,
+                            name_property
+                        ))
+                    }
+                };
+
+                let polygons = match feature.geometry {
+                    RawGeometry::Polygon { coordinates } => vec![Polygon::from_rings(coordinates)?],
+                    RawGeometry::MultiPolygon { coordinates } => coordinates
+                        .into_iter()
+                        .map(Polygon::from_rings)
+                        .collect::<Result<Vec<_>>>()?,
+                };
+
+                Ok(District { name, polygons })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { districts })
+    }
+
+    pub fn number_of_districts(&self) -> usize {
+        self.districts.len()
+    }
+
+    /// Returns the name of the district the location lies in. If districts overlap,
+    /// the one that comes first in the GeoJSON file is returned.
+    pub fn district_of(&self, location: &GeographicalLocation) -> Option<&str> {
+        let point = (location.longitude, location.latitude);
+
+        self.districts
+            .iter()
+            .find(|district| {
+                district
+                    .polygons
+                    .iter()
+                    .any(|polygon| polygon.contains(point))
+            })
+            .map(|district| district.name.as_str())
+    }
+}
+
+
+impl Polygon {
+    fn from_rings(rings: Vec<Vec<Vec<f64>>>) -> Result<Self> {
+        let mut rings = rings
+            .into_iter()
+            .map(|ring| {
+                ring.into_iter()
+                    .map(|position| match position[..] {
+                        [longitude, latitude, ..] => Ok((longitude, latitude)),
+                        _ => Err(miette!(
+                            "Invalid GeoJSON position (expected at least two coordinates)."
+                        )),
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter();
+
+        let exterior = rings
+            .next()
+            .ok_or_else(|| miette!("Invalid GeoJSON polygon (no exterior ring)."))?;
+
+        Ok(Self {
+            exterior,
+            holes: rings.collect(),
+        })
+    }
+
+    fn contains(&self, point: (f64, f64)) -> bool {
+        ring_contains(&self.exterior, point)
+            && !self.holes.iter().any(|hole| ring_contains(hole, point))
+    }
+}
+
+/// Checks whether the point is inside the ring using the
+/// [even-odd rule](https://en.wikipedia.org/wiki/Point_in_polygon#Ray_casting_algorithm).
+/// District-sized polygons are small enough to treat coordinates as planar.
+fn ring_contains(ring: &[(f64, f64)], (x, y): (f64, f64)) -> bool {
+    let mut is_inside = false;
+
+    let mut previous_point_index = ring.len().wrapping_sub(1);
+    for (point_index, &(point_x, point_y)) in ring.iter().enumerate() {
+        let (previous_x, previous_y) = ring[previous_point_index];
+
+        if (point_y > y) != (previous_y > y)
+            && x < (previous_x - point_x) * (y - point_y) / (previous_y - point_y) + point_x
+        {
+            is_inside = !is_inside;
+        }
+
+        previous_point_index = point_index;
+    }
+
+    is_inside
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_districts_of_locations() {
+        // Two made-up districts: a square with a hole (a park) in its center, and a
+        // multi-polygon consisting of the park and a square to the east.
+        let boundaries = DistrictBoundaries::from_geojson(
+            r#"{
+                "type": "FeatureCollection",
+                "features": [
+                    {
+                        "type": "Feature",
+                        "properties": { "NAZIV": "Center" },
+                        "geometry": {
+                            "type": "Polygon",
+                            "coordinates": [
+                                [[14.49, 46.04], [14.52, 46.04], [14.52, 46.06], [14.49, 46.06], [14.49, 46.04]],
+                                [[14.50, 46.045], [14.51, 46.045], [14.51, 46.055], [14.50, 46.055], [14.50, 46.045]]
+                            ]
+                        }
+                    },
+                    {
+                        "type": "Feature",
+                        "properties": { "NAZIV": "Moste" },
+                        "geometry": {
+                            "type": "MultiPolygon",
+                            "coordinates": [
+                                [[[14.50, 46.045, 295.0], [14.51, 46.045, 295.0], [14.51, 46.055, 295.0], [14.50, 46.055, 295.0]]],
+                                [[[14.52, 46.04], [14.55, 46.04], [14.55, 46.06], [14.52, 46.06], [14.52, 46.04]]]
+                            ]
+                        }
+                    }
+                ]
+            }"#,
+            "NAZIV",
+        )
+        .unwrap();
+
+        assert_eq!(boundaries.number_of_districts(), 2);
+
+        let district_of = |latitude, longitude| {
+            boundaries.district_of(&GeographicalLocation::new(latitude, longitude))
+        };
+
+        assert_eq!(district_of(46.05, 14.495), Some("Center"));
+        // Inside the hole of the first district, which belongs to the second one.
+        assert_eq!(district_of(46.05, 14.505), Some("Moste"));
+        assert_eq!(district_of(46.05, 14.54), Some("Moste"));
+        assert_eq!(district_of(46.07, 14.505), None);
+    }
+
+    #[test]
+    fn reject_invalid_district_boundaries() {
+        let point_feature = r#"{
+            "type": "FeatureCollection",
+            "features": [{
+                "type": "Feature",
+                "properties": { "name": "Bežigrad" },
+                "geometry": { "type": "Point", "coordinates": [14.51, 46.07] }
+            }]
+        }"#;
+        assert!(DistrictBoundaries::from_geojson(point_feature, "name").is_err());
+
+        let unnamed_feature = r#"{
+            "type": "FeatureCollection",
+            "features": [{
+                "type": "Feature",
+                "properties": {},
+                "geometry": { "type": "Polygon", "coordinates": [[[14.5, 46.0], [14.6, 46.0], [14.6, 46.1]]] }
+            }]
+        }"#;
+        assert!(DistrictBoundaries::from_geojson(unnamed_feature, "name").is_err());
+    }
+}
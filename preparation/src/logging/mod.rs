@@ -0,0 +1,138 @@
+use std::path::Path;
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+use tracing_appender::{
+    non_blocking::{NonBlocking, WorkerGuard},
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    prelude::__tracing_subscriber_SubscriberExt,
+    util::SubscriberInitExt,
+    EnvFilter,
+    Layer,
+};
+
+use self::size_rotation::SizeRotatingFileWriter;
+use crate::{configuration::LogFileRotation, telemetry::TelemetryLayer};
+
+mod size_rotation;
+
+/// Initialize the console and file logging.
+///
+/// The logs will be written to the specified directory into log files that are rotated
+/// according to `log_file_rotation`. If `log_file_max_retained_files` is `Some`, the oldest
+/// log files are deleted so that at most that many remain. If a `telemetry_layer` is given,
+/// spans are also exported to an OpenTelemetry collector (see [`crate::telemetry`]).
+///
+/// **IMPORTANT: Retain the returned
+/// [`WorkerGuard`](../tracing_appender/non_blocking/struct.WorkerGuard.html)
+/// in scope, otherwise flushing to file will stop.**
+pub fn initialize_tracing<P>(
+    console_level_filter: EnvFilter,
+    log_file_level_filter: EnvFilter,
+    log_file_directory_path: P,
+    log_file_rotation: LogFileRotation,
+    log_file_name_prefix: &str,
+    log_file_max_retained_files: Option<usize>,
+    telemetry_layer: Option<TelemetryLayer>,
+) -> Result<WorkerGuard>
+where
+    P: AsRef<Path>,
+{
+    let console_layer = {
+        let console_tracing_format = tracing_subscriber::fmt::format()
+            .with_ansi(true)
+            .with_target(true)
+            .with_level(true);
+
+        let console_layer = tracing_subscriber::fmt::layer()
+            .log_internal_errors(true)
+            .event_format(console_tracing_format);
+
+        let level_filter = if std::env::var("RUST_LOG").is_err() {
+            // If RUST_LOG is unset, use the configuration default.
+            console_level_filter
+        } else {
+            EnvFilter::from_default_env()
+        };
+
+        console_layer.with_filter(level_filter)
+    };
+
+    let (file_layer, file_guard) = {
+        let file_tracing_format = tracing_subscriber::fmt::format()
+            .with_ansi(false)
+            .with_target(true)
+            .with_level(true);
+
+        let (appender, guard) = initialize_log_file_writer(
+            log_file_directory_path.as_ref(),
+            log_file_rotation,
+            log_file_name_prefix,
+            log_file_max_retained_files,
+        )?;
+
+        let file_subscriber = tracing_subscriber::fmt::layer()
+            .with_writer(appender)
+            .log_internal_errors(true)
+            .event_format(file_tracing_format);
+
+        (
+            file_subscriber.with_filter(log_file_level_filter),
+            guard,
+        )
+    };
+
+    tracing_subscriber::registry()
+        .with(telemetry_layer)
+        .with(console_layer)
+        .with(file_layer)
+        .init();
+
+
+    Ok(file_guard)
+}
+
+
+fn initialize_log_file_writer(
+    log_file_directory_path: &Path,
+    log_file_rotation: LogFileRotation,
+    log_file_name_prefix: &str,
+    log_file_max_retained_files: Option<usize>,
+) -> Result<(NonBlocking, WorkerGuard)> {
+    let time_based_rotation = match log_file_rotation {
+        LogFileRotation::Hourly => Rotation::HOURLY,
+        LogFileRotation::Daily => Rotation::DAILY,
+        LogFileRotation::Size {
+            max_file_size_in_bytes,
+        } => {
+            let size_rotating_writer = SizeRotatingFileWriter::new(
+                log_file_directory_path,
+                log_file_name_prefix,
+                max_file_size_in_bytes,
+                log_file_max_retained_files,
+            )
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to initialize size-rotating log file."))?;
+
+            return Ok(tracing_appender::non_blocking(
+                size_rotating_writer,
+            ));
+        }
+    };
+
+    let mut appender_builder = RollingFileAppender::builder()
+        .rotation(time_based_rotation)
+        .filename_prefix(log_file_name_prefix);
+
+    if let Some(max_retained_files) = log_file_max_retained_files {
+        appender_builder = appender_builder.max_log_files(max_retained_files);
+    }
+
+    let appender = appender_builder
+        .build(log_file_directory_path)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to initialize rolling log file."))?;
+
+    Ok(tracing_appender::non_blocking(appender))
+}
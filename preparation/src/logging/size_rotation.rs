@@ -0,0 +1,277 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+
+const FILE_NAME_DATE_TIME_FORMAT: &str = "%Y-%m-%d_%H-%M-%S%.3f";
+
+
+/// A log file writer that starts a new file whenever the current one would
+/// exceed `max_file_size_in_bytes`, optionally deleting the oldest log files
+/// so that at most `max_retained_files` of them remain in the directory.
+///
+/// Log files are named `{prefix}.{timestamp}`, meaning that they sort
+/// chronologically by their file name.
+pub struct SizeRotatingFileWriter {
+    directory_path: PathBuf,
+    file_name_prefix: String,
+    max_file_size_in_bytes: u64,
+    max_retained_files: Option<usize>,
+
+    current_file: File,
+    current_file_size_in_bytes: u64,
+}
+
+impl SizeRotatingFileWriter {
+    pub fn new<P, S>(
+        directory_path: P,
+        file_name_prefix: S,
+        max_file_size_in_bytes: u64,
+        max_retained_files: Option<usize>,
+    ) -> io::Result<Self>
+    where
+        P: Into<PathBuf>,
+        S: Into<String>,
+    {
+        let directory_path: PathBuf = directory_path.into();
+        let file_name_prefix: String = file_name_prefix.into();
+
+        fs::create_dir_all(&directory_path)?;
+
+        let current_file = open_new_log_file(&directory_path, &file_name_prefix)?;
+
+        let writer = Self {
+            directory_path,
+            file_name_prefix,
+            max_file_size_in_bytes,
+            max_retained_files,
+            current_file,
+            current_file_size_in_bytes: 0,
+        };
+
+        writer.remove_old_log_files()?;
+
+        Ok(writer)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.current_file.flush()?;
+
+        self.current_file = open_new_log_file(&self.directory_path, &self.file_name_prefix)?;
+        self.current_file_size_in_bytes = 0;
+
+        self.remove_old_log_files()
+    }
+
+    fn remove_old_log_files(&self) -> io::Result<()> {
+        let Some(max_retained_files) = self.max_retained_files else {
+            return Ok(());
+        };
+
+        let log_file_name_start = format!("{}.", self.file_name_prefix);
+
+        let mut log_file_paths = Vec::new();
+        for entry in fs::read_dir(&self.directory_path)? {
+            let entry = entry?;
+
+            let is_log_file = entry.file_type()?.is_file()
+                && entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(&log_file_name_start);
+
+            if is_log_file {
+                log_file_paths.push(entry.path());
+            }
+        }
+
+        if log_file_paths.len() <= max_retained_files {
+            return Ok(());
+        }
+
+        log_file_paths.sort();
+
+        let number_of_files_to_remove = log_file_paths.len() - max_retained_files;
+        for old_log_file_path in log_file_paths.into_iter().take(number_of_files_to_remove) {
+            fs::remove_file(old_log_file_path)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let would_exceed_max_size =
+            self.current_file_size_in_bytes + buf.len() as u64 > self.max_file_size_in_bytes;
+
+        // A single write is never split across files; an empty file
+        // accepts any write, even if it is larger than the maximum size.
+        if would_exceed_max_size && self.current_file_size_in_bytes > 0 {
+            self.rotate()?;
+        }
+
+        let written_bytes = self.current_file.write(buf)?;
+        self.current_file_size_in_bytes += written_bytes as u64;
+
+        Ok(written_bytes)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.current_file.flush()
+    }
+}
+
+
+/// Opens a new log file. If a log file was already opened in the same millisecond
+/// (e.g. because of several rotations in a row), a counter is appended to its name.
+fn open_new_log_file(directory_path: &Path, file_name_prefix: &str) -> io::Result<File> {
+    let file_name = format!(
+        "{}.{}",
+        file_name_prefix,
+        Utc::now().format(FILE_NAME_DATE_TIME_FORMAT)
+    );
+
+    let mut file_path = directory_path.join(&file_name);
+    let mut counter: u32 = 0;
+
+    loop {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&file_path)
+        {
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                counter += 1;
+                file_path = directory_path.join(format!("{}.{:03}", file_name, counter));
+            }
+            result => return result,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    const FILE_NAME_PREFIX: &str = "recorder.log";
+
+    fn test_directory_path() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "lpp-recorder-size-rotation-test-{}",
+            Uuid::new_v4()
+        ))
+    }
+
+    /// Contents of the log files in the directory, oldest first.
+    fn log_file_contents(directory_path: &Path) -> Vec<String> {
+        let mut log_file_paths: Vec<PathBuf> = fs::read_dir(directory_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with(&format!("{}.", FILE_NAME_PREFIX))
+            })
+            .collect();
+        log_file_paths.sort();
+
+        log_file_paths
+            .into_iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn rotate_when_file_would_exceed_size_limit() {
+        let directory_path = test_directory_path();
+        let mut writer =
+            SizeRotatingFileWriter::new(&directory_path, FILE_NAME_PREFIX, 10, None).unwrap();
+
+        writer.write_all(b"aaaaaa").unwrap();
+        writer.write_all(b"bbbb").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(
+            log_file_contents(&directory_path),
+            vec!["aaaaaabbbb"]
+        );
+
+        writer.write_all(b"c").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(
+            log_file_contents(&directory_path),
+            vec!["aaaaaabbbb", "c"]
+        );
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+
+    #[test]
+    fn never_split_write_larger_than_size_limit() {
+        let directory_path = test_directory_path();
+        let mut writer =
+            SizeRotatingFileWriter::new(&directory_path, FILE_NAME_PREFIX, 10, None).unwrap();
+
+        let large_line = "x".repeat(25);
+
+        // An empty file accepts it as a whole, ...
+        writer.write_all(large_line.as_bytes()).unwrap();
+        // ... a non-empty one is rotated first.
+        writer.write_all(b"a").unwrap();
+        writer.write_all(large_line.as_bytes()).unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(
+            log_file_contents(&directory_path),
+            vec![large_line.clone(), String::from("a"), large_line]
+        );
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+
+    #[test]
+    fn remove_only_the_oldest_log_files_beyond_retention_limit() {
+        let directory_path = test_directory_path();
+        fs::create_dir_all(&directory_path).unwrap();
+
+        let unrelated_file_path = directory_path.join("unrelated.txt");
+        fs::write(&unrelated_file_path, "unrelated").unwrap();
+        for (date, contents) in [("2023-11-04", "oldest"), ("2023-11-05", "older")] {
+            fs::write(
+                directory_path.join(format!(
+                    "{}.{}_12-00-00.000",
+                    FILE_NAME_PREFIX, date
+                )),
+                contents,
+            )
+            .unwrap();
+        }
+
+        let mut writer =
+            SizeRotatingFileWriter::new(&directory_path, FILE_NAME_PREFIX, 10, Some(2)).unwrap();
+        assert_eq!(
+            log_file_contents(&directory_path),
+            vec!["older", ""]
+        );
+
+        writer.write_all(b"first").unwrap();
+        writer.write_all(b"second").unwrap();
+        writer.write_all(b"third").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(
+            log_file_contents(&directory_path),
+            vec!["second", "third"]
+        );
+
+        assert!(unrelated_file_path.is_file());
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+}
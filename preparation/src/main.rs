@@ -1,69 +1,468 @@
+use api::{client::LppApiClient, route_name_cache_statistics};
 use cancellation_token::CancellationToken;
+use chrono::{DateTime, Utc};
 use clap::Parser;
-use cli::{CLIArgs, RunMode};
+use cli::{CLIArgs, CLICommand, ConfigCommand, RunMode};
+use clock::SystemClock;
+#[cfg(feature = "hdf5-export")]
+use commands::export_hdf5::run_export_hdf5;
+use commands::{
+    compact_archive::run_compact_archive,
+    config_schema::run_config_schema,
+    doctor::run_doctor,
+    explore::run_explore,
+    fetch_plan::run_fetch_plan,
+    fsck::run_fsck,
+    logs_for_run::run_logs_for_run,
+    query::run_query,
+    report::run_report,
+    route_families::run_route_families,
+    route_segments::run_route_segments,
+    schema_dump::run_schema_dump,
+    station_posters::run_station_posters,
+    stats::run_stats,
+    timetable_matrix::run_timetable_matrix,
+    verify_signatures::run_verify_signatures,
+    watch::run_watch,
+};
 use logging::initialize_tracing;
 use miette::{miette, Context, IntoDiagnostic, Result};
-use recorder::initialize_station_and_route_details_snapshot_task;
+use notifications::EmailNotifier;
+use pause::{initialize_pause_watcher_task, PauseSwitch};
+use recorder::{
+    arrivals::initialize_arrival_recording_task,
+    initialize_station_and_route_details_snapshot_task,
+    CAPTURED_SNAPSHOTS_COUNTER,
+};
 use reqwest::Client;
-use tracing::info;
+use shutdown::initialize_shutdown_signal_task;
+use storage::{RunCounters, RunHistoryEntry, RunOutcome, StorageRoot};
+use telemetry::initialize_telemetry;
+use tracing::{info, warn};
+use upload::SnapshotUploader;
 
-use crate::configuration::Configuration;
+use crate::configuration::{CaptureMode, Configuration};
 
+mod analysis;
 mod api;
+mod calendar;
 mod cancellation_token;
 mod cli;
+mod clock;
+mod commands;
 mod configuration;
+mod display_names;
+mod districts;
 mod logging;
+mod notifications;
+mod pause;
 mod recorder;
+mod shutdown;
+mod signing;
 mod storage;
+mod systemd;
+mod telemetry;
+mod upload;
 
 
-pub async fn run_tasks(configuration: &Configuration, run_mode: RunMode) -> Result<()> {
+pub async fn run_tasks(
+    configuration: &Configuration,
+    run_mode: RunMode,
+    run_counters: RunCounters,
+    email_notifier: Option<EmailNotifier>,
+) -> Result<()> {
+    report_unfinished_intents(&configuration.lpp.recording.recording_storage_root);
+
     let http_client = Client::builder()
         .user_agent(&configuration.lpp.api.user_agent)
         .build()
         .unwrap();
 
+    let pause_switch = PauseSwitch::new();
+    let pause_watcher_task = initialize_pause_watcher_task(
+        pause_switch.clone(),
+        configuration.lpp.recording.recording_storage_root.path(),
+    );
+
+    let api_client = LppApiClient::new(
+        http_client,
+        configuration.lpp.api.warmup,
+        configuration.lpp.api.concurrency,
+        configuration.lpp.api.rate_limit,
+        configuration.lpp.api.schema_drift_sample_rate,
+        Some(pause_switch),
+    );
+
+    let uploader = configuration
+        .upload
+        .clone()
+        .map(|upload_configuration| {
+            SnapshotUploader::new(
+                upload_configuration,
+                configuration.lpp.recording.recording_storage_root.path(),
+                &configuration.lpp.api.user_agent,
+            )
+        })
+        .transpose()
+        .wrap_err_with(|| miette!("Failed to initialize snapshot uploader."))?;
+
+    // SIGINT and SIGTERM stop the recorder loops between requests (see `CancellationToken`).
     let job_cancellation_token = CancellationToken::new();
+    let shutdown_signal_task = initialize_shutdown_signal_task(job_cancellation_token.clone());
 
     let station_and_route_snapshot_task = initialize_station_and_route_details_snapshot_task(
         &configuration.lpp,
-        http_client.clone(),
+        api_client.clone(),
         job_cancellation_token.clone(),
         run_mode,
+        run_counters.clone(),
+        uploader,
+        email_notifier,
+        SystemClock::shared(),
     );
 
-    info!("Task spawned.");
+    let arrival_recording_task =
+        configuration
+            .lpp
+            .recording
+            .arrival_recording
+            .clone()
+            .map(|arrival_configuration| {
+                initialize_arrival_recording_task(
+                    &configuration.lpp,
+                    arrival_configuration,
+                    api_client.clone(),
+                    job_cancellation_token.clone(),
+                    run_counters.clone(),
+                    SystemClock::shared(),
+                )
+            });
 
-    station_and_route_snapshot_task
+    info!("Tasks spawned.");
+    systemd::notify_ready();
+
+    let task_result = station_and_route_snapshot_task
         .await
         .into_diagnostic()
-        .wrap_err_with(|| miette!("Station details recorder task panicked!"))??;
+        .wrap_err_with(|| miette!("Station details recorder task panicked!"));
+
+    // The arrival recorder runs for as long as the snapshot loop does.
+    job_cancellation_token.cancel();
+    let arrival_task_result = match arrival_recording_task {
+        Some(arrival_recording_task) => arrival_recording_task
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Arrival recorder task panicked!")),
+        None => Ok(Ok(())),
+    };
+
+    systemd::notify_stopping();
+    pause_watcher_task.abort();
+    shutdown_signal_task.abort();
+    record_api_concurrency_in_counters(&api_client, &run_counters);
+    task_result??;
+    arrival_task_result??;
 
     Ok(())
 }
 
 
+/// Reports the actions the previous recording run started, but never finished,
+/// i.e. where it crashed and which data might be incomplete.
+fn report_unfinished_intents(storage_root: &StorageRoot) {
+    let unfinished_intents = match storage_root.intent_log().take_unfinished_intents() {
+        Ok(unfinished_intents) => unfinished_intents,
+        Err(error) => {
+            warn!(error = ?error, "Failed to check the intent log for unfinished actions.");
+            return;
+        }
+    };
+
+    for intent in unfinished_intents {
+        warn!(
+            action = intent.action,
+            target = intent.target,
+            run_id = intent.run_id.map(|run_id| run_id.to_string()),
+            started_at = %intent.started_at,
+            "The previous run did not finish this action - it may have crashed during it, \
+            so its data might be incomplete."
+        );
+    }
+}
+
+
+/// Run history counters of how many route name parses were (not) answered from the cache.
+const ROUTE_NAME_CACHE_HITS_COUNTER: &str = "route_name_cache_hits";
+const ROUTE_NAME_CACHE_MISSES_COUNTER: &str = "route_name_cache_misses";
+
+
+/// Run history counters of the API client's adaptive concurrency limit.
+const API_CONCURRENCY_LIMIT_COUNTER: &str = "api_concurrency_limit";
+const API_PEAK_CONCURRENCY_LIMIT_COUNTER: &str = "api_peak_concurrency_limit";
+const API_CONCURRENCY_LIMIT_DECREASES_COUNTER: &str = "api_concurrency_limit_decreases";
+const API_RATE_LIMITED_RESPONSES_COUNTER: &str = "api_rate_limited_responses";
+
+
+/// Saves the final concurrency limit of the API client (and how it changed) into the run counters,
+/// if the client has one.
+fn record_api_concurrency_in_counters(api_client: &LppApiClient, run_counters: &RunCounters) {
+    let Some(statistics) = api_client.concurrency_statistics() else {
+        return;
+    };
+
+    info!(
+        concurrency_limit = statistics.current_limit,
+        peak_concurrency_limit = statistics.peak_limit,
+        limit_increases = statistics.limit_increases,
+        limit_decreases = statistics.limit_decreases,
+        rate_limited_responses = statistics.rate_limited_responses,
+        "Final concurrency limit of the API client."
+    );
+
+    run_counters.set(
+        API_CONCURRENCY_LIMIT_COUNTER,
+        statistics.current_limit as u64,
+    );
+    run_counters.set(
+        API_PEAK_CONCURRENCY_LIMIT_COUNTER,
+        statistics.peak_limit as u64,
+    );
+    run_counters.set(
+        API_CONCURRENCY_LIMIT_DECREASES_COUNTER,
+        statistics.limit_decreases,
+    );
+    run_counters.set(
+        API_RATE_LIMITED_RESPONSES_COUNTER,
+        statistics.rate_limited_responses,
+    );
+}
+
+
+/// Appends the outcome of this invocation to the run history (`runs.jsonl`) in the storage root
+/// if it was a recording run, and returns the entry. Failing to append it is only logged,
+/// as it shouldn't affect the outcome of the run itself.
+fn record_run_in_history(
+    configuration: &Configuration,
+    started_at: DateTime<Utc>,
+    mode: &str,
+    capture_mode: Option<CaptureMode>,
+    run_counters: &RunCounters,
+    run_result: &Result<()>,
+) -> RunHistoryEntry {
+    let outcome = match run_result {
+        Ok(_) => RunOutcome::Success,
+        Err(_) if run_counters.get(CAPTURED_SNAPSHOTS_COUNTER) > 0 => RunOutcome::Partial,
+        Err(_) => RunOutcome::Failed,
+    };
+
+    let error = run_result.as_ref().err().map(|error| {
+        error
+            .chain()
+            .map(|cause| cause.to_string())
+            .collect::<Vec<_>>()
+            .join(": ")
+    });
+
+    let mut counts = run_counters.to_map();
+
+    let route_name_cache = route_name_cache_statistics();
+    if route_name_cache.hits + route_name_cache.misses > 0 {
+        counts.insert(
+            ROUTE_NAME_CACHE_HITS_COUNTER.to_string(),
+            route_name_cache.hits,
+        );
+        counts.insert(
+            ROUTE_NAME_CACHE_MISSES_COUNTER.to_string(),
+            route_name_cache.misses,
+        );
+    }
+
+    let run_history_entry = RunHistoryEntry {
+        started_at,
+        finished_at: Utc::now(),
+        mode: mode.to_string(),
+        capture_mode,
+        configuration_hash: configuration.file_hash.clone(),
+        outcome,
+        error,
+        counts,
+        snapshot_run_ids: run_counters.snapshot_run_ids(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+
+    if let Err(error) =
+        run_history_entry.record_in(&configuration.lpp.recording.recording_storage_root)
+    {
+        warn!(error = ?error, "Failed to append this run to the run history.");
+    }
+
+    run_history_entry
+}
+
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli_args = CLIArgs::parse();
     let run_mode = cli_args.run_mode()?;
+    let capture_mode_override = cli_args.capture_mode()?;
+    let configuration_overrides = cli_args.configuration_overrides()?;
+
+    // Commands that don't require a configuration file.
+    if let Some(CLICommand::Config {
+        command: ConfigCommand::Schema(arguments),
+    }) = cli_args.command
+    {
+        return run_config_schema(arguments);
+    }
 
-    let configuration = match &cli_args.config_file_path {
-        Some(path) => Configuration::load_from_path(path),
-        None => Configuration::load_from_default_path(),
+    if let Some(CLICommand::SchemaDump(arguments)) = cli_args.command {
+        return run_schema_dump(arguments);
+    }
+
+    let mut configuration = match &cli_args.config_file_path {
+        Some(path) => Configuration::load_from_path(
+            path,
+            cli_args.strict_config,
+            configuration_overrides,
+        ),
+        None => {
+            Configuration::load_from_default_path(cli_args.strict_config, configuration_overrides)
+        }
     }
     .wrap_err_with(|| miette!("Failed to load configuration from default path."))?;
 
+    if let Some(capture_mode) = capture_mode_override {
+        configuration.lpp.recording.capture_mode = capture_mode;
+    }
+
+    let (telemetry_layer, telemetry_guard) = initialize_telemetry(configuration.telemetry.as_ref())
+        .wrap_err_with(|| miette!("Failed to initialize telemetry."))?;
+
     let _guard = initialize_tracing(
         configuration.logging.console_output_level_filter(),
         configuration.logging.log_file_output_level_filter(),
         &configuration.logging.log_file_output_directory,
+        configuration.logging.log_file_rotation,
+        &configuration.logging.log_file_name_prefix,
+        configuration.logging.log_file_max_retained_files,
+        telemetry_layer,
     )
     .wrap_err_with(|| miette!("Failed to initialize tracing."))?;
 
-    run_tasks(&configuration, run_mode).await?;
+    if let Some(telemetry_configuration) = &configuration.telemetry {
+        info!(
+            endpoint = %telemetry_configuration.endpoint,
+            "Exporting traces and metrics to OpenTelemetry collector."
+        );
+    }
+
+    for configuration_override in &configuration.overrides {
+        info!(
+            key = configuration_override.key,
+            value = %configuration_override.value,
+            "Applied configuration override."
+        );
+    }
+
+    let started_at = Utc::now();
+    let run_counters = RunCounters::default();
+
+    let (mode, capture_mode) = match &cli_args.command {
+        Some(CLICommand::VerifySignatures(_)) => ("verify-signatures", None),
+        Some(CLICommand::Fsck(_)) => ("fsck", None),
+        Some(CLICommand::CompactArchive(_)) => ("compact-archive", None),
+        Some(CLICommand::Explore) => ("explore", None),
+        Some(CLICommand::LogsForRun(_)) => ("logs-for-run", None),
+        Some(CLICommand::RouteFamilies(_)) => ("route-families", None),
+        Some(CLICommand::RouteSegments(_)) => ("route-segments", None),
+        Some(CLICommand::StationPosters(_)) => ("station-posters", None),
+        Some(CLICommand::TimetableMatrix(_)) => ("timetable-matrix", None),
+        Some(CLICommand::Stats(_)) => ("stats", None),
+        Some(CLICommand::Report(_)) => ("report", None),
+        Some(CLICommand::Query(_)) => ("query", None),
+        Some(CLICommand::Watch(_)) => ("watch", None),
+        Some(CLICommand::Doctor(_)) => ("doctor", None),
+        #[cfg(feature = "hdf5-export")]
+        Some(CLICommand::ExportHdf5(_)) => ("export-hdf5", None),
+        Some(CLICommand::SchemaDump(_)) | Some(CLICommand::Config { .. }) => unreachable!(),
+        None if cli_args.plan_only => ("plan", None),
+        None => {
+            let mode = match run_mode {
+                RunMode::Once => "record-once",
+                RunMode::Perpetual => "record-perpetual",
+            };
+
+            (
+                mode,
+                Some(configuration.lpp.recording.capture_mode),
+            )
+        }
+    };
 
+    // Run summaries are only sent for recording runs.
+    let email_notifier = match (capture_mode, &configuration.notifications.email) {
+        (Some(_), Some(email_configuration)) => Some(
+            EmailNotifier::new(email_configuration.clone())
+                .wrap_err_with(|| miette!("Failed to initialize email notifier."))?,
+        ),
+        _ => None,
+    };
+
+    let run_result = match cli_args.command {
+        Some(CLICommand::VerifySignatures(arguments)) => {
+            run_verify_signatures(&configuration, arguments)
+        }
+        Some(CLICommand::Fsck(arguments)) => run_fsck(&configuration, arguments),
+        Some(CLICommand::CompactArchive(arguments)) => {
+            run_compact_archive(&configuration, arguments)
+        }
+        Some(CLICommand::Explore) => run_explore(&configuration),
+        Some(CLICommand::LogsForRun(arguments)) => run_logs_for_run(&configuration, arguments),
+        Some(CLICommand::RouteFamilies(arguments)) => run_route_families(&configuration, arguments),
+        Some(CLICommand::RouteSegments(arguments)) => run_route_segments(&configuration, arguments),
+        Some(CLICommand::StationPosters(arguments)) => {
+            run_station_posters(&configuration, arguments)
+        }
+        Some(CLICommand::TimetableMatrix(arguments)) => {
+            run_timetable_matrix(&configuration, arguments)
+        }
+        Some(CLICommand::Stats(arguments)) => run_stats(&configuration, arguments),
+        Some(CLICommand::Report(arguments)) => run_report(&configuration, arguments),
+        Some(CLICommand::Query(arguments)) => run_query(&configuration, arguments),
+        Some(CLICommand::Watch(arguments)) => run_watch(&configuration, arguments).await,
+        Some(CLICommand::Doctor(arguments)) => run_doctor(&configuration, arguments).await,
+        #[cfg(feature = "hdf5-export")]
+        Some(CLICommand::ExportHdf5(arguments)) => run_export_hdf5(&configuration, arguments),
+        Some(CLICommand::SchemaDump(_)) | Some(CLICommand::Config { .. }) => unreachable!(),
+        None if cli_args.plan_only => run_fetch_plan(&configuration).await,
+        None => {
+            run_tasks(
+                &configuration,
+                run_mode,
+                run_counters.clone(),
+                email_notifier.clone(),
+            )
+            .await
+        }
+    };
+
+    let run_history_entry = record_run_in_history(
+        &configuration,
+        started_at,
+        mode,
+        capture_mode,
+        &run_counters,
+        &run_result,
+    );
+
+    if let (Some(email_notifier), Err(_)) = (&email_notifier, &run_result) {
+        email_notifier
+            .send_failure_summary(&run_history_entry)
+            .await;
+    }
+
+    // Flushes the last traces and metrics while logging still works.
+    drop(telemetry_guard);
     drop(_guard);
-    Ok(())
+    run_result
 }
@@ -0,0 +1,280 @@
+use chrono::{DateTime, Utc};
+use lettre::{
+    message::{header::ContentType, Attachment, MultiPart, SinglePart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport,
+    AsyncTransport,
+    Message,
+    Tokio1Executor,
+};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::Serialize;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    configuration::{EmailNotificationConfiguration, EmailNotificationTrigger, SmtpTls},
+    recorder::formats::{SnapshotStatus, SnapshotWarning},
+    storage::RunHistoryEntry,
+};
+
+/// File name of the JSON summary attached to each email.
+const SUMMARY_ATTACHMENT_FILE_NAME: &str = "summary.json";
+
+
+/// Summary of a single saved snapshot (station and route snapshot pair).
+#[derive(Serialize, Clone, Debug)]
+pub struct SnapshotSummary {
+    pub run_id: Uuid,
+    pub captured_at: DateTime<Utc>,
+    pub duration_seconds: u64,
+    pub status: SnapshotStatus,
+    pub captured_stations: usize,
+    pub expected_stations: usize,
+    pub captured_routes: usize,
+    pub expected_routes: usize,
+    pub warnings: Vec<SnapshotWarning>,
+    pub saved_files: Vec<String>,
+}
+
+
+/// Sends run summaries by email, as configured in the `[notifications.email]` table.
+/// Failing to send an email is only logged, as it shouldn't affect recording.
+#[derive(Clone)]
+pub struct EmailNotifier {
+    configuration: EmailNotificationConfiguration,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl EmailNotifier {
+    pub fn new(configuration: EmailNotificationConfiguration) -> Result<Self> {
+        let transport_builder = match configuration.tls {
+            SmtpTls::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&configuration.smtp_server)
+                    .into_diagnostic()
+                    .wrap_err_with(|| miette!("Failed to set up STARTTLS SMTP transport."))?
+            }
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&configuration.smtp_server)
+                .into_diagnostic()
+                .wrap_err_with(|| miette!("Failed to set up TLS SMTP transport."))?,
+            SmtpTls::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&configuration.smtp_server)
+            }
+        };
+
+        let transport_builder = match configuration.smtp_port {
+            Some(smtp_port) => transport_builder.port(smtp_port),
+            None => transport_builder,
+        };
+
+        let transport_builder = match &configuration.credentials {
+            Some((username, password)) => transport_builder.credentials(Credentials::new(
+                username.clone(),
+                password.clone(),
+            )),
+            None => transport_builder,
+        };
+
+        Ok(Self {
+            transport: transport_builder.build(),
+            configuration,
+        })
+    }
+
+    pub fn sends_every_snapshot(&self) -> bool {
+        self.configuration.send == EmailNotificationTrigger::EverySnapshot
+    }
+
+    pub async fn send_snapshot_summary(&self, summary: &SnapshotSummary) {
+        let (subject, body) = snapshot_summary_email(summary);
+        self.send(subject, body, summary).await;
+    }
+
+    pub async fn send_failure_summary(&self, run_history_entry: &RunHistoryEntry) {
+        let (subject, body) = failure_summary_email(run_history_entry);
+        self.send(subject, body, run_history_entry).await;
+    }
+
+    async fn send<S>(&self, subject: String, body: String, summary: &S)
+    where
+        S: Serialize,
+    {
+        let message = match build_message(&self.configuration, subject, body, summary) {
+            Ok(message) => message,
+            Err(error) => {
+                warn!(error = ?error, "Failed to build summary email.");
+                return;
+            }
+        };
+
+        match self.transport.send(message).await {
+            Ok(_) => info!(
+                recipients = self.configuration.to.len(),
+                "Sent summary email."
+            ),
+            Err(error) => warn!(error = ?error, "Failed to send summary email."),
+        }
+    }
+}
+
+
+fn snapshot_summary_email(summary: &SnapshotSummary) -> (String, String) {
+    let subject = match summary.warnings.len() {
+        0 => format!(
+            "[lpp-recorder] Snapshot captured ({:?})",
+            summary.status
+        ),
+        number_of_warnings => format!(
+            "[lpp-recorder] Snapshot captured ({:?}, {} warning(s))",
+            summary.status, number_of_warnings
+        ),
+    };
+
+    let mut body = format!(
+        "Snapshot run {} finished at {} in {} seconds.\n\n\
+        Status: {:?}\n\
+        Stations: {} of {}\n\
+        Routes: {} of {}\n",
+        summary.run_id,
+        summary.captured_at,
+        summary.duration_seconds,
+        summary.status,
+        summary.captured_stations,
+        summary.expected_stations,
+        summary.captured_routes,
+        summary.expected_routes,
+    );
+
+    if !summary.warnings.is_empty() {
+        body.push_str("\nWarnings:\n");
+        for warning in &summary.warnings {
+            body.push_str(&format!(
+                "- {:?}: {}\n",
+                warning.kind, warning.message
+            ));
+        }
+    }
+
+    body.push_str("\nSaved files:\n");
+    for saved_file in &summary.saved_files {
+        body.push_str(&format!("- {}\n", saved_file));
+    }
+
+    (subject, body)
+}
+
+fn failure_summary_email(run_history_entry: &RunHistoryEntry) -> (String, String) {
+    let subject = format!(
+        "[lpp-recorder] Recording failed ({:?})",
+        run_history_entry.outcome
+    );
+
+    let mut body = format!(
+        "Recording ({}) started at {} and failed at {}.\n\n\
+        Error: {}\n",
+        run_history_entry.mode,
+        run_history_entry.started_at,
+        run_history_entry.finished_at,
+        run_history_entry.error.as_deref().unwrap_or("unknown"),
+    );
+
+    if !run_history_entry.counts.is_empty() {
+        body.push_str("\nCounts:\n");
+        for (name, count) in &run_history_entry.counts {
+            body.push_str(&format!("- {}: {}\n", name, count));
+        }
+    }
+
+    (subject, body)
+}
+
+fn build_message<S>(
+    configuration: &EmailNotificationConfiguration,
+    subject: String,
+    body: String,
+    summary: &S,
+) -> Result<Message>
+where
+    S: Serialize,
+{
+    let summary_json = serde_json::to_vec_pretty(summary)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to serialize summary."))?;
+
+    let mut message_builder = Message::builder()
+        .from(configuration.from.clone())
+        .subject(subject);
+    for recipient in &configuration.to {
+        message_builder = message_builder.to(recipient.clone());
+    }
+
+    message_builder
+        .multipart(
+            MultiPart::mixed()
+                .singlepart(SinglePart::plain(body))
+                .singlepart(
+                    Attachment::new(SUMMARY_ATTACHMENT_FILE_NAME.to_string()).body(
+                        summary_json,
+                        ContentType::parse("application/json").unwrap(),
+                    ),
+                ),
+        )
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to build email message."))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::recorder::formats::SnapshotWarningKind;
+
+    #[test]
+    fn build_snapshot_summary_email() {
+        let configuration = EmailNotificationConfiguration {
+            smtp_server: String::from("smtp.example.com"),
+            smtp_port: None,
+            tls: SmtpTls::Starttls,
+            credentials: None,
+            from: "LPP recorder <recorder@example.com>".parse().unwrap(),
+            to: vec![
+                "first@example.com".parse().unwrap(),
+                "second@example.com".parse().unwrap(),
+            ],
+            send: EmailNotificationTrigger::EverySnapshot,
+        };
+
+        let summary = SnapshotSummary {
+            run_id: Uuid::nil(),
+            captured_at: Utc.with_ymd_and_hms(2023, 11, 6, 12, 0, 0).unwrap(),
+            duration_seconds: 1800,
+            status: SnapshotStatus::Partial,
+            captured_stations: 890,
+            expected_stations: 900,
+            captured_routes: 140,
+            expected_routes: 140,
+            warnings: vec![SnapshotWarning {
+                kind: SnapshotWarningKind::StationsFailed,
+                message: String::from("10 station(s) could not be fetched."),
+            }],
+            saved_files: vec![String::from("station-details_2023-11-06_12-00-00.json")],
+        };
+
+        let (subject, body) = snapshot_summary_email(&summary);
+        assert_eq!(
+            subject,
+            "[lpp-recorder] Snapshot captured (Partial, 1 warning(s))"
+        );
+        assert!(body.contains("Stations: 890 of 900\n"));
+        assert!(body.contains("- StationsFailed: 10 station(s) could not be fetched.\n"));
+
+        let message = build_message(&configuration, subject, body, &summary).unwrap();
+        let formatted_message = String::from_utf8(message.formatted()).unwrap();
+
+        assert!(formatted_message.contains("first@example.com"));
+        assert!(formatted_message.contains("second@example.com"));
+        assert!(formatted_message.contains(SUMMARY_ATTACHMENT_FILE_NAME));
+    }
+}
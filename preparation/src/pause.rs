@@ -0,0 +1,189 @@
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{info, warn};
+
+/// Name of the control file in the storage root.
+/// While it contains `pause`, recording is paused.
+pub const CONTROL_FILE_NAME: &str = "control";
+
+/// How often the control file is checked for changes.
+const CONTROL_FILE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+
+/// Shared paused/resumed state of the recorder. While paused, the API client
+/// holds back new requests, so all recorder loops stop cleanly between requests.
+#[derive(Clone, Debug)]
+pub struct PauseSwitch {
+    is_paused: Arc<watch::Sender<bool>>,
+}
+
+impl PauseSwitch {
+    pub fn new() -> Self {
+        let (is_paused, _) = watch::channel(false);
+
+        Self {
+            is_paused: Arc::new(is_paused),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.is_paused.borrow()
+    }
+
+    /// Pauses or resumes recording, logging the transition (if any).
+    fn set_paused(&self, paused: bool, reason: &str) {
+        let changed = self.is_paused.send_if_modified(|is_paused| {
+            let changed = *is_paused != paused;
+            *is_paused = paused;
+            changed
+        });
+
+        if !changed {
+            return;
+        }
+
+        match paused {
+            true => info!(reason = reason, "Recording has been paused."),
+            false => info!(reason = reason, "Recording has been resumed."),
+        }
+    }
+
+    /// Waits until recording is not paused (returns immediately if it isn't).
+    pub async fn wait_until_resumed(&self) {
+        let mut is_paused = self.is_paused.subscribe();
+
+        // The sender is owned by `self`, so it can't be dropped while we wait.
+        let _ = is_paused.wait_for(|is_paused| !is_paused).await;
+    }
+}
+
+
+/// The sources that can request a pause. Recording is paused while any of them requests it.
+#[derive(Default)]
+struct PauseRequests {
+    /// Toggled by `SIGUSR1`.
+    signal: bool,
+
+    /// Set while the control file contains `pause`.
+    control_file: bool,
+}
+
+impl PauseRequests {
+    fn is_pause_requested(&self) -> bool {
+        self.signal || self.control_file
+    }
+}
+
+/// Returns `true` if the control file exists and contains `pause`.
+fn control_file_requests_pause(control_file_path: &Path) -> bool {
+    match fs::read_to_string(control_file_path) {
+        Ok(contents) => contents.trim().eq_ignore_ascii_case("pause"),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => false,
+        Err(error) => {
+            warn!(
+                error = ?error,
+                file_path = %control_file_path.display(),
+                "Failed to read control file, ignoring it."
+            );
+            false
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn receive_signal(signal: &mut Option<tokio::signal::unix::Signal>) {
+    match signal {
+        Some(signal) => {
+            signal.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Spawns a task that pauses and resumes recording on `SIGUSR1` (which toggles the paused state,
+/// Unix only) and whenever the control file in `storage_root_path` changes.
+/// The task runs until it is aborted.
+pub fn initialize_pause_watcher_task(
+    pause_switch: PauseSwitch,
+    storage_root_path: &Path,
+) -> JoinHandle<()> {
+    let control_file_path: PathBuf = storage_root_path.join(CONTROL_FILE_NAME);
+
+    tokio::task::spawn(async move {
+        #[cfg(unix)]
+        let mut usr1_signal = {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            match signal(SignalKind::user_defined1()) {
+                Ok(usr1_signal) => Some(usr1_signal),
+                Err(error) => {
+                    warn!(
+                        error = ?error,
+                        "Failed to listen for SIGUSR1, recording can only be paused with the control file."
+                    );
+                    None
+                }
+            }
+        };
+
+        let mut pause_requests = PauseRequests::default();
+        let mut control_file_poll_interval = tokio::time::interval(CONTROL_FILE_POLL_INTERVAL);
+
+        loop {
+            #[cfg(unix)]
+            let reason = tokio::select! {
+                _ = control_file_poll_interval.tick() => {
+                    pause_requests.control_file = control_file_requests_pause(&control_file_path);
+                    "control file"
+                }
+                _ = receive_signal(&mut usr1_signal) => {
+                    pause_requests.signal = !pause_requests.signal;
+                    "SIGUSR1"
+                }
+            };
+
+            #[cfg(not(unix))]
+            let reason = {
+                control_file_poll_interval.tick().await;
+                pause_requests.control_file = control_file_requests_pause(&control_file_path);
+                "control file"
+            };
+
+            pause_switch.set_paused(pause_requests.is_pause_requested(), reason);
+        }
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn waits_until_resumed() {
+        let pause_switch = PauseSwitch::new();
+        pause_switch.wait_until_resumed().await;
+
+        pause_switch.set_paused(true, "test");
+        assert!(pause_switch.is_paused());
+
+        let waiting_switch = pause_switch.clone();
+        let waiter = tokio::spawn(async move { waiting_switch.wait_until_resumed().await });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        pause_switch.set_paused(false, "test");
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
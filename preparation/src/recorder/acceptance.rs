@@ -0,0 +1,157 @@
+use super::formats::{SnapshotStatus, SnapshotWarning, SnapshotWarningKind};
+
+/// Name of the directory in the storage root that rejected snapshots are saved into.
+pub const REJECTED_SNAPSHOTS_DIRECTORY_NAME: &str = "rejected";
+
+
+/// Minimum shares of the expected stations and routes a snapshot must capture to be accepted.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct AcceptancePolicy {
+    /// From `0.0` to `1.0`.
+    pub min_station_fraction: f64,
+
+    /// From `0.0` to `1.0`.
+    pub min_route_fraction: f64,
+}
+
+impl AcceptancePolicy {
+    /// A snapshot that captured everything is complete; otherwise it is partial
+    /// if it meets both minimums and rejected if it doesn't.
+    pub fn evaluate(&self, coverage: &CaptureCoverage) -> SnapshotStatus {
+        if coverage.captured_stations >= coverage.expected_stations
+            && coverage.captured_routes >= coverage.expected_routes
+        {
+            SnapshotStatus::Complete
+        } else if coverage.station_fraction() >= self.min_station_fraction
+            && coverage.route_fraction() >= self.min_route_fraction
+        {
+            SnapshotStatus::Partial
+        } else {
+            SnapshotStatus::Rejected
+        }
+    }
+}
+
+
+/// How many of the expected stations and routes a snapshot captured.
+/// Stations without any route groups are not expected to have timetables.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CaptureCoverage {
+    pub expected_stations: usize,
+    pub captured_stations: usize,
+    pub expected_routes: usize,
+    pub captured_routes: usize,
+}
+
+impl CaptureCoverage {
+    pub fn station_fraction(&self) -> f64 {
+        fraction(self.captured_stations, self.expected_stations)
+    }
+
+    pub fn route_fraction(&self) -> f64 {
+        fraction(self.captured_routes, self.expected_routes)
+    }
+
+    /// Describes the coverage of a snapshot that did not capture everything.
+    /// Routes are left out of the description if none were expected (e.g. for station snapshots).
+    pub fn snapshot_warning(&self, status: SnapshotStatus) -> Option<SnapshotWarning> {
+        if status == SnapshotStatus::Complete {
+            return None;
+        }
+
+        if self.expected_routes == 0 && self.captured_routes == 0 {
+            return Some(SnapshotWarning {
+                kind: SnapshotWarningKind::IncompleteCapture,
+                message: format!(
+                    "Captured {} of {} stations ({:.1}%).",
+                    self.captured_stations,
+                    self.expected_stations,
+                    self.station_fraction() * 100.0
+                ),
+            });
+        }
+
+        Some(SnapshotWarning {
+            kind: SnapshotWarningKind::IncompleteCapture,
+            message: format!(
+                "Captured {} of {} stations ({:.1}%) and {} of {} routes ({:.1}%).",
+                self.captured_stations,
+                self.expected_stations,
+                self.station_fraction() * 100.0,
+                self.captured_routes,
+                self.expected_routes,
+                self.route_fraction() * 100.0
+            ),
+        })
+    }
+}
+
+fn fraction(captured: usize, expected: usize) -> f64 {
+    if expected == 0 {
+        1.0
+    } else {
+        captured as f64 / expected as f64
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluate_acceptance_policy() {
+        let policy = AcceptancePolicy {
+            min_station_fraction: 0.95,
+            min_route_fraction: 0.9,
+        };
+        let coverage = |captured_stations, captured_routes| CaptureCoverage {
+            expected_stations: 100,
+            captured_stations,
+            expected_routes: 50,
+            captured_routes,
+        };
+
+        assert_eq!(
+            policy.evaluate(&coverage(100, 50)),
+            SnapshotStatus::Complete
+        );
+        assert_eq!(
+            policy.evaluate(&coverage(95, 45)),
+            SnapshotStatus::Partial
+        );
+        assert_eq!(
+            policy.evaluate(&coverage(94, 50)),
+            SnapshotStatus::Rejected
+        );
+        assert_eq!(
+            policy.evaluate(&coverage(100, 44)),
+            SnapshotStatus::Rejected
+        );
+
+        assert!(coverage(100, 50)
+            .snapshot_warning(SnapshotStatus::Complete)
+            .is_none());
+
+        // Station snapshots of full captures are saved before any routes are expected.
+        let station_coverage = CaptureCoverage {
+            expected_routes: 0,
+            ..coverage(95, 0)
+        };
+        assert_eq!(
+            policy.evaluate(&station_coverage),
+            SnapshotStatus::Partial
+        );
+        assert_eq!(
+            station_coverage
+                .snapshot_warning(SnapshotStatus::Partial)
+                .unwrap()
+                .message,
+            "Captured 95 of 100 stations (95.0%)."
+        );
+        assert_eq!(
+            AcceptancePolicy::default().evaluate(&coverage(0, 0)),
+            SnapshotStatus::Partial
+        );
+    }
+}
@@ -0,0 +1,573 @@
+//! Live arrival recording (see `lpp.recording.arrivals`).
+//!
+//! Next to the snapshot loop, the arrival recorder polls `route/arrivals-on-route` for every trip
+//! in rounds, up to `max_concurrent_requests` trips at a time. Each trip with at least one
+//! arriving bus is saved as a [`TripArrivalsSnapshot`] into `arrival-snapshots/<route>/`
+//! in the storage root. Trips without arriving buses (e.g. night routes during the day) are skipped.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use backoff::{exponential::ExponentialBackoff, ExponentialBackoffBuilder};
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt};
+use miette::{miette, Context, Result};
+use tracing::{debug, info, info_span, warn, Instrument};
+use uuid::Uuid;
+
+use super::{
+    formats::TripArrivalsSnapshot,
+    retry_lpp_api_errors,
+    retryable_async_with_exponential_backoff,
+    save_json_to_file,
+    serialize_to_json,
+    RetryableError,
+};
+use crate::{
+    api::{
+        arrivals_on_route::{fetch_arrivals_on_route, StationArrivalDetails},
+        client::LppApiClient,
+        routes::{fetch_all_routes, RouteDetails},
+    },
+    cancellation_token::CancellationToken,
+    clock::{Clock, SharedClock},
+    configuration::{ArrivalRecordingConfiguration, LppConfiguration},
+    signing::sign_file,
+    storage::{ArrivalStorage, ArrivalStorageRoot, RunCounters},
+    telemetry,
+};
+
+/// Name of the run counter that counts saved trip arrival snapshots.
+pub const CAPTURED_ARRIVAL_SNAPSHOTS_COUNTER: &str = "captured_arrival_snapshots";
+
+
+/// The trips the arrival recorder polls, requested anew every `trip_list_refresh_interval`.
+struct PolledTrips {
+    trips: Vec<RouteDetails>,
+    refreshed_at: Option<DateTime<Utc>>,
+}
+
+impl PolledTrips {
+    fn new() -> Self {
+        Self {
+            trips: Vec::new(),
+            refreshed_at: None,
+        }
+    }
+
+    fn needs_refresh(
+        &self,
+        now: DateTime<Utc>,
+        arrival_configuration: &ArrivalRecordingConfiguration,
+    ) -> bool {
+        let Some(refreshed_at) = self.refreshed_at else {
+            return true;
+        };
+
+        let Ok(refresh_interval) =
+            chrono::Duration::from_std(arrival_configuration.trip_list_refresh_interval)
+        else {
+            return false;
+        };
+
+        match refreshed_at.checked_add_signed(refresh_interval) {
+            Some(refresh_at) => now >= refresh_at,
+            None => false,
+        }
+    }
+
+    /// Requests all trips. If that fails, the previous trips are kept.
+    async fn refresh(
+        &mut self,
+        configuration: &LppConfiguration,
+        client: &LppApiClient,
+        clock: &dyn Clock,
+    ) {
+        match fetch_all_routes(&configuration.api, client).await {
+            Ok(trips) => {
+                debug!(
+                    number_of_trips = trips.len(),
+                    "Refreshed the list of trips to poll arrivals on."
+                );
+
+                self.trips = trips;
+                self.refreshed_at = Some(clock.now());
+            }
+            Err(error) => warn!(
+                error = ?error,
+                previous_number_of_trips = self.trips.len(),
+                "Failed to refresh the list of trips to poll arrivals on, keeping the previous one."
+            ),
+        }
+    }
+}
+
+
+/// Whether any bus is arriving to any station of a trip.
+fn has_arriving_buses(stations: &[StationArrivalDetails]) -> bool {
+    stations.iter().any(|station| !station.arrivals.is_empty())
+}
+
+/// Backoff for retrying the arrival request of a single trip (e.g. when the API rate-limits us,
+/// in which case we wait for as long as its `Retry-After` header asks). All retries must fit
+/// into the polling interval, so a single trip can't hold up the next round.
+fn arrival_retry_backoff(
+    arrival_configuration: &ArrivalRecordingConfiguration,
+) -> ExponentialBackoff<backoff::SystemClock> {
+    ExponentialBackoffBuilder::new()
+        .with_initial_interval(Duration::from_secs(2))
+        .with_randomization_factor(0.1)
+        .with_multiplier(2.0)
+        .with_max_interval(Duration::from_secs(20))
+        .with_max_elapsed_time(Some(arrival_configuration.polling_interval))
+        .build()
+}
+
+
+pub fn initialize_arrival_recording_task(
+    config: &LppConfiguration,
+    arrival_configuration: ArrivalRecordingConfiguration,
+    api_client: LppApiClient,
+    cancellation_token: CancellationToken,
+    run_counters: RunCounters,
+    clock: SharedClock,
+) -> tokio::task::JoinHandle<Result<()>> {
+    let arrival_recording_span = info_span!("arrival-recorder");
+    let arrival_recording_future = arrival_recording_loop(
+        config.clone(),
+        arrival_configuration,
+        api_client,
+        cancellation_token,
+        run_counters,
+        clock,
+    )
+    .instrument(arrival_recording_span);
+
+    info!("Spawning arrival recorder task.");
+    tokio::task::spawn(arrival_recording_future)
+}
+
+async fn arrival_recording_loop(
+    configuration: LppConfiguration,
+    arrival_configuration: ArrivalRecordingConfiguration,
+    client: LppApiClient,
+    cancellation_token: CancellationToken,
+    run_counters: RunCounters,
+    clock: SharedClock,
+) -> Result<()> {
+    let arrival_storage_root = configuration
+        .recording
+        .recording_storage_root
+        .arrivals()
+        .wrap_err_with(|| miette!("Failed to initialize storage location for arrivals."))?;
+
+    // Per-route storage, created once the first snapshot of a route is saved.
+    let mut route_storages: HashMap<String, ArrivalStorage> = HashMap::new();
+    let mut polled_trips = PolledTrips::new();
+
+    while !cancellation_token.is_cancelled() {
+        let round_started_at = Instant::now();
+
+        let polling_round_id = Uuid::new_v4();
+        let round_span = info_span!("arrival-polling-round", polling_round_id = %polling_round_id);
+
+        async {
+            if polled_trips.needs_refresh(clock.now(), &arrival_configuration) {
+                polled_trips
+                    .refresh(&configuration, &client, clock.as_ref())
+                    .await;
+            }
+
+            let mut trips_with_arrivals = 0;
+            let mut failed_trips = 0;
+
+            // Snapshots are saved in the order the requests complete.
+            let mut trip_fetches = stream::iter(polled_trips.trips.clone())
+                .map(|trip| {
+                    let configuration = &configuration;
+                    let arrival_configuration = &arrival_configuration;
+                    let client = &client;
+                    let cancellation_token = &cancellation_token;
+
+                    async move {
+                        if cancellation_token.is_cancelled() {
+                            return (trip, None);
+                        }
+
+                        let stations = retryable_async_with_exponential_backoff(
+                            || fetch_arrivals_on_route(&configuration.api, client, &trip.trip_id),
+                            retry_lpp_api_errors,
+                            Some(arrival_retry_backoff(arrival_configuration)),
+                            Some(cancellation_token),
+                        )
+                        .await;
+
+                        (trip, Some(stations))
+                    }
+                })
+                .buffer_unordered(configuration.recording.max_concurrent_requests);
+
+            while let Some((trip, fetch_result)) = trip_fetches.next().await {
+                let stations = match fetch_result {
+                    Some(Ok(stations)) => stations,
+                    None | Some(Err(RetryableError::Cancelled)) => continue,
+                    Some(Err(error)) => {
+                        warn!(
+                            error = ?error,
+                            trip_id = %trip.trip_id,
+                            route = %trip.route,
+                            "Failed to fetch arrivals on trip."
+                        );
+                        failed_trips += 1;
+                        continue;
+                    }
+                };
+
+                if !has_arriving_buses(&stations) {
+                    continue;
+                }
+
+                let snapshot = TripArrivalsSnapshot {
+                    captured_at: clock.now(),
+                    polling_round_id,
+                    trip,
+                    stations,
+                };
+
+                if let Err(error) = save_trip_arrivals_snapshot(
+                    &configuration,
+                    &arrival_storage_root,
+                    &mut route_storages,
+                    &snapshot,
+                ) {
+                    warn!(
+                        error = ?error,
+                        trip_id = %snapshot.trip.trip_id,
+                        "Failed to save arrivals on trip."
+                    );
+                    failed_trips += 1;
+                    continue;
+                }
+
+                trips_with_arrivals += 1;
+                run_counters.increment(CAPTURED_ARRIVAL_SNAPSHOTS_COUNTER);
+            }
+
+            info!(
+                polled_trips = polled_trips.trips.len(),
+                trips_with_arrivals = trips_with_arrivals,
+                failed_trips = failed_trips,
+                duration_seconds = round_started_at.elapsed().as_secs(),
+                "Arrival polling round complete."
+            );
+            telemetry::record_arrival_polling_round(
+                polled_trips.trips.len(),
+                trips_with_arrivals,
+                failed_trips,
+            );
+        }
+        .instrument(round_span)
+        .await;
+
+        if cancellation_token.is_cancelled() {
+            break;
+        }
+
+        // Rounds start every `polling_interval`, unless a round takes longer than that.
+        let round_duration = round_started_at.elapsed();
+        match arrival_configuration
+            .polling_interval
+            .checked_sub(round_duration)
+        {
+            Some(remaining_interval) => {
+                cancellation_token
+                    .run_until_cancelled(clock.sleep(remaining_interval))
+                    .await;
+            }
+            None => warn!(
+                round_duration_seconds = round_duration.as_secs(),
+                "Arrival polling round took longer than the polling interval, \
+                starting the next one right away."
+            ),
+        }
+    }
+
+    info!("Arrival recording loop has been cancelled, exiting.");
+    Ok(())
+}
+
+fn save_trip_arrivals_snapshot(
+    configuration: &LppConfiguration,
+    arrival_storage_root: &ArrivalStorageRoot,
+    route_storages: &mut HashMap<String, ArrivalStorage>,
+    snapshot: &TripArrivalsSnapshot,
+) -> Result<()> {
+    let route_name = snapshot.trip.route.to_string();
+
+    let route_storage = match route_storages.get(&route_name) {
+        Some(route_storage) => route_storage,
+        None => {
+            let route_storage = arrival_storage_root
+                .route(route_name.clone())
+                .wrap_err_with(|| {
+                    miette!(
+                        "Failed to initialize arrival storage for route {}.",
+                        route_name
+                    )
+                })?;
+
+            route_storages.entry(route_name).or_insert(route_storage)
+        }
+    };
+
+    let snapshot_json = serialize_to_json(snapshot)?;
+    let snapshot_file_path =
+        route_storage.generate_json_file_path(snapshot.captured_at, &snapshot_json);
+
+    save_json_to_file(&snapshot_json, &snapshot_file_path)
+        .wrap_err_with(|| miette!("Failed to save trip arrivals snapshot."))?;
+
+    if let Some(signing_key) = &configuration.recording.snapshot_signing_key {
+        sign_file(signing_key, &snapshot_file_path)
+            .wrap_err_with(|| miette!("Failed to sign trip arrivals snapshot."))?;
+    }
+
+    debug!(
+        file_path = %snapshot_file_path.display(),
+        "Trip arrivals snapshot has been saved to disk."
+    );
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::{FixedOffset, TimeZone};
+    use url::Url;
+
+    use super::*;
+    use crate::{
+        api::{
+            arrivals_on_route::{ArrivalData, ArrivalEstimation},
+            client::stub_server::{start_stub_server, ReceivedRequests},
+            BusRoute,
+            GeographicalLocation,
+            RouteId,
+            StationCode,
+            VehicleId,
+        },
+        clock::ManualClock,
+    };
+
+    const ALL_TRIPS_RESPONSE: &str = r#"{"success":true,"data":[
+        {"route_id":"R6","trip_id":"T6","trip_int_id":1,"route_number":"6B","route_name":"ČRNUČE","short_route_name":null},
+        {"route_id":"R18","trip_id":"T18","trip_int_id":2,"route_number":"18","route_name":"KOSEZE","short_route_name":null},
+        {"route_id":"R27","trip_id":"T27","trip_int_id":3,"route_number":"27","route_name":"NS RUDNIK","short_route_name":null}
+    ]}"#;
+
+    /// Arrivals on a trip with a single bus of route `route_name` arriving
+    /// (or no buses, if `route_name` is `None`).
+    fn arrivals_on_trip_response(route_name: Option<&str>) -> String {
+        let arrivals = match route_name {
+            Some(route_name) => format!(
+                r#"[{{"route_id":"R","vehicle_id":"V","type":0,"eta_min":3,"route_name":"{}","trip_name":"TRIP","depot":0}}]"#,
+                route_name
+            ),
+            None => String::from("[]"),
+        };
+
+        format!(
+            r#"{{"success":true,"data":[{{"station_int_id":1,"name":"BAVARSKI DVOR","station_code":"600011","order_no":1,"latitude":46.058,"longitude":14.506,"arrivals":{}}}]}}"#,
+            arrivals
+        )
+    }
+
+    /// Starts a stand-in for the LPP API: trip `T6` has a bus arriving, trip `T18` is
+    /// rate-limited once and then has a bus arriving, and trip `T27` has no buses.
+    /// The second request for all trips cancels `cancellation_token`.
+    async fn start_arrivals_server(
+        cancellation_token: CancellationToken,
+    ) -> (Url, ReceivedRequests) {
+        start_stub_server(move |request_path, previous_identical_requests| {
+            match request_path {
+                "/route/routes" => {
+                    if previous_identical_requests > 0 {
+                        cancellation_token.cancel();
+                    }
+                    ("200 OK", String::from(ALL_TRIPS_RESPONSE))
+                }
+                "/route/arrivals-on-route?trip-id=T6" => {
+                    ("200 OK", arrivals_on_trip_response(Some("6B")))
+                }
+                "/route/arrivals-on-route?trip-id=T18" if previous_identical_requests == 0 => {
+                    ("429 Too Many Requests", String::new())
+                }
+                "/route/arrivals-on-route?trip-id=T18" => {
+                    ("200 OK", arrivals_on_trip_response(Some("18")))
+                }
+                _ => ("200 OK", arrivals_on_trip_response(None)),
+            }
+        })
+        .await
+    }
+
+    fn arrival_configuration() -> ArrivalRecordingConfiguration {
+        ArrivalRecordingConfiguration {
+            polling_interval: Duration::from_secs(30),
+            trip_list_refresh_interval: Duration::from_secs(60 * 60),
+        }
+    }
+
+    fn station_without_arrivals() -> StationArrivalDetails {
+        StationArrivalDetails {
+            station_code: StationCode::new("600011"),
+            internal_station_id: 1,
+            name: String::from("BAVARSKI DVOR"),
+            stop_number: 1,
+            location: GeographicalLocation::new(46.058, 14.506),
+            arrivals: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn refresh_trips_that_were_never_requested() {
+        let now = Utc.with_ymd_and_hms(2023, 11, 6, 8, 0, 0).unwrap();
+
+        assert!(PolledTrips::new().needs_refresh(now, &arrival_configuration()));
+    }
+
+    #[test]
+    fn refresh_trips_once_the_refresh_interval_passes() {
+        let arrival_configuration = arrival_configuration();
+        let now = Utc.with_ymd_and_hms(2023, 11, 6, 8, 0, 0).unwrap();
+
+        let mut polled_trips = PolledTrips::new();
+        polled_trips.refreshed_at = Some(now);
+
+        assert!(!polled_trips.needs_refresh(
+            now + chrono::Duration::minutes(59),
+            &arrival_configuration
+        ));
+        assert!(polled_trips.needs_refresh(
+            now + chrono::Duration::minutes(60),
+            &arrival_configuration
+        ));
+    }
+
+    #[test]
+    fn skip_trips_without_arriving_buses() {
+        assert!(!has_arriving_buses(&[station_without_arrivals()]));
+        assert!(!has_arriving_buses(&[]));
+    }
+
+    #[test]
+    fn save_trips_with_arriving_buses() {
+        let mut station = station_without_arrivals();
+        station.arrivals.push(ArrivalData {
+            route_id: RouteId::new("route"),
+            vehicle_id: VehicleId::new("vehicle"),
+            arrival_estimation: ArrivalEstimation::LocationBased { eta_in_minutes: 3 },
+            route: BusRoute::from_route_name("6B").unwrap(),
+            trip_name: String::from("ČRNUČE"),
+            heading_to_garage: false,
+        });
+
+        assert!(has_arriving_buses(&[
+            station_without_arrivals(),
+            station
+        ]));
+    }
+
+    #[test]
+    fn fit_arrival_retries_into_the_polling_interval() {
+        assert_eq!(
+            arrival_retry_backoff(&arrival_configuration()).max_elapsed_time,
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[tokio::test]
+    async fn save_arrivals_of_every_polled_trip_until_cancelled() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-arrivals-test-{}",
+            Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory_path).unwrap();
+
+        let cancellation_token = CancellationToken::new();
+        let (base_url, requests) = start_arrivals_server(cancellation_token.clone()).await;
+
+        let configuration =
+            LppConfiguration::for_tests(&base_url, &directory_path, toml::Table::new());
+        let client = LppApiClient::new(reqwest::Client::new(), None, None, None, None, None);
+        let clock = ManualClock::new(
+            FixedOffset::east_opt(3600)
+                .unwrap()
+                .with_ymd_and_hms(2023, 11, 6, 8, 0, 0)
+                .unwrap(),
+        );
+        let run_counters = RunCounters::default();
+
+        // The trip list is requested anew each round, so the second round is cancelled
+        // before it polls any trips.
+        let arrival_configuration = ArrivalRecordingConfiguration {
+            polling_interval: Duration::from_secs(30),
+            trip_list_refresh_interval: Duration::from_secs(10),
+        };
+
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            arrival_recording_loop(
+                configuration.clone(),
+                arrival_configuration,
+                client,
+                cancellation_token,
+                run_counters.clone(),
+                clock,
+            ),
+        )
+        .await
+        .expect("arrival recording loop did not stop when cancelled")
+        .unwrap();
+
+        let requests = requests.lock().unwrap().clone();
+        let number_of_requests =
+            |path: &str| requests.iter().filter(|request| *request == path).count();
+        assert_eq!(number_of_requests("/route/routes"), 2);
+        assert_eq!(number_of_requests("/route/arrivals-on-route?trip-id=T6"), 1);
+        assert_eq!(number_of_requests("/route/arrivals-on-route?trip-id=T18"), 2);
+        assert_eq!(number_of_requests("/route/arrivals-on-route?trip-id=T27"), 1);
+
+        // The rate-limited trip was retried in the same round,
+        // and the trip without arriving buses was skipped.
+        let arrival_storage_root = configuration
+            .recording
+            .recording_storage_root
+            .arrivals()
+            .unwrap();
+        let mut saved_trip_ids = arrival_storage_root
+            .snapshot_file_paths()
+            .unwrap()
+            .into_iter()
+            .map(|snapshot_file_path| {
+                let snapshot: TripArrivalsSnapshot =
+                    serde_json::from_slice(&fs::read(snapshot_file_path).unwrap()).unwrap();
+                snapshot.trip.trip_id.to_string()
+            })
+            .collect::<Vec<_>>();
+        saved_trip_ids.sort();
+
+        assert_eq!(saved_trip_ids, ["T18", "T6"]);
+        assert_eq!(
+            run_counters.get(CAPTURED_ARRIVAL_SNAPSHOTS_COUNTER),
+            2
+        );
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+}
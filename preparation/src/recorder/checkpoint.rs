@@ -0,0 +1,234 @@
+//! Checkpoint of the station phases of a full capture (`checkpoint.json` in the storage root).
+//!
+//! Fetching all stations and their timetables takes most of a full capture. Once these phases
+//! finish, the capture saves their results into the checkpoint, then saves the station snapshot
+//! and records it in the checkpoint as well. If a later phase (e.g. fetching the routes) fails and
+//! the capture is retried (see `lpp.recording.snapshot_retries`), the retry reuses the checkpointed
+//! phases (and the saved station snapshot) and only redoes the rest. A checkpoint is only reused by
+//! the same snapshot run and for the same service date, and is removed once the run ends.
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, TimestampSecondsWithFrac};
+use uuid::Uuid;
+
+use super::{
+    formats::{
+        ReusedCapturePhases,
+        SnapshotStatus,
+        SnapshotWarning,
+        StationDetailsWithBusesAndTimetables,
+    },
+    route_matching::RouteMatchingMode,
+};
+use crate::{api::StationCode, calendar::ServiceDayType, storage::write_file_atomically};
+
+/// Name of the checkpoint file in the storage root.
+pub const CHECKPOINT_FILE_NAME: &str = "checkpoint.json";
+
+/// Capture phases whose results a checkpoint contains.
+pub const CHECKPOINTED_PHASES: &[&str] = &["station-details", "stations"];
+
+
+/// Results of the station phases of a full capture.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StationPhasesCheckpoint {
+    /// Identifier of the snapshot run whose attempt captured these phases.
+    pub run_id: Uuid,
+
+    pub service_date: NaiveDate,
+    pub service_day_type: ServiceDayType,
+
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub capture_started_at: DateTime<Utc>,
+
+    /// When the station phases finished.
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub checkpointed_at: DateTime<Utc>,
+
+    pub route_matching_mode: RouteMatchingMode,
+
+    /// Number of stations in the station details, including ones without route groups.
+    pub total_number_of_stations: usize,
+    pub stations_without_route_groups: usize,
+
+    /// Warnings raised during the station phases.
+    pub warnings: Vec<SnapshotWarning>,
+
+    pub stations_with_bus_trips: Vec<StationDetailsWithBusesAndTimetables>,
+
+    /// Stations that were not requested because the snapshot deadline passed.
+    #[serde(default)]
+    pub unprocessed_station_codes: Vec<StationCode>,
+
+    /// The station snapshot saved from these phases, once it has been saved.
+    #[serde(default)]
+    pub station_snapshot: Option<SavedStationSnapshot>,
+}
+
+/// A station snapshot that was saved as soon as the station phases finished.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedStationSnapshot {
+    /// The snapshot file and its signature (if it was signed).
+    pub file_paths: Vec<PathBuf>,
+
+    pub status: SnapshotStatus,
+
+    /// Warnings the snapshot has on top of the ones raised during the station phases.
+    pub warnings: Vec<SnapshotWarning>,
+}
+
+impl StationPhasesCheckpoint {
+    fn file_path(storage_root_path: &Path) -> PathBuf {
+        storage_root_path.join(CHECKPOINT_FILE_NAME)
+    }
+
+    /// Reads the checkpoint, returning `None` if there is none
+    /// or if it was saved by another run or for another service date.
+    pub fn load(
+        storage_root_path: &Path,
+        run_id: Uuid,
+        service_date: NaiveDate,
+    ) -> Result<Option<Self>> {
+        let checkpoint_file_contents = match fs::read(Self::file_path(storage_root_path)) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => {
+                return Err(error)
+                    .into_diagnostic()
+                    .wrap_err_with(|| miette!("Failed to read checkpoint."))
+            }
+        };
+
+        let checkpoint: Self = serde_json::from_slice(&checkpoint_file_contents)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to parse checkpoint."))?;
+
+        if checkpoint.run_id != run_id || checkpoint.service_date != service_date {
+            return Ok(None);
+        }
+
+        Ok(Some(checkpoint))
+    }
+
+    /// Replaces the checkpoint. It is written to a temporary file first and then renamed,
+    /// so a crash while saving never leaves a partially-written checkpoint behind.
+    pub fn save(&self, storage_root_path: &Path) -> Result<()> {
+        let serialized_checkpoint = serde_json::to_vec(self)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to serialize checkpoint."))?;
+
+        write_file_atomically(
+            &Self::file_path(storage_root_path),
+            &serialized_checkpoint,
+        )
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to replace checkpoint."))
+    }
+
+    /// Removes the checkpoint, if there is one.
+    pub fn remove(storage_root_path: &Path) -> Result<()> {
+        match fs::remove_file(Self::file_path(storage_root_path)) {
+            Ok(()) => Ok(()),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(error)
+                .into_diagnostic()
+                .wrap_err_with(|| miette!("Failed to remove checkpoint.")),
+        }
+    }
+
+    /// Describes the phases a capture reused from this checkpoint (recorded in its snapshots).
+    pub fn reused_phases(&self) -> ReusedCapturePhases {
+        ReusedCapturePhases {
+            phases: CHECKPOINTED_PHASES
+                .iter()
+                .map(|phase| phase.to_string())
+                .collect(),
+            checkpointed_at: self.checkpointed_at,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn reuse_checkpoint_only_within_the_same_run() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-checkpoint-test-{}",
+            Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory_path).unwrap();
+
+        let run_id = Uuid::new_v4();
+        let service_date = NaiveDate::from_ymd_opt(2023, 11, 6).unwrap();
+
+        assert!(
+            StationPhasesCheckpoint::load(&directory_path, run_id, service_date)
+                .unwrap()
+                .is_none()
+        );
+
+        let checkpoint = StationPhasesCheckpoint {
+            run_id,
+            service_date,
+            service_day_type: ServiceDayType::Weekday,
+            capture_started_at: Utc.with_ymd_and_hms(2023, 11, 6, 3, 0, 0).unwrap(),
+            checkpointed_at: Utc.with_ymd_and_hms(2023, 11, 6, 5, 30, 0).unwrap(),
+            route_matching_mode: RouteMatchingMode::FullRoute,
+            total_number_of_stations: 2,
+            stations_without_route_groups: 2,
+            warnings: Vec::new(),
+            stations_with_bus_trips: Vec::new(),
+            unprocessed_station_codes: Vec::new(),
+            station_snapshot: None,
+        };
+        checkpoint.save(&directory_path).unwrap();
+
+        let loaded_checkpoint =
+            StationPhasesCheckpoint::load(&directory_path, run_id, service_date)
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            loaded_checkpoint.reused_phases(),
+            checkpoint.reused_phases()
+        );
+        assert_eq!(loaded_checkpoint.total_number_of_stations, 2);
+
+        // Other runs and service dates start over.
+        assert!(
+            StationPhasesCheckpoint::load(&directory_path, Uuid::new_v4(), service_date)
+                .unwrap()
+                .is_none()
+        );
+        assert!(StationPhasesCheckpoint::load(
+            &directory_path,
+            run_id,
+            service_date.succ_opt().unwrap()
+        )
+        .unwrap()
+        .is_none());
+
+        StationPhasesCheckpoint::remove(&directory_path).unwrap();
+        StationPhasesCheckpoint::remove(&directory_path).unwrap();
+        assert!(
+            StationPhasesCheckpoint::load(&directory_path, run_id, service_date)
+                .unwrap()
+                .is_none()
+        );
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+}
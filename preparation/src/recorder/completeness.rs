@@ -0,0 +1,99 @@
+use super::formats::{TripDataCompleteness, TripStationWithTimetable};
+use crate::api::timetable::TimetableEntry;
+
+
+/// Computes the completeness metadata of a trip.
+///
+/// `total_stations` is the number of stations the LPP API reported for the trip,
+/// including the ones that were left out because they had no timetable.
+pub fn compute_trip_data_completeness(
+    stations_with_timetables: &[TripStationWithTimetable],
+    total_stations: usize,
+    has_route_shape: bool,
+) -> TripDataCompleteness {
+    let stations_with_interpolated_timetables = stations_with_timetables
+        .iter()
+        .filter(|station| station.timetable_is_interpolated)
+        .count();
+
+    let station_timetable_fraction = if total_stations > 0 {
+        stations_with_timetables.len() as f64 / total_stations as f64
+    } else {
+        0.0
+    };
+
+    let reference_timetable = stations_with_timetables
+        .iter()
+        .find(|station| !station.timetable_is_interpolated)
+        .map(|station| station.timetable.timetable.as_slice())
+        .unwrap_or_default();
+
+    TripDataCompleteness {
+        total_stations,
+        stations_with_timetables: stations_with_timetables.len(),
+        stations_with_interpolated_timetables,
+        station_timetable_fraction,
+        has_route_shape,
+        timetable_entry_count: reference_timetable.len(),
+        expected_timetable_entry_count: expected_timetable_entry_count(reference_timetable),
+    }
+}
+
+
+/// Estimates how many timetable entries there should be if buses ran
+/// at the median headway from the first to the last entry.
+fn expected_timetable_entry_count(timetable: &[TimetableEntry]) -> Option<usize> {
+    let mut minutes_of_day: Vec<u32> = timetable
+        .iter()
+        .map(|entry| entry.hour as u32 * 60 + entry.minute as u32)
+        .collect();
+    minutes_of_day.sort_unstable();
+
+    let mut headways: Vec<u32> = minutes_of_day
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .filter(|headway| *headway > 0)
+        .collect();
+
+    if headways.is_empty() {
+        return None;
+    }
+
+    headways.sort_unstable();
+    let median_headway = headways[headways.len() / 2];
+
+    // PANIC SAFETY: There are at least two entries if there is a headway.
+    let service_span = minutes_of_day[minutes_of_day.len() - 1] - minutes_of_day[0];
+
+    Some((service_span as f64 / median_headway as f64).round() as usize + 1)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_fixtures::timetable_entries;
+
+    #[test]
+    fn expected_entry_count_follows_median_headway() {
+        // Every 15 minutes from 5:00 to 6:00, with the 5:30 departure missing.
+        assert_eq!(
+            expected_timetable_entry_count(&timetable_entries(&[(5, 0), (5, 15), (5, 45), (6, 0)])),
+            Some(5)
+        );
+
+        assert_eq!(
+            expected_timetable_entry_count(&timetable_entries(&[(5, 0), (5, 10), (5, 20)])),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn expected_entry_count_needs_two_entries() {
+        assert_eq!(expected_timetable_entry_count(&[]), None);
+        assert_eq!(
+            expected_timetable_entry_count(&timetable_entries(&[(5, 0)])),
+            None
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+use super::formats::{SnapshotStatus, SnapshotWarning, SnapshotWarningKind};
+use crate::{
+    api::{BusRoute, StationCode},
+    cancellation_token::CancellationToken,
+    clock::Clock,
+};
+
+
+/// Time by which a capture must stop issuing requests (see `lpp.recording.snapshot_deadline`).
+/// If the deadline has a cancellation token, it also passes as soon as the token is cancelled
+/// (i.e. when the recorder is shutting down).
+#[derive(Clone, Debug)]
+pub struct CaptureDeadline {
+    deadline: Option<DateTime<Utc>>,
+    cancellation_token: Option<CancellationToken>,
+}
+
+impl CaptureDeadline {
+    /// A deadline `duration` after `capture_started_at`, or no deadline if `duration` is `None`.
+    pub fn after(capture_started_at: DateTime<Utc>, duration: Option<Duration>) -> Self {
+        let deadline = duration
+            .and_then(|duration| chrono::Duration::from_std(duration).ok())
+            .and_then(|duration| capture_started_at.checked_add_signed(duration));
+
+        Self {
+            deadline,
+            cancellation_token: None,
+        }
+    }
+
+    /// Makes the deadline pass early once `cancellation_token` is cancelled.
+    pub fn or_when_cancelled(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    /// The cancellation token the deadline was given, if any
+    /// (e.g. to stop retrying requests when shutting down).
+    pub fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation_token.as_ref()
+    }
+
+    pub fn has_passed(&self, clock: &dyn Clock) -> bool {
+        if let Some(cancellation_token) = &self.cancellation_token {
+            if cancellation_token.is_cancelled() {
+                return true;
+            }
+        }
+
+        match self.deadline {
+            Some(deadline) => clock.now() >= deadline,
+            None => false,
+        }
+    }
+}
+
+
+/// Stations and routes a capture did not request because its deadline passed
+/// (or because the recorder was shutting down).
+#[derive(Clone, Default, Debug)]
+pub struct UnprocessedWork {
+    pub station_codes: Vec<StationCode>,
+    pub routes: Vec<BusRoute>,
+}
+
+impl UnprocessedWork {
+    pub fn is_empty(&self) -> bool {
+        self.station_codes.is_empty() && self.routes.is_empty()
+    }
+
+    /// Describes the unprocessed stations and routes in the log and in a snapshot warning.
+    pub fn report(&self) -> Option<SnapshotWarning> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let station_codes = self
+            .station_codes
+            .iter()
+            .map(|station_code| station_code.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let routes = self
+            .routes
+            .iter()
+            .map(|route| route.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        warn!(
+            unprocessed_stations = self.station_codes.len(),
+            unprocessed_routes = self.routes.len(),
+            station_codes = %station_codes,
+            routes = %routes,
+            "Snapshot deadline passed (or the recorder is shutting down), \
+            these stations and routes were not requested."
+        );
+
+        Some(SnapshotWarning {
+            kind: SnapshotWarningKind::DeadlineExceeded,
+            message: format!(
+                "The snapshot deadline passed (or the recorder was shut down), \
+                so {} station(s) and {} route(s) were not requested. \
+                Stations: [{}]. Routes: [{}].",
+                self.station_codes.len(),
+                self.routes.len(),
+                station_codes,
+                routes
+            ),
+        })
+    }
+
+    /// Snapshots cut short by their deadline are kept as partial snapshots,
+    /// even if they would otherwise be rejected by the acceptance policy.
+    pub fn adjust_status(&self, status: SnapshotStatus) -> SnapshotStatus {
+        match (self.is_empty(), status) {
+            (false, SnapshotStatus::Complete | SnapshotStatus::Rejected) => SnapshotStatus::Partial,
+            _ => status,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, TimeZone};
+
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn deadline_cuts_snapshot_short() {
+        let capture_started_at = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2023, 11, 6, 3, 0, 0)
+            .unwrap();
+        let clock = ManualClock::new(capture_started_at);
+
+        let deadline = CaptureDeadline::after(
+            capture_started_at.with_timezone(&Utc),
+            Some(Duration::from_secs(60 * 60)),
+        );
+        let no_deadline = CaptureDeadline::after(capture_started_at.with_timezone(&Utc), None);
+
+        assert!(!deadline.has_passed(clock.as_ref()));
+        clock.advance(chrono::Duration::minutes(60));
+        assert!(deadline.has_passed(clock.as_ref()));
+        clock.advance(chrono::Duration::days(1));
+        assert!(!no_deadline.has_passed(clock.as_ref()));
+
+        // Shutting down cuts the snapshot short as well.
+        let cancellation_token = CancellationToken::new();
+        let cancellable_deadline = no_deadline.or_when_cancelled(cancellation_token.clone());
+        assert!(!cancellable_deadline.has_passed(clock.as_ref()));
+        cancellation_token.cancel();
+        assert!(cancellable_deadline.has_passed(clock.as_ref()));
+
+        let unprocessed_work = UnprocessedWork {
+            station_codes: vec![StationCode::new("600011"), StationCode::new("600012")],
+            routes: Vec::new(),
+        };
+        assert_eq!(
+            unprocessed_work.adjust_status(SnapshotStatus::Rejected),
+            SnapshotStatus::Partial
+        );
+        assert_eq!(
+            unprocessed_work.adjust_status(SnapshotStatus::Complete),
+            SnapshotStatus::Partial
+        );
+
+        let warning = unprocessed_work.report().unwrap();
+        assert_eq!(
+            warning.kind,
+            SnapshotWarningKind::DeadlineExceeded
+        );
+        assert!(warning.message.contains("Stations: [600011, 600012]."));
+
+        assert_eq!(
+            UnprocessedWork::default().adjust_status(SnapshotStatus::Rejected),
+            SnapshotStatus::Rejected
+        );
+        assert!(UnprocessedWork::default().report().is_none());
+    }
+}
@@ -0,0 +1,186 @@
+//! Resolves the free-text headsign of each trip (e.g. `VIŽMARJE` in `MESTNI LOG - VIŽMARJE`)
+//! to the station code of its destination, i.e. the last station of the trip.
+
+use tracing::warn;
+
+use super::formats::{SnapshotWarning, SnapshotWarningKind, TripWithStationsAndTimetables};
+use crate::api::{routes::RouteDetails, stations_on_route::StationOnRoute, StationCode};
+
+/// Separator of the station names in trip names, e.g. `MESTNI LOG - VIŽMARJE`.
+const TRIP_NAME_PART_SEPARATOR: &str = " - ";
+
+/// How many unresolved headsigns are listed in the snapshot warning.
+const LISTED_UNRESOLVED_HEADSIGNS: usize = 20;
+
+
+/// Returns the headsign of a trip: its short name or, if it has none,
+/// the last part of its full name (e.g. `VIŽMARJE` in `MESTNI LOG - VIŽMARJE`).
+pub fn trip_headsign(route: &RouteDetails) -> &str {
+    match route.short_name.as_deref().map(str::trim) {
+        Some(short_name) if !short_name.is_empty() => short_name,
+        _ => route
+            .name
+            .rsplit(TRIP_NAME_PART_SEPARATOR)
+            .next()
+            .unwrap_or(&route.name)
+            .trim(),
+    }
+}
+
+/// Normalizes a headsign or station name for comparison: uppercase words
+/// of letters and digits, separated by single spaces (e.g. `BTC-city` becomes `BTC CITY`).
+pub fn normalize_headsign(name: &str) -> String {
+    name.to_uppercase()
+        .split(|character: char| !character.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns the station code of the trip's last station if its name matches the trip's headsign,
+/// or `None` if the trip has no stations or its headsign names some other place.
+///
+/// Names match if they are the same after normalization or if one of them starts with all
+/// words of the other (e.g. the headsign `ČRNUČE` matches the station `ČRNUČE GARAŽA`).
+pub fn resolve_destination_station(
+    route: &RouteDetails,
+    stations_on_route: &[StationOnRoute],
+) -> Option<StationCode> {
+    let last_station = stations_on_route
+        .iter()
+        .max_by_key(|station| station.stop_number)?;
+
+    let headsign = normalize_headsign(trip_headsign(route));
+    let station_name = normalize_headsign(&last_station.name);
+
+    if headsign.is_empty() || station_name.is_empty() {
+        return None;
+    }
+
+    let starts_with_words =
+        |name: &str, prefix: &str| name == prefix || name.starts_with(&format!("{} ", prefix));
+
+    match starts_with_words(&station_name, &headsign) || starts_with_words(&headsign, &station_name)
+    {
+        true => Some(last_station.station_code.clone()),
+        false => None,
+    }
+}
+
+/// Logs the trips whose headsigns could not be resolved to a destination station
+/// and describes them in a snapshot warning.
+pub fn unresolved_destinations_warning(
+    routes: &[TripWithStationsAndTimetables],
+) -> Option<SnapshotWarning> {
+    let unresolved_headsigns: Vec<String> = routes
+        .iter()
+        .filter(|trip| trip.destination_station_code.is_none())
+        .map(|trip| {
+            format!(
+                "{} {}",
+                trip.route_details.route,
+                trip_headsign(&trip.route_details)
+            )
+        })
+        .collect();
+
+    if unresolved_headsigns.is_empty() {
+        return None;
+    }
+
+    warn!(
+        unresolved_trips = unresolved_headsigns.len(),
+        trips = %unresolved_headsigns.join(", "),
+        "Could not resolve the headsigns of some trips to their last station."
+    );
+
+    let mut listed_headsigns = unresolved_headsigns
+        .iter()
+        .take(LISTED_UNRESOLVED_HEADSIGNS)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if unresolved_headsigns.len() > LISTED_UNRESOLVED_HEADSIGNS {
+        listed_headsigns.push_str(", ...");
+    }
+
+    Some(SnapshotWarning {
+        kind: SnapshotWarningKind::UnresolvedHeadsigns,
+        message: format!(
+            "The headsigns of {} trip(s) don't match their last station, \
+            so they have no destination_station_code: {}.",
+            unresolved_headsigns.len(),
+            listed_headsigns
+        ),
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{test_fixtures::trip, GeographicalLocation};
+
+    fn stations(names: &[&str]) -> Vec<StationOnRoute> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| StationOnRoute {
+                station_code: StationCode::new(format!("60{:04}", index)),
+                internal_station_id: index as i32,
+                name: name.to_string(),
+                location: GeographicalLocation::new(46.05, 14.5),
+                stop_number: index as i32 + 1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resolve_destinations_from_headsigns() {
+        assert_eq!(
+            trip_headsign(&trip("1", "MESTNI LOG - VIŽMARJE", "T1")),
+            "VIŽMARJE"
+        );
+        assert_eq!(
+            normalize_headsign(" Btc-city  Atlantis"),
+            "BTC CITY ATLANTIS"
+        );
+
+        let stations_on_trip = stations(&["MESTNI LOG", "BAVARSKI DVOR", "Vižmarje"]);
+
+        assert_eq!(
+            resolve_destination_station(
+                &trip("1", "MESTNI LOG - VIŽMARJE", "T1"),
+                &stations_on_trip
+            ),
+            Some(StationCode::new("600002"))
+        );
+        assert_eq!(
+            resolve_destination_station(
+                &RouteDetails {
+                    short_name: Some(String::from("VIŽMARJE BROD")),
+                    ..trip("1", "MESTNI LOG - BROD", "T1")
+                },
+                &stations_on_trip
+            ),
+            Some(StationCode::new("600002"))
+        );
+
+        // Neither a different place nor a partial word matches.
+        assert_eq!(
+            resolve_destination_station(
+                &trip("1", "MESTNI LOG - GARAŽA", "T1"),
+                &stations_on_trip
+            ),
+            None
+        );
+        assert_eq!(
+            resolve_destination_station(&trip("1", "MESTNI LOG - VIŽ", "T1"), &stations_on_trip),
+            None
+        );
+        assert_eq!(
+            resolve_destination_station(&trip("1", "MESTNI LOG - VIŽMARJE", "T1"), &[]),
+            None
+        );
+    }
+}
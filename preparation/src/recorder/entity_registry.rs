@@ -0,0 +1,383 @@
+//! Registry of stable surrogate IDs (`entity-registry.json` in the storage root).
+//!
+//! Station codes, route IDs and trip IDs are assigned by LPP, which may reuse them for
+//! a different station or trip later on. The recorder therefore mints its own ID (a UUID) for
+//! every station, route and trip the first time it sees it and attaches it to the snapshots
+//! (as `stable_id`, `stable_route_id` and `stable_trip_id`).
+//!
+//! Each LPP identifier has one or more versions in the registry, told apart by a fingerprint:
+//! the route name of a route (e.g. `3G`) and the route and name of a trip. If an identifier
+//! shows up with a fingerprint we haven't seen for it yet, LPP has reused it, so a new version
+//! with a new stable ID is registered. If it shows up with the fingerprint of one of its earlier
+//! versions, that version's ID is reused.
+//!
+//! Stations are told apart by their location instead, since LPP renames stations now and then:
+//! a station within [`STATION_LOCATION_TOLERANCE_METERS`] of an earlier version is that version,
+//! even if its name changed (the name is kept as the fingerprint, for reference).
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, TimestampSecondsWithFrac};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use super::formats::{StationDetailsWithBusesAndTimetables, TripWithStationsAndTimetables};
+use crate::{api::GeographicalLocation, storage::write_file_atomically};
+
+/// Name of the registry file in the storage root.
+pub const ENTITY_REGISTRY_FILE_NAME: &str = "entity-registry.json";
+
+/// How far a station can be from an earlier version of its station code
+/// to still be considered the same station (e.g. a stop moved a bit down the street).
+pub const STATION_LOCATION_TOLERANCE_METERS: f64 = 150.0;
+
+
+/// One meaning of an LPP identifier.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EntityVersion {
+    pub stable_id: Uuid,
+
+    /// What the identifier referred to in this version (see the module docs).
+    pub fingerprint: String,
+
+    /// Location of a station when this version was registered.
+    /// Versions registered before locations were recorded don't have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<GeographicalLocation>,
+
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub first_seen_at: DateTime<Utc>,
+
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub last_seen_at: DateTime<Utc>,
+}
+
+/// Versions of LPP identifiers of a single kind, keyed by the identifier.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(transparent)]
+pub struct EntityVersions {
+    versions: BTreeMap<String, Vec<EntityVersion>>,
+}
+
+impl EntityVersions {
+    /// Returns the stable ID of the version of `lpp_id` with the given fingerprint
+    /// (or, if `location` is given, the version within [`STATION_LOCATION_TOLERANCE_METERS`]
+    /// of it), registering a new version if there is none.
+    fn stable_id_for(
+        &mut self,
+        lpp_id: &str,
+        fingerprint: &str,
+        location: Option<GeographicalLocation>,
+        seen_at: DateTime<Utc>,
+    ) -> Uuid {
+        let versions = self.versions.entry(lpp_id.to_string()).or_default();
+
+        if let Some(version) =
+            versions
+                .iter_mut()
+                .find(|version| match (version.location, location) {
+                    (Some(version_location), Some(location)) => {
+                        version_location.distance_in_meters_to(&location)
+                            <= STATION_LOCATION_TOLERANCE_METERS
+                    }
+                    _ => version.fingerprint == fingerprint,
+                })
+        {
+            if version.fingerprint != fingerprint {
+                info!(
+                    lpp_id = lpp_id,
+                    previous_fingerprint = version.fingerprint,
+                    fingerprint = fingerprint,
+                    "LPP identifier has been renamed, keeping its stable ID."
+                );
+                version.fingerprint = fingerprint.to_string();
+            }
+
+            version.location = version.location.or(location);
+            version.last_seen_at = version.last_seen_at.max(seen_at);
+            return version.stable_id;
+        }
+
+        if let Some(previous_version) = versions.last() {
+            info!(
+                lpp_id = lpp_id,
+                previous_fingerprint = previous_version.fingerprint,
+                fingerprint = fingerprint,
+                "LPP identifier now refers to something else, registering a new version of it."
+            );
+        }
+
+        let stable_id = Uuid::new_v4();
+        versions.push(EntityVersion {
+            stable_id,
+            fingerprint: fingerprint.to_string(),
+            location,
+            first_seen_at: seen_at,
+            last_seen_at: seen_at,
+        });
+
+        stable_id
+    }
+
+    fn number_of_versions(&self) -> usize {
+        self.versions.values().map(Vec::len).sum()
+    }
+}
+
+
+/// Stable surrogate IDs of all stations, routes and trips seen so far.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct EntityRegistry {
+    /// Keyed by station code.
+    #[serde(default)]
+    pub stations: EntityVersions,
+
+    /// Keyed by route ID.
+    #[serde(default)]
+    pub routes: EntityVersions,
+
+    /// Keyed by trip ID.
+    #[serde(default)]
+    pub trips: EntityVersions,
+}
+
+impl EntityRegistry {
+    fn file_path(storage_root_path: &Path) -> PathBuf {
+        storage_root_path.join(ENTITY_REGISTRY_FILE_NAME)
+    }
+
+    /// Reads the registry, returning an empty one if there is none yet.
+    pub fn load(storage_root_path: &Path) -> Result<Self> {
+        let registry_file_contents = match fs::read(Self::file_path(storage_root_path)) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => {
+                return Err(error)
+                    .into_diagnostic()
+                    .wrap_err_with(|| miette!("Failed to read entity registry."))
+            }
+        };
+
+        serde_json::from_slice(&registry_file_contents)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to parse entity registry."))
+    }
+
+    /// Replaces the registry. It is written to a temporary file first and then renamed,
+    /// so a crash while saving never leaves a partially-written registry behind.
+    pub fn save(&self, storage_root_path: &Path) -> Result<()> {
+        let serialized_registry = serde_json::to_vec_pretty(self)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to serialize entity registry."))?;
+
+        write_file_atomically(
+            &Self::file_path(storage_root_path),
+            &serialized_registry,
+        )
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to replace entity registry."))
+    }
+
+    /// Attaches stable IDs to the stations and trips of a snapshot run,
+    /// registering the ones seen for the first time.
+    pub fn assign_stable_ids(
+        &mut self,
+        seen_at: DateTime<Utc>,
+        stations: &mut [StationDetailsWithBusesAndTimetables],
+        trips: &mut [TripWithStationsAndTimetables],
+    ) {
+        for station in stations.iter_mut() {
+            station.stable_id = Some(self.stations.stable_id_for(
+                station.station_code.as_ref(),
+                &station.name,
+                Some(station.location),
+                seen_at,
+            ));
+        }
+
+        for trip in trips.iter_mut() {
+            let route_details = &trip.route_details;
+            let route_name = route_details.route.to_string();
+
+            trip.stable_route_id = Some(self.routes.stable_id_for(
+                &route_details.route_id.to_string(),
+                &route_name,
+                None,
+                seen_at,
+            ));
+            trip.stable_trip_id = Some(self.trips.stable_id_for(
+                route_details.trip_id.as_ref(),
+                &format!("{} {}", route_name, route_details.name),
+                None,
+                seen_at,
+            ));
+        }
+
+        debug!(
+            station_versions = self.stations.number_of_versions(),
+            route_versions = self.routes.number_of_versions(),
+            trip_versions = self.trips.number_of_versions(),
+            "Assigned stable IDs to stations, routes and trips."
+        );
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    const BAVARSKI_DVOR: GeographicalLocation = GeographicalLocation {
+        latitude: 46.05804,
+        longitude: 14.50569,
+    };
+
+    #[test]
+    fn mint_new_stable_id_when_lpp_identifier_is_reused() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-entity-registry-test-{}",
+            Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory_path).unwrap();
+
+        let first_seen_at = Utc.with_ymd_and_hms(2023, 11, 6, 3, 0, 0).unwrap();
+        let zelezna_location = GeographicalLocation::new(46.06531, 14.51498);
+
+        let mut registry = EntityRegistry::load(&directory_path).unwrap();
+        let bavarski_dvor = registry.stations.stable_id_for(
+            "600011",
+            "BAVARSKI DVOR",
+            Some(BAVARSKI_DVOR),
+            first_seen_at,
+        );
+        let zelezna = registry.stations.stable_id_for(
+            "201011",
+            "ŽELEZNA",
+            Some(zelezna_location),
+            first_seen_at,
+        );
+        assert_ne!(bavarski_dvor, zelezna);
+
+        registry.save(&directory_path).unwrap();
+        let mut registry = EntityRegistry::load(&directory_path).unwrap();
+
+        // The same station keeps its stable ID across runs.
+        let next_day = first_seen_at + Duration::days(1);
+        assert_eq!(
+            registry.stations.stable_id_for(
+                "600011",
+                "BAVARSKI DVOR",
+                Some(BAVARSKI_DVOR),
+                next_day
+            ),
+            bavarski_dvor
+        );
+
+        // A station code reused for a station elsewhere gets a new stable ID, ...
+        let reused_station_code = registry.stations.stable_id_for(
+            "600011",
+            "ŽELEZNA",
+            Some(zelezna_location),
+            next_day,
+        );
+        assert_ne!(reused_station_code, bavarski_dvor);
+
+        // ... but an earlier meaning of it keeps its original one.
+        assert_eq!(
+            registry.stations.stable_id_for(
+                "600011",
+                "BAVARSKI DVOR",
+                Some(BAVARSKI_DVOR),
+                next_day
+            ),
+            bavarski_dvor
+        );
+        assert_eq!(registry.stations.number_of_versions(), 3);
+        assert_eq!(
+            registry.stations.versions["600011"][0].last_seen_at,
+            next_day
+        );
+
+        // Trips are told apart by their fingerprint.
+        let trip = registry
+            .trips
+            .stable_id_for("trip", "6B ČRNUČE", None, next_day);
+        assert_ne!(
+            registry
+                .trips
+                .stable_id_for("trip", "6B BAVARSKI DVOR", None, next_day),
+            trip
+        );
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+
+    #[test]
+    fn keep_stable_id_of_renamed_station() {
+        let first_seen_at = Utc.with_ymd_and_hms(2023, 11, 6, 3, 0, 0).unwrap();
+        let mut registry = EntityRegistry::default();
+
+        let bavarski_dvor = registry.stations.stable_id_for(
+            "600011",
+            "BAVARSKI DVOR",
+            Some(BAVARSKI_DVOR),
+            first_seen_at,
+        );
+
+        // Renamed and moved a few meters down the street.
+        let moved_location = GeographicalLocation::new(
+            BAVARSKI_DVOR.latitude + 0.0003,
+            BAVARSKI_DVOR.longitude,
+        );
+        assert_eq!(
+            registry.stations.stable_id_for(
+                "600011",
+                "BAVARSKI DVOR - SLOVENSKA",
+                Some(moved_location),
+                first_seen_at + Duration::days(1),
+            ),
+            bavarski_dvor
+        );
+        assert_eq!(registry.stations.number_of_versions(), 1);
+        assert_eq!(
+            registry.stations.versions["600011"][0].fingerprint,
+            "BAVARSKI DVOR - SLOVENSKA"
+        );
+    }
+
+    #[test]
+    fn record_location_of_stations_registered_without_one() {
+        let first_seen_at = Utc.with_ymd_and_hms(2023, 11, 6, 3, 0, 0).unwrap();
+        let mut registry = EntityRegistry::default();
+
+        let bavarski_dvor =
+            registry
+                .stations
+                .stable_id_for("600011", "BAVARSKI DVOR", None, first_seen_at);
+
+        assert_eq!(
+            registry.stations.stable_id_for(
+                "600011",
+                "BAVARSKI DVOR",
+                Some(BAVARSKI_DVOR),
+                first_seen_at
+            ),
+            bavarski_dvor
+        );
+        assert_eq!(
+            registry.stations.versions["600011"][0].location,
+            Some(BAVARSKI_DVOR)
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use crate::api::{station_details::StationDetails, BaseBusRoute, BusRoute};
+
+/// How many of the largest route group sets are listed when displaying a [`FetchPlan`].
+const DISPLAYED_ROUTE_GROUP_SETS: usize = 10;
+
+
+/// Stations that are served by exactly the same route groups.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RouteGroupSet {
+    pub route_groups: Vec<BaseBusRoute>,
+    pub number_of_stations: usize,
+}
+
+/// An estimate of the requests a full snapshot will make, computed from
+/// the station details alone (i.e. before any per-station requests are made).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FetchPlan {
+    pub number_of_stations: usize,
+
+    /// Stations whose details don't list any route groups.
+    /// They still get a routes-on-station request, but most likely no timetable request.
+    pub stations_without_route_groups: usize,
+
+    /// Route group sets of the stations, the most common ones first.
+    pub route_group_sets: Vec<RouteGroupSet>,
+
+    /// One station details request and one all-routes request.
+    pub listing_requests: usize,
+
+    /// One routes-on-station request per station.
+    pub routes_on_station_requests: usize,
+
+    /// Timetable requests, including the extra ones for stations whose
+    /// route groups are split into several requests.
+    pub timetable_requests: usize,
+
+    /// Stations-on-route requests, estimated as two trips (directions) per distinct route.
+    pub estimated_stations_on_route_requests: usize,
+}
+
+impl FetchPlan {
+    pub fn for_stations(
+        stations: &[StationDetails],
+        max_route_groups_per_timetable_request: Option<usize>,
+    ) -> Self {
+        let mut stations_per_route_group_set: BTreeMap<BTreeSet<BaseBusRoute>, usize> =
+            BTreeMap::new();
+        let mut distinct_routes: BTreeSet<String> = BTreeSet::new();
+        let mut stations_without_route_groups = 0;
+        let mut timetable_requests = 0;
+
+        for station in stations {
+            let route_groups = station
+                .routes_on_station
+                .iter()
+                .map(BusRoute::to_base_route)
+                .collect::<BTreeSet<_>>();
+
+            distinct_routes.extend(station.routes_on_station.iter().map(BusRoute::to_string));
+
+            if route_groups.is_empty() {
+                stations_without_route_groups += 1;
+                continue;
+            }
+
+            timetable_requests += match max_route_groups_per_timetable_request {
+                Some(max_route_groups) if max_route_groups > 0 => {
+                    (route_groups.len() + max_route_groups - 1) / max_route_groups
+                }
+                _ => 1,
+            };
+
+            *stations_per_route_group_set
+                .entry(route_groups)
+                .or_default() += 1;
+        }
+
+        let mut route_group_sets = stations_per_route_group_set
+            .into_iter()
+            .map(
+                |(route_groups, number_of_stations)| RouteGroupSet {
+                    route_groups: route_groups.into_iter().collect(),
+                    number_of_stations,
+                },
+            )
+            .collect::<Vec<_>>();
+        // Stable sort, so sets with the same number of stations stay ordered by their route groups.
+        route_group_sets.sort_by_key(|route_group_set| Reverse(route_group_set.number_of_stations));
+
+        Self {
+            number_of_stations: stations.len(),
+            stations_without_route_groups,
+            route_group_sets,
+            listing_requests: 2,
+            routes_on_station_requests: stations.len(),
+            timetable_requests,
+            estimated_stations_on_route_requests: distinct_routes.len() * 2,
+        }
+    }
+
+    pub fn total_requests(&self) -> usize {
+        self.listing_requests
+            + self.routes_on_station_requests
+            + self.timetable_requests
+            + self.estimated_stations_on_route_requests
+    }
+
+    /// Estimates how long the snapshot will take if requests are made one after another
+    /// and each takes `average_request_duration` (retries and warmup pacing are not included).
+    pub fn estimated_duration(&self, average_request_duration: Duration) -> Duration {
+        average_request_duration
+            .saturating_mul(u32::try_from(self.total_requests()).unwrap_or(u32::MAX))
+    }
+}
+
+impl Display for FetchPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} stations ({} without route groups) in {} distinct route group sets.",
+            self.number_of_stations,
+            self.stations_without_route_groups,
+            self.route_group_sets.len()
+        )?;
+
+        writeln!(f, "Most common route group sets:")?;
+        for route_group_set in self
+            .route_group_sets
+            .iter()
+            .take(DISPLAYED_ROUTE_GROUP_SETS)
+        {
+            let route_groups = route_group_set
+                .route_groups
+                .iter()
+                .map(|route_group| route_group.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            writeln!(
+                f,
+                "  {:>4} stations: {}",
+                route_group_set.number_of_stations, route_groups
+            )?;
+        }
+
+        writeln!(f, "Requests:")?;
+        writeln!(
+            f,
+            "  {:>6} station details and all routes",
+            self.listing_requests
+        )?;
+        writeln!(
+            f,
+            "  {:>6} routes on station",
+            self.routes_on_station_requests
+        )?;
+        writeln!(f, "  {:>6} timetables", self.timetable_requests)?;
+        writeln!(
+            f,
+            "  {:>6} stations on route (estimated)",
+            self.estimated_stations_on_route_requests
+        )?;
+        write!(f, "  {:>6} in total", self.total_requests())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_fixtures::station;
+
+    #[test]
+    fn plan_groups_stations_and_counts_batched_requests() {
+        let stations = vec![
+            station("600011", "STATION", &["2", "3G", "11"]),
+            station("600012", "STATION", &["3", "2", "11B"]),
+            station("600013", "STATION", &["6"]),
+            station("600014", "STATION", &[]),
+        ];
+
+        let plan = FetchPlan::for_stations(&stations, Some(2));
+
+        assert_eq!(plan.stations_without_route_groups, 1);
+        assert_eq!(
+            plan.route_group_sets[0],
+            RouteGroupSet {
+                route_groups: vec![
+                    BaseBusRoute::new_from_number(2),
+                    BaseBusRoute::new_from_number(3),
+                    BaseBusRoute::new_from_number(11),
+                ],
+                number_of_stations: 2,
+            }
+        );
+        assert_eq!(plan.route_group_sets.len(), 2);
+
+        // Two requests for each of the three-group stations, one for the single-group station.
+        assert_eq!(plan.timetable_requests, 5);
+        assert_eq!(plan.routes_on_station_requests, 4);
+        // Routes 2, 3G, 11, 3, 11B and 6, two directions each.
+        assert_eq!(plan.estimated_stations_on_route_requests, 12);
+        assert_eq!(plan.total_requests(), 2 + 4 + 5 + 12);
+    }
+}
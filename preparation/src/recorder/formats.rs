@@ -1,48 +1,284 @@
-use chrono::{DateTime, Utc};
+use std::{
+    io::{self, BufRead, Read},
+    path::Path,
+};
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use miette::Diagnostic;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, TimestampSecondsWithFrac};
+use thiserror::Error;
+use uuid::Uuid;
 
-use crate::api::{
-    routes::RouteDetails,
-    routes_on_station::TripOnStation,
-    station_details::StationDetails,
-    stations_on_route::StationOnRoute,
-    timetable::{RouteGroupTimetable, TripTimetable},
-    GeographicalLocation,
-    StationCode,
+use crate::{
+    api::{
+        arrivals_on_route::StationArrivalDetails,
+        routes::RouteDetails,
+        routes_on_station::TripOnStation,
+        station_details::StationDetails,
+        stations_on_route::StationOnRoute,
+        timetable::{RouteGroupTimetable, TripTimetable},
+        GeographicalLocation,
+        StationCode,
+    },
+    calendar::ServiceDayType,
+    recorder::spatial_summary::StationSpatialSummary,
+    storage::read_snapshot_contents,
 };
 
 
+/// Reference to an earlier snapshot (in the same storage directory)
+/// whose station and route metadata was reused.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReusedSnapshotReference {
+    /// Example: `station-details_2023-11-06_12-00-00.000+UTC.json`
+    pub file_name: String,
+
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub captured_at: DateTime<Utc>,
+}
+
+/// Phases of a full capture that were not redone when the capture was retried after a failure,
+/// but reused from the checkpoint of an earlier attempt of the same run.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReusedCapturePhases {
+    /// Example: `["station-details", "stations"]`.
+    pub phases: Vec<String>,
+
+    /// When the reused phases finished.
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub checkpointed_at: DateTime<Utc>,
+}
+
+
+/// A problem that was detected while capturing a snapshot,
+/// but did not prevent it from being saved.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotWarning {
+    pub kind: SnapshotWarningKind,
+
+    /// Human-readable description of the problem.
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnapshotWarningKind {
+    /// The LPP API did not return any sub-routes, so routes were
+    /// matched with their timetables only by their base route number.
+    SubroutesMissing,
+
+    /// The LPP API returned some stations more than once; the duplicates were dropped.
+    DuplicateStationsDropped,
+
+    /// The LPP API returned stations with malformed station codes; depending on
+    /// configuration, these were either dropped or kept as-is.
+    InvalidStationCodes,
+
+    /// Some stations could not be fetched, even when requested again at the end of the snapshot,
+    /// so they are missing from it.
+    StationsFailed,
+
+    /// Some of the configured critical hub stations are missing
+    /// or don't have timetables for all of their route groups.
+    HubTimetablesIncomplete,
+
+    /// Not all expected stations and routes were captured
+    /// (see the snapshot's `status`).
+    IncompleteCapture,
+
+    /// The snapshot deadline passed before all stations and routes were requested,
+    /// so the snapshot was saved with what it had captured so far.
+    DeadlineExceeded,
+
+    /// The headsigns of some trips don't match their last station,
+    /// so their `destination_station_code` is missing.
+    UnresolvedHeadsigns,
+}
+
+/// Whether a snapshot captured all expected stations and routes,
+/// as judged by the configured acceptance policy.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnapshotStatus {
+    /// All expected stations and routes were captured.
+    Complete,
+
+    /// Some stations or routes are missing, but not more than the acceptance policy allows.
+    Partial,
+
+    /// Too many stations or routes are missing; the snapshot was saved
+    /// into the `rejected` directory instead of alongside the other snapshots.
+    Rejected,
+}
+
+/// Describes the service day of a snapshot, e.g. `2023-11-06 (Weekday)`.
+/// Snapshots recorded before the service day was recorded have an unknown service day.
+pub fn describe_service_day(
+    service_date: Option<NaiveDate>,
+    service_day_type: Option<ServiceDayType>,
+) -> String {
+    match (service_date, service_day_type) {
+        (Some(service_date), Some(service_day_type)) => {
+            format!("{} ({:?})", service_date, service_day_type)
+        }
+        (Some(service_date), None) => service_date.to_string(),
+        (None, _) => String::from("unknown service day"),
+    }
+}
+
+
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AllStationsSnapshot {
+    /// When the snapshot was saved. Its data was captured between
+    /// `capture_started_at` and `capture_finished_at`.
     #[serde_as(as = "TimestampSecondsWithFrac<String>")]
     pub captured_at: DateTime<Utc>,
+
+    /// When the capture of this snapshot's data started. Missing in snapshots
+    /// recorded before capture start and finish times were recorded.
+    #[serde_as(as = "Option<TimestampSecondsWithFrac<String>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture_started_at: Option<DateTime<Utc>>,
+
+    /// When the last of this snapshot's data was captured (see `capture_started_at`).
+    #[serde_as(as = "Option<TimestampSecondsWithFrac<String>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture_finished_at: Option<DateTime<Utc>>,
+
+    /// Identifier of the snapshot run that captured this snapshot
+    /// (also attached to all of the run's log lines).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<Uuid>,
+
+    /// The (local) date whose timetables this snapshot contains.
+    /// Missing in snapshots recorded before the service day was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_date: Option<NaiveDate>,
+
+    /// Type of service day the timetables in this snapshot are for (see `service_date`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_day_type: Option<ServiceDayType>,
+
+    /// If set, only the timetables in this snapshot are fresh; the station
+    /// and route metadata was reused from the referenced earlier snapshot.
+    #[serde(default)]
+    pub metadata_reused_from: Option<ReusedSnapshotReference>,
+
+    /// If set, some phases of the capture were reused from an earlier, failed attempt
+    /// instead of being redone (see `lpp.recording.snapshot_retries`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phases_reused_from_checkpoint: Option<ReusedCapturePhases>,
+
+    /// Whether this snapshot captured all expected stations. Full captures save the station
+    /// snapshot before fetching the routes, so only timetables-only captures (and snapshots
+    /// recorded before that) also account for the routes here. Missing in snapshots
+    /// recorded before snapshots were checked against an acceptance policy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<SnapshotStatus>,
+
+    /// Problems detected while capturing this snapshot.
+    /// Consumers should check these before trusting the data.
+    #[serde(default)]
+    pub warnings: Vec<SnapshotWarning>,
+
+    /// Bounding box, centroid and per-tile counts of the stations, computed at capture time.
+    /// Missing in snapshots recorded before it was computed, and in snapshots without stations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spatial_summary: Option<StationSpatialSummary>,
+
     pub station_details: Vec<StationDetailsWithBusesAndTimetables>,
 }
 
 impl AllStationsSnapshot {
     pub fn new(
         timestamp: DateTime<Utc>,
+        run_id: Option<Uuid>,
+        service_date: NaiveDate,
+        service_day_type: ServiceDayType,
+        metadata_reused_from: Option<ReusedSnapshotReference>,
+        warnings: Vec<SnapshotWarning>,
         station_details: Vec<StationDetailsWithBusesAndTimetables>,
     ) -> Self {
         Self {
             captured_at: timestamp,
+            capture_started_at: None,
+            capture_finished_at: None,
+            run_id,
+            service_date: Some(service_date),
+            service_day_type: Some(service_day_type),
+            metadata_reused_from,
+            phases_reused_from_checkpoint: None,
+            status: None,
+            warnings,
+            spatial_summary: StationSpatialSummary::for_stations(&station_details),
             station_details,
         }
     }
+
+    /// Records when the capture of this snapshot's data started and finished.
+    pub fn with_capture_window(
+        mut self,
+        capture_started_at: DateTime<Utc>,
+        capture_finished_at: DateTime<Utc>,
+    ) -> Self {
+        self.capture_started_at = Some(capture_started_at);
+        self.capture_finished_at = Some(capture_finished_at);
+        self
+    }
+
+    /// Records whether this snapshot was accepted (see [`SnapshotStatus`]).
+    pub fn with_status(mut self, status: SnapshotStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Records which capture phases were reused from a checkpoint, if any.
+    pub fn with_reused_phases(mut self, reused_phases: Option<ReusedCapturePhases>) -> Self {
+        self.phases_reused_from_checkpoint = reused_phases;
+        self
+    }
+
+    /// The (local) date whose timetables this snapshot contains. Snapshots recorded before
+    /// the service date was recorded fall back to the local date they were captured on,
+    /// which is what the service date was detected from.
+    pub fn service_date_or_capture_date(&self) -> NaiveDate {
+        self.service_date
+            .unwrap_or_else(|| self.captured_at.with_timezone(&Local).date_naive())
+    }
+
+    /// Describes the service day of this snapshot, see [`describe_service_day`].
+    pub fn service_day_description(&self) -> String {
+        describe_service_day(self.service_date, self.service_day_type)
+    }
 }
 
 
 
+#[serde_as]
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct StationDetailsWithBusesAndTimetables {
+    /// When the trips and timetables of this station were requested. Missing in
+    /// snapshots recorded before per-station capture times were recorded.
+    #[serde_as(as = "Option<TimestampSecondsWithFrac<String>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captured_at: Option<DateTime<Utc>>,
+
     /// Unique bus station identifier
     /// (useful in other station-related requests).
     ///
     /// Example: `201011`.
     pub station_code: StationCode,
 
+    /// Recorder-assigned ID of this station, which stays the same even if LPP
+    /// reuses `station_code` for another station (see `entity-registry.json`).
+    /// Missing in snapshots recorded before stable IDs were assigned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stable_id: Option<Uuid>,
+
     /// Unique *internal* station identifier.
     /// Unused in other parts of the API.
     ///
@@ -57,6 +293,11 @@ pub struct StationDetailsWithBusesAndTimetables {
     /// Geographical location of the bus station.
     pub location: GeographicalLocation,
 
+    /// City district the station lies in, if district boundaries are configured
+    /// (see `lpp.recording.districts`) and the station lies in one of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub district: Option<String>,
+
     /// A list of all trips that stop on this bus station.
     pub trips_on_station: Vec<TripOnStation>,
 
@@ -66,15 +307,19 @@ pub struct StationDetailsWithBusesAndTimetables {
 impl StationDetailsWithBusesAndTimetables {
     #[inline]
     pub fn from_station_and_trips(
+        captured_at: DateTime<Utc>,
         station: StationDetails,
         trips: Vec<TripOnStation>,
         timetables: Vec<RouteGroupTimetable>,
     ) -> Self {
         Self {
+            captured_at: Some(captured_at),
             station_code: station.station_code,
+            stable_id: None,
             internal_station_id: station.internal_station_id,
             name: station.name,
             location: station.location,
+            district: None,
             trips_on_station: trips,
             timetables,
         }
@@ -86,20 +331,120 @@ impl StationDetailsWithBusesAndTimetables {
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AllRoutesSnapshot {
+    /// When the snapshot was saved. Its data was captured between
+    /// `capture_started_at` and `capture_finished_at`.
     #[serde_as(as = "TimestampSecondsWithFrac<String>")]
     pub captured_at: DateTime<Utc>,
 
+    /// When the capture of this snapshot's data started. Missing in snapshots
+    /// recorded before capture start and finish times were recorded.
+    #[serde_as(as = "Option<TimestampSecondsWithFrac<String>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture_started_at: Option<DateTime<Utc>>,
+
+    /// When the last of this snapshot's data was captured (see `capture_started_at`).
+    #[serde_as(as = "Option<TimestampSecondsWithFrac<String>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture_finished_at: Option<DateTime<Utc>>,
+
+    /// Identifier of the snapshot run that captured this snapshot
+    /// (also attached to all of the run's log lines).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<Uuid>,
+
+    /// The (local) date whose timetables this snapshot contains.
+    /// Missing in snapshots recorded before the service day was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_date: Option<NaiveDate>,
+
+    /// Type of service day the timetables in this snapshot are for (see `service_date`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_day_type: Option<ServiceDayType>,
+
+    /// If set, only the timetables in this snapshot are fresh; the station
+    /// and route metadata was reused from the referenced earlier snapshot.
+    #[serde(default)]
+    pub metadata_reused_from: Option<ReusedSnapshotReference>,
+
+    /// If set, some phases of the capture were reused from an earlier, failed attempt
+    /// instead of being redone (see `lpp.recording.snapshot_retries`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phases_reused_from_checkpoint: Option<ReusedCapturePhases>,
+
+    /// Whether this snapshot captured all expected stations and routes. Missing in
+    /// snapshots recorded before snapshots were checked against an acceptance policy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<SnapshotStatus>,
+
+    /// Problems detected while capturing this snapshot.
+    /// Consumers should check these before trusting the data.
+    #[serde(default)]
+    pub warnings: Vec<SnapshotWarning>,
+
     pub routes: Vec<TripWithStationsAndTimetables>,
 }
 
 impl AllRoutesSnapshot {
     #[inline]
-    pub fn new(captured_at: DateTime<Utc>, routes: Vec<TripWithStationsAndTimetables>) -> Self {
+    pub fn new(
+        captured_at: DateTime<Utc>,
+        run_id: Option<Uuid>,
+        service_date: NaiveDate,
+        service_day_type: ServiceDayType,
+        metadata_reused_from: Option<ReusedSnapshotReference>,
+        warnings: Vec<SnapshotWarning>,
+        routes: Vec<TripWithStationsAndTimetables>,
+    ) -> Self {
         Self {
             captured_at,
+            capture_started_at: None,
+            capture_finished_at: None,
+            run_id,
+            service_date: Some(service_date),
+            service_day_type: Some(service_day_type),
+            metadata_reused_from,
+            phases_reused_from_checkpoint: None,
+            status: None,
+            warnings,
             routes,
         }
     }
+
+    /// Records when the capture of this snapshot's data started and finished.
+    pub fn with_capture_window(
+        mut self,
+        capture_started_at: DateTime<Utc>,
+        capture_finished_at: DateTime<Utc>,
+    ) -> Self {
+        self.capture_started_at = Some(capture_started_at);
+        self.capture_finished_at = Some(capture_finished_at);
+        self
+    }
+
+    /// Records whether this snapshot was accepted (see [`SnapshotStatus`]).
+    pub fn with_status(mut self, status: SnapshotStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Records which capture phases were reused from a checkpoint, if any.
+    pub fn with_reused_phases(mut self, reused_phases: Option<ReusedCapturePhases>) -> Self {
+        self.phases_reused_from_checkpoint = reused_phases;
+        self
+    }
+
+    /// The (local) date whose timetables this snapshot contains,
+    /// see [`AllStationsSnapshot::service_date_or_capture_date`].
+    #[cfg_attr(not(feature = "hdf5-export"), allow(dead_code))]
+    pub fn service_date_or_capture_date(&self) -> NaiveDate {
+        self.service_date
+            .unwrap_or_else(|| self.captured_at.with_timezone(&Local).date_naive())
+    }
+
+    /// Describes the service day of this snapshot, see [`describe_service_day`].
+    pub fn service_day_description(&self) -> String {
+        describe_service_day(self.service_date, self.service_day_type)
+    }
 }
 
 
@@ -110,11 +455,483 @@ pub struct TripWithStationsAndTimetables {
     pub captured_at: DateTime<Utc>,
 
     pub route_details: RouteDetails,
+
+    /// Recorder-assigned IDs of this trip's route (`route_details.route_id`) and of the trip
+    /// itself (`route_details.trip_id`), which stay the same even if LPP reuses its IDs
+    /// (see `entity-registry.json`). Missing in snapshots recorded before stable IDs were assigned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stable_route_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stable_trip_id: Option<Uuid>,
+
     pub stations_on_route_with_timetables: Vec<TripStationWithTimetable>,
+
+    /// Station code of the trip's destination: its last station, if the station's name
+    /// matches the trip's headsign (e.g. `VIŽMARJE` in `MESTNI LOG - VIŽMARJE`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination_station_code: Option<StationCode>,
+
+    /// How complete the data for this trip is. Missing in snapshots
+    /// recorded before completeness scoring was introduced.
+    #[serde(default)]
+    pub completeness: Option<TripDataCompleteness>,
+}
+
+/// Completeness metadata of a single trip, which lets consumers
+/// filter out trips with low-quality data.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TripDataCompleteness {
+    /// Number of stations on the trip, as reported by the LPP API.
+    pub total_stations: usize,
+
+    /// Number of stations that have a timetable (including interpolated ones).
+    pub stations_with_timetables: usize,
+
+    /// Number of stations whose timetable was interpolated.
+    pub stations_with_interpolated_timetables: usize,
+
+    /// Fraction of stations that have a timetable (from `0.0` to `1.0`).
+    pub station_timetable_fraction: f64,
+
+    /// Whether the route details include the GeoJSON shape of the route.
+    pub has_route_shape: bool,
+
+    /// Number of timetable entries at the first station with a non-interpolated timetable.
+    pub timetable_entry_count: usize,
+
+    /// Number of timetable entries we would expect at the same station if buses ran
+    /// at its median headway for its entire service span. `None` if the station
+    /// has fewer than two timetable entries.
+    pub expected_timetable_entry_count: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TripStationWithTimetable {
     pub station: StationOnRoute,
     pub timetable: TripTimetable,
+
+    /// Whether `timetable` was not provided by the LPP API, but estimated
+    /// from the timetables of the neighbouring stations on this trip.
+    #[serde(default)]
+    pub timetable_is_interpolated: bool,
+}
+
+
+/// Live arrivals on every station of a single trip, polled by the arrival recorder
+/// (saved into `arrival-snapshots/<route>/` in the storage root).
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TripArrivalsSnapshot {
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub captured_at: DateTime<Utc>,
+
+    /// Identifier of the trip's polling round (also attached to the round's log lines).
+    pub polling_round_id: Uuid,
+
+    pub trip: RouteDetails,
+
+    /// Stations of the trip, with the buses arriving to each of them.
+    pub stations: Vec<StationArrivalDetails>,
+}
+
+
+
+/*
+ * Snapshot loading
+ */
+
+/// A station or route snapshot, as loaded by [`load_snapshot`].
+#[derive(Debug, Clone)]
+pub enum Snapshot {
+    Stations(AllStationsSnapshot),
+    Routes(AllRoutesSnapshot),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SnapshotCompression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SnapshotSerialization {
+    Json,
+
+    /// MessagePack with named fields (i.e. written with `rmp_serde::to_vec_named`).
+    MessagePack,
+}
+
+/// A snapshot, along with the file format it was detected to be stored in.
+#[derive(Debug, Clone)]
+pub struct LoadedSnapshot {
+    pub compression: SnapshotCompression,
+    pub serialization: SnapshotSerialization,
+    pub snapshot: Snapshot,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+pub enum SnapshotLoadError {
+    #[error("Failed to read snapshot file: {0}")]
+    IoError(#[from] io::Error),
+
+    #[error("Failed to decompress {compression:?} snapshot: {reason}")]
+    DecompressionError {
+        compression: SnapshotCompression,
+        reason: io::Error,
+    },
+
+    #[error("Failed to decode {serialization:?} snapshot: {reason}")]
+    DecodingError {
+        serialization: SnapshotSerialization,
+        reason: String,
+    },
+
+    #[error("Snapshot is neither a station nor a route snapshot.")]
+    UnknownSnapshotKind,
+
+    /// The file was decoded, but doesn't match the current snapshot structure
+    /// (e.g. it was recorded by an older version that didn't record service days).
+    #[error("Snapshot does not match the current format: {reason}")]
+    FormatMismatch { reason: String },
+}
+
+impl SnapshotLoadError {
+    /// Returns `true` if the file itself is damaged (unreadable, truncated, ...),
+    /// as opposed to being intact, but in an unexpected format.
+    pub fn is_corruption(&self) -> bool {
+        matches!(
+            self,
+            SnapshotLoadError::IoError(_)
+                | SnapshotLoadError::DecompressionError { .. }
+                | SnapshotLoadError::DecodingError { .. }
+        )
+    }
+
+    /// Wraps an error that occurred while reading a snapshot with the given compression.
+    pub fn reading(compression: SnapshotCompression, error: io::Error) -> Self {
+        match compression {
+            SnapshotCompression::None => SnapshotLoadError::IoError(error),
+            compression => SnapshotLoadError::DecompressionError {
+                compression,
+                reason: error,
+            },
+        }
+    }
+
+    pub fn decoding<E>(serialization: SnapshotSerialization, error: E) -> Self
+    where
+        E: ToString,
+    {
+        SnapshotLoadError::DecodingError {
+            serialization,
+            reason: error.to_string(),
+        }
+    }
+}
+
+const GZIP_MAGIC_BYTES: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC_BYTES: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+impl SnapshotCompression {
+    /// Detects the compression from the magic bytes at the start of a snapshot.
+    pub fn detect(start: &[u8]) -> Self {
+        if start.starts_with(&GZIP_MAGIC_BYTES) {
+            SnapshotCompression::Gzip
+        } else if start.starts_with(&ZSTD_MAGIC_BYTES) {
+            SnapshotCompression::Zstd
+        } else {
+            SnapshotCompression::None
+        }
+    }
+}
+
+impl SnapshotSerialization {
+    /// Detects the serialization from the first non-whitespace byte of the (decompressed)
+    /// snapshot: JSON snapshots start with `{`, everything else is treated as MessagePack.
+    /// The leading whitespace is consumed from `reader`.
+    pub fn detect<R>(
+        reader: &mut R,
+        compression: SnapshotCompression,
+    ) -> Result<Self, SnapshotLoadError>
+    where
+        R: BufRead,
+    {
+        loop {
+            let buffer = reader
+                .fill_buf()
+                .map_err(|error| SnapshotLoadError::reading(compression, error))?;
+
+            match buffer.iter().position(|byte| !byte.is_ascii_whitespace()) {
+                Some(position) => {
+                    let serialization = match buffer[position] {
+                        b'{' => SnapshotSerialization::Json,
+                        _ => SnapshotSerialization::MessagePack,
+                    };
+
+                    reader.consume(position);
+                    return Ok(serialization);
+                }
+                None if buffer.is_empty() => return Ok(SnapshotSerialization::MessagePack),
+                None => {
+                    let length = buffer.len();
+                    reader.consume(length);
+                }
+            }
+        }
+    }
+}
+
+/// Wraps `reader` in a decoder for the compression detected from its magic bytes.
+pub fn decompressing_reader<'r, R>(
+    mut reader: R,
+) -> Result<(SnapshotCompression, Box<dyn BufRead + 'r>), SnapshotLoadError>
+where
+    R: BufRead + 'r,
+{
+    let compression = SnapshotCompression::detect(reader.fill_buf()?);
+
+    let decompressed_reader: Box<dyn BufRead + 'r> = match compression {
+        SnapshotCompression::None => Box::new(reader),
+        SnapshotCompression::Gzip => Box::new(io::BufReader::new(
+            flate2::bufread::GzDecoder::new(reader),
+        )),
+        SnapshotCompression::Zstd => Box::new(io::BufReader::new(
+            zstd::stream::read::Decoder::with_buffer(reader)
+                .map_err(|error| SnapshotLoadError::reading(compression, error))?,
+        )),
+    };
+
+    Ok((compression, decompressed_reader))
+}
+
+/// Loads a station or route snapshot from a file (or from a pack, see
+/// [`SnapshotPack`](crate::storage::SnapshotPack)).
+///
+/// Compression (none, gzip or zstd) is detected from the file's magic bytes, serialization
+/// (JSON or MessagePack) from the decompressed contents and the kind of snapshot from
+/// its fields, so the file extension doesn't matter.
+pub fn load_snapshot(file_path: &Path) -> Result<LoadedSnapshot, SnapshotLoadError> {
+    let file_contents = read_snapshot_contents(file_path)?;
+    load_snapshot_from_bytes(&file_contents)
+}
+
+pub fn load_snapshot_from_bytes(contents: &[u8]) -> Result<LoadedSnapshot, SnapshotLoadError> {
+    let (compression, mut reader) = decompressing_reader(contents)?;
+
+    let mut decompressed_contents = Vec::new();
+    reader
+        .read_to_end(&mut decompressed_contents)
+        .map_err(|error| SnapshotLoadError::reading(compression, error))?;
+
+    let serialization =
+        SnapshotSerialization::detect(&mut decompressed_contents.as_slice(), compression)?;
+
+    let value = match serialization {
+        SnapshotSerialization::Json => {
+            serde_json::from_slice::<serde_json::Value>(&decompressed_contents)
+                .map_err(|error| SnapshotLoadError::decoding(serialization, error))?
+        }
+        SnapshotSerialization::MessagePack => {
+            rmp_serde::from_slice::<serde_json::Value>(&decompressed_contents)
+                .map_err(|error| SnapshotLoadError::decoding(serialization, error))?
+        }
+    };
+
+    let format_mismatch = |error: serde_json::Error| SnapshotLoadError::FormatMismatch {
+        reason: error.to_string(),
+    };
+
+    let snapshot = if value.get("station_details").is_some() {
+        Snapshot::Stations(serde_json::from_value(value).map_err(format_mismatch)?)
+    } else if value.get("routes").is_some() {
+        Snapshot::Routes(serde_json::from_value(value).map_err(format_mismatch)?)
+    } else {
+        return Err(SnapshotLoadError::UnknownSnapshotKind);
+    };
+
+    Ok(LoadedSnapshot {
+        compression,
+        serialization,
+        snapshot,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn routes_snapshot() -> AllRoutesSnapshot {
+        AllRoutesSnapshot::new(
+            Utc.with_ymd_and_hms(2023, 11, 6, 12, 0, 0).unwrap(),
+            None,
+            NaiveDate::from_ymd_opt(2023, 11, 6).unwrap(),
+            ServiceDayType::Weekday,
+            None,
+            Vec::new(),
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn load_snapshot_in_every_format() {
+        let snapshot = routes_snapshot();
+
+        let json = serde_json::to_vec(&snapshot).unwrap();
+        let message_pack = rmp_serde::to_vec_named(&snapshot).unwrap();
+
+        let mut gzip_encoder =
+            flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip_encoder.write_all(&json).unwrap();
+        let gzipped_json = gzip_encoder.finish().unwrap();
+
+        let zstd_message_pack = zstd::stream::encode_all(message_pack.as_slice(), 0).unwrap();
+
+        for (contents, expected_compression, expected_serialization) in [
+            (
+                json,
+                SnapshotCompression::None,
+                SnapshotSerialization::Json,
+            ),
+            (
+                message_pack,
+                SnapshotCompression::None,
+                SnapshotSerialization::MessagePack,
+            ),
+            (
+                gzipped_json,
+                SnapshotCompression::Gzip,
+                SnapshotSerialization::Json,
+            ),
+            (
+                zstd_message_pack,
+                SnapshotCompression::Zstd,
+                SnapshotSerialization::MessagePack,
+            ),
+        ] {
+            let loaded = load_snapshot_from_bytes(&contents).unwrap();
+
+            assert_eq!(loaded.compression, expected_compression);
+            assert_eq!(loaded.serialization, expected_serialization);
+            let Snapshot::Routes(loaded_snapshot) = loaded.snapshot else {
+                panic!("expected a route snapshot");
+            };
+            assert_eq!(loaded_snapshot.captured_at, snapshot.captured_at);
+        }
+    }
+
+    #[test]
+    fn distinguish_corrupt_and_outdated_snapshots() {
+        let truncated = load_snapshot_from_bytes(br#"{"captured_at":"1.0","rou"#).unwrap_err();
+        assert!(truncated.is_corruption());
+
+        let outdated = load_snapshot_from_bytes(br#"{"captured_at":"1699272000.0","routes":"3G"}"#)
+            .unwrap_err();
+        assert!(matches!(
+            outdated,
+            SnapshotLoadError::FormatMismatch { .. }
+        ));
+        assert!(!outdated.is_corruption());
+    }
+
+    #[test]
+    fn capture_window_round_trips_and_is_optional() {
+        let capture_started_at = Utc.with_ymd_and_hms(2023, 11, 6, 11, 30, 0).unwrap();
+        let capture_finished_at = Utc.with_ymd_and_hms(2023, 11, 6, 11, 55, 0).unwrap();
+        let snapshot =
+            routes_snapshot().with_capture_window(capture_started_at, capture_finished_at);
+
+        let json = serde_json::to_vec(&snapshot).unwrap();
+        let Snapshot::Routes(loaded_snapshot) = load_snapshot_from_bytes(&json).unwrap().snapshot
+        else {
+            panic!("expected a route snapshot");
+        };
+        assert_eq!(
+            loaded_snapshot.capture_started_at,
+            Some(capture_started_at)
+        );
+        assert_eq!(
+            loaded_snapshot.capture_finished_at,
+            Some(capture_finished_at)
+        );
+
+        let Snapshot::Routes(older_snapshot) =
+            load_snapshot_from_bytes(br#"{"captured_at":"1699272000.0","routes":[]}"#)
+                .unwrap()
+                .snapshot
+        else {
+            panic!("expected a route snapshot");
+        };
+        assert_eq!(older_snapshot.capture_started_at, None);
+    }
+
+    /// A station snapshot as saved by the first version of the recorder.
+    const BASELINE_STATION_SNAPSHOT: &str = r#"{"captured_at":"1699272000","station_details":[{
+        "station_code":"600011","internal_station_id":3307,"name":"KONGRESNI TRG",
+        "location":{"latitude":46.05,"longitude":14.5},
+        "trips_on_station":[{"route_id":"A1","trip_id":"T1","route":"3G",
+            "short_trip_name":"BEŽIGRAD","trip_name":"BEŽIGRAD - GROSUPLJE","ends_in_garage":false}],
+        "timetables":[{"route_group_name":3,"trip_timetables":[{"route":"3G",
+            "trip_name":"BEŽIGRAD - GROSUPLJE","short_trip_name":null,"ends_in_garage":false,
+            "timetable":[{"hour":5,"minute":12}],
+            "stations":[{"station_code":"600011","name":"KONGRESNI TRG","stop_number":1}]}]}]}]}"#;
+
+    /// A route snapshot as saved by the first version of the recorder.
+    const BASELINE_ROUTE_SNAPSHOT: &str = r#"{"captured_at":"1699272000","routes":[{
+        "captured_at":"1699272000",
+        "route_details":{"route_id":"A1","trip_id":"T1","internal_trip_id":42,"route":"3G",
+            "name":"BEŽIGRAD - GROSUPLJE","short_name":null,"route_shape":null},
+        "stations_on_route_with_timetables":[{
+            "station":{"station_code":"600011","internal_station_id":3307,"name":"KONGRESNI TRG",
+                "location":{"latitude":46.05,"longitude":14.5},"stop_number":1},
+            "timetable":{"route":"3G","trip_name":"BEŽIGRAD - GROSUPLJE","short_trip_name":null,
+                "ends_in_garage":false,"timetable":[{"hour":5,"minute":12}],
+                "stations":[{"station_code":"600011","name":"KONGRESNI TRG","stop_number":1}]}}]}]}"#;
+
+    #[test]
+    fn load_snapshots_recorded_by_the_first_version() {
+        let Snapshot::Stations(station_snapshot) =
+            load_snapshot_from_bytes(BASELINE_STATION_SNAPSHOT.as_bytes())
+                .unwrap()
+                .snapshot
+        else {
+            panic!("expected a station snapshot");
+        };
+        assert_eq!(station_snapshot.station_details.len(), 1);
+        assert_eq!(station_snapshot.service_date, None);
+        assert_eq!(station_snapshot.service_day_type, None);
+        assert_eq!(
+            station_snapshot.service_day_description(),
+            "unknown service day"
+        );
+        assert_eq!(
+            station_snapshot.service_date_or_capture_date(),
+            station_snapshot
+                .captured_at
+                .with_timezone(&Local)
+                .date_naive()
+        );
+
+        let Snapshot::Routes(route_snapshot) =
+            load_snapshot_from_bytes(BASELINE_ROUTE_SNAPSHOT.as_bytes())
+                .unwrap()
+                .snapshot
+        else {
+            panic!("expected a route snapshot");
+        };
+        assert_eq!(route_snapshot.routes.len(), 1);
+        assert_eq!(route_snapshot.service_date, None);
+        assert_eq!(
+            route_snapshot.routes[0].stations_on_route_with_timetables[0]
+                .timetable
+                .timetable
+                .len(),
+            1
+        );
+    }
 }
@@ -0,0 +1,202 @@
+use std::collections::{BTreeSet, HashSet};
+
+use tracing::error;
+
+use super::{
+    formats::{SnapshotWarning, SnapshotWarningKind, StationDetailsWithBusesAndTimetables},
+    route_groups_on_station,
+};
+use crate::api::{timetable::RouteGroupTimetable, BaseBusRoute, StationCode};
+
+
+/// A critical hub station whose timetables were not fully captured.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum HubCoverageIssue {
+    /// The hub is missing from the snapshot entirely.
+    StationMissing { station_code: StationCode },
+
+    /// The hub was captured, but some of its route groups have no timetables.
+    RouteGroupsMissing {
+        station_code: StationCode,
+        station_name: String,
+        missing_route_groups: Vec<BaseBusRoute>,
+        total_route_groups: usize,
+    },
+}
+
+impl HubCoverageIssue {
+    pub fn describe(&self) -> String {
+        match self {
+            HubCoverageIssue::StationMissing { station_code } => {
+                format!("{} is missing", station_code)
+            }
+            HubCoverageIssue::RouteGroupsMissing {
+                station_code,
+                station_name,
+                missing_route_groups,
+                total_route_groups,
+            } => format!(
+                "{} ({}) has no timetables for {} of {} route groups: {}",
+                station_name,
+                station_code,
+                missing_route_groups.len(),
+                total_route_groups,
+                missing_route_groups
+                    .iter()
+                    .map(|route_group| route_group.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+
+/// Checks that each of the critical hub stations was captured with timetables
+/// for all of the route groups that stop there.
+pub fn check_hub_coverage(
+    critical_hub_stations: &[StationCode],
+    stations: &[StationDetailsWithBusesAndTimetables],
+) -> Vec<HubCoverageIssue> {
+    critical_hub_stations
+        .iter()
+        .filter_map(|hub_station_code| {
+            let Some(station) = stations
+                .iter()
+                .find(|station| &station.station_code == hub_station_code)
+            else {
+                return Some(HubCoverageIssue::StationMissing {
+                    station_code: hub_station_code.clone(),
+                });
+            };
+
+            let route_groups = route_groups_on_station(&station.trips_on_station);
+            let missing_route_groups = missing_route_groups(&route_groups, &station.timetables);
+
+            if missing_route_groups.is_empty() {
+                None
+            } else {
+                Some(HubCoverageIssue::RouteGroupsMissing {
+                    station_code: station.station_code.clone(),
+                    station_name: station.name.clone(),
+                    missing_route_groups,
+                    total_route_groups: route_groups.len(),
+                })
+            }
+        })
+        .collect()
+}
+
+/// Logs each hub coverage issue as an error and returns a snapshot warning describing them.
+pub fn report_hub_coverage(
+    critical_hub_stations: &[StationCode],
+    stations: &[StationDetailsWithBusesAndTimetables],
+) -> Option<SnapshotWarning> {
+    let issues = check_hub_coverage(critical_hub_stations, stations);
+    if issues.is_empty() {
+        return None;
+    }
+
+    for issue in &issues {
+        error!(
+            issue = %issue.describe(),
+            "Critical hub station does not have all of its timetables."
+        );
+    }
+
+    Some(SnapshotWarning {
+        kind: SnapshotWarningKind::HubTimetablesIncomplete,
+        message: format!(
+            "{} of {} critical hub station(s) are incomplete: {}.",
+            issues.len(),
+            critical_hub_stations.len(),
+            issues
+                .iter()
+                .map(HubCoverageIssue::describe)
+                .collect::<Vec<_>>()
+                .join("; ")
+        ),
+    })
+}
+
+/// Route groups without any trip timetables, in ascending order.
+fn missing_route_groups(
+    route_groups: &HashSet<BaseBusRoute>,
+    timetables: &[RouteGroupTimetable],
+) -> Vec<BaseBusRoute> {
+    route_groups
+        .iter()
+        .filter(|route_group| {
+            !timetables.iter().any(|timetable| {
+                &timetable.route_group_name == *route_group && !timetable.trip_timetables.is_empty()
+            })
+        })
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{timetable::TripTimetable, BusRoute};
+
+    fn route_group_timetable(route_group: u32, trip_timetables: usize) -> RouteGroupTimetable {
+        RouteGroupTimetable {
+            route_group_name: BaseBusRoute::new_from_number(route_group),
+            trip_timetables: vec![
+                TripTimetable {
+                    route: BusRoute::from_route_name(route_group.to_string()).unwrap(),
+                    trip_name: String::from("KOLODVOR - STANEŽIČE"),
+                    short_trip_name: None,
+                    ends_in_garage: false,
+                    timetable: Vec::new(),
+                    stations: Vec::new(),
+                };
+                trip_timetables
+            ],
+            skipped_trips: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn find_route_groups_without_timetables() {
+        let route_groups = [2, 3, 6, 18]
+            .into_iter()
+            .map(BaseBusRoute::new_from_number)
+            .collect::<HashSet<_>>();
+
+        let timetables = vec![
+            route_group_timetable(2, 1),
+            route_group_timetable(3, 0),
+            route_group_timetable(18, 2),
+        ];
+
+        assert_eq!(
+            missing_route_groups(&route_groups, &timetables),
+            vec![
+                BaseBusRoute::new_from_number(3),
+                BaseBusRoute::new_from_number(6),
+            ]
+        );
+        assert!(missing_route_groups(
+            &route_groups,
+            &[
+                route_group_timetable(2, 1),
+                route_group_timetable(3, 1),
+                route_group_timetable(6, 1),
+                route_group_timetable(18, 1),
+            ]
+        )
+        .is_empty());
+
+        assert_eq!(
+            check_hub_coverage(&[StationCode::new("600011")], &[]),
+            vec![HubCoverageIssue::StationMissing {
+                station_code: StationCode::new("600011")
+            }]
+        );
+    }
+}
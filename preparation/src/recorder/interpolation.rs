@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+
+use crate::{
+    api::{
+        routes::RouteGeoJsonShape,
+        stations_on_route::StationOnRoute,
+        timetable::{TimetableEntry, TripTimetable},
+        GeographicalLocation,
+        StationCode,
+    },
+    recorder::formats::TripStationWithTimetable,
+};
+
+
+/// Result of joining the stations on a trip with their timetables.
+pub struct ResolvedTripStations {
+    /// Stations with either their own or an interpolated timetable, in stop order.
+    pub stations_with_timetables: Vec<TripStationWithTimetable>,
+
+    /// Stations that had no timetable and for which one could not be interpolated
+    /// (e.g. they are the first or last stations on the trip, or their neighbours'
+    /// timetables don't line up).
+    pub unresolved_stations: Vec<StationOnRoute>,
+}
+
+impl ResolvedTripStations {
+    pub fn number_of_interpolated_stations(&self) -> usize {
+        self.stations_with_timetables
+            .iter()
+            .filter(|station| station.timetable_is_interpolated)
+            .count()
+    }
+}
+
+
+/// Joins the stations on a trip with their timetables. For stations that
+/// don't have a timetable, we estimate one from the nearest previous and next stations
+/// that do, proportionally to the distance travelled between them.
+///
+/// Distances are measured along `route_shape` if it is available, otherwise
+/// as straight-line distances between consecutive stations.
+pub fn resolve_trip_station_timetables(
+    mut stations_on_route: Vec<StationOnRoute>,
+    station_timetables: &HashMap<StationCode, TripTimetable>,
+    route_shape: Option<&RouteGeoJsonShape>,
+) -> ResolvedTripStations {
+    stations_on_route.sort_by_key(|station| station.stop_number);
+
+    let distances_along_route = distances_along_route(&stations_on_route, route_shape);
+
+    let known_timetable_indices: Vec<usize> = stations_on_route
+        .iter()
+        .enumerate()
+        .filter(|(_, station)| station_timetables.contains_key(&station.station_code))
+        .map(|(index, _)| index)
+        .collect();
+
+
+    let mut stations_with_timetables = Vec::with_capacity(stations_on_route.len());
+    let mut unresolved_stations = Vec::new();
+
+    for (station_index, station) in stations_on_route.iter().enumerate() {
+        if let Some(timetable) = station_timetables.get(&station.station_code) {
+            stations_with_timetables.push(TripStationWithTimetable {
+                station: station.clone(),
+                timetable: timetable.clone(),
+                timetable_is_interpolated: false,
+            });
+            continue;
+        }
+
+        let previous_known_index = known_timetable_indices
+            .iter()
+            .rev()
+            .find(|index| **index < station_index);
+        let next_known_index = known_timetable_indices
+            .iter()
+            .find(|index| **index > station_index);
+
+        let (Some(&previous_index), Some(&next_index)) = (previous_known_index, next_known_index)
+        else {
+            unresolved_stations.push(station.clone());
+            continue;
+        };
+
+        let previous_distance = distances_along_route[previous_index];
+        let next_distance = distances_along_route[next_index];
+
+        let fraction_of_segment = if next_distance > previous_distance {
+            (distances_along_route[station_index] - previous_distance)
+                / (next_distance - previous_distance)
+        } else {
+            // Fall back to the stop count if the distances are degenerate.
+            (station_index - previous_index) as f64 / (next_index - previous_index) as f64
+        };
+
+        // PANIC SAFETY: `known_timetable_indices` only contains stations with timetables.
+        let previous_timetable =
+            &station_timetables[&stations_on_route[previous_index].station_code];
+        let next_timetable = &station_timetables[&stations_on_route[next_index].station_code];
+
+        match interpolate_timetable_entries(
+            &previous_timetable.timetable,
+            &next_timetable.timetable,
+            fraction_of_segment.clamp(0.0, 1.0),
+        ) {
+            Some(interpolated_entries) => {
+                let mut interpolated_timetable = previous_timetable.clone();
+                interpolated_timetable.timetable = interpolated_entries;
+
+                stations_with_timetables.push(TripStationWithTimetable {
+                    station: station.clone(),
+                    timetable: interpolated_timetable,
+                    timetable_is_interpolated: true,
+                });
+            }
+            None => unresolved_stations.push(station.clone()),
+        }
+    }
+
+    ResolvedTripStations {
+        stations_with_timetables,
+        unresolved_stations,
+    }
+}
+
+
+/// Interpolates between two timetables of consecutive stations on the same trip.
+/// The `n`-th entry of both timetables is considered to be the same bus.
+///
+/// Returns `None` if the timetables can't be paired up (they have a different number
+/// of entries or a bus would arrive to the next station before the previous one).
+fn interpolate_timetable_entries(
+    previous_station_entries: &[TimetableEntry],
+    next_station_entries: &[TimetableEntry],
+    fraction_of_segment: f64,
+) -> Option<Vec<TimetableEntry>> {
+    if previous_station_entries.is_empty()
+        || previous_station_entries.len() != next_station_entries.len()
+    {
+        return None;
+    }
+
+    let mut interpolated_entries = Vec::with_capacity(previous_station_entries.len());
+
+    for (previous_entry, next_entry) in previous_station_entries
+        .iter()
+        .zip(next_station_entries.iter())
+    {
+        let previous_minute_of_day = previous_entry.hour as u32 * 60 + previous_entry.minute as u32;
+        let next_minute_of_day = next_entry.hour as u32 * 60 + next_entry.minute as u32;
+
+        if next_minute_of_day < previous_minute_of_day {
+            return None;
+        }
+
+        let interpolated_minute_of_day = previous_minute_of_day
+            + ((next_minute_of_day - previous_minute_of_day) as f64 * fraction_of_segment).round()
+                as u32;
+
+        let entry = TimetableEntry::new(
+            (interpolated_minute_of_day / 60) as u8,
+            (interpolated_minute_of_day % 60) as u8,
+        )
+        .ok()?;
+
+        interpolated_entries.push(entry);
+    }
+
+    Some(interpolated_entries)
+}
+
+
+/// Returns `true` if the route shape has enough points to measure distances along it.
+pub fn is_usable_route_shape(route_shape: Option<&RouteGeoJsonShape>) -> bool {
+    route_shape
+        .map(|shape| shape.path_coordinates.len() >= 2)
+        .unwrap_or(false)
+}
+
+/// Returns the distance (in meters) from the start of the route to each station
+/// (measured along the route shape if it is usable, see [`is_usable_route_shape`]).
+pub fn distances_along_route(
+    stations_on_route: &[StationOnRoute],
+    route_shape: Option<&RouteGeoJsonShape>,
+) -> Vec<f64> {
+    if let Some(shape) = route_shape.filter(|shape| is_usable_route_shape(Some(shape))) {
+        return distances_along_shape(stations_on_route, shape);
+    }
+
+    let mut distances = Vec::with_capacity(stations_on_route.len());
+    let mut total_distance = 0.0;
+
+    for (station_index, station) in stations_on_route.iter().enumerate() {
+        if station_index > 0 {
+            total_distance += stations_on_route[station_index - 1]
+                .location
+                .distance_in_meters_to(&station.location);
+        }
+
+        distances.push(total_distance);
+    }
+
+    distances
+}
+
+/// Projects each station onto the nearest point of the route shape (searching only
+/// forward from the previous station, so loops on the route are handled correctly)
+/// and returns the distance along the shape up to that point.
+fn distances_along_shape(
+    stations_on_route: &[StationOnRoute],
+    route_shape: &RouteGeoJsonShape,
+) -> Vec<f64> {
+    // GeoJSON coordinates are in (longitude, latitude) order.
+    let shape_points: Vec<GeographicalLocation> = route_shape
+        .path_coordinates
+        .iter()
+        .map(|[longitude, latitude]| GeographicalLocation::new(*latitude, *longitude))
+        .collect();
+
+    let segment_lengths: Vec<f64> = shape_points
+        .windows(2)
+        .map(|segment| segment[0].distance_in_meters_to(&segment[1]))
+        .collect();
+
+    let mut cumulative_shape_distances = Vec::with_capacity(segment_lengths.len());
+    let mut total_distance = 0.0;
+    for segment_length in &segment_lengths {
+        cumulative_shape_distances.push(total_distance);
+        total_distance += segment_length;
+    }
+
+
+    let mut distances = Vec::with_capacity(stations_on_route.len());
+    let mut search_start_index = 0;
+
+    for station in stations_on_route {
+        let nearest_projection = shape_points
+            .windows(2)
+            .enumerate()
+            .skip(search_start_index)
+            .map(|(segment_index, segment)| {
+                let (fraction_of_segment, distance_to_segment) =
+                    project_onto_segment(&segment[0], &segment[1], &station.location);
+
+                (
+                    segment_index,
+                    fraction_of_segment,
+                    distance_to_segment,
+                )
+            })
+            .min_by(
+                |(_, _, first_distance), (_, _, second_distance)| {
+                    first_distance.total_cmp(second_distance)
+                },
+            );
+
+        let Some((segment_index, fraction_of_segment, _)) = nearest_projection else {
+            distances.push(total_distance);
+            continue;
+        };
+
+        distances.push(
+            cumulative_shape_distances[segment_index]
+                + fraction_of_segment * segment_lengths[segment_index],
+        );
+        search_start_index = segment_index;
+    }
+
+    distances
+}
+
+/// Projects the point onto the segment between `start` and `end`, returning how far along
+/// the segment the projection lies (from `0.0` to `1.0`) and the distance (in meters)
+/// from the point to it. Shape segments are short enough to treat them as planar.
+fn project_onto_segment(
+    start: &GeographicalLocation,
+    end: &GeographicalLocation,
+    point: &GeographicalLocation,
+) -> (f64, f64) {
+    const METERS_PER_DEGREE_OF_LATITUDE: f64 = 111_195.0;
+
+    let meters_per_degree_of_longitude =
+        METERS_PER_DEGREE_OF_LATITUDE * start.latitude.to_radians().cos();
+
+    let to_local_meters = |location: &GeographicalLocation| {
+        (
+            (location.longitude - start.longitude) * meters_per_degree_of_longitude,
+            (location.latitude - start.latitude) * METERS_PER_DEGREE_OF_LATITUDE,
+        )
+    };
+
+    let (end_x, end_y) = to_local_meters(end);
+    let (point_x, point_y) = to_local_meters(point);
+
+    let squared_segment_length = end_x * end_x + end_y * end_y;
+    let fraction_of_segment = if squared_segment_length > 0.0 {
+        ((point_x * end_x + point_y * end_y) / squared_segment_length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    let distance_to_segment =
+        (point_x - fraction_of_segment * end_x).hypot(point_y - fraction_of_segment * end_y);
+
+    (fraction_of_segment, distance_to_segment)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_fixtures::timetable;
+
+    fn station(stop_number: i32, station_code: &str, latitude: f64) -> StationOnRoute {
+        StationOnRoute {
+            station_code: StationCode::new(station_code),
+            internal_station_id: stop_number,
+            name: station_code.to_string(),
+            location: GeographicalLocation::new(latitude, 14.5),
+            stop_number,
+        }
+    }
+
+    #[test]
+    fn interpolates_missing_station_proportionally_to_distance() {
+        let stations = vec![
+            station(1, "600011", 46.00),
+            station(2, "600012", 46.03),
+            station(3, "600013", 46.04),
+        ];
+
+        let mut station_timetables = HashMap::new();
+        station_timetables.insert(
+            StationCode::new("600011"),
+            timetable("6", "ČRNUČE - DOLGI MOST", &[(5, 0), (5, 50)]),
+        );
+        station_timetables.insert(
+            StationCode::new("600013"),
+            timetable("6", "ČRNUČE - DOLGI MOST", &[(5, 20), (6, 30)]),
+        );
+
+        let resolved = resolve_trip_station_timetables(stations, &station_timetables, None);
+
+        assert!(resolved.unresolved_stations.is_empty());
+        assert_eq!(resolved.number_of_interpolated_stations(), 1);
+
+        let interpolated_station = &resolved.stations_with_timetables[1];
+        assert!(interpolated_station.timetable_is_interpolated);
+
+        let interpolated_times: Vec<(u8, u8)> = interpolated_station
+            .timetable
+            .timetable
+            .iter()
+            .map(|entry| (entry.hour, entry.minute))
+            .collect();
+
+        // The station is three quarters of the way along the segment.
+        assert_eq!(interpolated_times, vec![(5, 15), (6, 20)]);
+    }
+
+    #[test]
+    fn does_not_interpolate_at_the_ends_of_a_trip() {
+        let stations = vec![station(1, "600011", 46.00), station(2, "600012", 46.01)];
+
+        let mut station_timetables = HashMap::new();
+        station_timetables.insert(
+            StationCode::new("600012"),
+            timetable("6", "ČRNUČE - DOLGI MOST", &[(5, 0)]),
+        );
+
+        let resolved = resolve_trip_station_timetables(stations, &station_timetables, None);
+
+        assert_eq!(resolved.stations_with_timetables.len(), 1);
+        assert_eq!(resolved.unresolved_stations.len(), 1);
+        assert_eq!(
+            resolved.unresolved_stations[0].station_code,
+            StationCode::new("600011")
+        );
+    }
+
+    #[test]
+    fn measures_distances_along_route_shape_between_shape_points() {
+        let route_shape = RouteGeoJsonShape {
+            // Heads north, then east.
+            path_coordinates: vec![[14.5, 46.00], [14.5, 46.02], [14.53, 46.02]],
+            bounding_box: [14.5, 46.00, 14.53, 46.02],
+        };
+
+        let mut stations = vec![
+            station(1, "600011", 46.00),
+            station(2, "600012", 46.01),
+            station(3, "600013", 46.02),
+        ];
+        // Slightly off the shape, in the middle of its second segment.
+        stations[2].location = GeographicalLocation::new(46.0201, 14.515);
+
+        let distances = distances_along_route(&stations, Some(&route_shape));
+
+        let first_segment_length = GeographicalLocation::new(46.00, 14.5)
+            .distance_in_meters_to(&GeographicalLocation::new(46.02, 14.5));
+        let second_segment_length = GeographicalLocation::new(46.02, 14.5)
+            .distance_in_meters_to(&GeographicalLocation::new(46.02, 14.53));
+
+        assert_eq!(distances[0], 0.0);
+        assert!((distances[1] - first_segment_length / 2.0).abs() < 1.0);
+        assert!((distances[2] - (first_segment_length + second_segment_length / 2.0)).abs() < 5.0);
+    }
+
+    #[test]
+    fn does_not_interpolate_mismatched_timetables() {
+        assert!(interpolate_timetable_entries(
+            &[TimetableEntry::new(5, 0).unwrap()],
+            &[
+                TimetableEntry::new(5, 10).unwrap(),
+                TimetableEntry::new(6, 10).unwrap()
+            ],
+            0.5
+        )
+        .is_none());
+
+        assert!(interpolate_timetable_entries(
+            &[TimetableEntry::new(5, 30).unwrap()],
+            &[TimetableEntry::new(5, 10).unwrap()],
+            0.5
+        )
+        .is_none());
+    }
+}
@@ -1,72 +1,130 @@
 use std::{
+    cmp::Reverse,
     collections::{HashMap, HashSet},
     error::Error,
     fs::OpenOptions,
     future::Future,
-    io::{BufWriter, Write},
-    path::Path,
+    io::Write,
+    path::{Path, PathBuf},
     time::{Duration, Instant},
 };
 
 use backoff::{backoff::Backoff, exponential::ExponentialBackoff, ExponentialBackoffBuilder};
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, Timelike, Utc};
 use miette::{miette, Context, Diagnostic, IntoDiagnostic, Result};
-use reqwest::Client;
 use serde::Serialize;
 use thiserror::Error;
 use tokio::task::yield_now;
 use tracing::{debug, error, info, info_span, warn, Instrument};
-
+use uuid::Uuid;
+
+pub mod acceptance;
+pub mod arrivals;
+mod checkpoint;
+mod completeness;
+mod deadline;
+mod destinations;
+mod entity_registry;
+pub mod fetch_plan;
 pub mod formats;
+mod hub_coverage;
+pub mod interpolation;
+mod phase_timing;
+pub mod preflight;
+mod route_matching;
+mod schedule;
+pub mod spatial_summary;
+mod station_fetch;
+mod timetables_only;
 
 use crate::{
+    analysis::changelog::{NetworkSummary, SnapshotChangelog, CHANGELOG_FILE_NAME},
     api::{
-        routes::fetch_all_routes,
-        routes_on_station::fetch_routes_on_station,
-        station_details::fetch_station_details,
-        stations_on_route::fetch_stations_on_route,
-        timetable::{fetch_timetable, TimetableFetchMode, TripTimetable},
+        client::LppApiClient,
+        errors::LppApiFetchError,
+        routes::{fetch_all_routes, RouteDetails},
+        routes_on_station::{fetch_routes_on_station, TripOnStation},
+        station_details::{
+            deduplicate_stations,
+            fetch_station_details,
+            validate_station_codes,
+            StationDetails,
+        },
+        stations_on_route::{fetch_stations_on_route, StationOnRoute},
+        timetable::{
+            fetch_timetable,
+            merge_route_group_timetables,
+            RouteGroupTimetable,
+            TimetableFetchMode,
+            TimetableWindowPolicy,
+            TripTimetable,
+        },
+        BaseBusRoute,
         BusRoute,
         StationCode,
     },
+    calendar::{HolidayCalendar, ServiceDayType},
     cancellation_token::CancellationToken,
     cli::RunMode,
-    configuration::LppConfiguration,
-    recorder::formats::{
-        AllRoutesSnapshot,
-        AllStationsSnapshot,
-        StationDetailsWithBusesAndTimetables,
-        TripStationWithTimetable,
-        TripWithStationsAndTimetables,
+    clock::{Clock, SharedClock},
+    configuration::{CaptureMode, LppConfiguration},
+    notifications::{EmailNotifier, SnapshotSummary},
+    recorder::{
+        acceptance::{CaptureCoverage, REJECTED_SNAPSHOTS_DIRECTORY_NAME},
+        checkpoint::{SavedStationSnapshot, StationPhasesCheckpoint, CHECKPOINTED_PHASES},
+        completeness::compute_trip_data_completeness,
+        deadline::{CaptureDeadline, UnprocessedWork},
+        destinations::{resolve_destination_station, unresolved_destinations_warning},
+        entity_registry::EntityRegistry,
+        fetch_plan::FetchPlan,
+        formats::{
+            AllRoutesSnapshot,
+            AllStationsSnapshot,
+            ReusedCapturePhases,
+            SnapshotStatus,
+            SnapshotWarning,
+            SnapshotWarningKind,
+            StationDetailsWithBusesAndTimetables,
+            TripWithStationsAndTimetables,
+        },
+        hub_coverage::report_hub_coverage,
+        interpolation::resolve_trip_station_timetables,
+        phase_timing::PhaseTimings,
+        preflight::run_preflight_probe,
+        route_matching::{find_route_timetables, RouteMatchingMode},
+        schedule::{CaptureSchedule, ScheduledCapture},
+        station_fetch::{fetch_stations, FetchedStations},
+        timetables_only::make_timetables_only_snapshot,
     },
-    storage::{RouteStorage, StationStorage},
+    signing::{save_public_key_to_storage_root, sign_file},
+    storage::{RouteStorage, RunCounters, SnapshotArchive, StationStorage, StorageRoot},
+    systemd,
+    telemetry,
+    upload::SnapshotUploader,
 };
 
 
-fn save_json_to_file<S>(data: &S, file_path: &Path) -> Result<()>
+fn serialize_to_json<S>(data: &S) -> Result<Vec<u8>>
 where
     S: Serialize,
 {
-    let file = OpenOptions::new()
+    serde_json::to_vec(data)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to serialize data to JSON."))
+}
+
+fn save_json_to_file(json_data: &[u8], file_path: &Path) -> Result<()> {
+    let mut file = OpenOptions::new()
         .create_new(true)
         .write(true)
         .open(file_path)
         .into_diagnostic()
         .wrap_err_with(|| miette!("Failed to open file for writing."))?;
 
-    let mut buf_writer = BufWriter::new(file);
-
-
-    serde_json::to_writer(&mut buf_writer, data)
+    file.write_all(json_data)
         .into_diagnostic()
         .wrap_err_with(|| miette!("Failed to write JSON data to file."))?;
 
-
-    let mut file = buf_writer
-        .into_inner()
-        .into_diagnostic()
-        .wrap_err_with(|| miette!("Failed to flush output file's BufWriter."))?;
-
     file.flush()
         .into_diagnostic()
         .wrap_err_with(|| miette!("Failed to flush output file."))?;
@@ -79,313 +137,1326 @@ where
  * Station and route details capture
  */
 
+/// What a single capture has saved. Snapshots rejected by the acceptance policy
+/// (saved into the rejected directory) are also returned, with the `Rejected` status.
+pub struct CapturedSnapshots {
+    pub saved_file_paths: Vec<PathBuf>,
+    pub status: SnapshotStatus,
+    pub coverage: CaptureCoverage,
+    pub warnings: Vec<SnapshotWarning>,
+}
+
 async fn make_station_and_route_snapshot(
     configuration: &LppConfiguration,
-    client: &Client,
+    client: &LppApiClient,
+    clock: &dyn Clock,
     station_storage: &StationStorage,
     route_storage: &RouteStorage,
-) -> Result<()> {
+    run_id: Uuid,
+    deadline: &CaptureDeadline,
+) -> Result<CapturedSnapshots> {
+    let (service_date, service_day_type) =
+        detect_service_day(&configuration.recording.holiday_calendar, clock);
+    let timetable_fetch_mode =
+        timetable_fetch_mode_for_capture(&configuration.recording.timetable_window, clock);
+
+    let storage_root_path = configuration.recording.recording_storage_root.path();
+
+    // Stations and trips are registered in the same registry, which is only saved
+    // once the IDs of both have been assigned.
+    let mut entity_registry = load_entity_registry(storage_root_path);
+
+    let mut phase_timings = PhaseTimings::new().with_intent_log(
+        configuration
+            .recording
+            .recording_storage_root
+            .intent_log()
+            .clone(),
+        run_id,
+    );
+
+    // If this is a retry, the station phases of an earlier attempt may have finished already.
+    let checkpoint = match StationPhasesCheckpoint::load(storage_root_path, run_id, service_date) {
+        Ok(checkpoint) => checkpoint,
+        Err(error) => {
+            warn!(error = ?error, "Failed to load checkpoint, will redo the station phases.");
+            None
+        }
+    };
+
+    let (mut station_phases, reused_phases) = match checkpoint {
+        Some(checkpoint) => {
+            info!(
+                phases = ?CHECKPOINTED_PHASES,
+                checkpointed_at = %checkpoint.checkpointed_at,
+                "Reusing the station phases of an earlier attempt from the checkpoint."
+            );
+
+            let reused_phases = checkpoint.reused_phases();
+            (checkpoint, Some(reused_phases))
+        }
+        None => {
+            let station_phases = capture_station_phases(
+                configuration,
+                client,
+                clock,
+                &mut phase_timings,
+                run_id,
+                service_date,
+                service_day_type,
+                timetable_fetch_mode,
+                deadline,
+            )
+            .await?;
+
+            if let Err(error) = station_phases.save(storage_root_path) {
+                warn!(
+                    error = ?error,
+                    "Failed to save checkpoint, a retry would redo the station phases."
+                );
+            }
+
+            (station_phases, None)
+        }
+    };
+
+    // The station snapshot is saved before the route phases start, so a failure in them can't
+    // lose it. Retries reuse the station snapshot saved by an earlier attempt.
+    let station_snapshot = match station_phases.station_snapshot.clone() {
+        Some(station_snapshot) => station_snapshot,
+        None => {
+            let station_snapshot = save_station_phases_snapshot(
+                configuration,
+                clock,
+                station_storage,
+                run_id,
+                &mut station_phases,
+                reused_phases.clone(),
+                entity_registry.as_mut(),
+                &mut phase_timings,
+            )
+            .await?;
+
+            station_phases.station_snapshot = Some(station_snapshot.clone());
+            if let Err(error) = station_phases.save(storage_root_path) {
+                warn!(
+                    error = ?error,
+                    "Failed to save checkpoint, a retry would save another station snapshot."
+                );
+            }
+
+            station_snapshot
+        }
+    };
+
+    let StationPhasesCheckpoint {
+        capture_started_at,
+        route_matching_mode,
+        total_number_of_stations,
+        stations_without_route_groups,
+        warnings: mut snapshot_warnings,
+        stations_with_bus_trips,
+        unprocessed_station_codes,
+        ..
+    } = station_phases;
+
+    // Add the timetables into a hash map for later access (when we'll assign timetables to bus trips).
+    let mut bus_trip_to_timetable: HashMap<BusRoute, HashMap<StationCode, TripTimetable>> =
+        HashMap::new();
+    for station in &stations_with_bus_trips {
+        add_timetables_to_trip_map(
+            &mut bus_trip_to_timetable,
+            &station.station_code,
+            &station.timetables,
+        );
+    }
+
+
+    // Now we'll fetch all bus routes and assign them a trip timetable.
+    // This request is made even if the deadline has passed (or the recorder is shutting down),
+    // so the unprocessed routes can be named. It is then only attempted once.
+    debug!("Requesting all routes.");
+
+    let all_routes_phase = phase_timings.start_phase("all-routes");
+    let all_routes = async {
+        if deadline.has_passed(clock) {
+            fetch_all_routes(&configuration.api, client)
+                .await
+                .into_diagnostic()
+        } else {
+            retryable_async_with_exponential_backoff(
+                || fetch_all_routes(&configuration.api, client),
+                retry_lpp_api_errors,
+                None,
+                deadline.cancellation_token(),
+            )
+            .await
+            .into_diagnostic()
+        }
+    }
+    .instrument(all_routes_phase.span())
+    .await
+    .wrap_err_with(|| miette!("Failed to fetch all routes."))?;
+    all_routes_phase.finish(all_routes.len(), &mut phase_timings);
+
+
+    let mut routes_with_context = Vec::with_capacity(all_routes.len());
+    let mut unprocessed_routes = Vec::new();
+
+    let number_of_all_routes = all_routes.len();
+
+    let routes_phase = phase_timings.start_phase("routes");
+    async {
+        for (route_index, route) in all_routes.into_iter().enumerate() {
+            let captured_at = clock.now();
+
+
+            let raw_route_timetables = match find_route_timetables(
+                &bus_trip_to_timetable,
+                &route.route,
+                route_matching_mode,
+            ) {
+                Some(timetable_map) => timetable_map,
+                None => {
+                    // It's possible that we have some bad data that has
+                    // no associated timetable data. In this case, we ignore the route.
+                    warn!(
+                        current_route = route_index + 1,
+                        total_routes = number_of_all_routes,
+                        route = %route.route,
+                        "Did not collect any timetables for this route - will skip."
+                    );
+                    continue;
+                }
+            };
+
+            if deadline.has_passed(clock) {
+                unprocessed_routes.push(route.route);
+                continue;
+            }
+
+
+            debug!(
+                current_route = route_index + 1,
+                total_routes = number_of_all_routes,
+                "Requesting stations on route."
+            );
+
+            let stations_on_route = match retryable_async_with_exponential_backoff(
+                || fetch_stations_on_route(&configuration.api, client, route.trip_id.clone()),
+                retry_lpp_api_errors,
+                None,
+                deadline.cancellation_token(),
+            )
+            .instrument(info_span!("fetch-one-route"))
+            .await
+            {
+                Ok(stations_on_route) => stations_on_route,
+                // The recorder is shutting down, so the route is reported as unprocessed.
+                Err(RetryableError::Cancelled) => {
+                    unprocessed_routes.push(route.route);
+                    continue;
+                }
+                Err(error) => {
+                    return Err(error)
+                        .into_diagnostic()
+                        .wrap_err_with(|| miette!("Failed to fetch individual route."));
+                }
+            };
+
+            let Some(stations_on_route) = stations_on_route else {
+                warn!(
+                    route_id = %route.route_id,
+                    route = %route.route,
+                    "Route did not contain any stations."
+                );
+                continue;
+            };
+
+
+            // Join with the per-station per-trip timetable data
+            // we collected into `bus_trip_to_timetable` earlier.
+            let total_stations_on_route = stations_on_route.len();
+            routes_with_context.push(join_trip_with_timetables(
+                captured_at,
+                route,
+                stations_on_route,
+                total_stations_on_route,
+                raw_route_timetables,
+            ));
+        }
+
+        Ok::<_, miette::Report>(())
+    }
+    .instrument(routes_phase.span())
+    .await?;
+    routes_phase.finish(routes_with_context.len(), &mut phase_timings);
+
+    // We've processed all the stations and all the routes, including their timetables.
+    info!("Finished requesting a snapshot of all stations and routes.");
+
+    // The station warnings were already reported when the station snapshot was saved.
+    snapshot_warnings.extend(station_snapshot.warnings.iter().cloned());
+    snapshot_warnings.extend(unresolved_destinations_warning(
+        &routes_with_context,
+    ));
+
+    let unprocessed_work = UnprocessedWork {
+        station_codes: unprocessed_station_codes,
+        routes: unprocessed_routes,
+    };
+    snapshot_warnings.extend(
+        UnprocessedWork {
+            station_codes: Vec::new(),
+            routes: unprocessed_work.routes.clone(),
+        }
+        .report(),
+    );
+
+    let capture_coverage = CaptureCoverage {
+        expected_stations: total_number_of_stations - stations_without_route_groups,
+        captured_stations: stations_with_bus_trips.len(),
+        expected_routes: number_of_all_routes,
+        captured_routes: routes_with_context.len(),
+    };
+    let snapshot_status = evaluate_capture_coverage(
+        configuration,
+        &capture_coverage,
+        &unprocessed_work,
+    );
+    snapshot_warnings.extend(capture_coverage.snapshot_warning(snapshot_status));
+
+
+    let snapshot_time = clock.now();
+    if let Some(entity_registry) = entity_registry.as_mut() {
+        entity_registry.assign_stable_ids(snapshot_time, &mut [], &mut routes_with_context);
+        save_entity_registry(entity_registry, storage_root_path);
+    }
+
+    // Route timetables are joined from the station timetables, so the
+    // route snapshot's data was captured over the entire run.
+    let route_details_snapshot = AllRoutesSnapshot::new(
+        snapshot_time,
+        Some(run_id),
+        service_date,
+        service_day_type,
+        None,
+        snapshot_warnings.clone(),
+        routes_with_context,
+    )
+    .with_capture_window(capture_started_at, snapshot_time)
+    .with_status(snapshot_status)
+    .with_reused_phases(reused_phases);
+
+    // We have the data we need, so it's not time-critical
+    // that we save it at this exact moment; let's yield.
+    yield_now().await;
+
+    let mut saved_file_paths = station_snapshot.file_paths;
+    saved_file_paths.extend(save_route_snapshot(
+        configuration,
+        route_storage,
+        &route_details_snapshot,
+        &mut phase_timings,
+    )?);
+
+    // The snapshots are saved, so a retry would have nothing left to reuse.
+    if let Err(error) = StationPhasesCheckpoint::remove(storage_root_path) {
+        warn!(error = ?error, "Failed to remove checkpoint.");
+    }
+
+    phase_timings.log_table();
+
+    Ok(CapturedSnapshots {
+        saved_file_paths,
+        status: snapshot_status,
+        coverage: capture_coverage,
+        warnings: snapshot_warnings,
+    })
+}
+
+/// Saves the station snapshot of a full capture as soon as its station phases finish.
+/// Its status only accounts for the stations; the routes haven't been fetched yet.
+#[allow(clippy::too_many_arguments)]
+async fn save_station_phases_snapshot(
+    configuration: &LppConfiguration,
+    clock: &dyn Clock,
+    station_storage: &StationStorage,
+    run_id: Uuid,
+    station_phases: &mut StationPhasesCheckpoint,
+    reused_phases: Option<ReusedCapturePhases>,
+    entity_registry: Option<&mut EntityRegistry>,
+    phase_timings: &mut PhaseTimings,
+) -> Result<SavedStationSnapshot> {
+    let mut station_warnings = Vec::new();
+    station_warnings.extend(report_hub_coverage(
+        &configuration.recording.critical_hub_stations,
+        &station_phases.stations_with_bus_trips,
+    ));
+
+    let unprocessed_stations = UnprocessedWork {
+        station_codes: station_phases.unprocessed_station_codes.clone(),
+        routes: Vec::new(),
+    };
+    station_warnings.extend(unprocessed_stations.report());
+
+    let station_coverage = CaptureCoverage {
+        expected_stations: station_phases.total_number_of_stations
+            - station_phases.stations_without_route_groups,
+        captured_stations: station_phases.stations_with_bus_trips.len(),
+        expected_routes: 0,
+        captured_routes: 0,
+    };
+    let station_status = evaluate_capture_coverage(
+        configuration,
+        &station_coverage,
+        &unprocessed_stations,
+    );
+
+    let mut snapshot_warnings = station_phases.warnings.clone();
+    snapshot_warnings.extend(station_warnings.iter().cloned());
+    snapshot_warnings.extend(station_coverage.snapshot_warning(station_status));
+
+
+    assign_station_districts(
+        configuration,
+        &mut station_phases.stations_with_bus_trips,
+    );
+
+    let snapshot_time = clock.now();
+    if let Some(entity_registry) = entity_registry {
+        entity_registry.assign_stable_ids(
+            snapshot_time,
+            &mut station_phases.stations_with_bus_trips,
+            &mut [],
+        );
+    }
+
+    // The stations are moved into the snapshot for saving and moved back afterwards,
+    // as the route phases still need them.
+    let station_details_snapshot = AllStationsSnapshot::new(
+        snapshot_time,
+        Some(run_id),
+        station_phases.service_date,
+        station_phases.service_day_type,
+        None,
+        snapshot_warnings,
+        std::mem::take(&mut station_phases.stations_with_bus_trips),
+    )
+    .with_capture_window(
+        station_phases.capture_started_at,
+        station_phases.checkpointed_at,
+    )
+    .with_status(station_status)
+    .with_reused_phases(reused_phases);
+
+    yield_now().await;
+
+    let saved_file_paths = save_station_snapshot(
+        configuration,
+        station_storage,
+        &station_details_snapshot,
+        phase_timings,
+    );
+    station_phases.stations_with_bus_trips = station_details_snapshot.station_details;
+
+    Ok(SavedStationSnapshot {
+        file_paths: saved_file_paths?,
+        status: station_status,
+        warnings: station_warnings,
+    })
+}
+
+/// Fetches the station details and then the trips and timetables of every station
+/// (the `station-details` and `stations` phases of a full capture).
+#[allow(clippy::too_many_arguments)]
+async fn capture_station_phases(
+    configuration: &LppConfiguration,
+    client: &LppApiClient,
+    clock: &dyn Clock,
+    phase_timings: &mut PhaseTimings,
+    run_id: Uuid,
+    service_date: NaiveDate,
+    service_day_type: ServiceDayType,
+    timetable_fetch_mode: TimetableFetchMode,
+    deadline: &CaptureDeadline,
+) -> Result<StationPhasesCheckpoint> {
+    let capture_started_at = clock.now();
+
     // Fetch all stations.
+    let station_details_phase = phase_timings.start_phase("station-details");
     let stations = retryable_async_with_exponential_backoff(
         || fetch_station_details(&configuration.api, client),
-        |result| match result {
-            Ok(details) => RetryableResult::Ok(details),
-            Err(error) => RetryableResult::TransientErr {
-                error,
-                override_retry_after: None,
-            },
-        },
+        retry_lpp_api_errors,
         None,
+        deadline.cancellation_token(),
     )
-    .instrument(info_span!("station-details"))
+    .instrument(station_details_phase.span())
     .await
     .into_diagnostic()
     .wrap_err_with(|| miette!("Failed to fetch station details."))?;
+    station_details_phase.finish(stations.len(), phase_timings);
 
+    let mut snapshot_warnings = Vec::new();
 
-    // For each station, get all buses (trips) that stop there.
-    let mut bus_trip_to_timetable: HashMap<BusRoute, HashMap<StationCode, TripTimetable>> =
-        HashMap::new();
-
-    let mut stations_with_bus_trips = Vec::with_capacity(stations.len());
+    let (stations, number_of_duplicate_stations) = deduplicate_stations(stations);
+    if number_of_duplicate_stations > 0 {
+        warn!(
+            duplicate_stations = number_of_duplicate_stations,
+            "Station details contained duplicate station codes, dropped the duplicates."
+        );
 
-    let total_number_of_stations = stations.len();
+        snapshot_warnings.push(SnapshotWarning {
+            kind: SnapshotWarningKind::DuplicateStationsDropped,
+            message: format!(
+                "The LPP API returned {} duplicate station(s) (by station code), \
+                which were dropped in favour of the entry with the most route groups.",
+                number_of_duplicate_stations
+            ),
+        });
+    }
 
-    for (station_index, station) in stations.into_iter().enumerate() {
-        debug!(
-            current_station = station_index + 1,
-            total_stations = total_number_of_stations,
-            station_name = station.name,
-            station_code = %station.station_code,
-            "Requesting routes on station."
+    let (mut stations, invalid_station_codes) =
+        validate_station_codes(stations, configuration.api.lenient_station_codes);
+    if !invalid_station_codes.is_empty() {
+        let invalid_station_codes = invalid_station_codes
+            .iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        warn!(
+            invalid_station_codes = %invalid_station_codes,
+            kept = configuration.api.lenient_station_codes,
+            "Station details contained malformed station codes."
         );
 
-        let trips_on_station = retryable_async_with_exponential_backoff(
-            || fetch_routes_on_station(&configuration.api, client, &station.station_code),
-            |result| match result {
-                Ok(details) => RetryableResult::Ok(details),
-                Err(error) => RetryableResult::TransientErr {
-                    error,
-                    override_retry_after: None,
+        snapshot_warnings.push(SnapshotWarning {
+            kind: SnapshotWarningKind::InvalidStationCodes,
+            message: format!(
+                "The LPP API returned stations with malformed station codes, which were {}: {}.",
+                if configuration.api.lenient_station_codes {
+                    "kept"
+                } else {
+                    "dropped"
                 },
-            },
-            None,
+                invalid_station_codes
+            ),
+        });
+    }
+
+    let route_matching_mode = RouteMatchingMode::detect(
+        stations
+            .iter()
+            .flat_map(|station| station.routes_on_station.iter()),
+    );
+    warn_if_subroutes_are_missing(route_matching_mode);
+    snapshot_warnings.extend(route_matching_mode.snapshot_warning());
+
+    if configuration.recording.prioritize_hub_stations {
+        // Stable sort, so stations with the same number of route groups keep their order.
+        stations.sort_by_cached_key(|station| Reverse(station.number_of_route_groups()));
+
+        debug!("Will fetch stations with the most route groups first.");
+    }
+
+    let fetch_plan = FetchPlan::for_stations(
+        &stations,
+        configuration.api.max_route_groups_per_timetable_request,
+    );
+    info!(
+        stations = fetch_plan.number_of_stations,
+        route_group_sets = fetch_plan.route_group_sets.len(),
+        estimated_requests = fetch_plan.total_requests(),
+        "Planned requests for this snapshot."
+    );
+
+
+    // For each station, get all buses (trips) that stop there.
+    let total_number_of_stations = stations.len();
+
+    let stations_phase = phase_timings.start_phase("stations");
+    let FetchedStations {
+        stations_with_bus_trips,
+        stations_without_route_groups,
+        stations_that_failed_twice,
+        unprocessed_station_codes,
+    } = fetch_stations(
+        configuration,
+        clock,
+        deadline,
+        stations,
+        |station: &StationDetails| {
+            let station_code = station.station_code.clone();
+
+            async move {
+                fetch_station_trips_and_timetables(
+                    configuration,
+                    client,
+                    clock,
+                    &station_code,
+                    timetable_fetch_mode,
+                    deadline.cancellation_token(),
+                )
+                .await
+            }
+        },
+    )
+    .instrument(stations_phase.span())
+    .await;
+    stations_phase.finish(stations_with_bus_trips.len(), phase_timings);
+
+    snapshot_warnings.extend(failed_stations_warning(
+        &stations_that_failed_twice,
+    ));
+
+    Ok(StationPhasesCheckpoint {
+        run_id,
+        service_date,
+        service_day_type,
+        capture_started_at,
+        checkpointed_at: clock.now(),
+        route_matching_mode,
+        total_number_of_stations,
+        stations_without_route_groups,
+        warnings: snapshot_warnings,
+        stations_with_bus_trips,
+        unprocessed_station_codes,
+        station_snapshot: None,
+    })
+}
+
+/// Captures a full snapshot, retrying it up to `snapshot_retries` times if it fails (unless
+/// the recorder is shutting down). Snapshots rejected by the acceptance policy are not retried. Retries reuse the station
+/// phases of earlier attempts from the checkpoint, so only the failed phases are redone.
+///
+/// The snapshot deadline (see `lpp.recording.snapshot_deadline`) spans all attempts,
+/// and a snapshot that fails after its deadline has passed is not retried.
+async fn make_station_and_route_snapshot_with_retries(
+    configuration: &LppConfiguration,
+    client: &LppApiClient,
+    clock: &dyn Clock,
+    station_storage: &StationStorage,
+    route_storage: &RouteStorage,
+    run_id: Uuid,
+    cancellation_token: &CancellationToken,
+) -> Result<CapturedSnapshots> {
+    let deadline = CaptureDeadline::after(
+        clock.now(),
+        configuration.recording.snapshot_deadline,
+    )
+    .or_when_cancelled(cancellation_token.clone());
+    let mut retries = 0;
+
+    loop {
+        let error = match make_station_and_route_snapshot(
+            configuration,
+            client,
+            clock,
+            station_storage,
+            route_storage,
+            run_id,
+            &deadline,
         )
-        .instrument(info_span!("trips-on-station"))
         .await
-        .into_diagnostic()
-        .wrap_err_with(|| miette!("Failed to fetch trips on station."))?;
+        {
+            Ok(captured_snapshots) => return Ok(captured_snapshots),
+            Err(error) => error,
+        };
 
+        let is_retryable = !cancellation_token.is_cancelled() && !deadline.has_passed(clock);
 
+        if !is_retryable || retries >= configuration.recording.snapshot_retries {
+            if let Err(error) = StationPhasesCheckpoint::remove(
+                configuration.recording.recording_storage_root.path(),
+            ) {
+                warn!(error = ?error, "Failed to remove checkpoint.");
+            }
 
-        let mut all_route_groups = HashSet::new();
-        for trip in &trips_on_station {
-            all_route_groups.insert(trip.route.to_base_route());
+            return Err(error);
         }
 
+        retries += 1;
+        warn!(
+            error = ?error,
+            retry = retries,
+            max_retries = configuration.recording.snapshot_retries,
+            retry_delay_seconds = configuration.recording.snapshot_retry_delay.as_secs(),
+            "Snapshot failed, will retry it (reusing any checkpointed phases)."
+        );
 
-        if all_route_groups.is_empty() {
-            debug!(
-                current_station = station_index + 1,
-                total_stations = total_number_of_stations,
-                station_name = station.name,
-                station_code = %station.station_code,
-                "Station has no route groups, will not request a timetable."
-            );
-            continue;
+        if cancellation_token
+            .run_until_cancelled(clock.sleep(configuration.recording.snapshot_retry_delay))
+            .await
+            .is_none()
+        {
+            return Err(error);
         }
+    }
+}
 
+/// Evaluates the acceptance policy and logs the outcome if the snapshot is not complete.
+/// Snapshots cut short by their deadline are never rejected, see [`UnprocessedWork::adjust_status`].
+fn evaluate_capture_coverage(
+    configuration: &LppConfiguration,
+    capture_coverage: &CaptureCoverage,
+    unprocessed_work: &UnprocessedWork,
+) -> SnapshotStatus {
+    let snapshot_status = unprocessed_work.adjust_status(
+        configuration
+            .recording
+            .acceptance_policy
+            .evaluate(capture_coverage),
+    );
 
-        debug!(
-            current_station = station_index + 1,
-            total_stations = total_number_of_stations,
-            station_name = station.name,
-            station_code = %station.station_code,
-            "Requesting full timetable for station."
+    if snapshot_status != SnapshotStatus::Complete {
+        warn!(
+            status = ?snapshot_status,
+            captured_stations = capture_coverage.captured_stations,
+            expected_stations = capture_coverage.expected_stations,
+            captured_routes = capture_coverage.captured_routes,
+            expected_routes = capture_coverage.expected_routes,
+            "Snapshot did not capture all expected stations and routes."
         );
+    }
 
-        let timetables = retryable_async_with_exponential_backoff(
-            || {
-                fetch_timetable(
-                    &configuration.api,
-                    client,
-                    &station.station_code,
-                    all_route_groups.clone(),
-                    TimetableFetchMode::FullDay,
-                )
-            },
-            |result| match result {
-                Ok(details) => RetryableResult::Ok(details),
-                Err(error) => RetryableResult::TransientErr {
-                    error,
-                    override_retry_after: None,
-                },
-            },
-            None,
-        )
-        .instrument(info_span!("timetable-on-station"))
-        .await
+    snapshot_status
+}
+
+/// A snapshot was rejected by the acceptance policy, see [`fail_if_rejected`].
+#[derive(Error, Debug, Diagnostic)]
+#[error(
+    "Snapshot was rejected by the acceptance policy: captured {station_percentage:.1}% of stations \
+    and {route_percentage:.1}% of routes."
+)]
+pub struct SnapshotRejectedError {
+    station_percentage: f64,
+    route_percentage: f64,
+}
+
+/// Fails the snapshot run if the snapshot was rejected by the acceptance policy
+/// (it has already been saved into the rejected directory by then).
+/// Only used in the `once` run mode, where the exit code is the signal of a failed capture.
+fn fail_if_rejected(
+    snapshot_status: SnapshotStatus,
+    capture_coverage: &CaptureCoverage,
+) -> Result<()> {
+    if snapshot_status == SnapshotStatus::Rejected {
+        return Err(SnapshotRejectedError {
+            station_percentage: capture_coverage.station_fraction() * 100.0,
+            route_percentage: capture_coverage.route_fraction() * 100.0,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Returns where to save a snapshot that was rejected by the acceptance policy:
+/// the rejected directory of the storage root, under the file name it would otherwise have.
+fn rejected_snapshot_file_path(
+    configuration: &LppConfiguration,
+    file_path: &Path,
+) -> Result<PathBuf> {
+    let rejected_directory_path = configuration
+        .recording
+        .recording_storage_root
+        .path()
+        .join(REJECTED_SNAPSHOTS_DIRECTORY_NAME);
+
+    std::fs::create_dir_all(&rejected_directory_path)
         .into_diagnostic()
-        .wrap_err_with(|| miette!("Failed to fetch timetables on station."))?;
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to create rejected snapshot directory {}.",
+                rejected_directory_path.display()
+            )
+        })?;
 
+    Ok(rejected_directory_path.join(file_path.file_name().unwrap_or_default()))
+}
 
-        // Add the timetables into a hash map for later access (when we'll assign timetables to bus trips).
-        for group_timetable in &timetables {
-            for trip_timetable in &group_timetable.trip_timetables {
-                if let Some(trips_map) = bus_trip_to_timetable.get_mut(&trip_timetable.route) {
-                    trips_map.insert(
-                        station.station_code.clone(),
-                        trip_timetable.clone(),
-                    );
-                } else {
-                    let mut map = HashMap::new();
-                    map.insert(
-                        station.station_code.clone(),
-                        trip_timetable.clone(),
-                    );
+/// Loudly warns when the LPP API omitted sub-routes, as the snapshot
+/// will then be of lower quality (see [`RouteMatchingMode::BaseRoute`]).
+fn warn_if_subroutes_are_missing(route_matching_mode: RouteMatchingMode) {
+    if route_matching_mode == RouteMatchingMode::BaseRoute {
+        warn!(
+            "The LPP API did not return any sub-routes (is show-subroutes=1 still honored?) - \
+            will match routes with timetables by their base route number only. \
+            The snapshot will include a warning about this."
+        );
+    }
+}
 
-                    bus_trip_to_timetable.insert(trip_timetable.route.clone(), map);
-                }
-            }
+/// Determines the service date and type of service day of the timetables we're about to capture.
+fn detect_service_day(
+    holiday_calendar: &HolidayCalendar,
+    clock: &dyn Clock,
+) -> (NaiveDate, ServiceDayType) {
+    // The timetables we'll receive are for the current local (Ljubljana) date.
+    let service_date = clock.local_now().date_naive();
+    let service_day_type = holiday_calendar.service_day_type(service_date);
+
+    info!(
+        service_date = %service_date,
+        service_day_type = ?service_day_type,
+        "Detected type of service day for this snapshot."
+    );
+
+    (service_date, service_day_type)
+}
+
+/// Resolves the configured timetable window into the timetable fetch mode for this capture.
+fn timetable_fetch_mode_for_capture(
+    timetable_window: &TimetableWindowPolicy,
+    clock: &dyn Clock,
+) -> TimetableFetchMode {
+    let timetable_fetch_mode = timetable_window.fetch_mode_at_hour(clock.local_now().hour());
+
+    info!(
+        timetable_fetch_mode = ?timetable_fetch_mode,
+        "Resolved timetable window for this snapshot."
+    );
+
+    timetable_fetch_mode
+}
+
+fn route_groups_on_station(trips_on_station: &[TripOnStation]) -> HashSet<BaseBusRoute> {
+    trips_on_station
+        .iter()
+        .map(|trip| trip.route.to_base_route())
+        .collect()
+}
+
+/// Trips that stop at a station and the timetables of their route groups.
+type StationTripsAndTimetables = (Vec<TripOnStation>, Vec<RouteGroupTimetable>);
+
+/// Fetches the trips on a station and their timetables (each request is retried with
+/// exponential backoff). Returns `None` if no route groups stop at the station.
+async fn fetch_station_trips_and_timetables(
+    configuration: &LppConfiguration,
+    client: &LppApiClient,
+    clock: &dyn Clock,
+    station_code: &StationCode,
+    timetable_fetch_mode: TimetableFetchMode,
+    cancellation_token: Option<&CancellationToken>,
+) -> Result<Option<StationTripsAndTimetables>> {
+    let trips_on_station = retryable_async_with_exponential_backoff(
+        || fetch_routes_on_station(&configuration.api, client, station_code),
+        retry_lpp_api_errors,
+        None,
+        cancellation_token,
+    )
+    .instrument(info_span!("trips-on-station"))
+    .await
+    .into_diagnostic()
+    .wrap_err_with(|| miette!("Failed to fetch trips on station."))?;
+
+    let all_route_groups = route_groups_on_station(&trips_on_station);
+    if all_route_groups.is_empty() {
+        return Ok(None);
+    }
+
+    let timetables = fetch_station_timetables(
+        configuration,
+        client,
+        clock,
+        station_code,
+        all_route_groups,
+        timetable_fetch_mode,
+        cancellation_token,
+    )
+    .await?;
+
+    Ok(Some((trips_on_station, timetables)))
+}
+
+/// Assigns each station the district it lies in, if district boundaries are configured.
+/// Otherwise, the stations are left as they are (reused stations keep their district).
+fn assign_station_districts(
+    configuration: &LppConfiguration,
+    stations: &mut [StationDetailsWithBusesAndTimetables],
+) {
+    let Some(district_boundaries) = &configuration.recording.district_boundaries else {
+        return;
+    };
+
+    let mut stations_outside_districts = 0;
+    for station in stations.iter_mut() {
+        station.district = district_boundaries
+            .district_of(&station.location)
+            .map(str::to_string);
+
+        if station.district.is_none() {
+            stations_outside_districts += 1;
+        }
+    }
+
+    debug!(
+        districts = district_boundaries.number_of_districts(),
+        stations = stations.len(),
+        stations_outside_districts,
+        "Assigned districts to stations."
+    );
+}
+
+/// Loads the entity registry that attaches stable IDs to the stations and trips
+/// of a capture (see [`EntityRegistry`]). If the registry can't be read, the IDs are left out
+/// instead of minting new ones, which would break the continuity of the registered IDs.
+fn load_entity_registry(storage_root_path: &Path) -> Option<EntityRegistry> {
+    match EntityRegistry::load(storage_root_path) {
+        Ok(entity_registry) => Some(entity_registry),
+        Err(error) => {
+            warn!(
+                error = ?error,
+                "Failed to load entity registry, snapshots will not contain stable IDs."
+            );
+            None
         }
+    }
+}
+
+fn save_entity_registry(entity_registry: &EntityRegistry, storage_root_path: &Path) {
+    if let Err(error) = entity_registry.save(storage_root_path) {
+        warn!(
+            error = ?error,
+            "Failed to save entity registry, newly registered stable IDs may change."
+        );
+    }
+}
+
+/// Describes the stations that could not be fetched even when requested a second time.
+fn failed_stations_warning(
+    stations_that_failed_twice: &[(StationCode, miette::Report)],
+) -> Option<SnapshotWarning> {
+    if stations_that_failed_twice.is_empty() {
+        return None;
+    }
 
+    let failed_stations = stations_that_failed_twice
+        .iter()
+        .map(|(station_code, error)| {
+            let reason = error
+                .chain()
+                .map(|cause| cause.to_string())
+                .collect::<Vec<_>>()
+                .join(": ");
+
+            format!("{} ({})", station_code, reason)
+        })
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    Some(SnapshotWarning {
+        kind: SnapshotWarningKind::StationsFailed,
+        message: format!(
+            "{} station(s) could not be fetched, even when requested a second time: {}.",
+            stations_that_failed_twice.len(),
+            failed_stations
+        ),
+    })
+}
 
-        let station_with_trips = StationDetailsWithBusesAndTimetables::from_station_and_trips(
-            station,
-            trips_on_station,
-            timetables,
-        );
+async fn fetch_station_timetables(
+    configuration: &LppConfiguration,
+    client: &LppApiClient,
+    clock: &dyn Clock,
+    station_code: &StationCode,
+    route_groups: HashSet<BaseBusRoute>,
+    timetable_fetch_mode: TimetableFetchMode,
+    cancellation_token: Option<&CancellationToken>,
+) -> Result<Vec<RouteGroupTimetable>> {
+    let Some(max_route_groups_per_request) = configuration
+        .api
+        .max_route_groups_per_timetable_request
+        .filter(|max_route_groups| route_groups.len() > *max_route_groups)
+    else {
+        return fetch_timetables_for_route_groups(
+            configuration,
+            client,
+            clock,
+            station_code,
+            route_groups.into_iter().collect(),
+            timetable_fetch_mode,
+            cancellation_token,
+        )
+        .await;
+    };
+
+    // The station has too many route groups for a single request (e.g. it is a hub),
+    // so we split them into several requests and merge the results afterwards.
+    let mut sorted_route_groups: Vec<BaseBusRoute> = route_groups.into_iter().collect();
+    sorted_route_groups.sort();
+
+    debug!(
+        station_code = %station_code,
+        route_groups = sorted_route_groups.len(),
+        max_route_groups_per_request,
+        "Splitting timetable request for station into batches."
+    );
 
-        stations_with_bus_trips.push(station_with_trips);
+    let mut timetable_batches = Vec::new();
+    for route_group_batch in sorted_route_groups.chunks(max_route_groups_per_request) {
+        timetable_batches.push(
+            fetch_timetables_for_route_groups(
+                configuration,
+                client,
+                clock,
+                station_code,
+                route_group_batch.to_vec(),
+                timetable_fetch_mode,
+                cancellation_token,
+            )
+            .await?,
+        );
     }
 
+    Ok(merge_route_group_timetables(timetable_batches))
+}
 
-    // Now we'll fetch all bus routes and assign them a trip timetable.
-    debug!("Requesting all routes.");
-
-    let all_routes = retryable_async_with_exponential_backoff(
-        || fetch_all_routes(&configuration.api, client),
-        |result| match result {
-            Ok(details) => RetryableResult::Ok(details),
-            Err(error) => RetryableResult::TransientErr {
-                error,
-                override_retry_after: None,
-            },
+async fn fetch_timetables_for_route_groups(
+    configuration: &LppConfiguration,
+    client: &LppApiClient,
+    clock: &dyn Clock,
+    station_code: &StationCode,
+    route_groups: Vec<BaseBusRoute>,
+    timetable_fetch_mode: TimetableFetchMode,
+    cancellation_token: Option<&CancellationToken>,
+) -> Result<Vec<RouteGroupTimetable>> {
+    retryable_async_with_exponential_backoff(
+        || {
+            fetch_timetable(
+                &configuration.api,
+                client,
+                clock,
+                station_code,
+                route_groups.clone(),
+                timetable_fetch_mode,
+            )
         },
+        retry_lpp_api_errors,
         None,
+        cancellation_token,
     )
-    .instrument(info_span!("all-routes"))
+    .instrument(info_span!("timetable-on-station"))
     .await
     .into_diagnostic()
-    .wrap_err_with(|| miette!("Failed to fetch all routes."))?;
-
-
-    let mut routes_with_context = Vec::with_capacity(all_routes.len());
-
-    let number_of_all_routes = all_routes.len();
-
-    for (route_index, route) in all_routes.into_iter().enumerate() {
-        let captured_at = Utc::now();
-
+    .wrap_err_with(|| miette!("Failed to fetch timetables on station."))
+}
 
-        let raw_route_timetables = match bus_trip_to_timetable.get(&route.route) {
-            Some(timetable_map) => timetable_map,
-            None => {
-                // It's possible that we have some bad data that has
-                // no associated timetable data. In this case, we ignore the route.
-                warn!(
-                    current_route = route_index + 1,
-                    total_routes = number_of_all_routes,
-                    route = %route.route,
-                    "Did not collect any timetables for this route - will skip."
-                );
-                continue;
-            }
-        };
+fn add_timetables_to_trip_map(
+    bus_trip_to_timetable: &mut HashMap<BusRoute, HashMap<StationCode, TripTimetable>>,
+    station_code: &StationCode,
+    timetables: &[RouteGroupTimetable],
+) {
+    for group_timetable in timetables {
+        for trip_timetable in &group_timetable.trip_timetables {
+            bus_trip_to_timetable
+                .entry(trip_timetable.route.clone())
+                .or_default()
+                .insert(station_code.clone(), trip_timetable.clone());
+        }
+    }
+}
 
+/// Joins the stations on a trip with their timetables. Stations without a timetable
+/// get one interpolated from their neighbours, if possible, otherwise they are left out.
+///
+/// `total_stations_on_route` is the number of stations the LPP API reported for the trip.
+fn join_trip_with_timetables(
+    captured_at: DateTime<Utc>,
+    route: RouteDetails,
+    stations_on_route: Vec<StationOnRoute>,
+    total_stations_on_route: usize,
+    raw_route_timetables: &HashMap<StationCode, TripTimetable>,
+) -> TripWithStationsAndTimetables {
+    // The destination is resolved before stations without timetables are left out of the trip.
+    let destination_station_code = resolve_destination_station(&route, &stations_on_route);
+
+    let resolved_stations = resolve_trip_station_timetables(
+        stations_on_route,
+        raw_route_timetables,
+        route.route_shape.as_ref(),
+    );
 
+    let number_of_interpolated_stations = resolved_stations.number_of_interpolated_stations();
+    if number_of_interpolated_stations > 0 {
         debug!(
-            current_route = route_index + 1,
-            total_routes = number_of_all_routes,
-            "Requesting stations on route."
+            route = %route.route,
+            interpolated_stations = number_of_interpolated_stations,
+            "Interpolated timetables for stations on the bus route."
         );
-
-        let stations_on_route = retryable_async_with_exponential_backoff(
-            || fetch_stations_on_route(&configuration.api, client, route.trip_id.clone()),
-            |result| match result {
-                Ok(details) => RetryableResult::Ok(details),
-                Err(error) => RetryableResult::TransientErr {
-                    error,
-                    override_retry_after: None,
-                },
-            },
-            None,
-        )
-        .instrument(info_span!("fetch-one-route"))
-        .await
-        .into_diagnostic()
-        .wrap_err_with(|| miette!("Failed to fetch individual route."))?;
-
-        let Some(stations_on_route) = stations_on_route else {
-            warn!(
-                route_id = %route.route_id,
-                route = %route.route,
-                "Route did not contain any stations."
-            );
-            continue;
-        };
-
-
-        // Join with the per-station per-trip timetable data
-        // we collected into `bus_trip_to_timetable` earlier.
-        let mut stations_with_timetables = Vec::with_capacity(stations_on_route.len());
-
-        for station_on_route in stations_on_route {
-            let associated_station_timetable =
-                match raw_route_timetables.get(&station_on_route.station_code) {
-                    Some(timetable) => timetable,
-                    None => {
-                        // It's possible that just one station on the route's way
-                        // did not return a timetable. In that case, we consider it bad
-                        // data and ignore the entire route.
-                        error!(
-                            route = %route.route,
-                            station_code = %station_on_route.station_code,
-                            "Did not find a timetable for station on the bus route. \
-                            Will ignore the entire route (not fatal)."
-                        );
-                        continue;
-                    }
-                };
-
-            stations_with_timetables.push(TripStationWithTimetable {
-                station: station_on_route,
-                timetable: associated_station_timetable.clone(),
-            });
-        }
-
-
-        routes_with_context.push(TripWithStationsAndTimetables {
-            captured_at,
-            route_details: route,
-            stations_on_route_with_timetables: stations_with_timetables,
-        });
     }
 
-    // We've processed all the stations and all the routes, including their timetables.
-    info!("Finished requesting a snapshot of all stations and routes.");
-
+    for unresolved_station in &resolved_stations.unresolved_stations {
+        // It's possible that just one station on the route's way
+        // did not return a timetable and we could not estimate it. In that case,
+        // we consider it bad data and leave the station out of the route.
+        error!(
+            route = %route.route,
+            station_code = %unresolved_station.station_code,
+            "Did not find a timetable for station on the bus route and could not \
+            interpolate it. Will ignore the station (not fatal)."
+        );
+    }
 
-    let snapshot_time = Utc::now();
+    let stations_with_timetables = resolved_stations.stations_with_timetables;
 
-    let station_details_snapshot = AllStationsSnapshot::new(snapshot_time, stations_with_bus_trips);
-    let route_details_snapshot = AllRoutesSnapshot::new(snapshot_time, routes_with_context);
+    let completeness = compute_trip_data_completeness(
+        &stations_with_timetables,
+        total_stations_on_route,
+        route.route_shape.is_some(),
+    );
 
+    TripWithStationsAndTimetables {
+        captured_at,
+        route_details: route,
+        stable_route_id: None,
+        stable_trip_id: None,
+        stations_on_route_with_timetables: stations_with_timetables,
+        destination_station_code,
+        completeness: Some(completeness),
+    }
+}
 
+async fn save_station_and_route_snapshots(
+    configuration: &LppConfiguration,
+    station_storage: &StationStorage,
+    route_storage: &RouteStorage,
+    station_details_snapshot: &AllStationsSnapshot,
+    route_details_snapshot: &AllRoutesSnapshot,
+    phase_timings: &mut PhaseTimings,
+) -> Result<Vec<PathBuf>> {
     // We have the data we need, so it's not time-critical
     // that we save it at this exact moment; let's yield.
     yield_now().await;
 
     debug!("Saving station and route details to disk.");
 
+    let mut saved_file_paths = save_station_snapshot(
+        configuration,
+        station_storage,
+        station_details_snapshot,
+        phase_timings,
+    )?;
+    saved_file_paths.extend(save_route_snapshot(
+        configuration,
+        route_storage,
+        route_details_snapshot,
+        phase_timings,
+    )?);
+
+    info!("Snapshots of both route and station details have been successfully saved.");
+
+    Ok(saved_file_paths)
+}
 
-    // Save station details.
-    let station_details_file_path = station_storage.generate_json_file_path(snapshot_time);
+/// Saves (and signs) a station snapshot and makes it the latest one, unless it was rejected.
+/// Returns the paths of the saved files.
+fn save_station_snapshot(
+    configuration: &LppConfiguration,
+    station_storage: &StationStorage,
+    station_details_snapshot: &AllStationsSnapshot,
+    phase_timings: &mut PhaseTimings,
+) -> Result<Vec<PathBuf>> {
+    let snapshot_time = station_details_snapshot.captured_at;
+    let mut saved_file_paths = Vec::with_capacity(2);
+
+    // Rejected snapshots are kept for inspection, but must not become the latest snapshot.
+    let is_rejected = station_details_snapshot.status == Some(SnapshotStatus::Rejected);
+
+    let serialization_phase = phase_timings.start_phase("serialization");
+    let station_details_json = serialization_phase
+        .span()
+        .in_scope(|| serialize_to_json(station_details_snapshot))?;
+    serialization_phase.finish(1, phase_timings);
+
+    let mut station_details_file_path =
+        station_storage.generate_json_file_path(snapshot_time, &station_details_json);
+    if is_rejected {
+        station_details_file_path =
+            rejected_snapshot_file_path(configuration, &station_details_file_path)?;
+    }
 
-    save_json_to_file(
-        &station_details_snapshot,
-        &station_details_file_path,
-    )
-    .wrap_err_with(|| miette!("Failed to save station details snapshot."))?;
+    let write_phase = phase_timings.start_file_phase("write", &station_details_file_path);
+    write_phase.span().in_scope(|| -> Result<()> {
+        save_json_to_file(&station_details_json, &station_details_file_path)
+            .wrap_err_with(|| miette!("Failed to save station details snapshot."))?;
+        saved_file_paths.push(station_details_file_path.clone());
+
+        if let Some(signing_key) = &configuration.recording.snapshot_signing_key {
+            let signature_file_path = sign_file(signing_key, &station_details_file_path)
+                .wrap_err_with(|| miette!("Failed to sign station details snapshot."))?;
+            saved_file_paths.push(signature_file_path);
+        }
+
+        if !is_rejected {
+            station_storage
+                .update_latest(&station_details_file_path, snapshot_time)
+                .wrap_err_with(|| {
+                    miette!("Failed to update latest station details snapshot pointer.")
+                })?;
+        }
+
+        Ok(())
+    })?;
+    write_phase.finish(1, phase_timings);
 
     info!(
         file_path = %station_details_file_path.display(),
         "A snapshot of current station details have been saved to disk."
     );
 
+    Ok(saved_file_paths)
+}
+
+/// Saves (and signs) a route snapshot and makes it the latest one, unless it was rejected.
+/// Returns the paths of the saved files.
+fn save_route_snapshot(
+    configuration: &LppConfiguration,
+    route_storage: &RouteStorage,
+    route_details_snapshot: &AllRoutesSnapshot,
+    phase_timings: &mut PhaseTimings,
+) -> Result<Vec<PathBuf>> {
+    let snapshot_time = route_details_snapshot.captured_at;
+    let mut saved_file_paths = Vec::with_capacity(2);
+
+    // Rejected snapshots are kept for inspection, but must not become the latest snapshot.
+    let is_rejected = route_details_snapshot.status == Some(SnapshotStatus::Rejected);
+
+    let serialization_phase = phase_timings.start_phase("serialization");
+    let route_details_json = serialization_phase
+        .span()
+        .in_scope(|| serialize_to_json(route_details_snapshot))?;
+    serialization_phase.finish(1, phase_timings);
+
+    let mut route_details_file_path =
+        route_storage.generate_json_file_path(snapshot_time, &route_details_json);
+    if is_rejected {
+        route_details_file_path =
+            rejected_snapshot_file_path(configuration, &route_details_file_path)?;
+    }
+
+    let write_phase = phase_timings.start_file_phase("write", &route_details_file_path);
+    write_phase.span().in_scope(|| -> Result<()> {
+        save_json_to_file(&route_details_json, &route_details_file_path)
+            .wrap_err_with(|| miette!("Failed to save a snapshot of route details."))?;
+        saved_file_paths.push(route_details_file_path.clone());
 
-    // Save route details.
-    let route_details_file_path = route_storage.generate_json_file_path(snapshot_time);
+        if let Some(signing_key) = &configuration.recording.snapshot_signing_key {
+            let signature_file_path = sign_file(signing_key, &route_details_file_path)
+                .wrap_err_with(|| miette!("Failed to sign route details snapshot."))?;
+            saved_file_paths.push(signature_file_path);
+        }
+
+        if !is_rejected {
+            route_storage
+                .update_latest(&route_details_file_path, snapshot_time)
+                .wrap_err_with(|| {
+                    miette!("Failed to update latest route details snapshot pointer.")
+                })?;
+        }
 
-    save_json_to_file(&route_details_snapshot, &route_details_file_path)
-        .wrap_err_with(|| miette!("Failed to save a snapshot of route details."))?;
+        Ok(())
+    })?;
+    write_phase.finish(1, phase_timings);
 
     info!(
         file_path = %route_details_file_path.display(),
         "A snapshot of current route details have been saved to disk."
     );
 
+    Ok(saved_file_paths)
+}
+
+/// Summarizes the latest station and route snapshots in the storage root
+/// (see [`NetworkSummary`]), or returns `None` if there are no snapshots yet.
+fn latest_network_summary(storage_root: &StorageRoot) -> Result<Option<NetworkSummary>> {
+    let snapshot_archive = SnapshotArchive::open(storage_root)
+        .wrap_err_with(|| miette!("Failed to open snapshot archive."))?;
+
+    let Some(station_snapshot) = snapshot_archive
+        .latest_station_snapshot()
+        .wrap_err_with(|| miette!("Failed to load latest station details snapshot."))?
+    else {
+        return Ok(None);
+    };
+
+    let Some(route_snapshot) = snapshot_archive
+        .latest_route_snapshot()
+        .wrap_err_with(|| miette!("Failed to load latest route details snapshot."))?
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(NetworkSummary::from_snapshots(
+        &station_snapshot.snapshot,
+        &route_snapshot.snapshot,
+    )))
+}
 
-    info!("A full snapshot of both route and station details has been successfully saved.");
+/// Compares the snapshots that were just saved with the previous ones, logs the changelog
+/// and appends it to the changelog file in the storage root. Failures are logged, but
+/// don't fail the snapshot run.
+fn report_changes_since_previous_snapshot(
+    storage_root: &StorageRoot,
+    previous_network_summary: &mut Option<NetworkSummary>,
+) {
+    let network_summary = match latest_network_summary(storage_root) {
+        Ok(Some(network_summary)) => network_summary,
+        Ok(None) => return,
+        Err(error) => {
+            warn!(
+                error = ?error,
+                "Failed to summarize the latest snapshots, can't report changes since the previous snapshot."
+            );
+            return;
+        }
+    };
 
-    Ok(())
+    if let Some(previous_network_summary) = previous_network_summary.as_ref() {
+        let changelog = SnapshotChangelog::between(previous_network_summary, &network_summary);
+        info!(
+            "Changes since the previous snapshot:\n{}",
+            changelog
+        );
+
+        let changelog_file_path = storage_root.path().join(CHANGELOG_FILE_NAME);
+        match changelog.append_to_file(&changelog_file_path) {
+            Ok(()) => info!(
+                file_path = %changelog_file_path.display(),
+                "Changelog has been saved."
+            ),
+            Err(error) => warn!(
+                error = ?error,
+                file_path = %changelog_file_path.display(),
+                "Failed to save changelog."
+            ),
+        }
+    } else {
+        info!("There is no previous snapshot to compare this one to.");
+    }
+
+    *previous_network_summary = Some(network_summary);
 }
 
+
+/// Name of the run counter that counts saved station and route snapshot pairs.
+pub const CAPTURED_SNAPSHOTS_COUNTER: &str = "captured_snapshots";
+
+/// Name of the run counter that counts snapshots rejected by the acceptance policy.
+pub const REJECTED_SNAPSHOTS_COUNTER: &str = "rejected_snapshots";
+
+#[allow(clippy::too_many_arguments)]
 async fn station_and_route_details_snapshot_loop(
     configuration: LppConfiguration,
-    client: Client,
+    client: LppApiClient,
     cancellation_token: CancellationToken,
     run_mode: RunMode,
+    run_counters: RunCounters,
+    uploader: Option<SnapshotUploader>,
+    email_notifier: Option<EmailNotifier>,
+    clock: SharedClock,
 ) -> Result<()> {
     let stations_storage = configuration
         .recording
@@ -399,22 +1470,206 @@ async fn station_and_route_details_snapshot_loop(
         .routes()
         .wrap_err_with(|| miette!("Failed to initialize storage location for route details."))?;
 
+    if let Some(signing_key) = &configuration.recording.snapshot_signing_key {
+        let public_key_file_path = save_public_key_to_storage_root(
+            &configuration.recording.recording_storage_root,
+            signing_key,
+        )?;
+
+        info!(
+            file_path = %public_key_file_path.display(),
+            "Snapshot signing is enabled, public key has been saved."
+        );
+    }
+
+
+    // Summary of the previous snapshots, which the next ones are compared to.
+    let mut previous_network_summary =
+        match latest_network_summary(&configuration.recording.recording_storage_root) {
+            Ok(network_summary) => network_summary,
+            Err(error) => {
+                warn!(
+                    error = ?error,
+                    "Failed to summarize the latest existing snapshots, \
+                    the next snapshot won't be compared to them."
+                );
+                None
+            }
+        };
+
+    let mut capture_schedule = CaptureSchedule::starting_now(
+        configuration
+            .recording
+            .full_station_and_timetable_details_request_interval,
+        clock.clone(),
+    );
 
     #[allow(clippy::never_loop)]
     while !cancellation_token.is_cancelled() {
         let time_begin = Instant::now();
 
-        info!("Performing station and route snapshot.");
+        // Every log line of this snapshot run carries its identifier,
+        // so the run can be found in the log files later (see the `logs-for-run` command).
+        let run_id = Uuid::new_v4();
+        let run_span = info_span!("snapshot-run", run_id = %run_id);
 
-        make_station_and_route_snapshot(
-            &configuration,
-            &client,
-            &stations_storage,
-            &route_storage,
-        )
-        .await?;
+        let captured_snapshots = async {
+            if configuration.recording.preflight.enabled {
+                info!("Running pre-flight API probe.");
+
+                let preflight_report = run_preflight_probe(
+                    &configuration.api,
+                    &client,
+                    clock.as_ref(),
+                    &configuration.recording.preflight.station_code,
+                )
+                .await;
+                preflight_report.log();
+                preflight_report.into_result()?;
+            }
+
+            info!("Performing station and route snapshot.");
+
+            match configuration.recording.capture_mode {
+                CaptureMode::Full => {
+                    make_station_and_route_snapshot_with_retries(
+                        &configuration,
+                        &client,
+                        clock.as_ref(),
+                        &stations_storage,
+                        &route_storage,
+                        run_id,
+                        &cancellation_token,
+                    )
+                    .await
+                }
+                CaptureMode::TimetablesOnly => {
+                    make_timetables_only_snapshot(
+                        &configuration,
+                        &client,
+                        clock.as_ref(),
+                        &stations_storage,
+                        &route_storage,
+                        run_id,
+                        &cancellation_token,
+                    )
+                    .await
+                }
+            }
+        }
+        .instrument(run_span.clone())
+        .await;
+
+        let captured_snapshots = match captured_snapshots {
+            Ok(captured_snapshots) => captured_snapshots,
+            // Shutting down is a clean stop, whatever was captured by then has been saved.
+            Err(error) if cancellation_token.is_cancelled() => {
+                run_span.in_scope(|| {
+                    info!(
+                        error = ?error,
+                        "Snapshot was interrupted because the recorder is shutting down."
+                    )
+                });
+                break;
+            }
+            Err(error) => {
+                telemetry::record_failed_snapshot();
+                return Err(error);
+            }
+        };
+
+        if captured_snapshots.status == SnapshotStatus::Rejected {
+            run_counters.increment(REJECTED_SNAPSHOTS_COUNTER);
+            telemetry::record_snapshot(
+                captured_snapshots.status,
+                &captured_snapshots.coverage,
+                time_begin.elapsed(),
+            );
+
+            if run_mode == RunMode::Once {
+                return fail_if_rejected(
+                    captured_snapshots.status,
+                    &captured_snapshots.coverage,
+                );
+            }
+
+            // A single bad capture (e.g. during an API outage) shouldn't stop the recorder.
+            run_span.in_scope(|| {
+                error!(
+                    captured_stations = captured_snapshots.coverage.captured_stations,
+                    expected_stations = captured_snapshots.coverage.expected_stations,
+                    captured_routes = captured_snapshots.coverage.captured_routes,
+                    expected_routes = captured_snapshots.coverage.expected_routes,
+                    "Snapshot was rejected by the acceptance policy (and saved into the rejected \
+                    directory), will capture the next one as scheduled."
+                )
+            });
+
+            wait_for_next_capture(
+                &mut capture_schedule,
+                &cancellation_token,
+                configuration.recording.catch_up_missed_captures,
+            )
+            .await;
+            continue;
+        }
+
+        if let Some(uploader) = &uploader {
+            uploader
+                .upload_files(
+                    &captured_snapshots.saved_file_paths,
+                    &cancellation_token,
+                )
+                .instrument(run_span.clone())
+                .await;
+        }
+
+        run_span.in_scope(|| {
+            report_changes_since_previous_snapshot(
+                &configuration.recording.recording_storage_root,
+                &mut previous_network_summary,
+            )
+        });
+
+        run_counters.increment(CAPTURED_SNAPSHOTS_COUNTER);
+        run_counters.record_snapshot_run_id(run_id);
+        systemd::notify_watchdog();
+        telemetry::record_snapshot(
+            captured_snapshots.status,
+            &captured_snapshots.coverage,
+            time_begin.elapsed(),
+        );
+        run_span.in_scope(|| {
+            info!(
+                duration_seconds = time_begin.elapsed().as_secs(),
+                "Station and route snapshot complete."
+            )
+        });
 
-        info!("Station and route snapshot complete.");
+        if let Some(email_notifier) = email_notifier
+            .as_ref()
+            .filter(|email_notifier| email_notifier.sends_every_snapshot())
+        {
+            email_notifier
+                .send_snapshot_summary(&SnapshotSummary {
+                    run_id,
+                    captured_at: clock.now(),
+                    duration_seconds: time_begin.elapsed().as_secs(),
+                    status: captured_snapshots.status,
+                    captured_stations: captured_snapshots.coverage.captured_stations,
+                    expected_stations: captured_snapshots.coverage.expected_stations,
+                    captured_routes: captured_snapshots.coverage.captured_routes,
+                    expected_routes: captured_snapshots.coverage.expected_routes,
+                    warnings: captured_snapshots.warnings,
+                    saved_files: captured_snapshots
+                        .saved_file_paths
+                        .iter()
+                        .map(|file_path| file_path.display().to_string())
+                        .collect(),
+                })
+                .instrument(run_span.clone())
+                .await;
+        }
 
         if run_mode == RunMode::Once {
             info!("Run mode is \"once\", exiting.");
@@ -422,40 +1677,82 @@ async fn station_and_route_details_snapshot_loop(
         }
 
 
-        // Wait for the configured amount of time
-        // until the next snapshot should be captured.
-        let time_since_start_of_request = time_begin.elapsed();
+        wait_for_next_capture(
+            &mut capture_schedule,
+            &cancellation_token,
+            configuration.recording.catch_up_missed_captures,
+        )
+        .await;
+    }
 
-        let time_to_wait_until_next_capture = configuration
-            .recording
-            .full_station_and_timetable_details_request_interval
-            .saturating_sub(time_since_start_of_request);
+    info!("Station and route snapshotting loop has been cancelled, exiting.");
+    Ok(())
+}
 
+/// Waits until the next snapshot should be captured (or the recorder is shutting down).
+/// The schedule follows the wall clock, so captures that were missed while the system
+/// was asleep are noticed (and logged).
+async fn wait_for_next_capture(
+    capture_schedule: &mut CaptureSchedule,
+    cancellation_token: &CancellationToken,
+    catch_up_missed_captures: bool,
+) {
+    loop {
         info!(
-            sleep_duration_seconds = time_to_wait_until_next_capture.as_secs(),
+            next_capture_at = %capture_schedule.next_capture_at(),
             "Snapshot loop will sleep until it's time for the next station snapshot."
         );
 
-        tokio::time::sleep(time_to_wait_until_next_capture).await;
-    }
+        let Some(scheduled_capture) = cancellation_token
+            .run_until_cancelled(capture_schedule.wait_for_next_capture())
+            .await
+        else {
+            return;
+        };
 
-    info!("Station and route snapshotting loop has been cancelled, exiting.");
-    Ok(())
+        match scheduled_capture {
+            ScheduledCapture::OnTime => return,
+            ScheduledCapture::Overran => {
+                warn!("Snapshot took longer than the capture interval, capturing the next one right away.");
+                return;
+            }
+            ScheduledCapture::Missed { missed_captures } => {
+                warn!(
+                    missed_captures = missed_captures,
+                    "Missed scheduled snapshot capture(s), most likely because the system was asleep."
+                );
+
+                if catch_up_missed_captures {
+                    info!("Capturing a catch-up snapshot right away.");
+                    return;
+                }
+            }
+        }
+    }
 }
 
 
+#[allow(clippy::too_many_arguments)]
 pub fn initialize_station_and_route_details_snapshot_task(
     config: &LppConfiguration,
-    http_client: Client,
+    api_client: LppApiClient,
     cancellation_token: CancellationToken,
     run_mode: RunMode,
+    run_counters: RunCounters,
+    uploader: Option<SnapshotUploader>,
+    email_notifier: Option<EmailNotifier>,
+    clock: SharedClock,
 ) -> tokio::task::JoinHandle<Result<()>> {
     let station_fetching_span = info_span!("station-details-recorder");
     let station_details_fetching_future = station_and_route_details_snapshot_loop(
         config.clone(),
-        http_client,
+        api_client,
         cancellation_token,
         run_mode,
+        run_counters,
+        uploader,
+        email_notifier,
+        clock,
     )
     .instrument(station_fetching_span);
 
@@ -486,12 +1783,37 @@ pub enum RetryableError {
 
     #[error("Timed out while retrying operation.")]
     TimedOut,
+
+    #[error("Stopped retrying operation, the recorder is shutting down.")]
+    Cancelled,
+}
+
+/// Validator for [`retryable_async_with_exponential_backoff`] that retries all LPP API errors,
+/// waiting for as long as the API asked us to (via `Retry-After`) if we were rate-limited.
+fn retry_lpp_api_errors<R>(
+    result: Result<R, LppApiFetchError>,
+) -> RetryableResult<R, LppApiFetchError> {
+    match result {
+        Ok(value) => RetryableResult::Ok(value),
+        Err(error) => {
+            let override_retry_after = match &error {
+                LppApiFetchError::RateLimited { retry_after } => *retry_after,
+                _ => None,
+            };
+
+            RetryableResult::TransientErr {
+                error,
+                override_retry_after,
+            }
+        }
+    }
 }
 
 pub async fn retryable_async_with_exponential_backoff<C, F, O, P, E, R>(
     future_producer: C,
     future_output_validator: P,
     backoff: Option<ExponentialBackoff<backoff::SystemClock>>,
+    cancellation_token: Option<&CancellationToken>,
 ) -> Result<R, RetryableError>
 where
     C: Fn() -> F,
@@ -510,6 +1832,10 @@ where
     });
 
     loop {
+        if cancellation_token.is_some_and(CancellationToken::is_cancelled) {
+            return Err(RetryableError::Cancelled);
+        }
+
         // Generate a future and await it.
         let future_output = future_producer().await;
 
@@ -534,16 +1860,35 @@ where
                     "Encountered a transient error, will retry."
                 );
 
+                // The override replaces the delay, but must still fit into the time left for
+                // retrying. If the server asks us to wait longer than that, we give up right away.
+                let fits_into_retry_budget = |retry_after: Duration| {
+                    exponential_backoff
+                        .max_elapsed_time
+                        .map_or(true, |max_elapsed_time| {
+                            exponential_backoff.get_elapsed_time() + retry_after <= max_elapsed_time
+                        })
+                };
+
                 let real_retry_after = match override_retry_after {
-                    Some(after) => {
-                        exponential_backoff.next_backoff();
-                        Some(after)
-                    }
+                    Some(after) if !fits_into_retry_budget(after) => None,
+                    Some(after) => exponential_backoff.next_backoff().map(|_| after),
                     None => exponential_backoff.next_backoff(),
                 };
 
                 if let Some(retry_after) = real_retry_after {
-                    tokio::time::sleep(retry_after).await;
+                    match cancellation_token {
+                        Some(cancellation_token) => {
+                            if cancellation_token
+                                .run_until_cancelled(tokio::time::sleep(retry_after))
+                                .await
+                                .is_none()
+                            {
+                                return Err(RetryableError::Cancelled);
+                            }
+                        }
+                        None => tokio::time::sleep(retry_after).await,
+                    }
                 } else {
                     // We've hit the retry limit, abort.
                     return Err(RetryableError::TimedOut);
@@ -772,3 +2117,156 @@ pub fn initialize_route_state_recording(
 }
 
  */
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::{FixedOffset, TimeZone};
+
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn central_european_time() -> FixedOffset {
+        FixedOffset::east_opt(3600).unwrap()
+    }
+
+    fn central_european_summer_time() -> FixedOffset {
+        FixedOffset::east_opt(2 * 3600).unwrap()
+    }
+
+    #[test]
+    fn detect_service_day_at_local_midnight() {
+        let holiday_calendar = HolidayCalendar::from_entries(["12-25"]).unwrap();
+        let clock = ManualClock::new(
+            central_european_time()
+                .with_ymd_and_hms(2023, 12, 24, 23, 30, 0)
+                .unwrap(),
+        );
+
+        assert_eq!(
+            detect_service_day(&holiday_calendar, clock.as_ref()),
+            (
+                NaiveDate::from_ymd_opt(2023, 12, 24).unwrap(),
+                ServiceDayType::Sunday
+            )
+        );
+
+        // It's still the 24th in UTC, but the local service day is already Christmas.
+        clock.advance(chrono::Duration::hours(1));
+        assert_eq!(
+            clock.now().date_naive(),
+            NaiveDate::from_ymd_opt(2023, 12, 24).unwrap()
+        );
+        assert_eq!(
+            detect_service_day(&holiday_calendar, clock.as_ref()),
+            (
+                NaiveDate::from_ymd_opt(2023, 12, 25).unwrap(),
+                ServiceDayType::Holiday
+            )
+        );
+    }
+
+    #[test]
+    fn resolve_timetable_window_across_daylight_saving_time_transitions() {
+        let timetable_window = TimetableWindowPolicy::ServiceDayRemaining;
+        let next_hours = |clock: &ManualClock| match timetable_fetch_mode_for_capture(
+            &timetable_window,
+            clock,
+        ) {
+            TimetableFetchMode::Manual { next_hours, .. } => next_hours,
+            TimetableFetchMode::FullDay => unreachable!(),
+        };
+
+        // Clocks go forward from 02:00 to 03:00, so an hour later it's 03:30.
+        let clock = ManualClock::new(
+            central_european_time()
+                .with_ymd_and_hms(2024, 3, 31, 1, 30, 0)
+                .unwrap(),
+        );
+        assert_eq!(next_hours(&clock), 23);
+
+        clock.advance(chrono::Duration::hours(1));
+        clock.set_utc_offset(central_european_summer_time());
+        assert_eq!(next_hours(&clock), 21);
+
+        // Clocks go back from 03:00 to 02:00, so an hour later it's 02:30 again.
+        let clock = ManualClock::new(
+            central_european_summer_time()
+                .with_ymd_and_hms(2024, 10, 27, 2, 30, 0)
+                .unwrap(),
+        );
+        assert_eq!(next_hours(&clock), 22);
+
+        clock.advance(chrono::Duration::hours(1));
+        clock.set_utc_offset(central_european_time());
+        assert_eq!(next_hours(&clock), 22);
+        assert_eq!(
+            detect_service_day(&HolidayCalendar::default(), &*clock).0,
+            NaiveDate::from_ymd_opt(2024, 10, 27).unwrap()
+        );
+    }
+
+    fn retry_backoff(max_elapsed_time: Duration) -> ExponentialBackoff<backoff::SystemClock> {
+        ExponentialBackoffBuilder::new()
+            .with_initial_interval(Duration::from_secs(5))
+            .with_randomization_factor(0.0)
+            .with_max_elapsed_time(Some(max_elapsed_time))
+            .build()
+    }
+
+    fn rate_limited_until_attempt(
+        attempts: &AtomicUsize,
+        successful_attempt: usize,
+        retry_after: Duration,
+    ) -> std::result::Result<usize, LppApiFetchError> {
+        let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+
+        if attempt < successful_attempt {
+            Err(LppApiFetchError::RateLimited {
+                retry_after: Some(retry_after),
+            })
+        } else {
+            Ok(attempt)
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_after_the_delay_the_api_asked_for() {
+        let attempts = AtomicUsize::new(0);
+        let started_at = Instant::now();
+
+        // The backoff alone would wait 5 seconds before retrying.
+        let attempt = retryable_async_with_exponential_backoff(
+            || async { rate_limited_until_attempt(&attempts, 2, Duration::from_millis(50)) },
+            retry_lpp_api_errors,
+            Some(retry_backoff(Duration::from_secs(60))),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(attempt, 2);
+        assert!(started_at.elapsed() >= Duration::from_millis(50));
+        assert!(started_at.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn give_up_if_the_api_asks_to_wait_longer_than_the_retry_budget() {
+        let attempts = AtomicUsize::new(0);
+        let started_at = Instant::now();
+
+        let result = retryable_async_with_exponential_backoff(
+            || async { rate_limited_until_attempt(&attempts, 2, Duration::from_secs(3600)) },
+            retry_lpp_api_errors,
+            Some(retry_backoff(Duration::from_secs(60))),
+            None,
+        )
+        .await;
+
+        assert!(matches!(result, Err(RetryableError::TimedOut)));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,186 @@
+use std::{fmt::Write, path::Path, time::Duration};
+
+use tokio::time::Instant;
+use tracing::{debug, field, info, info_span, Span};
+use uuid::Uuid;
+
+use crate::storage::{Intent, IntentLog};
+
+
+/// A phase of a snapshot capture that is being measured (see [`PhaseTimings::start_phase`]).
+pub struct SnapshotPhase {
+    name: &'static str,
+    span: Span,
+    started_at: Instant,
+
+    /// Finished (dropped) along with the phase, see [`IntentLog::begin`].
+    _intent: Option<Intent>,
+}
+
+impl SnapshotPhase {
+    /// The span of this phase; all of the phase's operations should run inside it.
+    pub fn span(&self) -> Span {
+        self.span.clone()
+    }
+
+    /// Records the duration and the number of processed items
+    /// (e.g. stations) of this phase.
+    pub fn finish(self, count: usize, phase_timings: &mut PhaseTimings) {
+        let duration = self.started_at.elapsed();
+
+        self.span.record("count", count);
+        self.span.record("duration_ms", duration.as_millis() as u64);
+        self.span
+            .in_scope(|| debug!("Snapshot phase has finished."));
+
+        phase_timings.add(self.name, duration, count);
+    }
+}
+
+
+#[derive(Clone, PartialEq, Debug)]
+struct PhaseTiming {
+    name: &'static str,
+    duration: Duration,
+    count: usize,
+}
+
+/// Durations and item counts of the phases of a single snapshot capture,
+/// logged as a table once the capture is complete.
+#[derive(Default, Debug)]
+pub struct PhaseTimings {
+    phases: Vec<PhaseTiming>,
+
+    /// If set, each phase is recorded in the intent log (with the snapshot run identifier).
+    intent_log: Option<(IntentLog, Uuid)>,
+}
+
+impl PhaseTimings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records each phase in the intent log, so a crash during it is reported on the next start.
+    pub fn with_intent_log(mut self, intent_log: IntentLog, run_id: Uuid) -> Self {
+        self.intent_log = Some((intent_log, run_id));
+        self
+    }
+
+    /// Starts measuring a phase, e.g. `stations`.
+    pub fn start_phase(&self, name: &'static str) -> SnapshotPhase {
+        self.start_phase_with_target(name, None)
+    }
+
+    /// Starts measuring a phase that writes the given file.
+    pub fn start_file_phase(&self, name: &'static str, file_path: &Path) -> SnapshotPhase {
+        self.start_phase_with_target(name, Some(file_path.display().to_string()))
+    }
+
+    fn start_phase_with_target(&self, name: &'static str, target: Option<String>) -> SnapshotPhase {
+        let intent = self
+            .intent_log
+            .as_ref()
+            .map(|(intent_log, run_id)| intent_log.begin(Some(*run_id), name, target));
+
+        SnapshotPhase {
+            name,
+            span: info_span!(
+                "phase",
+                phase = name,
+                count = field::Empty,
+                duration_ms = field::Empty
+            ),
+            started_at: Instant::now(),
+            _intent: intent,
+        }
+    }
+
+    /// Adds a measurement. Phases that are measured more than once
+    /// (e.g. serialization of each snapshot) are summed up.
+    pub fn add(&mut self, name: &'static str, duration: Duration, count: usize) {
+        match self.phases.iter_mut().find(|phase| phase.name == name) {
+            Some(phase) => {
+                phase.duration += duration;
+                phase.count += count;
+            }
+            None => self.phases.push(PhaseTiming {
+                name,
+                duration,
+                count,
+            }),
+        }
+    }
+
+    /// Logs the phase timing table at info level.
+    pub fn log_table(&self) {
+        info!("Snapshot phase timings:\n{}", self.render_table());
+    }
+
+    fn render_table(&self) -> String {
+        let total_duration: Duration = self.phases.iter().map(|phase| phase.duration).sum();
+
+        let name_width = self
+            .phases
+            .iter()
+            .map(|phase| phase.name.len())
+            .chain(["phase".len(), "total".len()])
+            .max()
+            .unwrap_or_default();
+
+        let mut table = String::new();
+
+        // Writing into a String can't fail.
+        let _ = writeln!(
+            table,
+            "{:<name_width$} {:>10} {:>7} {:>8}",
+            "phase", "duration", "share", "count"
+        );
+
+        for phase in &self.phases {
+            let share = match total_duration.is_zero() {
+                true => 0.0,
+                false => phase.duration.as_secs_f64() / total_duration.as_secs_f64() * 100.0,
+            };
+
+            let _ = writeln!(
+                table,
+                "{:<name_width$} {:>9.2}s {:>6.1}% {:>8}",
+                phase.name,
+                phase.duration.as_secs_f64(),
+                share,
+                phase.count
+            );
+        }
+
+        let _ = write!(
+            table,
+            "{:<name_width$} {:>9.2}s",
+            "total",
+            total_duration.as_secs_f64()
+        );
+
+        table
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_phase_timing_table() {
+        let mut phase_timings = PhaseTimings::new();
+        phase_timings.add("stations", Duration::from_millis(3000), 250);
+        phase_timings.add("serialization", Duration::from_millis(400), 1);
+        phase_timings.add("serialization", Duration::from_millis(600), 1);
+
+        assert_eq!(
+            phase_timings.render_table(),
+            "phase           duration   share    count\n\
+            stations           3.00s   75.0%      250\n\
+            serialization      1.00s   25.0%        2\n\
+            total              4.00s"
+        );
+    }
+}
@@ -0,0 +1,386 @@
+//! Pre-flight probe of the LPP API: one request per endpoint against a known station
+//! (and a trip that stops there), checking that each response still parses.
+//!
+//! Snapshots run it before capturing when `lpp.recording.preflight.enabled` is set, so a broken
+//! endpoint fails the run right away instead of an hour into the station loop.
+//! The `doctor` command runs it on demand and prints the full report.
+
+use std::{
+    fmt::{self, Display},
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use miette::Diagnostic;
+use thiserror::Error;
+use tracing::{error, info};
+
+use crate::{
+    api::{
+        arrivals_on_route::fetch_arrivals_on_route,
+        client::LppApiClient,
+        errors::{LppApiEndpoint, LppApiFetchError},
+        routes::fetch_all_routes,
+        routes_on_station::fetch_routes_on_station,
+        station_details::fetch_station_details,
+        stations_on_route::fetch_stations_on_route,
+        timetable::{fetch_timetable, TimetableFetchMode},
+        StationCode,
+        TripId,
+    },
+    clock::Clock,
+    configuration::LppApiConfiguration,
+};
+
+
+/// Outcome of probing a single endpoint.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ProbeOutcome {
+    /// The response was parsed. Example summary: `3 route groups`.
+    Passed { summary: String },
+
+    /// The request failed or its response could not be parsed.
+    Failed { error: String },
+
+    /// The endpoint was not probed, because the probe it needed an input from failed.
+    Skipped { reason: String },
+}
+
+impl Display for ProbeOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeOutcome::Passed { summary } => write!(f, "ok ({})", summary),
+            ProbeOutcome::Failed { error } => write!(f, "FAILED: {}", error),
+            ProbeOutcome::Skipped { reason } => write!(f, "skipped ({})", reason),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EndpointProbe {
+    pub endpoint: LppApiEndpoint,
+    pub outcome: ProbeOutcome,
+
+    /// Zero for skipped probes.
+    pub duration: Duration,
+}
+
+/// Outcomes of probing every endpoint, in the order they were probed.
+#[derive(Clone, Debug)]
+pub struct PreflightReport {
+    pub station_code: StationCode,
+    pub probes: Vec<EndpointProbe>,
+}
+
+/// Some endpoints failed the pre-flight probe, see [`PreflightReport::into_result`].
+#[derive(Error, Debug, Diagnostic)]
+#[error("Pre-flight API probe failed: {}.", .failures.join("; "))]
+#[diagnostic(help(
+    "The LPP API is either unavailable or its responses changed. \
+    Run the `doctor` command for a report of every endpoint."
+))]
+pub struct PreflightFailedError {
+    /// Example: `station/timetable: HTTP request failed with server error: 500 Internal Server Error`.
+    pub failures: Vec<String>,
+}
+
+impl PreflightReport {
+    pub fn is_healthy(&self) -> bool {
+        self.probes
+            .iter()
+            .all(|probe| !matches!(probe.outcome, ProbeOutcome::Failed { .. }))
+    }
+
+    /// Logs the outcome of each probe (failures as errors).
+    pub fn log(&self) {
+        for probe in &self.probes {
+            match &probe.outcome {
+                ProbeOutcome::Failed { error } => error!(
+                    endpoint = %probe.endpoint,
+                    duration_ms = probe.duration.as_millis() as u64,
+                    error = error,
+                    "Pre-flight probe failed."
+                ),
+                outcome => info!(
+                    endpoint = %probe.endpoint,
+                    duration_ms = probe.duration.as_millis() as u64,
+                    outcome = %outcome,
+                    "Pre-flight probe finished."
+                ),
+            }
+        }
+    }
+
+    /// Renders the report as a plain-text table, one endpoint per line.
+    pub fn to_console_table(&self) -> String {
+        let endpoint_width = self
+            .probes
+            .iter()
+            .map(|probe| probe.endpoint.to_string().len())
+            .max()
+            .unwrap_or_default();
+
+        let mut table = format!(
+            "Pre-flight probe of the LPP API (station {}):\n\n",
+            self.station_code
+        );
+
+        for probe in &self.probes {
+            table.push_str(&format!(
+                "{:<width$}  {:>6} ms  {}\n",
+                probe.endpoint.to_string(),
+                probe.duration.as_millis(),
+                probe.outcome,
+                width = endpoint_width
+            ));
+        }
+
+        table
+    }
+
+    pub fn into_result(self) -> Result<(), PreflightFailedError> {
+        let failures: Vec<String> = self
+            .probes
+            .into_iter()
+            .filter_map(|probe| match probe.outcome {
+                ProbeOutcome::Failed { error } => Some(format!("{}: {}", probe.endpoint, error)),
+                _ => None,
+            })
+            .collect();
+
+        match failures.is_empty() {
+            true => Ok(()),
+            false => Err(PreflightFailedError { failures }),
+        }
+    }
+}
+
+
+/// Probes every endpoint once. The timetable request asks for one route group of `station_code`
+/// and the route requests use a trip that stops there (or any trip, if the station has none).
+///
+/// Probes are not retried: a single failed request fails the probe.
+pub async fn run_preflight_probe(
+    api_configuration: &LppApiConfiguration,
+    client: &LppApiClient,
+    clock: &dyn Clock,
+    station_code: &StationCode,
+) -> PreflightReport {
+    let mut probes = Vec::with_capacity(6);
+
+    let (probe, _) = probe_endpoint(
+        LppApiEndpoint::StationDetails,
+        fetch_station_details(api_configuration, client),
+        |stations| {
+            let contains_station = stations
+                .iter()
+                .any(|station| &station.station_code == station_code);
+
+            format!(
+                "{} stations, {}",
+                stations.len(),
+                match contains_station {
+                    true => "including the probed station",
+                    false => "without the probed station",
+                }
+            )
+        },
+    )
+    .await;
+    probes.push(probe);
+
+    let (probe, trips_on_station) = probe_endpoint(
+        LppApiEndpoint::RoutesOnStation,
+        fetch_routes_on_station(api_configuration, client, station_code),
+        |trips| format!("{} trips", trips.len()),
+    )
+    .await;
+    probes.push(probe);
+
+    let first_trip_on_station = trips_on_station
+        .as_ref()
+        .and_then(|trips| trips.first())
+        .cloned();
+
+    let probe = match &first_trip_on_station {
+        Some(trip) => {
+            probe_endpoint(
+                LppApiEndpoint::Timetable,
+                fetch_timetable(
+                    api_configuration,
+                    client,
+                    clock,
+                    station_code,
+                    [trip.route.to_base_route()],
+                    TimetableFetchMode::FullDay,
+                ),
+                |timetables| {
+                    format!(
+                        "{} trip timetables of route group {}",
+                        timetables
+                            .iter()
+                            .map(|timetable| timetable.trip_timetables.len())
+                            .sum::<usize>(),
+                        trip.route.to_base_route()
+                    )
+                },
+            )
+            .await
+            .0
+        }
+        None => skipped_probe(
+            LppApiEndpoint::Timetable,
+            "no trips on the probed station to take a route group from",
+        ),
+    };
+    probes.push(probe);
+
+    let (probe, all_routes) = probe_endpoint(
+        LppApiEndpoint::Routes,
+        fetch_all_routes(api_configuration, client),
+        |routes| format!("{} trips", routes.len()),
+    )
+    .await;
+    probes.push(probe);
+
+    let probed_trip_id: Option<TripId> =
+        first_trip_on_station.map(|trip| trip.trip_id).or_else(|| {
+            all_routes
+                .as_ref()
+                .and_then(|routes| routes.first())
+                .map(|route| route.trip_id.clone())
+        });
+
+    match &probed_trip_id {
+        Some(trip_id) => {
+            let (probe, _) = probe_endpoint(
+                LppApiEndpoint::StationsOnRoute,
+                fetch_stations_on_route(api_configuration, client, trip_id.clone()),
+                |stations| match stations {
+                    Some(stations) => format!("{} stations on trip {}", stations.len(), trip_id),
+                    None => format!("no stations on trip {}", trip_id),
+                },
+            )
+            .await;
+            probes.push(probe);
+
+            let (probe, _) = probe_endpoint(
+                LppApiEndpoint::ArrivalsOnRoute,
+                fetch_arrivals_on_route(api_configuration, client, trip_id),
+                |stations| format!("{} stations on trip {}", stations.len(), trip_id),
+            )
+            .await;
+            probes.push(probe);
+        }
+        None => {
+            for endpoint in [
+                LppApiEndpoint::StationsOnRoute,
+                LppApiEndpoint::ArrivalsOnRoute,
+            ] {
+                probes.push(skipped_probe(
+                    endpoint,
+                    "no trip to probe, as neither trips on the station nor all routes were fetched",
+                ));
+            }
+        }
+    }
+
+    PreflightReport {
+        station_code: station_code.clone(),
+        probes,
+    }
+}
+
+/// Makes a single probe request, summarizing the parsed response with `summarize`.
+async fn probe_endpoint<T, F, S>(
+    endpoint: LppApiEndpoint,
+    request: F,
+    summarize: S,
+) -> (EndpointProbe, Option<T>)
+where
+    F: Future<Output = Result<T, LppApiFetchError>>,
+    S: FnOnce(&T) -> String,
+{
+    let started_at = Instant::now();
+    let result = request.await;
+    let duration = started_at.elapsed();
+
+    match result {
+        Ok(response) => (
+            EndpointProbe {
+                endpoint,
+                outcome: ProbeOutcome::Passed {
+                    summary: summarize(&response),
+                },
+                duration,
+            },
+            Some(response),
+        ),
+        Err(error) => (
+            EndpointProbe {
+                endpoint,
+                outcome: ProbeOutcome::Failed {
+                    error: error.to_string(),
+                },
+                duration,
+            },
+            None,
+        ),
+    }
+}
+
+fn skipped_probe(endpoint: LppApiEndpoint, reason: &str) -> EndpointProbe {
+    EndpointProbe {
+        endpoint,
+        outcome: ProbeOutcome::Skipped {
+            reason: reason.to_string(),
+        },
+        duration: Duration::ZERO,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fail_only_on_failed_probes() {
+        let mut report = PreflightReport {
+            station_code: StationCode::new("600011"),
+            probes: vec![
+                EndpointProbe {
+                    endpoint: LppApiEndpoint::StationDetails,
+                    outcome: ProbeOutcome::Passed {
+                        summary: String::from("1 stations, including the probed station"),
+                    },
+                    duration: Duration::from_millis(120),
+                },
+                skipped_probe(
+                    LppApiEndpoint::Timetable,
+                    "no trips on the probed station to take a route group from",
+                ),
+            ],
+        };
+
+        assert!(report.is_healthy());
+        assert!(report.clone().into_result().is_ok());
+
+        report.probes.push(EndpointProbe {
+            endpoint: LppApiEndpoint::Routes,
+            outcome: ProbeOutcome::Failed {
+                error: String::from("HTTP request failed with server error: 500"),
+            },
+            duration: Duration::from_millis(80),
+        });
+
+        assert!(!report.is_healthy());
+        assert!(report
+            .to_console_table()
+            .contains("route/routes                 80 ms  FAILED: "));
+        assert_eq!(
+            report.into_result().unwrap_err().to_string(),
+            "Pre-flight API probe failed: route/routes: HTTP request failed with server error: 500."
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::formats::{SnapshotWarning, SnapshotWarningKind};
+use crate::api::{timetable::TripTimetable, BusRoute, StationCode};
+
+
+/// How routes are matched with the timetables we collected from individual stations.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum RouteMatchingMode {
+    /// Routes are matched by their full name, including any suffix (e.g. `3G`).
+    FullRoute,
+
+    /// The LPP API did not return any sub-routes (it stopped honoring `show-subroutes=1`),
+    /// so routes are matched only by their base route number (e.g. `3`).
+    BaseRoute,
+}
+
+impl RouteMatchingMode {
+    /// Detects whether the LPP API returned sub-routes. Suffixed routes (e.g. `3G`, `19B`)
+    /// always exist in the LPP network, so if none of the routes
+    /// have a suffix, the API must have omitted them.
+    pub fn detect<'a, I>(routes: I) -> Self
+    where
+        I: IntoIterator<Item = &'a BusRoute>,
+    {
+        let mut has_any_routes = false;
+
+        for route in routes {
+            if route.suffix.is_some() {
+                return Self::FullRoute;
+            }
+
+            has_any_routes = true;
+        }
+
+        match has_any_routes {
+            true => Self::BaseRoute,
+            false => Self::FullRoute,
+        }
+    }
+
+    /// Returns the warning to include in the snapshot, if any.
+    pub fn snapshot_warning(&self) -> Option<SnapshotWarning> {
+        match self {
+            RouteMatchingMode::FullRoute => None,
+            RouteMatchingMode::BaseRoute => Some(SnapshotWarning {
+                kind: SnapshotWarningKind::SubroutesMissing,
+                message: String::from(
+                    "The LPP API did not return any sub-routes (e.g. 3G or 19B). Routes were \
+                    matched with timetables only by their base route number, so sub-routes \
+                    of the same route may share a timetable.",
+                ),
+            }),
+        }
+    }
+}
+
+
+/// Looks up the per-station timetables of a route. An exact match is always preferred;
+/// in [`RouteMatchingMode::BaseRoute`] mode, timetables of any route with the same base route
+/// are used otherwise (the one with the lexicographically smallest name, to stay deterministic).
+pub fn find_route_timetables<'a>(
+    bus_trip_to_timetable: &'a HashMap<BusRoute, HashMap<StationCode, TripTimetable>>,
+    route: &BusRoute,
+    matching_mode: RouteMatchingMode,
+) -> Option<&'a HashMap<StationCode, TripTimetable>> {
+    if let Some(route_timetables) = bus_trip_to_timetable.get(route) {
+        return Some(route_timetables);
+    }
+
+    match matching_mode {
+        RouteMatchingMode::FullRoute => None,
+        RouteMatchingMode::BaseRoute => {
+            let base_route = route.to_base_route();
+
+            bus_trip_to_timetable
+                .iter()
+                .filter(|(timetable_route, _)| timetable_route.to_base_route() == base_route)
+                .min_by_key(|(timetable_route, _)| timetable_route.to_string())
+                .map(|(_, route_timetables)| route_timetables)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routes(route_names: &[&str]) -> Vec<BusRoute> {
+        route_names
+            .iter()
+            .map(|route_name| BusRoute::from_route_name(*route_name).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn detect_missing_subroutes() {
+        assert_eq!(
+            RouteMatchingMode::detect(&routes(&["3G", "11", "N5"])),
+            RouteMatchingMode::FullRoute
+        );
+        assert_eq!(
+            RouteMatchingMode::detect(&routes(&["3", "11", "N5"])),
+            RouteMatchingMode::BaseRoute
+        );
+        assert_eq!(
+            RouteMatchingMode::detect(&routes(&[])),
+            RouteMatchingMode::FullRoute
+        );
+    }
+
+    #[test]
+    fn fall_back_to_base_route() {
+        let timetable_routes = routes(&["3", "19"]);
+        let bus_trip_to_timetable: HashMap<BusRoute, HashMap<StationCode, TripTimetable>> =
+            timetable_routes
+                .into_iter()
+                .map(|route| (route, HashMap::new()))
+                .collect();
+
+        let route = BusRoute::from_route_name("3G").unwrap();
+
+        assert!(find_route_timetables(
+            &bus_trip_to_timetable,
+            &route,
+            RouteMatchingMode::FullRoute
+        )
+        .is_none());
+        assert!(find_route_timetables(
+            &bus_trip_to_timetable,
+            &route,
+            RouteMatchingMode::BaseRoute
+        )
+        .is_some());
+        assert!(find_route_timetables(
+            &bus_trip_to_timetable,
+            &BusRoute::from_route_name("6B").unwrap(),
+            RouteMatchingMode::BaseRoute
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,219 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tracing::debug;
+
+use crate::clock::SharedClock;
+
+/// Longest single sleep while waiting for the next capture. Monotonic timers
+/// don't advance while the system is suspended, so we sleep in short steps
+/// and check the wall clock in between.
+const MAX_SLEEP_STEP: Duration = Duration::from_secs(30);
+
+/// How late a capture may start before it is considered missed.
+const LATENESS_TOLERANCE: Duration = Duration::from_secs(60);
+
+
+/// Why [`CaptureSchedule::wait_for_next_capture`] returned.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScheduledCapture {
+    /// The next capture is due (give or take [`LATENESS_TOLERANCE`]).
+    OnTime,
+
+    /// The previous capture took longer than the capture interval,
+    /// so the next one was already due when we started waiting.
+    Overran,
+
+    /// The wall clock jumped past one or more scheduled captures while we were
+    /// waiting, most likely because the system was asleep.
+    Missed { missed_captures: u32 },
+}
+
+/// Schedules captures on a fixed wall-clock cadence (every `interval` after the first capture),
+/// so that a late capture doesn't shift all the following ones.
+#[derive(Clone)]
+pub struct CaptureSchedule {
+    clock: SharedClock,
+    interval: chrono::Duration,
+    next_capture_at: DateTime<Utc>,
+}
+
+impl CaptureSchedule {
+    /// Creates a schedule whose first capture happens right now
+    /// (i.e. the next one is due after `interval`).
+    pub fn starting_now(interval: Duration, clock: SharedClock) -> Self {
+        let interval = chrono::Duration::from_std(interval).unwrap_or(chrono::Duration::MAX);
+        let next_capture_at = clock
+            .now()
+            .checked_add_signed(interval)
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+
+        Self {
+            clock,
+            interval,
+            next_capture_at,
+        }
+    }
+
+    pub fn next_capture_at(&self) -> DateTime<Utc> {
+        self.next_capture_at
+    }
+
+    /// Waits until the next capture is due and moves the schedule on to the one after it.
+    ///
+    /// If captures were missed, the schedule is realigned to the first capture time (on the
+    /// original cadence) that is still in the future; it's up to the caller to decide whether
+    /// to capture right away or to wait again.
+    pub async fn wait_for_next_capture(&mut self) -> ScheduledCapture {
+        if self.clock.now() > self.next_capture_at + tolerance() {
+            let (_, next_capture_at) = realign_schedule(
+                self.next_capture_at,
+                self.clock.now(),
+                self.interval,
+            );
+            self.next_capture_at = next_capture_at;
+
+            return ScheduledCapture::Overran;
+        }
+
+        loop {
+            let now = self.clock.now();
+            if now >= self.next_capture_at {
+                break;
+            }
+
+            let remaining = (self.next_capture_at - now).to_std().unwrap_or_default();
+            self.clock.sleep(remaining.min(MAX_SLEEP_STEP)).await;
+        }
+
+        let now = self.clock.now();
+        let scheduled_capture_at = self.next_capture_at;
+
+        let (missed_captures, next_capture_at) =
+            realign_schedule(scheduled_capture_at, now, self.interval);
+        self.next_capture_at = next_capture_at;
+
+        if now - scheduled_capture_at > tolerance() {
+            ScheduledCapture::Missed { missed_captures }
+        } else {
+            debug!(
+                next_capture_at = %self.next_capture_at,
+                "Capture is due."
+            );
+
+            ScheduledCapture::OnTime
+        }
+    }
+}
+
+fn tolerance() -> chrono::Duration {
+    chrono::Duration::from_std(LATENESS_TOLERANCE).unwrap_or_else(|_| chrono::Duration::zero())
+}
+
+/// Given a capture that was scheduled at `scheduled_at`, but is only starting `now`, returns
+/// how many scheduled captures have passed (including the one at `scheduled_at`) and when
+/// the next capture on the original cadence is due.
+fn realign_schedule(
+    scheduled_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+    interval: chrono::Duration,
+) -> (u32, DateTime<Utc>) {
+    let overdue_milliseconds = (now - scheduled_at).num_milliseconds().max(0);
+    let interval_milliseconds = interval.num_milliseconds().max(1);
+
+    let additional_passed_captures = overdue_milliseconds / interval_milliseconds;
+    let passed_captures = additional_passed_captures + 1;
+
+    let next_capture_at = scheduled_at
+        + chrono::Duration::milliseconds(interval_milliseconds.saturating_mul(passed_captures));
+
+    (
+        u32::try_from(passed_captures).unwrap_or(u32::MAX),
+        next_capture_at,
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, TimeZone};
+
+    use super::*;
+    use crate::clock::{Clock, ManualClock};
+
+    #[test]
+    fn realign_to_original_cadence_after_sleeping() {
+        let scheduled_at = Utc.with_ymd_and_hms(2023, 11, 6, 12, 0, 0).unwrap();
+        let interval = chrono::Duration::hours(6);
+
+        assert_eq!(
+            realign_schedule(scheduled_at, scheduled_at, interval),
+            (
+                1,
+                Utc.with_ymd_and_hms(2023, 11, 6, 18, 0, 0).unwrap()
+            )
+        );
+
+        // Woke up at 01:30 the next day, having missed the 12:00, 18:00 and 00:00 captures.
+        assert_eq!(
+            realign_schedule(
+                scheduled_at,
+                Utc.with_ymd_and_hms(2023, 11, 7, 1, 30, 0).unwrap(),
+                interval
+            ),
+            (
+                3,
+                Utc.with_ymd_and_hms(2023, 11, 7, 6, 0, 0).unwrap()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn follow_schedule_across_days_and_suspensions() {
+        let central_european_time = FixedOffset::east_opt(3600).unwrap();
+        let clock = ManualClock::new(
+            central_european_time
+                .with_ymd_and_hms(2023, 11, 6, 23, 0, 0)
+                .unwrap(),
+        );
+
+        let mut schedule =
+            CaptureSchedule::starting_now(Duration::from_secs(6 * 3600), clock.clone());
+        assert_eq!(
+            schedule.next_capture_at(),
+            Utc.with_ymd_and_hms(2023, 11, 7, 4, 0, 0).unwrap()
+        );
+
+        // Waiting crosses midnight, the capture happens on the next day on time.
+        assert_eq!(
+            schedule.wait_for_next_capture().await,
+            ScheduledCapture::OnTime
+        );
+        assert_eq!(
+            clock.now(),
+            Utc.with_ymd_and_hms(2023, 11, 7, 4, 0, 0).unwrap()
+        );
+
+        // The system is suspended for 13 hours, missing the 10:00 and 16:00 captures.
+        clock.suspend_during_next_sleep(chrono::Duration::hours(13));
+        assert_eq!(
+            schedule.wait_for_next_capture().await,
+            ScheduledCapture::Missed { missed_captures: 2 }
+        );
+        assert_eq!(
+            schedule.next_capture_at(),
+            Utc.with_ymd_and_hms(2023, 11, 7, 22, 0, 0).unwrap()
+        );
+
+        // A (catch-up) capture takes until past the next scheduled capture.
+        clock.advance(chrono::Duration::hours(6));
+        assert_eq!(
+            schedule.wait_for_next_capture().await,
+            ScheduledCapture::Overran
+        );
+        assert_eq!(
+            schedule.next_capture_at(),
+            Utc.with_ymd_and_hms(2023, 11, 8, 4, 0, 0).unwrap()
+        );
+    }
+}
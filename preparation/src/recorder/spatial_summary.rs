@@ -0,0 +1,145 @@
+use std::{collections::BTreeMap, f64::consts::PI};
+
+use serde::{Deserialize, Serialize};
+
+use super::formats::StationDetailsWithBusesAndTimetables;
+use crate::api::GeographicalLocation;
+
+/// Zoom level of the map tiles stations are counted in (at this zoom,
+/// a tile around Ljubljana is roughly 1.7 km wide).
+pub const STATION_TILE_ZOOM: u8 = 14;
+
+
+/// Where the stations of a station snapshot are, so a map can set its initial viewport
+/// and shade tiles by station density without going over every station first.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct StationSpatialSummary {
+    /// Smallest box containing all stations, in GeoJSON `bbox` order:
+    /// `[min_longitude, min_latitude, max_longitude, max_latitude]`.
+    pub bounding_box: [f64; 4],
+
+    /// Average location of all stations.
+    pub centroid: GeographicalLocation,
+
+    /// Zoom level of the tiles in `stations_per_tile`.
+    pub tile_zoom: u8,
+
+    /// Number of stations in each (Web Mercator, i.e. "slippy map") tile
+    /// that contains at least one station, ordered by `x` and then `y`.
+    pub stations_per_tile: Vec<StationTileCount>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StationTileCount {
+    pub x: u32,
+    pub y: u32,
+    pub stations: usize,
+}
+
+impl StationSpatialSummary {
+    /// Summarizes the locations of the given stations, or returns `None` if there are none.
+    pub fn for_stations(stations: &[StationDetailsWithBusesAndTimetables]) -> Option<Self> {
+        let first_location = stations.first()?.location;
+
+        let mut bounding_box = [
+            first_location.longitude,
+            first_location.latitude,
+            first_location.longitude,
+            first_location.latitude,
+        ];
+        let mut latitude_sum = 0.0;
+        let mut longitude_sum = 0.0;
+        let mut stations_per_tile: BTreeMap<(u32, u32), usize> = BTreeMap::new();
+
+        for station in stations {
+            let location = station.location;
+
+            bounding_box[0] = bounding_box[0].min(location.longitude);
+            bounding_box[1] = bounding_box[1].min(location.latitude);
+            bounding_box[2] = bounding_box[2].max(location.longitude);
+            bounding_box[3] = bounding_box[3].max(location.latitude);
+
+            latitude_sum += location.latitude;
+            longitude_sum += location.longitude;
+
+            *stations_per_tile
+                .entry(tile_containing(location, STATION_TILE_ZOOM))
+                .or_default() += 1;
+        }
+
+        let number_of_stations = stations.len() as f64;
+
+        Some(Self {
+            bounding_box,
+            centroid: GeographicalLocation::new(
+                latitude_sum / number_of_stations,
+                longitude_sum / number_of_stations,
+            ),
+            tile_zoom: STATION_TILE_ZOOM,
+            stations_per_tile: stations_per_tile
+                .into_iter()
+                .map(|((x, y), stations)| StationTileCount { x, y, stations })
+                .collect(),
+        })
+    }
+}
+
+/// Returns the `(x, y)` coordinates of the Web Mercator tile containing `location`
+/// (see <https://wiki.openstreetmap.org/wiki/Slippy_map_tilenames>).
+fn tile_containing(location: GeographicalLocation, zoom: u8) -> (u32, u32) {
+    let number_of_tiles = f64::from(1u32 << zoom);
+    let latitude = location.latitude.to_radians();
+
+    let x = (location.longitude + 180.0) / 360.0 * number_of_tiles;
+    let y = (1.0 - (latitude.tan() + 1.0 / latitude.cos()).ln() / PI) / 2.0 * number_of_tiles;
+
+    let last_tile = number_of_tiles - 1.0;
+    (
+        x.floor().clamp(0.0, last_tile) as u32,
+        y.floor().clamp(0.0, last_tile) as u32,
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_fixtures::station_at;
+
+    #[test]
+    fn summarize_station_locations() {
+        assert!(StationSpatialSummary::for_stations(&[]).is_none());
+
+        // Bavarski dvor (twice, i.e. both directions) and Črnuče.
+        let summary = StationSpatialSummary::for_stations(&[
+            station_at("600011", 46.0580, 14.5060),
+            station_at("600012", 46.0584, 14.5062),
+            station_at("300011", 46.1020, 14.5300),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            summary.bounding_box,
+            [14.5060, 46.0580, 14.5300, 46.1020]
+        );
+        assert!((summary.centroid.latitude - 46.0728).abs() < 1e-9);
+        assert!((summary.centroid.longitude - 14.5140666).abs() < 1e-6);
+
+        assert_eq!(summary.tile_zoom, 14);
+        assert_eq!(
+            summary.stations_per_tile,
+            vec![
+                StationTileCount {
+                    x: 8852,
+                    y: 5824,
+                    stations: 2
+                },
+                StationTileCount {
+                    x: 8853,
+                    y: 5822,
+                    stations: 1
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,364 @@
+//! The `stations` phase shared by full and timetables-only captures: requesting every station
+//! (up to `max_concurrent_requests` at a time), retrying failed stations once more at the end
+//! and leaving out the ones the deadline didn't leave time for.
+
+use std::future::Future;
+
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt};
+use miette::Result;
+use tracing::{debug, info, warn};
+
+use super::{
+    deadline::CaptureDeadline,
+    formats::StationDetailsWithBusesAndTimetables,
+    StationTripsAndTimetables,
+};
+use crate::{
+    api::{station_details::StationDetails, timetable::RouteGroupTimetable, StationCode},
+    clock::Clock,
+    configuration::LppConfiguration,
+};
+
+
+/// A station that is requested in the `stations` phase.
+pub(super) trait StationToFetch {
+    /// What is fetched for the station (e.g. its trips and timetables).
+    type Fetched;
+
+    fn station_code(&self) -> &StationCode;
+
+    fn name(&self) -> &str;
+
+    fn into_captured_station(
+        self,
+        captured_at: DateTime<Utc>,
+        fetched: Self::Fetched,
+    ) -> StationDetailsWithBusesAndTimetables;
+}
+
+/// Full captures fetch the trips and timetables of each station.
+impl StationToFetch for StationDetails {
+    type Fetched = StationTripsAndTimetables;
+
+    fn station_code(&self) -> &StationCode {
+        &self.station_code
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn into_captured_station(
+        self,
+        captured_at: DateTime<Utc>,
+        (trips_on_station, timetables): Self::Fetched,
+    ) -> StationDetailsWithBusesAndTimetables {
+        StationDetailsWithBusesAndTimetables::from_station_and_trips(
+            captured_at,
+            self,
+            trips_on_station,
+            timetables,
+        )
+    }
+}
+
+/// Timetables-only captures reuse the stations of an earlier snapshot
+/// and only fetch their timetables again.
+impl StationToFetch for StationDetailsWithBusesAndTimetables {
+    type Fetched = Vec<RouteGroupTimetable>;
+
+    fn station_code(&self) -> &StationCode {
+        &self.station_code
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn into_captured_station(
+        self,
+        captured_at: DateTime<Utc>,
+        timetables: Self::Fetched,
+    ) -> StationDetailsWithBusesAndTimetables {
+        StationDetailsWithBusesAndTimetables {
+            captured_at: Some(captured_at),
+            timetables,
+            ..self
+        }
+    }
+}
+
+
+/// Outcome of the `stations` phase.
+pub(super) struct FetchedStations {
+    /// In the order the stations were given in.
+    pub stations_with_bus_trips: Vec<StationDetailsWithBusesAndTimetables>,
+
+    pub stations_without_route_groups: usize,
+
+    /// Stations whose requests failed even when they were requested again at the end.
+    pub stations_that_failed_twice: Vec<(StationCode, miette::Report)>,
+
+    /// Stations that were not requested because the deadline passed.
+    pub unprocessed_station_codes: Vec<StationCode>,
+}
+
+/// Requests every station with `fetch_station`, which returns `None` for stations
+/// without route groups. Up to `max_concurrent_requests` stations are requested at the same time.
+///
+/// Stations whose requests failed (even after retrying) are requested once more after all other
+/// stations, so a temporary outage doesn't end the snapshot.
+pub(super) async fn fetch_stations<S, F, Fut>(
+    configuration: &LppConfiguration,
+    clock: &dyn Clock,
+    deadline: &CaptureDeadline,
+    stations: Vec<S>,
+    fetch_station: F,
+) -> FetchedStations
+where
+    S: StationToFetch,
+    F: Fn(&S) -> Fut,
+    Fut: Future<Output = Result<Option<S::Fetched>>>,
+{
+    let total_number_of_stations = stations.len();
+
+    let mut fetched_stations = FetchedStations {
+        stations_with_bus_trips: Vec::with_capacity(total_number_of_stations),
+        stations_without_route_groups: 0,
+        stations_that_failed_twice: Vec::new(),
+        unprocessed_station_codes: Vec::new(),
+    };
+    let mut failed_stations = Vec::new();
+
+    // The results are handled in the original order of the stations,
+    // so the snapshot stays in that order.
+    let fetch_station = &fetch_station;
+    let mut station_fetches = stream::iter(stations.into_iter().enumerate())
+        .map(|(station_index, station)| async move {
+            if deadline.has_passed(clock) {
+                return (station_index, station, None);
+            }
+
+            let station_captured_at = clock.now();
+
+            debug!(
+                current_station = station_index + 1,
+                total_stations = total_number_of_stations,
+                station_name = station.name(),
+                station_code = %station.station_code(),
+                "Requesting station."
+            );
+
+            let fetch_result = fetch_station(&station).await;
+
+            (
+                station_index,
+                station,
+                Some((station_captured_at, fetch_result)),
+            )
+        })
+        .buffered(configuration.recording.max_concurrent_requests);
+
+    while let Some((station_index, station, fetch)) = station_fetches.next().await {
+        let Some((station_captured_at, fetch_result)) = fetch else {
+            fetched_stations
+                .unprocessed_station_codes
+                .push(station.station_code().clone());
+            continue;
+        };
+
+        match fetch_result {
+            Ok(Some(fetched)) => fetched_stations
+                .stations_with_bus_trips
+                .push(station.into_captured_station(station_captured_at, fetched)),
+            Ok(None) => {
+                debug!(
+                    current_station = station_index + 1,
+                    total_stations = total_number_of_stations,
+                    station_name = station.name(),
+                    station_code = %station.station_code(),
+                    "Station has no route groups, will not request a timetable."
+                );
+                fetched_stations.stations_without_route_groups += 1;
+            }
+            Err(error) => {
+                warn!(
+                    error = ?error,
+                    station_name = station.name(),
+                    station_code = %station.station_code(),
+                    "Failed to fetch station, will try again after all other stations."
+                );
+                failed_stations.push(station);
+            }
+        }
+    }
+
+    if !failed_stations.is_empty() {
+        info!(
+            failed_stations = failed_stations.len(),
+            "Requesting stations that failed once again."
+        );
+    }
+
+    for station in failed_stations {
+        if deadline.has_passed(clock) {
+            fetched_stations
+                .unprocessed_station_codes
+                .push(station.station_code().clone());
+            continue;
+        }
+
+        let station_captured_at = clock.now();
+
+        match fetch_station(&station).await {
+            Ok(Some(fetched)) => fetched_stations
+                .stations_with_bus_trips
+                .push(station.into_captured_station(station_captured_at, fetched)),
+            Ok(None) => fetched_stations.stations_without_route_groups += 1,
+            Err(error) => {
+                warn!(
+                    error = ?error,
+                    station_name = station.name(),
+                    station_code = %station.station_code(),
+                    "Failed to fetch station for the second time, it will be missing from the snapshot."
+                );
+                fetched_stations
+                    .stations_that_failed_twice
+                    .push((station.station_code().clone(), error));
+            }
+        }
+    }
+
+    fetched_stations
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+    use chrono::{FixedOffset, TimeZone};
+    use miette::miette;
+    use url::Url;
+
+    use super::*;
+    use crate::{api::test_fixtures::station, clock::ManualClock};
+
+    /// Configuration that requests one station at a time, so stations are requested in order.
+    fn configuration() -> LppConfiguration {
+        let mut recording = toml::Table::new();
+        recording.insert(String::from("max_concurrent_requests"), 1.into());
+
+        LppConfiguration::for_tests(
+            &Url::parse("http://127.0.0.1/").unwrap(),
+            &std::env::temp_dir(),
+            recording,
+        )
+    }
+
+    fn stations(station_codes: &[&str]) -> Vec<StationDetails> {
+        station_codes
+            .iter()
+            .map(|station_code| station(station_code, "STATION", &["6B"]))
+            .collect()
+    }
+
+    fn station_codes(stations: &[StationDetailsWithBusesAndTimetables]) -> Vec<String> {
+        stations
+            .iter()
+            .map(|station| station.station_code.to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn request_failed_stations_once_more_at_the_end() {
+        let clock = ManualClock::new(
+            FixedOffset::east_opt(3600)
+                .unwrap()
+                .with_ymd_and_hms(2023, 11, 6, 4, 0, 0)
+                .unwrap(),
+        );
+        let deadline = CaptureDeadline::after(clock.now(), None);
+
+        let requests_per_station: Mutex<HashMap<String, usize>> = Mutex::new(HashMap::new());
+
+        let fetched_stations = fetch_stations(
+            &configuration(),
+            clock.as_ref(),
+            &deadline,
+            stations(&["600011", "600012", "600013", "600014"]),
+            |station: &StationDetails| {
+                let station_code = station.station_code.to_string();
+                let previous_requests = {
+                    let mut requests_per_station = requests_per_station.lock().unwrap();
+                    let requests = requests_per_station.entry(station_code.clone()).or_default();
+                    *requests += 1;
+                    *requests - 1
+                };
+
+                async move {
+                    match (station_code.as_str(), previous_requests) {
+                        // Fails on the first request only.
+                        ("600012", 0) => Err(miette!("Temporary outage.")),
+                        // Has no route groups.
+                        ("600013", _) => Ok(None),
+                        // Always fails.
+                        ("600014", _) => Err(miette!("Station is broken.")),
+                        _ => Ok(Some((Vec::new(), Vec::new()))),
+                    }
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(
+            station_codes(&fetched_stations.stations_with_bus_trips),
+            ["600011", "600012"]
+        );
+        assert_eq!(fetched_stations.stations_without_route_groups, 1);
+        assert_eq!(fetched_stations.stations_that_failed_twice.len(), 1);
+        assert_eq!(
+            fetched_stations.stations_that_failed_twice[0].0,
+            StationCode::new("600014")
+        );
+        assert!(fetched_stations.unprocessed_station_codes.is_empty());
+        assert_eq!(requests_per_station.lock().unwrap()["600014"], 2);
+    }
+
+    #[tokio::test]
+    async fn leave_out_stations_after_the_deadline() {
+        let clock = ManualClock::new(
+            FixedOffset::east_opt(3600)
+                .unwrap()
+                .with_ymd_and_hms(2023, 11, 6, 4, 0, 0)
+                .unwrap(),
+        );
+        let deadline = CaptureDeadline::after(clock.now(), Some(Duration::from_secs(60 * 60)));
+
+        let fetched_stations = fetch_stations(
+            &configuration(),
+            clock.as_ref(),
+            &deadline,
+            stations(&["600011", "600012", "600013"]),
+            |station: &StationDetails| {
+                // The second station takes longer than the deadline allows.
+                if station.station_code == StationCode::new("600012") {
+                    clock.advance(chrono::Duration::hours(2));
+                }
+
+                async { Ok(Some((Vec::new(), Vec::new()))) }
+            },
+        )
+        .await;
+
+        assert_eq!(
+            station_codes(&fetched_stations.stations_with_bus_trips),
+            ["600011", "600012"]
+        );
+        assert_eq!(
+            fetched_stations.unprocessed_station_codes,
+            [StationCode::new("600013")]
+        );
+    }
+}
@@ -0,0 +1,577 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use miette::{miette, Context, Result};
+use tracing::{debug, info, warn, Instrument};
+use uuid::Uuid;
+
+use super::{
+    acceptance::CaptureCoverage,
+    add_timetables_to_trip_map,
+    assign_station_districts,
+    deadline::{CaptureDeadline, UnprocessedWork},
+    destinations::unresolved_destinations_warning,
+    detect_service_day,
+    evaluate_capture_coverage,
+    failed_stations_warning,
+    fetch_station_timetables,
+    formats::{
+        AllRoutesSnapshot,
+        AllStationsSnapshot,
+        ReusedSnapshotReference,
+        StationDetailsWithBusesAndTimetables,
+    },
+    hub_coverage::report_hub_coverage,
+    join_trip_with_timetables,
+    phase_timing::PhaseTimings,
+    route_groups_on_station,
+    route_matching::{find_route_timetables, RouteMatchingMode},
+    save_station_and_route_snapshots,
+    station_fetch::{fetch_stations, FetchedStations},
+    timetable_fetch_mode_for_capture,
+    warn_if_subroutes_are_missing,
+    CapturedSnapshots,
+};
+use crate::{
+    api::client::LppApiClient,
+    cancellation_token::CancellationToken,
+    clock::Clock,
+    configuration::LppConfiguration,
+    storage::{
+        ArchivableSnapshot,
+        ArchivedSnapshot,
+        LatestSnapshotError,
+        RouteStorage,
+        SnapshotArchive,
+        StationStorage,
+    },
+};
+
+
+/// Captures a snapshot by only refreshing the timetables. The station list, trips on
+/// each station and stations on each route are reused from the latest stored snapshots,
+/// which the new snapshots reference in their `metadata_reused_from` field.
+///
+/// Stations that were left out of a route in the reused snapshot (because they had
+/// no timetable) stay left out, as the reused snapshot does not contain them.
+pub(super) async fn make_timetables_only_snapshot(
+    configuration: &LppConfiguration,
+    client: &LppApiClient,
+    clock: &dyn Clock,
+    station_storage: &StationStorage,
+    route_storage: &RouteStorage,
+    run_id: Uuid,
+    cancellation_token: &CancellationToken,
+) -> Result<CapturedSnapshots> {
+    let capture_started_at = clock.now();
+    let deadline = CaptureDeadline::after(
+        capture_started_at,
+        configuration.recording.snapshot_deadline,
+    )
+    .or_when_cancelled(cancellation_token.clone());
+
+    let snapshot_archive = SnapshotArchive::open(&configuration.recording.recording_storage_root)
+        .wrap_err_with(|| miette!("Failed to open snapshot archive."))?;
+
+    let reused_station_snapshot =
+        latest_archived_snapshot(snapshot_archive.latest_station_snapshot())?;
+    let reused_route_snapshot = latest_archived_snapshot(snapshot_archive.latest_route_snapshot())?;
+
+    let reused_station_snapshot_file_name = reused_station_snapshot.file_name();
+    let reused_route_snapshot_file_name = reused_route_snapshot.file_name();
+    let reused_station_snapshot = reused_station_snapshot.snapshot;
+    let reused_route_snapshot = reused_route_snapshot.snapshot;
+
+    info!(
+        station_snapshot = reused_station_snapshot_file_name,
+        route_snapshot = reused_route_snapshot_file_name,
+        "Reusing station and route metadata, will only refresh timetables."
+    );
+
+    let (service_date, service_day_type) =
+        detect_service_day(&configuration.recording.holiday_calendar, clock);
+    let timetable_fetch_mode =
+        timetable_fetch_mode_for_capture(&configuration.recording.timetable_window, clock);
+
+    // The station details aren't fetched again, so we detect missing
+    // sub-routes from the reused trips on each station instead.
+    let route_matching_mode = RouteMatchingMode::detect(
+        reused_station_snapshot
+            .station_details
+            .iter()
+            .flat_map(|station| station.trips_on_station.iter())
+            .map(|trip| &trip.route),
+    );
+    warn_if_subroutes_are_missing(route_matching_mode);
+
+
+    let mut reused_stations = reused_station_snapshot.station_details;
+    if configuration.recording.prioritize_hub_stations {
+        // Stable sort, so stations with the same number of route groups keep their order.
+        reused_stations.sort_by_cached_key(|station| {
+            Reverse(route_groups_on_station(&station.trips_on_station).len())
+        });
+
+        debug!("Will fetch stations with the most route groups first.");
+    }
+
+    let mut phase_timings = PhaseTimings::new().with_intent_log(
+        configuration
+            .recording
+            .recording_storage_root
+            .intent_log()
+            .clone(),
+        run_id,
+    );
+
+    // Stations without route groups have no timetables to refresh.
+    let total_number_of_stations = reused_stations.len();
+    reused_stations
+        .retain(|station| !route_groups_on_station(&station.trips_on_station).is_empty());
+    let stations_without_route_groups = total_number_of_stations - reused_stations.len();
+
+    let deadline = &deadline;
+    let stations_phase = phase_timings.start_phase("stations");
+    let FetchedStations {
+        mut stations_with_bus_trips,
+        stations_without_route_groups: _,
+        stations_that_failed_twice,
+        unprocessed_station_codes,
+    } = fetch_stations(
+        configuration,
+        clock,
+        deadline,
+        reused_stations,
+        |station: &StationDetailsWithBusesAndTimetables| {
+            let station_code = station.station_code.clone();
+            let route_groups = route_groups_on_station(&station.trips_on_station);
+
+            async move {
+                fetch_station_timetables(
+                    configuration,
+                    client,
+                    clock,
+                    &station_code,
+                    route_groups,
+                    timetable_fetch_mode,
+                    deadline.cancellation_token(),
+                )
+                .await
+                .map(Some)
+            }
+        },
+    )
+    .instrument(stations_phase.span())
+    .await;
+    stations_phase.finish(stations_with_bus_trips.len(), &mut phase_timings);
+    let stations_captured_at = clock.now();
+
+    let mut bus_trip_to_timetable = HashMap::new();
+    for station in &stations_with_bus_trips {
+        add_timetables_to_trip_map(
+            &mut bus_trip_to_timetable,
+            &station.station_code,
+            &station.timetables,
+        );
+    }
+
+
+    let routes_phase = phase_timings.start_phase("routes");
+    let number_of_reused_routes = reused_route_snapshot.routes.len();
+    let mut routes_with_context = Vec::with_capacity(number_of_reused_routes);
+
+    for reused_route in reused_route_snapshot.routes {
+        let route = reused_route.route_details;
+
+        let Some(raw_route_timetables) = find_route_timetables(
+            &bus_trip_to_timetable,
+            &route.route,
+            route_matching_mode,
+        ) else {
+            warn!(
+                route = %route.route,
+                "Did not collect any timetables for this route - will skip."
+            );
+            continue;
+        };
+
+        let stations_on_route: Vec<_> = reused_route
+            .stations_on_route_with_timetables
+            .into_iter()
+            .map(|station_with_timetable| station_with_timetable.station)
+            .collect();
+
+        let total_stations_on_route = reused_route
+            .completeness
+            .map(|completeness| completeness.total_stations)
+            .unwrap_or(stations_on_route.len());
+
+        routes_with_context.push(join_trip_with_timetables(
+            clock.now(),
+            route,
+            stations_on_route,
+            total_stations_on_route,
+            raw_route_timetables,
+        ));
+    }
+
+    routes_phase.finish(routes_with_context.len(), &mut phase_timings);
+
+    info!("Finished refreshing timetables of all stations and routes.");
+
+    // Stations on routes are reused, so refreshing routes doesn't issue any requests
+    // and only stations can be left unprocessed by the deadline.
+    let unprocessed_work = UnprocessedWork {
+        station_codes: unprocessed_station_codes,
+        routes: Vec::new(),
+    };
+
+    let capture_coverage = CaptureCoverage {
+        expected_stations: total_number_of_stations - stations_without_route_groups,
+        captured_stations: stations_with_bus_trips.len(),
+        expected_routes: number_of_reused_routes,
+        captured_routes: routes_with_context.len(),
+    };
+    let snapshot_status = evaluate_capture_coverage(
+        configuration,
+        &capture_coverage,
+        &unprocessed_work,
+    );
+
+    let mut snapshot_warnings: Vec<_> =
+        route_matching_mode.snapshot_warning().into_iter().collect();
+    snapshot_warnings.extend(unprocessed_work.report());
+    snapshot_warnings.extend(failed_stations_warning(
+        &stations_that_failed_twice,
+    ));
+    snapshot_warnings.extend(report_hub_coverage(
+        &configuration.recording.critical_hub_stations,
+        &stations_with_bus_trips,
+    ));
+    snapshot_warnings.extend(unresolved_destinations_warning(
+        &routes_with_context,
+    ));
+    snapshot_warnings.extend(capture_coverage.snapshot_warning(snapshot_status));
+
+
+    assign_station_districts(configuration, &mut stations_with_bus_trips);
+
+    let snapshot_time = clock.now();
+
+    let station_details_snapshot = AllStationsSnapshot::new(
+        snapshot_time,
+        Some(run_id),
+        service_date,
+        service_day_type,
+        Some(ReusedSnapshotReference {
+            file_name: reused_station_snapshot_file_name,
+            captured_at: reused_station_snapshot.captured_at,
+        }),
+        snapshot_warnings.clone(),
+        stations_with_bus_trips,
+    )
+    .with_capture_window(capture_started_at, stations_captured_at)
+    .with_status(snapshot_status);
+    let route_details_snapshot = AllRoutesSnapshot::new(
+        snapshot_time,
+        Some(run_id),
+        service_date,
+        service_day_type,
+        Some(ReusedSnapshotReference {
+            file_name: reused_route_snapshot_file_name,
+            captured_at: reused_route_snapshot.captured_at,
+        }),
+        snapshot_warnings.clone(),
+        routes_with_context,
+    )
+    .with_capture_window(capture_started_at, snapshot_time)
+    .with_status(snapshot_status);
+
+    let saved_file_paths = save_station_and_route_snapshots(
+        configuration,
+        station_storage,
+        route_storage,
+        &station_details_snapshot,
+        &route_details_snapshot,
+        &mut phase_timings,
+    )
+    .await?;
+
+    phase_timings.log_table();
+
+    Ok(CapturedSnapshots {
+        saved_file_paths,
+        status: snapshot_status,
+        coverage: capture_coverage,
+        warnings: snapshot_warnings,
+    })
+}
+
+
+/// Unwraps the latest archived snapshot of some kind.
+fn latest_archived_snapshot<S>(
+    latest_snapshot: Result<Option<ArchivedSnapshot<S>>, LatestSnapshotError>,
+) -> Result<ArchivedSnapshot<S>>
+where
+    S: ArchivableSnapshot,
+{
+    let latest_snapshot = latest_snapshot
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to load latest {} snapshot.",
+                S::DESCRIPTION
+            )
+        })?
+        .ok_or_else(|| {
+            miette!(
+                "No {} snapshot to reuse - capture a full snapshot first.",
+                S::DESCRIPTION
+            )
+        })?;
+
+    debug!(
+        file_path = %latest_snapshot.file_path.display(),
+        compression = ?latest_snapshot.compression,
+        serialization = ?latest_snapshot.serialization,
+        captured_at = %latest_snapshot.snapshot.captured_at(),
+        "Loaded {} snapshot to reuse.",
+        S::DESCRIPTION
+    );
+
+    Ok(latest_snapshot)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone, Utc};
+
+    use super::*;
+    use crate::{
+        api::{
+            client::stub_server::start_stub_server,
+            routes::RouteDetails,
+            routes_on_station::TripOnStation,
+            stations_on_route::StationOnRoute,
+            test_fixtures::timetable,
+            timetable::{RouteGroupTimetable, TripTimetable},
+            BaseBusRoute,
+            BusRoute,
+            GeographicalLocation,
+            RouteId,
+            StationCode,
+            TripId,
+        },
+        calendar::ServiceDayType,
+        clock::ManualClock,
+        recorder::formats::{TripStationWithTimetable, TripWithStationsAndTimetables},
+        storage::StorageRoot,
+    };
+
+    /// Timetable of the only station, with a single departure of route 6B at 7:10.
+    const STATION_TIMETABLE_RESPONSE: &str = r#"{"success":true,"data":{
+        "station":{"ref_id":"600011","name":"BAVARSKI DVOR"},
+        "route_groups":[{"route_group_number":"6","routes":[{
+            "timetable":[{"hour":7,"minutes":[10],"is_current":false,"timestamp":"2023-11-07T07:10:00"}],
+            "stations":[{"ref_id":"600011","name":"BAVARSKI DVOR","order_no":1}],
+            "name":"ČRNUČE","parent_name":"BAVARSKI DVOR - ČRNUČE","group_name":"6",
+            "route_number_prefix":"","route_number_suffix":"B","is_garage":0
+        }]}]
+    }}"#;
+
+    fn route() -> BusRoute {
+        BusRoute::from_route_name("6B").unwrap()
+    }
+
+    /// Timetable of route 6B with a single departure at 5:00.
+    fn stale_timetable() -> TripTimetable {
+        timetable("6B", "BAVARSKI DVOR - ČRNUČE", &[(5, 0)])
+    }
+
+    fn departures(timetable: &TripTimetable) -> Vec<(u8, u8)> {
+        timetable
+            .timetable
+            .iter()
+            .map(|entry| (entry.hour, entry.minute))
+            .collect()
+    }
+
+    /// Saves a full snapshot of a network with a single station served by route 6B
+    /// into `storage_root`, as the earlier snapshot a timetables-only capture reuses.
+    fn save_reused_snapshots(storage_root: &StorageRoot, captured_at: DateTime<Utc>) {
+        let service_date = NaiveDate::from_ymd_opt(2023, 11, 6).unwrap();
+
+        let station_snapshot = AllStationsSnapshot::new(
+            captured_at,
+            None,
+            service_date,
+            ServiceDayType::Weekday,
+            None,
+            Vec::new(),
+            vec![StationDetailsWithBusesAndTimetables {
+                captured_at: Some(captured_at),
+                station_code: StationCode::new("600011"),
+                stable_id: None,
+                internal_station_id: 1,
+                name: String::from("BAVARSKI DVOR"),
+                location: GeographicalLocation::new(46.058, 14.506),
+                district: None,
+                trips_on_station: vec![TripOnStation {
+                    route_id: RouteId::new("R6"),
+                    trip_id: TripId::new("T6"),
+                    route: route(),
+                    short_trip_name: Some(String::from("ČRNUČE")),
+                    trip_name: String::from("BAVARSKI DVOR - ČRNUČE"),
+                    ends_in_garage: false,
+                }],
+                timetables: vec![RouteGroupTimetable {
+                    route_group_name: BaseBusRoute::new_from_number(6),
+                    trip_timetables: vec![stale_timetable()],
+                    skipped_trips: Vec::new(),
+                }],
+            }],
+        );
+
+        let route_snapshot = AllRoutesSnapshot::new(
+            captured_at,
+            None,
+            service_date,
+            ServiceDayType::Weekday,
+            None,
+            Vec::new(),
+            vec![TripWithStationsAndTimetables {
+                captured_at,
+                route_details: RouteDetails {
+                    route_id: RouteId::new("R6"),
+                    trip_id: TripId::new("T6"),
+                    internal_trip_id: 1,
+                    route: route(),
+                    name: String::from("BAVARSKI DVOR - ČRNUČE"),
+                    short_name: Some(String::from("ČRNUČE")),
+                    route_shape: None,
+                },
+                stable_route_id: None,
+                stable_trip_id: None,
+                stations_on_route_with_timetables: vec![TripStationWithTimetable {
+                    station: StationOnRoute {
+                        station_code: StationCode::new("600011"),
+                        internal_station_id: 1,
+                        name: String::from("BAVARSKI DVOR"),
+                        location: GeographicalLocation::new(46.058, 14.506),
+                        stop_number: 1,
+                    },
+                    timetable: stale_timetable(),
+                    timetable_is_interpolated: false,
+                }],
+                destination_station_code: None,
+                completeness: None,
+            }],
+        );
+
+        let station_storage = storage_root.stations().unwrap();
+        let station_snapshot_json = serde_json::to_vec(&station_snapshot).unwrap();
+        let station_snapshot_path =
+            station_storage.generate_json_file_path(captured_at, &station_snapshot_json);
+        fs::write(&station_snapshot_path, station_snapshot_json).unwrap();
+        station_storage
+            .update_latest(&station_snapshot_path, captured_at)
+            .unwrap();
+
+        let route_storage = storage_root.routes().unwrap();
+        let route_snapshot_json = serde_json::to_vec(&route_snapshot).unwrap();
+        let route_snapshot_path =
+            route_storage.generate_json_file_path(captured_at, &route_snapshot_json);
+        fs::write(&route_snapshot_path, route_snapshot_json).unwrap();
+        route_storage
+            .update_latest(&route_snapshot_path, captured_at)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn refresh_only_station_timetables() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-timetables-only-test-{}",
+            Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory_path).unwrap();
+
+        let (base_url, requests) = start_stub_server(|request_path, _| {
+            match request_path.starts_with("/station/timetable?") {
+                true => ("200 OK", String::from(STATION_TIMETABLE_RESPONSE)),
+                false => ("404 Not Found", String::new()),
+            }
+        })
+        .await;
+
+        let configuration =
+            LppConfiguration::for_tests(&base_url, &directory_path, toml::Table::new());
+        let storage_root = &configuration.recording.recording_storage_root;
+        let client = LppApiClient::new(reqwest::Client::new(), None, None, None, None, None);
+        let clock = ManualClock::new(
+            FixedOffset::east_opt(3600)
+                .unwrap()
+                .with_ymd_and_hms(2023, 11, 7, 4, 0, 0)
+                .unwrap(),
+        );
+
+        let reused_captured_at = Utc.with_ymd_and_hms(2023, 11, 6, 3, 0, 0).unwrap();
+        save_reused_snapshots(storage_root, reused_captured_at);
+        let archive = SnapshotArchive::open(storage_root).unwrap();
+        let reused_station_snapshot_name = archive
+            .latest_station_snapshot()
+            .unwrap()
+            .unwrap()
+            .file_name();
+
+        let captured_snapshots = make_timetables_only_snapshot(
+            &configuration,
+            &client,
+            clock.as_ref(),
+            &storage_root.stations().unwrap(),
+            &storage_root.routes().unwrap(),
+            Uuid::new_v4(),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(captured_snapshots.saved_file_paths.len(), 2);
+
+        // Only timetables were requested: no station list, trips on stations or routes.
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("/station/timetable?station-code=600011&"));
+
+        let station_snapshot = archive.latest_station_snapshot().unwrap().unwrap();
+        assert_ne!(station_snapshot.file_name(), reused_station_snapshot_name);
+        assert_eq!(
+            station_snapshot
+                .snapshot
+                .metadata_reused_from
+                .as_ref()
+                .map(|reused_from| reused_from.file_name.clone()),
+            Some(reused_station_snapshot_name)
+        );
+
+        let station = &station_snapshot.snapshot.station_details[0];
+        assert_eq!(station.trips_on_station.len(), 1);
+        assert_eq!(
+            departures(&station.timetables[0].trip_timetables[0]),
+            [(7, 10)]
+        );
+
+        // Routes are rebuilt from the reused stations on each route and the fresh timetables.
+        let route_snapshot = archive.latest_route_snapshot().unwrap().unwrap();
+        let captured_route = &route_snapshot.snapshot.routes[0];
+        assert_eq!(
+            route_snapshot.file_path,
+            captured_snapshots.saved_file_paths[1]
+        );
+        assert_eq!(
+            departures(&captured_route.stations_on_route_with_timetables[0].timetable),
+            [(7, 10)]
+        );
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+}
@@ -0,0 +1,63 @@
+use tokio::task::JoinHandle;
+use tracing::{error, warn};
+
+use crate::cancellation_token::CancellationToken;
+
+/// Exit code when the recorder is stopped forcefully (by a second shutdown signal).
+const FORCED_SHUTDOWN_EXIT_CODE: i32 = 130;
+
+
+/// Waits for the next `SIGINT` (Ctrl+C) or, on Unix, `SIGTERM`.
+/// Returns the name of the received signal.
+async fn receive_shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate_signal) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => "SIGINT",
+                    _ = terminate_signal.recv() => "SIGTERM",
+                }
+            }
+            Err(error) => {
+                warn!(
+                    error = ?error,
+                    "Failed to listen for SIGTERM, only SIGINT will shut the recorder down gracefully."
+                );
+
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "SIGINT"
+    }
+}
+
+/// Spawns a task that cancels `cancellation_token` on `SIGINT` or `SIGTERM`, so the recorder loops
+/// stop issuing requests and finish writing their current files before exiting.
+/// A second signal exits right away. The task runs until it is aborted.
+pub fn initialize_shutdown_signal_task(cancellation_token: CancellationToken) -> JoinHandle<()> {
+    tokio::task::spawn(async move {
+        let signal_name = receive_shutdown_signal().await;
+        warn!(
+            signal = signal_name,
+            "Received shutdown signal, finishing the current work and exiting \
+            (send it again to exit immediately)."
+        );
+        cancellation_token.cancel();
+
+        let signal_name = receive_shutdown_signal().await;
+        error!(
+            signal = signal_name,
+            "Received second shutdown signal, exiting immediately."
+        );
+        std::process::exit(FORCED_SHUTDOWN_EXIT_CODE);
+    })
+}
@@ -0,0 +1,297 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use crate::storage::StorageRoot;
+
+/// Extension that is appended to a snapshot's file name to get its signature sidecar file,
+/// e.g. `station-details_[...].json` is signed by `station-details_[...].json.sig`.
+pub const SIGNATURE_FILE_EXTENSION: &str = "sig";
+
+/// Name of the file in the storage root that contains the
+/// hex-encoded Ed25519 public key snapshots are signed with.
+pub const PUBLIC_KEY_FILE_NAME: &str = "snapshot-signing-key.pub";
+
+
+/// Loads an Ed25519 signing (private) key from a file.
+/// The file must contain the 32-byte secret key, encoded as hex.
+pub fn load_signing_key_from_file(key_file_path: &Path) -> Result<SigningKey> {
+    let secret_key_bytes = read_hex_key_file(key_file_path)
+        .wrap_err_with(|| miette!("Failed to read signing key file."))?;
+
+    Ok(SigningKey::from_bytes(&secret_key_bytes))
+}
+
+/// Loads an Ed25519 verifying (public) key from a file.
+/// The file must contain the 32-byte public key, encoded as hex.
+pub fn load_verifying_key_from_file(key_file_path: &Path) -> Result<VerifyingKey> {
+    let public_key_bytes = read_hex_key_file(key_file_path)
+        .wrap_err_with(|| miette!("Failed to read public key file."))?;
+
+    VerifyingKey::from_bytes(&public_key_bytes)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("File does not contain a valid Ed25519 public key."))
+}
+
+fn read_hex_key_file(key_file_path: &Path) -> Result<[u8; 32]> {
+    let key_file_contents = fs::read_to_string(key_file_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Failed to read file at {}.",
+                key_file_path.display()
+            )
+        })?;
+
+    let key_bytes = hex::decode(key_file_contents.trim())
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Key file does not contain valid hex."))?;
+
+    <[u8; 32]>::try_from(key_bytes.as_slice()).map_err(|_| {
+        miette!(
+            "Expected a 32-byte key, got {} bytes.",
+            key_bytes.len()
+        )
+    })
+}
+
+/// Writes the public half of `signing_key` into the storage root
+/// so the archive can later be verified with the `verify-signatures` command.
+pub fn save_public_key_to_storage_root(
+    storage_root: &StorageRoot,
+    signing_key: &SigningKey,
+) -> Result<PathBuf> {
+    let public_key_file_path = storage_root.path().join(PUBLIC_KEY_FILE_NAME);
+    let encoded_public_key = hex::encode(signing_key.verifying_key().as_bytes());
+
+    fs::write(&public_key_file_path, encoded_public_key)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to write public key file."))?;
+
+    Ok(public_key_file_path)
+}
+
+
+/// Returns the path of the signature sidecar file for the given file.
+pub fn signature_file_path(file_path: &Path) -> PathBuf {
+    let mut signature_file_name = file_path.as_os_str().to_os_string();
+    signature_file_name.push(".");
+    signature_file_name.push(SIGNATURE_FILE_EXTENSION);
+
+    PathBuf::from(signature_file_name)
+}
+
+/// Signs the contents of the given file and saves the
+/// hex-encoded signature into its sidecar file (see [`signature_file_path`]).
+pub fn sign_file(signing_key: &SigningKey, file_path: &Path) -> Result<PathBuf> {
+    let file_contents = fs::read(file_path)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to read file to sign."))?;
+
+    let signature = signing_key.sign(&file_contents);
+
+    let signature_path = signature_file_path(file_path);
+    fs::write(&signature_path, hex::encode(signature.to_bytes()))
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to write signature file."))?;
+
+    Ok(signature_path)
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SignatureVerificationOutcome {
+    /// The signature matches the file contents.
+    Valid,
+
+    /// The signature sidecar file does not exist.
+    MissingSignature,
+
+    /// The signature sidecar file is not a validly-encoded signature.
+    MalformedSignature,
+
+    /// The signature does not match the file contents,
+    /// i.e. the file has been modified since it was signed.
+    Invalid,
+}
+
+/// Verifies the given file against its signature sidecar file.
+pub fn verify_file_signature(
+    verifying_key: &VerifyingKey,
+    file_path: &Path,
+) -> Result<SignatureVerificationOutcome> {
+    let signature_path = signature_file_path(file_path);
+    if !signature_path.is_file() {
+        return Ok(SignatureVerificationOutcome::MissingSignature);
+    }
+
+    let encoded_signature = fs::read_to_string(&signature_path)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to read signature file."))?;
+
+    let file_contents = fs::read(file_path)
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to read signed file."))?;
+
+    Ok(verify_signature(
+        verifying_key,
+        &file_contents,
+        &encoded_signature,
+    ))
+}
+
+/// Verifies contents against a hex-encoded signature
+/// (e.g. of a packed snapshot, whose signature is stored in the pack).
+pub fn verify_signature(
+    verifying_key: &VerifyingKey,
+    contents: &[u8],
+    encoded_signature: &str,
+) -> SignatureVerificationOutcome {
+    let Ok(signature_bytes) = hex::decode(encoded_signature.trim()) else {
+        return SignatureVerificationOutcome::MalformedSignature;
+    };
+
+    let Ok(signature) = Signature::from_slice(&signature_bytes) else {
+        return SignatureVerificationOutcome::MalformedSignature;
+    };
+
+    match verifying_key.verify(contents, &signature) {
+        Ok(()) => SignatureVerificationOutcome::Valid,
+        Err(_) => SignatureVerificationOutcome::Invalid,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::storage::FileNameTemplates;
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    fn snapshot_file(directory_path: &Path) -> PathBuf {
+        let snapshot_file_path = directory_path.join("station-details_2023-11-06_03-00-00.json");
+        fs::write(
+            &snapshot_file_path,
+            r#"{"station_code":"600011","name":"BAVARSKI DVOR"}"#,
+        )
+        .unwrap();
+
+        snapshot_file_path
+    }
+
+    #[test]
+    fn signed_snapshot_fails_verification_after_one_byte_changes() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-signing-test-{}",
+            Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory_path).unwrap();
+
+        let signing_key = signing_key();
+        let snapshot_file_path = snapshot_file(&directory_path);
+
+        let signature_path = sign_file(&signing_key, &snapshot_file_path).unwrap();
+        assert_eq!(
+            signature_path,
+            directory_path.join("station-details_2023-11-06_03-00-00.json.sig")
+        );
+        assert_eq!(
+            verify_file_signature(&signing_key.verifying_key(), &snapshot_file_path).unwrap(),
+            SignatureVerificationOutcome::Valid
+        );
+
+        let mut snapshot_contents = fs::read(&snapshot_file_path).unwrap();
+        snapshot_contents[20] ^= 1;
+        fs::write(&snapshot_file_path, snapshot_contents).unwrap();
+
+        assert_eq!(
+            verify_file_signature(&signing_key.verifying_key(), &snapshot_file_path).unwrap(),
+            SignatureVerificationOutcome::Invalid
+        );
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+
+    #[test]
+    fn detect_missing_and_mismatched_signature_files() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-signing-test-{}",
+            Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory_path).unwrap();
+
+        let signing_key = signing_key();
+        let snapshot_file_path = snapshot_file(&directory_path);
+
+        assert_eq!(
+            verify_file_signature(&signing_key.verifying_key(), &snapshot_file_path).unwrap(),
+            SignatureVerificationOutcome::MissingSignature
+        );
+
+        // A signature made with a different key.
+        sign_file(&SigningKey::from_bytes(&[8; 32]), &snapshot_file_path).unwrap();
+        assert_eq!(
+            verify_file_signature(&signing_key.verifying_key(), &snapshot_file_path).unwrap(),
+            SignatureVerificationOutcome::Invalid
+        );
+
+        // A signature of some other file.
+        let other_file_path = directory_path.join("route-details_2023-11-06_03-00-00.json");
+        fs::write(&other_file_path, "[]").unwrap();
+        let other_signature_path = sign_file(&signing_key, &other_file_path).unwrap();
+        fs::copy(other_signature_path, signature_file_path(&snapshot_file_path)).unwrap();
+        assert_eq!(
+            verify_file_signature(&signing_key.verifying_key(), &snapshot_file_path).unwrap(),
+            SignatureVerificationOutcome::Invalid
+        );
+
+        fs::write(signature_file_path(&snapshot_file_path), "not a signature").unwrap();
+        assert_eq!(
+            verify_file_signature(&signing_key.verifying_key(), &snapshot_file_path).unwrap(),
+            SignatureVerificationOutcome::MalformedSignature
+        );
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+
+    #[test]
+    fn saved_public_key_verifies_signatures() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-signing-test-{}",
+            Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory_path).unwrap();
+
+        let signing_key = signing_key();
+        let storage_root =
+            StorageRoot::new(&directory_path, FileNameTemplates::default()).unwrap();
+
+        let public_key_file_path =
+            save_public_key_to_storage_root(&storage_root, &signing_key).unwrap();
+        assert_eq!(
+            public_key_file_path,
+            directory_path.join(PUBLIC_KEY_FILE_NAME)
+        );
+
+        let verifying_key = load_verifying_key_from_file(&public_key_file_path).unwrap();
+        assert_eq!(verifying_key, signing_key.verifying_key());
+
+        let snapshot_file_path = snapshot_file(&directory_path);
+        sign_file(&signing_key, &snapshot_file_path).unwrap();
+        assert_eq!(
+            verify_file_signature(&verifying_key, &snapshot_file_path).unwrap(),
+            SignatureVerificationOutcome::Valid
+        );
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+}
@@ -0,0 +1,494 @@
+use std::{
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    vec,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use miette::Diagnostic;
+use thiserror::Error;
+
+use super::{
+    json_files_by_modification_time,
+    latest_pointer::latest_snapshot_file_path,
+    StorageError,
+    StorageRoot,
+};
+use crate::{
+    calendar::ServiceDayType,
+    recorder::formats::{
+        load_snapshot,
+        AllRoutesSnapshot,
+        AllStationsSnapshot,
+        Snapshot,
+        SnapshotCompression,
+        SnapshotLoadError,
+        SnapshotSerialization,
+    },
+};
+
+
+/// A kind of snapshot that can be read from a [`SnapshotArchive`].
+pub trait ArchivableSnapshot: Sized {
+    /// Human-readable name of this kind of snapshot, e.g. `station details`.
+    const DESCRIPTION: &'static str;
+
+    /// Returns `None` if the snapshot is of a different kind.
+    fn from_snapshot(snapshot: Snapshot) -> Option<Self>;
+
+    fn captured_at(&self) -> DateTime<Utc>;
+}
+
+impl ArchivableSnapshot for AllStationsSnapshot {
+    const DESCRIPTION: &'static str = "station details";
+
+    fn from_snapshot(snapshot: Snapshot) -> Option<Self> {
+        match snapshot {
+            Snapshot::Stations(snapshot) => Some(snapshot),
+            Snapshot::Routes(_) => None,
+        }
+    }
+
+    fn captured_at(&self) -> DateTime<Utc> {
+        self.captured_at
+    }
+}
+
+impl ArchivableSnapshot for AllRoutesSnapshot {
+    const DESCRIPTION: &'static str = "route details";
+
+    fn from_snapshot(snapshot: Snapshot) -> Option<Self> {
+        match snapshot {
+            Snapshot::Routes(snapshot) => Some(snapshot),
+            Snapshot::Stations(_) => None,
+        }
+    }
+
+    fn captured_at(&self) -> DateTime<Utc> {
+        self.captured_at
+    }
+}
+
+
+/// A snapshot read from a [`SnapshotArchive`], along with where and how it was stored.
+#[derive(Debug, Clone)]
+pub struct ArchivedSnapshot<S> {
+    pub file_path: PathBuf,
+    pub compression: SnapshotCompression,
+    pub serialization: SnapshotSerialization,
+    pub snapshot: S,
+}
+
+impl<S> ArchivedSnapshot<S> {
+    /// Example: `station-details_2023-11-06_12-00-00.000+UTC.json`
+    pub fn file_name(&self) -> String {
+        self.file_path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Error, Debug, Diagnostic)]
+#[error("Failed to read archived snapshot {}: {error}", .file_path.display())]
+pub struct ArchivedSnapshotError {
+    pub file_path: PathBuf,
+
+    #[source]
+    pub error: SnapshotLoadError,
+}
+
+/// The station and route snapshots that describe the network at some point in time,
+/// see [`SnapshotArchive::network_at`].
+#[derive(Debug, Clone)]
+pub struct NetworkState {
+    pub station_snapshot: ArchivedSnapshot<AllStationsSnapshot>,
+    pub route_snapshot: ArchivedSnapshot<AllRoutesSnapshot>,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+pub enum LatestSnapshotError {
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+
+    #[error(transparent)]
+    Snapshot(#[from] ArchivedSnapshotError),
+}
+
+
+/// Read access to the station and route snapshots in a storage root,
+/// so they can be consumed without dealing with file paths and formats.
+#[derive(Debug, Clone)]
+pub struct SnapshotArchive {
+    stations_directory_path: PathBuf,
+    routes_directory_path: PathBuf,
+}
+
+impl SnapshotArchive {
+    pub fn open(storage_root: &StorageRoot) -> Result<Self, StorageError> {
+        Ok(Self {
+            stations_directory_path: storage_root.stations()?.directory_path().to_path_buf(),
+            routes_directory_path: storage_root.routes()?.directory_path().to_path_buf(),
+        })
+    }
+
+    /// Returns a lazy iterator over all station snapshots, oldest first.
+    pub fn station_snapshots(
+        &self,
+    ) -> Result<ArchivedSnapshots<AllStationsSnapshot>, StorageError> {
+        ArchivedSnapshots::in_directory(&self.stations_directory_path)
+    }
+
+    /// Returns a lazy iterator over all route snapshots, oldest first.
+    pub fn route_snapshots(&self) -> Result<ArchivedSnapshots<AllRoutesSnapshot>, StorageError> {
+        ArchivedSnapshots::in_directory(&self.routes_directory_path)
+    }
+
+    /// Reads the latest station snapshot, or returns `None` if there are no station snapshots.
+    pub fn latest_station_snapshot(
+        &self,
+    ) -> Result<Option<ArchivedSnapshot<AllStationsSnapshot>>, LatestSnapshotError> {
+        latest_snapshot_in(
+            &self.stations_directory_path,
+            self.station_snapshots()?,
+        )
+    }
+
+    /// Reads the latest route snapshot, or returns `None` if there are no route snapshots.
+    pub fn latest_route_snapshot(
+        &self,
+    ) -> Result<Option<ArchivedSnapshot<AllRoutesSnapshot>>, LatestSnapshotError> {
+        latest_snapshot_in(
+            &self.routes_directory_path,
+            self.route_snapshots()?,
+        )
+    }
+
+    /// Reads the latest station and route snapshots that were captured at or before `timestamp`,
+    /// or returns `None` if there is no such snapshot of either kind.
+    ///
+    /// Snapshots are searched from the most recently saved one backwards,
+    /// so looking far into the past reads (and parses) every snapshot in between.
+    pub fn network_at(
+        &self,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Option<NetworkState>, LatestSnapshotError> {
+        let Some(station_snapshot) =
+            latest_snapshot_at_or_before(self.station_snapshots()?, timestamp)?
+        else {
+            return Ok(None);
+        };
+
+        let Some(route_snapshot) =
+            latest_snapshot_at_or_before(self.route_snapshots()?, timestamp)?
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(NetworkState {
+            station_snapshot,
+            route_snapshot,
+        }))
+    }
+
+    /// Reads the latest station snapshot with timetables for `service_date`,
+    /// or returns `None` if no station snapshot was captured for that date.
+    ///
+    /// Like [`Self::network_at`], this reads every snapshot saved after the one it finds.
+    pub fn latest_station_snapshot_for(
+        &self,
+        service_date: NaiveDate,
+    ) -> Result<Option<ArchivedSnapshot<AllStationsSnapshot>>, LatestSnapshotError> {
+        for archived_snapshot in self.station_snapshots()?.rev() {
+            let archived_snapshot = archived_snapshot?;
+
+            if archived_snapshot.snapshot.service_date_or_capture_date() == service_date {
+                return Ok(Some(archived_snapshot));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Reads the latest station snapshot whose timetables are the ones LPP runs on days of
+    /// `service_day_type` (so Sunday snapshots are found for holidays and vice versa),
+    /// or returns `None` if no station snapshot was captured on such a day.
+    ///
+    /// Like [`Self::network_at`], this reads every snapshot saved after the one it finds.
+    pub fn latest_station_snapshot_for_day_type(
+        &self,
+        service_day_type: ServiceDayType,
+    ) -> Result<Option<ArchivedSnapshot<AllStationsSnapshot>>, LatestSnapshotError> {
+        for archived_snapshot in self.station_snapshots()?.rev() {
+            let archived_snapshot = archived_snapshot?;
+
+            // Snapshots recorded before the service day type was recorded are skipped.
+            if archived_snapshot
+                .snapshot
+                .service_day_type
+                .is_some_and(|snapshot_day_type| {
+                    snapshot_day_type.has_same_timetables_as(service_day_type)
+                })
+            {
+                return Ok(Some(archived_snapshot));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Reads the snapshot the `latest.json` pointer points to. Directories without a (valid) pointer,
+/// e.g. ones written by older versions, fall back to the most recently modified snapshot.
+fn latest_snapshot_in<S>(
+    directory_path: &Path,
+    mut snapshots: ArchivedSnapshots<S>,
+) -> Result<Option<ArchivedSnapshot<S>>, LatestSnapshotError>
+where
+    S: ArchivableSnapshot,
+{
+    match latest_snapshot_file_path(directory_path)? {
+        Some(file_path) => Ok(Some(ArchivedSnapshots::read_snapshot(file_path)?)),
+        None => Ok(snapshots.next_back().transpose()?),
+    }
+}
+
+fn latest_snapshot_at_or_before<S>(
+    snapshots: ArchivedSnapshots<S>,
+    timestamp: DateTime<Utc>,
+) -> Result<Option<ArchivedSnapshot<S>>, ArchivedSnapshotError>
+where
+    S: ArchivableSnapshot,
+{
+    for archived_snapshot in snapshots.rev() {
+        let archived_snapshot = archived_snapshot?;
+
+        if archived_snapshot.snapshot.captured_at() <= timestamp {
+            return Ok(Some(archived_snapshot));
+        }
+    }
+
+    Ok(None)
+}
+
+
+/// Lazy iterator over the snapshots of one kind, ordered by the modification time of their files
+/// (which is when they were saved). Each snapshot is only read once the iterator reaches it,
+/// so e.g. `next_back()` reads just the latest snapshot.
+pub struct ArchivedSnapshots<S> {
+    file_paths: vec::IntoIter<PathBuf>,
+    snapshot_kind: PhantomData<S>,
+}
+
+impl<S> ArchivedSnapshots<S>
+where
+    S: ArchivableSnapshot,
+{
+    fn in_directory(directory_path: &Path) -> Result<Self, StorageError> {
+        Ok(Self {
+            file_paths: json_files_by_modification_time(directory_path)?.into_iter(),
+            snapshot_kind: PhantomData,
+        })
+    }
+
+    /// Paths of the snapshot files the iterator has not reached yet, oldest first.
+    pub fn file_paths(&self) -> &[PathBuf] {
+        self.file_paths.as_slice()
+    }
+
+    /// Reads a single snapshot file of this kind.
+    pub fn read_snapshot(file_path: PathBuf) -> Result<ArchivedSnapshot<S>, ArchivedSnapshotError> {
+        let loaded_snapshot = match load_snapshot(&file_path) {
+            Ok(loaded_snapshot) => loaded_snapshot,
+            Err(error) => return Err(ArchivedSnapshotError { file_path, error }),
+        };
+
+        let Some(snapshot) = S::from_snapshot(loaded_snapshot.snapshot) else {
+            return Err(ArchivedSnapshotError {
+                file_path,
+                error: SnapshotLoadError::FormatMismatch {
+                    reason: format!("expected a {} snapshot", S::DESCRIPTION),
+                },
+            });
+        };
+
+        Ok(ArchivedSnapshot {
+            file_path,
+            compression: loaded_snapshot.compression,
+            serialization: loaded_snapshot.serialization,
+            snapshot,
+        })
+    }
+}
+
+impl<S> Iterator for ArchivedSnapshots<S>
+where
+    S: ArchivableSnapshot,
+{
+    type Item = Result<ArchivedSnapshot<S>, ArchivedSnapshotError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.file_paths.next().map(Self::read_snapshot)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.file_paths.size_hint()
+    }
+}
+
+impl<S> DoubleEndedIterator for ArchivedSnapshots<S>
+where
+    S: ArchivableSnapshot,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.file_paths.next_back().map(Self::read_snapshot)
+    }
+}
+
+impl<S> ExactSizeIterator for ArchivedSnapshots<S> where S: ArchivableSnapshot {}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::storage::FileNameTemplates;
+
+    fn captured_at(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 11, day, 3, 0, 0).unwrap()
+    }
+
+    /// Saves an empty station snapshot captured on `day` of November 2023.
+    fn save_station_snapshot(storage_root: &StorageRoot, day: u32) -> PathBuf {
+        let snapshot = AllStationsSnapshot::new(
+            captured_at(day),
+            None,
+            NaiveDate::from_ymd_opt(2023, 11, day).unwrap(),
+            ServiceDayType::Weekday,
+            None,
+            Vec::new(),
+            Vec::new(),
+        );
+
+        let station_storage = storage_root.stations().unwrap();
+        let snapshot_json = serde_json::to_vec(&snapshot).unwrap();
+        let snapshot_file_path =
+            station_storage.generate_json_file_path(snapshot.captured_at, &snapshot_json);
+        fs::write(&snapshot_file_path, snapshot_json).unwrap();
+
+        snapshot_file_path
+    }
+
+    /// Saves an empty route snapshot captured on `day` of November 2023.
+    fn save_route_snapshot(storage_root: &StorageRoot, day: u32) -> PathBuf {
+        let snapshot = AllRoutesSnapshot::new(
+            captured_at(day),
+            None,
+            NaiveDate::from_ymd_opt(2023, 11, day).unwrap(),
+            ServiceDayType::Weekday,
+            None,
+            Vec::new(),
+            Vec::new(),
+        );
+
+        let route_storage = storage_root.routes().unwrap();
+        let snapshot_json = serde_json::to_vec(&snapshot).unwrap();
+        let snapshot_file_path =
+            route_storage.generate_json_file_path(snapshot.captured_at, &snapshot_json);
+        fs::write(&snapshot_file_path, snapshot_json).unwrap();
+
+        snapshot_file_path
+    }
+
+    #[test]
+    fn read_back_written_snapshots() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-archive-test-{}",
+            Uuid::new_v4()
+        ));
+        let storage_root = StorageRoot::new(&directory_path, FileNameTemplates::default()).unwrap();
+
+        save_station_snapshot(&storage_root, 6);
+        let latest_station_snapshot_path = save_station_snapshot(&storage_root, 7);
+        storage_root
+            .stations()
+            .unwrap()
+            .update_latest(&latest_station_snapshot_path, captured_at(7))
+            .unwrap();
+        save_route_snapshot(&storage_root, 6);
+
+        let archive = SnapshotArchive::open(&storage_root).unwrap();
+
+        let station_snapshot_times = archive
+            .station_snapshots()
+            .unwrap()
+            .map(|archived_snapshot| archived_snapshot.unwrap().snapshot.captured_at)
+            .collect::<Vec<_>>();
+        assert_eq!(station_snapshot_times, [captured_at(6), captured_at(7)]);
+
+        let latest_station_snapshot = archive.latest_station_snapshot().unwrap().unwrap();
+        assert_eq!(latest_station_snapshot.file_path, latest_station_snapshot_path);
+        assert_eq!(latest_station_snapshot.serialization, SnapshotSerialization::Json);
+        assert_eq!(latest_station_snapshot.compression, SnapshotCompression::None);
+
+        let network = archive
+            .network_at(captured_at(6) + chrono::Duration::hours(12))
+            .unwrap()
+            .unwrap();
+        assert_eq!(network.station_snapshot.snapshot.captured_at, captured_at(6));
+        assert_eq!(network.route_snapshot.snapshot.captured_at, captured_at(6));
+        assert!(archive.network_at(captured_at(5)).unwrap().is_none());
+
+        let service_date_snapshot = archive
+            .latest_station_snapshot_for(NaiveDate::from_ymd_opt(2023, 11, 6).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(service_date_snapshot.snapshot.captured_at, captured_at(6));
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+
+    #[test]
+    fn find_latest_snapshot_without_a_latest_pointer() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-archive-test-{}",
+            Uuid::new_v4()
+        ));
+        let storage_root = StorageRoot::new(&directory_path, FileNameTemplates::default()).unwrap();
+        let archive = SnapshotArchive::open(&storage_root).unwrap();
+
+        assert!(archive.latest_station_snapshot().unwrap().is_none());
+        assert!(archive.latest_route_snapshot().unwrap().is_none());
+
+        // Written by a version without `latest.json` pointers.
+        save_route_snapshot(&storage_root, 6);
+        save_route_snapshot(&storage_root, 7);
+
+        let latest_route_snapshot = archive.latest_route_snapshot().unwrap().unwrap();
+        assert_eq!(latest_route_snapshot.snapshot.captured_at, captured_at(7));
+
+        // Route snapshots can't be read as station snapshots.
+        let misplaced_snapshot_path = save_route_snapshot(&storage_root, 8);
+        let misplaced_station_snapshot_path = storage_root
+            .stations()
+            .unwrap()
+            .directory_path()
+            .join(misplaced_snapshot_path.file_name().unwrap());
+        fs::rename(&misplaced_snapshot_path, &misplaced_station_snapshot_path).unwrap();
+
+        let error = archive.latest_station_snapshot().unwrap_err();
+        assert!(matches!(
+            error,
+            LatestSnapshotError::Snapshot(ArchivedSnapshotError {
+                error: SnapshotLoadError::FormatMismatch { .. },
+                ..
+            })
+        ));
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+}
@@ -0,0 +1,261 @@
+use chrono::{DateTime, Utc};
+use miette::{miette, Result};
+use sha2::{Digest, Sha256};
+
+use super::DATE_TIME_FORMAT;
+
+/// Default template, which produces e.g. `station-details_2023-11-06_12-00-00.000+UTC.json`.
+pub const DEFAULT_FILE_NAME_TEMPLATE: &str = "{kind}_{timestamp}.json";
+
+/// Placeholders that make each generated file name unique.
+const UNIQUE_PLACEHOLDERS: [&str; 3] = ["{timestamp}", "{sequence}", "{hash8}"];
+
+
+/// Values that are substituted into a [`FileNameTemplate`].
+pub struct FileNameTemplateValues<'a> {
+    /// Replaces `{timestamp}`.
+    pub timestamp: DateTime<Utc>,
+
+    /// Replaces `{kind}`, e.g. `station-details`.
+    pub kind: &'a str,
+
+    /// Replaces `{sequence}` (zero-padded to six digits).
+    pub sequence: u64,
+
+    /// Contents of the file; the first 8 hex characters of their SHA-256 hash replace `{hash8}`.
+    pub contents: &'a [u8],
+}
+
+
+/// A snapshot file name pattern, e.g. `{kind}_{timestamp}.json`.
+///
+/// Supported placeholders are `{timestamp}`, `{kind}`, `{sequence}` and `{hash8}`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FileNameTemplate {
+    template: String,
+}
+
+impl FileNameTemplate {
+    pub fn parse<S>(template: S) -> Result<Self>
+    where
+        S: Into<String>,
+    {
+        let template: String = template.into();
+
+        let mut remaining_template = template.as_str();
+        while let Some(placeholder_start) = remaining_template.find('{') {
+            let Some(placeholder_length) = remaining_template[placeholder_start..].find('}') else {
+                return Err(miette!(
+                    "Unclosed placeholder in file name template \"{}\".",
+                    template
+                ));
+            };
+
+            let placeholder =
+                &remaining_template[placeholder_start..placeholder_start + placeholder_length + 1];
+            if !matches!(
+                placeholder,
+                "{timestamp}" | "{kind}" | "{sequence}" | "{hash8}"
+            ) {
+                return Err(miette!(
+                    "Unknown placeholder {} in file name template \"{}\" \
+                    (expected one of {{timestamp}}, {{kind}}, {{sequence}} or {{hash8}}).",
+                    placeholder,
+                    template
+                ));
+            }
+
+            remaining_template = &remaining_template[placeholder_start + placeholder_length + 1..];
+        }
+
+        if template.contains('/') || template.contains('\\') {
+            return Err(miette!(
+                "File name template \"{}\" must not contain path separators.",
+                template
+            ));
+        }
+
+        if !template.ends_with(".json") {
+            return Err(miette!(
+                "File name template \"{}\" must end with \".json\".",
+                template
+            ));
+        }
+
+        if !UNIQUE_PLACEHOLDERS
+            .iter()
+            .any(|placeholder| template.contains(placeholder))
+        {
+            return Err(miette!(
+                "File name template \"{}\" must contain at least one of \
+                {{timestamp}}, {{sequence}} or {{hash8}}, otherwise file names would collide.",
+                template
+            ));
+        }
+
+        Ok(Self { template })
+    }
+
+    /// Returns `true` if the rendered file name depends on the file contents.
+    pub fn uses_hash(&self) -> bool {
+        self.template.contains("{hash8}")
+    }
+
+    pub fn render(&self, values: FileNameTemplateValues) -> String {
+        let mut file_name = self
+            .template
+            .replace(
+                "{timestamp}",
+                &values.timestamp.format(DATE_TIME_FORMAT).to_string(),
+            )
+            .replace("{kind}", values.kind)
+            .replace("{sequence}", &format!("{:06}", values.sequence));
+
+        if self.uses_hash() {
+            let hash = hex::encode(Sha256::digest(values.contents));
+            file_name = file_name.replace("{hash8}", &hash[..8]);
+        }
+
+        file_name
+    }
+
+    /// Parses the `{sequence}` number out of a file name this template rendered for snapshots
+    /// of `kind`. Returns `None` if the template has no `{sequence}` or the file name doesn't match.
+    pub fn parse_sequence(&self, kind: &str, file_name: &str) -> Option<u64> {
+        if !self.template.contains("{sequence}") {
+            return None;
+        }
+
+        match_template(&self.template, kind, file_name).flatten()
+    }
+}
+
+/// Matches `file_name` against (the rest of) a template. Returns `None` if it doesn't match,
+/// otherwise the sequence number it contains (if the matched part of the template has one).
+fn match_template(template: &str, kind: &str, file_name: &str) -> Option<Option<u64>> {
+    let Some(placeholder_start) = template.find('{') else {
+        return (template == file_name).then_some(None);
+    };
+
+    let file_name = file_name.strip_prefix(&template[..placeholder_start])?;
+    let placeholder_end = placeholder_start + template[placeholder_start..].find('}')? + 1;
+    let rest_of_template = &template[placeholder_end..];
+
+    match &template[placeholder_start..placeholder_end] {
+        "{kind}" => match_template(
+            rest_of_template,
+            kind,
+            file_name.strip_prefix(kind)?,
+        ),
+        "{hash8}" => {
+            let hash = file_name.get(..8)?;
+            if !hash.chars().all(|character| character.is_ascii_hexdigit()) {
+                return None;
+            }
+
+            match_template(rest_of_template, kind, &file_name[8..])
+        }
+        "{sequence}" => {
+            let number_of_digits = file_name.chars().take_while(char::is_ascii_digit).count();
+
+            (1..=number_of_digits).rev().find_map(|length| {
+                let sequence = file_name[..length].parse().ok()?;
+
+                match_template(rest_of_template, kind, &file_name[length..]).map(|_| Some(sequence))
+            })
+        }
+        // `{timestamp}` can be of any length.
+        _ => (1..=file_name.len())
+            .filter(|&length| file_name.is_char_boundary(length))
+            .find_map(|length| match_template(rest_of_template, kind, &file_name[length..])),
+    }
+}
+
+impl Default for FileNameTemplate {
+    fn default() -> Self {
+        Self {
+            template: DEFAULT_FILE_NAME_TEMPLATE.to_string(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn values(contents: &[u8]) -> FileNameTemplateValues<'_> {
+        FileNameTemplateValues {
+            timestamp: Utc.with_ymd_and_hms(2023, 11, 6, 12, 30, 0).unwrap(),
+            kind: "station-details",
+            sequence: 42,
+            contents,
+        }
+    }
+
+    #[test]
+    fn default_template_matches_original_naming() {
+        assert_eq!(
+            FileNameTemplate::default().render(values(b"{}")),
+            "station-details_2023-11-06_12-30-00.000+UTC.json"
+        );
+    }
+
+    #[test]
+    fn render_all_placeholders() {
+        let template =
+            FileNameTemplate::parse("lpp-{kind}-{sequence}-{hash8}-{timestamp}.json").unwrap();
+
+        // SHA-256 of "{}" starts with 44136fa3.
+        assert_eq!(
+            template.render(values(b"{}")),
+            "lpp-station-details-000042-44136fa3-2023-11-06_12-30-00.000+UTC.json"
+        );
+    }
+
+    #[test]
+    fn reject_invalid_templates() {
+        assert!(FileNameTemplate::parse("{kind}_{date}.json").is_err());
+        assert!(FileNameTemplate::parse("{kind}_{timestamp.json").is_err());
+        assert!(FileNameTemplate::parse("{kind}/{timestamp}.json").is_err());
+        assert!(FileNameTemplate::parse("{kind}_{timestamp}.txt").is_err());
+        assert!(FileNameTemplate::parse("{kind}.json").is_err());
+    }
+
+    #[test]
+    fn parse_sequence_from_rendered_file_names() {
+        let template =
+            FileNameTemplate::parse("lpp-{kind}-{sequence}-{hash8}-{timestamp}.json").unwrap();
+        let file_name = template.render(values(b"{}"));
+
+        assert_eq!(
+            template.parse_sequence("station-details", &file_name),
+            Some(42)
+        );
+        assert_eq!(
+            template.parse_sequence("route-details", &file_name),
+            None
+        );
+        assert_eq!(
+            template.parse_sequence("station-details", "station-details-notes.json"),
+            None
+        );
+
+        // Sequence numbers outgrow their padding.
+        let template = FileNameTemplate::parse("{kind}-{sequence}.json").unwrap();
+        assert_eq!(
+            template.parse_sequence("station-details", "station-details-1234567.json"),
+            Some(1234567)
+        );
+
+        assert_eq!(
+            FileNameTemplate::default().parse_sequence(
+                "station-details",
+                "station-details_2023-11-06_12-30-00.000+UTC.json"
+            ),
+            None
+        );
+    }
+}
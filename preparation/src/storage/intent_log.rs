@@ -0,0 +1,250 @@
+//! Write-ahead intent log (`intents.jsonl` in the storage root), used to diagnose crashes.
+//!
+//! Before each phase of a snapshot capture (e.g. fetching all routes or writing a snapshot file),
+//! the recorder appends a `started` record. Once the phase ends (successfully or with an error),
+//! it appends a `finished` record, and the log is emptied whenever no phase is in progress.
+//! Any intent that is still unfinished when the recorder starts again was interrupted
+//! by a crash (a panic, the process being killed, a power loss, ...).
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use chrono::{DateTime, Utc};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, TimestampSecondsWithFrac};
+use tracing::warn;
+use uuid::Uuid;
+
+/// Name of the intent log file in the storage root.
+pub const INTENT_LOG_FILE_NAME: &str = "intents.jsonl";
+
+
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+enum IntentLogRecord {
+    Started {
+        intent_id: Uuid,
+
+        /// Identifier of the snapshot run the intent belongs to (see `logs-for-run`).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        run_id: Option<Uuid>,
+
+        /// Example: `all-routes` or `write`.
+        action: String,
+
+        /// What the action works on, e.g. the path of the written file.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        target: Option<String>,
+
+        #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+        started_at: DateTime<Utc>,
+    },
+    Finished {
+        intent_id: Uuid,
+    },
+}
+
+/// An intent that was started, but never finished (see the module documentation).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UnfinishedIntent {
+    pub run_id: Option<Uuid>,
+    pub action: String,
+    pub target: Option<String>,
+    pub started_at: DateTime<Utc>,
+}
+
+
+/// Handle to the intent log of a storage root. Clones share the same state.
+#[derive(Clone, Debug)]
+pub struct IntentLog {
+    file_path: PathBuf,
+
+    /// Number of intents in progress. Also serializes all writes to the log.
+    number_of_open_intents: Arc<Mutex<usize>>,
+}
+
+impl IntentLog {
+    pub fn new(storage_root_path: &Path) -> Self {
+        Self {
+            file_path: storage_root_path.join(INTENT_LOG_FILE_NAME),
+            number_of_open_intents: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    /// Records that an action is about to start. The intent is finished once the returned
+    /// guard is dropped, unless that happens because of a panic (which counts as a crash).
+    ///
+    /// Failing to write to the intent log is only logged, as it shouldn't affect the recording.
+    pub fn begin(&self, run_id: Option<Uuid>, action: &str, target: Option<String>) -> Intent {
+        let intent_id = Uuid::new_v4();
+
+        // PANIC SAFETY: The lock is never held across a panicking call.
+        let mut number_of_open_intents = self.number_of_open_intents.lock().unwrap();
+        *number_of_open_intents += 1;
+
+        if let Err(error) = self.append(&IntentLogRecord::Started {
+            intent_id,
+            run_id,
+            action: action.to_string(),
+            target,
+            started_at: Utc::now(),
+        }) {
+            warn!(error = ?error, action = action, "Failed to append to the intent log.");
+        }
+
+        Intent {
+            intent_log: self.clone(),
+            intent_id,
+        }
+    }
+
+    fn finish(&self, intent_id: Uuid) {
+        // PANIC SAFETY: The lock is never held across a panicking call.
+        let mut number_of_open_intents = self.number_of_open_intents.lock().unwrap();
+        *number_of_open_intents = number_of_open_intents.saturating_sub(1);
+
+        // Nothing is in progress, so there's nothing a crash could interrupt.
+        let result = match *number_of_open_intents {
+            0 => File::create(&self.file_path).map(|_| ()),
+            _ => self.append(&IntentLogRecord::Finished { intent_id }),
+        };
+
+        if let Err(error) = result {
+            warn!(error = ?error, "Failed to update the intent log.");
+        }
+    }
+
+    fn append(&self, record: &IntentLogRecord) -> io::Result<()> {
+        let mut serialized_record = serde_json::to_vec(record)?;
+        serialized_record.push(b'\n');
+
+        let mut intent_log_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file_path)?;
+
+        intent_log_file.write_all(&serialized_record)?;
+        intent_log_file.sync_data()
+    }
+
+    /// Returns the intents the previous run left unfinished and empties the log.
+    /// Should be called before this run starts any intents.
+    pub fn take_unfinished_intents(&self) -> Result<Vec<UnfinishedIntent>> {
+        let contents = match fs::read_to_string(&self.file_path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => {
+                return Err(error)
+                    .into_diagnostic()
+                    .wrap_err_with(|| miette!("Failed to read intent log."))
+            }
+        };
+
+        let mut unfinished_intents: Vec<(Uuid, UnfinishedIntent)> = Vec::new();
+
+        // The last line may have been cut off by the crash, so unreadable lines are skipped.
+        for record in contents
+            .lines()
+            .filter_map(|line| serde_json::from_str::<IntentLogRecord>(line).ok())
+        {
+            match record {
+                IntentLogRecord::Started {
+                    intent_id,
+                    run_id,
+                    action,
+                    target,
+                    started_at,
+                } => unfinished_intents.push((
+                    intent_id,
+                    UnfinishedIntent {
+                        run_id,
+                        action,
+                        target,
+                        started_at,
+                    },
+                )),
+                IntentLogRecord::Finished { intent_id } => unfinished_intents
+                    .retain(|(started_intent_id, _)| *started_intent_id != intent_id),
+            }
+        }
+
+        File::create(&self.file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to empty intent log."))?;
+
+        Ok(unfinished_intents
+            .into_iter()
+            .map(|(_, intent)| intent)
+            .collect())
+    }
+}
+
+
+/// An action in progress, see [`IntentLog::begin`].
+#[derive(Debug)]
+pub struct Intent {
+    intent_log: IntentLog,
+    intent_id: Uuid,
+}
+
+impl Drop for Intent {
+    fn drop(&mut self) {
+        if !thread::panicking() {
+            self.intent_log.finish(self.intent_id);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_intents_interrupted_by_a_crash() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-intent-log-test-{}",
+            Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory_path).unwrap();
+
+        let intent_log = IntentLog::new(&directory_path);
+        assert!(intent_log.take_unfinished_intents().unwrap().is_empty());
+
+        let run_id = Uuid::new_v4();
+        drop(intent_log.begin(Some(run_id), "station-details", None));
+
+        let serialization = intent_log.begin(Some(run_id), "serialization", None);
+        let write = intent_log.begin(
+            Some(run_id),
+            "write",
+            Some(String::from("stations/a.json")),
+        );
+        drop(serialization);
+        // Simulates a crash while writing.
+        std::mem::forget(write);
+
+        let unfinished_intents = IntentLog::new(&directory_path)
+            .take_unfinished_intents()
+            .unwrap();
+        assert_eq!(unfinished_intents.len(), 1);
+        assert_eq!(unfinished_intents[0].action, "write");
+        assert_eq!(
+            unfinished_intents[0].target.as_deref(),
+            Some("stations/a.json")
+        );
+        assert_eq!(unfinished_intents[0].run_id, Some(run_id));
+
+        // The log is emptied once the intents have been reported.
+        assert!(intent_log.take_unfinished_intents().unwrap().is_empty());
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+}
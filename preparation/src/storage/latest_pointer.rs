@@ -0,0 +1,172 @@
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, TimestampSecondsWithFrac};
+
+use super::{write_file_atomically, StorageError};
+
+/// Name of the file in each snapshot directory that points to the latest snapshot in it.
+pub const LATEST_POINTER_FILE_NAME: &str = "latest.json";
+
+
+/// Contents of the `latest.json` file in a snapshot directory.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LatestSnapshotPointer {
+    /// Name of the latest snapshot file, relative to the directory of the pointer.
+    pub file_name: String,
+
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub captured_at: DateTime<Utc>,
+}
+
+impl LatestSnapshotPointer {
+    /// Reads the pointer in `directory`. Returns `None` if there is no pointer yet
+    /// or if the snapshot it points to no longer exists (e.g. because it was quarantined).
+    pub(super) fn read_from(directory: &Path) -> Result<Option<Self>, StorageError> {
+        let pointer_file_contents = match fs::read(directory.join(LATEST_POINTER_FILE_NAME)) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+
+        let pointer: Self = serde_json::from_slice(&pointer_file_contents)?;
+
+        match directory.join(&pointer.file_name).is_file() {
+            true => Ok(Some(pointer)),
+            false => Ok(None),
+        }
+    }
+
+    /// Replaces the pointer in `directory`. The pointer is written to a temporary file first
+    /// and then renamed, so readers never see a partially-written pointer.
+    pub(super) fn write_to(&self, directory: &Path) -> Result<(), StorageError> {
+        let serialized_pointer = serde_json::to_vec(self)?;
+
+        write_file_atomically(
+            &directory.join(LATEST_POINTER_FILE_NAME),
+            &serialized_pointer,
+        )?;
+
+        Ok(())
+    }
+}
+
+
+/// Returns the path of the latest snapshot in `directory`, as recorded in its pointer.
+pub(super) fn latest_snapshot_file_path(directory: &Path) -> Result<Option<PathBuf>, StorageError> {
+    Ok(LatestSnapshotPointer::read_from(directory)?
+        .map(|pointer| directory.join(pointer.file_name)))
+}
+
+/// Points the pointer in `directory` to `snapshot_file_path`.
+pub(super) fn update_latest_snapshot(
+    directory: &Path,
+    snapshot_file_path: &Path,
+    captured_at: DateTime<Utc>,
+) -> Result<(), StorageError> {
+    let file_name = snapshot_file_path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    LatestSnapshotPointer {
+        file_name,
+        captured_at,
+    }
+    .write_to(directory)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn replace_pointer_without_leaving_temporary_files() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-latest-pointer-test-{}",
+            Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory_path).unwrap();
+
+        let first_snapshot_path = directory_path.join("station-details_2023-11-06.json");
+        let second_snapshot_path = directory_path.join("station-details_2023-11-07.json");
+        fs::write(&first_snapshot_path, "{}").unwrap();
+        fs::write(&second_snapshot_path, "{}").unwrap();
+
+        let first_captured_at = Utc.with_ymd_and_hms(2023, 11, 6, 3, 0, 0).unwrap();
+        update_latest_snapshot(&directory_path, &first_snapshot_path, first_captured_at).unwrap();
+        assert_eq!(
+            latest_snapshot_file_path(&directory_path).unwrap(),
+            Some(first_snapshot_path)
+        );
+
+        let second_captured_at = Utc.with_ymd_and_hms(2023, 11, 7, 3, 0, 0).unwrap();
+        update_latest_snapshot(&directory_path, &second_snapshot_path, second_captured_at).unwrap();
+
+        let pointer = LatestSnapshotPointer::read_from(&directory_path)
+            .unwrap()
+            .unwrap();
+        assert_eq!(pointer.file_name, "station-details_2023-11-07.json");
+        assert_eq!(pointer.captured_at, second_captured_at);
+
+        let mut file_names = fs::read_dir(&directory_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        file_names.sort();
+        assert_eq!(
+            file_names,
+            [
+                LATEST_POINTER_FILE_NAME,
+                "station-details_2023-11-06.json",
+                "station-details_2023-11-07.json"
+            ]
+        );
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+
+    #[test]
+    fn ignore_missing_and_stale_pointers() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-latest-pointer-test-{}",
+            Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory_path).unwrap();
+
+        assert!(latest_snapshot_file_path(&directory_path)
+            .unwrap()
+            .is_none());
+
+        // The snapshot the pointer points to has since been quarantined.
+        let snapshot_path = directory_path.join("station-details_2023-11-06.json");
+        fs::write(&snapshot_path, "{}").unwrap();
+        update_latest_snapshot(
+            &directory_path,
+            &snapshot_path,
+            Utc.with_ymd_and_hms(2023, 11, 6, 3, 0, 0).unwrap(),
+        )
+        .unwrap();
+        fs::remove_file(&snapshot_path).unwrap();
+
+        assert!(latest_snapshot_file_path(&directory_path)
+            .unwrap()
+            .is_none());
+
+        // A pointer that can't be parsed is an error rather than a missing pointer.
+        fs::write(directory_path.join(LATEST_POINTER_FILE_NAME), "{\"file_na").unwrap();
+        assert!(latest_snapshot_file_path(&directory_path).is_err());
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+}
@@ -1,19 +1,54 @@
 use std::{
     fs,
-    io,
+    io::{self, Write},
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::SystemTime,
 };
 
 use chrono::{DateTime, Utc};
 use miette::Diagnostic;
 use thiserror::Error;
 
+mod archive;
+mod file_name_template;
+mod intent_log;
+mod latest_pointer;
+mod pack;
+mod run_history;
+
+pub use archive::{
+    ArchivableSnapshot,
+    ArchivedSnapshot,
+    ArchivedSnapshots,
+    LatestSnapshotError,
+    SnapshotArchive,
+};
+pub use file_name_template::{FileNameTemplate, FileNameTemplateValues};
+pub use intent_log::{Intent, IntentLog};
+pub use latest_pointer::LATEST_POINTER_FILE_NAME;
+pub use pack::{
+    is_pack_file,
+    locate_snapshot_contents,
+    read_snapshot_contents,
+    SnapshotPack,
+    SnapshotPackWriter,
+    PACK_FILE_EXTENSION,
+};
+pub use run_history::{RunCounters, RunHistoryEntry, RunOutcome, RUN_HISTORY_FILE_NAME};
+
 
 #[derive(Error, Debug, Diagnostic)]
 pub enum StorageError {
     #[error("Expected \"{}\" to be a directory.", .path.display())]
     PathIsNotADirectory { path: PathBuf },
 
+    #[error("Invalid latest snapshot pointer: {0}")]
+    InvalidLatestPointer(#[from] serde_json::Error),
+
     #[error("Encountered other IO error: {0}")]
     OtherIoError(#[from] io::Error),
 }
@@ -32,37 +67,203 @@ fn ensure_directory_exists(path: &Path) -> Result<(), StorageError> {
     Ok(())
 }
 
+/// Returns `true` if the path is a `.json` snapshot file
+/// (and not e.g. the [`LATEST_POINTER_FILE_NAME`] pointer).
+pub fn is_snapshot_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .map(|extension| extension == "json")
+            .unwrap_or(false)
+        && path
+            .file_name()
+            .map(|file_name| file_name != LATEST_POINTER_FILE_NAME)
+            .unwrap_or(false)
+}
+
+/// Replaces the contents of a file. The contents are written to a temporary `.tmp` file
+/// next to it first, synced to disk and then renamed over the file, so readers (and a crash
+/// while writing) never see a partially-written file.
+pub fn write_file_atomically(file_path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temporary_file_name = file_path
+        .file_name()
+        .map(|file_name| file_name.to_os_string())
+        .unwrap_or_default();
+    temporary_file_name.push(".tmp");
+    let temporary_file_path = file_path.with_file_name(temporary_file_name);
+
+    let mut temporary_file = fs::File::create(&temporary_file_path)?;
+    temporary_file.write_all(contents)?;
+    temporary_file.sync_all()?;
+    drop(temporary_file);
+
+    fs::rename(&temporary_file_path, file_path)
+}
+
+/// Returns all snapshot files in the directory, ordered by their modification time
+/// (oldest first, ties are ordered by path). This includes the snapshots in packs
+/// (see [`SnapshotPack`]), ordered by the modification time of their original files.
+fn json_files_by_modification_time(directory: &Path) -> Result<Vec<PathBuf>, StorageError> {
+    let mut json_files: Vec<(SystemTime, PathBuf)> = Vec::new();
+
+    for entry in fs::read_dir(directory)? {
+        let entry_path = entry?.path();
+
+        if is_pack_file(&entry_path) {
+            let pack = SnapshotPack::open(&entry_path)?;
+
+            for packed_snapshot in pack.snapshots() {
+                json_files.push((
+                    packed_snapshot.modified_at.into(),
+                    pack.snapshot_path(packed_snapshot),
+                ));
+            }
+
+            continue;
+        }
+
+        if !is_snapshot_file(&entry_path) {
+            continue;
+        }
+
+        let modified_at = fs::metadata(&entry_path)?.modified()?;
+        json_files.push((modified_at, entry_path));
+    }
+
+    json_files.sort();
+
+    Ok(json_files.into_iter().map(|(_, path)| path).collect())
+}
+
+
+/// File name templates for each kind of stored snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct FileNameTemplates {
+    pub stations: FileNameTemplate,
+    pub routes: FileNameTemplate,
+    pub arrivals: FileNameTemplate,
+}
+
+
+/// Generates file names for one kind of snapshot from its [`FileNameTemplate`].
+#[derive(Debug, Clone)]
+struct SnapshotFileNamer {
+    kind: &'static str,
+    template: FileNameTemplate,
+
+    /// Shared between clones of the storage, so sequence numbers are never reused.
+    next_sequence: Arc<AtomicU64>,
+}
+
+impl SnapshotFileNamer {
+    /// Sequence numbers continue after the highest sequence number in the names of the snapshots
+    /// already in `directory` (including packed ones), or after the number of those snapshots
+    /// if that is higher (e.g. if the template didn't use `{sequence}` before).
+    /// Snapshots that were removed therefore never cause a name to be reused.
+    fn new(
+        kind: &'static str,
+        template: FileNameTemplate,
+        directory: &Path,
+    ) -> Result<Self, StorageError> {
+        let mut existing_snapshot_count = 0;
+        let mut highest_sequence = 0;
+        let mut observe_file_name = |file_name: &str| {
+            existing_snapshot_count += 1;
+
+            if let Some(sequence) = template.parse_sequence(kind, file_name) {
+                highest_sequence = highest_sequence.max(sequence);
+            }
+        };
+
+        for entry in fs::read_dir(directory)? {
+            let entry_path = entry?.path();
+
+            if is_pack_file(&entry_path) {
+                for packed_snapshot in SnapshotPack::open(&entry_path)?.snapshots() {
+                    observe_file_name(&packed_snapshot.file_name);
+                }
+            } else if is_snapshot_file(&entry_path) {
+                observe_file_name(&entry_path.file_name().unwrap_or_default().to_string_lossy());
+            }
+        }
+
+        Ok(Self {
+            kind,
+            template,
+            next_sequence: Arc::new(AtomicU64::new(
+                existing_snapshot_count.max(highest_sequence) + 1,
+            )),
+        })
+    }
+
+    fn generate_file_name(&self, at_time: DateTime<Utc>, contents: &[u8]) -> String {
+        self.template.render(FileNameTemplateValues {
+            timestamp: at_time,
+            kind: self.kind,
+            sequence: self.next_sequence.fetch_add(1, Ordering::SeqCst),
+            contents,
+        })
+    }
+}
+
 
 #[derive(Debug, Clone)]
 pub struct StorageRoot {
     base_storage_path: PathBuf,
+    file_name_templates: FileNameTemplates,
+    intent_log: IntentLog,
 }
 
 impl StorageRoot {
-    pub fn new<P>(base_storage_path: P) -> Result<Self, StorageError>
+    pub fn new<P>(
+        base_storage_path: P,
+        file_name_templates: FileNameTemplates,
+    ) -> Result<Self, StorageError>
     where
         P: Into<PathBuf>,
     {
         let base_storage_path: PathBuf = base_storage_path.into();
         ensure_directory_exists(&base_storage_path)?;
 
-        Ok(Self { base_storage_path })
+        Ok(Self {
+            intent_log: IntentLog::new(&base_storage_path),
+            base_storage_path,
+            file_name_templates,
+        })
     }
 
     pub fn path(&self) -> &Path {
         &self.base_storage_path
     }
 
+    pub fn run_history_file_path(&self) -> PathBuf {
+        self.base_storage_path.join(RUN_HISTORY_FILE_NAME)
+    }
+
+    /// The intent log of this storage root (shared by all clones of it).
+    pub fn intent_log(&self) -> &IntentLog {
+        &self.intent_log
+    }
+
     pub fn stations(&self) -> Result<StationStorage, StorageError> {
-        StationStorage::new(self.base_storage_path.join("stations"))
+        StationStorage::new(
+            self.base_storage_path.join("stations"),
+            self.file_name_templates.stations.clone(),
+        )
     }
 
     pub fn routes(&self) -> Result<RouteStorage, StorageError> {
-        RouteStorage::new(self.base_storage_path.join("routes"))
+        RouteStorage::new(
+            self.base_storage_path.join("routes"),
+            self.file_name_templates.routes.clone(),
+        )
     }
 
     pub fn arrivals(&self) -> Result<ArrivalStorageRoot, StorageError> {
-        ArrivalStorageRoot::new(self.base_storage_path.join("arrival-snapshots"))
+        ArrivalStorageRoot::new(
+            self.base_storage_path.join("arrival-snapshots"),
+            self.file_name_templates.arrivals.clone(),
+        )
     }
 }
 
@@ -70,18 +271,29 @@ impl StorageRoot {
 #[derive(Debug, Clone)]
 pub struct StationStorage {
     stations_storage_path: PathBuf,
+    file_namer: SnapshotFileNamer,
 }
 
 impl StationStorage {
-    pub fn new<P>(stations_storage_path: P) -> Result<Self, StorageError>
+    pub fn new<P>(
+        stations_storage_path: P,
+        file_name_template: FileNameTemplate,
+    ) -> Result<Self, StorageError>
     where
         P: Into<PathBuf>,
     {
         let stations_storage_path: PathBuf = stations_storage_path.into();
         ensure_directory_exists(&stations_storage_path)?;
 
+        let file_namer = SnapshotFileNamer::new(
+            "station-details",
+            file_name_template,
+            &stations_storage_path,
+        )?;
+
         Ok(Self {
             stations_storage_path,
+            file_namer,
         })
     }
 
@@ -89,30 +301,59 @@ impl StationStorage {
         &self.stations_storage_path
     }
 
-    pub fn generate_json_file_path(&self, at_time: DateTime<Utc>) -> PathBuf {
-        let formatted_time = at_time.format(DATE_TIME_FORMAT);
-        let file_name = format!("station-details_{}.json", formatted_time);
+    /// Generates the path of a new snapshot file with the given contents.
+    pub fn generate_json_file_path(&self, at_time: DateTime<Utc>, contents: &[u8]) -> PathBuf {
+        let file_name = self.file_namer.generate_file_name(at_time, contents);
 
         self.stations_storage_path.join(file_name)
     }
+
+    /// Returns the path of the latest snapshot, as recorded in the `latest.json` pointer.
+    pub fn latest(&self) -> Result<Option<PathBuf>, StorageError> {
+        latest_pointer::latest_snapshot_file_path(&self.stations_storage_path)
+    }
+
+    /// Atomically points the `latest.json` pointer to a newly saved snapshot.
+    pub fn update_latest(
+        &self,
+        snapshot_file_path: &Path,
+        captured_at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        latest_pointer::update_latest_snapshot(
+            &self.stations_storage_path,
+            snapshot_file_path,
+            captured_at,
+        )
+    }
 }
 
 
 #[derive(Debug, Clone)]
 pub struct RouteStorage {
     route_storage_root_path: PathBuf,
+    file_namer: SnapshotFileNamer,
 }
 
 impl RouteStorage {
-    pub fn new<P>(route_storage_root_path: P) -> Result<Self, StorageError>
+    pub fn new<P>(
+        route_storage_root_path: P,
+        file_name_template: FileNameTemplate,
+    ) -> Result<Self, StorageError>
     where
         P: Into<PathBuf>,
     {
         let route_storage_root_path: PathBuf = route_storage_root_path.into();
         ensure_directory_exists(&route_storage_root_path)?;
 
+        let file_namer = SnapshotFileNamer::new(
+            "route-details",
+            file_name_template,
+            &route_storage_root_path,
+        )?;
+
         Ok(Self {
             route_storage_root_path,
+            file_namer,
         })
     }
 
@@ -120,12 +361,30 @@ impl RouteStorage {
         &self.route_storage_root_path
     }
 
-    pub fn generate_json_file_path(&self, at_time: DateTime<Utc>) -> PathBuf {
-        let formatted_time = at_time.format(DATE_TIME_FORMAT);
-        let file_name = format!("route-details_{}.json", formatted_time);
+    /// Generates the path of a new snapshot file with the given contents.
+    pub fn generate_json_file_path(&self, at_time: DateTime<Utc>, contents: &[u8]) -> PathBuf {
+        let file_name = self.file_namer.generate_file_name(at_time, contents);
 
         self.route_storage_root_path.join(file_name)
     }
+
+    /// Returns the path of the latest snapshot, as recorded in the `latest.json` pointer.
+    pub fn latest(&self) -> Result<Option<PathBuf>, StorageError> {
+        latest_pointer::latest_snapshot_file_path(&self.route_storage_root_path)
+    }
+
+    /// Atomically points the `latest.json` pointer to a newly saved snapshot.
+    pub fn update_latest(
+        &self,
+        snapshot_file_path: &Path,
+        captured_at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        latest_pointer::update_latest_snapshot(
+            &self.route_storage_root_path,
+            snapshot_file_path,
+            captured_at,
+        )
+    }
 }
 
 
@@ -133,10 +392,14 @@ impl RouteStorage {
 #[derive(Debug, Clone)]
 pub struct ArrivalStorageRoot {
     arrival_storage_root_path: PathBuf,
+    file_name_template: FileNameTemplate,
 }
 
 impl ArrivalStorageRoot {
-    pub fn new<P>(arrival_storage_root_path: P) -> Result<Self, StorageError>
+    pub fn new<P>(
+        arrival_storage_root_path: P,
+        file_name_template: FileNameTemplate,
+    ) -> Result<Self, StorageError>
     where
         P: Into<PathBuf>,
     {
@@ -145,24 +408,65 @@ impl ArrivalStorageRoot {
 
         Ok(Self {
             arrival_storage_root_path,
+            file_name_template,
         })
     }
 
     pub fn directory_path(&self) -> &Path {
         &self.arrival_storage_root_path
     }
+
+    pub fn route<N>(&self, route_name: N) -> Result<ArrivalStorage, StorageError>
+    where
+        N: Into<String>,
+    {
+        ArrivalStorage::new(
+            &self.arrival_storage_root_path,
+            route_name,
+            self.file_name_template.clone(),
+        )
+    }
+
+    /// Returns the arrival snapshot files of all routes, each route's ordered
+    /// by modification time (oldest first).
+    #[cfg_attr(not(feature = "hdf5-export"), allow(dead_code))]
+    pub fn snapshot_file_paths(&self) -> Result<Vec<PathBuf>, StorageError> {
+        let mut route_directory_paths = Vec::new();
+        for entry in fs::read_dir(&self.arrival_storage_root_path)? {
+            let entry_path = entry?.path();
+
+            if entry_path.is_dir() {
+                route_directory_paths.push(entry_path);
+            }
+        }
+
+        route_directory_paths.sort();
+
+        let mut snapshot_file_paths = Vec::new();
+        for route_directory_path in route_directory_paths {
+            snapshot_file_paths.extend(json_files_by_modification_time(
+                &route_directory_path,
+            )?);
+        }
+
+        Ok(snapshot_file_paths)
+    }
 }
 
 
-#[allow(dead_code)]
 pub struct ArrivalStorage {
     full_route_name: String,
     arrival_storage_path: PathBuf,
+    file_namer: SnapshotFileNamer,
 }
 
 #[allow(dead_code)]
 impl ArrivalStorage {
-    pub fn new<P, N>(arrival_storage_root_path: P, route_name: N) -> Result<Self, StorageError>
+    pub fn new<P, N>(
+        arrival_storage_root_path: P,
+        route_name: N,
+        file_name_template: FileNameTemplate,
+    ) -> Result<Self, StorageError>
     where
         P: Into<PathBuf>,
         N: Into<String>,
@@ -173,9 +477,16 @@ impl ArrivalStorage {
         let arrival_storage_path = arrival_storage_root_path.join(&route_name);
         ensure_directory_exists(&arrival_storage_path)?;
 
+        let file_namer = SnapshotFileNamer::new(
+            "arrival",
+            file_name_template,
+            &arrival_storage_path,
+        )?;
+
         Ok(Self {
             full_route_name: route_name,
             arrival_storage_path,
+            file_namer,
         })
     }
 
@@ -187,10 +498,55 @@ impl ArrivalStorage {
         &self.arrival_storage_path
     }
 
-    pub fn generate_json_file_path(&self, at_time: DateTime<Utc>) -> PathBuf {
-        let formatted_time = at_time.format(DATE_TIME_FORMAT);
-        let file_name = format!("arrival_{}.json", formatted_time);
+    /// Generates the path of a new snapshot file with the given contents.
+    pub fn generate_json_file_path(&self, at_time: DateTime<Utc>, contents: &[u8]) -> PathBuf {
+        let file_name = self.file_namer.generate_file_name(at_time, contents);
 
         self.arrival_storage_path.join(file_name)
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn continue_sequence_after_the_highest_existing_one() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-file-namer-test-{}",
+            Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory_path).unwrap();
+
+        let template = FileNameTemplate::parse("{kind}-{sequence}.json").unwrap();
+        let at_time = Utc.with_ymd_and_hms(2023, 11, 6, 12, 0, 0).unwrap();
+
+        let file_namer = SnapshotFileNamer::new(
+            "station-details",
+            template.clone(),
+            &directory_path,
+        )
+        .unwrap();
+        for _ in 0..3 {
+            let file_name = file_namer.generate_file_name(at_time, b"{}");
+            fs::write(directory_path.join(file_name), b"{}").unwrap();
+        }
+
+        // With a snapshot removed (e.g. quarantined by fsck), counting the snapshots
+        // would reuse the name of the newest one.
+        fs::remove_file(directory_path.join("station-details-000001.json")).unwrap();
+
+        let file_namer =
+            SnapshotFileNamer::new("station-details", template, &directory_path).unwrap();
+        assert_eq!(
+            file_namer.generate_file_name(at_time, b"{}"),
+            "station-details-000004.json"
+        );
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+}
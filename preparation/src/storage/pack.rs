@@ -0,0 +1,361 @@
+//! Monthly snapshot packs, written by the `compact-archive` command.
+//!
+//! A pack (e.g. `stations/2023-11.pack`) contains the unmodified contents of many snapshot files,
+//! followed by a JSON index of their offsets and a fixed-size trailer:
+//!
+//! ```text
+//! "LPPPACK1" | snapshot contents ... | index (JSON) | index offset (u64 LE) | index length (u64 LE)
+//! ```
+//!
+//! A packed snapshot is addressed by the path it would have if the pack were a directory,
+//! e.g. `stations/2023-11.pack/station-details_2023-11-06_12-00-00.000+UTC.json`,
+//! so it can be loaded with the same functions as a standalone snapshot file.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, TimestampSecondsWithFrac};
+
+/// Extension of snapshot pack files.
+pub const PACK_FILE_EXTENSION: &str = "pack";
+
+const PACK_MAGIC_BYTES: &[u8; 8] = b"LPPPACK1";
+const PACK_TRAILER_LENGTH: u64 = 16;
+
+
+/// A single snapshot in a pack's index.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PackedSnapshot {
+    /// Name of the original snapshot file.
+    pub file_name: String,
+
+    /// Modification time of the original snapshot file
+    /// (snapshots are ordered by it, see [`SnapshotArchive`](super::SnapshotArchive)).
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub modified_at: DateTime<Utc>,
+
+    /// Contents of the original signature sidecar file, if the snapshot was signed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+
+    offset: u64,
+    length: u64,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+struct PackIndex {
+    snapshots: Vec<PackedSnapshot>,
+}
+
+
+/// Read access to a snapshot pack.
+#[derive(Debug, Clone)]
+pub struct SnapshotPack {
+    file_path: PathBuf,
+    index: PackIndex,
+}
+
+impl SnapshotPack {
+    /// Opens a pack and reads its index.
+    pub fn open(file_path: &Path) -> io::Result<Self> {
+        let mut file = File::open(file_path)?;
+        let file_length = file.metadata()?.len();
+
+        let mut magic_bytes = [0; PACK_MAGIC_BYTES.len()];
+        file.read_exact(&mut magic_bytes)?;
+        if &magic_bytes != PACK_MAGIC_BYTES || file_length < 8 + PACK_TRAILER_LENGTH {
+            return Err(invalid_pack(file_path, "not a snapshot pack"));
+        }
+
+        file.seek(SeekFrom::End(-(PACK_TRAILER_LENGTH as i64)))?;
+        let mut trailer = [0; PACK_TRAILER_LENGTH as usize];
+        file.read_exact(&mut trailer)?;
+
+        let index_offset = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+        let index_length = u64::from_le_bytes(trailer[8..].try_into().unwrap());
+        if index_offset.checked_add(index_length) != Some(file_length - PACK_TRAILER_LENGTH) {
+            return Err(invalid_pack(file_path, "index is out of bounds"));
+        }
+
+        file.seek(SeekFrom::Start(index_offset))?;
+        let mut serialized_index = vec![0; index_length as usize];
+        file.read_exact(&mut serialized_index)?;
+
+        let index: PackIndex = serde_json::from_slice(&serialized_index)
+            .map_err(|error| invalid_pack(file_path, &format!("invalid index: {}", error)))?;
+
+        if index.snapshots.iter().any(|snapshot| {
+            snapshot
+                .offset
+                .checked_add(snapshot.length)
+                .map(|end| end > index_offset)
+                .unwrap_or(true)
+        }) {
+            return Err(invalid_pack(
+                file_path,
+                "snapshot is out of bounds",
+            ));
+        }
+
+        Ok(Self {
+            file_path: file_path.to_path_buf(),
+            index,
+        })
+    }
+
+    /// The packed snapshots, in the order they were added to the pack.
+    pub fn snapshots(&self) -> &[PackedSnapshot] {
+        &self.index.snapshots
+    }
+
+    pub fn find(&self, file_name: &str) -> Option<&PackedSnapshot> {
+        self.index
+            .snapshots
+            .iter()
+            .find(|snapshot| snapshot.file_name == file_name)
+    }
+
+    /// Returns the path that addresses a packed snapshot (see the module documentation).
+    pub fn snapshot_path(&self, snapshot: &PackedSnapshot) -> PathBuf {
+        self.file_path.join(&snapshot.file_name)
+    }
+
+    pub fn read(&self, snapshot: &PackedSnapshot) -> io::Result<Vec<u8>> {
+        read_range(&self.file_path, snapshot.offset, snapshot.length)
+    }
+}
+
+
+/// Writes a new snapshot pack. The pack is written to a temporary file and only
+/// renamed to its final path in [`SnapshotPackWriter::finish`], so a pack is never
+/// seen partially written (an existing pack at the same path is replaced).
+pub struct SnapshotPackWriter {
+    file_path: PathBuf,
+    temporary_file_path: PathBuf,
+    writer: BufWriter<File>,
+    position: u64,
+    index: PackIndex,
+}
+
+impl SnapshotPackWriter {
+    pub fn create(file_path: &Path) -> io::Result<Self> {
+        let temporary_file_path = file_path.with_extension(format!("{}.tmp", PACK_FILE_EXTENSION));
+
+        let mut writer = BufWriter::new(File::create(&temporary_file_path)?);
+        writer.write_all(PACK_MAGIC_BYTES)?;
+
+        Ok(Self {
+            file_path: file_path.to_path_buf(),
+            temporary_file_path,
+            writer,
+            position: PACK_MAGIC_BYTES.len() as u64,
+            index: PackIndex::default(),
+        })
+    }
+
+    pub fn contains(&self, file_name: &str) -> bool {
+        self.index
+            .snapshots
+            .iter()
+            .any(|snapshot| snapshot.file_name == file_name)
+    }
+
+    pub fn add(
+        &mut self,
+        file_name: String,
+        modified_at: DateTime<Utc>,
+        signature: Option<String>,
+        contents: &[u8],
+    ) -> io::Result<()> {
+        self.writer.write_all(contents)?;
+
+        self.index.snapshots.push(PackedSnapshot {
+            file_name,
+            modified_at,
+            signature,
+            offset: self.position,
+            length: contents.len() as u64,
+        });
+        self.position += contents.len() as u64;
+
+        Ok(())
+    }
+
+    /// Writes the index and moves the pack into place.
+    pub fn finish(mut self) -> io::Result<PathBuf> {
+        let serialized_index = serde_json::to_vec(&self.index)?;
+
+        self.writer.write_all(&serialized_index)?;
+        self.writer.write_all(&self.position.to_le_bytes())?;
+        self.writer
+            .write_all(&(serialized_index.len() as u64).to_le_bytes())?;
+
+        let file = self
+            .writer
+            .into_inner()
+            .map_err(|error| error.into_error())?;
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&self.temporary_file_path, &self.file_path)?;
+
+        Ok(self.file_path)
+    }
+}
+
+
+/// Where the contents of a snapshot are stored: a whole standalone file
+/// or a range of a pack file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotContentsLocation {
+    pub file_path: PathBuf,
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// Returns `true` if the path is a snapshot pack file.
+pub fn is_pack_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .map(|extension| extension == PACK_FILE_EXTENSION)
+            .unwrap_or(false)
+}
+
+/// Finds the contents of a snapshot, which may be a standalone file or packed.
+pub fn locate_snapshot_contents(snapshot_path: &Path) -> io::Result<SnapshotContentsLocation> {
+    let packed_location = snapshot_path
+        .parent()
+        .filter(|parent_path| is_pack_file(parent_path))
+        .zip(snapshot_path.file_name());
+
+    let Some((pack_file_path, file_name)) = packed_location else {
+        return Ok(SnapshotContentsLocation {
+            file_path: snapshot_path.to_path_buf(),
+            offset: 0,
+            length: fs::metadata(snapshot_path)?.len(),
+        });
+    };
+
+    let pack = SnapshotPack::open(pack_file_path)?;
+    let snapshot = pack.find(&file_name.to_string_lossy()).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "snapshot is not in pack {}",
+                pack_file_path.display()
+            ),
+        )
+    })?;
+
+    Ok(SnapshotContentsLocation {
+        file_path: pack_file_path.to_path_buf(),
+        offset: snapshot.offset,
+        length: snapshot.length,
+    })
+}
+
+/// Reads the contents of a snapshot, which may be a standalone file or packed.
+pub fn read_snapshot_contents(snapshot_path: &Path) -> io::Result<Vec<u8>> {
+    let location = locate_snapshot_contents(snapshot_path)?;
+
+    read_range(
+        &location.file_path,
+        location.offset,
+        location.length,
+    )
+}
+
+
+fn read_range(file_path: &Path, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+    let mut file = File::open(file_path)?;
+    file.seek(SeekFrom::Start(offset))?;
+
+    let mut contents = vec![0; length as usize];
+    file.read_exact(&mut contents)?;
+
+    Ok(contents)
+}
+
+fn invalid_pack(file_path: &Path, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "invalid snapshot pack {}: {}",
+            file_path.display(),
+            reason
+        ),
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn read_snapshots_through_pack_index() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-pack-test-{}",
+            uuid::Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory_path).unwrap();
+
+        let pack_file_path = directory_path.join("2023-11.pack");
+        let modified_at = Utc.with_ymd_and_hms(2023, 11, 6, 12, 0, 0).unwrap();
+
+        let mut writer = SnapshotPackWriter::create(&pack_file_path).unwrap();
+        writer
+            .add(
+                String::from("first.json"),
+                modified_at,
+                None,
+                br#"{"routes":[]}"#,
+            )
+            .unwrap();
+        writer
+            .add(
+                String::from("second.json"),
+                modified_at,
+                Some(String::from("abcd")),
+                br#"{"station_details":[]}"#,
+            )
+            .unwrap();
+        assert!(writer.contains("second.json"));
+        writer.finish().unwrap();
+
+        let pack = SnapshotPack::open(&pack_file_path).unwrap();
+        assert_eq!(pack.snapshots().len(), 2);
+        assert_eq!(
+            pack.find("second.json").unwrap().signature.as_deref(),
+            Some("abcd")
+        );
+
+        // Packed snapshots are addressed as if the pack were a directory.
+        let second_snapshot_path = pack.snapshot_path(&pack.snapshots()[1]);
+        assert_eq!(
+            read_snapshot_contents(&second_snapshot_path).unwrap(),
+            br#"{"station_details":[]}"#
+        );
+
+        let standalone_file_path = directory_path.join("standalone.json");
+        fs::write(&standalone_file_path, b"{}").unwrap();
+        assert_eq!(
+            read_snapshot_contents(&standalone_file_path).unwrap(),
+            b"{}"
+        );
+
+        assert!(read_snapshot_contents(&pack_file_path.join("missing.json")).is_err());
+        assert!(SnapshotPack::open(&standalone_file_path).is_err());
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+}
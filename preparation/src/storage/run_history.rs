@@ -0,0 +1,220 @@
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, TimestampSecondsWithFrac};
+use uuid::Uuid;
+
+use super::StorageRoot;
+use crate::configuration::CaptureMode;
+
+/// Name of the append-only run history file in the storage root.
+pub const RUN_HISTORY_FILE_NAME: &str = "runs.jsonl";
+
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum RunOutcome {
+    Success,
+
+    /// The run failed, but only after it had already done some work
+    /// (e.g. captured some snapshots).
+    Partial,
+
+    Failed,
+}
+
+
+/// A single line of the run history (`runs.jsonl`), describing one invocation of the recorder.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RunHistoryEntry {
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub started_at: DateTime<Utc>,
+
+    #[serde_as(as = "TimestampSecondsWithFrac<String>")]
+    pub finished_at: DateTime<Utc>,
+
+    /// Example: `record-once` or `record-perpetual`.
+    ///
+    /// Only recording runs are recorded (see [`RunHistoryEntry::record_in`]), but histories
+    /// written by older versions also contain other commands (e.g. `fsck` or `report`).
+    pub mode: String,
+
+    /// Only set for recording runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture_mode: Option<CaptureMode>,
+
+    /// Hex-encoded SHA-256 hash of the configuration file contents.
+    pub configuration_hash: String,
+
+    pub outcome: RunOutcome,
+
+    /// Error message, if the run did not succeed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Named counters, e.g. `captured_snapshots`.
+    pub counts: BTreeMap<String, u64>,
+
+    /// Identifiers of the snapshot runs (loop iterations) that completed during this run,
+    /// in order. Snapshots and log lines carry the same identifier.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub snapshot_run_ids: Vec<Uuid>,
+
+    /// Version of the recorder that performed the run.
+    pub version: String,
+}
+
+impl RunHistoryEntry {
+    /// Whether the run recorded snapshots (i.e. ran one of the recording modes).
+    pub fn is_recording_run(&self) -> bool {
+        self.capture_mode.is_some()
+    }
+
+    /// Appends this entry to the run history file in the storage root if it is a recording run.
+    /// Other commands (e.g. `explore` or `report`) only read the storage, so they are left out
+    /// to keep the history (and its outcome counts) about recording.
+    ///
+    /// Returns whether the entry was appended.
+    pub fn record_in(&self, storage_root: &StorageRoot) -> Result<bool> {
+        if !self.is_recording_run() {
+            return Ok(false);
+        }
+
+        self.append_to(storage_root)?;
+        Ok(true)
+    }
+
+    /// Appends this entry to the run history file in the storage root.
+    fn append_to(&self, storage_root: &StorageRoot) -> Result<()> {
+        let mut serialized_entry = serde_json::to_vec(self)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to serialize run history entry."))?;
+        serialized_entry.push(b'\n');
+
+        let mut run_history_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(storage_root.run_history_file_path())
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to open run history file."))?;
+
+        // The entire line is written with a single call, so concurrent
+        // invocations don't interleave their entries.
+        run_history_file
+            .write_all(&serialized_entry)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to write run history entry."))
+    }
+}
+
+
+/// Named counters (and snapshot run identifiers) that are shared with the tasks
+/// of a run and saved into its run history entry once it completes.
+#[derive(Clone, Default, Debug)]
+pub struct RunCounters {
+    counts: Arc<Mutex<BTreeMap<String, u64>>>,
+    snapshot_run_ids: Arc<Mutex<Vec<Uuid>>>,
+}
+
+impl RunCounters {
+    pub fn increment(&self, counter_name: &str) {
+        // PANIC SAFETY: The lock is never held across a panicking call.
+        let mut counts = self.counts.lock().unwrap();
+
+        *counts.entry(counter_name.to_string()).or_default() += 1;
+    }
+
+    /// Sets a counter to `value`, e.g. for values that are only known at the end of a run.
+    pub fn set(&self, counter_name: &str, value: u64) {
+        // PANIC SAFETY: The lock is never held across a panicking call.
+        let mut counts = self.counts.lock().unwrap();
+
+        counts.insert(counter_name.to_string(), value);
+    }
+
+    pub fn get(&self, counter_name: &str) -> u64 {
+        // PANIC SAFETY: The lock is never held across a panicking call.
+        let counts = self.counts.lock().unwrap();
+
+        counts.get(counter_name).copied().unwrap_or_default()
+    }
+
+    pub fn to_map(&self) -> BTreeMap<String, u64> {
+        // PANIC SAFETY: The lock is never held across a panicking call.
+        self.counts.lock().unwrap().clone()
+    }
+
+    pub fn record_snapshot_run_id(&self, run_id: Uuid) {
+        // PANIC SAFETY: The lock is never held across a panicking call.
+        self.snapshot_run_ids.lock().unwrap().push(run_id);
+    }
+
+    pub fn snapshot_run_ids(&self) -> Vec<Uuid> {
+        // PANIC SAFETY: The lock is never held across a panicking call.
+        self.snapshot_run_ids.lock().unwrap().clone()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::storage::FileNameTemplates;
+
+    fn run_history_entry(mode: &str, capture_mode: Option<CaptureMode>) -> RunHistoryEntry {
+        RunHistoryEntry {
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            mode: mode.to_string(),
+            capture_mode,
+            configuration_hash: String::from("hash"),
+            outcome: RunOutcome::Success,
+            error: None,
+            counts: BTreeMap::new(),
+            snapshot_run_ids: Vec::new(),
+            version: String::from("1.0.0"),
+        }
+    }
+
+    #[test]
+    fn only_record_recording_runs() {
+        let directory_path = std::env::temp_dir().join(format!(
+            "lpp-recorder-run-history-test-{}",
+            Uuid::new_v4()
+        ));
+        let storage_root = StorageRoot::new(&directory_path, FileNameTemplates::default()).unwrap();
+
+        for read_only_mode in ["explore", "query", "stats", "report"] {
+            assert!(!run_history_entry(read_only_mode, None)
+                .record_in(&storage_root)
+                .unwrap());
+        }
+        assert!(!storage_root.run_history_file_path().exists());
+
+        assert!(
+            run_history_entry("record-once", Some(CaptureMode::Full))
+                .record_in(&storage_root)
+                .unwrap()
+        );
+
+        let run_history = fs::read_to_string(storage_root.run_history_file_path()).unwrap();
+        let recorded_entries: Vec<RunHistoryEntry> = run_history
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(recorded_entries.len(), 1);
+        assert_eq!(recorded_entries[0].mode, "record-once");
+
+        fs::remove_dir_all(&directory_path).unwrap();
+    }
+}
@@ -0,0 +1,33 @@
+//! Service state notifications for systemd (`Type=notify` units), available with the `systemd`
+//! cargo feature. Without it (or when not started by systemd), these functions do nothing.
+//!
+//! As the watchdog is only pinged after each captured snapshot, `WatchdogSec` should be set
+//! comfortably above the capture interval plus the duration of a single capture.
+
+
+/// Notifies systemd that the recorder has been initialized.
+pub fn notify_ready() {
+    #[cfg(feature = "systemd")]
+    notify(sd_notify::NotifyState::Ready);
+}
+
+/// Pings the systemd watchdog.
+pub fn notify_watchdog() {
+    #[cfg(feature = "systemd")]
+    notify(sd_notify::NotifyState::Watchdog);
+}
+
+/// Notifies systemd that the recorder is shutting down.
+pub fn notify_stopping() {
+    #[cfg(feature = "systemd")]
+    notify(sd_notify::NotifyState::Stopping);
+}
+
+
+#[cfg(feature = "systemd")]
+fn notify(state: sd_notify::NotifyState) {
+    // If the recorder isn't running under systemd (`NOTIFY_SOCKET` is unset), this does nothing.
+    if let Err(error) = sd_notify::notify(false, &[state]) {
+        tracing::warn!(error = ?error, "Failed to notify systemd about service state.");
+    }
+}
@@ -0,0 +1,314 @@
+//! Export of traces and health metrics to an OpenTelemetry collector over OTLP/HTTP (the `[telemetry]`
+//! table), available with the `telemetry` cargo feature. Without it (or without the table),
+//! the recording functions do nothing.
+//!
+//! Every recorder reports its `service.instance.id` (the configured `instance_name` or the host name),
+//! so several recorders can report into the same collector and be compared side by side.
+//! The exported metrics are:
+//! - `lpp_recorder.snapshots` (by `status`): saved snapshots,
+//! - `lpp_recorder.snapshot_failures`: snapshot runs that failed,
+//! - `lpp_recorder.snapshot.duration`: how long each saved snapshot took (in seconds),
+//! - `lpp_recorder.snapshot.station_coverage` and `lpp_recorder.snapshot.route_coverage`:
+//!   the shares of the expected stations and routes each saved snapshot captured,
+//! - `lpp_recorder.api.responses` (by `status_code`): responses of the LPP API,
+//! - `lpp_recorder.arrivals.polled_trips`, `lpp_recorder.arrivals.trips_with_arrivals` and
+//!   `lpp_recorder.arrivals.failed_trips`: trips polled by the arrival recorder.
+//!
+//! Spans (e.g. `snapshot-run` and the capture phases) are exported as traces.
+
+use std::time::Duration;
+
+use miette::Result;
+use reqwest::StatusCode;
+use tracing_subscriber::{Layer, Registry};
+
+use crate::{
+    configuration::TelemetryConfiguration,
+    recorder::{acceptance::CaptureCoverage, formats::SnapshotStatus},
+};
+
+/// Tracing layer that exports spans to the collector.
+pub type TelemetryLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+
+/// Flushes and stops the exporters when dropped.
+///
+/// **Drop it before the logging guard**, so problems with the final export are still logged.
+pub struct TelemetryGuard {
+    #[cfg(feature = "telemetry")]
+    meter_provider: Option<opentelemetry_sdk::metrics::MeterProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "telemetry")]
+        if let Some(meter_provider) = self.meter_provider.take() {
+            opentelemetry::global::shutdown_tracer_provider();
+
+            // The periodic reader marks itself as shut down before its last collection,
+            // so the final metrics have to be flushed explicitly beforehand.
+            if let Err(error) = meter_provider.force_flush() {
+                tracing::warn!(error = ?error, "Failed to export final metrics.");
+            }
+
+            if let Err(error) = meter_provider.shutdown() {
+                tracing::debug!(error = ?error, "Failed to shut down metric exporter.");
+            }
+        }
+    }
+}
+
+/// Starts exporting metrics to the collector and returns the tracing layer that exports spans
+/// (to be passed to [`initialize_tracing`](crate::logging::initialize_tracing)).
+/// Must be called from within the tokio runtime.
+pub fn initialize_telemetry(
+    configuration: Option<&TelemetryConfiguration>,
+) -> Result<(Option<TelemetryLayer>, TelemetryGuard)> {
+    #[cfg(feature = "telemetry")]
+    if let Some(configuration) = configuration {
+        return exporter::initialize(configuration);
+    }
+
+    // Resolving the configuration fails if telemetry is configured without the feature.
+    #[cfg(not(feature = "telemetry"))]
+    let _ = configuration;
+
+    Ok((
+        None,
+        TelemetryGuard {
+            #[cfg(feature = "telemetry")]
+            meter_provider: None,
+        },
+    ))
+}
+
+
+/// Records a saved snapshot.
+pub fn record_snapshot(status: SnapshotStatus, coverage: &CaptureCoverage, duration: Duration) {
+    #[cfg(feature = "telemetry")]
+    {
+        use opentelemetry::KeyValue;
+
+        let instruments = exporter::instruments();
+        let status = match status {
+            SnapshotStatus::Complete => "complete",
+            SnapshotStatus::Partial => "partial",
+            SnapshotStatus::Rejected => "rejected",
+        };
+
+        instruments
+            .snapshots
+            .add(1, &[KeyValue::new("status", status)]);
+        instruments
+            .snapshot_duration
+            .record(duration.as_secs_f64(), &[]);
+        instruments
+            .station_coverage
+            .record(coverage.station_fraction(), &[]);
+        instruments
+            .route_coverage
+            .record(coverage.route_fraction(), &[]);
+    }
+
+    #[cfg(not(feature = "telemetry"))]
+    let _ = (status, coverage, duration);
+}
+
+/// Records a snapshot run that failed.
+pub fn record_failed_snapshot() {
+    #[cfg(feature = "telemetry")]
+    exporter::instruments().snapshot_failures.add(1, &[]);
+}
+
+/// Records a response of the LPP API.
+pub fn record_api_response(status: StatusCode) {
+    #[cfg(feature = "telemetry")]
+    exporter::instruments().api_responses.add(
+        1,
+        &[opentelemetry::KeyValue::new(
+            "status_code",
+            i64::from(status.as_u16()),
+        )],
+    );
+
+    #[cfg(not(feature = "telemetry"))]
+    let _ = status;
+}
+
+/// Records a polling round of the arrival recorder.
+pub fn record_arrival_polling_round(
+    polled_trips: usize,
+    trips_with_arrivals: usize,
+    failed_trips: usize,
+) {
+    #[cfg(feature = "telemetry")]
+    {
+        let instruments = exporter::instruments();
+
+        instruments
+            .arrival_polled_trips
+            .add(polled_trips as u64, &[]);
+        instruments
+            .arrival_trips_with_arrivals
+            .add(trips_with_arrivals as u64, &[]);
+        instruments
+            .arrival_failed_trips
+            .add(failed_trips as u64, &[]);
+    }
+
+    #[cfg(not(feature = "telemetry"))]
+    let _ = (polled_trips, trips_with_arrivals, failed_trips);
+}
+
+
+#[cfg(feature = "telemetry")]
+mod exporter {
+    use std::{collections::HashMap, sync::OnceLock};
+
+    use miette::{miette, Context, IntoDiagnostic, Result};
+    use opentelemetry::{
+        global,
+        metrics::{Counter, Histogram, Unit},
+        KeyValue,
+    };
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::{runtime, trace, Resource};
+    use tracing_subscriber::Layer;
+
+    use super::{TelemetryGuard, TelemetryLayer};
+    use crate::configuration::TelemetryConfiguration;
+
+    const METER_NAME: &str = "lpp-timetable-recorder";
+
+
+    pub(super) fn initialize(
+        configuration: &TelemetryConfiguration,
+    ) -> Result<(Option<TelemetryLayer>, TelemetryGuard)> {
+        let instance_name = configuration.instance_name.clone().unwrap_or_else(|| {
+            hostname::get()
+                .ok()
+                .and_then(|host_name| host_name.into_string().ok())
+                .unwrap_or_else(|| String::from("unknown"))
+        });
+
+        let resource = Resource::new([
+            KeyValue::new("service.name", configuration.service_name.clone()),
+            KeyValue::new("service.instance.id", instance_name),
+            KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+        ]);
+
+        // The exporters append the path of each signal (e.g. `/v1/traces`) to the endpoint.
+        let endpoint = configuration.endpoint.as_str().trim_end_matches('/');
+        let headers: HashMap<String, String> = configuration
+            .headers
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .http()
+                    .with_endpoint(endpoint)
+                    .with_headers(headers.clone()),
+            )
+            .with_trace_config(trace::config().with_resource(resource.clone()))
+            .install_batch(runtime::Tokio)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to initialize trace exporter."))?;
+
+        let meter_provider = opentelemetry_otlp::new_pipeline()
+            .metrics(runtime::Tokio)
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .http()
+                    .with_endpoint(endpoint)
+                    .with_headers(headers),
+            )
+            .with_resource(resource)
+            .with_period(configuration.metric_export_interval)
+            .build()
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to initialize metric exporter."))?;
+
+        let layer = tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(configuration.trace_level_filter())
+            .boxed();
+
+        Ok((
+            Some(layer),
+            TelemetryGuard {
+                meter_provider: Some(meter_provider),
+            },
+        ))
+    }
+
+
+    pub(super) struct Instruments {
+        pub snapshots: Counter<u64>,
+        pub snapshot_failures: Counter<u64>,
+        pub snapshot_duration: Histogram<f64>,
+        pub station_coverage: Histogram<f64>,
+        pub route_coverage: Histogram<f64>,
+        pub api_responses: Counter<u64>,
+        pub arrival_polled_trips: Counter<u64>,
+        pub arrival_trips_with_arrivals: Counter<u64>,
+        pub arrival_failed_trips: Counter<u64>,
+    }
+
+    /// The metric instruments, created on first use (after the meter provider has been installed,
+    /// or from the no-op global meter provider if telemetry isn't configured).
+    pub(super) fn instruments() -> &'static Instruments {
+        static INSTRUMENTS: OnceLock<Instruments> = OnceLock::new();
+
+        INSTRUMENTS.get_or_init(|| {
+            let meter = global::meter(METER_NAME);
+
+            Instruments {
+                snapshots: meter
+                    .u64_counter("lpp_recorder.snapshots")
+                    .with_description("Saved snapshots, by status.")
+                    .init(),
+                snapshot_failures: meter
+                    .u64_counter("lpp_recorder.snapshot_failures")
+                    .with_description("Snapshot runs that failed.")
+                    .init(),
+                snapshot_duration: meter
+                    .f64_histogram("lpp_recorder.snapshot.duration")
+                    .with_description("How long each saved snapshot took.")
+                    .with_unit(Unit::new("s"))
+                    .init(),
+                station_coverage: meter
+                    .f64_histogram("lpp_recorder.snapshot.station_coverage")
+                    .with_description(
+                        "Share of the expected stations each saved snapshot captured.",
+                    )
+                    .init(),
+                route_coverage: meter
+                    .f64_histogram("lpp_recorder.snapshot.route_coverage")
+                    .with_description("Share of the expected routes each saved snapshot captured.")
+                    .init(),
+                api_responses: meter
+                    .u64_counter("lpp_recorder.api.responses")
+                    .with_description("Responses of the LPP API, by status code.")
+                    .init(),
+                arrival_polled_trips: meter
+                    .u64_counter("lpp_recorder.arrivals.polled_trips")
+                    .with_description("Trips polled by the arrival recorder.")
+                    .init(),
+                arrival_trips_with_arrivals: meter
+                    .u64_counter("lpp_recorder.arrivals.trips_with_arrivals")
+                    .with_description(
+                        "Polled trips with arriving buses, whose arrivals were saved.",
+                    )
+                    .init(),
+                arrival_failed_trips: meter
+                    .u64_counter("lpp_recorder.arrivals.failed_trips")
+                    .with_description("Polled trips whose arrivals could not be fetched or saved.")
+                    .init(),
+            }
+        })
+    }
+}
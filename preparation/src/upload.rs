@@ -0,0 +1,294 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+use reqwest::{Client, StatusCode, Url};
+use tracing::{debug, info, warn};
+
+use crate::{
+    cancellation_token::CancellationToken,
+    configuration::{UploadConfiguration, UploadMethod},
+};
+
+/// Name of the default outbox directory in the recording storage root.
+pub const DEFAULT_OUTBOX_DIRECTORY_NAME: &str = "upload-outbox";
+
+
+/// Reason a single upload attempt has failed.
+enum UploadAttemptError {
+    /// The request might succeed if it is retried (e.g. a connection error or a 5xx response).
+    Transient(miette::Report),
+
+    /// The endpoint rejected the file (a 4xx response), retrying won't help.
+    Permanent(miette::Report),
+}
+
+/// Uploads saved snapshot files to the HTTP endpoint configured in the `[upload]` table.
+///
+/// Files that can't be uploaded are copied into the outbox directory
+/// and uploaded again after the next capture (see [`Self::upload_files`]).
+#[derive(Clone, Debug)]
+pub struct SnapshotUploader {
+    http_client: Client,
+    configuration: UploadConfiguration,
+    outbox_directory_path: PathBuf,
+}
+
+impl SnapshotUploader {
+    pub fn new(
+        configuration: UploadConfiguration,
+        storage_root_path: &Path,
+        user_agent: &str,
+    ) -> Result<Self> {
+        let outbox_directory_path = configuration
+            .outbox_directory
+            .clone()
+            .unwrap_or_else(|| storage_root_path.join(DEFAULT_OUTBOX_DIRECTORY_NAME));
+
+        fs::create_dir_all(&outbox_directory_path)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to create upload outbox directory."))?;
+
+        let http_client = Client::builder()
+            .user_agent(user_agent)
+            .build()
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to build upload HTTP client."))?;
+
+        Ok(Self {
+            http_client,
+            configuration,
+            outbox_directory_path,
+        })
+    }
+
+    /// Uploads the given files, then retries everything that is waiting in the outbox.
+    ///
+    /// Upload failures never fail the recording: they are logged
+    /// and the affected files are kept in the outbox instead.
+    pub async fn upload_files(
+        &self,
+        file_paths: &[PathBuf],
+        cancellation_token: &CancellationToken,
+    ) {
+        for file_path in file_paths {
+            if let Err(error) = self.upload_file(file_path, cancellation_token).await {
+                warn!(
+                    error = ?error,
+                    file_path = %file_path.display(),
+                    "Failed to upload snapshot file, moving it to the outbox."
+                );
+
+                if let Err(error) = self.copy_to_outbox(file_path) {
+                    warn!(
+                        error = ?error,
+                        file_path = %file_path.display(),
+                        "Failed to copy snapshot file into the upload outbox."
+                    );
+                }
+            }
+        }
+
+        if let Err(error) = self.flush_outbox(file_paths, cancellation_token).await {
+            warn!(error = ?error, "Failed to upload files from the upload outbox.");
+        }
+    }
+
+    /// Uploads all files in the outbox (except the ones that have just failed to upload),
+    /// removing them from the outbox once they are uploaded.
+    async fn flush_outbox(
+        &self,
+        just_attempted_file_paths: &[PathBuf],
+        cancellation_token: &CancellationToken,
+    ) -> Result<()> {
+        let mut outbox_file_paths = Vec::new();
+        for entry in fs::read_dir(&self.outbox_directory_path).into_diagnostic()? {
+            let entry_path = entry.into_diagnostic()?.path();
+
+            let was_just_attempted = just_attempted_file_paths
+                .iter()
+                .any(|file_path| file_path.file_name() == entry_path.file_name());
+
+            if entry_path.is_file() && !was_just_attempted {
+                outbox_file_paths.push(entry_path);
+            }
+        }
+
+        if outbox_file_paths.is_empty() {
+            return Ok(());
+        }
+
+        outbox_file_paths.sort();
+        info!(
+            files = outbox_file_paths.len(),
+            "Uploading files waiting in the upload outbox."
+        );
+
+        for outbox_file_path in outbox_file_paths {
+            match self
+                .upload_file(&outbox_file_path, cancellation_token)
+                .await
+            {
+                Ok(()) => fs::remove_file(&outbox_file_path)
+                    .into_diagnostic()
+                    .wrap_err_with(|| miette!("Failed to remove uploaded file from the outbox."))?,
+                Err(error) => warn!(
+                    error = ?error,
+                    file_path = %outbox_file_path.display(),
+                    "Failed to upload file from the outbox, keeping it for later."
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn copy_to_outbox(&self, file_path: &Path) -> Result<()> {
+        let file_name = file_path
+            .file_name()
+            .ok_or_else(|| miette!("Snapshot file path has no file name."))?;
+
+        fs::copy(
+            file_path,
+            self.outbox_directory_path.join(file_name),
+        )
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to copy file into the outbox."))?;
+
+        Ok(())
+    }
+
+    /// Uploads a single file, retrying transient failures up to the configured number of
+    /// attempts (waiting twice as long after each failed attempt). If `cancellation_token`
+    /// is cancelled while waiting to retry, the upload fails with the last attempt's error.
+    async fn upload_file(
+        &self,
+        file_path: &Path,
+        cancellation_token: &CancellationToken,
+    ) -> Result<()> {
+        let file_name = file_path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .ok_or_else(|| miette!("Snapshot file path has no valid file name."))?;
+
+        let upload_url = self
+            .configuration
+            .url
+            .join(file_name)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to build upload URL."))?;
+
+        let file_contents = tokio::fs::read(file_path)
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to read file to upload."))?;
+
+        let mut retry_interval = self.configuration.retry_interval;
+        let mut attempt: u32 = 1;
+
+        loop {
+            let error = match self.attempt_upload(&upload_url, &file_contents).await {
+                Ok(()) => {
+                    debug!(url = %upload_url, "Snapshot file has been uploaded.");
+                    return Ok(());
+                }
+                Err(UploadAttemptError::Permanent(error)) => return Err(error),
+                Err(UploadAttemptError::Transient(error)) => error,
+            };
+
+            if attempt >= self.configuration.max_attempts {
+                return Err(error.wrap_err(format!(
+                    "Giving up on upload after {attempt} attempts."
+                )));
+            }
+
+            debug!(
+                error = ?error,
+                attempt = attempt,
+                retry_in_seconds = retry_interval.as_secs_f64(),
+                "Upload attempt failed, retrying."
+            );
+
+            if cancellation_token
+                .run_until_cancelled(tokio::time::sleep(retry_interval))
+                .await
+                .is_none()
+            {
+                return Err(error.wrap_err("Upload was cancelled before it could be retried."));
+            }
+
+            retry_interval *= 2;
+            attempt += 1;
+        }
+    }
+
+    async fn attempt_upload(
+        &self,
+        upload_url: &Url,
+        file_contents: &[u8],
+    ) -> Result<(), UploadAttemptError> {
+        let request = match self.configuration.method {
+            UploadMethod::Put => self.http_client.put(upload_url.clone()),
+            UploadMethod::Post => self.http_client.post(upload_url.clone()),
+        };
+        let request = match &self.configuration.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+
+        match request.body(file_contents.to_vec()).send().await {
+            Ok(response) => classify_response_status(response.status()),
+            Err(error) => Err(UploadAttemptError::Transient(
+                miette!("{error}").wrap_err("Failed to send upload request."),
+            )),
+        }
+    }
+}
+
+fn classify_response_status(status: StatusCode) -> Result<(), UploadAttemptError> {
+    if status.is_success() {
+        Ok(())
+    } else if status.is_client_error()
+        && status != StatusCode::REQUEST_TIMEOUT
+        && status != StatusCode::TOO_MANY_REQUESTS
+    {
+        Err(UploadAttemptError::Permanent(miette!(
+            "Upload endpoint rejected the file with status {status}."
+        )))
+    } else {
+        Err(UploadAttemptError::Transient(miette!(
+            "Upload endpoint responded with status {status}."
+        )))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_retry_transient_response_statuses() {
+        let is_transient = |status| {
+            matches!(
+                classify_response_status(status),
+                Err(UploadAttemptError::Transient(_))
+            )
+        };
+        let is_permanent = |status| {
+            matches!(
+                classify_response_status(status),
+                Err(UploadAttemptError::Permanent(_))
+            )
+        };
+
+        assert!(classify_response_status(StatusCode::CREATED).is_ok());
+        assert!(is_permanent(StatusCode::UNAUTHORIZED));
+        assert!(is_permanent(StatusCode::PAYLOAD_TOO_LARGE));
+        assert!(is_transient(StatusCode::REQUEST_TIMEOUT));
+        assert!(is_transient(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_transient(StatusCode::BAD_GATEWAY));
+    }
+}
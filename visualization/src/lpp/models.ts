@@ -367,15 +367,22 @@ export class TripWithStationsAndTimetables {
     public capturedAt: Date;
     public routeDetails: RouteDetails;
     public stationsOnRouteWithTimetables: TripStationWithTimetable[];
+    /**
+     * How complete the data for this trip is
+     * (`null` for snapshots recorded before this was introduced).
+     */
+    public completeness: TripDataCompleteness | null;
 
     constructor(
       capturedAt: Date,
       routeDetails: RouteDetails,
       stationsOnRouteWithTimetables: TripStationWithTimetable[],
+      completeness: TripDataCompleteness | null,
     ) {
         this.capturedAt = capturedAt;
         this.routeDetails = routeDetails;
         this.stationsOnRouteWithTimetables = stationsOnRouteWithTimetables;
+        this.completeness = completeness;
     }
 
     public static fromRawData(rawData: Record<string, any>): TripWithStationsAndTimetables {
@@ -389,7 +396,60 @@ export class TripWithStationsAndTimetables {
             stationsOnRouteWithTimetables.push(TripStationWithTimetable.fromRawData(station));
         }
 
-        return new TripWithStationsAndTimetables(capturedAt, routeDetails, stationsOnRouteWithTimetables);
+        const completenessRaw = getOptionalField(rawData, "completeness", null);
+        const completeness = completenessRaw === null ? null : TripDataCompleteness.fromRawData(completenessRaw);
+
+        return new TripWithStationsAndTimetables(capturedAt, routeDetails, stationsOnRouteWithTimetables, completeness);
+    }
+}
+
+export class TripDataCompleteness {
+    public totalStations: number;
+    public stationsWithTimetables: number;
+    public stationsWithInterpolatedTimetables: number;
+    public stationTimetableFraction: number;
+    public hasRouteShape: boolean;
+    public timetableEntryCount: number;
+    public expectedTimetableEntryCount: number | null;
+
+    constructor(
+      totalStations: number,
+      stationsWithTimetables: number,
+      stationsWithInterpolatedTimetables: number,
+      stationTimetableFraction: number,
+      hasRouteShape: boolean,
+      timetableEntryCount: number,
+      expectedTimetableEntryCount: number | null,
+    ) {
+        this.totalStations = totalStations;
+        this.stationsWithTimetables = stationsWithTimetables;
+        this.stationsWithInterpolatedTimetables = stationsWithInterpolatedTimetables;
+        this.stationTimetableFraction = stationTimetableFraction;
+        this.hasRouteShape = hasRouteShape;
+        this.timetableEntryCount = timetableEntryCount;
+        this.expectedTimetableEntryCount = expectedTimetableEntryCount;
+    }
+
+    public static fromRawData(rawData: Record<string, any>): TripDataCompleteness {
+        const totalStations = Number(getRequiredField(rawData, "total_stations"));
+        const stationsWithTimetables = Number(getRequiredField(rawData, "stations_with_timetables"));
+        const stationsWithInterpolatedTimetables = Number(getRequiredField(rawData, "stations_with_interpolated_timetables"));
+        const stationTimetableFraction = Number(getRequiredField(rawData, "station_timetable_fraction"));
+        const hasRouteShape = Boolean(getRequiredField(rawData, "has_route_shape"));
+        const timetableEntryCount = Number(getRequiredField(rawData, "timetable_entry_count"));
+
+        const expectedTimetableEntryCountRaw = getOptionalField(rawData, "expected_timetable_entry_count", null);
+        const expectedTimetableEntryCount = expectedTimetableEntryCountRaw === null ? null : Number(expectedTimetableEntryCountRaw);
+
+        return new TripDataCompleteness(
+          totalStations,
+          stationsWithTimetables,
+          stationsWithInterpolatedTimetables,
+          stationTimetableFraction,
+          hasRouteShape,
+          timetableEntryCount,
+          expectedTimetableEntryCount,
+        );
     }
 }
 
@@ -460,20 +520,28 @@ export class RouteGeoJsonShape {
 export class TripStationWithTimetable {
     public station: StationOnRoute;
     public timetable: TripTimetable;
+    /**
+     * Whether the timetable was estimated from neighbouring stations
+     * instead of being provided by the LPP API.
+     */
+    public timetableIsInterpolated: boolean;
 
     constructor(
       station: StationOnRoute,
-      timetable: TripTimetable
+      timetable: TripTimetable,
+      timetableIsInterpolated: boolean,
     ) {
         this.station = station;
         this.timetable = timetable;
+        this.timetableIsInterpolated = timetableIsInterpolated;
     }
 
     public static fromRawData(rawData: Record<string, any>): TripStationWithTimetable {
         const station = StationOnRoute.fromRawData(getRequiredField(rawData, "station"));
         const timetable = TripTimetable.fromRawData(getRequiredField(rawData, "timetable"));
+        const timetableIsInterpolated = getOptionalField(rawData, "timetable_is_interpolated", false);
 
-        return new TripStationWithTimetable(station, timetable);
+        return new TripStationWithTimetable(station, timetable, timetableIsInterpolated);
     }
 }
 